@@ -0,0 +1,366 @@
+use constant::Value;
+use loc::Loc;
+use symtab::SymbolId;
+use types::TypeId;
+use token::TokenType as TT;
+
+/// A complete GoLite source file: a package clause
+/// followed by top-level declarations.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub package: Ident,
+    pub decls: Vec<TopLevelDecl>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TopLevelDecl {
+    Var(Vec<VarSpec>),
+    Type(Vec<TypeSpec>),
+    Func(FuncDecl),
+    /// Placeholder for a declaration that could not be parsed.
+    Error(Loc),
+}
+
+/// An identifier, either declared (names, parameters, struct
+/// fields, selectors) or used (in expressions and types).
+/// The blank identifier is represented with the name `"_"`.
+#[derive(Debug, Clone)]
+pub struct Ident {
+    pub name: String,
+    pub loc: Loc,
+    /// The symbol that the identifier declares or refers to;
+    /// filled in by name resolution.  Struct fields, selectors
+    /// and the blank identifier have no symbol.
+    pub sym: Option<SymbolId>,
+}
+
+impl Ident {
+    pub fn new(name: &str, loc: Loc) -> Ident {
+        Ident { name: name.to_string(), loc: loc, sym: None }
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.name == "_"
+    }
+}
+
+/// `var x, y T = e1, e2`; either the type or the
+/// values (but not both) may be omitted.
+#[derive(Debug, Clone)]
+pub struct VarSpec {
+    pub names: Vec<Ident>,
+    pub ty: Option<TypeExpr>,
+    pub values: Vec<Expr>,
+    pub loc: Loc,
+}
+
+/// `type T U`
+#[derive(Debug, Clone)]
+pub struct TypeSpec {
+    pub name: Ident,
+    pub ty: TypeExpr,
+    pub loc: Loc,
+}
+
+#[derive(Debug, Clone)]
+pub struct FuncDecl {
+    pub name: Ident,
+    pub params: Vec<Param>,
+    pub result: Option<TypeExpr>,
+    pub body: Block,
+    pub loc: Loc,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Ident,
+    pub ty: TypeExpr,
+}
+
+
+// TYPES
+
+#[derive(Debug, Clone)]
+pub struct TypeExpr {
+    pub kind: TypeExprKind,
+    pub loc: Loc,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeExprKind {
+    Name(Ident),
+    Array(Box<Expr>, Box<TypeExpr>),
+    Slice(Box<TypeExpr>),
+    Struct(Vec<Field>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: Ident,
+    pub ty: TypeExpr,
+}
+
+
+// STATEMENTS
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub loc: Loc,
+    /// The location of the closing brace.
+    pub end: Loc,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub loc: Loc,
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+    Block(Block),
+    Expr(Expr),
+    Var(Vec<VarSpec>),
+    Type(Vec<TypeSpec>),
+    Assign(Vec<Expr>, Vec<Expr>),
+    OpAssign(BinOp, Expr, Expr),
+    Incr(Expr),
+    Decr(Expr),
+    ShortVarDecl(Vec<Ident>, Vec<Expr>),
+    Print(Vec<Expr>),
+    Println(Vec<Expr>),
+    Return(Option<Expr>),
+    If(IfStmt),
+    Switch(SwitchStmt),
+    For(ForStmt),
+    Break,
+    Continue,
+    /// Placeholder for a statement that could not be parsed.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct IfStmt {
+    pub init: Option<Box<Stmt>>,
+    pub cond: Expr,
+    pub then: Block,
+    /// Either another `if` statement or a block.
+    pub els: Option<Box<Stmt>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwitchStmt {
+    pub init: Option<Box<Stmt>>,
+    pub tag: Option<Expr>,
+    pub clauses: Vec<CaseClause>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseClause {
+    /// `None` for the `default` clause.
+    pub exprs: Option<Vec<Expr>>,
+    pub body: Vec<Stmt>,
+    pub loc: Loc,
+}
+
+/// All three forms of `for` share this node: the infinite
+/// loop has no init, condition or post statement, and the
+/// "while" loop only has a condition.
+#[derive(Debug, Clone)]
+pub struct ForStmt {
+    pub init: Option<Box<Stmt>>,
+    pub cond: Option<Expr>,
+    pub post: Option<Box<Stmt>>,
+    pub body: Block,
+}
+
+
+// EXPRESSIONS
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub loc: Loc,
+    /// The type of the expression, filled in by the type checker.
+    /// Untyped constants get the type imposed by their context.
+    pub ty: Option<TypeId>,
+    /// The value of a constant expression, filled in by the
+    /// type checker and represented exactly in `ty`.
+    pub value: Option<Value>,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, loc: Loc) -> Expr {
+        Expr { kind: kind, loc: loc, ty: None, value: None }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntBase { Dec, Oct, Hex }
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Id(Ident),
+    Blank,
+    /// The digits as they appear in the source; hexadecimal
+    /// literals do not include the `0x` prefix.
+    Int(String, IntBase),
+    Float(String),
+    Rune(char),
+    String(String),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// Function calls and type conversions; they are
+    /// syntactically indistinguishable.
+    Call(Box<Expr>, Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Selector(Box<Expr>, Ident),
+    Append(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Plus, Neg, Not, BitNot,
+}
+
+impl UnOp {
+    pub fn from_token(ty: TT) -> Option<UnOp> {
+        match ty {
+            TT::Plus => Some(UnOp::Plus),
+            TT::Minus => Some(UnOp::Neg),
+            TT::Not => Some(UnOp::Not),
+            TT::Bitnot => Some(UnOp::BitNot),
+            _ => None,
+        }
+    }
+
+    pub fn token(self) -> TT {
+        match self {
+            UnOp::Plus => TT::Plus,
+            UnOp::Neg => TT::Minus,
+            UnOp::Not => TT::Not,
+            UnOp::BitNot => TT::Bitnot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Or, And,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    Add, Sub, BitOr, BitXor,
+    Mul, Div, Rem, Shl, Shr, BitAnd, BitClear,
+}
+
+impl BinOp {
+    pub fn from_token(ty: TT) -> Option<BinOp> {
+        use self::BinOp::*;
+        match ty {
+            TT::Or => Some(Or),
+            TT::And => Some(And),
+            TT::Eq => Some(Eq),
+            TT::Ne => Some(Ne),
+            TT::Lt => Some(Lt),
+            TT::Le => Some(Le),
+            TT::Gt => Some(Gt),
+            TT::Ge => Some(Ge),
+            TT::Plus => Some(Add),
+            TT::Minus => Some(Sub),
+            TT::Bitor => Some(BitOr),
+            TT::Bitnot => Some(BitXor),
+            TT::Star => Some(Mul),
+            TT::Slash => Some(Div),
+            TT::Percent => Some(Rem),
+            TT::LeftShift => Some(Shl),
+            TT::RightShift => Some(Shr),
+            TT::Bitand => Some(BitAnd),
+            TT::BitClear => Some(BitClear),
+            _ => None,
+        }
+    }
+
+    /// Returns the operator of an op-assignment token
+    /// (e.g., `+=` yields `Add`).
+    pub fn from_assign_token(ty: TT) -> Option<BinOp> {
+        use self::BinOp::*;
+        match ty {
+            TT::PlusEq => Some(Add),
+            TT::MinusEq => Some(Sub),
+            TT::StarEq => Some(Mul),
+            TT::SlashEq => Some(Div),
+            TT::PercentEq => Some(Rem),
+            TT::BitandEq => Some(BitAnd),
+            TT::BitorEq => Some(BitOr),
+            TT::LeftShiftEq => Some(Shl),
+            TT::RightShiftEq => Some(Shr),
+            _ => None,
+        }
+    }
+
+    pub fn token(self) -> TT {
+        use self::BinOp::*;
+        match self {
+            Or => TT::Or,
+            And => TT::And,
+            Eq => TT::Eq,
+            Ne => TT::Ne,
+            Lt => TT::Lt,
+            Le => TT::Le,
+            Gt => TT::Gt,
+            Ge => TT::Ge,
+            Add => TT::Plus,
+            Sub => TT::Minus,
+            BitOr => TT::Bitor,
+            BitXor => TT::Bitnot,
+            Mul => TT::Star,
+            Div => TT::Slash,
+            Rem => TT::Percent,
+            Shl => TT::LeftShift,
+            Shr => TT::RightShift,
+            BitAnd => TT::Bitand,
+            BitClear => TT::BitClear,
+        }
+    }
+
+    /// Returns the op-assignment token for this operator,
+    /// if GoLite has one.
+    pub fn assign_token(self) -> Option<TT> {
+        use self::BinOp::*;
+        match self {
+            Add => Some(TT::PlusEq),
+            Sub => Some(TT::MinusEq),
+            Mul => Some(TT::StarEq),
+            Div => Some(TT::SlashEq),
+            Rem => Some(TT::PercentEq),
+            BitAnd => Some(TT::BitandEq),
+            BitOr => Some(TT::BitorEq),
+            Shl => Some(TT::LeftShiftEq),
+            Shr => Some(TT::RightShiftEq),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the operator compares its operands
+    /// and yields a boolean.
+    pub fn is_comparison(self) -> bool {
+        use self::BinOp::*;
+        match self {
+            Eq | Ne | Lt | Le | Gt | Ge => true,
+            _ => false,
+        }
+    }
+
+    /// Binary operator precedence.
+    /// Ref.: https://golang.org/ref/spec#Operator_precedence
+    pub fn precedence(self) -> u8 {
+        use self::BinOp::*;
+        match self {
+            Or => 1,
+            And => 2,
+            Eq | Ne | Lt | Le | Gt | Ge => 3,
+            Add | Sub | BitOr | BitXor => 4,
+            Mul | Div | Rem | Shl | Shr | BitAnd | BitClear => 5,
+        }
+    }
+}
@@ -0,0 +1,556 @@
+//! The file format of compiled programs.
+//!
+//! A file is a header, the encoded module and a checksum:
+//!
+//! ```text
+//! magic     "GORE"
+//! version   u32
+//! length    u32, the number of bytes of the module
+//! module    filename, globals, entry, constant pool,
+//!           layouts, function table
+//! checksum  u32, the CRC-32 of the module
+//! ```
+//!
+//! Integers are little-endian; strings and lists start with
+//! their length as a `u32`.  Every function has its name, its
+//! number of parameters and locals, whether it returns a value,
+//! its code and its line-number table, which lists the
+//! instructions where the source position changes.
+//!
+//! Reading a file checks the module as well as the encoding, so
+//! that the machine can execute it without running past the end
+//! of a function, out of its operand stack or out of any table
+//! of the module.
+
+use std::fmt;
+use std::mem;
+use std::result;
+
+use bytecode::{Const, Function, Layout, Module, Op, Pos};
+
+pub const MAGIC: &[u8; 4] = b"GORE";
+
+/// The version of the format, incremented whenever the encoding
+/// or the instruction set changes.
+pub const VERSION: u32 = 1;
+
+/// How deeply array and struct layouts may be nested.
+const MAX_LAYOUT_DEPTH: usize = 256;
+
+/// Why a file could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub msg: String,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid bytecode file: {}", self.msg)
+    }
+}
+
+type Result<T> = result::Result<T, FormatError>;
+
+fn error<T>(msg: String) -> Result<T> {
+    return Err(FormatError { msg: msg });
+}
+
+/// Encodes a module.
+pub fn write(module: &Module) -> Vec<u8> {
+    let mut body = Writer { bytes: Vec::new() };
+    body.module(module);
+    let mut out = Writer { bytes: Vec::new() };
+    out.bytes.extend_from_slice(MAGIC);
+    out.u32(VERSION);
+    out.u32(body.bytes.len() as u32);
+    out.bytes.extend_from_slice(&body.bytes);
+    out.u32(crc32(&body.bytes));
+    return out.bytes;
+}
+
+/// Decodes and checks a module.
+pub fn read(bytes: &[u8]) -> Result<Module> {
+    if bytes.len() < MAGIC.len() || &bytes[.. MAGIC.len()] != MAGIC {
+        return error("not a GoLite bytecode file".to_string());
+    }
+    if bytes.len() < MAGIC.len() + 8 {
+        return error(format!("truncated file ({} bytes)", bytes.len()));
+    }
+    let mut header = Reader { bytes: bytes, pos: MAGIC.len() };
+    let version = header.u32()?;
+    if version != VERSION {
+        return error(format!("unsupported format version {} (expected {})", version, VERSION));
+    }
+    let length = header.u32()? as usize;
+    let start = header.pos;
+    let expected = start as u64 + length as u64 + 4;
+    if (bytes.len() as u64) < expected {
+        return error(format!("truncated file ({} bytes, expected {})", bytes.len(), expected));
+    }
+    if bytes.len() as u64 > expected {
+        return error(format!("{} bytes of trailing data", bytes.len() as u64 - expected));
+    }
+    let body = &bytes[start .. start + length];
+    let mut trailer = Reader { bytes: bytes, pos: start + length };
+    if trailer.u32()? != crc32(body) {
+        return error("checksum mismatch, the file is corrupted".to_string());
+    }
+    let mut reader = Reader { bytes: body, pos: 0 };
+    let module = reader.module()?;
+    if reader.pos != body.len() {
+        return error(format!("{} bytes of unused data", body.len() - reader.pos));
+    }
+    verify(&module)?;
+    return Ok(module);
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.bytes.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.bytes.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn len(&mut self, n: usize) {
+        self.u32(n as u32);
+    }
+
+    fn str(&mut self, s: &[u8]) {
+        self.len(s.len());
+        self.bytes.extend_from_slice(s);
+    }
+
+    fn module(&mut self, module: &Module) {
+        self.str(module.filename.as_bytes());
+        self.u32(module.globals);
+        self.u32(module.entry);
+        self.len(module.constants.len());
+        for c in &module.constants {
+            match *c {
+                Const::Int(n) => {
+                    self.u8(0);
+                    self.u64(n as u64);
+                }
+                Const::Float(f) => {
+                    self.u8(1);
+                    self.u64(f.to_bits());
+                }
+                Const::Bool(b) => {
+                    self.u8(2);
+                    self.u8(b as u8);
+                }
+                Const::Str(ref s) => {
+                    self.u8(3);
+                    self.str(s);
+                }
+            }
+        }
+        self.len(module.layouts.len());
+        for layout in &module.layouts {
+            self.layout(layout);
+        }
+        self.len(module.functions.len());
+        for f in &module.functions {
+            self.function(f);
+        }
+    }
+
+    fn layout(&mut self, layout: &Layout) {
+        match *layout {
+            Layout::Int => self.u8(0),
+            Layout::Float => self.u8(1),
+            Layout::Bool => self.u8(2),
+            Layout::Str => self.u8(3),
+            Layout::Slice => self.u8(4),
+            Layout::Array(len, ref elem) => {
+                self.u8(5);
+                self.u64(len);
+                self.layout(elem);
+            }
+            Layout::Struct(ref fields) => {
+                self.u8(6);
+                self.len(fields.len());
+                for &(compared, ref field) in fields {
+                    self.u8(compared as u8);
+                    self.layout(field);
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, f: &Function) {
+        self.str(f.name.as_bytes());
+        self.u32(f.params);
+        self.u32(f.locals);
+        self.u8(f.result as u8);
+        self.len(f.code.len());
+        for op in &f.code {
+            self.u8(opcode(op));
+            if let Some(n) = op.operand() {
+                self.u32(n);
+            }
+        }
+        // The line-number table: the first instruction of every
+        // run of instructions with the same position.
+        let runs: Vec<usize> = (0 .. f.positions.len())
+            .filter(|&pc| pc == 0 || f.positions[pc] != f.positions[pc - 1])
+            .collect();
+        self.len(runs.len());
+        for pc in runs {
+            self.u32(pc as u32);
+            self.u32(f.positions[pc].line);
+            self.u32(f.positions[pc].col);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() - self.pos < n {
+            return error("unexpected end of data".to_string());
+        }
+        let bytes = &self.bytes[self.pos .. self.pos + n];
+        self.pos += n;
+        return Ok(bytes);
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        return Ok(u32::from_le_bytes(buf));
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        return Ok(u64::from_le_bytes(buf));
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => return Ok(false),
+            1 => return Ok(true),
+            b => return error(format!("invalid boolean {}", b)),
+        }
+    }
+
+    /// Reads the length of a list, which has at least one byte
+    /// per element.
+    fn len(&mut self) -> Result<usize> {
+        let n = self.u32()? as usize;
+        if n > self.bytes.len() - self.pos {
+            return error("unexpected end of data".to_string());
+        }
+        return Ok(n);
+    }
+
+    fn str(&mut self) -> Result<Vec<u8>> {
+        let n = self.len()?;
+        return Ok(self.take(n)?.to_vec());
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match String::from_utf8(self.str()?) {
+            Ok(s) => return Ok(s),
+            Err(_) => return error("invalid UTF-8 in a name".to_string()),
+        }
+    }
+
+    fn module(&mut self) -> Result<Module> {
+        let filename = self.string()?;
+        let globals = self.u32()?;
+        let entry = self.u32()?;
+        let mut constants = Vec::new();
+        for _ in 0 .. self.len()? {
+            let c = match self.u8()? {
+                0 => Const::Int(self.u64()? as i64),
+                1 => Const::Float(f64::from_bits(self.u64()?)),
+                2 => Const::Bool(self.bool()?),
+                3 => Const::Str(self.str()?),
+                tag => return error(format!("invalid constant tag {}", tag)),
+            };
+            constants.push(c);
+        }
+        let mut layouts = Vec::new();
+        for _ in 0 .. self.len()? {
+            layouts.push(self.layout(0)?);
+        }
+        let mut functions = Vec::new();
+        for _ in 0 .. self.len()? {
+            functions.push(self.function()?);
+        }
+        return Ok(Module {
+            filename: filename,
+            constants: constants,
+            layouts: layouts,
+            globals: globals,
+            functions: functions,
+            entry: entry,
+        });
+    }
+
+    fn layout(&mut self, depth: usize) -> Result<Layout> {
+        if depth > MAX_LAYOUT_DEPTH {
+            return error("layout nested too deeply".to_string());
+        }
+        match self.u8()? {
+            0 => return Ok(Layout::Int),
+            1 => return Ok(Layout::Float),
+            2 => return Ok(Layout::Bool),
+            3 => return Ok(Layout::Str),
+            4 => return Ok(Layout::Slice),
+            5 => {
+                let len = self.u64()?;
+                return Ok(Layout::Array(len, Box::new(self.layout(depth + 1)?)));
+            }
+            6 => {
+                let mut fields = Vec::new();
+                for _ in 0 .. self.len()? {
+                    let compared = self.bool()?;
+                    fields.push((compared, self.layout(depth + 1)?));
+                }
+                return Ok(Layout::Struct(fields));
+            }
+            tag => return error(format!("invalid layout tag {}", tag)),
+        }
+    }
+
+    fn function(&mut self) -> Result<Function> {
+        let name = self.string()?;
+        let params = self.u32()?;
+        let locals = self.u32()?;
+        let result = self.bool()?;
+        let mut code = Vec::new();
+        for _ in 0 .. self.len()? {
+            let code_byte = self.u8()?;
+            let op = match decode(code_byte, 0) {
+                Some(op) if op.operand().is_some() => decode(code_byte, self.u32()?),
+                op => op,
+            };
+            match op {
+                Some(op) => code.push(op),
+                None => return error(format!("{}: invalid opcode {}", name, code_byte)),
+            }
+        }
+        let mut positions: Vec<Pos> = Vec::with_capacity(code.len());
+        let runs = self.len()?;
+        for i in 0 .. runs {
+            let pc = self.u32()? as usize;
+            let pos = Pos { line: self.u32()?, col: self.u32()? };
+            let starts_run = if i == 0 { pc == 0 } else { pc >= positions.len() };
+            if !starts_run || pc >= code.len() {
+                return error(format!("{}: invalid line-number table", name));
+            }
+            if let Some(&last) = positions.last() {
+                positions.resize(pc, last);
+            }
+            positions.push(pos);
+        }
+        if let Some(&last) = positions.last() {
+            positions.resize(code.len(), last);
+        } else if !code.is_empty() {
+            return error(format!("{}: missing line-number table", name));
+        }
+        return Ok(Function {
+            name: name,
+            params: params,
+            locals: locals,
+            result: result,
+            code: code,
+            positions: positions,
+        });
+    }
+}
+
+/// The instructions, in the order of their opcodes.
+const OPCODES: [Op; 56] = [
+    Op::Const(0), Op::Zero(0), Op::Pop, Op::Dup, Op::Dup2,
+    Op::LoadLocal(0), Op::StoreLocal(0), Op::LoadGlobal(0), Op::StoreGlobal(0), Op::Copy,
+    Op::Index, Op::SetIndex, Op::Field(0), Op::SetField(0), Op::Len, Op::Cap, Op::Append,
+    Op::AddInt, Op::SubInt, Op::MulInt, Op::DivInt, Op::RemInt, Op::NegInt,
+    Op::Shl, Op::Shr, Op::BitAnd, Op::BitOr, Op::BitXor, Op::BitClear, Op::BitNot, Op::Wrap32,
+    Op::AddFloat, Op::SubFloat, Op::MulFloat, Op::DivFloat, Op::NegFloat, Op::Concat, Op::Not,
+    Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge, Op::EqAgg(0),
+    Op::IntToFloat, Op::FloatToInt, Op::RuneToString,
+    Op::Jump(0), Op::JumpIfFalse(0), Op::JumpIfTrue(0), Op::Call(0),
+    Op::Return, Op::ReturnValue, Op::Print(0), Op::Println(0),
+];
+
+fn opcode(op: &Op) -> u8 {
+    let kind = mem::discriminant(op);
+    return OPCODES.iter().position(|op| mem::discriminant(op) == kind).unwrap() as u8;
+}
+
+/// Returns the instruction of an opcode with operand `n`.
+fn decode(code: u8, n: u32) -> Option<Op> {
+    let op = match *OPCODES.get(code as usize)? {
+        Op::Const(_) => Op::Const(n),
+        Op::Zero(_) => Op::Zero(n),
+        Op::LoadLocal(_) => Op::LoadLocal(n),
+        Op::StoreLocal(_) => Op::StoreLocal(n),
+        Op::LoadGlobal(_) => Op::LoadGlobal(n),
+        Op::StoreGlobal(_) => Op::StoreGlobal(n),
+        Op::Field(_) => Op::Field(n),
+        Op::SetField(_) => Op::SetField(n),
+        Op::EqAgg(_) => Op::EqAgg(n),
+        Op::Jump(_) => Op::Jump(n),
+        Op::JumpIfFalse(_) => Op::JumpIfFalse(n),
+        Op::JumpIfTrue(_) => Op::JumpIfTrue(n),
+        Op::Call(_) => Op::Call(n),
+        Op::Print(_) => Op::Print(n),
+        Op::Println(_) => Op::Println(n),
+        op => op,
+    };
+    return Some(op);
+}
+
+/// Checks that the operands of the instructions are in range,
+/// that every function returns the way its table entry says,
+/// and that the operand stack of every instruction has the same
+/// height however it is reached and holds its operands.
+pub fn verify(module: &Module) -> Result<()> {
+    match module.functions.get(module.entry as usize) {
+        Some(f) if f.params == 0 && !f.result => {}
+        Some(_) => return error("the entry function takes parameters or returns a value".to_string()),
+        None => return error(format!("entry function {} out of range", module.entry)),
+    }
+    for (i, f) in module.functions.iter().enumerate() {
+        if let Err(msg) = verify_function(module, f) {
+            return error(format!("function {} ({}): {}", i, f.name, msg));
+        }
+    }
+    return Ok(());
+}
+
+fn verify_function(module: &Module, f: &Function) -> result::Result<(), String> {
+    if f.locals < f.params {
+        return Err("fewer locals than parameters".to_string());
+    }
+    if f.positions.len() != f.code.len() {
+        return Err("line-number table does not cover the code".to_string());
+    }
+    // The heights of the operand stack before each instruction,
+    // above the local variables.
+    let mut heights: Vec<Option<u32>> = vec![None; f.code.len()];
+    let mut work = vec![(0, 0)];
+    while let Some((pc, height)) = work.pop() {
+        let op = match f.code.get(pc) {
+            Some(&op) => op,
+            None => return Err("execution runs past the end of the code".to_string()),
+        };
+        match heights[pc] {
+            Some(h) if h == height => continue,
+            Some(h) => return Err(format!("{:04}: stack height {} and {}", pc, h, height)),
+            None => heights[pc] = Some(height),
+        }
+        let in_range = |n: u32, len: usize, what: &str| {
+            if (n as usize) < len {
+                return Ok(());
+            }
+            return Err(format!("{:04}: {} {} out of range", pc, what, n));
+        };
+        let (pops, pushes) = match op {
+            Op::Const(n) => {
+                in_range(n, module.constants.len(), "constant")?;
+                (0, 1)
+            }
+            Op::Zero(n) => {
+                in_range(n, module.layouts.len(), "layout")?;
+                (0, 1)
+            }
+            Op::LoadLocal(n) => {
+                in_range(n, f.locals as usize, "local")?;
+                (0, 1)
+            }
+            Op::StoreLocal(n) => {
+                in_range(n, f.locals as usize, "local")?;
+                (1, 0)
+            }
+            Op::LoadGlobal(n) => {
+                in_range(n, module.globals as usize, "global")?;
+                (0, 1)
+            }
+            Op::StoreGlobal(n) => {
+                in_range(n, module.globals as usize, "global")?;
+                (1, 0)
+            }
+            Op::EqAgg(n) => {
+                in_range(n, module.layouts.len(), "layout")?;
+                (2, 1)
+            }
+            Op::Jump(n) | Op::JumpIfFalse(n) | Op::JumpIfTrue(n) => {
+                in_range(n, f.code.len(), "jump target")?;
+                if op == Op::Jump(n) { (0, 0) } else { (1, 0) }
+            }
+            Op::Call(n) => {
+                in_range(n, module.functions.len(), "function")?;
+                let callee = &module.functions[n as usize];
+                (callee.params, callee.result as u32)
+            }
+            Op::Return if f.result => return Err(format!("{:04}: return without a value", pc)),
+            Op::ReturnValue if !f.result => return Err(format!("{:04}: return with a value", pc)),
+            Op::Return => (0, 0),
+            Op::ReturnValue => (1, 0),
+            Op::Print(n) | Op::Println(n) => (n, 0),
+            Op::Pop => (1, 0),
+            Op::Dup => (1, 2),
+            Op::Dup2 => (2, 4),
+            Op::Field(_) => (1, 1),
+            Op::SetField(_) => (2, 0),
+            Op::Index => (2, 1),
+            Op::SetIndex => (3, 0),
+            Op::Copy | Op::Len | Op::Cap | Op::NegInt | Op::BitNot | Op::Wrap32 | Op::NegFloat
+            | Op::Not | Op::IntToFloat | Op::FloatToInt | Op::RuneToString => (1, 1),
+            Op::Append | Op::AddInt | Op::SubInt | Op::MulInt | Op::DivInt | Op::RemInt
+            | Op::Shl | Op::Shr | Op::BitAnd | Op::BitOr | Op::BitXor | Op::BitClear
+            | Op::AddFloat | Op::SubFloat | Op::MulFloat | Op::DivFloat | Op::Concat
+            | Op::Eq | Op::Ne | Op::Lt | Op::Le | Op::Gt | Op::Ge => (2, 1),
+        };
+        if height < pops {
+            return Err(format!("{:04}: {} needs {} operands, the stack has {}",
+                               pc, op.name(), pops, height));
+        }
+        let height = height - pops + pushes;
+        match op {
+            Op::Return | Op::ReturnValue => {}
+            Op::Jump(n) => work.push((n as usize, height)),
+            Op::JumpIfFalse(n) | Op::JumpIfTrue(n) => {
+                work.push((n as usize, height));
+                work.push((pc + 1, height));
+            }
+            _ => work.push((pc + 1, height)),
+        }
+    }
+    return Ok(());
+}
+
+/// The CRC-32 (IEEE 802.3) of a sequence of bytes.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0 .. 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    return !crc;
+}
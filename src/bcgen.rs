@@ -0,0 +1,814 @@
+use std::collections::HashMap;
+use std::mem;
+
+use ast::*;
+use bytecode::{Const, Function, Layout, Module, Op, Pos};
+use constant::{self, Value};
+use loc::Loc;
+use lower::one;
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// Compiles a type-checked program to bytecode.
+///
+/// Every local variable gets a slot of its own in the frame of
+/// its function, so that the slots of a function never depend
+/// on its scopes.  The entry function of the module initializes
+/// the package variables in the order of their declarations and
+/// then calls `main`.
+pub fn compile(prog: &Program, types: &TypeTable, table: &SymbolTable) -> Module {
+    let filename = prog.package.loc.filename.clone();
+    let mut compiler = Compiler {
+        types: types,
+        table: table,
+        module: Module {
+            filename: filename,
+            constants: Vec::new(),
+            layouts: Vec::new(),
+            globals: 0,
+            functions: Vec::new(),
+            entry: 0,
+        },
+        funcs: HashMap::new(),
+        globals: HashMap::new(),
+        layouts: HashMap::new(),
+        func: new_function("<init>", 0),
+        locals: HashMap::new(),
+        targets: Vec::new(),
+    };
+    compiler.program(prog);
+    return compiler.module;
+}
+
+fn new_function(name: &str, params: u32) -> Function {
+    Function {
+        name: name.to_string(),
+        params: params,
+        locals: params,
+        result: false,
+        code: Vec::new(),
+        positions: Vec::new(),
+    }
+}
+
+/// A statement that `break` or `continue` may leave, with
+/// the jumps to patch once its end is known.
+struct Target {
+    is_loop: bool,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    module: Module,
+    /// The indices of the functions and of the package
+    /// variables, and the layouts created so far.
+    funcs: HashMap<SymbolId, u32>,
+    globals: HashMap<SymbolId, u32>,
+    layouts: HashMap<TypeId, u32>,
+    /// The function being compiled and the slots of its
+    /// local variables.
+    func: Function,
+    locals: HashMap<SymbolId, u32>,
+    /// The loops and switches being compiled, from the
+    /// outermost one.
+    targets: Vec<Target>,
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, op: Op, loc: &Loc) -> usize {
+        self.func.code.push(op);
+        self.func.positions.push(Pos { line: loc.line as u32, col: loc.col as u32 });
+        return self.func.code.len() - 1;
+    }
+
+    /// Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let here = self.func.code.len() as u32;
+        self.func.code[at] = match self.func.code[at] {
+            Op::Jump(_) => Op::Jump(here),
+            Op::JumpIfFalse(_) => Op::JumpIfFalse(here),
+            Op::JumpIfTrue(_) => Op::JumpIfTrue(here),
+            op => op,
+        };
+    }
+
+    fn here(&self) -> u32 {
+        return self.func.code.len() as u32;
+    }
+
+    fn constant(&mut self, c: Const) -> u32 {
+        // Floats are compared bit for bit, so that 0.0 and -0.0
+        // are distinct constants.
+        let same = |a: &Const| match (a, &c) {
+            (&Const::Float(x), &Const::Float(y)) => x.to_bits() == y.to_bits(),
+            _ => *a == c,
+        };
+        if let Some(i) = self.module.constants.iter().position(same) {
+            return i as u32;
+        }
+        self.module.constants.push(c);
+        return (self.module.constants.len() - 1) as u32;
+    }
+
+    fn layout(&mut self, ty: TypeId) -> u32 {
+        let ty = self.types.underlying(self.types.default_type(ty));
+        if let Some(&i) = self.layouts.get(&ty) {
+            return i;
+        }
+        let layout = self.layout_of(ty);
+        self.module.layouts.push(layout);
+        let i = (self.module.layouts.len() - 1) as u32;
+        self.layouts.insert(ty, i);
+        return i;
+    }
+
+    fn layout_of(&self, ty: TypeId) -> Layout {
+        match *self.types.under(self.types.default_type(ty)) {
+            TypeKind::Float64 => return Layout::Float,
+            TypeKind::Bool => return Layout::Bool,
+            TypeKind::String => return Layout::Str,
+            TypeKind::Slice(_) => return Layout::Slice,
+            TypeKind::Array(len, elem) => return Layout::Array(len, Box::new(self.layout_of(elem))),
+            TypeKind::Struct(ref fields) => {
+                return Layout::Struct(fields.iter()
+                                      .map(|&(ref name, ty)| (name != "_", self.layout_of(ty)))
+                                      .collect());
+            }
+            _ => return Layout::Int,
+        }
+    }
+
+    fn is_aggregate(&self, ty: TypeId) -> bool {
+        match *self.types.under(self.types.default_type(ty)) {
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => return true,
+            _ => return false,
+        }
+    }
+
+    fn is_rune(&self, ty: TypeId) -> bool {
+        return *self.types.under(self.types.default_type(ty)) == TypeKind::Rune;
+    }
+
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    /// Returns a new slot for a temporary.
+    fn temp(&mut self) -> u32 {
+        self.func.locals += 1;
+        return self.func.locals - 1;
+    }
+
+    /// Returns the slot of a local variable, creating it if
+    /// it is declared here.
+    fn slot(&mut self, sym: SymbolId) -> u32 {
+        if let Some(&slot) = self.locals.get(&sym) {
+            return slot;
+        }
+        let slot = self.temp();
+        self.locals.insert(sym, slot);
+        return slot;
+    }
+
+    /// Stores the value on top of the stack in a variable.
+    fn store_var(&mut self, id: &Ident, loc: &Loc) {
+        let sym = match id.sym {
+            Some(sym) if !id.is_blank() => sym,
+            _ => {
+                self.emit(Op::Pop, loc);
+                return;
+            }
+        };
+        match self.globals.get(&sym) {
+            Some(&global) if !self.locals.contains_key(&sym) => {
+                self.emit(Op::StoreGlobal(global), loc);
+            }
+            _ => {
+                let slot = self.slot(sym);
+                self.emit(Op::StoreLocal(slot), loc);
+            }
+        }
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) => Some(f),
+                _ => None,
+            })
+            .collect();
+        // The entry function comes first.
+        for (i, f) in funcs.iter().enumerate() {
+            if let Some(sym) = f.name.sym {
+                self.funcs.insert(sym, i as u32 + 1);
+            }
+        }
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        if let Some(sym) = id.sym {
+                            self.globals.insert(sym, self.module.globals);
+                            self.module.globals += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.module.functions.push(new_function("<init>", 0));
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec);
+                }
+            }
+        }
+        let loc = &prog.package.loc;
+        if let Some(main) = funcs.iter().find(|f| f.name.name == "main") {
+            let index = main.name.sym.and_then(|sym| self.funcs.get(&sym).cloned());
+            if let Some(index) = index {
+                self.emit(Op::Call(index), loc);
+            }
+        }
+        self.emit(Op::Return, loc);
+        let init = mem::replace(&mut self.func, new_function("", 0));
+        self.module.functions[0] = init;
+
+        for f in funcs {
+            self.func_decl(f);
+        }
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.func = new_function(&f.name.name, f.params.len() as u32);
+        self.func.result = f.result.is_some();
+        self.locals.clear();
+        for (i, p) in f.params.iter().enumerate() {
+            if let Some(sym) = p.name.sym {
+                self.locals.insert(sym, i as u32);
+            }
+        }
+        self.stmts(&f.body.stmts);
+        // The type checker makes sure that functions with a
+        // result do not reach their end.
+        let ends_with_return = match f.body.stmts.last() {
+            Some(&Stmt { kind: StmtKind::Return(_), .. }) => true,
+            _ => false,
+        };
+        if !ends_with_return {
+            if !self.func.result {
+                self.emit(Op::Return, &f.body.loc);
+            } else {
+                let result = match f.name.sym.and_then(|sym| self.table.symbol(sym).ty) {
+                    Some(ty) => match *self.types.kind(ty) {
+                        TypeKind::Func(_, result) => result,
+                        _ => INVALID,
+                    },
+                    None => INVALID,
+                };
+                let layout = self.layout(result);
+                self.emit(Op::Zero(layout), &f.body.loc);
+                self.emit(Op::ReturnValue, &f.body.loc);
+            }
+        }
+        let func = mem::replace(&mut self.func, new_function("", 0));
+        self.module.functions.push(func);
+    }
+
+    /// Declares the variables of a `var` spec.  The values are
+    /// all evaluated before they are stored, from last to first.
+    fn var_spec(&mut self, spec: &VarSpec) {
+        if spec.values.is_empty() {
+            for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                let layout = self.layout(self.var_type(id));
+                self.emit(Op::Zero(layout), &id.loc);
+                self.store_var(id, &id.loc);
+            }
+            return;
+        }
+        for e in &spec.values {
+            self.expr(e);
+        }
+        for id in spec.names.iter().rev() {
+            self.store_var(id, &id.loc);
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let loc = &stmt.loc;
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => {
+                self.expr(e);
+                if e.ty.is_some_and(|ty| ty != VOID) {
+                    self.emit(Op::Pop, loc);
+                }
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => {
+                if lhs.len() == 1 {
+                    self.assign(&lhs[0], &rhs[0], loc);
+                } else {
+                    self.multi_assign(lhs, rhs, loc);
+                }
+            }
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                for e in values {
+                    self.expr(e);
+                }
+                for id in names.iter().rev() {
+                    self.store_var(id, &id.loc);
+                }
+            }
+            StmtKind::Print(ref args) | StmtKind::Println(ref args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+                let n = args.len() as u32;
+                match stmt.kind {
+                    StmtKind::Print(_) => self.emit(Op::Print(n), loc),
+                    _ => self.emit(Op::Println(n), loc),
+                };
+            }
+            StmtKind::Return(None) => {
+                self.emit(Op::Return, loc);
+            }
+            StmtKind::Return(Some(ref e)) => {
+                self.expr(e);
+                self.emit(Op::ReturnValue, loc);
+            }
+            StmtKind::Break => {
+                let jump = self.emit(Op::Jump(0), loc);
+                if let Some(target) = self.targets.last_mut() {
+                    target.breaks.push(jump);
+                }
+            }
+            StmtKind::Continue => {
+                let jump = self.emit(Op::Jump(0), loc);
+                if let Some(target) = self.targets.iter_mut().rev().find(|t| t.is_loop) {
+                    target.continues.push(jump);
+                }
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt, loc),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt, loc),
+        }
+    }
+
+    /// Pushes the operands of an indexing or selector expression
+    /// that is assigned to, and returns the instruction that
+    /// stores a value in it; `None` for variables.
+    fn target(&mut self, e: &Expr) -> Option<Op> {
+        match e.kind {
+            ExprKind::Index(ref a, ref i) => {
+                self.operand(a);
+                self.expr(i);
+                return Some(Op::SetIndex);
+            }
+            ExprKind::Selector(ref x, ref field) => {
+                self.operand(x);
+                return Some(Op::SetField(self.field_index(x, field)));
+            }
+            _ => return None,
+        }
+    }
+
+    /// Stores the value on top of the stack in `e`, with the
+    /// instruction returned by `target`.
+    fn store(&mut self, store: Option<Op>, e: &Expr, loc: &Loc) {
+        match store {
+            Some(op) => {
+                self.emit(op, &e.loc);
+            }
+            None => match e.kind {
+                ExprKind::Id(ref id) => self.store_var(id, loc),
+                _ => {
+                    self.emit(Op::Pop, loc);
+                }
+            },
+        }
+    }
+
+    fn assign(&mut self, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let store = self.target(lhs);
+        self.expr(rhs);
+        self.store(store, lhs, loc);
+    }
+
+    /// The operands of the indexing expressions on the left and
+    /// the values on the right are all evaluated, and kept in
+    /// temporaries, before the assignments are made from left to
+    /// right.
+    fn multi_assign(&mut self, lhs: &[Expr], rhs: &[Expr], loc: &Loc) {
+        let mut targets = Vec::new();
+        for e in lhs {
+            let store = self.target(e);
+            // An indexing expression has two operands, which
+            // are popped in reverse order.
+            let count = match store {
+                Some(Op::SetIndex) => 2,
+                Some(_) => 1,
+                None => 0,
+            };
+            let slots: Vec<u32> = (0 .. count).map(|_| self.temp()).collect();
+            for &slot in slots.iter().rev() {
+                self.emit(Op::StoreLocal(slot), loc);
+            }
+            targets.push((store, slots));
+        }
+        let mut values = Vec::new();
+        for e in rhs {
+            self.expr(e);
+            let slot = self.temp();
+            self.emit(Op::StoreLocal(slot), loc);
+            values.push(slot);
+        }
+        for ((e, (store, slots)), value) in lhs.iter().zip(targets).zip(values) {
+            for slot in slots {
+                self.emit(Op::LoadLocal(slot), loc);
+            }
+            self.emit(Op::LoadLocal(value), loc);
+            self.store(store, e, loc);
+        }
+    }
+
+    /// `x op= y`: the operands that the store of `x` takes are
+    /// duplicated to load its value first.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let ty = lhs.ty.unwrap_or(INVALID);
+        let store = self.target(lhs);
+        match store {
+            Some(Op::SetIndex) => {
+                self.emit(Op::Dup2, loc);
+                self.emit(Op::Index, &lhs.loc);
+            }
+            Some(Op::SetField(field)) => {
+                self.emit(Op::Dup, loc);
+                self.emit(Op::Field(field), loc);
+            }
+            _ => self.operand(lhs),
+        }
+        self.expr(rhs);
+        self.arith(op, ty, loc);
+        self.store(store, lhs, loc);
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        if let Some(ref init) = if_stmt.init {
+            self.stmt(init);
+        }
+        self.expr(&if_stmt.cond);
+        let to_else = self.emit(Op::JumpIfFalse(0), &if_stmt.cond.loc);
+        self.stmts(&if_stmt.then.stmts);
+        match if_stmt.els {
+            Some(ref els) => {
+                let to_end = self.emit(Op::Jump(0), &if_stmt.then.loc);
+                self.patch(to_else);
+                self.stmt(els);
+                self.patch(to_end);
+            }
+            None => self.patch(to_else),
+        }
+    }
+
+    /// The tag is kept in a temporary and compared with the case
+    /// expressions in order until one of them matches; each
+    /// comparison jumps to the body of its clause.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt, loc: &Loc) {
+        if let Some(ref init) = switch_stmt.init {
+            self.stmt(init);
+        }
+        let tag = match switch_stmt.tag {
+            Some(ref tag) => {
+                self.expr(tag);
+                let slot = self.temp();
+                self.emit(Op::StoreLocal(slot), &tag.loc);
+                Some((slot, tag.ty.unwrap_or(INVALID)))
+            }
+            None => None,
+        };
+        let mut jumps = Vec::new();
+        for (i, case) in switch_stmt.clauses.iter().enumerate() {
+            if let Some(ref exprs) = case.exprs {
+                for e in exprs {
+                    match tag {
+                        Some((slot, ty)) => {
+                            self.emit(Op::LoadLocal(slot), &e.loc);
+                            self.operand(e);
+                            self.equal(ty, false, &e.loc);
+                        }
+                        None => self.expr(e),
+                    }
+                    jumps.push((i, self.emit(Op::JumpIfTrue(0), &e.loc)));
+                }
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|c| c.exprs.is_none());
+        let to_default = self.emit(Op::Jump(0), loc);
+
+        self.targets.push(Target { is_loop: false, breaks: Vec::new(), continues: Vec::new() });
+        let mut ends = Vec::new();
+        for (i, case) in switch_stmt.clauses.iter().enumerate() {
+            for &(_, jump) in jumps.iter().filter(|&&(clause, _)| clause == i) {
+                self.patch(jump);
+            }
+            if default == Some(i) {
+                self.patch(to_default);
+            }
+            self.stmts(&case.body);
+            ends.push(self.emit(Op::Jump(0), &case.loc));
+        }
+        if default.is_none() {
+            self.patch(to_default);
+        }
+        let target = self.targets.pop();
+        for jump in ends.into_iter().chain(target.map_or(Vec::new(), |t| t.breaks)) {
+            self.patch(jump);
+        }
+    }
+
+    fn for_stmt(&mut self, for_stmt: &ForStmt, loc: &Loc) {
+        if let Some(ref init) = for_stmt.init {
+            self.stmt(init);
+        }
+        let top = self.here();
+        let exit = match for_stmt.cond {
+            Some(ref cond) => {
+                self.expr(cond);
+                Some(self.emit(Op::JumpIfFalse(0), &cond.loc))
+            }
+            None => None,
+        };
+        self.targets.push(Target { is_loop: true, breaks: Vec::new(), continues: Vec::new() });
+        self.stmts(&for_stmt.body.stmts);
+        let target = self.targets.pop();
+        let (breaks, continues) = target.map_or((Vec::new(), Vec::new()), |t| (t.breaks, t.continues));
+        for jump in continues {
+            self.patch(jump);
+        }
+        if let Some(ref post) = for_stmt.post {
+            self.stmt(post);
+        }
+        self.emit(Op::Jump(top), loc);
+        for jump in breaks.into_iter().chain(exit) {
+            self.patch(jump);
+        }
+    }
+
+
+    // EXPRESSIONS
+
+    /// Compiles an expression whose value is used, copying the
+    /// arrays and structs that are stored in variables.
+    fn expr(&mut self, e: &Expr) {
+        self.operand(e);
+        let stored = match e.kind {
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => true,
+            _ => false,
+        };
+        if stored && e.value.is_none() && self.is_aggregate(e.ty.unwrap_or(INVALID)) {
+            self.emit(Op::Copy, &e.loc);
+        }
+    }
+
+    /// Compiles an expression without copying its value, for
+    /// expressions that are only indexed, selected or compared.
+    fn operand(&mut self, e: &Expr) {
+        let ty = e.ty.unwrap_or(INVALID);
+        let loc = &e.loc;
+        if let Some(ref value) = e.value {
+            let c = self.value(value, ty);
+            let c = self.constant(c);
+            self.emit(Op::Const(c), loc);
+            return;
+        }
+        match e.kind {
+            ExprKind::Id(ref id) => {
+                let sym = id.sym.unwrap_or(0);
+                match self.locals.get(&sym) {
+                    Some(&slot) => {
+                        self.emit(Op::LoadLocal(slot), loc);
+                    }
+                    None => {
+                        let global = self.globals.get(&sym).cloned().unwrap_or(0);
+                        self.emit(Op::LoadGlobal(global), loc);
+                    }
+                }
+            }
+            ExprKind::Unary(op, ref x) => {
+                self.expr(x);
+                let float = self.types.is_float(ty);
+                match op {
+                    UnOp::Plus => {}
+                    UnOp::Neg if float => {
+                        self.emit(Op::NegFloat, loc);
+                    }
+                    UnOp::Neg => {
+                        self.emit(Op::NegInt, loc);
+                        if self.is_rune(ty) {
+                            self.emit(Op::Wrap32, loc);
+                        }
+                    }
+                    UnOp::Not => {
+                        self.emit(Op::Not, loc);
+                    }
+                    UnOp::BitNot => {
+                        self.emit(Op::BitNot, loc);
+                    }
+                }
+            }
+            ExprKind::Binary(BinOp::And, ref x, ref y) | ExprKind::Binary(BinOp::Or, ref x, ref y) => {
+                // `x && y` is `x` if `x` is false, `y` otherwise.
+                self.expr(x);
+                self.emit(Op::Dup, loc);
+                let jump = match e.kind {
+                    ExprKind::Binary(BinOp::And, _, _) => self.emit(Op::JumpIfFalse(0), loc),
+                    _ => self.emit(Op::JumpIfTrue(0), loc),
+                };
+                self.emit(Op::Pop, loc);
+                self.expr(y);
+                self.patch(jump);
+            }
+            ExprKind::Binary(op, ref x, ref y) if op.is_comparison() => {
+                let operand_ty = x.ty.unwrap_or(INVALID);
+                self.operand(x);
+                self.operand(y);
+                match op {
+                    BinOp::Eq => self.equal(operand_ty, false, loc),
+                    BinOp::Ne => self.equal(operand_ty, true, loc),
+                    BinOp::Lt => { self.emit(Op::Lt, loc); }
+                    BinOp::Le => { self.emit(Op::Le, loc); }
+                    BinOp::Gt => { self.emit(Op::Gt, loc); }
+                    _ => { self.emit(Op::Ge, loc); }
+                }
+            }
+            ExprKind::Binary(op, ref x, ref y) => {
+                self.expr(x);
+                self.expr(y);
+                self.arith(op, x.ty.unwrap_or(INVALID), loc);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let sym = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => sym,
+                    _ => return,
+                };
+                match self.table.symbol(sym).kind {
+                    SymbolKind::Type => self.conversion(ty, &args[0]),
+                    SymbolKind::Builtin => {
+                        self.operand(&args[0]);
+                        if self.table.symbol(sym).name == "cap" {
+                            self.emit(Op::Cap, loc);
+                        } else {
+                            self.emit(Op::Len, loc);
+                        }
+                    }
+                    _ => {
+                        for arg in args {
+                            self.expr(arg);
+                        }
+                        let index = self.funcs.get(&sym).cloned().unwrap_or(0);
+                        self.emit(Op::Call(index), loc);
+                    }
+                }
+            }
+            ExprKind::Index(ref a, ref i) => {
+                self.operand(a);
+                self.expr(i);
+                self.emit(Op::Index, loc);
+            }
+            ExprKind::Selector(ref x, ref field) => {
+                self.operand(x);
+                let index = self.field_index(x, field);
+                self.emit(Op::Field(index), loc);
+            }
+            ExprKind::Append(ref s, ref x) => {
+                self.expr(s);
+                self.expr(x);
+                self.emit(Op::Append, loc);
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => {}
+        }
+    }
+
+    /// Returns the constant that represents a value of type `ty`.
+    fn value(&self, value: &Value, ty: TypeId) -> Const {
+        let ty = self.types.default_type(ty);
+        match *value {
+            Value::Bool(b) => return Const::Bool(b),
+            Value::String(ref s) => return Const::Str(constant::string_bytes(s)),
+            Value::Int(_) | Value::Float(_) if self.types.is_float(ty) => {
+                return Const::Float(value.to_rat().map_or(0.0, |r| r.to_f64()));
+            }
+            Value::Int(_) | Value::Float(_) => {
+                return Const::Int(value.to_int().and_then(|n| n.to_i64()).unwrap_or(0));
+            }
+        }
+    }
+
+    fn field_index(&self, x: &Expr, field: &Ident) -> u32 {
+        let index = match *self.types.under(x.ty.unwrap_or(INVALID)) {
+            TypeKind::Struct(ref fields) => fields.iter().position(|f| f.0 == field.name),
+            _ => None,
+        };
+        return index.unwrap_or(0) as u32;
+    }
+
+    /// Emits the operation `op` on two operands of type `ty`.
+    /// Integer operations wrap around, as in Go.
+    fn arith(&mut self, op: BinOp, ty: TypeId, loc: &Loc) {
+        let ty = self.types.default_type(ty);
+        if self.types.is_string(ty) {
+            self.emit(Op::Concat, loc);
+            return;
+        }
+        if self.types.is_float(ty) {
+            let op = match op {
+                BinOp::Add => Op::AddFloat,
+                BinOp::Sub => Op::SubFloat,
+                BinOp::Mul => Op::MulFloat,
+                _ => Op::DivFloat,
+            };
+            self.emit(op, loc);
+            return;
+        }
+        let (op, wraps) = match op {
+            BinOp::Add => (Op::AddInt, true),
+            BinOp::Sub => (Op::SubInt, true),
+            BinOp::Mul => (Op::MulInt, true),
+            BinOp::Div => (Op::DivInt, true),
+            BinOp::Rem => (Op::RemInt, false),
+            BinOp::Shl => (Op::Shl, true),
+            BinOp::Shr => (Op::Shr, false),
+            BinOp::BitAnd => (Op::BitAnd, false),
+            BinOp::BitOr => (Op::BitOr, false),
+            BinOp::BitXor => (Op::BitXor, false),
+            _ => (Op::BitClear, false),
+        };
+        self.emit(op, loc);
+        if wraps && self.is_rune(ty) {
+            self.emit(Op::Wrap32, loc);
+        }
+    }
+
+    fn equal(&mut self, ty: TypeId, negate: bool, loc: &Loc) {
+        if self.is_aggregate(ty) {
+            let layout = self.layout(ty);
+            self.emit(Op::EqAgg(layout), loc);
+            if negate {
+                self.emit(Op::Not, loc);
+            }
+        } else if negate {
+            self.emit(Op::Ne, loc);
+        } else {
+            self.emit(Op::Eq, loc);
+        }
+    }
+
+    fn conversion(&mut self, ty: TypeId, arg: &Expr) {
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        let loc = &arg.loc;
+        self.expr(arg);
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            self.emit(Op::RuneToString, loc);
+        } else if self.types.is_float(ty) && !self.types.is_float(arg_ty) {
+            self.emit(Op::IntToFloat, loc);
+        } else if self.types.is_integer(ty) {
+            if self.types.is_float(arg_ty) {
+                self.emit(Op::FloatToInt, loc);
+            }
+            if self.is_rune(ty) && !self.is_rune(arg_ty) {
+                self.emit(Op::Wrap32, loc);
+            }
+        }
+    }
+}
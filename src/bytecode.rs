@@ -0,0 +1,320 @@
+use std::fmt;
+
+/// An instruction of the virtual machine.
+///
+/// The machine is a stack machine: instructions pop their
+/// operands from the operand stack and push their result.  The
+/// local variables of a call are the first slots of its part of
+/// the stack, starting with its parameters.  Integer operands of
+/// instructions index the constant pool, the layouts, the local
+/// or global variables, the fields of a struct, the functions of
+/// the module or the code of the current function (for jumps).
+///
+/// Arrays and structs are references to heap objects, which are
+/// copied with `Copy` wherever GoLite copies a value.  Stores to
+/// a variable, element or field that holds an array or struct
+/// overwrite the object in place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Pushes a constant of the pool.
+    Const(u32),
+    /// Pushes the zero value of a layout.
+    Zero(u32),
+    Pop,
+    Dup,
+    /// Duplicates the two values on top of the stack.
+    Dup2,
+    LoadLocal(u32),
+    StoreLocal(u32),
+    LoadGlobal(u32),
+    StoreGlobal(u32),
+    /// Replaces an array or struct with a copy of it.
+    Copy,
+
+    /// `a i -> a[i]`
+    Index,
+    /// `a i x ->`, stores `x` in `a[i]`
+    SetIndex,
+    /// `s -> s.f`
+    Field(u32),
+    /// `s x ->`, stores `x` in `s.f`
+    SetField(u32),
+    Len,
+    Cap,
+    /// `s x -> append(s, x)`
+    Append,
+
+    // Integer operations wrap around at 64 bits; `Wrap32`
+    // brings the result back in the range of runes.
+    AddInt,
+    SubInt,
+    MulInt,
+    DivInt,
+    RemInt,
+    NegInt,
+    Shl,
+    Shr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitClear,
+    BitNot,
+    Wrap32,
+    AddFloat,
+    SubFloat,
+    MulFloat,
+    DivFloat,
+    NegFloat,
+    Concat,
+    Not,
+
+    // Comparisons of scalars (numbers, booleans and strings);
+    // `EqAgg` compares arrays and structs of a layout.
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    EqAgg(u32),
+
+    IntToFloat,
+    FloatToInt,
+    /// Converts a code point to a string.
+    RuneToString,
+
+    Jump(u32),
+    /// Pops a boolean and jumps if it is false.
+    JumpIfFalse(u32),
+    JumpIfTrue(u32),
+    /// Calls a function, whose arguments are on the stack.
+    Call(u32),
+    Return,
+    ReturnValue,
+    /// Pops and prints values, as `print` does.
+    Print(u32),
+    Println(u32),
+}
+
+impl Op {
+    /// Returns the name of the instruction, without operand.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Op::Const(_) => "const",
+            Op::Zero(_) => "zero",
+            Op::Pop => "pop",
+            Op::Dup => "dup",
+            Op::Dup2 => "dup2",
+            Op::LoadLocal(_) => "load_local",
+            Op::StoreLocal(_) => "store_local",
+            Op::LoadGlobal(_) => "load_global",
+            Op::StoreGlobal(_) => "store_global",
+            Op::Copy => "copy",
+            Op::Index => "index",
+            Op::SetIndex => "set_index",
+            Op::Field(_) => "field",
+            Op::SetField(_) => "set_field",
+            Op::Len => "len",
+            Op::Cap => "cap",
+            Op::Append => "append",
+            Op::AddInt => "add_int",
+            Op::SubInt => "sub_int",
+            Op::MulInt => "mul_int",
+            Op::DivInt => "div_int",
+            Op::RemInt => "rem_int",
+            Op::NegInt => "neg_int",
+            Op::Shl => "shl",
+            Op::Shr => "shr",
+            Op::BitAnd => "bit_and",
+            Op::BitOr => "bit_or",
+            Op::BitXor => "bit_xor",
+            Op::BitClear => "bit_clear",
+            Op::BitNot => "bit_not",
+            Op::Wrap32 => "wrap32",
+            Op::AddFloat => "add_float",
+            Op::SubFloat => "sub_float",
+            Op::MulFloat => "mul_float",
+            Op::DivFloat => "div_float",
+            Op::NegFloat => "neg_float",
+            Op::Concat => "concat",
+            Op::Not => "not",
+            Op::Eq => "eq",
+            Op::Ne => "ne",
+            Op::Lt => "lt",
+            Op::Le => "le",
+            Op::Gt => "gt",
+            Op::Ge => "ge",
+            Op::EqAgg(_) => "eq_agg",
+            Op::IntToFloat => "int_to_float",
+            Op::FloatToInt => "float_to_int",
+            Op::RuneToString => "rune_to_string",
+            Op::Jump(_) => "jump",
+            Op::JumpIfFalse(_) => "jump_if_false",
+            Op::JumpIfTrue(_) => "jump_if_true",
+            Op::Call(_) => "call",
+            Op::Return => "return",
+            Op::ReturnValue => "return_value",
+            Op::Print(_) => "print",
+            Op::Println(_) => "println",
+        }
+    }
+
+    /// Returns the operand of the instruction, if it has one.
+    pub fn operand(&self) -> Option<u32> {
+        match *self {
+            Op::Const(n)
+            | Op::Zero(n)
+            | Op::LoadLocal(n)
+            | Op::StoreLocal(n)
+            | Op::LoadGlobal(n)
+            | Op::StoreGlobal(n)
+            | Op::Field(n)
+            | Op::SetField(n)
+            | Op::EqAgg(n)
+            | Op::Jump(n)
+            | Op::JumpIfFalse(n)
+            | Op::JumpIfTrue(n)
+            | Op::Call(n)
+            | Op::Print(n)
+            | Op::Println(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+/// A constant of the pool.  Strings are sequences of bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Const {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Vec<u8>),
+}
+
+impl fmt::Display for Const {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Const::Int(n) => write!(f, "{}", n),
+            Const::Float(x) => write!(f, "{:?}", x),
+            Const::Bool(b) => write!(f, "{}", b),
+            Const::Str(ref s) => write!(f, "{:?}", String::from_utf8_lossy(s)),
+        }
+    }
+}
+
+/// The shape of the values of a type, which is what the machine
+/// needs to know to create zero values and to compare arrays
+/// and structs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Int,
+    Float,
+    Bool,
+    Str,
+    Slice,
+    Array(u64, Box<Layout>),
+    /// The fields, and whether each of them is compared
+    /// (blank fields are not).
+    Struct(Vec<(bool, Layout)>),
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Layout::Int => write!(f, "int"),
+            Layout::Float => write!(f, "float"),
+            Layout::Bool => write!(f, "bool"),
+            Layout::Str => write!(f, "string"),
+            Layout::Slice => write!(f, "slice"),
+            Layout::Array(len, ref elem) => write!(f, "[{}]{}", len, elem),
+            Layout::Struct(ref fields) => {
+                write!(f, "struct {{")?;
+                for (i, &(compared, ref field)) in fields.iter().enumerate() {
+                    write!(f, "{}{}{}", if i > 0 { "; " } else { " " },
+                           if compared { "" } else { "_ " }, field)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// A source position, for the messages of runtime errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub line: u32,
+    pub col: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: u32,
+    /// The number of local variables, including the
+    /// parameters and the compiler's temporaries.
+    pub locals: u32,
+    /// Whether the function returns a value.
+    pub result: bool,
+    pub code: Vec<Op>,
+    /// The source position of every instruction.
+    pub positions: Vec<Pos>,
+}
+
+/// A compiled program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    /// The name of the source file.
+    pub filename: String,
+    pub constants: Vec<Const>,
+    pub layouts: Vec<Layout>,
+    pub globals: u32,
+    pub functions: Vec<Function>,
+    /// The function that initializes the package variables
+    /// and calls `main`.
+    pub entry: u32,
+}
+
+/// Returns a listing of the module: its constants and layouts,
+/// then the instructions of every function with the source line
+/// they come from (`|` if it is the line of the previous one).
+pub fn disassemble(module: &Module) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("; {}\n", module.filename));
+    out.push_str(&format!("; {} globals, entry {}\n", module.globals,
+                          module.functions[module.entry as usize].name));
+    if !module.constants.is_empty() {
+        out.push_str("\nconstants:\n");
+        for (i, c) in module.constants.iter().enumerate() {
+            out.push_str(&format!("  {:4}  {}\n", i, c));
+        }
+    }
+    if !module.layouts.is_empty() {
+        out.push_str("\nlayouts:\n");
+        for (i, layout) in module.layouts.iter().enumerate() {
+            out.push_str(&format!("  {:4}  {}\n", i, layout));
+        }
+    }
+    for (i, f) in module.functions.iter().enumerate() {
+        out.push_str(&format!("\nfunc {} {} (params {}, locals {}):\n", i, f.name, f.params, f.locals));
+        let mut line = None;
+        for (pc, (op, pos)) in f.code.iter().zip(&f.positions).enumerate() {
+            let src = if line == Some(pos.line) { "|".to_string() } else { pos.line.to_string() };
+            line = Some(pos.line);
+            let mut text = op.name().to_string();
+            if let Some(n) = op.operand() {
+                text.push_str(&format!(" {}", n));
+            }
+            let comment = match *op {
+                Op::Const(n) => module.constants.get(n as usize).map(|c| c.to_string()),
+                Op::Zero(n) | Op::EqAgg(n) => module.layouts.get(n as usize).map(|l| l.to_string()),
+                Op::Call(n) => module.functions.get(n as usize).map(|f| f.name.clone()),
+                _ => None,
+            };
+            match comment {
+                Some(comment) => out.push_str(&format!("  {:04} {:>5}  {:<20} ; {}\n", pc, src, text, comment)),
+                None => out.push_str(&format!("  {:04} {:>5}  {}\n", pc, src, text)),
+            }
+        }
+    }
+    return out;
+}
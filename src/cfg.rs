@@ -0,0 +1,386 @@
+use ast::*;
+use loc::Loc;
+use pretty::{pretty_expr, pretty_stmt};
+
+/// The entry and exit blocks of every graph, which contain no
+/// statements.
+pub const ENTRY: usize = 0;
+pub const EXIT: usize = 1;
+
+/// The control-flow graph of a function: basic blocks of
+/// statements, and the edges between them.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub name: String,
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// A sequence of statements that are executed one after the
+/// other.  The conditions of `if`, `for` and `switch` statements
+/// and the expressions of `case` clauses are the statements that
+/// end the blocks they branch from.
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock {
+    /// The source text of each statement, and where it starts.
+    pub stmts: Vec<(String, Loc)>,
+    pub succs: Vec<Edge>,
+}
+
+/// An edge of the graph; the edges that leave a condition are
+/// labelled `true` or `false`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub to: usize,
+    pub label: Option<&'static str>,
+}
+
+impl BasicBlock {
+    /// The first and last source lines of the statements.
+    pub fn lines(&self) -> Option<(usize, usize)> {
+        let first = self.stmts.iter().map(|stmt| stmt.1.line).min()?;
+        let last = self.stmts.iter().map(|stmt| stmt.1.line).max()?;
+        return Some((first, last));
+    }
+}
+
+/// Builds the graph of every function of a program.
+pub fn build_program(prog: &Program) -> Vec<Cfg> {
+    let mut cfgs = Vec::new();
+    for decl in &prog.decls {
+        if let TopLevelDecl::Func(ref f) = *decl {
+            cfgs.push(build(f));
+        }
+    }
+    return cfgs;
+}
+
+/// Builds the graph of a function.  Blocks that only pass the
+/// control to the next one are merged away, and the code that
+/// follows a `return`, `break` or `continue` starts a block
+/// without predecessors.
+pub fn build(f: &FuncDecl) -> Cfg {
+    let mut builder = Builder {
+        blocks: vec![BasicBlock::default(), BasicBlock::default()],
+        current: ENTRY,
+        targets: Vec::new(),
+    };
+    let first = builder.new_block();
+    builder.jump(first);
+    builder.stmts(&f.body.stmts);
+    builder.jump(EXIT);
+    let mut cfg = Cfg { name: f.name.name.clone(), blocks: builder.blocks };
+    simplify(&mut cfg);
+    return cfg;
+}
+
+struct Builder {
+    blocks: Vec<BasicBlock>,
+    current: usize,
+    /// The blocks that `break` and `continue` go to in the
+    /// enclosing loops and switch statements.
+    targets: Vec<(usize, Option<usize>)>,
+}
+
+impl Builder {
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(BasicBlock::default());
+        return self.blocks.len() - 1;
+    }
+
+    fn add(&mut self, text: String, loc: &Loc) {
+        let current = self.current;
+        self.blocks[current].stmts.push((text, loc.clone()));
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<&'static str>) {
+        self.blocks[from].succs.push(Edge { to: to, label: label });
+    }
+
+    /// Ends the current block with an edge to `to`, and continues
+    /// in it.
+    fn jump(&mut self, to: usize) {
+        let current = self.current;
+        self.edge(current, to, None);
+        self.current = to;
+    }
+
+    /// Ends the current block with a condition that goes to `t`
+    /// when it holds and to `e` otherwise.
+    fn branch(&mut self, t: usize, e: usize) {
+        let current = self.current;
+        self.edge(current, t, Some("true"));
+        self.edge(current, e, Some("false"));
+    }
+
+    /// Ends the current block with an edge to `to`; the code that
+    /// follows is not reachable from it.
+    fn leave(&mut self, to: usize) {
+        let current = self.current;
+        self.edge(current, to, None);
+        self.current = self.new_block();
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            // Each spec of a group is a statement of its own.
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    let stmt = Stmt { kind: StmtKind::Var(vec![spec.clone()]), loc: spec.loc.clone() };
+                    self.add(pretty_stmt(&stmt), &spec.loc);
+                }
+            }
+            StmtKind::Type(ref specs) => {
+                for spec in specs {
+                    let stmt = Stmt { kind: StmtKind::Type(vec![spec.clone()]), loc: spec.loc.clone() };
+                    self.add(pretty_stmt(&stmt), &spec.loc);
+                }
+            }
+            StmtKind::Return(_) => {
+                self.add(pretty_stmt(stmt), &stmt.loc);
+                self.leave(EXIT);
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                self.add(pretty_stmt(stmt), &stmt.loc);
+                // `continue` skips the enclosing switch statements.
+                let target = match stmt.kind {
+                    StmtKind::Break => self.targets.last().map(|&(exit, _)| exit),
+                    _ => self.targets.iter().rev().find_map(|&(_, next)| next),
+                };
+                let target = target.unwrap_or(EXIT);
+                self.leave(target);
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt, &stmt.loc),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+            _ => self.add(pretty_stmt(stmt), &stmt.loc),
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        if let Some(ref init) = if_stmt.init {
+            self.stmt(init);
+        }
+        self.add(format!("if {}", pretty_expr(&if_stmt.cond)), &if_stmt.cond.loc);
+        let then = self.new_block();
+        let end = self.new_block();
+        let els = match if_stmt.els {
+            Some(_) => self.new_block(),
+            None => end,
+        };
+        self.branch(then, els);
+        self.current = then;
+        self.stmts(&if_stmt.then.stmts);
+        self.jump(end);
+        if let Some(ref stmt) = if_stmt.els {
+            self.current = els;
+            self.stmt(stmt);
+            self.jump(end);
+        }
+        self.current = end;
+    }
+
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        if let Some(ref init) = for_stmt.init {
+            self.stmt(init);
+        }
+        let header = self.new_block();
+        let body = self.new_block();
+        let post = self.new_block();
+        let exit = self.new_block();
+        self.jump(header);
+        match for_stmt.cond {
+            Some(ref cond) => {
+                self.add(format!("for {}", pretty_expr(cond)), &cond.loc);
+                self.branch(body, exit);
+            }
+            None => self.edge(header, body, None),
+        }
+        self.current = body;
+        self.targets.push((exit, Some(post)));
+        self.stmts(&for_stmt.body.stmts);
+        self.targets.pop();
+        self.jump(post);
+        if let Some(ref stmt) = for_stmt.post {
+            self.stmt(stmt);
+        }
+        self.edge(post, header, None);
+        self.current = exit;
+    }
+
+    /// The `case` clauses are tested in order, and the default
+    /// clause is executed when none of them matches.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt, loc: &Loc) {
+        if let Some(ref init) = switch_stmt.init {
+            self.stmt(init);
+        }
+        let text = match switch_stmt.tag {
+            Some(ref tag) => format!("switch {}", pretty_expr(tag)),
+            None => "switch".to_string(),
+        };
+        self.add(text, switch_stmt.tag.as_ref().map_or(loc, |tag| &tag.loc));
+        let end = self.new_block();
+        let bodies: Vec<usize> = switch_stmt.clauses.iter().map(|_| self.new_block()).collect();
+        for (clause, &body) in switch_stmt.clauses.iter().zip(&bodies) {
+            if let Some(ref exprs) = clause.exprs {
+                let test = self.new_block();
+                self.jump(test);
+                let exprs: Vec<String> = exprs.iter().map(pretty_expr).collect();
+                self.add(format!("case {}", exprs.join(", ")), &clause.loc);
+                let next = self.new_block();
+                self.branch(body, next);
+                self.current = next;
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|clause| clause.exprs.is_none());
+        self.jump(default.map_or(end, |i| bodies[i]));
+        self.targets.push((end, None));
+        for (clause, &body) in switch_stmt.clauses.iter().zip(&bodies) {
+            self.current = body;
+            self.stmts(&clause.body);
+            self.jump(end);
+        }
+        self.targets.pop();
+        self.current = end;
+    }
+}
+
+
+/// Merges away the blocks without statements that only pass the
+/// control to another block, and removes the blocks without
+/// statements that are not reachable; the entry and exit blocks
+/// remain.
+fn simplify(cfg: &mut Cfg) {
+    let n = cfg.blocks.len();
+    // The edges to each block that passes the control on go to
+    // its successor instead; in a cycle of such blocks, the last
+    // one remains, and loops to itself.
+    let mut removed = vec![false; n];
+    for (b, removed) in removed.iter_mut().enumerate().skip(EXIT + 1) {
+        let to = match cfg.blocks[b].succs[..] {
+            [Edge { to, label: None }] if to != b && cfg.blocks[b].stmts.is_empty() => to,
+            _ => continue,
+        };
+        for block in &mut cfg.blocks {
+            for edge in &mut block.succs {
+                if edge.to == b {
+                    edge.to = to;
+                }
+            }
+        }
+        *removed = true;
+    }
+
+    let mut reachable = vec![false; n];
+    let mut work = vec![ENTRY];
+    while let Some(b) = work.pop() {
+        if !reachable[b] {
+            reachable[b] = true;
+            work.extend(cfg.blocks[b].succs.iter().map(|edge| edge.to));
+        }
+    }
+    let keep: Vec<bool> = (0 .. n).map(|b| {
+        b <= EXIT || (!removed[b] && (reachable[b] || !cfg.blocks[b].stmts.is_empty()))
+    }).collect();
+
+    // The blocks are numbered in the order of their statements in
+    // the source.
+    let mut order: Vec<usize> = (EXIT + 1 .. n).filter(|&b| keep[b]).collect();
+    order.sort_by_key(|&b| {
+        let start = cfg.blocks[b].stmts.first().map(|stmt| (stmt.1.line, stmt.1.col));
+        (start.unwrap_or((usize::MAX, 0)), b)
+    });
+    let mut numbers = vec![0; n];
+    for (i, &b) in [ENTRY, EXIT].iter().chain(&order).enumerate() {
+        numbers[b] = i;
+    }
+    let mut blocks: Vec<Option<BasicBlock>> = cfg.blocks.drain(..).map(Some).collect();
+    for &b in [ENTRY, EXIT].iter().chain(&order) {
+        let mut block = blocks[b].take().unwrap_or_default();
+        block.succs.retain(|edge| keep[edge.to]);
+        for edge in &mut block.succs {
+            edge.to = numbers[edge.to];
+        }
+        cfg.blocks.push(block);
+    }
+}
+
+
+// GRAPHVIZ
+
+/// Returns the graphs of the functions of a file in the DOT
+/// language of Graphviz, each function in a cluster of its own.
+/// Blocks are labelled with their number, the range of source
+/// lines of their statements and the statements themselves.
+pub fn dot(filename: &str, cfgs: &[Cfg]) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("digraph {} {{\n", quote(filename)));
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    for cfg in cfgs {
+        out.push_str(&format!("    subgraph {} {{\n", quote(&format!("cluster_{}", cfg.name))));
+        out.push_str(&format!("        label={};\n", quote(&cfg.name)));
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            let node = quote(&format!("{}.{}", cfg.name, block_name(b)));
+            if b <= EXIT {
+                out.push_str(&format!("        {} [label={}, shape=oval];\n", node, quote(&block_name(b))));
+                continue;
+            }
+            let mut label = block_name(b);
+            match block.lines() {
+                Some((first, last)) if first == last => label.push_str(&format!(" (line {})", first)),
+                Some((first, last)) => label.push_str(&format!(" (lines {}-{})", first, last)),
+                None => {}
+            }
+            label.push('\n');
+            for stmt in &block.stmts {
+                label.push_str(&stmt.0);
+                label.push('\n');
+            }
+            out.push_str(&format!("        {} [label={}];\n", node, quote(&label).replace("\\n", "\\l")));
+        }
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            for edge in &block.succs {
+                out.push_str(&format!("        {} -> {}", quote(&format!("{}.{}", cfg.name, block_name(b))),
+                                      quote(&format!("{}.{}", cfg.name, block_name(edge.to)))));
+                if let Some(label) = edge.label {
+                    out.push_str(&format!(" [label={}]", quote(label)));
+                }
+                out.push_str(";\n");
+            }
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    return out;
+}
+
+fn block_name(b: usize) -> String {
+    match b {
+        ENTRY => return "entry".to_string(),
+        EXIT => return "exit".to_string(),
+        _ => return format!("b{}", b - EXIT),
+    }
+}
+
+/// Quotes a DOT identifier; newlines become `\n`.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
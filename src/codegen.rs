@@ -0,0 +1,973 @@
+use std::mem;
+
+use ast::*;
+use constant::{self, Value};
+use loc::Loc;
+use lower::{self, one};
+use symtab::{SymbolKind, SymbolTable};
+use types::*;
+
+/// The runtime support, which is copied at the beginning of
+/// every generated file so that it can be compiled on its own.
+const RUNTIME: &str = include_str!("runtime/gore_rt.h");
+
+/// Translates a type-checked program to a C99 program.
+///
+/// GoLite values map directly to C values: `int` and `rune` are
+/// `int64_t` and `int32_t`, strings and slices are small structs
+/// defined by the runtime, and arrays and structs are C structs,
+/// so that they are copied on assignment.  Every variable gets
+/// a unique name made of its name and its symbol id, so that C
+/// scoping never matters.
+///
+/// C leaves the order of evaluation of operands unspecified, while
+/// Go evaluates function calls from left to right: every call is
+/// made in a statement of its own, which stores its result in a
+/// temporary, so that the C expressions that remain are free of
+/// side effects.
+pub fn generate(prog: &Program, types: &TypeTable, table: &SymbolTable) -> String {
+    let mut gen = Generator {
+        types: types,
+        table: table,
+        buf: String::new(),
+        indent: 0,
+        temps: 0,
+        labels: 0,
+        targets: Vec::new(),
+    };
+    gen.write(RUNTIME);
+    gen.type_defs();
+    gen.program(prog);
+    return gen.buf;
+}
+
+/// A statement that `break` or `continue` may leave.
+struct Target {
+    label: usize,
+    is_loop: bool,
+    /// Whether its `goto` label is used.
+    used: bool,
+}
+
+struct Generator<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    buf: String,
+    indent: usize,
+    /// Counters for the names of temporaries and labels; the
+    /// temporaries are `tmpN`, since the types are `tN`.
+    temps: usize,
+    labels: usize,
+    /// The loops and switches that enclose the current
+    /// statement, from the outermost one.
+    targets: Vec<Target>,
+}
+
+impl<'a> Generator<'a> {
+    fn write(&mut self, s: &str) {
+        self.buf.push_str(s);
+    }
+
+    /// Writes a line at the current indentation.
+    fn line(&mut self, s: &str) {
+        for _ in 0 .. self.indent {
+            self.buf.push_str("    ");
+        }
+        self.buf.push_str(s);
+        self.buf.push('\n');
+    }
+
+    /// Runs `f` with an empty buffer, one level of indentation
+    /// deeper, and returns the lines it wrote and its result.
+    fn capture<T, F>(&mut self, f: F) -> (String, T)
+        where F: FnOnce(&mut Generator<'a>) -> T
+    {
+        let saved = mem::take(&mut self.buf);
+        self.indent += 1;
+        let result = f(self);
+        self.indent -= 1;
+        let captured = mem::replace(&mut self.buf, saved);
+        return (captured, result);
+    }
+
+    /// Declares a temporary of type `ty` holding `value`.
+    fn temp(&mut self, ty: TypeId, value: &str) -> String {
+        self.temps += 1;
+        let name = format!("tmp{}", self.temps);
+        let decl = format!("{} {} = {};", self.ctype(ty), name, value);
+        self.line(&decl);
+        return name;
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels += 1;
+        return self.labels;
+    }
+
+
+    // TYPES
+
+    /// Returns the C type that represents a GoLite type.
+    fn ctype(&self, ty: TypeId) -> String {
+        let ty = self.types.default_type(ty);
+        match *self.types.under(ty) {
+            TypeKind::Int => "int64_t".to_string(),
+            TypeKind::Float64 => "double".to_string(),
+            TypeKind::Bool => "bool".to_string(),
+            TypeKind::Rune => "int32_t".to_string(),
+            TypeKind::String => "gore_string".to_string(),
+            TypeKind::Slice(_) => "gore_slice".to_string(),
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => format!("t{}", self.types.underlying(ty)),
+            _ => "void".to_string(),
+        }
+    }
+
+    /// Returns the value of type `ty` whose bytes are all zero,
+    /// which is the zero value of every GoLite type.
+    fn zero(&self, ty: TypeId) -> String {
+        if self.is_scalar(ty) {
+            return "0".to_string();
+        }
+        return format!("({}){{0}}", self.ctype(ty));
+    }
+
+    fn is_scalar(&self, ty: TypeId) -> bool {
+        let ty = self.types.default_type(ty);
+        return self.types.is_numeric(ty) || self.types.is_bool(ty);
+    }
+
+    /// Defines a C struct for every array and struct type, and
+    /// an equality function for those that are comparable.
+    /// Element and field types are defined first; slices do not
+    /// need the definition of their element type.
+    fn type_defs(&mut self) {
+        let mut defined = vec![false; self.types.len()];
+        for ty in 0 .. self.types.len() {
+            self.type_def(ty, &mut defined);
+        }
+    }
+
+    fn type_def(&mut self, ty: TypeId, defined: &mut Vec<bool>) {
+        let ty = self.types.underlying(ty);
+        if defined[ty] {
+            return;
+        }
+        defined[ty] = true;
+        let name = format!("t{}", ty);
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                self.type_def(elem, defined);
+                self.write("\n");
+                self.line("typedef struct {");
+                let decl = format!("    {} a[{}];", self.ctype(elem), len.max(1));
+                self.line(&decl);
+                self.line(&format!("}} {};", name));
+                if self.types.is_comparable(ty) {
+                    self.write("\n");
+                    self.line(&format!("static inline bool eq_{}({} a, {} b)", name, name, name));
+                    self.line("{");
+                    self.line("    int64_t i;");
+                    self.line(&format!("    for (i = 0; i < {}; i++)", len));
+                    let eq = self.equal(elem, "a.a[i]", "b.a[i]");
+                    self.line(&format!("        if (!{})", eq));
+                    self.line("            return false;");
+                    self.line("    return true;");
+                    self.line("}");
+                }
+            }
+            TypeKind::Struct(fields) => {
+                for &(_, field_ty) in &fields {
+                    self.type_def(field_ty, defined);
+                }
+                self.write("\n");
+                self.line("typedef struct {");
+                for (i, &(ref field, field_ty)) in fields.iter().enumerate() {
+                    let decl = format!("    {} {};", self.ctype(field_ty), field_name(field, i));
+                    self.line(&decl);
+                }
+                // C does not allow empty structs.
+                if fields.is_empty() {
+                    self.line("    char unused;");
+                }
+                self.line(&format!("}} {};", name));
+                if self.types.is_comparable(ty) {
+                    // Blank fields are not compared.
+                    let eqs: Vec<String> = fields.iter().enumerate()
+                        .filter(|&(_, (field, _))| field != "_")
+                        .map(|(i, &(ref field, field_ty))| {
+                            let field = field_name(field, i);
+                            self.equal(field_ty, &format!("a.{}", field), &format!("b.{}", field))
+                        })
+                        .collect();
+                    self.write("\n");
+                    self.line(&format!("static inline bool eq_{}({} a, {} b)", name, name, name));
+                    self.line("{");
+                    if eqs.is_empty() {
+                        self.line("    (void)a;");
+                        self.line("    (void)b;");
+                        self.line("    return true;");
+                    } else {
+                        self.line(&format!("    return {};", eqs.join("\n        && ")));
+                    }
+                    self.line("}");
+                }
+            }
+            _ => {}
+        }
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        self.write("\n");
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        let decl = format!("static {} {};", self.ctype(self.var_type(id)), self.var_name(id));
+                        self.line(&decl);
+                    }
+                }
+            }
+        }
+
+        self.write("\n");
+        for f in &funcs {
+            let decl = format!("{};", self.signature(f));
+            self.line(&decl);
+        }
+
+        // Package variables are initialized in the order of
+        // their declarations.
+        self.write("\n");
+        self.line("static void gore_init(void)");
+        self.line("{");
+        self.indent += 1;
+        self.temps = 0;
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec, false);
+                }
+            }
+        }
+        self.indent -= 1;
+        self.line("}");
+
+        for f in &funcs {
+            self.func_decl(f);
+        }
+
+        self.write("\n");
+        self.line("int main(void)");
+        self.line("{");
+        self.line("    gore_init();");
+        if funcs.iter().any(|f| f.name.name == "main") {
+            self.line("    fn_main();");
+        }
+        self.line("    return 0;");
+        self.line("}");
+    }
+
+    fn signature(&self, f: &FuncDecl) -> String {
+        let result = lower::result_type(f, self.types, self.table);
+        let params: Vec<String> = f.params.iter().enumerate()
+            .map(|(i, p)| {
+                let name = if p.name.is_blank() { format!("unused{}", i) } else { self.var_name(&p.name) };
+                format!("{} {}", self.ctype(self.var_type(&p.name)), name)
+            })
+            .collect();
+        let params = if params.is_empty() { "void".to_string() } else { params.join(", ") };
+        return format!("static {} fn_{}({})", self.ctype(result), f.name.name, params);
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.temps = 0;
+        self.labels = 0;
+        self.write("\n");
+        let signature = self.signature(f);
+        self.line(&signature);
+        self.line("{");
+        self.indent += 1;
+        for (i, p) in f.params.iter().enumerate() {
+            if p.name.is_blank() {
+                self.line(&format!("(void)unused{};", i));
+            }
+        }
+        self.stmts(&f.body.stmts);
+        // Keeps the C compiler from warning about a missing
+        // return; the type checker makes sure that it is not
+        // reached.
+        let result = lower::result_type(f, self.types, self.table);
+        if result != VOID {
+            let ends_with_return = match f.body.stmts.last() {
+                Some(&Stmt { kind: StmtKind::Return(_), .. }) => true,
+                _ => false,
+            };
+            if !ends_with_return {
+                let ret = format!("return {};", self.zero(result));
+                self.line(&ret);
+            }
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    fn var_name(&self, id: &Ident) -> String {
+        return format!("{}_{}", id.name, id.sym.unwrap_or(0));
+    }
+
+    /// Declares (or, for package variables, initializes) the
+    /// variables of a `var` spec.
+    fn var_spec(&mut self, spec: &VarSpec, local: bool) {
+        if spec.values.is_empty() {
+            if local {
+                for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                    let init = if self.is_scalar(self.var_type(id)) { "0" } else { "{0}" };
+                    self.declare(id, init);
+                }
+            }
+            return;
+        }
+        let values = self.values(&spec.values);
+        for (id, value) in spec.names.iter().zip(values) {
+            if id.is_blank() {
+                self.line(&format!("(void){};", value));
+            } else if local {
+                self.declare(id, &value);
+            } else {
+                let assign = format!("{} = {};", self.var_name(id), value);
+                self.line(&assign);
+            }
+        }
+    }
+
+    /// Declares a local variable.  The elements and fields of an
+    /// array or struct may be assigned without the variable ever
+    /// being read, which the C compiler would warn about.
+    fn declare(&mut self, id: &Ident, value: &str) {
+        let ty = self.var_type(id);
+        let name = self.var_name(id);
+        self.line(&format!("{} {} = {};", self.ctype(ty), name, value));
+        match *self.types.under(ty) {
+            TypeKind::Array(..) | TypeKind::Struct(..) => self.line(&format!("(void){};", name)),
+            _ => {}
+        }
+    }
+
+    /// Evaluates the right-hand sides of an assignment, in
+    /// temporaries if there are several of them, since all of
+    /// them are evaluated before anything is assigned.
+    fn values(&mut self, exprs: &[Expr]) -> Vec<String> {
+        let values: Vec<String> = exprs.iter().map(|e| self.expr(e)).collect();
+        if exprs.len() == 1 {
+            return values;
+        }
+        return exprs.iter().zip(values)
+            .map(|(e, value)| {
+                if e.value.is_some() {
+                    value
+                } else {
+                    self.temp(e.ty.unwrap_or(INVALID), &value)
+                }
+            })
+            .collect();
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.line("{");
+        self.indent += 1;
+        self.stmts(&block.stmts);
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.block(block),
+            StmtKind::Expr(ref e) => {
+                if let ExprKind::Call(ref f, ref args) = e.kind {
+                    let call = self.func_call(f, args);
+                    self.line(&format!("{};", call));
+                }
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec, true);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs),
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, &stmt.loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), &stmt.loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), &stmt.loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                let values = self.values(values);
+                for (id, value) in names.iter().zip(values) {
+                    if id.is_blank() {
+                        self.line(&format!("(void){};", value));
+                    } else if self.is_new_var(id) {
+                        self.declare(id, &value);
+                    } else {
+                        let assign = format!("{} = {};", self.var_name(id), value);
+                        self.line(&assign);
+                    }
+                }
+            }
+            StmtKind::Print(ref args) => self.print(args, false),
+            StmtKind::Println(ref args) => self.print(args, true),
+            StmtKind::Return(None) => self.line("return;"),
+            StmtKind::Return(Some(ref e)) => {
+                let value = self.expr(e);
+                self.line(&format!("return {};", value));
+            }
+            StmtKind::Break => {
+                let stmt = match self.targets.last_mut() {
+                    Some(ref mut target) if !target.is_loop => {
+                        target.used = true;
+                        format!("goto end{};", target.label)
+                    }
+                    _ => "break;".to_string(),
+                };
+                self.line(&stmt);
+            }
+            StmtKind::Continue => {
+                let label = match self.targets.iter_mut().rev().find(|t| t.is_loop) {
+                    Some(target) => {
+                        target.used = true;
+                        target.label
+                    }
+                    None => 0,
+                };
+                self.line(&format!("goto continue{};", label));
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+        }
+    }
+
+    /// Returns whether a name of a `:=` statement declares a
+    /// variable, rather than assigning to an existing one.
+    fn is_new_var(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => self.table.symbol(sym).loc.as_ref() == Some(&id.loc),
+            None => false,
+        }
+    }
+
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr]) {
+        if lhs.len() == 1 {
+            if let ExprKind::Blank = lhs[0].kind {
+                let value = self.expr(&rhs[0]);
+                self.line(&format!("(void){};", value));
+                return;
+            }
+            let target = self.expr(&lhs[0]);
+            let value = self.expr(&rhs[0]);
+            self.line(&format!("{} = {};", target, value));
+            return;
+        }
+        // The operands of the indexing expressions on the left
+        // are evaluated before the values, and the addresses of
+        // the elements are kept in temporaries.
+        let mut targets = Vec::new();
+        for e in lhs {
+            let target = match e.kind {
+                ExprKind::Blank => None,
+                ExprKind::Id(ref id) => Some(self.var_name(id)),
+                _ => {
+                    let target = self.expr(e);
+                    self.temps += 1;
+                    let ptr = format!("tmp{}", self.temps);
+                    let decl = format!("{} *{} = &{};", self.ctype(e.ty.unwrap_or(INVALID)), ptr, target);
+                    self.line(&decl);
+                    Some(format!("*{}", ptr))
+                }
+            };
+            targets.push(target);
+        }
+        let values = self.values(rhs);
+        for (target, value) in targets.into_iter().zip(values) {
+            match target {
+                Some(target) => self.line(&format!("{} = {};", target, value)),
+                None => self.line(&format!("(void){};", value)),
+            }
+        }
+    }
+
+    /// `x op= y`: unless `x` is a variable, a pointer to it is kept
+    /// in a temporary for reading and writing it.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let ty = lhs.ty.unwrap_or(INVALID);
+        let target = match lhs.kind {
+            ExprKind::Id(ref id) => self.var_name(id),
+            _ => {
+                let target = self.expr(lhs);
+                self.temps += 1;
+                let ptr = format!("tmp{}", self.temps);
+                self.line(&format!("{} *{} = &{};", self.ctype(ty), ptr, target));
+                format!("*{}", ptr)
+            }
+        };
+        let value = self.expr(rhs);
+        let result = self.arith(op, ty, &target, &value, loc);
+        self.line(&format!("{} = {};", target, result));
+    }
+
+    /// Stores the arguments in temporaries, when there are
+    /// several, before printing them one by one.
+    fn print(&mut self, args: &[Expr], newline: bool) {
+        let mut values = Vec::new();
+        for arg in args {
+            let value = self.expr(arg);
+            if args.len() > 1 && arg.value.is_none() && !is_simple(&value) {
+                values.push(self.temp(arg.ty.unwrap_or(INVALID), &value));
+            } else {
+                values.push(value);
+            }
+        }
+        for (i, (arg, value)) in args.iter().zip(values).enumerate() {
+            if newline && i > 0 {
+                self.line("putchar(' ');");
+            }
+            let ty = self.types.default_type(arg.ty.unwrap_or(INVALID));
+            let stmt = match *self.types.under(ty) {
+                TypeKind::Float64 => format!("gore_print_float({});", value),
+                TypeKind::Bool => format!("gore_print_bool({});", value),
+                TypeKind::String => format!("gore_print_string({});", value),
+                _ => format!("gore_print_int({});", value),
+            };
+            self.line(&stmt);
+        }
+        if newline {
+            self.line("putchar('\\n');");
+        }
+    }
+
+    /// Generates a simple statement in a block of its own, so that
+    /// the variables it declares are local to the statement that
+    /// it introduces; returns whether a block was opened.
+    fn init(&mut self, init: &Option<Box<Stmt>>) -> bool {
+        match *init {
+            Some(ref init) => {
+                self.line("{");
+                self.indent += 1;
+                self.stmt(init);
+                return true;
+            }
+            None => return false,
+        }
+    }
+
+    fn close(&mut self, opened: bool) {
+        if opened {
+            self.indent -= 1;
+            self.line("}");
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        let opened = self.init(&if_stmt.init);
+        let cond = self.expr(&if_stmt.cond);
+        self.line(&format!("if ({}) {{", cond));
+        self.indent += 1;
+        self.stmts(&if_stmt.then.stmts);
+        self.indent -= 1;
+        match if_stmt.els {
+            Some(ref els) => {
+                self.line("} else {");
+                self.indent += 1;
+                match els.kind {
+                    StmtKind::Block(ref block) => self.stmts(&block.stmts),
+                    _ => self.stmt(els),
+                }
+                self.indent -= 1;
+                self.line("}");
+            }
+            None => self.line("}"),
+        }
+        self.close(opened);
+    }
+
+    /// The tag and the case expressions are evaluated in order
+    /// until a case matches, which selects the clause to execute.
+    /// A case expression may involve calls, which may only be
+    /// made if no previous case matched.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) {
+        let opened = self.init(&switch_stmt.init);
+        let tag = match switch_stmt.tag {
+            Some(ref tag) => {
+                let value = self.expr(tag);
+                Some((self.temp(tag.ty.unwrap_or(INVALID), &value), tag.ty.unwrap_or(INVALID)))
+            }
+            None => None,
+        };
+        if switch_stmt.clauses.is_empty() {
+            if let Some((ref tag, _)) = tag {
+                self.line(&format!("(void){};", tag));
+            }
+            self.close(opened);
+            return;
+        }
+        let label = self.new_label();
+        let clause = self.temp(INT, "-1");
+        for (i, case) in switch_stmt.clauses.iter().enumerate() {
+            let exprs = match case.exprs {
+                Some(ref exprs) => exprs,
+                None => continue,
+            };
+            for e in exprs {
+                let (pre, value) = self.capture(|g| g.expr(e));
+                let cond = match tag {
+                    Some((ref tag, ty)) => self.equal(ty, tag, &value),
+                    None => value,
+                };
+                if pre.is_empty() {
+                    self.line(&format!("if ({} < 0 && {})", clause, cond));
+                    self.line(&format!("    {} = {};", clause, i));
+                } else {
+                    self.line(&format!("if ({} < 0) {{", clause));
+                    self.write(&pre);
+                    self.line(&format!("    if ({})", cond));
+                    self.line(&format!("        {} = {};", clause, i));
+                    self.line("}");
+                }
+            }
+        }
+        if let Some(i) = switch_stmt.clauses.iter().position(|c| c.exprs.is_none()) {
+            self.line(&format!("if ({} < 0)", clause));
+            self.line(&format!("    {} = {};", clause, i));
+        }
+
+        self.targets.push(Target { label: label, is_loop: false, used: false });
+        for (i, case) in switch_stmt.clauses.iter().enumerate() {
+            let keyword = if i == 0 { "if" } else { "} else if" };
+            self.line(&format!("{} ({} == {}) {{", keyword, clause, i));
+            self.indent += 1;
+            self.stmts(&case.body);
+            self.indent -= 1;
+        }
+        self.line("}");
+        let target = self.targets.pop();
+        if target.is_some_and(|t| t.used) {
+            self.line(&format!("end{}: ;", label));
+        }
+        self.close(opened);
+    }
+
+    /// All loops become infinite C loops that test their condition
+    /// first, since evaluating it may take several statements.
+    /// `continue` jumps to the post statement at the end.
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        let opened = self.init(&for_stmt.init);
+        let label = self.new_label();
+        self.line("for (;;) {");
+        self.indent += 1;
+        if let Some(ref cond) = for_stmt.cond {
+            let cond = self.expr(cond);
+            self.line(&format!("if (!{})", cond));
+            self.line("    break;");
+        }
+        self.targets.push(Target { label: label, is_loop: true, used: false });
+        self.stmts(&for_stmt.body.stmts);
+        let target = self.targets.pop();
+        if target.is_some_and(|t| t.used) {
+            self.line(&format!("continue{}: ;", label));
+        }
+        if let Some(ref post) = for_stmt.post {
+            self.stmt(post);
+        }
+        self.indent -= 1;
+        self.line("}");
+        self.close(opened);
+    }
+
+
+    // EXPRESSIONS
+
+    /// Returns a C expression without side effects that has the
+    /// value of `e`, after generating the statements that make
+    /// the calls of `e`.
+    fn expr(&mut self, e: &Expr) -> String {
+        let ty = e.ty.unwrap_or(INVALID);
+        if let Some(ref value) = e.value {
+            return self.constant(value, ty);
+        }
+        match e.kind {
+            ExprKind::Id(ref id) => return self.var_name(id),
+            ExprKind::Unary(op, ref x) => {
+                let x = self.expr(x);
+                return self.unary(op, ty, &x);
+            }
+            ExprKind::Binary(BinOp::And, ref x, ref y) => return self.logical(true, x, y),
+            ExprKind::Binary(BinOp::Or, ref x, ref y) => return self.logical(false, x, y),
+            ExprKind::Binary(op, ref x, ref y) => {
+                let operand_ty = x.ty.unwrap_or(INVALID);
+                let x = self.expr(x);
+                let y = self.expr(y);
+                if op.is_comparison() {
+                    return self.compare(op, operand_ty, &x, &y);
+                }
+                return self.arith(op, operand_ty, &x, &y, &e.loc);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                match kind {
+                    SymbolKind::Type => return self.conversion(ty, &args[0]),
+                    SymbolKind::Builtin => return self.builtin(f, &args[0]),
+                    _ => {
+                        let call = self.func_call(f, args);
+                        return self.temp(ty, &call);
+                    }
+                }
+            }
+            ExprKind::Index(ref a, ref i) => {
+                let a_ty = a.ty.unwrap_or(INVALID);
+                let mut a = self.expr(a);
+                let i = self.expr(i);
+                let loc = c_string(&e.loc.to_string());
+                match *self.types.under(a_ty) {
+                    TypeKind::Array(len, _) => return format!("{}.a[gore_index({}, {}, {})]", a, i, len, loc),
+                    _ => {
+                        // The slice is used twice.
+                        if !is_simple(&a) {
+                            a = self.temp(a_ty, &a);
+                        }
+                        return format!("(({} *){}.data)[gore_index({}, {}.len, {})]",
+                                       self.ctype(ty), a, i, a, loc);
+                    }
+                }
+            }
+            ExprKind::Selector(ref x, ref field) => {
+                let index = match *self.types.under(x.ty.unwrap_or(INVALID)) {
+                    TypeKind::Struct(ref fields) => fields.iter().position(|f| f.0 == field.name),
+                    _ => None,
+                };
+                let x = self.expr(x);
+                return format!("{}.{}", x, field_name(&field.name, index.unwrap_or(0)));
+            }
+            ExprKind::Append(ref s, ref x) => {
+                let s = self.expr(s);
+                let elem = match *self.types.under(ty) {
+                    TypeKind::Slice(elem) => elem,
+                    _ => INVALID,
+                };
+                let x = self.expr(x);
+                let x = self.temp(elem, &x);
+                return format!("gore_append({}, &{}, sizeof({}))", s, x, self.ctype(elem));
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => return "0".to_string(),
+        }
+    }
+
+    fn constant(&self, value: &Value, ty: TypeId) -> String {
+        let ty = self.types.default_type(ty);
+        match *value {
+            Value::Bool(b) => return b.to_string(),
+            Value::String(ref s) => {
+                return format!("GORE_STR({}, {})", c_string(s), s.chars().count());
+            }
+            Value::Int(_) | Value::Float(_) if self.types.is_float(ty) => {
+                let f = value.to_rat().map_or(0.0, |r| r.to_f64());
+                let lit = format!("{:?}", f);
+                if f < 0.0 {
+                    return format!("({})", lit);
+                }
+                return lit;
+            }
+            Value::Int(_) | Value::Float(_) => {
+                let n = value.to_int().and_then(|n| n.to_i64()).unwrap_or(0);
+                let lit = if n == i64::MIN {
+                    "INT64_MIN".to_string()
+                } else if n < 0 {
+                    format!("(-INT64_C({}))", -n)
+                } else {
+                    format!("INT64_C({})", n)
+                };
+                if let TypeKind::Rune = *self.types.under(ty) {
+                    return format!("((int32_t){})", lit);
+                }
+                return lit;
+            }
+        }
+    }
+
+    fn unary(&self, op: UnOp, ty: TypeId, x: &str) -> String {
+        let kind = self.types.under(self.types.default_type(ty));
+        match (op, kind) {
+            (UnOp::Plus, _) => return x.to_string(),
+            (UnOp::Neg, &TypeKind::Int) => return format!("GORE_NEG64({})", x),
+            (UnOp::Neg, &TypeKind::Rune) => return format!("GORE_NEG32({})", x),
+            (UnOp::Neg, _) => return format!("(-{})", x),
+            (UnOp::Not, _) => return format!("(!{})", x),
+            (UnOp::BitNot, &TypeKind::Rune) => return format!("((int32_t)~{})", x),
+            (UnOp::BitNot, _) => return format!("(~{})", x),
+        }
+    }
+
+    /// `&&` and `||` only evaluate their right operand if
+    /// needed, which includes making its calls.
+    fn logical(&mut self, and: bool, x: &Expr, y: &Expr) -> String {
+        let x = self.expr(x);
+        let (pre, y) = self.capture(|g| g.expr(y));
+        if pre.is_empty() {
+            return format!("({} {} {})", x, if and { "&&" } else { "||" }, y);
+        }
+        let result = self.temp(BOOL, &x);
+        self.line(&format!("if ({}{}) {{", if and { "" } else { "!" }, result));
+        self.write(&pre);
+        self.line(&format!("    {} = {};", result, y));
+        self.line("}");
+        return result;
+    }
+
+    /// Returns the result of an arithmetic or bitwise operator on
+    /// operands of type `ty`.  Integer operations wrap around and
+    /// division by zero panics, as in Go.
+    fn arith(&self, op: BinOp, ty: TypeId, x: &str, y: &str, loc: &Loc) -> String {
+        let ty = self.types.default_type(ty);
+        let bits = match *self.types.under(ty) {
+            TypeKind::Int => 64,
+            TypeKind::Rune => 32,
+            TypeKind::String => return format!("gore_concat({}, {})", x, y),
+            _ => return format!("({} {} {})", x, op.token(), y),
+        };
+        let loc = c_string(&loc.to_string());
+        match op {
+            BinOp::Add => return format!("GORE_ADD{}({}, {})", bits, x, y),
+            BinOp::Sub => return format!("GORE_SUB{}({}, {})", bits, x, y),
+            BinOp::Mul => return format!("GORE_MUL{}({}, {})", bits, x, y),
+            BinOp::Div => return format!("gore_div{}({}, {}, {})", bits, x, y, loc),
+            BinOp::Rem => return format!("gore_rem{}({}, {}, {})", bits, x, y, loc),
+            BinOp::Shl => return format!("gore_shl{}({}, {}, {})", bits, x, y, loc),
+            BinOp::Shr => return format!("gore_shr{}({}, {}, {})", bits, x, y, loc),
+            BinOp::BitClear => return format!("({} & ~{})", x, y),
+            _ => return format!("({} {} {})", x, op.token(), y),
+        }
+    }
+
+    fn compare(&self, op: BinOp, ty: TypeId, x: &str, y: &str) -> String {
+        match op {
+            BinOp::Eq => return self.equal(ty, x, y),
+            BinOp::Ne => return format!("(!{})", self.equal(ty, x, y)),
+            _ => {}
+        }
+        if self.types.is_string(ty) {
+            return format!("(gore_string_cmp({}, {}) {} 0)", x, y, op.token());
+        }
+        return format!("({} {} {})", x, op.token(), y);
+    }
+
+    fn equal(&self, ty: TypeId, x: &str, y: &str) -> String {
+        let ty = self.types.default_type(ty);
+        match *self.types.under(ty) {
+            TypeKind::String => return format!("gore_string_eq({}, {})", x, y),
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => {
+                return format!("eq_{}({}, {})", self.ctype(ty), x, y);
+            }
+            _ => return format!("({} == {})", x, y),
+        }
+    }
+
+    /// Returns the call of a function, after evaluating its
+    /// arguments.
+    fn func_call(&mut self, f: &Expr, args: &[Expr]) -> String {
+        let name = match f.kind {
+            ExprKind::Id(ref id) => id.name.clone(),
+            _ => String::new(),
+        };
+        let args: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+        return format!("fn_{}({})", name, args.join(", "));
+    }
+
+    fn conversion(&mut self, ty: TypeId, arg: &Expr) -> String {
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        let x = self.expr(arg);
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            return format!("gore_string_from_rune({})", x);
+        }
+        if self.types.is_float(arg_ty) && !self.types.is_float(ty) {
+            return format!("(({})gore_float_to_int({}))", self.ctype(ty), x);
+        }
+        if self.types.is_numeric(ty) {
+            return format!("(({}){})", self.ctype(ty), x);
+        }
+        return x;
+    }
+
+    fn builtin(&mut self, f: &Expr, arg: &Expr) -> String {
+        let cap = match f.kind {
+            ExprKind::Id(ref id) => id.name == "cap",
+            _ => false,
+        };
+        let ty = arg.ty.unwrap_or(INVALID);
+        let x = self.expr(arg);
+        if let TypeKind::Array(len, _) = *self.types.under(ty) {
+            return format!("INT64_C({})", len);
+        }
+        return format!("{}.{}", x, if cap { "cap" } else { "len" });
+    }
+}
+
+/// Returns the C name of a struct field; blank fields are
+/// numbered since a struct may have several of them.
+fn field_name(name: &str, index: usize) -> String {
+    if name == "_" {
+        return format!("blank{}", index);
+    }
+    return format!("f_{}", name);
+}
+
+fn is_simple(expr: &str) -> bool {
+    return expr.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+}
+
+/// Returns a C string literal with the bytes of a string stored
+/// one `char` per byte.  Octal escapes are used for anything but
+/// printable ASCII, and `?` is escaped to avoid trigraphs.
+fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for b in constant::string_bytes(s) {
+        match b {
+            b'"' | b'\\' | b'?' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20 ..= 0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push('"');
+    return out;
+}
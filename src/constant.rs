@@ -0,0 +1,684 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use ast::{BinOp, UnOp};
+
+/// An arbitrary-precision integer, stored as a sign and a
+/// little-endian magnitude of 32-bit limbs without leading
+/// zeros (zero has no limbs and is never negative).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    neg: bool,
+    mag: Vec<u32>,
+}
+
+/// An exact rational number, kept in lowest terms with
+/// a positive denominator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rat {
+    num: BigInt,
+    den: BigInt,
+}
+
+/// The value of a constant expression.  Integer constants
+/// (including runes) are `Int`s and floating-point constants
+/// are `Float`s, whether they are typed or not.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// The bytes of a string, one `char` per byte, as the
+    /// scanner stores the contents of string literals.
+    String(String),
+    Int(BigInt),
+    Float(Rat),
+}
+
+
+// MAGNITUDES
+
+fn trim(mut v: Vec<u32>) -> Vec<u32> {
+    while v.last() == Some(&0) {
+        v.pop();
+    }
+    return v;
+}
+
+fn mag_cmp(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for i in (0 .. a.len()).rev() {
+        if a[i] != b[i] {
+            return a[i].cmp(&b[i]);
+        }
+    }
+    return Ordering::Equal;
+}
+
+fn mag_add(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0 .. a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push(sum as u32);
+        carry = sum >> 32;
+    }
+    out.push(carry as u32);
+    return trim(out);
+}
+
+/// Computes `a - b`, where `a >= b`.
+fn mag_sub(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &x) in a.iter().enumerate() {
+        let mut diff = x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        out.push(diff as u32);
+    }
+    return trim(out);
+}
+
+fn mag_mul(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = x as u64 * y as u64 + out[i + j] as u64 + carry;
+            out[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        out[i + b.len()] = carry as u32;
+    }
+    return trim(out);
+}
+
+fn mag_shl(a: &[u32], n: usize) -> Vec<u32> {
+    if a.is_empty() {
+        return Vec::new();
+    }
+    let (limbs, bits) = (n / 32, n % 32);
+    let mut out = vec![0u32; limbs];
+    let mut carry = 0u32;
+    for &x in a {
+        if bits == 0 {
+            out.push(x);
+        } else {
+            out.push((x << bits) | carry);
+            carry = x >> (32 - bits);
+        }
+    }
+    out.push(carry);
+    return trim(out);
+}
+
+fn mag_shr(a: &[u32], n: usize) -> Vec<u32> {
+    let (limbs, bits) = (n / 32, n % 32);
+    if limbs >= a.len() {
+        return Vec::new();
+    }
+    let mut out = Vec::with_capacity(a.len() - limbs);
+    for i in limbs .. a.len() {
+        let lo = a[i] >> bits;
+        let hi = if bits == 0 { 0 } else { a.get(i + 1).map_or(0, |&x| x << (32 - bits)) };
+        out.push(lo | hi);
+    }
+    return trim(out);
+}
+
+fn mag_bit_len(a: &[u32]) -> usize {
+    match a.last() {
+        Some(&top) => a.len() * 32 - top.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// Divides `a` by a single limb.
+fn mag_divmod_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut out = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0 .. a.len()).rev() {
+        let cur = (rem << 32) | a[i] as u64;
+        out[i] = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    return (trim(out), rem as u32);
+}
+
+/// Long division, one bit at a time; constants are small
+/// enough that this is not worth optimizing.
+fn mag_divmod(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if mag_cmp(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (q, r) = mag_divmod_small(a, b[0]);
+        return (q, trim(vec![r]));
+    }
+    let bits = mag_bit_len(a);
+    let mut q = vec![0u32; a.len()];
+    let mut r: Vec<u32> = Vec::new();
+    for i in (0 .. bits).rev() {
+        r = mag_shl(&r, 1);
+        if (a[i / 32] >> (i % 32)) & 1 == 1 {
+            if r.is_empty() {
+                r.push(1);
+            } else {
+                r[0] |= 1;
+            }
+        }
+        if mag_cmp(&r, b) != Ordering::Less {
+            r = mag_sub(&r, b);
+            q[i / 32] |= 1 << (i % 32);
+        }
+    }
+    return (trim(q), r);
+}
+
+
+// INTEGERS
+
+impl BigInt {
+    fn new(neg: bool, mag: Vec<u32>) -> BigInt {
+        let mag = trim(mag);
+        BigInt { neg: neg && !mag.is_empty(), mag: mag }
+    }
+
+    pub fn zero() -> BigInt {
+        BigInt { neg: false, mag: Vec::new() }
+    }
+
+    pub fn from_u64(n: u64) -> BigInt {
+        BigInt::new(false, vec![n as u32, (n >> 32) as u32])
+    }
+
+    pub fn from_i64(n: i64) -> BigInt {
+        let mut b = BigInt::from_u64(n.unsigned_abs());
+        b.neg = n < 0;
+        return b;
+    }
+
+    /// Parses digits in the given radix; no sign or prefix.
+    pub fn parse(digits: &str, radix: u32) -> Option<BigInt> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut mag: Vec<u32> = Vec::new();
+        for c in digits.chars() {
+            let d = c.to_digit(radix)?;
+            mag = mag_mul(&mag, &[radix]);
+            mag = mag_add(&mag, &[d]);
+        }
+        return Some(BigInt::new(false, mag));
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.neg
+    }
+
+    pub fn bit_len(&self) -> usize {
+        mag_bit_len(&self.mag)
+    }
+
+    pub fn neg(&self) -> BigInt {
+        BigInt::new(!self.neg, self.mag.clone())
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.mag.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.neg == other.neg {
+            return BigInt::new(self.neg, mag_add(&self.mag, &other.mag));
+        }
+        match mag_cmp(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::new(other.neg, mag_sub(&other.mag, &self.mag)),
+            _ => BigInt::new(self.neg, mag_sub(&self.mag, &other.mag)),
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::new(self.neg != other.neg, mag_mul(&self.mag, &other.mag))
+    }
+
+    /// Truncated division, as in Go: the quotient is rounded
+    /// toward zero and the remainder has the sign of `self`.
+    /// The divisor must not be zero.
+    pub fn div_rem(&self, other: &BigInt) -> (BigInt, BigInt) {
+        let (q, r) = mag_divmod(&self.mag, &other.mag);
+        (BigInt::new(self.neg != other.neg, q), BigInt::new(self.neg, r))
+    }
+
+    pub fn shl(&self, n: usize) -> BigInt {
+        BigInt::new(self.neg, mag_shl(&self.mag, n))
+    }
+
+    /// Arithmetic shift: negative numbers are rounded
+    /// toward negative infinity.
+    pub fn shr(&self, n: usize) -> BigInt {
+        if self.neg {
+            let one = BigInt::from_u64(1);
+            let m = self.abs().sub(&one);
+            return BigInt::new(false, mag_shr(&m.mag, n)).add(&one).neg();
+        }
+        return BigInt::new(false, mag_shr(&self.mag, n));
+    }
+
+    /// Returns `len` limbs of the two's complement representation.
+    fn twos(&self, len: usize) -> Vec<u32> {
+        let mag = if self.neg { self.abs().sub(&BigInt::from_u64(1)).mag } else { self.mag.clone() };
+        let mut out: Vec<u32> = (0 .. len).map(|i| *mag.get(i).unwrap_or(&0)).collect();
+        if self.neg {
+            for x in &mut out {
+                *x = !*x;
+            }
+        }
+        return out;
+    }
+
+    fn from_twos(v: Vec<u32>) -> BigInt {
+        let neg = v.last().is_some_and(|&top| top >> 31 == 1);
+        if neg {
+            let inverted: Vec<u32> = v.iter().map(|&x| !x).collect();
+            return BigInt::new(false, inverted).add(&BigInt::from_u64(1)).neg();
+        }
+        return BigInt::new(false, v);
+    }
+
+    /// Applies a bitwise operation to the infinite two's
+    /// complement representations of two integers.
+    fn bitwise<F: Fn(u32, u32) -> u32>(&self, other: &BigInt, f: F) -> BigInt {
+        let len = self.mag.len().max(other.mag.len()) + 1;
+        let (a, b) = (self.twos(len), other.twos(len));
+        BigInt::from_twos(a.iter().zip(b.iter()).map(|(&x, &y)| f(x, y)).collect())
+    }
+
+    pub fn and(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |x, y| x & y)
+    }
+
+    pub fn or(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |x, y| x | y)
+    }
+
+    pub fn xor(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |x, y| x ^ y)
+    }
+
+    pub fn and_not(&self, other: &BigInt) -> BigInt {
+        self.bitwise(other, |x, y| x & !y)
+    }
+
+    /// Bitwise complement, `-x - 1`.
+    pub fn not(&self) -> BigInt {
+        self.neg().sub(&BigInt::from_u64(1))
+    }
+
+    pub fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.neg, other.neg) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => mag_cmp(&self.mag, &other.mag),
+            (true, true) => mag_cmp(&other.mag, &self.mag),
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.bit_len() > 64 {
+            return None;
+        }
+        let mag = *self.mag.first().unwrap_or(&0) as u64
+            | (*self.mag.get(1).unwrap_or(&0) as u64) << 32;
+        if self.neg {
+            if mag <= 1 << 63 {
+                return Some((mag as i64).wrapping_neg());
+            }
+            return None;
+        }
+        if mag < 1 << 63 {
+            return Some(mag as i64);
+        }
+        return None;
+    }
+
+    /// Returns whether the integer fits in `bits` bits
+    /// as a signed two's complement number.
+    pub fn fits_signed(&self, bits: usize) -> bool {
+        if self.neg {
+            return self.abs().sub(&BigInt::from_u64(1)).bit_len() < bits;
+        }
+        return self.bit_len() < bits;
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mut f = 0.0;
+        for &limb in self.mag.iter().rev() {
+            f = f * 4294967296.0 + limb as f64;
+        }
+        if self.neg { -f } else { f }
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.mag.is_empty() {
+            return write!(f, "0");
+        }
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = mag_divmod_small(&mag, 1_000_000_000);
+            chunks.push(r);
+            mag = q;
+        }
+        let mut s = String::new();
+        if self.neg {
+            s.push('-');
+        }
+        s.push_str(&chunks.pop().unwrap_or(0).to_string());
+        for chunk in chunks.iter().rev() {
+            s.push_str(&format!("{:09}", chunk));
+        }
+        write!(f, "{}", s)
+    }
+}
+
+
+// RATIONALS
+
+fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while !b.is_zero() {
+        let r = a.div_rem(&b).1;
+        a = b;
+        b = r;
+    }
+    return a;
+}
+
+impl Rat {
+    /// Creates `num/den`; the denominator must not be zero.
+    pub fn new(num: BigInt, den: BigInt) -> Rat {
+        let g = gcd(&num, &den);
+        let (mut num, mut den) = if g.is_zero() { (num, den) } else { (num.div_rem(&g).0, den.div_rem(&g).0) };
+        if den.is_negative() {
+            num = num.neg();
+            den = den.neg();
+        }
+        Rat { num: num, den: den }
+    }
+
+    pub fn from_int(n: BigInt) -> Rat {
+        Rat { num: n, den: BigInt::from_u64(1) }
+    }
+
+    /// Parses a decimal literal such as `1.5`, `.5` or `1.`.
+    pub fn parse(lexeme: &str) -> Option<Rat> {
+        let (int, frac) = match lexeme.find('.') {
+            Some(i) => (&lexeme[..i], &lexeme[i + 1..]),
+            None => (lexeme, ""),
+        };
+        let digits = format!("{}{}", int, frac);
+        let num = BigInt::parse(&digits, 10)?;
+        let den = BigInt::parse(&format!("1{}", "0".repeat(frac.len())), 10)?;
+        return Some(Rat::new(num, den));
+    }
+
+    /// Returns the exact value of a finite float.
+    pub fn from_f64(f: f64) -> Option<Rat> {
+        if !f.is_finite() {
+            return None;
+        }
+        let bits = f.to_bits();
+        let neg = bits >> 63 == 1;
+        let exp = ((bits >> 52) & 0x7ff) as i64;
+        let frac = bits & ((1 << 52) - 1);
+        let (mantissa, exp) = if exp == 0 { (frac, -1074) } else { (frac | (1 << 52), exp - 1075) };
+        let mut num = BigInt::from_u64(mantissa);
+        let mut den = BigInt::from_u64(1);
+        if exp >= 0 {
+            num = num.shl(exp as usize);
+        } else {
+            den = den.shl((-exp) as usize);
+        }
+        if neg {
+            num = num.neg();
+        }
+        return Some(Rat::new(num, den));
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub fn is_int(&self) -> bool {
+        self.den == BigInt::from_u64(1)
+    }
+
+    /// The integer part, rounded toward zero.
+    pub fn trunc(&self) -> BigInt {
+        self.num.div_rem(&self.den).0
+    }
+
+    pub fn neg(&self) -> Rat {
+        Rat { num: self.num.neg(), den: self.den.clone() }
+    }
+
+    pub fn add(&self, other: &Rat) -> Rat {
+        Rat::new(self.num.mul(&other.den).add(&other.num.mul(&self.den)), self.den.mul(&other.den))
+    }
+
+    pub fn sub(&self, other: &Rat) -> Rat {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Rat) -> Rat {
+        Rat::new(self.num.mul(&other.num), self.den.mul(&other.den))
+    }
+
+    /// The divisor must not be zero.
+    pub fn quo(&self, other: &Rat) -> Rat {
+        Rat::new(self.num.mul(&other.den), self.den.mul(&other.num))
+    }
+
+    pub fn cmp(&self, other: &Rat) -> Ordering {
+        self.num.mul(&other.den).cmp(&other.num.mul(&self.den))
+    }
+
+    /// Returns the nearest float; infinite if the value is
+    /// out of range.
+    pub fn to_f64(&self) -> f64 {
+        // Scale the quotient to 64 significant bits, so that
+        // a single rounding happens when converting it.
+        let shift = 64 + self.den.bit_len() as i64 - self.num.bit_len() as i64;
+        let q = if shift >= 0 {
+            self.num.abs().shl(shift as usize).div_rem(&self.den).0
+        } else {
+            self.num.abs().div_rem(&self.den.shl((-shift) as usize)).0
+        };
+        // Scaling by a power of two is exact, unless the
+        // result is subnormal or out of range.
+        let mut f = q.to_f64();
+        let mut exp = -shift;
+        while exp != 0 {
+            let step = exp.clamp(-1000, 1000);
+            f *= (2.0f64).powi(step as i32);
+            exp -= step;
+        }
+        if self.num.is_negative() { -f } else { f }
+    }
+}
+
+impl fmt::Display for Rat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_int() {
+            return write!(f, "{}", self.num);
+        }
+        write!(f, "{}", format_float(self.to_f64()))
+    }
+}
+
+/// Formats a float the way Go's `%g` verb does for constants.
+pub fn format_float(f: f64) -> String {
+    if f != 0.0 && (f.abs() >= 1e21 || f.abs() < 1e-4) {
+        let s = format!("{:e}", f);
+        return match s.find('e') {
+            Some(i) if !s[i + 1..].starts_with('-') => format!("{}e+{}", &s[..i], &s[i + 1..]),
+            _ => s,
+        };
+    }
+    return format!("{}", f);
+}
+
+
+// VALUES
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(ref s) => write!(f, "{:?}", s),
+            Value::Int(ref n) => write!(f, "{}", n),
+            Value::Float(ref r) => write!(f, "{}", r),
+        }
+    }
+}
+
+/// Returns the bytes of a string stored one `char` per byte.
+pub fn string_bytes(s: &str) -> Vec<u8> {
+    return s.chars().map(|c| c as u32 as u8).collect();
+}
+
+/// Returns the UTF-8 encoding of a code point as a string
+/// stored one `char` per byte.
+pub fn encode_rune(c: char) -> String {
+    let mut buf = [0; 4];
+    return c.encode_utf8(&mut buf).bytes().map(|b| b as char).collect();
+}
+
+impl Value {
+    pub fn to_rat(&self) -> Option<Rat> {
+        match *self {
+            Value::Int(ref n) => Some(Rat::from_int(n.clone())),
+            Value::Float(ref r) => Some(r.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as an integer, if it is integral.
+    pub fn to_int(&self) -> Option<BigInt> {
+        match *self {
+            Value::Int(ref n) => Some(n.clone()),
+            Value::Float(ref r) if r.is_int() => Some(r.trunc()),
+            _ => None,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Value::Int(ref n) => n.is_zero(),
+            Value::Float(ref r) => r.is_zero(),
+            _ => false,
+        }
+    }
+}
+
+/// Evaluates a unary operator on a constant of the right kind.
+pub fn unary_op(op: UnOp, x: &Value) -> Option<Value> {
+    match (op, x) {
+        (UnOp::Plus, _) => Some(x.clone()),
+        (UnOp::Neg, Value::Int(n)) => Some(Value::Int(n.neg())),
+        (UnOp::Neg, Value::Float(r)) => Some(Value::Float(r.neg())),
+        (UnOp::Not, &Value::Bool(b)) => Some(Value::Bool(!b)),
+        (UnOp::BitNot, Value::Int(n)) => Some(Value::Int(n.not())),
+        _ => None,
+    }
+}
+
+/// Evaluates `x op y` exactly.  Integers are divided with
+/// truncation; if either operand is a float, both are treated
+/// as floats.  Divisions by zero and shifts are the caller's
+/// responsibility and yield `None`, as do operands of the
+/// wrong kinds.
+pub fn binary_op(op: BinOp, x: &Value, y: &Value) -> Option<Value> {
+    match (x, y) {
+        (&Value::Bool(a), &Value::Bool(b)) => {
+            let v = match op {
+                BinOp::And => a && b,
+                BinOp::Or => a || b,
+                BinOp::Eq => a == b,
+                BinOp::Ne => a != b,
+                _ => return None,
+            };
+            return Some(Value::Bool(v));
+        }
+        (Value::String(a), Value::String(b)) => {
+            if op == BinOp::Add {
+                return Some(Value::String(format!("{}{}", a, b)));
+            }
+            return compare(op, a.cmp(b)).map(Value::Bool);
+        }
+        (Value::Int(a), Value::Int(b)) => {
+            if op.is_comparison() {
+                return compare(op, a.cmp(b)).map(Value::Bool);
+            }
+            if (op == BinOp::Div || op == BinOp::Rem) && b.is_zero() {
+                return None;
+            }
+            let v = match op {
+                BinOp::Add => a.add(b),
+                BinOp::Sub => a.sub(b),
+                BinOp::Mul => a.mul(b),
+                BinOp::Div => a.div_rem(b).0,
+                BinOp::Rem => a.div_rem(b).1,
+                BinOp::BitAnd => a.and(b),
+                BinOp::BitOr => a.or(b),
+                BinOp::BitXor => a.xor(b),
+                BinOp::BitClear => a.and_not(b),
+                _ => return None,
+            };
+            return Some(Value::Int(v));
+        }
+        _ => {}
+    }
+    let (a, b) = match (x.to_rat(), y.to_rat()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return None,
+    };
+    if op.is_comparison() {
+        return compare(op, a.cmp(&b)).map(Value::Bool);
+    }
+    let v = match op {
+        BinOp::Add => a.add(&b),
+        BinOp::Sub => a.sub(&b),
+        BinOp::Mul => a.mul(&b),
+        BinOp::Div if !b.is_zero() => a.quo(&b),
+        _ => return None,
+    };
+    return Some(Value::Float(v));
+}
+
+fn compare(op: BinOp, ord: Ordering) -> Option<bool> {
+    match op {
+        BinOp::Eq => Some(ord == Ordering::Equal),
+        BinOp::Ne => Some(ord != Ordering::Equal),
+        BinOp::Lt => Some(ord == Ordering::Less),
+        BinOp::Le => Some(ord != Ordering::Greater),
+        BinOp::Gt => Some(ord == Ordering::Greater),
+        BinOp::Ge => Some(ord != Ordering::Less),
+        _ => None,
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::mem;
+
+use ast::BinOp;
+use bytecode::Const;
+use ir::{self, Function, Inst, Op, Term, Ty};
+
+/// Removes the blocks of a function that are not reachable from
+/// its entry, and the instructions whose result is never used
+/// and which have no effect.  Stores, calls, prints and appends
+/// (which may write to the array of another slice) have effects,
+/// and so do the operations that may panic: index checks, and the
+/// integer divisions and shifts whose divisor or count is not a
+/// constant that makes them safe.
+///
+/// A block that is only reached by a jump from another one is
+/// also merged into it.
+pub fn eliminate(f: &mut Function) {
+    let mut reachable = vec![false; f.blocks.len()];
+    for b in f.reverse_postorder() {
+        reachable[b] = true;
+    }
+    ir::retain_blocks(f, &reachable);
+    for block in &mut f.blocks {
+        if let Term::Branch(_, t, e) = block.term {
+            if t == e {
+                block.term = Term::Jump(t);
+            }
+        }
+    }
+    merge_blocks(f);
+
+    // The values that the effects and the terminators use are
+    // live, and so are the operands of the live values; the other
+    // values are dead, even those of a cycle of phi nodes.
+    let mut consts = vec![None; f.types.len()];
+    let mut defs = vec![None; f.types.len()];
+    let mut live = vec![false; f.types.len()];
+    let mut work = Vec::new();
+    for (b, block) in f.blocks.iter().enumerate() {
+        for (i, inst) in block.insts.iter().enumerate() {
+            if let Some(v) = inst.result {
+                defs[v] = Some((b, i));
+                if let Op::Const(ref c) = inst.op {
+                    consts[v] = Some(c.clone());
+                }
+            }
+        }
+        work.extend(block.term.operands());
+    }
+    for block in &f.blocks {
+        for inst in &block.insts {
+            if !is_pure(inst, &f.types, &consts) {
+                work.extend(inst.op.operands());
+            }
+        }
+    }
+    while let Some(v) = work.pop() {
+        if live[v] {
+            continue;
+        }
+        live[v] = true;
+        if let Some((b, i)) = defs[v] {
+            work.extend(f.blocks[b].insts[i].op.operands());
+        }
+    }
+    let Function { ref mut blocks, ref types, .. } = *f;
+    for block in blocks {
+        block.insts.retain(|inst| inst.result.is_some_and(|v| live[v]) || !is_pure(inst, types, &consts));
+    }
+    ir::renumber(f);
+}
+
+/// Appends every block to the only block that jumps to it, if no
+/// other block branches to it.
+fn merge_blocks(f: &mut Function) {
+    let mut preds = f.predecessors();
+    let mut keep = vec![true; f.blocks.len()];
+    let mut replaced = HashMap::new();
+    for b in 0 .. f.blocks.len() {
+        if !keep[b] {
+            continue;
+        }
+        while let Term::Jump(s) = f.blocks[b].term {
+            if s == 0 || s == b || preds[s] != [b] {
+                break;
+            }
+            // The phi nodes of the block have a single operand.
+            let insts = mem::take(&mut f.blocks[s].insts);
+            for inst in insts {
+                match (inst.op, inst.result) {
+                    (Op::Phi(incoming), Some(v)) => {
+                        replaced.insert(v, incoming[0].1);
+                    }
+                    (op, result) => f.blocks[b].insts.push(Inst { result: result, op: op, pos: inst.pos }),
+                }
+            }
+            let term = mem::replace(&mut f.blocks[s].term, Term::Unreachable);
+            for t in term.successors() {
+                for inst in &mut f.blocks[t].insts {
+                    if let Op::Phi(ref mut incoming) = inst.op {
+                        for &mut (ref mut p, _) in incoming {
+                            if *p == s {
+                                *p = b;
+                            }
+                        }
+                    }
+                }
+                for p in &mut preds[t] {
+                    if *p == s {
+                        *p = b;
+                    }
+                }
+            }
+            f.blocks[b].term = term;
+            f.blocks[b].pos = f.blocks[s].pos;
+            keep[s] = false;
+        }
+    }
+    ir::retain_blocks(f, &keep);
+    ir::replace_uses(f, &replaced);
+}
+
+/// Whether an instruction only computes its result.
+pub fn is_pure(inst: &Inst, types: &[Ty], consts: &[Option<Const>]) -> bool {
+    let integer = match inst.result {
+        Some(v) => types[v].is_integer(),
+        None => return false,
+    };
+    match inst.op {
+        Op::Param(_) | Op::Const(_) | Op::Zero | Op::Phi(_) | Op::Unary(_, _) | Op::Convert(_)
+            | Op::Len(_) | Op::Cap(_) | Op::Index(_, _) | Op::SetIndex(_, _, _) | Op::Field(_, _)
+            | Op::SetField(_, _, _) | Op::LoadElem(_, _) | Op::Load(_) => return true,
+        Op::Binary(op, _, y) if integer => match (op, &consts[y]) {
+            (BinOp::Div, &Some(Const::Int(n))) | (BinOp::Rem, &Some(Const::Int(n))) => return n != 0,
+            (BinOp::Shl, &Some(Const::Int(n))) | (BinOp::Shr, &Some(Const::Int(n))) => return n >= 0,
+            (BinOp::Div, _) | (BinOp::Rem, _) | (BinOp::Shl, _) | (BinOp::Shr, _) => return false,
+            _ => return true,
+        },
+        Op::Binary(_, _, _) => return true,
+        Op::Check(_, _) | Op::StoreElem(_, _, _) | Op::Append(_, _) | Op::Store(_, _)
+            | Op::Call(_, _) | Op::Print(_) | Op::Println(_) => return false,
+    }
+}
@@ -9,6 +9,10 @@ pub fn err<T>(ty: ErrorType, loc: Loc) -> Result<T> {
     Err(Error::new(ty, loc))
 }
 
+pub fn err_msg<T>(ty: ErrorType, loc: Loc, msg: String) -> Result<T> {
+    Err(Error::with_msg(ty, loc, msg))
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ErrorType {
     Internal,
@@ -24,6 +28,58 @@ pub enum ErrorType {
     NewlineInString,
     NewlineInRune,
     EmptyRune,
+
+    // Parser errors
+    UnexpectedToken,
+    ExpectedExpression,
+    ExpectedType,
+    NonNameInShortVarDecl,
+    MissingCondition,
+
+    // Weeder errors
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    ContinueInSwitch,
+    MultipleDefaults,
+    BlankAsValue,
+    NonAddressableAssignment,
+    AssignmentMismatch,
+    MissingReturn,
+
+    // Name resolution errors
+    RedeclaredIdentifier,
+    UndeclaredIdentifier,
+    NotAType,
+    NoNewVariables,
+
+    // Type checking errors
+    InvalidRecursiveType,
+    DuplicateField,
+    InvalidArrayLength,
+    IncompatibleAssign,
+    MismatchedTypes,
+    InvalidOperation,
+    InvalidArgument,
+    NonBooleanCondition,
+    NotAnExpression,
+    NoValue,
+    NotCallable,
+    WrongArgumentCount,
+    InvalidConversion,
+    InvalidIndex,
+    UnknownField,
+    UnusedExpression,
+    ReturnMismatch,
+    InvalidMain,
+    ConstantOverflow,
+    ConstantTruncated,
+    DivisionByZero,
+    UnusedVariable,
+
+    // Warnings
+    UnreachableCode,
+    UnusedParameter,
+    IneffectualAssignment,
 }
 
 impl ErrorType {
@@ -41,6 +97,48 @@ impl ErrorType {
             NewlineInString => "newline in interpreted string literal",
             NewlineInRune => "newline in rune literal",
             EmptyRune => "empty rune literal",
+            UnexpectedToken => "syntax error: unexpected token",
+            ExpectedExpression => "syntax error: expected expression",
+            ExpectedType => "syntax error: expected type",
+            NonNameInShortVarDecl => "syntax error: non-name on left side of :=",
+            MissingCondition => "syntax error: missing condition",
+            BreakOutsideLoop => "break is not in a loop or switch",
+            ContinueOutsideLoop => "continue is not in a loop",
+            ContinueInSwitch => "continue is in a switch but not in a loop",
+            MultipleDefaults => "multiple defaults in switch",
+            BlankAsValue => "cannot use _ as value",
+            NonAddressableAssignment => "cannot assign to non-addressable expression",
+            AssignmentMismatch => "assignment mismatch",
+            MissingReturn => "missing return at end of function",
+            RedeclaredIdentifier => "identifier redeclared in this block",
+            UndeclaredIdentifier => "undefined identifier",
+            NotAType => "not a type",
+            NoNewVariables => "no new variables on left side of :=",
+            InvalidRecursiveType => "invalid recursive type",
+            DuplicateField => "duplicate field",
+            InvalidArrayLength => "invalid array length",
+            IncompatibleAssign => "cannot use value as this type",
+            MismatchedTypes => "invalid operation: mismatched types",
+            InvalidOperation => "invalid operation",
+            InvalidArgument => "invalid argument",
+            NonBooleanCondition => "non-boolean condition",
+            NotAnExpression => "not an expression",
+            NoValue => "no value used as value",
+            NotCallable => "cannot call non-function",
+            WrongArgumentCount => "wrong number of arguments",
+            InvalidConversion => "invalid conversion",
+            InvalidIndex => "invalid index",
+            UnknownField => "undefined field",
+            UnusedExpression => "expression evaluated but not used",
+            ReturnMismatch => "wrong number of return values",
+            InvalidMain => "func main must have no arguments and no return values",
+            ConstantOverflow => "constant overflows its type",
+            ConstantTruncated => "constant truncated to integer",
+            DivisionByZero => "division by constant zero",
+            UnusedVariable => "declared and not used",
+            UnreachableCode => "unreachable code",
+            UnusedParameter => "unused parameter",
+            IneffectualAssignment => "ineffectual assignment",
         }
     }
 }
@@ -51,15 +149,26 @@ impl fmt::Display for ErrorType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Error {
     pub ty: ErrorType,
-    pub loc: Loc
+    pub loc: Loc,
+    /// A message describing this particular occurrence
+    /// of the error; replaces the generic description of
+    /// `ty` when displayed.
+    pub msg: Option<String>,
+    /// Whether this is a warning, which does not prevent the
+    /// program from being compiled.
+    pub warning: bool,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}: {}", self.loc, self.ty)
+        let severity = if self.warning { "warning: " } else { "" };
+        match self.msg {
+            Some(ref msg) => write!(f, "{}: {}{}", self.loc, severity, msg),
+            None => write!(f, "{}: {}{}", self.loc, severity, self.ty)
+        }
     }
 }
 
@@ -71,6 +180,14 @@ impl error::Error for Error {
 
 impl Error {
     pub fn new(ty: ErrorType, loc: Loc) -> Error {
-        Error { ty: ty, loc: loc }
+        Error { ty: ty, loc: loc, msg: None, warning: false }
+    }
+
+    pub fn with_msg(ty: ErrorType, loc: Loc, msg: String) -> Error {
+        Error { ty: ty, loc: loc, msg: Some(msg), warning: false }
+    }
+
+    pub fn warning(ty: ErrorType, loc: Loc) -> Error {
+        Error { ty: ty, loc: loc, msg: None, warning: true }
     }
 }
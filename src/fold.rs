@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::{BinOp, UnOp};
+use bytecode::Const;
+use constant;
+use ir::{self, BlockId, Function, Op, Term, Ty, Value};
+
+/// What is known of a value while constants are propagated.
+#[derive(Debug, Clone, PartialEq)]
+enum Lattice {
+    /// Not computed yet: the value may still be any constant.
+    Unknown,
+    Const(Const),
+    /// The value is not a constant, or may panic.
+    Varying,
+}
+
+/// Folds the constant values of a function and propagates them,
+/// with the sparse conditional constant propagation of Wegman and
+/// Zadeck: the blocks are only assumed to be executed once a
+/// branch that reaches them is, so that the values that only
+/// come from blocks that never run do not make a phi node vary.
+///
+/// The values found to be constant become `const` instructions,
+/// the checks of constant indices in range disappear, branches on
+/// constant conditions become jumps and the blocks that are never
+/// executed are removed.  Operations that panic, such as a
+/// division by zero, are never folded.
+pub fn fold(f: &mut Function) {
+    let mut sccp = Propagation::new(f);
+    sccp.run();
+    let Propagation { values, executable, edges, .. } = sccp;
+
+    // Branches on constants become jumps, and the phi nodes lose
+    // the operands that come from edges that are never taken.
+    for (b, block) in f.blocks.iter_mut().enumerate() {
+        if !executable[b] {
+            continue;
+        }
+        if let Term::Branch(c, t, e) = block.term {
+            match values[c] {
+                Lattice::Const(Const::Bool(true)) => block.term = Term::Jump(t),
+                Lattice::Const(Const::Bool(false)) => block.term = Term::Jump(e),
+                _ => {}
+            }
+        }
+        for inst in &mut block.insts {
+            if let Op::Phi(ref mut incoming) = inst.op {
+                incoming.retain(|&(p, _)| edges.contains(&(p, b)));
+            }
+        }
+    }
+    ir::retain_blocks(f, &executable);
+
+    // A phi node whose operands are all the same value is that
+    // value; constants are rewritten after the phi nodes.
+    let mut replaced = HashMap::new();
+    for block in &mut f.blocks {
+        let mut phis = Vec::new();
+        let mut insts = Vec::new();
+        for mut inst in block.insts.drain(..) {
+            let value = inst.result.map_or(Lattice::Varying, |v| values[v].clone());
+            if let Lattice::Const(c) = value {
+                if inst.op != Op::Const(c.clone()) {
+                    inst.op = Op::Const(c);
+                }
+                insts.push(inst);
+                continue;
+            }
+            match inst.op {
+                Op::Phi(ref incoming) => {
+                    let v = inst.result.unwrap_or(0);
+                    let first = incoming.iter().map(|&(_, w)| w).find(|&w| w != v);
+                    if let (Some(_), Some(first)) = (inst.result, first) {
+                        if incoming.iter().all(|&(_, w)| w == first || w == v) {
+                            replaced.insert(v, first);
+                            continue;
+                        }
+                    }
+                    phis.push(inst);
+                }
+                Op::Check(i, n) if in_range(&values[i], &values[n]) => {}
+                _ => insts.push(inst),
+            }
+        }
+        phis.extend(insts);
+        block.insts = phis;
+    }
+    ir::replace_uses(f, &replaced);
+    ir::renumber(f);
+}
+
+fn in_range(i: &Lattice, n: &Lattice) -> bool {
+    match (i, n) {
+        (&Lattice::Const(Const::Int(i)), &Lattice::Const(Const::Int(n))) => return i >= 0 && i < n,
+        _ => return false,
+    }
+}
+
+fn meet(a: &Lattice, b: &Lattice) -> Lattice {
+    match (a, b) {
+        (&Lattice::Unknown, x) | (x, &Lattice::Unknown) => return x.clone(),
+        (Lattice::Const(x), Lattice::Const(y)) if same(x, y) => return a.clone(),
+        _ => return Lattice::Varying,
+    }
+}
+
+/// Whether two constants are the same; floats are compared bit
+/// for bit, so that 0.0 and -0.0 are distinct.
+fn same(x: &Const, y: &Const) -> bool {
+    match (x, y) {
+        (&Const::Float(x), &Const::Float(y)) => return x.to_bits() == y.to_bits(),
+        _ => return x == y,
+    }
+}
+
+/// Where a value is used: an instruction of a block, or its
+/// terminator (`None`).
+type Use = (BlockId, Option<usize>);
+
+struct Propagation<'a> {
+    f: &'a Function,
+    values: Vec<Lattice>,
+    /// The blocks and the edges that may be executed.
+    executable: Vec<bool>,
+    edges: HashSet<(BlockId, BlockId)>,
+    uses: Vec<Vec<Use>>,
+    /// The edges that became executable, and the values whose
+    /// lattice changed, which are still to be followed.
+    flow: Vec<(Option<BlockId>, BlockId)>,
+    changed: Vec<Value>,
+}
+
+impl<'a> Propagation<'a> {
+    fn new(f: &'a Function) -> Propagation<'a> {
+        let mut uses = vec![Vec::new(); f.types.len()];
+        for (b, block) in f.blocks.iter().enumerate() {
+            for (i, inst) in block.insts.iter().enumerate() {
+                for v in inst.op.operands() {
+                    uses[v].push((b, Some(i)));
+                }
+            }
+            for v in block.term.operands() {
+                uses[v].push((b, None));
+            }
+        }
+        Propagation {
+            f: f,
+            values: vec![Lattice::Unknown; f.types.len()],
+            executable: vec![false; f.blocks.len()],
+            edges: HashSet::new(),
+            uses: uses,
+            flow: vec![(None, 0)],
+            changed: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) {
+        loop {
+            if let Some((from, to)) = self.flow.pop() {
+                if let Some(from) = from {
+                    if !self.edges.insert((from, to)) {
+                        continue;
+                    }
+                }
+                // The phi nodes see a new operand, and the rest of
+                // the block is only visited the first time.
+                let f = self.f;
+                for (i, inst) in f.blocks[to].insts.iter().enumerate() {
+                    if let Op::Phi(_) = inst.op {
+                        self.visit(to, Some(i));
+                    }
+                }
+                if !self.executable[to] {
+                    self.executable[to] = true;
+                    for (i, inst) in f.blocks[to].insts.iter().enumerate() {
+                        match inst.op {
+                            Op::Phi(_) => {}
+                            _ => self.visit(to, Some(i)),
+                        }
+                    }
+                    self.visit(to, None);
+                }
+            } else if let Some(v) = self.changed.pop() {
+                for (b, i) in self.uses[v].clone() {
+                    if self.executable[b] {
+                        self.visit(b, i);
+                    }
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn visit(&mut self, b: BlockId, i: Option<usize>) {
+        let f = self.f;
+        let block = &f.blocks[b];
+        let inst = match i {
+            Some(i) => &block.insts[i],
+            None => {
+                match block.term {
+                    Term::Jump(t) => self.flow.push((Some(b), t)),
+                    Term::Branch(c, t, e) => match self.values[c] {
+                        Lattice::Unknown => {}
+                        Lattice::Const(Const::Bool(true)) => self.flow.push((Some(b), t)),
+                        Lattice::Const(Const::Bool(false)) => self.flow.push((Some(b), e)),
+                        _ => {
+                            self.flow.push((Some(b), t));
+                            self.flow.push((Some(b), e));
+                        }
+                    },
+                    Term::Return(_) | Term::Unreachable => {}
+                }
+                return;
+            }
+        };
+        let v = match inst.result {
+            Some(v) => v,
+            None => return,
+        };
+        let value = match inst.op {
+            Op::Phi(ref incoming) => {
+                let mut value = Lattice::Unknown;
+                for &(p, x) in incoming {
+                    if self.edges.contains(&(p, b)) {
+                        value = meet(&value, &self.values[x]);
+                    }
+                }
+                value
+            }
+            ref op => self.evaluate(op, &f.types[v]),
+        };
+        let value = meet(&self.values[v], &value);
+        if value != self.values[v] {
+            self.values[v] = value;
+            self.changed.push(v);
+        }
+    }
+
+    /// Evaluates an operation whose result has type `ty`.
+    fn evaluate(&self, op: &Op, ty: &Ty) -> Lattice {
+        let f = self.f;
+        let operands: Vec<&Lattice> = op.operands().into_iter().map(|v| &self.values[v]).collect();
+        match *op {
+            Op::Const(ref c) => return Lattice::Const(c.clone()),
+            Op::Zero => return zero(ty).map_or(Lattice::Varying, Lattice::Const),
+            // The length of an array does not depend on its value.
+            Op::Len(x) | Op::Cap(x) => {
+                if let Ty::Array(len, _) = f.types[x] {
+                    return Lattice::Const(Const::Int(len as i64));
+                }
+            }
+            Op::Unary(_, _) | Op::Binary(_, _, _) | Op::Convert(_) => {}
+            _ => return Lattice::Varying,
+        }
+        if operands.iter().any(|x| **x == Lattice::Varying) {
+            return Lattice::Varying;
+        }
+        let consts: Vec<&Const> = operands.iter().filter_map(|x| match **x {
+            Lattice::Const(ref c) => Some(c),
+            _ => None,
+        }).collect();
+        if consts.len() < operands.len() {
+            return Lattice::Unknown;
+        }
+        let result = match *op {
+            Op::Unary(op, _) => unary(op, ty, consts[0]),
+            Op::Binary(op, x, _) => binary(op, &f.types[x], consts[0], consts[1]),
+            Op::Convert(x) => convert(&f.types[x], ty, consts[0]),
+            Op::Len(_) => match *consts[0] {
+                Const::Str(ref s) => Some(Const::Int(s.len() as i64)),
+                _ => None,
+            },
+            _ => None,
+        };
+        return result.map_or(Lattice::Varying, Lattice::Const);
+    }
+}
+
+/// The zero value of a scalar type.
+fn zero(ty: &Ty) -> Option<Const> {
+    match *ty {
+        Ty::Int | Ty::Rune => return Some(Const::Int(0)),
+        Ty::Float => return Some(Const::Float(0.0)),
+        Ty::Bool => return Some(Const::Bool(false)),
+        Ty::Str => return Some(Const::Str(Vec::new())),
+        _ => return None,
+    }
+}
+
+/// Brings the result of an integer operation back in the range
+/// of its type.
+fn wrap(ty: &Ty, n: i64) -> i64 {
+    if *ty == Ty::Rune {
+        return n as i32 as i64;
+    }
+    return n;
+}
+
+/// Applies a unary operation to a constant of type `ty`.
+pub fn unary(op: UnOp, ty: &Ty, x: &Const) -> Option<Const> {
+    match (op, x) {
+        (UnOp::Neg, &Const::Int(n)) => return Some(Const::Int(wrap(ty, n.wrapping_neg()))),
+        (UnOp::Neg, &Const::Float(x)) => return Some(Const::Float(-x)),
+        (UnOp::Not, &Const::Bool(b)) => return Some(Const::Bool(!b)),
+        (UnOp::BitNot, &Const::Int(n)) => return Some(Const::Int(!n)),
+        (UnOp::Plus, x) => return Some(x.clone()),
+        _ => return None,
+    }
+}
+
+/// Applies a binary operation to constants of type `ty`, as the
+/// program would at run time; returns `None` if the operation
+/// panics.
+pub fn binary(op: BinOp, ty: &Ty, x: &Const, y: &Const) -> Option<Const> {
+    match (x, y) {
+        (&Const::Int(x), &Const::Int(y)) => {
+            let n = match op {
+                BinOp::Eq => return Some(Const::Bool(x == y)),
+                BinOp::Ne => return Some(Const::Bool(x != y)),
+                BinOp::Lt => return Some(Const::Bool(x < y)),
+                BinOp::Le => return Some(Const::Bool(x <= y)),
+                BinOp::Gt => return Some(Const::Bool(x > y)),
+                BinOp::Ge => return Some(Const::Bool(x >= y)),
+                BinOp::Add => x.wrapping_add(y),
+                BinOp::Sub => x.wrapping_sub(y),
+                BinOp::Mul => x.wrapping_mul(y),
+                BinOp::Div | BinOp::Rem if y == 0 => return None,
+                BinOp::Div => x.wrapping_div(y),
+                BinOp::Rem => x.wrapping_rem(y),
+                BinOp::Shl | BinOp::Shr if y < 0 => return None,
+                BinOp::Shl if y >= 64 => 0,
+                BinOp::Shl => ((x as u64) << y) as i64,
+                BinOp::Shr => x >> y.min(63),
+                BinOp::BitAnd => x & y,
+                BinOp::BitOr => x | y,
+                BinOp::BitXor => x ^ y,
+                BinOp::BitClear => x & !y,
+                BinOp::And | BinOp::Or => return None,
+            };
+            return Some(Const::Int(wrap(ty, n)));
+        }
+        (&Const::Float(x), &Const::Float(y)) => match op {
+            BinOp::Eq => return Some(Const::Bool(x == y)),
+            BinOp::Ne => return Some(Const::Bool(x != y)),
+            BinOp::Lt => return Some(Const::Bool(x < y)),
+            BinOp::Le => return Some(Const::Bool(x <= y)),
+            BinOp::Gt => return Some(Const::Bool(x > y)),
+            BinOp::Ge => return Some(Const::Bool(x >= y)),
+            BinOp::Add => return Some(Const::Float(x + y)),
+            BinOp::Sub => return Some(Const::Float(x - y)),
+            BinOp::Mul => return Some(Const::Float(x * y)),
+            BinOp::Div => return Some(Const::Float(x / y)),
+            _ => return None,
+        },
+        (&Const::Bool(x), &Const::Bool(y)) => match op {
+            BinOp::Eq => return Some(Const::Bool(x == y)),
+            BinOp::Ne => return Some(Const::Bool(x != y)),
+            _ => return None,
+        },
+        (Const::Str(x), Const::Str(y)) => match op {
+            BinOp::Eq => return Some(Const::Bool(x == y)),
+            BinOp::Ne => return Some(Const::Bool(x != y)),
+            BinOp::Lt => return Some(Const::Bool(x < y)),
+            BinOp::Le => return Some(Const::Bool(x <= y)),
+            BinOp::Gt => return Some(Const::Bool(x > y)),
+            BinOp::Ge => return Some(Const::Bool(x >= y)),
+            BinOp::Add => {
+                let mut s = x.clone();
+                s.extend_from_slice(y);
+                return Some(Const::Str(s));
+            }
+            _ => return None,
+        },
+        _ => return None,
+    }
+}
+
+/// Converts a constant of type `from` to type `to`.  Floats are
+/// truncated toward zero, saturating at the bounds of `int`.
+pub fn convert(from: &Ty, to: &Ty, x: &Const) -> Option<Const> {
+    match (x, to) {
+        (&Const::Int(n), &Ty::Float) => return Some(Const::Float(n as f64)),
+        (&Const::Float(x), to) if to.is_integer() => return Some(Const::Int(wrap(to, x as i64))),
+        (&Const::Int(n), &Ty::Str) => {
+            // Invalid code points become U+FFFD.
+            let c = if n >= 0 && n <= u32::MAX as i64 { char::from_u32(n as u32) } else { None };
+            let s = constant::encode_rune(c.unwrap_or('\u{fffd}'));
+            return Some(Const::Str(constant::string_bytes(&s)));
+        }
+        (&Const::Int(n), to) if to != from => return Some(Const::Int(wrap(to, n))),
+        (x, _) => return Some(x.clone()),
+    }
+}
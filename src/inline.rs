@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use bytecode::Pos;
+use ir::{self, BlockId, Block, Function, Inst, Module, Op, Term, Value};
+
+/// The largest function that is inlined, counted in instructions
+/// and blocks.
+pub const MAX_CALLEE_SIZE: usize = 40;
+
+/// The size past which a function does not grow by inlining the
+/// functions it calls.
+pub const MAX_CALLER_SIZE: usize = 2000;
+
+/// What became of a call site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub caller: String,
+    pub callee: String,
+    pub pos: Pos,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// The call was replaced with the body of the callee, of
+    /// this size.
+    Inlined(usize),
+    /// The callee is larger than `MAX_CALLEE_SIZE`.
+    TooLarge(usize),
+    /// The callee may call itself, directly or not.
+    Recursive,
+    /// The caller is already larger than `MAX_CALLER_SIZE`.
+    CallerTooLarge,
+}
+
+/// Replaces the calls of small functions with their bodies.
+///
+/// The functions are inlined bottom-up, callees before callers,
+/// so that each body that is copied has its own calls inlined
+/// already; the calls in the copies are not considered again.  A
+/// function that may call itself, directly or through others, is
+/// never inlined, so that inlining terminates.
+///
+/// The arguments replace the parameters of the copy.  Since
+/// arrays and structs are values in this representation, which
+/// `SetIndex` and `SetField` copy rather than modify, the copy of
+/// the callee cannot change the arguments of its caller, as Go
+/// requires.
+///
+/// Returns what became of every call site, in the order of the
+/// functions and of the calls in each of them.
+pub fn inline(module: &mut Module) -> Vec<Decision> {
+    let sccs = components(module);
+    let mut recursive = vec![false; module.functions.len()];
+    for scc in &sccs {
+        let calls_itself = |f: usize| callees(&module.functions[f]).contains(&f);
+        if scc.len() > 1 || calls_itself(scc[0]) {
+            for &f in scc {
+                recursive[f] = true;
+            }
+        }
+    }
+    let mut decisions = vec![Vec::new(); module.functions.len()];
+    for scc in sccs {
+        for f in scc {
+            let mut caller = module.functions[f].clone();
+            decisions[f] = inline_calls(module, &mut caller, &recursive);
+            module.functions[f] = caller;
+        }
+    }
+    return decisions.into_iter().flatten().collect();
+}
+
+/// Returns the lines of a report of the decisions of `inline`.
+pub fn report(filename: &str, decisions: &[Decision]) -> String {
+    let mut out = String::new();
+    for d in decisions {
+        let reason = match d.reason {
+            Reason::Inlined(size) => {
+                out.push_str(&format!("{}:{}:{}: inlined call to {} in {} (size {})\n",
+                                      filename, d.pos.line, d.pos.col, d.callee, d.caller, size));
+                continue;
+            }
+            Reason::TooLarge(size) => format!("{} is too large (size {} > {})", d.callee, size, MAX_CALLEE_SIZE),
+            Reason::Recursive => format!("{} is recursive", d.callee),
+            Reason::CallerTooLarge => format!("{} is too large (size > {})", d.caller, MAX_CALLER_SIZE),
+        };
+        out.push_str(&format!("{}:{}:{}: call to {} in {} not inlined: {}\n",
+                              filename, d.pos.line, d.pos.col, d.callee, d.caller, reason));
+    }
+    return out;
+}
+
+/// Returns the size of a function: its number of instructions,
+/// other than parameters, and of blocks.
+pub fn size(f: &Function) -> usize {
+    let insts = f.blocks.iter().flat_map(|block| &block.insts);
+    return f.blocks.len() + insts.filter(|inst| !matches!(inst.op, Op::Param(_))).count();
+}
+
+fn callees(f: &Function) -> Vec<usize> {
+    let mut callees = Vec::new();
+    for inst in f.blocks.iter().flat_map(|block| &block.insts) {
+        if let Op::Call(callee, _) = inst.op {
+            callees.push(callee);
+        }
+    }
+    return callees;
+}
+
+/// Returns the strongly connected components of the call graph
+/// with the algorithm of Tarjan, which finds the components that
+/// a component calls before it.
+fn components(module: &Module) -> Vec<Vec<usize>> {
+    struct Tarjan {
+        calls: Vec<Vec<usize>>,
+        visited: usize,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        sccs: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan {
+        fn visit(&mut self, f: usize) {
+            let index = self.visited;
+            self.visited += 1;
+            self.index[f] = Some(index);
+            self.low[f] = index;
+            self.stack.push(f);
+            self.on_stack[f] = true;
+            for g in self.calls[f].clone() {
+                match self.index[g] {
+                    None => {
+                        self.visit(g);
+                        self.low[f] = self.low[f].min(self.low[g]);
+                    }
+                    Some(i) if self.on_stack[g] => self.low[f] = self.low[f].min(i),
+                    Some(_) => {}
+                }
+            }
+            if self.low[f] == index {
+                let mut scc = Vec::new();
+                while let Some(g) = self.stack.pop() {
+                    self.on_stack[g] = false;
+                    scc.push(g);
+                    if g == f {
+                        break;
+                    }
+                }
+                scc.reverse();
+                self.sccs.push(scc);
+            }
+        }
+    }
+
+    let n = module.functions.len();
+    let mut tarjan = Tarjan {
+        calls: module.functions.iter().map(|f| callees(f).into_iter().filter(|&g| g < n).collect()).collect(),
+        visited: 0,
+        index: vec![None; n],
+        low: vec![0; n],
+        stack: Vec::new(),
+        on_stack: vec![false; n],
+        sccs: Vec::new(),
+    };
+    for f in 0 .. n {
+        if tarjan.index[f].is_none() {
+            tarjan.visit(f);
+        }
+    }
+    return tarjan.sccs;
+}
+
+/// Inlines the calls of `caller`, whose callees are final.
+fn inline_calls(module: &Module, caller: &mut Function, recursive: &[bool]) -> Vec<Decision> {
+    let mut decisions = Vec::new();
+    let mut replaced = HashMap::new();
+    // The blocks that hold instructions of the caller itself, and
+    // where their calls start; the blocks of the copies are not
+    // searched.
+    let mut work: Vec<(BlockId, usize)> = (0 .. caller.blocks.len()).rev().map(|b| (b, 0)).collect();
+    while let Some((b, start)) = work.pop() {
+        let call = caller.blocks[b].insts.iter().skip(start).position(|inst| matches!(inst.op, Op::Call(_, _)));
+        let (i, callee) = match call.map(|i| (start + i, &caller.blocks[b].insts[start + i])) {
+            Some((i, &Inst { op: Op::Call(callee, _), .. })) => (i, callee),
+            _ => continue,
+        };
+        let g = &module.functions[callee];
+        let reason = if recursive[callee] {
+            Reason::Recursive
+        } else if size(g) > MAX_CALLEE_SIZE {
+            Reason::TooLarge(size(g))
+        } else if size(caller) > MAX_CALLER_SIZE {
+            Reason::CallerTooLarge
+        } else {
+            Reason::Inlined(size(g))
+        };
+        let inlined = matches!(reason, Reason::Inlined(_));
+        decisions.push(Decision {
+            caller: caller.name.clone(),
+            callee: g.name.clone(),
+            pos: caller.blocks[b].insts[i].pos,
+            reason: reason,
+        });
+        if inlined {
+            // The instructions after the call move to a block of
+            // their own, which the copy returns to.
+            let rest = split_block(caller, b, i + 1);
+            inline_call(caller, b, g, &mut replaced);
+            work.push((rest, 0));
+        } else {
+            work.push((b, i + 1));
+        }
+    }
+    ir::replace_uses(caller, &replaced);
+    ir::renumber(caller);
+    return decisions;
+}
+
+/// Moves the instructions of block `b` from index `i` on to a new
+/// block, to which `b` jumps, and returns the new block.
+fn split_block(f: &mut Function, b: BlockId, i: usize) -> BlockId {
+    let rest = f.blocks.len();
+    let insts = f.blocks[b].insts.split_off(i);
+    let pos = f.blocks[b].pos;
+    let term = std::mem::replace(&mut f.blocks[b].term, Term::Jump(rest));
+    for s in term.successors() {
+        for inst in &mut f.blocks[s].insts {
+            if let Op::Phi(ref mut incoming) = inst.op {
+                for &mut (ref mut p, _) in incoming {
+                    if *p == b {
+                        *p = rest;
+                    }
+                }
+            }
+        }
+    }
+    f.blocks.push(Block { insts: insts, term: term, pos: pos });
+    return rest;
+}
+
+/// Replaces the call that ends block `b` of `f` with a copy of
+/// the blocks of `g`, which return to the block that `b` jumps
+/// to and which has no phi nodes.  The values that replace the
+/// result of the call are added to `replaced`.
+fn inline_call(f: &mut Function, b: BlockId, g: &Function, replaced: &mut HashMap<Value, Value>) {
+    let call = match f.blocks[b].insts.pop() {
+        Some(call) => call,
+        None => return,
+    };
+    let args = match call.op {
+        Op::Call(_, args) => args,
+        _ => return,
+    };
+    let next = match f.blocks[b].term {
+        Term::Jump(next) => next,
+        _ => return,
+    };
+    let base = f.blocks.len();
+    let mut values: Vec<Value> = g.types.iter().map(|ty| f.new_value(ty.clone())).collect();
+    for inst in &g.blocks[0].insts {
+        if let (Op::Param(k), Some(v)) = (&inst.op, inst.result) {
+            values[v] = args[*k];
+        }
+    }
+    let mut results = Vec::new();
+    for (gb, block) in g.blocks.iter().enumerate() {
+        let mut insts = Vec::new();
+        for inst in &block.insts {
+            let mut op = match inst.op {
+                Op::Param(_) => continue,
+                Op::Phi(ref incoming) => Op::Phi(incoming.iter().map(|&(p, v)| (base + p, v)).collect()),
+                ref op => op.clone(),
+            };
+            op.map_operands(|v| values[v]);
+            insts.push(Inst { result: inst.result.map(|v| values[v]), op: op, pos: inst.pos });
+        }
+        let term = match block.term {
+            Term::Jump(target) => Term::Jump(base + target),
+            Term::Branch(c, t, e) => Term::Branch(values[c], base + t, base + e),
+            Term::Return(v) => {
+                if let Some(v) = v {
+                    results.push((base + gb, values[v]));
+                }
+                Term::Jump(next)
+            }
+            Term::Unreachable => Term::Unreachable,
+        };
+        f.blocks.push(Block { insts: insts, term: term, pos: block.pos });
+    }
+    f.blocks[b].term = Term::Jump(base);
+
+    // The values that the copy returns become the result of the
+    // call, in the block after it.
+    if let Some(result) = call.result {
+        if results.len() == 1 {
+            replaced.insert(result, results[0].1);
+        } else {
+            let op = if results.is_empty() { Op::Zero } else { Op::Phi(results) };
+            f.blocks[next].insts.insert(0, Inst { result: Some(result), op: op, pos: call.pos });
+        }
+    }
+}
@@ -0,0 +1,817 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+use std::result;
+
+use ast::*;
+use constant::{self, Value};
+use loc::Loc;
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// The maximum depth of nested function calls; deeper
+/// recursion is reported as a stack overflow rather than
+/// overflowing the stack of the interpreter itself.
+pub const MAX_DEPTH: usize = 20000;
+
+/// A run-time error, reported as a Go panic.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub msg: String,
+    pub loc: Loc,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "panic: runtime error: {}\n\tat {}", self.msg, self.loc)
+    }
+}
+
+type Result<T> = result::Result<T, RuntimeError>;
+
+fn panic<T>(msg: String, loc: &Loc) -> Result<T> {
+    Err(RuntimeError { msg: msg, loc: loc.clone() })
+}
+
+/// A run-time value.  `int` and `rune` values are both `Int`s;
+/// runes are kept in the range of 32-bit integers.  Arrays and
+/// structs hold their elements and fields, and are copied on
+/// assignment; slices share their backing array.
+#[derive(Debug, Clone)]
+pub enum Val {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<Vec<u8>>),
+    Array(Vec<Val>),
+    Struct(Vec<Val>),
+    Slice(Slice),
+    /// The result of a function without a result.
+    Void,
+}
+
+/// A slice refers to the first `len` elements of a backing
+/// array, whose length is the capacity of the slice.
+#[derive(Debug, Clone)]
+pub struct Slice {
+    data: Rc<RefCell<Vec<Val>>>,
+    len: usize,
+}
+
+impl Val {
+    fn int(&self) -> i64 {
+        match *self {
+            Val::Int(n) => n,
+            _ => 0,
+        }
+    }
+
+    fn bool(&self) -> bool {
+        match *self {
+            Val::Bool(b) => b,
+            _ => false,
+        }
+    }
+}
+
+/// Executes a type-checked program, writing what it prints to
+/// `out`: package variables are initialized in the order of
+/// their declarations, then `main` is called.
+pub fn run(prog: &Program, types: &TypeTable, table: &SymbolTable, out: &mut dyn Write)
+           -> Result<()> {
+    let mut interp = Interpreter {
+        types: types,
+        table: table,
+        funcs: HashMap::new(),
+        globals: HashMap::new(),
+        frames: Vec::new(),
+        out: out,
+    };
+    let mut main = None;
+    for decl in &prog.decls {
+        if let TopLevelDecl::Func(ref f) = *decl {
+            if let Some(sym) = f.name.sym {
+                interp.funcs.insert(sym, f);
+            }
+            if f.name.name == "main" {
+                main = Some(f);
+            }
+        }
+    }
+    for decl in &prog.decls {
+        if let TopLevelDecl::Var(ref specs) = *decl {
+            for spec in specs {
+                interp.var_spec(spec)?;
+            }
+        }
+    }
+    if let Some(f) = main {
+        interp.call(f, Vec::new(), &f.loc)?;
+    }
+    return Ok(());
+}
+
+/// How the execution of a statement ends.
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Val),
+}
+
+/// Where a variable or element is stored: a path of element
+/// and field indices from a variable, an element of a slice's
+/// backing array, or a temporary value.
+struct Place {
+    root: Root,
+    path: Vec<usize>,
+    /// The panic of the first index out of range in the operands,
+    /// which happens when the place is used.
+    fault: Option<RuntimeError>,
+}
+
+enum Root {
+    Local(SymbolId),
+    Global(SymbolId),
+    Elem(Rc<RefCell<Vec<Val>>>, usize),
+    Temp(Val),
+}
+
+struct Interpreter<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    funcs: HashMap<SymbolId, &'a FuncDecl>,
+    globals: HashMap<SymbolId, Val>,
+    /// The local variables of the active calls, innermost last.
+    /// Every variable has a symbol of its own, so the variables
+    /// of a call need not be organized in scopes.
+    frames: Vec<HashMap<SymbolId, Val>>,
+    out: &'a mut dyn Write,
+}
+
+impl<'a> Interpreter<'a> {
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    /// Creates a variable (or sets it anew, for declarations
+    /// executed several times) in the current call, or in the
+    /// package if there is no call.
+    fn declare(&mut self, id: &Ident, value: Val) {
+        if let Some(sym) = id.sym {
+            match self.frames.last_mut() {
+                Some(frame) => frame.insert(sym, value),
+                None => self.globals.insert(sym, value),
+            };
+        }
+    }
+
+    /// Returns the zero value of a type.
+    fn zero(&self, ty: TypeId) -> Val {
+        match *self.types.under(self.types.default_type(ty)) {
+            TypeKind::Int | TypeKind::Rune => return Val::Int(0),
+            TypeKind::Float64 => return Val::Float(0.0),
+            TypeKind::Bool => return Val::Bool(false),
+            TypeKind::String => return Val::Str(Rc::new(Vec::new())),
+            TypeKind::Array(len, elem) => {
+                return Val::Array((0 .. len).map(|_| self.zero(elem)).collect());
+            }
+            TypeKind::Struct(ref fields) => {
+                return Val::Struct(fields.iter().map(|&(_, ty)| self.zero(ty)).collect());
+            }
+            TypeKind::Slice(_) => {
+                return Val::Slice(Slice { data: Rc::new(RefCell::new(Vec::new())), len: 0 });
+            }
+            _ => return Val::Void,
+        }
+    }
+
+    /// Brings an integer back in the range of its type, so
+    /// that `rune` arithmetic wraps around at 32 bits.
+    fn wrap(&self, ty: TypeId, n: i64) -> i64 {
+        match *self.types.under(self.types.default_type(ty)) {
+            TypeKind::Rune => return n as i32 as i64,
+            _ => return n,
+        }
+    }
+
+
+    // DECLARATIONS
+
+    fn var_spec(&mut self, spec: &VarSpec) -> Result<()> {
+        if spec.values.is_empty() {
+            for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                let zero = self.zero(self.var_type(id));
+                self.declare(id, zero);
+            }
+            return Ok(());
+        }
+        let values = self.exprs(&spec.values)?;
+        for (id, value) in spec.names.iter().zip(values) {
+            self.declare(id, value);
+        }
+        return Ok(());
+    }
+
+    fn call(&mut self, f: &FuncDecl, args: Vec<Val>, loc: &Loc) -> Result<Val> {
+        if self.frames.len() >= MAX_DEPTH {
+            return panic("stack overflow".to_string(), loc);
+        }
+        self.frames.push(HashMap::new());
+        for (p, arg) in f.params.iter().zip(args) {
+            self.declare(&p.name, arg);
+        }
+        let flow = self.stmts(&f.body.stmts);
+        self.frames.pop();
+        match flow? {
+            Flow::Return(value) => return Ok(value),
+            _ => return Ok(Val::Void),
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Result<Flow> {
+        for stmt in stmts {
+            match self.stmt(stmt)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        return Ok(Flow::Next);
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<Flow> {
+        match stmt.kind {
+            StmtKind::Block(ref block) => return self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => {
+                self.expr(e)?;
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec)?;
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs)?,
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => {
+                let place = self.place(lhs)?;
+                let x = self.load(&place);
+                let y = self.expr(rhs)?;
+                let value = self.arith(op, lhs.ty.unwrap_or(INVALID), x, y, &stmt.loc)?;
+                self.store(place, value);
+            }
+            StmtKind::Incr(ref e) | StmtKind::Decr(ref e) => {
+                let place = self.place(e)?;
+                let x = self.load(&place);
+                let value = match x {
+                    Val::Float(f) if matches!(stmt.kind, StmtKind::Incr(_)) => Val::Float(f + 1.0),
+                    Val::Float(f) => Val::Float(f - 1.0),
+                    Val::Int(n) if matches!(stmt.kind, StmtKind::Incr(_)) => {
+                        Val::Int(self.wrap(e.ty.unwrap_or(INVALID), n.wrapping_add(1)))
+                    }
+                    Val::Int(n) => Val::Int(self.wrap(e.ty.unwrap_or(INVALID), n.wrapping_sub(1))),
+                    x => x,
+                };
+                self.store(place, value);
+            }
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                let values = self.exprs(values)?;
+                for (id, value) in names.iter().zip(values) {
+                    self.declare(id, value);
+                }
+            }
+            StmtKind::Print(ref args) => self.print(args, false, &stmt.loc)?,
+            StmtKind::Println(ref args) => self.print(args, true, &stmt.loc)?,
+            StmtKind::Return(None) => return Ok(Flow::Return(Val::Void)),
+            StmtKind::Return(Some(ref e)) => return Ok(Flow::Return(self.expr(e)?)),
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::If(ref if_stmt) => return self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => return self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => return self.for_stmt(for_stmt),
+        }
+        return Ok(Flow::Next);
+    }
+
+    /// The operands of the indexing expressions on the left and
+    /// the values on the right are all evaluated before anything
+    /// is assigned, or any index on the left is found out of range.
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr]) -> Result<()> {
+        let mut places = Vec::new();
+        for e in lhs {
+            match e.kind {
+                ExprKind::Blank => places.push(None),
+                _ => places.push(Some(self.target(e)?)),
+            }
+        }
+        let values = self.exprs(rhs)?;
+        for (place, value) in places.into_iter().zip(values) {
+            if let Some(mut place) = place {
+                if let Some(err) = place.fault.take() {
+                    return Err(err);
+                }
+                self.store(place, value);
+            }
+        }
+        return Ok(());
+    }
+
+    /// Formats the values of the arguments and writes them all
+    /// at once.
+    fn print(&mut self, args: &[Expr], newline: bool, loc: &Loc) -> Result<()> {
+        let values = self.exprs(args)?;
+        let mut bytes = Vec::new();
+        for (i, value) in values.iter().enumerate() {
+            if newline && i > 0 {
+                bytes.push(b' ');
+            }
+            match *value {
+                Val::Int(n) => bytes.extend_from_slice(n.to_string().as_bytes()),
+                Val::Float(f) => bytes.extend_from_slice(format_float(f).as_bytes()),
+                Val::Bool(b) => bytes.extend_from_slice(b.to_string().as_bytes()),
+                Val::Str(ref s) => bytes.extend_from_slice(s),
+                _ => {}
+            }
+        }
+        if newline {
+            bytes.push(b'\n');
+        }
+        if let Err(err) = self.out.write_all(&bytes) {
+            return panic(err.to_string(), loc);
+        }
+        return Ok(());
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) -> Result<Flow> {
+        if let Some(ref init) = if_stmt.init {
+            self.stmt(init)?;
+        }
+        if self.expr(&if_stmt.cond)?.bool() {
+            return self.stmts(&if_stmt.then.stmts);
+        }
+        match if_stmt.els {
+            Some(ref els) => return self.stmt(els),
+            None => return Ok(Flow::Next),
+        }
+    }
+
+    /// The case expressions are evaluated from top to bottom and
+    /// left to right until one of them matches; the `default`
+    /// clause is executed if none does.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) -> Result<Flow> {
+        if let Some(ref init) = switch_stmt.init {
+            self.stmt(init)?;
+        }
+        let tag = match switch_stmt.tag {
+            Some(ref tag) => Some((self.expr(tag)?, tag.ty.unwrap_or(INVALID))),
+            None => None,
+        };
+        let mut selected = None;
+        'clauses: for case in &switch_stmt.clauses {
+            if let Some(ref exprs) = case.exprs {
+                for e in exprs {
+                    let value = self.expr(e)?;
+                    let matches = match tag {
+                        Some((ref tag, ty)) => self.equal(ty, tag, &value),
+                        None => value.bool(),
+                    };
+                    if matches {
+                        selected = Some(case);
+                        break 'clauses;
+                    }
+                }
+            }
+        }
+        if selected.is_none() {
+            selected = switch_stmt.clauses.iter().find(|c| c.exprs.is_none());
+        }
+        match selected {
+            Some(case) => match self.stmts(&case.body)? {
+                Flow::Break => return Ok(Flow::Next),
+                flow => return Ok(flow),
+            },
+            None => return Ok(Flow::Next),
+        }
+    }
+
+    fn for_stmt(&mut self, for_stmt: &ForStmt) -> Result<Flow> {
+        if let Some(ref init) = for_stmt.init {
+            self.stmt(init)?;
+        }
+        loop {
+            if let Some(ref cond) = for_stmt.cond {
+                if !self.expr(cond)?.bool() {
+                    break;
+                }
+            }
+            match self.stmts(&for_stmt.body.stmts)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Next | Flow::Continue => {}
+            }
+            if let Some(ref post) = for_stmt.post {
+                self.stmt(post)?;
+            }
+        }
+        return Ok(Flow::Next);
+    }
+
+
+    // VARIABLES
+
+    /// Evaluates the operands of an addressable expression (or of
+    /// an element of a temporary array or struct) and returns
+    /// where its value is stored.
+    fn place(&mut self, e: &Expr) -> Result<Place> {
+        let mut place = self.target(e)?;
+        match place.fault.take() {
+            Some(err) => return Err(err),
+            None => return Ok(place),
+        }
+    }
+
+    /// Like `place`, but an index out of range only panics when
+    /// the place is stored to, once all the operands are evaluated.
+    fn target(&mut self, e: &Expr) -> Result<Place> {
+        match e.kind {
+            ExprKind::Id(Ident { sym: Some(sym), .. })
+                if self.table.symbol(sym).kind == SymbolKind::Var => {
+                let local = self.frames.last().is_some_and(|frame| frame.contains_key(&sym));
+                let root = if local { Root::Local(sym) } else { Root::Global(sym) };
+                return Ok(Place { root: root, path: Vec::new(), fault: None });
+            }
+            ExprKind::Index(ref a, ref i) => {
+                match *self.types.under(a.ty.unwrap_or(INVALID)) {
+                    TypeKind::Slice(_) => {
+                        let s = match self.expr(a)? {
+                            Val::Slice(s) => s,
+                            _ => return panic("indexing a non-slice".to_string(), &e.loc),
+                        };
+                        let i = self.expr(i)?.int();
+                        let place = match self.check_index(i, s.len, &e.loc) {
+                            Ok(i) => Place { root: Root::Elem(s.data, i), path: Vec::new(), fault: None },
+                            Err(err) => Place { root: Root::Temp(Val::Void), path: Vec::new(), fault: Some(err) },
+                        };
+                        return Ok(place);
+                    }
+                    TypeKind::Array(len, _) => {
+                        let mut place = self.target(a)?;
+                        let i = self.expr(i)?.int();
+                        match self.check_index(i, len as usize, &e.loc) {
+                            Ok(i) => place.path.push(i),
+                            Err(err) => {
+                                place.fault.get_or_insert(err);
+                                place.path.push(0);
+                            }
+                        }
+                        return Ok(place);
+                    }
+                    _ => {}
+                }
+            }
+            ExprKind::Selector(ref x, ref field) => {
+                let index = match *self.types.under(x.ty.unwrap_or(INVALID)) {
+                    TypeKind::Struct(ref fields) => fields.iter().position(|f| f.0 == field.name),
+                    _ => None,
+                };
+                let mut place = self.target(x)?;
+                place.path.push(index.unwrap_or(0));
+                return Ok(place);
+            }
+            // Functions and types are not values.
+            ExprKind::Id(_) => return Ok(Place { root: Root::Temp(Val::Void), path: Vec::new(), fault: None }),
+            _ => {}
+        }
+        let value = self.expr(e)?;
+        return Ok(Place { root: Root::Temp(value), path: Vec::new(), fault: None });
+    }
+
+    fn check_index(&self, i: i64, len: usize, loc: &Loc) -> Result<usize> {
+        if i < 0 || i as u64 >= len as u64 {
+            return panic(format!("index out of range [{}] with length {}", i, len), loc);
+        }
+        return Ok(i as usize);
+    }
+
+    fn load(&self, place: &Place) -> Val {
+        fn walk(mut v: &Val, path: &[usize]) -> Val {
+            for &i in path {
+                v = match *v {
+                    Val::Array(ref elems) | Val::Struct(ref elems) => &elems[i],
+                    _ => break,
+                };
+            }
+            return v.clone();
+        }
+        match place.root {
+            Root::Local(sym) => return walk(&self.frames.last().unwrap()[&sym], &place.path),
+            Root::Global(sym) => return walk(&self.globals[&sym], &place.path),
+            Root::Elem(ref data, i) => return walk(&data.borrow()[i], &place.path),
+            Root::Temp(ref v) => return walk(v, &place.path),
+        }
+    }
+
+    fn store(&mut self, place: Place, value: Val) {
+        fn walk<'v>(mut v: &'v mut Val, path: &[usize]) -> &'v mut Val {
+            for &i in path {
+                v = match *v {
+                    Val::Array(ref mut elems) | Val::Struct(ref mut elems) => &mut elems[i],
+                    _ => break,
+                };
+            }
+            return v;
+        }
+        match place.root {
+            Root::Local(sym) => {
+                if let Some(v) = self.frames.last_mut().and_then(|frame| frame.get_mut(&sym)) {
+                    *walk(v, &place.path) = value;
+                }
+            }
+            Root::Global(sym) => {
+                if let Some(v) = self.globals.get_mut(&sym) {
+                    *walk(v, &place.path) = value;
+                }
+            }
+            Root::Elem(data, i) => *walk(&mut data.borrow_mut()[i], &place.path) = value,
+            Root::Temp(_) => {}
+        }
+    }
+
+
+    // EXPRESSIONS
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Result<Vec<Val>> {
+        let mut values = Vec::with_capacity(exprs.len());
+        for e in exprs {
+            values.push(self.expr(e)?);
+        }
+        return Ok(values);
+    }
+
+    fn expr(&mut self, e: &Expr) -> Result<Val> {
+        let ty = e.ty.unwrap_or(INVALID);
+        if let Some(ref value) = e.value {
+            return Ok(self.constant(value, ty));
+        }
+        match e.kind {
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => {
+                let place = self.place(e)?;
+                return Ok(self.load(&place));
+            }
+            ExprKind::Unary(op, ref x) => {
+                let x = self.expr(x)?;
+                return Ok(self.unary(op, ty, x));
+            }
+            ExprKind::Binary(BinOp::And, ref x, ref y) => {
+                if !self.expr(x)?.bool() {
+                    return Ok(Val::Bool(false));
+                }
+                return self.expr(y);
+            }
+            ExprKind::Binary(BinOp::Or, ref x, ref y) => {
+                if self.expr(x)?.bool() {
+                    return Ok(Val::Bool(true));
+                }
+                return self.expr(y);
+            }
+            ExprKind::Binary(op, ref x, ref y) => {
+                let operand_ty = x.ty.unwrap_or(INVALID);
+                let x = self.expr(x)?;
+                let y = self.expr(y)?;
+                if op.is_comparison() {
+                    return Ok(Val::Bool(self.compare(op, operand_ty, &x, &y)));
+                }
+                return self.arith(op, operand_ty, x, y, &e.loc);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let sym = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => sym,
+                    _ => return Ok(Val::Void),
+                };
+                match self.table.symbol(sym).kind {
+                    SymbolKind::Type => {
+                        let arg_ty = args[0].ty.unwrap_or(INVALID);
+                        let x = self.expr(&args[0])?;
+                        return Ok(self.conversion(ty, arg_ty, x));
+                    }
+                    SymbolKind::Builtin => {
+                        let x = self.expr(&args[0])?;
+                        let cap = self.table.symbol(sym).name == "cap";
+                        return Ok(Val::Int(builtin(cap, &x) as i64));
+                    }
+                    _ => {
+                        let values = self.exprs(args)?;
+                        let f = match self.funcs.get(&sym) {
+                            Some(&f) => f,
+                            None => return Ok(Val::Void),
+                        };
+                        return self.call(f, values, &e.loc);
+                    }
+                }
+            }
+            ExprKind::Append(ref s, ref x) => {
+                let s = self.expr(s)?;
+                let x = self.expr(x)?;
+                let elem = match *self.types.under(ty) {
+                    TypeKind::Slice(elem) => elem,
+                    _ => INVALID,
+                };
+                match s {
+                    Val::Slice(s) => return Ok(Val::Slice(self.append(s, x, elem))),
+                    s => return Ok(s),
+                }
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => return Ok(Val::Void),
+        }
+    }
+
+    fn constant(&self, value: &Value, ty: TypeId) -> Val {
+        let ty = self.types.default_type(ty);
+        match *value {
+            Value::Bool(b) => return Val::Bool(b),
+            Value::String(ref s) => return Val::Str(Rc::new(constant::string_bytes(s))),
+            Value::Int(_) | Value::Float(_) if self.types.is_float(ty) => {
+                return Val::Float(value.to_rat().map_or(0.0, |r| r.to_f64()));
+            }
+            Value::Int(_) | Value::Float(_) => {
+                return Val::Int(value.to_int().and_then(|n| n.to_i64()).unwrap_or(0));
+            }
+        }
+    }
+
+    fn unary(&self, op: UnOp, ty: TypeId, x: Val) -> Val {
+        match (op, x) {
+            (UnOp::Neg, Val::Int(n)) => return Val::Int(self.wrap(ty, n.wrapping_neg())),
+            (UnOp::Neg, Val::Float(f)) => return Val::Float(-f),
+            (UnOp::Not, Val::Bool(b)) => return Val::Bool(!b),
+            (UnOp::BitNot, Val::Int(n)) => return Val::Int(!n),
+            (_, x) => return x,
+        }
+    }
+
+    /// Applies an arithmetic or bitwise operator to operands of
+    /// type `ty`.  Integer operations wrap around and division by
+    /// zero panics, as in Go.
+    fn arith(&self, op: BinOp, ty: TypeId, x: Val, y: Val, loc: &Loc) -> Result<Val> {
+        let (x, y) = match (x, y) {
+            (Val::Float(x), Val::Float(y)) => {
+                let f = match op {
+                    BinOp::Add => x + y,
+                    BinOp::Sub => x - y,
+                    BinOp::Mul => x * y,
+                    _ => x / y,
+                };
+                return Ok(Val::Float(f));
+            }
+            (Val::Str(x), Val::Str(y)) => {
+                let mut s = Vec::with_capacity(x.len() + y.len());
+                s.extend_from_slice(&x);
+                s.extend_from_slice(&y);
+                return Ok(Val::Str(Rc::new(s)));
+            }
+            (x, y) => (x.int(), y.int()),
+        };
+        let n = match op {
+            BinOp::Add => x.wrapping_add(y),
+            BinOp::Sub => x.wrapping_sub(y),
+            BinOp::Mul => x.wrapping_mul(y),
+            BinOp::Div | BinOp::Rem if y == 0 => {
+                return panic("integer divide by zero".to_string(), loc);
+            }
+            BinOp::Div => x.wrapping_div(y),
+            BinOp::Rem => x.wrapping_rem(y),
+            BinOp::Shl | BinOp::Shr if y < 0 => {
+                return panic("negative shift amount".to_string(), loc);
+            }
+            BinOp::Shl if y >= 64 => 0,
+            BinOp::Shl => ((x as u64) << y) as i64,
+            BinOp::Shr => x >> y.min(63),
+            BinOp::BitAnd => x & y,
+            BinOp::BitOr => x | y,
+            BinOp::BitXor => x ^ y,
+            BinOp::BitClear => x & !y,
+            _ => 0,
+        };
+        return Ok(Val::Int(self.wrap(ty, n)));
+    }
+
+    fn compare(&self, op: BinOp, ty: TypeId, x: &Val, y: &Val) -> bool {
+        match (op, x, y) {
+            (BinOp::Eq, _, _) => return self.equal(ty, x, y),
+            (BinOp::Ne, _, _) => return !self.equal(ty, x, y),
+            (_, Val::Int(x), Val::Int(y)) => return ordered(op, x, y),
+            (_, Val::Float(x), Val::Float(y)) => return ordered(op, x, y),
+            (_, Val::Str(x), Val::Str(y)) => return ordered(op, x, y),
+            _ => return false,
+        }
+    }
+
+    /// Compares values of type `ty`; arrays and structs are equal
+    /// if their elements and non-blank fields are.
+    fn equal(&self, ty: TypeId, x: &Val, y: &Val) -> bool {
+        let ty = self.types.default_type(ty);
+        match (self.types.under(ty), x, y) {
+            (&TypeKind::Array(_, elem), Val::Array(x), Val::Array(y)) => {
+                return x.iter().zip(y).all(|(x, y)| self.equal(elem, x, y));
+            }
+            (TypeKind::Struct(fields), Val::Struct(x), Val::Struct(y)) => {
+                return fields.iter().zip(x.iter().zip(y))
+                    .filter(|&(field, _)| field.0 != "_")
+                    .all(|(field, (x, y))| self.equal(field.1, x, y));
+            }
+            (_, Val::Int(x), Val::Int(y)) => return x == y,
+            (_, Val::Float(x), Val::Float(y)) => return x == y,
+            (_, Val::Bool(x), Val::Bool(y)) => return x == y,
+            (_, Val::Str(x), Val::Str(y)) => return x == y,
+            _ => return false,
+        }
+    }
+
+    fn conversion(&self, ty: TypeId, arg_ty: TypeId, x: Val) -> Val {
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            // Invalid code points become U+FFFD.
+            let c = match x {
+                Val::Int(n) if n >= 0 && n <= u32::MAX as i64 => char::from_u32(n as u32),
+                _ => None,
+            };
+            let c = c.unwrap_or('\u{fffd}');
+            return Val::Str(Rc::new(constant::string_bytes(&constant::encode_rune(c))));
+        }
+        if self.types.is_float(ty) {
+            match x {
+                Val::Int(n) => return Val::Float(n as f64),
+                x => return x,
+            }
+        }
+        if self.types.is_integer(ty) {
+            match x {
+                Val::Float(f) => return Val::Int(self.wrap(ty, f as i64)),
+                Val::Int(n) => return Val::Int(self.wrap(ty, n)),
+                x => return x,
+            }
+        }
+        return x;
+    }
+
+    /// Appends `x` to `s`, copying the elements to a new backing
+    /// array twice as large if `s` is full.
+    fn append(&self, s: Slice, x: Val, elem: TypeId) -> Slice {
+        let cap = s.data.borrow().len();
+        if s.len < cap {
+            s.data.borrow_mut()[s.len] = x;
+            return Slice { data: s.data, len: s.len + 1 };
+        }
+        let new_cap = if cap == 0 { 1 } else { 2 * cap };
+        let mut data = Vec::with_capacity(new_cap);
+        data.extend_from_slice(&s.data.borrow()[.. s.len]);
+        data.push(x);
+        while data.len() < new_cap {
+            data.push(self.zero(elem));
+        }
+        return Slice { data: Rc::new(RefCell::new(data)), len: s.len + 1 };
+    }
+}
+
+fn builtin(cap: bool, x: &Val) -> usize {
+    match *x {
+        Val::Str(ref s) => return s.len(),
+        Val::Array(ref elems) => return elems.len(),
+        Val::Slice(ref s) if cap => return s.data.borrow().len(),
+        Val::Slice(ref s) => return s.len,
+        _ => return 0,
+    }
+}
+
+fn ordered<T: PartialOrd>(op: BinOp, x: T, y: T) -> bool {
+    match op {
+        BinOp::Lt => return x < y,
+        BinOp::Le => return x <= y,
+        BinOp::Gt => return x > y,
+        _ => return x >= y,
+    }
+}
+
+/// Formats a float as Go's `print` does: with a sign, 7
+/// significant digits and a three-digit exponent.
+pub fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "+Inf" } else { "-Inf" }.to_string();
+    }
+    let s = format!("{:+.6e}", f);
+    let (mantissa, exp) = s.split_at(s.find('e').unwrap_or(s.len()));
+    let exp: i32 = exp[1 ..].parse().unwrap_or(0);
+    return format!("{}e{}{:03}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs());
+}
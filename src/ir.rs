@@ -0,0 +1,821 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+
+use ast::{BinOp, UnOp};
+use bytecode::{Const, Pos};
+
+/// A value of a function: the result of one of its instructions.
+pub type Value = usize;
+
+/// The index of a basic block in its function.
+pub type BlockId = usize;
+
+/// The type of a value.  Integer operations wrap around at the
+/// width of their type: 64 bits for `int`, 32 bits for `rune`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    Rune,
+    Str,
+    Slice(Box<Ty>),
+    Array(u64, Box<Ty>),
+    /// The fields, and whether each of them is compared
+    /// (blank fields are not).
+    Struct(Vec<(bool, Ty)>),
+}
+
+impl Ty {
+    pub fn is_integer(&self) -> bool {
+        return *self == Ty::Int || *self == Ty::Rune;
+    }
+
+    pub fn is_aggregate(&self) -> bool {
+        match *self {
+            Ty::Array(_, _) | Ty::Struct(_) => return true,
+            _ => return false,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Ty::Int => write!(f, "int"),
+            Ty::Float => write!(f, "float64"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Rune => write!(f, "rune"),
+            Ty::Str => write!(f, "string"),
+            Ty::Slice(ref elem) => write!(f, "[]{}", elem),
+            Ty::Array(len, ref elem) => write!(f, "[{}]{}", len, elem),
+            Ty::Struct(ref fields) => {
+                write!(f, "struct {{")?;
+                for (i, &(compared, ref field)) in fields.iter().enumerate() {
+                    write!(f, "{}{}{}", if i > 0 { "; " } else { " " },
+                           if compared { "" } else { "_ " }, field)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+/// An operation of the intermediate representation.
+///
+/// Operations take at most three operands, which are values of
+/// the function.  Arrays and structs are values like the others:
+/// `SetIndex` and `SetField` return a new aggregate rather than
+/// modifying their operand, so that local variables of any type
+/// are in SSA form.  Only package variables (`Load`, `Store`)
+/// and the backing arrays of slices (`LoadElem`, `StoreElem`)
+/// live in memory.
+///
+/// The operations that may panic are `Check`, the division,
+/// remainder and shifts of integers, and calls.  `LoadElem`,
+/// `StoreElem`, `Index` and `SetIndex` assume that their index
+/// has been checked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    /// The parameter of the function at an index; parameters are
+    /// only defined in the entry block.
+    Param(usize),
+    Const(Const),
+    /// The zero value of the type of the result.
+    Zero,
+    /// The value that comes from each predecessor of the block;
+    /// phi nodes come before the other instructions of a block.
+    Phi(Vec<(BlockId, Value)>),
+    /// Unary and binary operations, on operands of the same type
+    /// except for the count of shifts.  `&&` and `||` are
+    /// control flow, and `+` is not an operation.
+    Unary(UnOp, Value),
+    Binary(BinOp, Value, Value),
+    /// Converts a value to the type of the result.
+    Convert(Value),
+    /// Panics unless `0 <= index < len`: `Check(index, len)`.
+    Check(Value, Value),
+    Len(Value),
+    Cap(Value),
+    /// `a[i]` and `a` with `a[i]` replaced, for arrays.
+    Index(Value, Value),
+    SetIndex(Value, Value, Value),
+    /// `s.f` and `s` with `s.f` replaced, for structs.
+    Field(Value, usize),
+    SetField(Value, usize, Value),
+    /// Reads and writes an element of a slice.
+    LoadElem(Value, Value),
+    StoreElem(Value, Value, Value),
+    Append(Value, Value),
+    /// Reads and writes a package variable.
+    Load(usize),
+    Store(usize, Value),
+    Call(usize, Vec<Value>),
+    Print(Vec<Value>),
+    Println(Vec<Value>),
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Op::Param(_) => return "param",
+            Op::Const(_) => return "const",
+            Op::Zero => return "zero",
+            Op::Phi(_) => return "phi",
+            Op::Unary(op, _) => return unop_name(op),
+            Op::Binary(op, _, _) => return binop_name(op),
+            Op::Convert(_) => return "convert",
+            Op::Check(_, _) => return "check",
+            Op::Len(_) => return "len",
+            Op::Cap(_) => return "cap",
+            Op::Index(_, _) => return "index",
+            Op::SetIndex(_, _, _) => return "setindex",
+            Op::Field(_, _) => return "field",
+            Op::SetField(_, _, _) => return "setfield",
+            Op::LoadElem(_, _) => return "loadelem",
+            Op::StoreElem(_, _, _) => return "storeelem",
+            Op::Append(_, _) => return "append",
+            Op::Load(_) => return "load",
+            Op::Store(_, _) => return "store",
+            Op::Call(_, _) => return "call",
+            Op::Print(_) => return "print",
+            Op::Println(_) => return "println",
+        }
+    }
+
+    /// Returns the values that the operation uses, in order.
+    pub fn operands(&self) -> Vec<Value> {
+        match *self {
+            Op::Param(_) | Op::Const(_) | Op::Zero | Op::Load(_) => return Vec::new(),
+            Op::Phi(ref incoming) => return incoming.iter().map(|&(_, v)| v).collect(),
+            Op::Unary(_, x) | Op::Convert(x) | Op::Len(x) | Op::Cap(x) | Op::Field(x, _)
+            | Op::Store(_, x) => return vec![x],
+            Op::Binary(_, x, y) | Op::Check(x, y) | Op::Index(x, y) | Op::SetField(x, _, y)
+            | Op::LoadElem(x, y) | Op::Append(x, y) => return vec![x, y],
+            Op::SetIndex(x, y, z) | Op::StoreElem(x, y, z) => return vec![x, y, z],
+            Op::Call(_, ref args) | Op::Print(ref args) | Op::Println(ref args) => return args.clone(),
+        }
+    }
+
+    /// Replaces every operand `v` with `f(v)`.
+    pub fn map_operands<F: FnMut(Value) -> Value>(&mut self, mut f: F) {
+        match *self {
+            Op::Param(_) | Op::Const(_) | Op::Zero | Op::Load(_) => {}
+            Op::Phi(ref mut incoming) => {
+                for &mut (_, ref mut v) in incoming {
+                    *v = f(*v);
+                }
+            }
+            Op::Unary(_, ref mut x) | Op::Convert(ref mut x) | Op::Len(ref mut x) | Op::Cap(ref mut x)
+            | Op::Field(ref mut x, _) | Op::Store(_, ref mut x) => *x = f(*x),
+            Op::Binary(_, ref mut x, ref mut y) | Op::Check(ref mut x, ref mut y)
+            | Op::Index(ref mut x, ref mut y) | Op::SetField(ref mut x, _, ref mut y)
+            | Op::LoadElem(ref mut x, ref mut y) | Op::Append(ref mut x, ref mut y) => {
+                *x = f(*x);
+                *y = f(*y);
+            }
+            Op::SetIndex(ref mut x, ref mut y, ref mut z) | Op::StoreElem(ref mut x, ref mut y, ref mut z) => {
+                *x = f(*x);
+                *y = f(*y);
+                *z = f(*z);
+            }
+            Op::Call(_, ref mut args) | Op::Print(ref mut args) | Op::Println(ref mut args) => {
+                for arg in args {
+                    *arg = f(*arg);
+                }
+            }
+        }
+    }
+}
+
+pub fn unop_name(op: UnOp) -> &'static str {
+    match op {
+        UnOp::Plus => return "plus",
+        UnOp::Neg => return "neg",
+        UnOp::Not => return "not",
+        UnOp::BitNot => return "compl",
+    }
+}
+
+pub fn binop_name(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Or => return "lor",
+        BinOp::And => return "land",
+        BinOp::Eq => return "eq",
+        BinOp::Ne => return "ne",
+        BinOp::Lt => return "lt",
+        BinOp::Le => return "le",
+        BinOp::Gt => return "gt",
+        BinOp::Ge => return "ge",
+        BinOp::Add => return "add",
+        BinOp::Sub => return "sub",
+        BinOp::BitOr => return "or",
+        BinOp::BitXor => return "xor",
+        BinOp::Mul => return "mul",
+        BinOp::Div => return "div",
+        BinOp::Rem => return "rem",
+        BinOp::Shl => return "shl",
+        BinOp::Shr => return "shr",
+        BinOp::BitAnd => return "and",
+        BinOp::BitClear => return "andnot",
+    }
+}
+
+/// An instruction: an operation, the value it defines (`None`
+/// for stores, prints and calls of functions without a result)
+/// and the source position it comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inst {
+    pub result: Option<Value>,
+    pub op: Op,
+    pub pos: Pos,
+}
+
+/// How control leaves a basic block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Jump(BlockId),
+    /// Goes to the first block if the value is true, to the
+    /// second otherwise.
+    Branch(Value, BlockId, BlockId),
+    Return(Option<Value>),
+    /// The end of a function with a result, which the type
+    /// checker makes sure is never reached.
+    Unreachable,
+}
+
+impl Term {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match *self {
+            Term::Jump(b) => return vec![b],
+            Term::Branch(_, t, f) => return vec![t, f],
+            Term::Return(_) | Term::Unreachable => return Vec::new(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match *self {
+            Term::Branch(v, _, _) | Term::Return(Some(v)) => return vec![v],
+            _ => return Vec::new(),
+        }
+    }
+
+    pub fn map_operands<F: FnMut(Value) -> Value>(&mut self, mut f: F) {
+        match *self {
+            Term::Branch(ref mut v, _, _) | Term::Return(Some(ref mut v)) => *v = f(*v),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub term: Term,
+    /// The position of the terminator.
+    pub pos: Pos,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Ty>,
+    pub result: Option<Ty>,
+    /// The basic blocks; the first one is the entry block,
+    /// which has no predecessors.
+    pub blocks: Vec<Block>,
+    /// The type of every value.
+    pub types: Vec<Ty>,
+}
+
+impl Function {
+    pub fn new(name: &str, params: Vec<Ty>, result: Option<Ty>) -> Function {
+        Function {
+            name: name.to_string(),
+            params: params,
+            result: result,
+            blocks: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// Returns a new value of type `ty`.
+    pub fn new_value(&mut self, ty: Ty) -> Value {
+        self.types.push(ty);
+        return self.types.len() - 1;
+    }
+
+    /// Returns the predecessors of every block, in the order of
+    /// the blocks; a block that branches twice to the same block
+    /// is listed once.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (b, block) in self.blocks.iter().enumerate() {
+            for s in block.term.successors() {
+                if s < preds.len() && !preds[s].contains(&b) {
+                    preds[s].push(b);
+                }
+            }
+        }
+        return preds;
+    }
+
+    /// Returns the blocks reachable from the entry block in
+    /// reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut order = Vec::new();
+        let mut visited = vec![false; self.blocks.len()];
+        // The blocks being visited, with their successors.
+        let mut stack = Vec::new();
+        if !self.blocks.is_empty() {
+            visited[0] = true;
+            stack.push((0, self.blocks[0].term.successors()));
+        }
+        while let Some(&mut (b, ref mut succs)) = stack.last_mut() {
+            match succs.pop() {
+                Some(s) if s < visited.len() && !visited[s] => {
+                    visited[s] = true;
+                    let next = self.blocks[s].term.successors();
+                    stack.push((s, next));
+                }
+                Some(_) => {}
+                None => {
+                    order.push(b);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        return order;
+    }
+}
+
+/// Numbers the values of a function in the order in which the
+/// blocks define them, dropping the values that are no longer
+/// defined.
+pub fn renumber(f: &mut Function) {
+    let mut numbers = vec![None; f.types.len()];
+    let mut types = Vec::new();
+    for block in &mut f.blocks {
+        for inst in &mut block.insts {
+            if let Some(v) = inst.result {
+                numbers[v] = Some(types.len());
+                types.push(f.types[v].clone());
+                inst.result = numbers[v];
+            }
+        }
+    }
+    let number = |v: Value| numbers.get(v).cloned().unwrap_or(None).unwrap_or(v);
+    for block in &mut f.blocks {
+        for inst in &mut block.insts {
+            inst.op.map_operands(number);
+        }
+        block.term.map_operands(number);
+    }
+    f.types = types;
+}
+
+/// Removes the blocks for which `keep` is false, which no kept
+/// block may branch to, and the operands of phi nodes that come
+/// from them.
+pub fn retain_blocks(f: &mut Function, keep: &[bool]) {
+    let mut numbers = vec![None; f.blocks.len()];
+    let mut n = 0;
+    for (b, &kept) in keep.iter().enumerate().take(f.blocks.len()) {
+        if kept {
+            numbers[b] = Some(n);
+            n += 1;
+        }
+    }
+    let blocks = mem::take(&mut f.blocks);
+    for (b, mut block) in blocks.into_iter().enumerate() {
+        if numbers[b].is_none() {
+            continue;
+        }
+        for inst in &mut block.insts {
+            if let Op::Phi(ref mut incoming) = inst.op {
+                incoming.retain(|&(p, _)| numbers.get(p).is_some_and(|n| n.is_some()));
+                for &mut (ref mut p, _) in incoming {
+                    *p = numbers[*p].unwrap_or(0);
+                }
+            }
+        }
+        let number = |b: BlockId| numbers.get(b).cloned().unwrap_or(None).unwrap_or(0);
+        block.term = match block.term {
+            Term::Jump(target) => Term::Jump(number(target)),
+            Term::Branch(v, t, e) => Term::Branch(v, number(t), number(e)),
+            term => term,
+        };
+        f.blocks.push(block);
+    }
+}
+
+/// Replaces the uses of values with other values, following
+/// chains of replacements.
+pub fn replace_uses(f: &mut Function, replaced: &HashMap<Value, Value>) {
+    let resolve = |mut v: Value| {
+        while let Some(&w) = replaced.get(&v) {
+            if w == v {
+                break;
+            }
+            v = w;
+        }
+        return v;
+    };
+    for block in &mut f.blocks {
+        for inst in &mut block.insts {
+            inst.op.map_operands(resolve);
+        }
+        block.term.map_operands(resolve);
+    }
+}
+
+/// Returns the immediate dominator of every block, with the
+/// algorithm of Cooper, Harvey and Kennedy.  The entry block is
+/// its own dominator, and unreachable blocks have none.
+pub fn dominators(f: &Function) -> Vec<Option<BlockId>> {
+    let order = f.reverse_postorder();
+    let mut number = vec![usize::MAX; f.blocks.len()];
+    for (i, &b) in order.iter().enumerate() {
+        number[b] = i;
+    }
+    let preds = f.predecessors();
+    let mut idom = vec![None; f.blocks.len()];
+    if order.is_empty() {
+        return idom;
+    }
+    idom[0] = Some(0);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in order.iter().skip(1) {
+            let mut new_idom = None;
+            for &p in preds[b].iter().filter(|&&p| idom[p].is_some()) {
+                new_idom = match new_idom {
+                    None => Some(p),
+                    Some(mut x) => {
+                        let mut y = p;
+                        while x != y {
+                            while number[x] > number[y] {
+                                x = idom[x].unwrap_or(0);
+                            }
+                            while number[y] > number[x] {
+                                y = idom[y].unwrap_or(0);
+                            }
+                        }
+                        Some(x)
+                    }
+                };
+            }
+            if new_idom != idom[b] {
+                idom[b] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    return idom;
+}
+
+/// Returns whether block `a` dominates block `b`.
+pub fn dominates(idom: &[Option<BlockId>], a: BlockId, mut b: BlockId) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        match idom[b] {
+            Some(d) if d != b => b = d,
+            _ => return false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub ty: Ty,
+}
+
+/// A program in SSA form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    /// The name of the source file.
+    pub filename: String,
+    /// The package variables, which start with their zero value.
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+    /// The function that initializes the package variables
+    /// and calls `main`.
+    pub entry: usize,
+}
+
+
+// LISTING
+
+/// Returns the text of a module: its package variables, then the
+/// blocks of every function.  Values are written `%n: type`
+/// where they are defined and `%n` where they are used.
+pub fn dump(module: &Module) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("; {}\n", module.filename));
+    if let Some(entry) = module.functions.get(module.entry) {
+        out.push_str(&format!("; entry {}\n", entry.name));
+    }
+    if !module.globals.is_empty() {
+        out.push('\n');
+        for g in &module.globals {
+            out.push_str(&format!("global @{} {}\n", g.name, g.ty));
+        }
+    }
+    for f in &module.functions {
+        out.push('\n');
+        out.push_str(&dump_function(module, f));
+    }
+    return out;
+}
+
+pub fn dump_function(module: &Module, f: &Function) -> String {
+    let params: Vec<String> = f.params.iter().map(|ty| ty.to_string()).collect();
+    let mut out = format!("func {}({})", f.name, params.join(", "));
+    if let Some(ref result) = f.result {
+        out.push_str(&format!(" {}", result));
+    }
+    out.push_str(" {\n");
+    for (b, block) in f.blocks.iter().enumerate() {
+        out.push_str(&format!("b{}:\n", b));
+        for inst in &block.insts {
+            out.push_str(&format!("    {}\n", dump_inst(module, f, inst)));
+        }
+        let term = match block.term {
+            Term::Jump(target) => format!("jump b{}", target),
+            Term::Branch(v, t, e) => format!("branch %{}, b{}, b{}", v, t, e),
+            Term::Return(Some(v)) => format!("ret %{}", v),
+            Term::Return(None) => "ret".to_string(),
+            Term::Unreachable => "unreachable".to_string(),
+        };
+        out.push_str(&format!("    {}\n", term));
+    }
+    out.push_str("}\n");
+    return out;
+}
+
+pub fn dump_inst(module: &Module, f: &Function, inst: &Inst) -> String {
+    let mut out = String::new();
+    if let Some(v) = inst.result {
+        let ty = f.types.get(v).map_or("?".to_string(), |ty| ty.to_string());
+        out.push_str(&format!("%{}: {} = ", v, ty));
+    }
+    out.push_str(inst.op.name());
+    let values = |vs: &[Value]| vs.iter().map(|v| format!("%{}", v)).collect::<Vec<String>>().join(", ");
+    let global = |g: usize| module.globals.get(g).map_or(format!("{}", g), |g| g.name.clone());
+    let operands = match inst.op {
+        Op::Param(i) => i.to_string(),
+        Op::Const(ref c) => c.to_string(),
+        Op::Zero => String::new(),
+        Op::Phi(ref incoming) => {
+            incoming.iter().map(|&(b, v)| format!("[b{}: %{}]", b, v)).collect::<Vec<String>>().join(", ")
+        }
+        Op::Field(x, i) => format!("%{}, {}", x, i),
+        Op::SetField(x, i, y) => format!("%{}, {}, %{}", x, i, y),
+        Op::Load(g) => format!("@{}", global(g)),
+        Op::Store(g, x) => format!("@{}, %{}", global(g), x),
+        Op::Call(callee, ref args) => {
+            let name = module.functions.get(callee).map_or(format!("{}", callee), |f| f.name.clone());
+            format!("{}({})", name, values(args))
+        }
+        ref op => values(&op.operands()),
+    };
+    if !operands.is_empty() {
+        out.push(' ');
+        out.push_str(&operands);
+    }
+    return out;
+}
+
+
+// VERIFICATION
+
+/// Checks that a module is well formed: every value is defined
+/// once, by an instruction that dominates its uses, phi nodes
+/// have one operand per predecessor, and the operands of every
+/// instruction have the types that its operation expects.
+pub fn verify(module: &Module) -> Result<(), String> {
+    match module.functions.get(module.entry) {
+        Some(f) if f.params.is_empty() && f.result.is_none() => {}
+        Some(_) => return Err("the entry function takes parameters or returns a value".to_string()),
+        None => return Err(format!("entry function {} out of range", module.entry)),
+    }
+    for f in &module.functions {
+        if let Err(msg) = verify_function(module, f) {
+            return Err(format!("func {}: {}", f.name, msg));
+        }
+    }
+    return Ok(());
+}
+
+fn verify_function(module: &Module, f: &Function) -> Result<(), String> {
+    if f.blocks.is_empty() {
+        return Err("no blocks".to_string());
+    }
+    // Where every value is defined: its block and its index in
+    // the block.
+    let mut defs = vec![None; f.types.len()];
+    for (b, block) in f.blocks.iter().enumerate() {
+        let mut phis = true;
+        for (i, inst) in block.insts.iter().enumerate() {
+            let at = format!("b{}: {}", b, dump_inst(module, f, inst));
+            match inst.op {
+                Op::Phi(_) if !phis => return Err(format!("{}: phi after other instructions", at)),
+                Op::Phi(_) => {}
+                Op::Param(_) if b != 0 => return Err(format!("{}: parameter outside the entry block", at)),
+                _ => phis = false,
+            }
+            if let Some(v) = inst.result {
+                match defs.get(v) {
+                    Some(&None) => defs[v] = Some((b, i)),
+                    Some(&Some(_)) => return Err(format!("{}: %{} defined twice", at, v)),
+                    None => return Err(format!("{}: %{} has no type", at, v)),
+                }
+            }
+        }
+        for s in block.term.successors() {
+            if s >= f.blocks.len() {
+                return Err(format!("b{}: branch to missing block b{}", b, s));
+            }
+        }
+    }
+
+    let preds = f.predecessors();
+    if !preds[0].is_empty() {
+        return Err("the entry block has predecessors".to_string());
+    }
+    let idom = dominators(f);
+    // Whether the definition of `v` dominates position `i` of
+    // block `b`; uses in unreachable blocks are not checked.
+    let dominated = |v: Value, b: BlockId, i: usize| -> Result<(), String> {
+        match defs.get(v).cloned() {
+            Some(Some((db, di))) => {
+                if idom[b].is_none() || (db == b && di < i) || (db != b && dominates(&idom, db, b)) {
+                    return Ok(());
+                }
+                return Err(format!("%{} does not dominate its use", v));
+            }
+            _ => return Err(format!("%{} is not defined", v)),
+        }
+    };
+    for (b, block) in f.blocks.iter().enumerate() {
+        for (i, inst) in block.insts.iter().enumerate() {
+            let at = format!("b{}: {}", b, dump_inst(module, f, inst));
+            if let Op::Phi(ref incoming) = inst.op {
+                let sources: HashSet<BlockId> = incoming.iter().map(|&(p, _)| p).collect();
+                if sources.len() != incoming.len() || sources != preds[b].iter().cloned().collect() {
+                    return Err(format!("{}: the operands do not match the predecessors", at));
+                }
+                // An operand must be available at the end of
+                // its predecessor.
+                for &(p, v) in incoming {
+                    let end = f.blocks[p].insts.len();
+                    dominated(v, p, end).map_err(|msg| format!("{}: {}", at, msg))?;
+                }
+            } else {
+                for v in inst.op.operands() {
+                    dominated(v, b, i).map_err(|msg| format!("{}: {}", at, msg))?;
+                }
+            }
+            check_types(module, f, inst).map_err(|msg| format!("{}: {}", at, msg))?;
+        }
+        for v in block.term.operands() {
+            dominated(v, b, block.insts.len()).map_err(|msg| format!("b{}: {}", b, msg))?;
+        }
+        match block.term {
+            Term::Branch(v, _, _) if f.types[v] != Ty::Bool => {
+                return Err(format!("b{}: branch on %{} of type {}", b, v, f.types[v]));
+            }
+            Term::Return(v) => {
+                let ty = v.map(|v| &f.types[v]);
+                if ty != f.result.as_ref() {
+                    return Err(format!("b{}: return of the wrong type", b));
+                }
+            }
+            _ => {}
+        }
+    }
+    return Ok(());
+}
+
+/// Checks the types of the operands and of the result of an
+/// instruction whose operands are defined.
+fn check_types(module: &Module, f: &Function, inst: &Inst) -> Result<(), String> {
+    let ty = |v: Value| &f.types[v];
+    let result = inst.result.map(|v| &f.types[v]);
+    let expect = |ok: bool| if ok { Ok(()) } else { Err("operands of the wrong type".to_string()) };
+    let scalar = |t: &Ty| !t.is_aggregate() && !matches!(*t, Ty::Slice(_));
+    let has_result = match inst.op {
+        Op::Store(_, _) | Op::StoreElem(_, _, _) | Op::Check(_, _) | Op::Print(_) | Op::Println(_) => false,
+        Op::Call(callee, _) => module.functions.get(callee).is_some_and(|g| g.result.is_some()),
+        _ => true,
+    };
+    if has_result != result.is_some() {
+        return Err("wrong number of results".to_string());
+    }
+    match inst.op {
+        Op::Param(i) => return expect(f.params.get(i) == result),
+        Op::Const(ref c) => {
+            let ok = match (c, result) {
+                (&Const::Int(_), Some(t)) => t.is_integer(),
+                (&Const::Float(_), Some(&Ty::Float)) => true,
+                (&Const::Bool(_), Some(&Ty::Bool)) => true,
+                (&Const::Str(_), Some(&Ty::Str)) => true,
+                _ => false,
+            };
+            return expect(ok);
+        }
+        Op::Zero => return Ok(()),
+        Op::Phi(ref incoming) => return expect(incoming.iter().all(|&(_, v)| Some(ty(v)) == result)),
+        Op::Unary(op, x) => {
+            let ok = match op {
+                UnOp::Neg => ty(x).is_integer() || *ty(x) == Ty::Float,
+                UnOp::Not => *ty(x) == Ty::Bool,
+                UnOp::BitNot => ty(x).is_integer(),
+                UnOp::Plus => false,
+            };
+            return expect(ok && result == Some(ty(x)));
+        }
+        Op::Binary(op, x, y) => {
+            let (x, y) = (ty(x), ty(y));
+            let ok = match op {
+                BinOp::And | BinOp::Or => false,
+                BinOp::Shl | BinOp::Shr => x.is_integer() && y.is_integer() && result == Some(x),
+                BinOp::Eq | BinOp::Ne => x == y && !matches!(*x, Ty::Slice(_)) && result == Some(&Ty::Bool),
+                BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                    x == y && (x.is_integer() || *x == Ty::Float || *x == Ty::Str) && result == Some(&Ty::Bool)
+                }
+                BinOp::Add => x == y && (x.is_integer() || *x == Ty::Float || *x == Ty::Str) && result == Some(x),
+                BinOp::Sub | BinOp::Mul | BinOp::Div => {
+                    x == y && (x.is_integer() || *x == Ty::Float) && result == Some(x)
+                }
+                _ => x == y && x.is_integer() && result == Some(x),
+            };
+            return expect(ok);
+        }
+        Op::Convert(x) => {
+            let ok = match (ty(x), result) {
+                (from, Some(to)) if from == to => true,
+                (from, Some(&Ty::Str)) => from.is_integer(),
+                (from, Some(to)) => (from.is_integer() || *from == Ty::Float) && (to.is_integer() || *to == Ty::Float),
+                _ => false,
+            };
+            return expect(ok);
+        }
+        Op::Check(i, n) => return expect(ty(i).is_integer() && *ty(n) == Ty::Int),
+        Op::Len(x) | Op::Cap(x) => {
+            let ok = match *ty(x) {
+                Ty::Str => inst.op == Op::Len(x),
+                Ty::Slice(_) | Ty::Array(_, _) => true,
+                _ => false,
+            };
+            return expect(ok && result == Some(&Ty::Int));
+        }
+        Op::Index(a, i) => match *ty(a) {
+            Ty::Array(_, ref elem) => return expect(ty(i).is_integer() && result == Some(elem)),
+            _ => return expect(false),
+        },
+        Op::SetIndex(a, i, x) => match *ty(a) {
+            Ty::Array(_, ref elem) => return expect(ty(i).is_integer() && ty(x) == &**elem && result == Some(ty(a))),
+            _ => return expect(false),
+        },
+        Op::Field(s, k) => match *ty(s) {
+            Ty::Struct(ref fields) => return expect(fields.get(k).map(|f| &f.1) == result),
+            _ => return expect(false),
+        },
+        Op::SetField(s, k, x) => match *ty(s) {
+            Ty::Struct(ref fields) => {
+                return expect(fields.get(k).map(|f| &f.1) == Some(ty(x)) && result == Some(ty(s)));
+            }
+            _ => return expect(false),
+        },
+        Op::LoadElem(s, i) => match *ty(s) {
+            Ty::Slice(ref elem) => return expect(ty(i).is_integer() && result == Some(elem)),
+            _ => return expect(false),
+        },
+        Op::StoreElem(s, i, x) => match *ty(s) {
+            Ty::Slice(ref elem) => return expect(ty(i).is_integer() && ty(x) == &**elem),
+            _ => return expect(false),
+        },
+        Op::Append(s, x) => match *ty(s) {
+            Ty::Slice(ref elem) => return expect(ty(x) == &**elem && result == Some(ty(s))),
+            _ => return expect(false),
+        },
+        Op::Load(g) => return expect(module.globals.get(g).map(|g| &g.ty) == result),
+        Op::Store(g, x) => return expect(module.globals.get(g).map(|g| &g.ty) == Some(ty(x))),
+        Op::Call(callee, ref args) => {
+            let g = match module.functions.get(callee) {
+                Some(g) => g,
+                None => return Err(format!("call of missing function {}", callee)),
+            };
+            let ok = g.params.len() == args.len() && g.params.iter().zip(args).all(|(p, &a)| p == ty(a));
+            return expect(ok && g.result.as_ref() == result);
+        }
+        Op::Print(ref args) | Op::Println(ref args) => return expect(args.iter().all(|&a| scalar(ty(a)))),
+    }
+}
@@ -0,0 +1,397 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::result;
+
+use ast::{BinOp, UnOp};
+use bytecode::{Const, Pos};
+use constant;
+use interp::{self, RuntimeError, MAX_DEPTH};
+use ir::{Function, Module, Op, Term, Ty, Value};
+use loc::Loc;
+
+type Result<T> = result::Result<T, RuntimeError>;
+
+/// A value of the IR.  Strings, arrays and structs are immutable
+/// and shared; slices share their backing array, which is the
+/// only mutable data besides the package variables.
+#[derive(Debug, Clone)]
+enum Val {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<Vec<u8>>),
+    /// The backing array (whose length is the capacity) and
+    /// the length of a slice.
+    Slice(Rc<RefCell<Vec<Val>>>, usize),
+    /// The elements of an array or the fields of a struct.
+    Agg(Rc<Vec<Val>>),
+}
+
+/// Executes the SSA form of a program, writing what it prints
+/// to `out`.  This is the reference against which the lowering
+/// to the IR and the passes over it are tested.
+pub fn run(module: &Module, out: &mut dyn Write) -> Result<()> {
+    let mut machine = Machine {
+        module: module,
+        globals: module.globals.iter().map(|g| zero(&g.ty)).collect(),
+        out: out,
+        depth: 0,
+    };
+    machine.call(module.entry, Vec::new(), Pos { line: 0, col: 0 })?;
+    return Ok(());
+}
+
+struct Machine<'a> {
+    module: &'a Module,
+    globals: Vec<Val>,
+    out: &'a mut dyn Write,
+    /// The number of calls in progress.
+    depth: usize,
+}
+
+impl<'a> Machine<'a> {
+    fn error(&self, msg: String, pos: Pos) -> RuntimeError {
+        return RuntimeError {
+            msg: msg,
+            loc: Loc::new(&self.module.filename, pos.line as usize, pos.col as usize),
+        };
+    }
+
+    fn call(&mut self, index: usize, args: Vec<Val>, pos: Pos) -> Result<Option<Val>> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("stack overflow".to_string(), pos));
+        }
+        self.depth += 1;
+        let result = self.execute(&self.module.functions[index], args);
+        self.depth -= 1;
+        return result;
+    }
+
+    fn execute(&mut self, f: &Function, args: Vec<Val>) -> Result<Option<Val>> {
+        let mut values = vec![Val::Int(0); f.types.len()];
+        let mut block = 0;
+        let mut pred = 0;
+        loop {
+            let b = &f.blocks[block];
+            // The phi nodes of a block read their operands before
+            // any of them is assigned.
+            let phis = b.insts.iter().take_while(|inst| match inst.op {
+                Op::Phi(_) => true,
+                _ => false,
+            }).count();
+            let mut incoming = Vec::with_capacity(phis);
+            for inst in &b.insts[.. phis] {
+                if let Op::Phi(ref sources) = inst.op {
+                    match sources.iter().find(|&&(p, _)| p == pred) {
+                        Some(&(_, v)) => incoming.push(values[v].clone()),
+                        None => return Err(self.error(invalid(f, "phi without operand for its predecessor"), inst.pos)),
+                    }
+                }
+            }
+            for (inst, value) in b.insts[.. phis].iter().zip(incoming) {
+                if let Some(v) = inst.result {
+                    values[v] = value;
+                }
+            }
+
+            for inst in &b.insts[phis ..] {
+                let get = |v: Value| values[v].clone();
+                let result = match inst.op {
+                    Op::Param(i) => args.get(i).cloned(),
+                    Op::Const(ref c) => Some(match *c {
+                        Const::Int(n) => Val::Int(n),
+                        Const::Float(x) => Val::Float(x),
+                        Const::Bool(b) => Val::Bool(b),
+                        Const::Str(ref s) => Val::Str(Rc::new(s.clone())),
+                    }),
+                    Op::Zero => inst.result.map(|v| zero(&f.types[v])),
+                    Op::Phi(_) => return Err(self.error(invalid(f, "phi after other instructions"), inst.pos)),
+                    Op::Unary(op, x) => {
+                        let ty = &f.types[x];
+                        Some(match (op, get(x)) {
+                            (UnOp::Neg, Val::Int(n)) => Val::Int(wrap(ty, n.wrapping_neg())),
+                            (UnOp::Neg, Val::Float(x)) => Val::Float(-x),
+                            (UnOp::Not, Val::Bool(b)) => Val::Bool(!b),
+                            (UnOp::BitNot, Val::Int(n)) => Val::Int(!n),
+                            (_, x) => x,
+                        })
+                    }
+                    Op::Binary(op, x, y) => Some(self.binary(op, &f.types[x], get(x), get(y), inst.pos)?),
+                    Op::Convert(x) => {
+                        let to = inst.result.map_or(&Ty::Int, |v| &f.types[v]);
+                        Some(convert(&f.types[x], to, get(x)))
+                    }
+                    Op::Check(i, n) => {
+                        if let (Val::Int(i), Val::Int(n)) = (get(i), get(n)) {
+                            if i < 0 || i >= n {
+                                let msg = format!("index out of range [{}] with length {}", i, n);
+                                return Err(self.error(msg, inst.pos));
+                            }
+                        }
+                        None
+                    }
+                    Op::Len(x) | Op::Cap(x) => {
+                        let n = match get(x) {
+                            Val::Str(s) => s.len(),
+                            Val::Agg(elems) => elems.len(),
+                            Val::Slice(elems, _) if inst.op == Op::Cap(x) => elems.borrow().len(),
+                            Val::Slice(_, len) => len,
+                            _ => 0,
+                        };
+                        Some(Val::Int(n as i64))
+                    }
+                    Op::Index(a, i) | Op::LoadElem(a, i) => {
+                        let i = int(&get(i)) as usize;
+                        match get(a) {
+                            Val::Agg(elems) => elems.get(i).cloned(),
+                            Val::Slice(elems, _) => elems.borrow().get(i).cloned(),
+                            _ => None,
+                        }
+                    }
+                    Op::Field(s, k) => match get(s) {
+                        Val::Agg(fields) => fields.get(k).cloned(),
+                        _ => None,
+                    },
+                    Op::SetIndex(a, i, x) => Some(replace(get(a), int(&get(i)) as usize, get(x))),
+                    Op::SetField(s, k, x) => Some(replace(get(s), k, get(x))),
+                    Op::StoreElem(s, i, x) => {
+                        if let Val::Slice(elems, _) = get(s) {
+                            if let Some(elem) = elems.borrow_mut().get_mut(int(&get(i)) as usize) {
+                                *elem = get(x);
+                            }
+                        }
+                        None
+                    }
+                    Op::Append(s, x) => match get(s) {
+                        Val::Slice(elems, len) => Some(append(elems, len, get(x))),
+                        _ => None,
+                    },
+                    Op::Load(g) => self.globals.get(g).cloned(),
+                    Op::Store(g, x) => {
+                        self.globals[g] = get(x);
+                        None
+                    }
+                    Op::Call(callee, ref operands) => {
+                        let args = operands.iter().map(|&v| get(v)).collect();
+                        self.call(callee, args, inst.pos)?
+                    }
+                    Op::Print(ref operands) | Op::Println(ref operands) => {
+                        let newline = match inst.op {
+                            Op::Println(_) => true,
+                            _ => false,
+                        };
+                        let args: Vec<Val> = operands.iter().map(|&v| get(v)).collect();
+                        if let Err(err) = self.out.write_all(&print(&args, newline)) {
+                            return Err(self.error(err.to_string(), inst.pos));
+                        }
+                        None
+                    }
+                };
+                if let Some(v) = inst.result {
+                    match result {
+                        Some(result) => values[v] = result,
+                        None => return Err(self.error(invalid(f, "instruction without result"), inst.pos)),
+                    }
+                }
+            }
+
+            pred = block;
+            block = match b.term {
+                Term::Jump(target) => target,
+                Term::Branch(c, t, e) => match values[c] {
+                    Val::Bool(true) => t,
+                    _ => e,
+                },
+                Term::Return(v) => return Ok(v.map(|v| values[v].clone())),
+                Term::Unreachable => return Err(self.error(invalid(f, "unreachable code reached"), b.pos)),
+            };
+        }
+    }
+
+    /// Applies a binary operation to operands of type `ty`.
+    fn binary(&self, op: BinOp, ty: &Ty, x: Val, y: Val, pos: Pos) -> Result<Val> {
+        match (x, y) {
+            (Val::Int(x), Val::Int(y)) => {
+                let n = match op {
+                    BinOp::Eq => return Ok(Val::Bool(x == y)),
+                    BinOp::Ne => return Ok(Val::Bool(x != y)),
+                    BinOp::Lt => return Ok(Val::Bool(x < y)),
+                    BinOp::Le => return Ok(Val::Bool(x <= y)),
+                    BinOp::Gt => return Ok(Val::Bool(x > y)),
+                    BinOp::Ge => return Ok(Val::Bool(x >= y)),
+                    BinOp::Add => x.wrapping_add(y),
+                    BinOp::Sub => x.wrapping_sub(y),
+                    BinOp::Mul => x.wrapping_mul(y),
+                    BinOp::Div | BinOp::Rem if y == 0 => {
+                        return Err(self.error("integer divide by zero".to_string(), pos));
+                    }
+                    BinOp::Div => x.wrapping_div(y),
+                    BinOp::Rem => x.wrapping_rem(y),
+                    BinOp::Shl | BinOp::Shr if y < 0 => {
+                        return Err(self.error("negative shift amount".to_string(), pos));
+                    }
+                    BinOp::Shl if y >= 64 => 0,
+                    BinOp::Shl => ((x as u64) << y) as i64,
+                    BinOp::Shr => x >> y.min(63),
+                    BinOp::BitAnd => x & y,
+                    BinOp::BitOr => x | y,
+                    BinOp::BitXor => x ^ y,
+                    BinOp::BitClear => x & !y,
+                    BinOp::And | BinOp::Or => return Ok(Val::Int(0)),
+                };
+                return Ok(Val::Int(wrap(ty, n)));
+            }
+            (Val::Float(x), Val::Float(y)) => match op {
+                BinOp::Eq => return Ok(Val::Bool(x == y)),
+                BinOp::Ne => return Ok(Val::Bool(x != y)),
+                BinOp::Lt => return Ok(Val::Bool(x < y)),
+                BinOp::Le => return Ok(Val::Bool(x <= y)),
+                BinOp::Gt => return Ok(Val::Bool(x > y)),
+                BinOp::Ge => return Ok(Val::Bool(x >= y)),
+                BinOp::Add => return Ok(Val::Float(x + y)),
+                BinOp::Sub => return Ok(Val::Float(x - y)),
+                BinOp::Mul => return Ok(Val::Float(x * y)),
+                _ => return Ok(Val::Float(x / y)),
+            },
+            (Val::Str(x), Val::Str(y)) => match op {
+                BinOp::Lt => return Ok(Val::Bool(x < y)),
+                BinOp::Le => return Ok(Val::Bool(x <= y)),
+                BinOp::Gt => return Ok(Val::Bool(x > y)),
+                BinOp::Ge => return Ok(Val::Bool(x >= y)),
+                BinOp::Add => {
+                    let mut s = (*x).clone();
+                    s.extend_from_slice(&y);
+                    return Ok(Val::Str(Rc::new(s)));
+                }
+                _ => {
+                    let eq = x == y;
+                    return Ok(Val::Bool(if op == BinOp::Eq { eq } else { !eq }));
+                }
+            },
+            (x, y) => {
+                let eq = equal(ty, &x, &y);
+                return Ok(Val::Bool(if op == BinOp::Ne { !eq } else { eq }));
+            }
+        }
+    }
+}
+
+/// The error for a malformed module, which the builder never
+/// produces and the verifier rejects.
+fn invalid(f: &Function, msg: &str) -> String {
+    return format!("invalid IR in {}: {}", f.name, msg);
+}
+
+fn int(value: &Val) -> i64 {
+    match *value {
+        Val::Int(n) => return n,
+        _ => return 0,
+    }
+}
+
+/// Brings the result of an integer operation back in the range
+/// of its type.
+fn wrap(ty: &Ty, n: i64) -> i64 {
+    if *ty == Ty::Rune {
+        return n as i32 as i64;
+    }
+    return n;
+}
+
+fn zero(ty: &Ty) -> Val {
+    match *ty {
+        Ty::Int | Ty::Rune => return Val::Int(0),
+        Ty::Float => return Val::Float(0.0),
+        Ty::Bool => return Val::Bool(false),
+        Ty::Str => return Val::Str(Rc::new(Vec::new())),
+        Ty::Slice(_) => return Val::Slice(Rc::new(RefCell::new(Vec::new())), 0),
+        Ty::Array(len, ref elem) => return Val::Agg(Rc::new((0 .. len).map(|_| zero(elem)).collect())),
+        Ty::Struct(ref fields) => return Val::Agg(Rc::new(fields.iter().map(|f| zero(&f.1)).collect())),
+    }
+}
+
+fn convert(from: &Ty, to: &Ty, x: Val) -> Val {
+    match (x, to) {
+        (Val::Int(n), &Ty::Float) => return Val::Float(n as f64),
+        (Val::Float(x), to) if to.is_integer() => return Val::Int(wrap(to, x as i64)),
+        (Val::Int(n), &Ty::Str) => {
+            // Invalid code points become U+FFFD.
+            let c = if n >= 0 && n <= u32::MAX as i64 { char::from_u32(n as u32) } else { None };
+            let s = constant::encode_rune(c.unwrap_or('\u{fffd}'));
+            return Val::Str(Rc::new(constant::string_bytes(&s)));
+        }
+        (Val::Int(n), to) if to != from => return Val::Int(wrap(to, n)),
+        (x, _) => return x,
+    }
+}
+
+/// Returns an array or struct with one element replaced.
+fn replace(agg: Val, i: usize, x: Val) -> Val {
+    match agg {
+        Val::Agg(mut elems) => {
+            if let Some(elem) = Rc::make_mut(&mut elems).get_mut(i) {
+                *elem = x;
+            }
+            return Val::Agg(elems);
+        }
+        agg => return agg,
+    }
+}
+
+/// Appends to a slice, copying its elements to a new backing
+/// array twice as large if it is full.  The remaining elements
+/// of the new array are never read before they are appended.
+fn append(elems: Rc<RefCell<Vec<Val>>>, len: usize, value: Val) -> Val {
+    let cap = elems.borrow().len();
+    if len < cap {
+        elems.borrow_mut()[len] = value;
+        return Val::Slice(elems, len + 1);
+    }
+    let new_cap = if cap == 0 { 1 } else { 2 * cap };
+    let mut data = Vec::with_capacity(new_cap);
+    data.extend_from_slice(&elems.borrow()[.. len]);
+    data.push(value);
+    data.resize(new_cap, Val::Int(0));
+    return Val::Slice(Rc::new(RefCell::new(data)), len + 1);
+}
+
+/// Compares two values of type `ty`; the blank fields of
+/// structs are skipped.
+fn equal(ty: &Ty, x: &Val, y: &Val) -> bool {
+    match (x, y) {
+        (Val::Int(x), Val::Int(y)) => return x == y,
+        (Val::Float(x), Val::Float(y)) => return x == y,
+        (Val::Bool(x), Val::Bool(y)) => return x == y,
+        (Val::Str(x), Val::Str(y)) => return x == y,
+        (Val::Agg(x), Val::Agg(y)) => match *ty {
+            Ty::Array(_, ref elem) => return x.iter().zip(y.iter()).all(|(x, y)| equal(elem, x, y)),
+            Ty::Struct(ref fields) => {
+                return fields.iter().zip(x.iter().zip(y.iter()))
+                    .all(|(field, (x, y))| !field.0 || equal(&field.1, x, y));
+            }
+            _ => return false,
+        },
+        _ => return false,
+    }
+}
+
+fn print(values: &[Val], newline: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        if newline && i > 0 {
+            bytes.push(b' ');
+        }
+        match *value {
+            Val::Int(n) => bytes.extend_from_slice(n.to_string().as_bytes()),
+            Val::Float(f) => bytes.extend_from_slice(interp::format_float(f).as_bytes()),
+            Val::Bool(b) => bytes.extend_from_slice(b.to_string().as_bytes()),
+            Val::Str(ref s) => bytes.extend_from_slice(s),
+            _ => {}
+        }
+    }
+    if newline {
+        bytes.push(b'\n');
+    }
+    return bytes;
+}
@@ -0,0 +1,1002 @@
+//! The translation of type-checked programs to the SSA form of
+//! `ir`.
+//!
+//! The IR currently feeds only its evaluator, `ireval`, which
+//! runs `gore run --ir`, and `gore ir`: the backends, the
+//! bytecode compiler and the interpreter still lower the syntax
+//! tree themselves, so the optimisations of `opt` do not reach
+//! the code that they generate.
+
+use std::collections::HashMap;
+use std::mem;
+
+use ast::*;
+use bytecode::{Const, Pos};
+use constant::{self, Value as ConstValue};
+use ir::{self, Block, BlockId, Function, Global, Inst, Module, Op, Term, Ty, Value};
+use loc::Loc;
+use lower::{one, Targets};
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// Translates a type-checked program to its SSA form.
+///
+/// The SSA form is built directly from the syntax tree with the
+/// algorithm of Braun et al. ("Simple and Efficient Construction
+/// of Static Single Assignment Form"): every block records the
+/// value of the local variables that it assigns, a read in a
+/// block that does not assign the variable looks for its value
+/// in the predecessors, and blocks whose predecessors are not
+/// all known yet (the headers of loops) get phi nodes that are
+/// completed once they are.  Trivial phi nodes are removed at
+/// the end of every function.
+///
+/// The entry function of the module initializes the package
+/// variables in the order of their declarations and then calls
+/// `main`.
+pub fn build(prog: &Program, types: &TypeTable, table: &SymbolTable) -> Module {
+    let mut builder = Builder {
+        types: types,
+        table: table,
+        module: Module {
+            filename: prog.package.loc.filename.clone(),
+            globals: Vec::new(),
+            functions: Vec::new(),
+            entry: 0,
+        },
+        funcs: HashMap::new(),
+        globals: HashMap::new(),
+        func: Function::new("<init>", Vec::new(), None),
+        current: 0,
+        terminated: false,
+        preds: Vec::new(),
+        sealed: Vec::new(),
+        entered: Vec::new(),
+        bodies: Vec::new(),
+        prologues: Vec::new(),
+        phis: Vec::new(),
+        incomplete: HashMap::new(),
+        defs: HashMap::new(),
+        targets: Targets::default(),
+    };
+    builder.program(prog);
+    return builder.module;
+}
+
+/// A phi node under construction.
+struct Phi {
+    value: Value,
+    block: BlockId,
+    incoming: Vec<(BlockId, Value)>,
+    pos: Pos,
+}
+
+/// Where an assignment stores its value: a variable, an element
+/// of a slice or nothing (the blank identifier), then the fields
+/// and array elements that lead to the target inside it.
+struct Place {
+    root: Root,
+    steps: Vec<Step>,
+    /// The check of the last index, which is only made once
+    /// the value to assign is known.
+    check: Option<(Value, Length, Loc)>,
+}
+
+enum Root {
+    Local(SymbolId),
+    Global(usize),
+    Elem(Value, Value),
+    Blank,
+}
+
+enum Step {
+    Field(usize),
+    Index(Value),
+}
+
+/// The length against which an index is checked.
+enum Length {
+    Array(u64),
+    Slice(Value),
+}
+
+struct Builder<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    module: Module,
+    /// The indices of the functions and of the package variables.
+    funcs: HashMap<SymbolId, usize>,
+    globals: HashMap<SymbolId, usize>,
+    /// The function being built and the block that receives
+    /// its instructions.
+    func: Function,
+    current: BlockId,
+    /// Whether the current block has ended, in which case the
+    /// next statement starts a block of its own.
+    terminated: bool,
+    /// The predecessors of the blocks created so far, whether
+    /// they are all known, and the order in which the blocks
+    /// were started.
+    preds: Vec<Vec<BlockId>>,
+    sealed: Vec<bool>,
+    entered: Vec<BlockId>,
+    /// The instructions of every block, and the zero values that
+    /// stand for variables read where they are not defined; the
+    /// phi nodes are added in front of them at the end.
+    bodies: Vec<Vec<Inst>>,
+    prologues: Vec<Vec<Inst>>,
+    phis: Vec<Phi>,
+    /// The phi nodes of unsealed blocks, with their variable.
+    incomplete: HashMap<BlockId, Vec<(SymbolId, usize)>>,
+    /// The value of the local variables at the end of the
+    /// blocks that assign them.
+    defs: HashMap<(SymbolId, BlockId), Value>,
+    /// The blocks that `break` and `continue` go to.
+    targets: Targets<BlockId>,
+}
+
+fn pos(loc: &Loc) -> Pos {
+    return Pos { line: loc.line as u32, col: loc.col as u32 };
+}
+
+impl<'a> Builder<'a> {
+    fn ty(&self, ty: TypeId) -> Ty {
+        match *self.types.under(self.types.default_type(ty)) {
+            TypeKind::Float64 => return Ty::Float,
+            TypeKind::Bool => return Ty::Bool,
+            TypeKind::Rune => return Ty::Rune,
+            TypeKind::String => return Ty::Str,
+            TypeKind::Slice(elem) => return Ty::Slice(Box::new(self.ty(elem))),
+            TypeKind::Array(len, elem) => return Ty::Array(len, Box::new(self.ty(elem))),
+            TypeKind::Struct(ref fields) => {
+                return Ty::Struct(fields.iter().map(|&(ref name, ty)| (name != "_", self.ty(ty))).collect());
+            }
+            _ => return Ty::Int,
+        }
+    }
+
+    fn var_type(&self, sym: SymbolId) -> Ty {
+        return self.ty(self.table.symbol(sym).ty.unwrap_or(INVALID));
+    }
+
+    fn value_type(&self, v: Value) -> Ty {
+        return self.func.types[v].clone();
+    }
+
+
+    // BLOCKS AND INSTRUCTIONS
+
+    fn new_block(&mut self) -> BlockId {
+        self.preds.push(Vec::new());
+        self.sealed.push(false);
+        self.bodies.push(Vec::new());
+        self.prologues.push(Vec::new());
+        self.func.blocks.push(Block { insts: Vec::new(), term: Term::Unreachable, pos: Pos { line: 0, col: 0 } });
+        return self.func.blocks.len() - 1;
+    }
+
+    /// Makes `block` the block that receives the instructions.
+    fn enter(&mut self, block: BlockId) {
+        self.current = block;
+        self.terminated = false;
+        self.entered.push(block);
+    }
+
+    /// Ends the current block, unless a `return`, `break` or
+    /// `continue` has ended it.
+    fn terminate(&mut self, term: Term, loc: &Loc) {
+        if self.terminated {
+            return;
+        }
+        self.terminated = true;
+        for s in term.successors() {
+            if !self.preds[s].contains(&self.current) {
+                self.preds[s].push(self.current);
+            }
+        }
+        let block = &mut self.func.blocks[self.current];
+        block.term = term;
+        block.pos = pos(loc);
+    }
+
+    /// Starts a block for statements that follow a `return`,
+    /// `break` or `continue`, which are never executed.
+    fn enter_dead_block(&mut self) {
+        let block = self.new_block();
+        self.seal(block);
+        self.enter(block);
+    }
+
+    fn emit(&mut self, op: Op, ty: Option<Ty>, loc: &Loc) -> Option<Value> {
+        let result = ty.map(|ty| self.func.new_value(ty));
+        self.bodies[self.current].push(Inst { result: result, op: op, pos: pos(loc) });
+        return result;
+    }
+
+    fn value(&mut self, op: Op, ty: Ty, loc: &Loc) -> Value {
+        let v = self.func.new_value(ty);
+        self.bodies[self.current].push(Inst { result: Some(v), op: op, pos: pos(loc) });
+        return v;
+    }
+
+    fn int(&mut self, n: i64, loc: &Loc) -> Value {
+        return self.value(Op::Const(Const::Int(n)), Ty::Int, loc);
+    }
+
+    fn zero(&mut self, ty: Ty, loc: &Loc) -> Value {
+        let op = match ty {
+            Ty::Int | Ty::Rune => Op::Const(Const::Int(0)),
+            Ty::Float => Op::Const(Const::Float(0.0)),
+            Ty::Bool => Op::Const(Const::Bool(false)),
+            Ty::Str => Op::Const(Const::Str(Vec::new())),
+            _ => Op::Zero,
+        };
+        return self.value(op, ty, loc);
+    }
+
+
+    // SSA CONSTRUCTION
+
+    fn write_var(&mut self, sym: SymbolId, v: Value) {
+        self.defs.insert((sym, self.current), v);
+    }
+
+    fn read_var(&mut self, sym: SymbolId, block: BlockId, loc: &Loc) -> Value {
+        if let Some(&v) = self.defs.get(&(sym, block)) {
+            return v;
+        }
+        let v = if !self.sealed[block] {
+            let phi = self.new_phi(block, self.var_type(sym), loc);
+            self.incomplete.entry(block).or_default().push((sym, phi));
+            self.phis[phi].value
+        } else if self.preds[block].len() == 1 {
+            let pred = self.preds[block][0];
+            self.read_var(sym, pred, loc)
+        } else if self.preds[block].is_empty() {
+            // Only unreachable code reads variables that are not
+            // defined.
+            let v = self.func.new_value(self.var_type(sym));
+            self.prologues[block].push(Inst { result: Some(v), op: Op::Zero, pos: pos(loc) });
+            v
+        } else {
+            // The phi node is the value of the variable while its
+            // operands are looked for, which ends the search in
+            // loops.
+            let phi = self.new_phi(block, self.var_type(sym), loc);
+            let v = self.phis[phi].value;
+            self.defs.insert((sym, block), v);
+            self.add_phi_operands(sym, phi, loc);
+            v
+        };
+        self.defs.insert((sym, block), v);
+        return v;
+    }
+
+    fn new_phi(&mut self, block: BlockId, ty: Ty, loc: &Loc) -> usize {
+        let v = self.func.new_value(ty);
+        self.phis.push(Phi { value: v, block: block, incoming: Vec::new(), pos: pos(loc) });
+        return self.phis.len() - 1;
+    }
+
+    fn add_phi_operands(&mut self, sym: SymbolId, phi: usize, loc: &Loc) {
+        let block = self.phis[phi].block;
+        for pred in self.preds[block].clone() {
+            let v = self.read_var(sym, pred, loc);
+            self.phis[phi].incoming.push((pred, v));
+        }
+    }
+
+    /// Records that all the predecessors of `block` are known.
+    fn seal(&mut self, block: BlockId) {
+        self.sealed[block] = true;
+        for (sym, phi) in self.incomplete.remove(&block).unwrap_or_default() {
+            let loc = Loc::new(&self.module.filename, self.phis[phi].pos.line as usize,
+                               self.phis[phi].pos.col as usize);
+            self.add_phi_operands(sym, phi, &loc);
+        }
+    }
+
+    /// Completes the function being built: removes the trivial
+    /// phi nodes, puts the phi nodes in front of their blocks and
+    /// lays the blocks out in the order in which they were
+    /// started.
+    fn finish_function(&mut self) -> Function {
+        // A phi node whose operands are all the same value, or
+        // itself, is that value.
+        let mut replaced: HashMap<Value, Value> = HashMap::new();
+        let resolve = |replaced: &HashMap<Value, Value>, mut v: Value| {
+            while let Some(&w) = replaced.get(&v) {
+                v = w;
+            }
+            return v;
+        };
+        let mut removed = vec![false; self.phis.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, phi) in self.phis.iter().enumerate() {
+                if removed[i] {
+                    continue;
+                }
+                let mut same = None;
+                let mut trivial = true;
+                for &(_, v) in &phi.incoming {
+                    let v = resolve(&replaced, v);
+                    if v == phi.value || same == Some(v) {
+                        continue;
+                    }
+                    if same.is_some() {
+                        trivial = false;
+                        break;
+                    }
+                    same = Some(v);
+                }
+                if trivial {
+                    if let Some(v) = same {
+                        replaced.insert(phi.value, v);
+                        removed[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        let mut insts: Vec<Vec<Inst>> = vec![Vec::new(); self.func.blocks.len()];
+        for (i, phi) in mem::take(&mut self.phis).into_iter().enumerate() {
+            if removed[i] {
+                continue;
+            }
+            // A phi node without operands is in a block that
+            // is never executed.
+            let op = if phi.incoming.is_empty() { Op::Zero } else { Op::Phi(phi.incoming) };
+            insts[phi.block].push(Inst { result: Some(phi.value), op: op, pos: phi.pos });
+        }
+        let bodies = mem::take(&mut self.bodies);
+        let prologues = mem::take(&mut self.prologues);
+        for (b, (prologue, body)) in prologues.into_iter().zip(bodies).enumerate() {
+            insts[b].extend(prologue);
+            insts[b].extend(body);
+        }
+
+        let mut order = mem::take(&mut self.entered);
+        let mut seen = vec![false; self.func.blocks.len()];
+        order.retain(|&b| !mem::replace(&mut seen[b], true));
+        order.extend((0 .. seen.len()).filter(|&b| !seen[b]));
+        let mut number = vec![0; order.len()];
+        for (i, &b) in order.iter().enumerate() {
+            number[b] = i;
+        }
+
+        let mut func = mem::replace(&mut self.func, Function::new("", Vec::new(), None));
+        let mut blocks: Vec<Option<Block>> = mem::take(&mut func.blocks).into_iter().map(Some).collect();
+        for &b in &order {
+            let mut block = match blocks[b].take() {
+                Some(block) => block,
+                None => continue,
+            };
+            block.insts = mem::take(&mut insts[b]);
+            for inst in &mut block.insts {
+                inst.op.map_operands(|v| resolve(&replaced, v));
+                if let Op::Phi(ref mut incoming) = inst.op {
+                    for &mut (ref mut pred, _) in incoming {
+                        *pred = number[*pred];
+                    }
+                }
+            }
+            block.term.map_operands(|v| resolve(&replaced, v));
+            block.term = match block.term {
+                Term::Jump(target) => Term::Jump(number[target]),
+                Term::Branch(v, t, e) => Term::Branch(v, number[t], number[e]),
+                term => term,
+            };
+            func.blocks.push(block);
+        }
+        ir::renumber(&mut func);
+
+        self.preds.clear();
+        self.sealed.clear();
+        self.incomplete.clear();
+        self.defs.clear();
+        return func;
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) => Some(f),
+                _ => None,
+            })
+            .collect();
+        // The entry function comes first.
+        for (i, f) in funcs.iter().enumerate() {
+            if let Some(sym) = f.name.sym {
+                self.funcs.insert(sym, i + 1);
+            }
+        }
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        if let Some(sym) = id.sym {
+                            self.globals.insert(sym, self.module.globals.len());
+                            let ty = self.var_type(sym);
+                            self.module.globals.push(Global { name: id.name.clone(), ty: ty });
+                        }
+                    }
+                }
+            }
+        }
+        // The signatures of the functions are needed to check
+        // calls, so they are all known before any body is built.
+        self.module.functions.push(Function::new("<init>", Vec::new(), None));
+        for f in &funcs {
+            let (params, result) = self.signature(f);
+            self.module.functions.push(Function::new(&f.name.name, params, result));
+        }
+
+        self.func = Function::new("<init>", Vec::new(), None);
+        let entry = self.new_block();
+        self.seal(entry);
+        self.enter(entry);
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec);
+                }
+            }
+        }
+        let loc = &prog.package.loc;
+        if let Some(main) = funcs.iter().find(|f| f.name.name == "main") {
+            let index = main.name.sym.and_then(|sym| self.funcs.get(&sym).cloned());
+            if let Some(index) = index {
+                self.emit(Op::Call(index, Vec::new()), None, loc);
+            }
+        }
+        self.terminate(Term::Return(None), loc);
+        self.module.functions[0] = self.finish_function();
+
+        for (i, f) in funcs.into_iter().enumerate() {
+            let func = self.func_decl(f);
+            self.module.functions[i + 1] = func;
+        }
+    }
+
+    fn signature(&self, f: &FuncDecl) -> (Vec<Ty>, Option<Ty>) {
+        match f.name.sym.and_then(|sym| self.table.symbol(sym).ty).map(|ty| self.types.kind(ty)) {
+            Some(&TypeKind::Func(ref params, result)) => {
+                let result = if result == VOID { None } else { Some(self.ty(result)) };
+                return (params.iter().map(|&ty| self.ty(ty)).collect(), result);
+            }
+            _ => return (Vec::new(), None),
+        }
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) -> Function {
+        let (params, result) = self.signature(f);
+        self.func = Function::new(&f.name.name, params.clone(), result.clone());
+        let entry = self.new_block();
+        self.seal(entry);
+        self.enter(entry);
+        for (i, (p, ty)) in f.params.iter().zip(params).enumerate() {
+            let v = self.value(Op::Param(i), ty, &p.name.loc);
+            if let Some(sym) = p.name.sym {
+                if !p.name.is_blank() {
+                    self.write_var(sym, v);
+                }
+            }
+        }
+        self.stmts(&f.body.stmts);
+        // The type checker makes sure that functions with a
+        // result do not reach their end.
+        let term = if result.is_some() { Term::Unreachable } else { Term::Return(None) };
+        self.terminate(term, &f.body.loc);
+        return self.finish_function();
+    }
+
+    /// Declares the variables of a `var` spec.  The values are
+    /// all evaluated before they are stored.
+    fn var_spec(&mut self, spec: &VarSpec) {
+        if spec.values.is_empty() {
+            for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                let sym = match id.sym {
+                    Some(sym) => sym,
+                    None => continue,
+                };
+                // Package variables start with their zero value.
+                if !self.globals.contains_key(&sym) {
+                    let zero = self.zero(self.var_type(sym), &id.loc);
+                    self.write_var(sym, zero);
+                }
+            }
+            return;
+        }
+        self.define(&spec.names, &spec.values);
+    }
+
+    /// Assigns values to the variables of a declaration.
+    fn define(&mut self, names: &[Ident], values: &[Expr]) {
+        let values: Vec<Value> = values.iter().map(|e| self.expr(e)).collect();
+        for (id, v) in names.iter().zip(values) {
+            let root = self.root(id);
+            self.write_root(&root, v, &id.loc);
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let loc = &stmt.loc;
+        if self.terminated {
+            self.enter_dead_block();
+        }
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => match e.kind {
+                ExprKind::Call(_, _) if e.value.is_none() => {
+                    self.call(e);
+                }
+                _ => {
+                    self.expr(e);
+                }
+            },
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs, loc),
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => self.define(names, values),
+            StmtKind::Print(ref args) | StmtKind::Println(ref args) => {
+                let values: Vec<Value> = args.iter().map(|e| self.expr(e)).collect();
+                let op = match stmt.kind {
+                    StmtKind::Print(_) => Op::Print(values),
+                    _ => Op::Println(values),
+                };
+                self.emit(op, None, loc);
+            }
+            StmtKind::Return(ref e) => {
+                let v = e.as_ref().map(|e| self.expr(e));
+                self.terminate(Term::Return(v), loc);
+            }
+            StmtKind::Break => {
+                if let Some(target) = self.targets.brk() {
+                    self.terminate(Term::Jump(target), loc);
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(target) = self.targets.cont() {
+                    self.terminate(Term::Jump(target), loc);
+                }
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt, loc),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt, loc),
+        }
+    }
+
+    fn root(&self, id: &Ident) -> Root {
+        match id.sym {
+            Some(_) if id.is_blank() => return Root::Blank,
+            Some(sym) => match self.globals.get(&sym) {
+                Some(&global) => return Root::Global(global),
+                None => return Root::Local(sym),
+            },
+            None => return Root::Blank,
+        }
+    }
+
+    /// Evaluates the operands of an expression that is assigned
+    /// to.  The indices are checked as they are evaluated, except
+    /// the last one.
+    fn place(&mut self, e: &Expr) -> Place {
+        match e.kind {
+            ExprKind::Id(ref id) => return Place { root: self.root(id), steps: Vec::new(), check: None },
+            ExprKind::Selector(ref x, ref field) => {
+                let mut place = self.place(x);
+                self.check(&mut place);
+                place.steps.push(Step::Field(self.field_index(x, field)));
+                return place;
+            }
+            ExprKind::Index(ref a, ref i) => match *self.types.under(a.ty.unwrap_or(INVALID)) {
+                TypeKind::Array(len, _) => {
+                    let mut place = self.place(a);
+                    self.check(&mut place);
+                    let i = self.expr(i);
+                    place.steps.push(Step::Index(i));
+                    place.check = Some((i, Length::Array(len), e.loc.clone()));
+                    return place;
+                }
+                _ => {
+                    let s = self.expr(a);
+                    let i = self.expr(i);
+                    return Place { root: Root::Elem(s, i), steps: Vec::new(),
+                                   check: Some((i, Length::Slice(s), e.loc.clone())) };
+                }
+            },
+            _ => {
+                self.expr(e);
+                return Place { root: Root::Blank, steps: Vec::new(), check: None };
+            }
+        }
+    }
+
+    /// Makes the check of the last index of a place.
+    fn check(&mut self, place: &mut Place) {
+        if let Some((i, len, loc)) = place.check.take() {
+            self.check_index(i, len, &loc);
+        }
+    }
+
+    fn check_index(&mut self, i: Value, len: Length, loc: &Loc) {
+        let n = match len {
+            Length::Array(n) => self.int(n as i64, loc),
+            Length::Slice(s) => self.value(Op::Len(s), Ty::Int, loc),
+        };
+        self.emit(Op::Check(i, n), None, loc);
+    }
+
+    fn read_root(&mut self, root: &Root, ty: Ty, loc: &Loc) -> Value {
+        match *root {
+            Root::Local(sym) => {
+                let block = self.current;
+                return self.read_var(sym, block, loc);
+            }
+            Root::Global(g) => return self.value(Op::Load(g), ty, loc),
+            Root::Elem(s, i) => return self.value(Op::LoadElem(s, i), ty, loc),
+            Root::Blank => return self.zero(ty, loc),
+        }
+    }
+
+    fn write_root(&mut self, root: &Root, v: Value, loc: &Loc) {
+        match *root {
+            Root::Local(sym) => self.write_var(sym, v),
+            Root::Global(g) => {
+                self.emit(Op::Store(g, v), None, loc);
+            }
+            Root::Elem(s, i) => {
+                self.emit(Op::StoreElem(s, i, v), None, loc);
+            }
+            Root::Blank => {}
+        }
+    }
+
+    /// The type of the variable or slice element of a place.
+    fn root_type(&self, root: &Root) -> Ty {
+        match *root {
+            Root::Local(sym) => return self.var_type(sym),
+            Root::Global(g) => return self.module.globals[g].ty.clone(),
+            Root::Elem(s, _) => match self.func.types[s] {
+                Ty::Slice(ref elem) => return (**elem).clone(),
+                _ => return Ty::Int,
+            },
+            Root::Blank => return Ty::Int,
+        }
+    }
+
+    /// Returns the value of the root of a place and of the
+    /// aggregates that contain its target, outermost first.
+    fn path(&mut self, place: &Place, loc: &Loc) -> Vec<Value> {
+        let ty = self.root_type(&place.root);
+        let mut path = vec![self.read_root(&place.root, ty, loc)];
+        for step in place.steps.iter().take(place.steps.len().saturating_sub(1)) {
+            let agg = path[path.len() - 1];
+            let v = self.extract(agg, step, loc);
+            path.push(v);
+        }
+        return path;
+    }
+
+    fn extract(&mut self, agg: Value, step: &Step, loc: &Loc) -> Value {
+        match (step, self.value_type(agg)) {
+            (&Step::Field(k), Ty::Struct(ref fields)) => {
+                let ty = fields.get(k).map_or(Ty::Int, |f| f.1.clone());
+                return self.value(Op::Field(agg, k), ty, loc);
+            }
+            (&Step::Index(i), Ty::Array(_, ref elem)) => return self.value(Op::Index(agg, i), (**elem).clone(), loc),
+            _ => return agg,
+        }
+    }
+
+    /// Stores a value in a place: the arrays and structs that
+    /// contain the target are rebuilt around the new value, from
+    /// the innermost one out, and the result is stored in the
+    /// variable or slice element.
+    fn store(&mut self, mut place: Place, v: Value, loc: &Loc) {
+        self.check(&mut place);
+        if place.steps.is_empty() {
+            self.write_root(&place.root, v, loc);
+            return;
+        }
+        let path = self.path(&place, loc);
+        let mut v = v;
+        for (step, &agg) in place.steps.iter().zip(&path).rev() {
+            let ty = self.value_type(agg);
+            v = match *step {
+                Step::Field(k) => self.value(Op::SetField(agg, k, v), ty, loc),
+                Step::Index(i) => self.value(Op::SetIndex(agg, i, v), ty, loc),
+            };
+        }
+        self.write_root(&place.root, v, loc);
+    }
+
+    /// The operands of the indexing expressions on the left and
+    /// the values on the right are all evaluated before the
+    /// assignments are made from left to right.
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr], loc: &Loc) {
+        let places: Vec<Place> = lhs.iter().map(|e| self.place(e)).collect();
+        let values: Vec<Value> = rhs.iter().map(|e| self.expr(e)).collect();
+        for (place, v) in places.into_iter().zip(values) {
+            self.store(place, v, loc);
+        }
+    }
+
+    /// `x op= y`: the value of `x` is extracted from the path to its
+    /// place, which the store then reuses.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let mut place = self.place(lhs);
+        self.check(&mut place);
+        let path = self.path(&place, &lhs.loc);
+        let x = match place.steps.last() {
+            Some(step) => self.extract(path[path.len() - 1], step, &lhs.loc),
+            None => path[0],
+        };
+        let y = self.expr(rhs);
+        let ty = self.value_type(x);
+        let v = self.value(Op::Binary(op, x, y), ty, loc);
+        self.store(place, v, loc);
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        if let Some(ref init) = if_stmt.init {
+            self.stmt(init);
+        }
+        let cond = self.expr(&if_stmt.cond);
+        let then = self.new_block();
+        let els = if_stmt.els.as_ref().map(|_| self.new_block());
+        let end = self.new_block();
+        self.terminate(Term::Branch(cond, then, els.unwrap_or(end)), &if_stmt.cond.loc);
+        self.seal(then);
+        self.enter(then);
+        self.stmts(&if_stmt.then.stmts);
+        self.terminate(Term::Jump(end), &if_stmt.then.loc);
+        if let (Some(els), Some(ref stmt)) = (els, &if_stmt.els) {
+            self.seal(els);
+            self.enter(els);
+            self.stmt(stmt);
+            self.terminate(Term::Jump(end), &stmt.loc);
+        }
+        self.seal(end);
+        self.enter(end);
+    }
+
+    /// The tag is compared with the case expressions in order
+    /// until one of them matches; each comparison branches to
+    /// the body of its clause.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt, loc: &Loc) {
+        if let Some(ref init) = switch_stmt.init {
+            self.stmt(init);
+        }
+        let tag = switch_stmt.tag.as_ref().map(|tag| self.expr(tag));
+        let bodies: Vec<BlockId> = switch_stmt.clauses.iter().map(|_| self.new_block()).collect();
+        let end = self.new_block();
+        for (i, case) in switch_stmt.clauses.iter().enumerate() {
+            for e in case.exprs.iter().flatten() {
+                let cond = match tag {
+                    Some(tag) => {
+                        let x = self.expr(e);
+                        self.value(Op::Binary(BinOp::Eq, tag, x), Ty::Bool, &e.loc)
+                    }
+                    None => self.expr(e),
+                };
+                let next = self.new_block();
+                self.terminate(Term::Branch(cond, bodies[i], next), &e.loc);
+                self.seal(next);
+                self.enter(next);
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|c| c.exprs.is_none());
+        self.terminate(Term::Jump(default.map_or(end, |i| bodies[i])), loc);
+
+        self.targets.push_switch(end);
+        for (case, &body) in switch_stmt.clauses.iter().zip(&bodies) {
+            self.seal(body);
+            self.enter(body);
+            self.stmts(&case.body);
+            self.terminate(Term::Jump(end), &case.loc);
+        }
+        self.targets.pop();
+        self.seal(end);
+        self.enter(end);
+    }
+
+    fn for_stmt(&mut self, for_stmt: &ForStmt, loc: &Loc) {
+        if let Some(ref init) = for_stmt.init {
+            self.stmt(init);
+        }
+        let header = self.new_block();
+        let body = self.new_block();
+        let post = self.new_block();
+        let exit = self.new_block();
+        self.terminate(Term::Jump(header), loc);
+        self.enter(header);
+        match for_stmt.cond {
+            Some(ref cond) => {
+                let c = self.expr(cond);
+                self.terminate(Term::Branch(c, body, exit), &cond.loc);
+            }
+            None => self.terminate(Term::Jump(body), loc),
+        }
+        self.seal(body);
+        self.enter(body);
+        self.targets.push_loop(exit, post);
+        self.stmts(&for_stmt.body.stmts);
+        self.targets.pop();
+        self.terminate(Term::Jump(post), &for_stmt.body.loc);
+        self.seal(post);
+        self.enter(post);
+        if let Some(ref stmt) = for_stmt.post {
+            self.stmt(stmt);
+        }
+        self.terminate(Term::Jump(header), loc);
+        self.seal(header);
+        self.seal(exit);
+        self.enter(exit);
+    }
+
+
+    // EXPRESSIONS
+
+    fn expr(&mut self, e: &Expr) -> Value {
+        let ty = e.ty.unwrap_or(INVALID);
+        let loc = &e.loc;
+        if let Some(ref value) = e.value {
+            let c = self.constant(value, ty);
+            return self.value(Op::Const(c), self.ty(ty), loc);
+        }
+        match e.kind {
+            ExprKind::Id(ref id) => {
+                let root = self.root(id);
+                return self.read_root(&root, self.ty(ty), loc);
+            }
+            ExprKind::Unary(UnOp::Plus, ref x) => return self.expr(x),
+            ExprKind::Unary(op, ref x) => {
+                let x = self.expr(x);
+                return self.value(Op::Unary(op, x), self.ty(ty), loc);
+            }
+            ExprKind::Binary(op, ref x, ref y) if op == BinOp::And || op == BinOp::Or => {
+                // `x && y` is `x` if `x` is false, `y` otherwise.
+                let x = self.expr(x);
+                let from = self.current;
+                let rhs = self.new_block();
+                let join = self.new_block();
+                let term = match op {
+                    BinOp::And => Term::Branch(x, rhs, join),
+                    _ => Term::Branch(x, join, rhs),
+                };
+                self.terminate(term, loc);
+                self.seal(rhs);
+                self.enter(rhs);
+                let y = self.expr(y);
+                let end = self.current;
+                self.terminate(Term::Jump(join), loc);
+                self.seal(join);
+                self.enter(join);
+                let phi = self.new_phi(join, Ty::Bool, loc);
+                self.phis[phi].incoming = vec![(from, x), (end, y)];
+                return self.phis[phi].value;
+            }
+            ExprKind::Binary(op, ref x, ref y) => {
+                let x = self.expr(x);
+                let y = self.expr(y);
+                let result = if op.is_comparison() { Ty::Bool } else { self.ty(ty) };
+                return self.value(Op::Binary(op, x, y), result, loc);
+            }
+            ExprKind::Call(_, _) => return self.call(e).unwrap_or_else(|| self.zero(Ty::Int, loc)),
+            ExprKind::Index(ref a, ref i) => {
+                let a_ty = self.ty(a.ty.unwrap_or(INVALID));
+                let a = self.expr(a);
+                let i = self.expr(i);
+                match a_ty {
+                    Ty::Array(len, _) => {
+                        self.check_index(i, Length::Array(len), loc);
+                        return self.value(Op::Index(a, i), self.ty(ty), loc);
+                    }
+                    _ => {
+                        self.check_index(i, Length::Slice(a), loc);
+                        return self.value(Op::LoadElem(a, i), self.ty(ty), loc);
+                    }
+                }
+            }
+            ExprKind::Selector(ref x, ref field) => {
+                let k = self.field_index(x, field);
+                let x = self.expr(x);
+                return self.value(Op::Field(x, k), self.ty(ty), loc);
+            }
+            ExprKind::Append(ref s, ref x) => {
+                let s = self.expr(s);
+                let x = self.expr(x);
+                return self.value(Op::Append(s, x), self.ty(ty), loc);
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => return self.zero(self.ty(ty), loc),
+        }
+    }
+
+    /// Builds a call, a conversion or a call of a built-in
+    /// function; returns `None` for functions without a result.
+    fn call(&mut self, e: &Expr) -> Option<Value> {
+        let (f, args) = match e.kind {
+            ExprKind::Call(ref f, ref args) => (f, args),
+            _ => return None,
+        };
+        let sym = match f.kind {
+            ExprKind::Id(Ident { sym: Some(sym), .. }) => sym,
+            _ => return None,
+        };
+        let ty = e.ty.unwrap_or(INVALID);
+        let loc = &e.loc;
+        match self.table.symbol(sym).kind {
+            SymbolKind::Type => {
+                let x = self.expr(&args[0]);
+                let ty = self.ty(ty);
+                if self.func.types[x] == ty {
+                    return Some(x);
+                }
+                return Some(self.value(Op::Convert(x), ty, loc));
+            }
+            SymbolKind::Builtin => {
+                let x = self.expr(&args[0]);
+                let op = if self.table.symbol(sym).name == "cap" { Op::Cap(x) } else { Op::Len(x) };
+                return Some(self.value(op, Ty::Int, loc));
+            }
+            _ => {
+                let args: Vec<Value> = args.iter().map(|e| self.expr(e)).collect();
+                let index = self.funcs.get(&sym).cloned().unwrap_or(0);
+                let result = if ty == VOID { None } else { Some(self.ty(ty)) };
+                return self.emit(Op::Call(index, args), result, loc);
+            }
+        }
+    }
+
+    /// Returns the constant that represents a value of type `ty`.
+    fn constant(&self, value: &ConstValue, ty: TypeId) -> Const {
+        let ty = self.types.default_type(ty);
+        match *value {
+            ConstValue::Bool(b) => return Const::Bool(b),
+            ConstValue::String(ref s) => return Const::Str(constant::string_bytes(s)),
+            ConstValue::Int(_) | ConstValue::Float(_) if self.types.is_float(ty) => {
+                return Const::Float(value.to_rat().map_or(0.0, |r| r.to_f64()));
+            }
+            ConstValue::Int(_) | ConstValue::Float(_) => {
+                return Const::Int(value.to_int().and_then(|n| n.to_i64()).unwrap_or(0));
+            }
+        }
+    }
+
+    fn field_index(&self, x: &Expr, field: &Ident) -> usize {
+        let index = match *self.types.under(x.ty.unwrap_or(INVALID)) {
+            TypeKind::Struct(ref fields) => fields.iter().position(|f| f.0 == field.name),
+            _ => None,
+        };
+        return index.unwrap_or(0);
+    }
+}
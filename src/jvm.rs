@@ -0,0 +1,1608 @@
+use std::collections::HashMap;
+use std::mem;
+use std::path::Path;
+
+use ast::*;
+use constant::{self, Value};
+use loc::Loc;
+use lower::{self, one, Targets};
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// The runtime support, which is compiled with the classes of
+/// every program.
+const RUNTIME: &str = include_str!("runtime/GoreRuntime.java");
+
+/// Translates a type-checked program to Jasmin assembly for the
+/// JVM, and returns the files to assemble, with their names: one
+/// class for the program, one for every struct type, and the
+/// source of the runtime class (see `runtime/GoreRuntime.java`).
+///
+/// Functions are static methods of the program class, named as
+/// in the program, and package variables are static fields.
+/// Ints are `long`s, floats `double`s, runes `int`s and booleans
+/// `boolean`s; strings are byte arrays, slices `GoreRuntime`
+/// objects, arrays Java arrays, and structs objects of their
+/// classes.  Every variable, element and field of an array or
+/// struct type keeps its object, into which assignments copy
+/// their values, and loading one copies it, so that every value
+/// belongs to the code that uses it.  Static methods of the
+/// program class build the zero values of these types
+/// (`zero$tN`), copy them (`set$tN` and `copy$tN`) and compare
+/// them (`eq$tN`).
+///
+/// `gore` computes the stack height and the local slots of every
+/// method itself, as it generates them.
+pub fn generate(prog: &Program, types: &TypeTable, table: &SymbolTable) -> Vec<(String, String)> {
+    let filename = prog.package.loc.filename.clone();
+    let mut gen = Generator {
+        types: types,
+        table: table,
+        class: class_name(&filename),
+        header: format!("; Generated by gore from {}; see GoreRuntime.java.\n", filename),
+        vars: HashMap::new(),
+        strings: Vec::new(),
+        fields: String::new(),
+        methods: String::new(),
+        structs: Vec::new(),
+        body: String::new(),
+        stack: 0,
+        max_stack: 0,
+        locals: 0,
+        labels: 0,
+        targets: Targets::default(),
+    };
+    gen.type_defs();
+    gen.program(prog);
+
+    // The string literals are built when the class is loaded.
+    gen.begin_method(0);
+    for (i, s) in mem::take(&mut gen.strings).iter().enumerate() {
+        gen.emit(&format!("ldc {}", jasmin_string(s)), 1);
+        gen.invoke("invokestatic", "GoreRuntime/bytes(Ljava/lang/String;)[B");
+        gen.emit(&format!("putstatic {}/str${} [B", gen.class, i), -1);
+    }
+    gen.emit("return", 0);
+    gen.end_method("static <clinit>()V");
+
+    let mut out = gen.header.clone();
+    out.push_str(&format!(".class public {}\n.super java/lang/Object\n\n", gen.class));
+    out.push_str(&gen.fields);
+    out.push_str(&gen.methods);
+    let mut files = vec![(format!("{}.j", gen.class), out)];
+    files.append(&mut gen.structs);
+    files.push(("GoreRuntime.java".to_string(), RUNTIME.to_string()));
+    return files;
+}
+
+/// Where a value is stored.
+#[derive(Clone)]
+enum Place {
+    /// A local variable, in a slot of the current method.
+    Local(u16),
+    /// A local variable that is being declared, whose slots do
+    /// not hold a value yet.
+    New(u16),
+    /// A static field of the program class.
+    Static(String),
+    /// A field, given as `class/name`, of the object on top of
+    /// the stack.
+    Field(String),
+    /// An element of the array below its index on the stack.
+    /// Slices of strings, slices, arrays and structs store their
+    /// elements in `Object` arrays, hence `erased`.
+    Elem { erased: bool },
+    /// An element whose index is only checked when it is
+    /// accessed: the stack holds the array, or the slice twice,
+    /// below the index.
+    Unchecked { len: Option<u64>, elem: TypeId, loc: Loc },
+    /// A field or an element whose operands are in locals.
+    SavedField(u16, String),
+    SavedElem { array: u16, index: u16, erased: bool },
+}
+
+struct Generator<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    /// The name of the program class, and the comment at the
+    /// beginning of every file.
+    class: String,
+    header: String,
+    /// Where the package variables and the variables of the
+    /// current method are stored.
+    vars: HashMap<SymbolId, Place>,
+    /// The string literals, which are the static fields `str$N`.
+    strings: Vec<Vec<u8>>,
+    fields: String,
+    methods: String,
+    /// The classes of the struct types.
+    structs: Vec<(String, String)>,
+    /// The instructions of the current method, the height of its
+    /// operand stack at the current instruction and its maximum,
+    /// and the number of its local slots.
+    body: String,
+    stack: i32,
+    max_stack: i32,
+    locals: u16,
+    /// A counter for the names of labels.
+    labels: usize,
+    targets: Targets<String>,
+}
+
+impl<'a> Generator<'a> {
+    /// Writes an instruction that changes the height of the
+    /// stack by `delta` words.
+    fn emit(&mut self, s: &str, delta: i32) {
+        self.body.push_str(&format!("    {}\n", s));
+        self.stack += delta;
+        self.max_stack = self.max_stack.max(self.stack);
+    }
+
+    fn label(&mut self, label: &str) {
+        self.body.push_str(&format!("  {}:\n", label));
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        return format!("L{}", self.labels);
+    }
+
+    /// Calls a method given as `class/name(descriptor)`.
+    fn invoke(&mut self, inst: &str, method: &str) {
+        let (args, result) = desc_words(method);
+        let this = if inst == "invokestatic" { 0 } else { 1 };
+        self.emit(&format!("{} {}", inst, method), result - args - this);
+    }
+
+    /// Reserves the slots of a local that takes `words` words.
+    fn slot(&mut self, words: i32) -> u16 {
+        let slot = self.locals;
+        self.locals += words as u16;
+        return slot;
+    }
+
+    /// Pops a value of type `ty` into a new local.
+    fn stash(&mut self, ty: TypeId) -> u16 {
+        let words = self.words(ty);
+        let slot = self.slot(words);
+        self.store_local(slot, ty);
+        return slot;
+    }
+
+    fn load_local(&mut self, slot: u16, ty: TypeId) {
+        let (prefix, words) = (self.prefix(ty), self.words(ty));
+        self.emit(&format!("{}load {}", prefix, slot), words);
+    }
+
+    fn store_local(&mut self, slot: u16, ty: TypeId) {
+        let (prefix, words) = (self.prefix(ty), self.words(ty));
+        self.emit(&format!("{}store {}", prefix, slot), -words);
+    }
+
+    fn pop(&mut self, ty: TypeId) {
+        match self.words(ty) {
+            0 => {}
+            1 => self.emit("pop", -1),
+            _ => self.emit("pop2", -2),
+        }
+    }
+
+    fn push_int(&mut self, n: i64) {
+        let inst = match n {
+            -1 => "iconst_m1".to_string(),
+            0 ..= 5 => format!("iconst_{}", n),
+            -128 ..= 127 => format!("bipush {}", n),
+            -32768 ..= 32767 => format!("sipush {}", n),
+            _ => format!("ldc {}", n),
+        };
+        self.emit(&inst, 1);
+    }
+
+    fn push_long(&mut self, n: i64) {
+        match n {
+            0 | 1 => self.emit(&format!("lconst_{}", n), 2),
+            _ => self.emit(&format!("ldc2_w {}", n), 2),
+        }
+    }
+
+    /// Pushes the location of a runtime error, as a Java string.
+    fn location(&mut self, loc: &Loc) {
+        let s = jasmin_string(loc.to_string().as_bytes());
+        self.emit(&format!("ldc {}", s), 1);
+    }
+
+    /// Pushes 1 if the conditional jump `jump`, which pops
+    /// `words` words, is taken, and 0 otherwise.
+    fn test(&mut self, jump: &str, words: i32) {
+        let (yes, end) = (self.new_label(), self.new_label());
+        self.emit(&format!("{} {}", jump, yes), -words);
+        self.emit("iconst_0", 1);
+        self.emit(&format!("goto {}", end), 0);
+        self.stack -= 1;
+        self.label(&yes);
+        self.emit("iconst_1", 1);
+        self.label(&end);
+    }
+
+
+    // TYPES
+
+    /// Returns the kind of a type, untyped constants having
+    /// their default type.
+    fn kind(&self, ty: TypeId) -> &TypeKind {
+        return self.types.under(self.types.default_type(ty));
+    }
+
+    fn is_rune(&self, ty: TypeId) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Rune => return true,
+            _ => return false,
+        }
+    }
+
+    fn is_aggregate(&self, ty: TypeId) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => return true,
+            _ => return false,
+        }
+    }
+
+    /// Returns whether the values of a type are references.
+    fn is_reference(&self, ty: TypeId) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Int | TypeKind::Float64 | TypeKind::Bool | TypeKind::Rune => return false,
+            _ => return true,
+        }
+    }
+
+    /// Returns the number of words that a value takes on the
+    /// stack and in the locals.
+    fn words(&self, ty: TypeId) -> i32 {
+        if ty == VOID {
+            return 0;
+        }
+        match *self.kind(ty) {
+            TypeKind::Int | TypeKind::Float64 => return 2,
+            _ => return 1,
+        }
+    }
+
+    /// Returns the prefix of the instructions on values of a type.
+    fn prefix(&self, ty: TypeId) -> &'static str {
+        match *self.kind(ty) {
+            TypeKind::Int => return "l",
+            TypeKind::Float64 => return "d",
+            TypeKind::Bool | TypeKind::Rune => return "i",
+            _ => return "a",
+        }
+    }
+
+    /// Returns the prefix of the instructions on the elements of
+    /// arrays of a type.
+    fn array_prefix(&self, ty: TypeId) -> &'static str {
+        match *self.kind(ty) {
+            TypeKind::Bool => return "b",
+            _ => return self.prefix(ty),
+        }
+    }
+
+    /// Returns the name of the class of a struct type.
+    fn struct_class(&self, ty: TypeId) -> String {
+        return format!("{}$t{}", self.class, self.types.underlying(self.types.default_type(ty)));
+    }
+
+    /// Returns the JVM descriptor of a type.
+    fn desc(&self, ty: TypeId) -> String {
+        if ty == VOID {
+            return "V".to_string();
+        }
+        match *self.kind(ty) {
+            TypeKind::Int => return "J".to_string(),
+            TypeKind::Float64 => return "D".to_string(),
+            TypeKind::Bool => return "Z".to_string(),
+            TypeKind::Rune => return "I".to_string(),
+            TypeKind::String => return "[B".to_string(),
+            TypeKind::Slice(_) => return "LGoreRuntime;".to_string(),
+            TypeKind::Array(_, elem) => return format!("[{}", self.desc(elem)),
+            _ => return format!("L{};", self.struct_class(ty)),
+        }
+    }
+
+    /// Returns the name of a reference type, as `checkcast` and
+    /// `anewarray` take it.
+    fn class_ref(&self, ty: TypeId) -> String {
+        match *self.kind(ty) {
+            TypeKind::Slice(_) => return "GoreRuntime".to_string(),
+            TypeKind::Struct(_) => return self.struct_class(ty),
+            _ => return self.desc(ty),
+        }
+    }
+
+    /// Returns the type of the array that holds the elements of
+    /// a slice, and the type of the elements that `append` takes.
+    fn backing(&self, elem: TypeId) -> (&'static str, &'static str) {
+        match *self.kind(elem) {
+            TypeKind::Int => return ("[J", "J"),
+            TypeKind::Float64 => return ("[D", "D"),
+            TypeKind::Bool => return ("[Z", "Z"),
+            TypeKind::Rune => return ("[I", "I"),
+            _ => return ("[Ljava/lang/Object;", "Ljava/lang/Object;"),
+        }
+    }
+
+    /// Returns the `class/name` of the field that a selector
+    /// expression selects.
+    fn field(&self, e: &Expr) -> String {
+        if let ExprKind::Selector(ref x, ref field) = e.kind {
+            let x_ty = x.ty.unwrap_or(INVALID);
+            if let TypeKind::Struct(ref fields) = *self.kind(x_ty) {
+                let i = fields.iter().position(|f| f.0 == field.name).unwrap_or(0);
+                return format!("{}/{}", self.struct_class(x_ty), field_name(&field.name, i));
+            }
+        }
+        return String::new();
+    }
+
+    /// Defines the class of every struct type, and the methods
+    /// that build, copy and compare arrays and structs.
+    fn type_defs(&mut self) {
+        let mut defined = vec![false; self.types.len()];
+        for ty in 0 .. self.types.len() {
+            let ty = self.types.underlying(ty);
+            if defined[ty] {
+                continue;
+            }
+            defined[ty] = true;
+            match *self.types.kind(ty) {
+                TypeKind::Array(_, _) | TypeKind::Struct(_) => {}
+                _ => continue,
+            }
+            let types = self.types;
+            if let TypeKind::Struct(ref fields) = *types.kind(ty) {
+                self.struct_def(ty, fields);
+            }
+            self.zero_method(ty);
+            self.set_method(ty);
+            self.copy_method(ty);
+            if self.types.is_comparable(ty) {
+                self.eq_method(ty);
+            }
+        }
+    }
+
+    /// Writes the class of a struct type, whose fields are those
+    /// of the struct; blank fields are named after their index.
+    fn struct_def(&mut self, ty: TypeId, fields: &[(String, TypeId)]) {
+        let class = self.struct_class(ty);
+        let mut out = self.header.clone();
+        out.push_str(&format!(".class public {}\n.super java/lang/Object\n\n", class));
+        for (i, &(ref name, field_ty)) in fields.iter().enumerate() {
+            out.push_str(&format!(".field public {} {}\n", field_name(name, i), self.desc(field_ty)));
+        }
+        out.push_str("\n.method public <init>()V\n    .limit stack 1\n    .limit locals 1\n    aload 0\n    \
+                      invokespecial java/lang/Object/<init>()V\n    return\n.end method\n");
+        self.structs.push((format!("{}.j", class), out));
+    }
+
+    /// Defines `zero$tN`, which returns a new zero value of the
+    /// array or struct type `N`.
+    fn zero_method(&mut self, ty: TypeId) {
+        self.begin_method(0);
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                self.push_int(len as i64);
+                self.new_array(elem);
+                if self.is_aggregate(elem) {
+                    let (array, i) = (self.stash(ty), self.slot(1));
+                    self.each(i, len, |gen| {
+                        gen.emit(&format!("aload {}", array), 1);
+                        gen.emit(&format!("iload {}", i), 1);
+                        gen.zero_value(elem);
+                        gen.emit("aastore", -3);
+                    });
+                    self.emit(&format!("aload {}", array), 1);
+                }
+            }
+            TypeKind::Struct(fields) => {
+                self.new_object(ty);
+                for (i, &(ref name, field_ty)) in fields.iter().enumerate() {
+                    if self.is_aggregate(field_ty) {
+                        self.emit("dup", 1);
+                        self.zero_value(field_ty);
+                        let field = format!("{}/{}", self.struct_class(ty), field_name(name, i));
+                        self.emit(&format!("putfield {} {}", field, self.desc(field_ty)), -2);
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.emit("areturn", -1);
+        let header = format!("static zero$t{}(){}", ty, self.desc(ty));
+        self.end_method(&header);
+    }
+
+    /// Defines `set$tN`, which copies an array or a struct of
+    /// type `N` into another, whose objects stay where they are.
+    fn set_method(&mut self, ty: TypeId) {
+        self.begin_method(2);
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) if self.is_aggregate(elem) => {
+                let i = self.slot(1);
+                self.each(i, len, |gen| {
+                    for a in 0 .. 2 {
+                        gen.emit(&format!("aload {}", a), 1);
+                        gen.emit(&format!("iload {}", i), 1);
+                        gen.emit("aaload", -1);
+                    }
+                    gen.set(elem);
+                });
+            }
+            TypeKind::Array(len, _) => {
+                self.emit("aload 1", 1);
+                self.emit("iconst_0", 1);
+                self.emit("aload 0", 1);
+                self.emit("iconst_0", 1);
+                self.push_int(len as i64);
+                self.invoke("invokestatic", "java/lang/System/arraycopy(Ljava/lang/Object;ILjava/lang/Object;II)V");
+            }
+            TypeKind::Struct(fields) => {
+                for (i, &(ref name, field_ty)) in fields.iter().enumerate() {
+                    let field = format!("{}/{} {}", self.struct_class(ty), field_name(name, i), self.desc(field_ty));
+                    let words = self.words(field_ty);
+                    if self.is_aggregate(field_ty) {
+                        for a in 0 .. 2 {
+                            self.emit(&format!("aload {}", a), 1);
+                            self.emit(&format!("getfield {}", field), 0);
+                        }
+                        self.set(field_ty);
+                    } else {
+                        self.emit("aload 0", 1);
+                        self.emit("aload 1", 1);
+                        self.emit(&format!("getfield {}", field), words - 1);
+                        self.emit(&format!("putfield {}", field), -words - 1);
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.emit("return", 0);
+        let desc = self.desc(ty);
+        self.end_method(&format!("static set$t{}({}{})V", ty, desc, desc));
+    }
+
+    /// Defines `copy$tN`, which returns a copy of an array or a
+    /// struct of type `N`.
+    fn copy_method(&mut self, ty: TypeId) {
+        self.begin_method(1);
+        self.zero_value(ty);
+        self.emit("dup", 1);
+        self.emit("aload 0", 1);
+        self.set(ty);
+        self.emit("areturn", -1);
+        let desc = self.desc(ty);
+        self.end_method(&format!("static copy$t{}({}){}", ty, desc, desc));
+    }
+
+    /// Defines `eq$tN`, which compares two arrays or structs of
+    /// type `N`.  Blank fields are not compared.
+    fn eq_method(&mut self, ty: TypeId) {
+        self.begin_method(2);
+        let differ = self.new_label();
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                let i = self.slot(1);
+                let inst = format!("{}aload", self.array_prefix(elem));
+                let words = self.words(elem);
+                self.each(i, len, |gen| {
+                    for a in 0 .. 2 {
+                        gen.emit(&format!("aload {}", a), 1);
+                        gen.emit(&format!("iload {}", i), 1);
+                        gen.emit(&inst, words - 2);
+                    }
+                    gen.equal(elem);
+                    gen.emit(&format!("ifeq {}", differ), -1);
+                });
+            }
+            TypeKind::Struct(fields) => {
+                for (i, &(ref name, field_ty)) in fields.iter().enumerate() {
+                    if name == "_" {
+                        continue;
+                    }
+                    let field = format!("{}/{} {}", self.struct_class(ty), field_name(name, i), self.desc(field_ty));
+                    let words = self.words(field_ty);
+                    for a in 0 .. 2 {
+                        self.emit(&format!("aload {}", a), 1);
+                        self.emit(&format!("getfield {}", field), words - 1);
+                    }
+                    self.equal(field_ty);
+                    self.emit(&format!("ifeq {}", differ), -1);
+                }
+            }
+            _ => {}
+        }
+        self.emit("iconst_1", 1);
+        self.emit("ireturn", -1);
+        self.label(&differ);
+        self.emit("iconst_0", 1);
+        self.emit("ireturn", -1);
+        let desc = self.desc(ty);
+        self.end_method(&format!("static eq$t{}({}{})Z", ty, desc, desc));
+    }
+
+    /// Generates a loop over the indices of an array of length
+    /// `len`, in the local `i`.
+    fn each<F: FnMut(&mut Generator<'a>)>(&mut self, i: u16, len: u64, mut body: F) {
+        let (head, end) = (self.new_label(), self.new_label());
+        self.emit("iconst_0", 1);
+        self.emit(&format!("istore {}", i), -1);
+        self.label(&head);
+        self.emit(&format!("iload {}", i), 1);
+        self.push_int(len as i64);
+        self.emit(&format!("if_icmpge {}", end), -2);
+        body(self);
+        self.emit(&format!("iinc {} 1", i), 0);
+        self.emit(&format!("goto {}", head), 0);
+        self.label(&end);
+    }
+
+    /// Replaces the length on top of the stack by a new array.
+    fn new_array(&mut self, elem: TypeId) {
+        match *self.kind(elem) {
+            TypeKind::Int => self.emit("newarray long", 0),
+            TypeKind::Float64 => self.emit("newarray double", 0),
+            TypeKind::Bool => self.emit("newarray boolean", 0),
+            TypeKind::Rune => self.emit("newarray int", 0),
+            _ => {
+                let class = self.class_ref(elem);
+                self.emit(&format!("anewarray {}", class), 0);
+            }
+        }
+    }
+
+    /// Pushes a new object of a struct type, whose fields are
+    /// not initialized.
+    fn new_object(&mut self, ty: TypeId) {
+        let class = self.struct_class(ty);
+        self.emit(&format!("new {}", class), 1);
+        self.emit("dup", 1);
+        self.invoke("invokespecial", &format!("{}/<init>()V", class));
+    }
+
+    /// Pushes the zero value of a type.
+    fn zero_value(&mut self, ty: TypeId) {
+        match *self.kind(ty) {
+            TypeKind::Int => self.emit("lconst_0", 2),
+            TypeKind::Float64 => self.emit("dconst_0", 2),
+            TypeKind::Bool | TypeKind::Rune => self.emit("iconst_0", 1),
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => {
+                let method = format!("{}/zero$t{}(){}", self.class, self.types.underlying(ty), self.desc(ty));
+                self.invoke("invokestatic", &method);
+            }
+            _ => self.emit("aconst_null", 1),
+        }
+    }
+
+    /// Copies the array or struct on top of the stack into the
+    /// one below it.
+    fn set(&mut self, ty: TypeId) {
+        let desc = self.desc(ty);
+        let method = format!("{}/set$t{}({}{})V", self.class, self.types.underlying(ty), desc, desc);
+        self.invoke("invokestatic", &method);
+    }
+
+    /// Replaces the array or struct on top of the stack by a
+    /// copy.
+    fn copy(&mut self, ty: TypeId) {
+        let desc = self.desc(ty);
+        let method = format!("{}/copy$t{}({}){}", self.class, self.types.underlying(ty), desc, desc);
+        self.invoke("invokestatic", &method);
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) if !f.name.is_blank() => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        let mut aggregates = Vec::new();
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        let ty = self.var_type(id);
+                        self.fields.push_str(&format!(".field static {} {}\n", id.name, self.desc(ty)));
+                        if self.is_aggregate(ty) {
+                            aggregates.push((id.name.clone(), ty));
+                        }
+                        if let Some(sym) = id.sym {
+                            self.vars.insert(sym, Place::Static(id.name.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Package variables are initialized in the order of
+        // their declarations, once all the arrays and structs
+        // exist.
+        self.begin_method(0);
+        for (name, ty) in aggregates {
+            self.zero_value(ty);
+            let inst = format!("putstatic {}/{} {}", self.class, name, self.desc(ty));
+            self.emit(&inst, -1);
+        }
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec, false);
+                }
+            }
+        }
+        self.emit("return", 0);
+        self.end_method("static gore$init()V");
+
+        for f in &funcs {
+            self.func_decl(f);
+        }
+
+        self.begin_method(1);
+        let class = self.class.clone();
+        self.invoke("invokestatic", &format!("{}/gore$init()V", class));
+        if funcs.iter().any(|f| f.name.name == "main") {
+            self.invoke("invokestatic", &format!("{}/main()V", class));
+        }
+        self.invoke("invokestatic", "GoreRuntime/exit()V");
+        self.emit("return", 0);
+        self.end_method("static main([Ljava/lang/String;)V");
+    }
+
+    /// Starts a method whose parameters take `params` slots.
+    fn begin_method(&mut self, params: u16) {
+        self.stack = 0;
+        self.max_stack = 0;
+        self.locals = params;
+        self.labels = 0;
+    }
+
+    /// Writes the current method, given its name and
+    /// descriptor, with the limits of its stack and locals.
+    fn end_method(&mut self, header: &str) {
+        let body = mem::take(&mut self.body);
+        self.methods.push_str(&format!("\n.method public {}\n    .limit stack {}\n    .limit locals {}\n{}.end method\n",
+                                       header, self.max_stack, self.locals, body));
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.begin_method(0);
+        let result = lower::result_type(f, self.types, self.table);
+        let mut desc = String::from("(");
+        for p in &f.params {
+            let ty = self.var_type(&p.name);
+            let words = self.words(ty);
+            let slot = self.slot(words);
+            if let Some(sym) = p.name.sym {
+                self.vars.insert(sym, Place::Local(slot));
+            }
+            desc.push_str(&self.desc(ty));
+        }
+        desc.push(')');
+        desc.push_str(&self.desc(result));
+        self.stmts(&f.body.stmts);
+        // The type checker makes sure that the end of a function
+        // with a result is not reached, but labels may refer to
+        // it.
+        if result == VOID {
+            self.emit("return", 0);
+        } else {
+            self.emit("aconst_null", 1);
+            self.emit("athrow", -1);
+        }
+        self.end_method(&format!("static {}{}", f.name.name, desc));
+    }
+
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    fn var(&self, id: &Ident) -> Place {
+        match id.sym.and_then(|sym| self.vars.get(&sym)) {
+            Some(place) => return place.clone(),
+            None => return Place::Local(0),
+        }
+    }
+
+    /// Declares a local variable in new slots.
+    fn local(&mut self, id: &Ident) -> Place {
+        let ty = self.var_type(id);
+        let words = self.words(ty);
+        let slot = self.slot(words);
+        if let Some(sym) = id.sym {
+            self.vars.insert(sym, Place::Local(slot));
+        }
+        return Place::New(slot);
+    }
+
+    /// Declares (or, for package variables, initializes) the
+    /// variables of a `var` spec.
+    fn var_spec(&mut self, spec: &VarSpec, local: bool) {
+        if spec.values.is_empty() {
+            if local {
+                for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                    let var = self.local(id);
+                    let ty = self.var_type(id);
+                    self.zero_value(ty);
+                    self.store(var, ty);
+                }
+            }
+            return;
+        }
+        let targets: Vec<Option<Place>> = spec.names.iter()
+            .map(|id| {
+                if id.is_blank() {
+                    return None;
+                }
+                return Some(if local { self.local(id) } else { self.var(id) });
+            })
+            .collect();
+        self.store_values(targets, &spec.values);
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => {
+                if let ExprKind::Call(ref f, ref args) = e.kind {
+                    let ty = e.ty.unwrap_or(VOID);
+                    self.func_call(f, args, ty);
+                    self.pop(ty);
+                }
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec, true);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs),
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, &stmt.loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), &stmt.loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), &stmt.loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                let targets: Vec<Option<Place>> = names.iter()
+                    .map(|id| {
+                        if id.is_blank() {
+                            return None;
+                        }
+                        return Some(if self.is_new_var(id) { self.local(id) } else { self.var(id) });
+                    })
+                    .collect();
+                self.store_values(targets, values);
+            }
+            StmtKind::Print(ref args) => self.print(args, false),
+            StmtKind::Println(ref args) => self.print(args, true),
+            StmtKind::Return(ref e) => {
+                match *e {
+                    Some(ref e) => {
+                        let ty = e.ty.unwrap_or(INVALID);
+                        self.expr(e);
+                        let (prefix, words) = (self.prefix(ty), self.words(ty));
+                        self.emit(&format!("{}return", prefix), -words);
+                    }
+                    None => self.emit("return", 0),
+                }
+            }
+            StmtKind::Break => {
+                if let Some(label) = self.targets.brk() {
+                    self.emit(&format!("goto {}", label), 0);
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(label) = self.targets.cont() {
+                    self.emit(&format!("goto {}", label), 0);
+                }
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+        }
+    }
+
+    /// Returns whether a name of a `:=` statement declares a
+    /// variable, rather than assigning to an existing one.
+    fn is_new_var(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => self.table.symbol(sym).loc.as_ref() == Some(&id.loc),
+            None => false,
+        }
+    }
+
+    /// Assigns values to variables, elements and fields.  The
+    /// index of a single element is checked once its value is
+    /// known; with several targets, their operands are all
+    /// evaluated and checked before the values.
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr]) {
+        if lhs.len() == 1 {
+            if let ExprKind::Blank = lhs[0].kind {
+                self.expr(&rhs[0]);
+                self.pop(rhs[0].ty.unwrap_or(INVALID));
+                return;
+            }
+            let place = self.place(&lhs[0]);
+            let place = self.prepare(place);
+            self.expr(&rhs[0]);
+            self.store(place, rhs[0].ty.unwrap_or(INVALID));
+            return;
+        }
+        let mut targets = Vec::new();
+        for e in lhs {
+            let target = match e.kind {
+                ExprKind::Blank => None,
+                _ => {
+                    let place = self.place(e);
+                    Some(self.save(place))
+                }
+            };
+            targets.push(target);
+        }
+        self.store_values(targets, rhs);
+    }
+
+    /// Evaluates all the values before storing them, from left
+    /// to right, in places whose operands are not on the stack.
+    fn store_values(&mut self, targets: Vec<Option<Place>>, values: &[Expr]) {
+        if values.len() == 1 {
+            let ty = values[0].ty.unwrap_or(INVALID);
+            match targets.into_iter().next() {
+                Some(Some(place)) => {
+                    let place = self.prepare(place);
+                    self.expr(&values[0]);
+                    self.store(place, ty);
+                }
+                _ => {
+                    self.expr(&values[0]);
+                    self.pop(ty);
+                }
+            }
+            return;
+        }
+        let temps: Vec<u16> = values.iter()
+            .map(|e| {
+                self.expr(e);
+                return self.stash(e.ty.unwrap_or(INVALID));
+            })
+            .collect();
+        for ((target, e), temp) in targets.into_iter().zip(values).zip(temps) {
+            if let Some(place) = target {
+                let ty = e.ty.unwrap_or(INVALID);
+                let place = self.prepare(place);
+                self.load_local(temp, ty);
+                self.store(place, ty);
+            }
+        }
+    }
+
+    /// `x op= y`: the object or array and index of `x` are saved
+    /// in locals, for its load and then its store.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let ty = lhs.ty.unwrap_or(INVALID);
+        let place = self.place(lhs);
+        let place = self.save(place);
+        let target = self.prepare(place.clone());
+        self.load(place, ty);
+        self.expr(rhs);
+        self.arith(op, ty, rhs.ty.unwrap_or(INVALID), loc);
+        self.store(target, ty);
+    }
+
+    /// Stashes the values of all the arguments in locals, then
+    /// passes each of them to the runtime class.
+    fn print(&mut self, args: &[Expr], newline: bool) {
+        let temps: Vec<u16> = args.iter()
+            .map(|arg| {
+                self.expr(arg);
+                return self.stash(arg.ty.unwrap_or(INVALID));
+            })
+            .collect();
+        for (i, (arg, temp)) in args.iter().zip(temps).enumerate() {
+            if newline && i > 0 {
+                self.invoke("invokestatic", "GoreRuntime/printSpace()V");
+            }
+            let ty = arg.ty.unwrap_or(INVALID);
+            self.load_local(temp, ty);
+            self.to_long(ty);
+            let desc = if self.is_rune(ty) { "J".to_string() } else { self.desc(ty) };
+            self.invoke("invokestatic", &format!("GoreRuntime/print({})V", desc));
+        }
+        if newline {
+            self.invoke("invokestatic", "GoreRuntime/println()V");
+        }
+    }
+
+    /// Generates the simple statement that introduces an `if`,
+    /// `switch` or `for` statement.
+    fn init(&mut self, init: &Option<Box<Stmt>>) {
+        if let Some(ref init) = *init {
+            self.stmt(init);
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        self.init(&if_stmt.init);
+        let (els, end) = (self.new_label(), self.new_label());
+        self.expr(&if_stmt.cond);
+        self.emit(&format!("ifeq {}", els), -1);
+        self.stmts(&if_stmt.then.stmts);
+        match if_stmt.els {
+            Some(ref stmt) => {
+                self.emit(&format!("goto {}", end), 0);
+                self.label(&els);
+                self.stmt(stmt);
+                self.label(&end);
+            }
+            None => self.label(&els),
+        }
+    }
+
+    /// The tag and the case expressions are evaluated in order
+    /// until a case matches, which jumps to its clause; the
+    /// clauses follow each other, and jump to the end.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) {
+        self.init(&switch_stmt.init);
+        let tag = switch_stmt.tag.as_ref().map(|tag| {
+            self.expr(tag);
+            let ty = tag.ty.unwrap_or(INVALID);
+            return (self.stash(ty), ty);
+        });
+        let end = self.new_label();
+        let bodies: Vec<String> = switch_stmt.clauses.iter().map(|_| self.new_label()).collect();
+        for (case, body) in switch_stmt.clauses.iter().zip(&bodies) {
+            let exprs = match case.exprs {
+                Some(ref exprs) => exprs,
+                None => continue,
+            };
+            for e in exprs {
+                match tag {
+                    Some((tag, ty)) => {
+                        self.load_local(tag, ty);
+                        self.expr(e);
+                        self.equal(ty);
+                    }
+                    None => self.expr(e),
+                }
+                self.emit(&format!("ifne {}", body), -1);
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|c| c.exprs.is_none());
+        let otherwise = default.map_or(end.clone(), |i| bodies[i].clone());
+        self.emit(&format!("goto {}", otherwise), 0);
+
+        self.targets.push_switch(end.clone());
+        for (i, (case, body)) in switch_stmt.clauses.iter().zip(&bodies).enumerate() {
+            self.label(body);
+            self.stmts(&case.body);
+            if i + 1 < bodies.len() {
+                self.emit(&format!("goto {}", end), 0);
+            }
+        }
+        self.targets.pop();
+        self.label(&end);
+    }
+
+    /// The condition is tested at the top of the loop, and
+    /// `continue` jumps to the post statement.
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        self.init(&for_stmt.init);
+        let (head, post, end) = (self.new_label(), self.new_label(), self.new_label());
+        self.label(&head);
+        if let Some(ref cond) = for_stmt.cond {
+            self.expr(cond);
+            self.emit(&format!("ifeq {}", end), -1);
+        }
+        self.targets.push_loop(end.clone(), post.clone());
+        self.stmts(&for_stmt.body.stmts);
+        self.targets.pop();
+        self.label(&post);
+        if let Some(ref stmt) = for_stmt.post {
+            self.stmt(stmt);
+        }
+        self.emit(&format!("goto {}", head), 0);
+        self.label(&end);
+    }
+
+
+    // EXPRESSIONS
+
+    /// Pushes the value of `e`, if it has one.
+    fn expr(&mut self, e: &Expr) {
+        let ty = e.ty.unwrap_or(INVALID);
+        if let Some(ref value) = e.value {
+            self.constant(value, ty);
+            return;
+        }
+        match e.kind {
+            ExprKind::Unary(op, ref x) => self.unary(op, ty, x),
+            ExprKind::Binary(BinOp::And, ref x, ref y) => self.logical(true, x, y),
+            ExprKind::Binary(BinOp::Or, ref x, ref y) => self.logical(false, x, y),
+            ExprKind::Binary(op, ref x, ref y) => {
+                let (x_ty, y_ty) = (x.ty.unwrap_or(INVALID), y.ty.unwrap_or(INVALID));
+                self.expr(x);
+                self.expr(y);
+                if op.is_comparison() {
+                    self.compare(op, x_ty);
+                } else {
+                    self.arith(op, x_ty, y_ty, &e.loc);
+                }
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                match kind {
+                    SymbolKind::Type => self.conversion(ty, &args[0]),
+                    SymbolKind::Builtin => self.builtin(f, &args[0]),
+                    _ => self.func_call(f, args, ty),
+                }
+            }
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => {
+                let place = self.place(e);
+                self.load(place, ty);
+            }
+            ExprKind::Append(ref s, ref x) => {
+                self.expr(s);
+                self.expr(x);
+                let (_, elem) = self.backing(x.ty.unwrap_or(INVALID));
+                self.invoke("invokestatic", &format!("GoreRuntime/append(LGoreRuntime;{})LGoreRuntime;", elem));
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => self.zero_value(ty),
+        }
+    }
+
+    /// Returns where the value of a variable, element or field
+    /// is stored, after pushing the operands of its access.
+    fn place(&mut self, e: &Expr) -> Place {
+        match e.kind {
+            ExprKind::Id(ref id) => return self.var(id),
+            ExprKind::Index(ref a, ref i) => {
+                let elem = e.ty.unwrap_or(INVALID);
+                let len = match *self.kind(a.ty.unwrap_or(INVALID)) {
+                    TypeKind::Array(len, _) => {
+                        self.base(a);
+                        Some(len)
+                    }
+                    _ => {
+                        self.expr(a);
+                        self.emit("dup", 1);
+                        None
+                    }
+                };
+                self.expr(i);
+                self.to_long(i.ty.unwrap_or(INVALID));
+                return Place::Unchecked { len: len, elem: elem, loc: e.loc.clone() };
+            }
+            ExprKind::Selector(ref x, _) => {
+                self.base(x);
+                return Place::Field(self.field(e));
+            }
+            // Only variables, elements and fields are stored to.
+            _ => return Place::Local(0),
+        }
+    }
+
+    /// Pushes the array or the struct whose element or field an
+    /// expression selects, without copying it.
+    fn base(&mut self, e: &Expr) {
+        match e.kind {
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => {
+                let place = self.place(e);
+                self.push_ref(place, e.ty.unwrap_or(INVALID));
+            }
+            _ => self.expr(e),
+        }
+    }
+
+    /// Checks the index of an element, and leaves the array that
+    /// holds it below the index.
+    fn resolve(&mut self, place: Place) -> Place {
+        match place {
+            Place::Unchecked { len: Some(len), .. } => {
+                self.push_long(len as i64);
+                return self.resolve_at(place);
+            }
+            Place::Unchecked { elem, .. } => {
+                let place = self.resolve_at(place);
+                let (backing, _) = self.backing(elem);
+                self.emit("swap", 0);
+                self.emit("getfield GoreRuntime/data Ljava/lang/Object;", 0);
+                self.emit(&format!("checkcast {}", backing), 0);
+                self.emit("swap", 0);
+                return place;
+            }
+            place => return place,
+        }
+    }
+
+    fn resolve_at(&mut self, place: Place) -> Place {
+        let (len, elem, loc) = match place {
+            Place::Unchecked { len, elem, loc } => (len, elem, loc),
+            place => return place,
+        };
+        self.location(&loc);
+        if len.is_some() {
+            self.invoke("invokestatic", "GoreRuntime/index(JJLjava/lang/String;)I");
+        } else {
+            self.invoke("invokestatic", "GoreRuntime/index(LGoreRuntime;JLjava/lang/String;)I");
+        }
+        return Place::Elem { erased: len.is_none() && self.is_reference(elem) };
+    }
+
+    /// Moves the operands of a field or an element to locals.
+    fn save(&mut self, place: Place) -> Place {
+        match self.resolve(place) {
+            Place::Field(field) => {
+                let object = self.slot(1);
+                self.emit(&format!("astore {}", object), -1);
+                return Place::SavedField(object, field);
+            }
+            Place::Elem { erased } => {
+                let (array, index) = (self.slot(1), self.slot(1));
+                self.emit(&format!("istore {}", index), -1);
+                self.emit(&format!("astore {}", array), -1);
+                return Place::SavedElem { array: array, index: index, erased: erased };
+            }
+            place => return place,
+        }
+    }
+
+    /// Pushes the saved operands of a field or an element,
+    /// before its value is pushed.
+    fn prepare(&mut self, place: Place) -> Place {
+        match place {
+            Place::SavedField(object, field) => {
+                self.emit(&format!("aload {}", object), 1);
+                return Place::Field(field);
+            }
+            Place::SavedElem { array, index, erased } => {
+                self.emit(&format!("aload {}", array), 1);
+                self.emit(&format!("iload {}", index), 1);
+                return Place::Elem { erased: erased };
+            }
+            place => return place,
+        }
+    }
+
+    /// Pushes the value stored in a place, without copying it.
+    fn push_ref(&mut self, place: Place, ty: TypeId) {
+        let words = self.words(ty);
+        let place = self.resolve(place);
+        match self.prepare(place) {
+            Place::Local(slot) | Place::New(slot) => self.load_local(slot, ty),
+            Place::Static(name) => {
+                let inst = format!("getstatic {}/{} {}", self.class, name, self.desc(ty));
+                self.emit(&inst, words);
+            }
+            Place::Field(field) => {
+                let inst = format!("getfield {} {}", field, self.desc(ty));
+                self.emit(&inst, words - 1);
+            }
+            Place::Elem { erased } => {
+                let inst = format!("{}aload", if erased { "a" } else { self.array_prefix(ty) });
+                self.emit(&inst, words - 2);
+                if erased {
+                    let class = self.class_ref(ty);
+                    self.emit(&format!("checkcast {}", class), 0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Pushes the value stored in a place; an array or a struct
+    /// is copied.
+    fn load(&mut self, place: Place, ty: TypeId) {
+        self.push_ref(place, ty);
+        if self.is_aggregate(ty) {
+            self.copy(ty);
+        }
+    }
+
+    /// Pops a value into a place, whose operands, if any, are
+    /// below it on the stack; arrays and structs are copied into
+    /// the objects of their places.  The index of an element is
+    /// checked after its value is computed.
+    fn store(&mut self, place: Place, ty: TypeId) {
+        let words = self.words(ty);
+        match place {
+            Place::New(slot) => self.store_local(slot, ty),
+            place if self.is_aggregate(ty) => {
+                let value = self.stash(ty);
+                self.push_ref(place, ty);
+                self.load_local(value, ty);
+                self.set(ty);
+            }
+            Place::Local(slot) => self.store_local(slot, ty),
+            Place::Static(name) => {
+                let inst = format!("putstatic {}/{} {}", self.class, name, self.desc(ty));
+                self.emit(&inst, -words);
+            }
+            Place::Field(field) => {
+                let inst = format!("putfield {} {}", field, self.desc(ty));
+                self.emit(&inst, -words - 1);
+            }
+            Place::Elem { erased } => {
+                let inst = format!("{}astore", if erased { "a" } else { self.array_prefix(ty) });
+                self.emit(&inst, -words - 2);
+            }
+            place => {
+                let value = self.stash(ty);
+                let place = self.resolve(place);
+                let place = self.prepare(place);
+                self.load_local(value, ty);
+                self.store(place, ty);
+            }
+        }
+    }
+
+    /// Widens a rune on top of the stack to an `int`.
+    fn to_long(&mut self, ty: TypeId) {
+        if self.is_rune(ty) {
+            self.emit("i2l", 1);
+        }
+    }
+
+    fn constant(&mut self, value: &Value, ty: TypeId) {
+        match *value {
+            Value::Bool(b) => self.push_int(b as i64),
+            Value::String(ref s) => {
+                let bytes = constant::string_bytes(s);
+                if bytes.is_empty() {
+                    self.emit("aconst_null", 1);
+                    return;
+                }
+                let i = match self.strings.iter().position(|t| *t == bytes) {
+                    Some(i) => i,
+                    None => {
+                        self.strings.push(bytes);
+                        self.fields.push_str(&format!(".field static final str${} [B\n", self.strings.len() - 1));
+                        self.strings.len() - 1
+                    }
+                };
+                let inst = format!("getstatic {}/str${} [B", self.class, i);
+                self.emit(&inst, 1);
+            }
+            Value::Int(_) | Value::Float(_) if self.prefix(ty) == "d" => {
+                let f = value.to_rat().map_or(0.0, |r| r.to_f64());
+                if f == 0.0 && f.is_sign_positive() {
+                    self.emit("dconst_0", 2);
+                } else if f == 1.0 {
+                    self.emit("dconst_1", 2);
+                } else {
+                    self.emit(&format!("ldc2_w {}", jasmin_double(f)), 2);
+                }
+            }
+            Value::Int(_) | Value::Float(_) => {
+                let n = value.to_int().and_then(|n| n.to_i64()).unwrap_or(0);
+                if self.prefix(ty) == "l" {
+                    self.push_long(n);
+                } else {
+                    self.push_int(n as i32 as i64);
+                }
+            }
+        }
+    }
+
+    fn unary(&mut self, op: UnOp, ty: TypeId, x: &Expr) {
+        self.expr(x);
+        let prefix = self.prefix(ty);
+        match op {
+            UnOp::Plus => {}
+            UnOp::Neg => self.emit(&format!("{}neg", prefix), 0),
+            UnOp::Not => {
+                self.emit("iconst_1", 1);
+                self.emit("ixor", -1);
+            }
+            UnOp::BitNot if prefix == "l" => {
+                self.push_long(-1);
+                self.emit("lxor", -2);
+            }
+            UnOp::BitNot => {
+                self.emit("iconst_m1", 1);
+                self.emit("ixor", -1);
+            }
+        }
+    }
+
+    /// `&&` and `||` only evaluate their right operand if
+    /// needed.
+    fn logical(&mut self, and: bool, x: &Expr, y: &Expr) {
+        let (short, end) = (self.new_label(), self.new_label());
+        self.expr(x);
+        self.emit(&format!("{} {}", if and { "ifeq" } else { "ifne" }, short), -1);
+        self.expr(y);
+        self.emit(&format!("goto {}", end), 0);
+        self.stack -= 1;
+        self.label(&short);
+        self.emit(if and { "iconst_0" } else { "iconst_1" }, 1);
+        self.label(&end);
+    }
+
+    /// Applies an arithmetic or bitwise operator to the two
+    /// values on top of the stack, of type `ty` (and `y_ty` for
+    /// the count of a shift).  Integer operations wrap around
+    /// and division by zero panics, as in Go.
+    fn arith(&mut self, op: BinOp, ty: TypeId, y_ty: TypeId, loc: &Loc) {
+        let (prefix, words) = (self.prefix(ty), self.words(ty));
+        let desc = match *self.kind(ty) {
+            TypeKind::Int => "J",
+            TypeKind::Rune => "I",
+            TypeKind::String => {
+                self.invoke("invokestatic", "GoreRuntime/concat([B[B)[B");
+                return;
+            }
+            _ => {
+                let inst = match op {
+                    BinOp::Add => "dadd",
+                    BinOp::Sub => "dsub",
+                    BinOp::Mul => "dmul",
+                    _ => "ddiv",
+                };
+                self.emit(inst, -2);
+                return;
+            }
+        };
+        let inst = match op {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::BitAnd => "and",
+            BinOp::BitOr => "or",
+            BinOp::BitXor => "xor",
+            BinOp::BitClear => {
+                if prefix == "l" {
+                    self.push_long(-1);
+                } else {
+                    self.emit("iconst_m1", 1);
+                }
+                self.emit(&format!("{}xor", prefix), -words);
+                "and"
+            }
+            BinOp::Div | BinOp::Rem => {
+                self.location(loc);
+                let f = if op == BinOp::Div { "div" } else { "rem" };
+                self.invoke("invokestatic", &format!("GoreRuntime/{}({}{}Ljava/lang/String;){}", f, desc, desc, desc));
+                return;
+            }
+            _ => {
+                self.to_long(y_ty);
+                self.location(loc);
+                let f = if op == BinOp::Shl { "shl" } else { "shr" };
+                self.invoke("invokestatic", &format!("GoreRuntime/{}({}JLjava/lang/String;){}", f, desc, desc));
+                return;
+            }
+        };
+        self.emit(&format!("{}{}", prefix, inst), -words);
+    }
+
+    /// Compares the two values on top of the stack.
+    fn compare(&mut self, op: BinOp, ty: TypeId) {
+        match op {
+            BinOp::Eq => return self.equal(ty),
+            BinOp::Ne => {
+                self.equal(ty);
+                self.emit("iconst_1", 1);
+                self.emit("ixor", -1);
+                return;
+            }
+            _ => {}
+        }
+        let cond = match op {
+            BinOp::Lt => "lt",
+            BinOp::Le => "le",
+            BinOp::Gt => "gt",
+            _ => "ge",
+        };
+        match *self.kind(ty) {
+            // NaN is neither less nor greater than anything.
+            TypeKind::Float64 if cond == "lt" || cond == "le" => self.emit("dcmpg", -3),
+            TypeKind::Float64 => self.emit("dcmpl", -3),
+            TypeKind::String => self.invoke("invokestatic", "GoreRuntime/compare([B[B)I"),
+            TypeKind::Int => self.emit("lcmp", -3),
+            _ => return self.test(&format!("if_icmp{}", cond), 2),
+        }
+        self.test(&format!("if{}", cond), 1);
+    }
+
+    fn equal(&mut self, ty: TypeId) {
+        let ty = self.types.default_type(ty);
+        match *self.kind(ty) {
+            TypeKind::Float64 => {
+                self.emit("dcmpl", -3);
+                self.test("ifeq", 1);
+            }
+            TypeKind::Int => {
+                self.emit("lcmp", -3);
+                self.test("ifeq", 1);
+            }
+            TypeKind::String => self.invoke("invokestatic", "GoreRuntime/equal([B[B)Z"),
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => {
+                let desc = self.desc(ty);
+                let method = format!("{}/eq$t{}({}{})Z", self.class, self.types.underlying(ty), desc, desc);
+                self.invoke("invokestatic", &method);
+            }
+            _ => self.test("if_icmpeq", 2),
+        }
+    }
+
+    /// Calls a function, after evaluating its arguments, and
+    /// pushes its result.
+    fn func_call(&mut self, f: &Expr, args: &[Expr], ty: TypeId) {
+        let (name, params) = match f.kind {
+            ExprKind::Id(ref id) => {
+                let params = match id.sym.and_then(|sym| self.table.symbol(sym).ty) {
+                    Some(ty) => match *self.types.kind(ty) {
+                        TypeKind::Func(ref params, _) => params.clone(),
+                        _ => Vec::new(),
+                    },
+                    None => Vec::new(),
+                };
+                (id.name.clone(), params)
+            }
+            _ => (String::new(), Vec::new()),
+        };
+        for arg in args {
+            self.expr(arg);
+        }
+        let mut desc = String::from("(");
+        for &param in &params {
+            desc.push_str(&self.desc(param));
+        }
+        desc.push(')');
+        desc.push_str(&self.desc(ty));
+        let method = format!("{}/{}{}", self.class, name, desc);
+        self.invoke("invokestatic", &method);
+    }
+
+    fn conversion(&mut self, ty: TypeId, arg: &Expr) {
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        self.expr(arg);
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            self.to_long(arg_ty);
+            self.invoke("invokestatic", "GoreRuntime/fromRune(J)[B");
+            return;
+        }
+        match (self.prefix(arg_ty), self.prefix(ty)) {
+            // Out of range values saturate, as in the
+            // interpreters.
+            ("d", "l") => self.emit("d2l", 0),
+            ("d", "i") => {
+                self.emit("d2l", 0);
+                self.emit("l2i", -1);
+            }
+            ("l", "d") => self.emit("l2d", 0),
+            ("i", "d") => self.emit("i2d", 1),
+            ("i", "l") => self.emit("i2l", 1),
+            ("l", "i") => self.emit("l2i", -1),
+            _ => {}
+        }
+    }
+
+    fn builtin(&mut self, f: &Expr, arg: &Expr) {
+        let cap = match f.kind {
+            ExprKind::Id(ref id) => id.name == "cap",
+            _ => false,
+        };
+        let ty = arg.ty.unwrap_or(INVALID);
+        match *self.kind(ty) {
+            TypeKind::Array(len, _) => self.push_long(len as i64),
+            TypeKind::String => {
+                self.expr(arg);
+                self.invoke("invokestatic", "GoreRuntime/len([B)J");
+            }
+            _ => {
+                self.expr(arg);
+                let f = if cap { "cap" } else { "len" };
+                self.invoke("invokestatic", &format!("GoreRuntime/{}(LGoreRuntime;)J", f));
+            }
+        }
+    }
+}
+
+/// Returns the name of the class of a program, after its file.
+fn class_name(filename: &str) -> String {
+    let stem = Path::new(filename).file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+    let mut name: String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    return name;
+}
+
+/// Returns the name of the field of a struct class for the field
+/// `i` of a struct type.
+fn field_name(name: &str, i: usize) -> String {
+    if name == "_" {
+        return format!("_${}", i);
+    }
+    return name.to_string();
+}
+
+/// Returns the number of words that the arguments and the result
+/// of a method take, given its descriptor.
+fn desc_words(method: &str) -> (i32, i32) {
+    let desc = &method[method.find('(').unwrap_or(0) + 1 ..];
+    let mut chars = desc.chars();
+    let mut args = 0;
+    loop {
+        let mut c = match chars.next() {
+            Some(')') | None => break,
+            Some(c) => c,
+        };
+        let array = c == '[';
+        while c == '[' {
+            c = chars.next().unwrap_or('V');
+        }
+        if c == 'L' {
+            while chars.next().is_some_and(|c| c != ';') {}
+        }
+        args += if !array && (c == 'J' || c == 'D') { 2 } else { 1 };
+    }
+    let result = match chars.next() {
+        Some('V') => 0,
+        Some('J') | Some('D') => 2,
+        _ => 1,
+    };
+    return (args, result);
+}
+
+/// Returns a Jasmin string literal whose characters are the
+/// given bytes.  Quotes, backslashes and anything but printable
+/// ASCII are escaped.
+fn jasmin_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20 ..= 0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\u{:04x}", b)),
+        }
+    }
+    out.push('"');
+    return out;
+}
+
+/// Formats a double so that Jasmin does not take it for a long.
+fn jasmin_double(f: f64) -> String {
+    let s = format!("{:?}", f);
+    if s.contains('.') {
+        return s;
+    }
+    match s.find('e') {
+        Some(i) => return format!("{}.0{}", &s[.. i], &s[i ..]),
+        None => return format!("{}.0", s),
+    }
+}
@@ -1,4 +1,44 @@
+// The code base favours explicit `return` statements and
+// explicit field initializers.
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+#![allow(clippy::manual_range_contains, clippy::match_like_matches_macro)]
+#![allow(clippy::should_implement_trait, clippy::wrong_self_convention)]
+#![allow(clippy::large_enum_variant)]
+
 pub mod loc;
 pub mod error;
 pub mod token;
 pub mod scanner;
+pub mod ast;
+pub mod parser;
+pub mod pretty;
+pub mod weeder;
+pub mod symtab;
+pub mod types;
+pub mod constant;
+pub mod lower;
+pub mod typecheck;
+pub mod reach;
+pub mod unused;
+pub mod cfg;
+pub mod codegen;
+pub mod llvm;
+pub mod regalloc;
+pub mod x86_64;
+pub mod wasm;
+pub mod jvm;
+pub mod interp;
+pub mod bytecode;
+pub mod bcgen;
+pub mod vm;
+pub mod bcfile;
+pub mod ir;
+pub mod irgen;
+pub mod ireval;
+pub mod fold;
+pub mod dce;
+pub mod inline;
+pub mod loops;
+pub mod licm;
+pub mod strength;
+pub mod opt;
@@ -0,0 +1,89 @@
+use ast::BinOp;
+use bytecode::Const;
+use dce;
+use ir::{Function, Inst, Op, Ty};
+use loops::{self, Loop};
+
+/// Moves the instructions of the loops of a function that compute
+/// the same value at every iteration, because their operands are
+/// defined outside the loop or are themselves invariant, to the
+/// preheaders of the loops.  Inner loops come first, so that an
+/// instruction can leave several loops.
+///
+/// Hoisted instructions run even if the loop condition is false,
+/// so the operations that may panic, index checks and the integer
+/// divisions and shifts that `dce` keeps, are only hoisted from
+/// the start of the header, which computes the condition and runs
+/// as soon as the preheader does, if everything before them in it
+/// is hoisted too.  Calls never are, nor the operations that
+/// assume that their index was checked, nor the reads of memory,
+/// which the loop may write.
+pub fn hoist(f: &mut Function) {
+    let loops = loops::insert_preheaders(f);
+    let mut consts = vec![None; f.types.len()];
+    for inst in f.blocks.iter().flat_map(|block| &block.insts) {
+        if let (Some(v), Op::Const(c)) = (inst.result, &inst.op) {
+            consts[v] = Some(c.clone());
+        }
+    }
+    let mut order: Vec<&Loop> = loops.iter().collect();
+    order.sort_by_key(|l| std::cmp::Reverse(l.depth));
+    for l in order {
+        hoist_loop(f, l, &consts);
+    }
+}
+
+fn hoist_loop(f: &mut Function, l: &Loop, consts: &[Option<Const>]) {
+    let preheader = match l.preheader {
+        Some(p) => p,
+        None => return,
+    };
+    let mut variant = vec![false; f.types.len()];
+    for &b in &l.blocks {
+        for v in f.blocks[b].insts.iter().filter_map(|inst| inst.result) {
+            variant[v] = true;
+        }
+    }
+    // The definitions of the operands of an instruction dominate
+    // it, so they are hoisted first.
+    for b in f.reverse_postorder().into_iter().filter(|&b| l.contains(b)) {
+        let insts = std::mem::take(&mut f.blocks[b].insts);
+        // Whether every instruction of the header so far is hoisted.
+        let mut start = b == l.header;
+        for inst in insts {
+            let hoistable = match inst.op {
+                Op::Phi(_) => false,
+                _ if is_hoistable(&inst, &f.types, consts) => true,
+                _ => start && may_only_panic(&inst, &f.types),
+            };
+            if hoistable && inst.op.operands().iter().all(|&v| !variant[v]) {
+                if let Some(v) = inst.result {
+                    variant[v] = false;
+                }
+                f.blocks[preheader].insts.push(inst);
+            } else {
+                start = start && matches!(inst.op, Op::Phi(_));
+                f.blocks[b].insts.push(inst);
+            }
+        }
+    }
+}
+
+/// Whether an instruction has no effect but its panics.
+fn may_only_panic(inst: &Inst, types: &[Ty]) -> bool {
+    match inst.op {
+        Op::Check(_, _) => return true,
+        Op::Binary(BinOp::Div, _, _) | Op::Binary(BinOp::Rem, _, _) | Op::Binary(BinOp::Shl, _, _)
+            | Op::Binary(BinOp::Shr, _, _) => return inst.result.is_some_and(|v| types[v].is_integer()),
+        _ => return false,
+    }
+}
+
+fn is_hoistable(inst: &Inst, types: &[Ty], consts: &[Option<Const>]) -> bool {
+    match inst.op {
+        Op::Param(_) | Op::Phi(_) | Op::Index(_, _) | Op::SetIndex(_, _, _) | Op::LoadElem(_, _) | Op::Load(_) => {
+            return false;
+        }
+        _ => return dce::is_pure(inst, types, consts),
+    }
+}
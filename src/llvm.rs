@@ -0,0 +1,1141 @@
+use std::collections::HashMap;
+use std::mem;
+
+use ast::*;
+use constant::{self, Value};
+use loc::Loc;
+use lower::{self, one, Targets};
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// The runtime support, which is copied at the beginning of
+/// every generated module so that it can be compiled on its own.
+const RUNTIME: &str = include_str!("runtime/gore_rt.ll");
+
+/// Translates a type-checked program to a module of textual
+/// LLVM IR, with opaque pointers.
+///
+/// Every variable lives in memory: package variables are
+/// globals and local variables are `alloca`s of the entry block,
+/// which `mem2reg` promotes to registers.  Expressions evaluate
+/// to first-class values, including arrays and structs, which
+/// are copied by loading and storing them.  Elements and fields
+/// are accessed through the address of their variable, or of a
+/// temporary copy of the array or struct if it is not stored in
+/// a variable.
+///
+/// Every instruction of a function carries the location of the
+/// statement or expression that it comes from in its `!dbg`
+/// metadata.
+pub fn generate(prog: &Program, types: &TypeTable, table: &SymbolTable) -> String {
+    let filename = prog.package.loc.filename.clone();
+    let mut gen = Generator {
+        types: types,
+        table: table,
+        globals: HashMap::new(),
+        strings: HashMap::new(),
+        consts: String::new(),
+        funcs: String::new(),
+        body: String::new(),
+        allocas: String::new(),
+        temps: 0,
+        labels: 0,
+        block: String::new(),
+        terminated: false,
+        targets: Targets::default(),
+        metadata: Vec::new(),
+        locations: HashMap::new(),
+        subprogram: None,
+        loc: (0, 0),
+    };
+    gen.metadata.push("distinct !DICompileUnit(language: DW_LANG_Go, file: !1, producer: \"gore\", \
+                       isOptimized: false, runtimeVersion: 0, emissionKind: FullDebug)".to_string());
+    gen.metadata.push(format!("!DIFile(filename: {}, directory: \".\")", meta_string(&filename)));
+    gen.metadata.push("!DISubroutineType(types: !3)".to_string());
+    gen.metadata.push("!{}".to_string());
+    gen.metadata.push("!{i32 2, !\"Debug Info Version\", i32 3}".to_string());
+    gen.metadata.push("!{i32 7, !\"Dwarf Version\", i32 4}".to_string());
+
+    let mut out = format!("; ModuleID = '{}'\nsource_filename = {}\n\n", filename, meta_string(&filename));
+    out.push_str(RUNTIME);
+    out.push_str(&gen.type_defs());
+    gen.program(prog);
+    out.push_str(&gen.consts);
+    out.push_str(&gen.funcs);
+    out.push_str("\n!llvm.dbg.cu = !{!0}\n!llvm.module.flags = !{!4, !5}\n\n");
+    for (i, node) in gen.metadata.iter().enumerate() {
+        out.push_str(&format!("!{} = {}\n", i, node));
+    }
+    return out;
+}
+
+/// Where a value is stored.
+enum Place {
+    Ptr(String),
+    /// An element whose index is only checked when it is
+    /// accessed: the type and pointer operands of its
+    /// `getelementptr`, without the index, then its index and
+    /// the length of its array or slice.
+    Elem { gep: String, index: String, len: String, loc: Loc },
+}
+
+struct Generator<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    /// The package variables.
+    globals: HashMap<SymbolId, String>,
+    /// The globals that hold string literals and locations.
+    strings: HashMap<(bool, Vec<u8>), String>,
+    consts: String,
+    funcs: String,
+    /// The instructions of the current function, after its
+    /// entry block, which only has its `alloca`s.
+    body: String,
+    allocas: String,
+    /// Counters for the names of temporaries and labels.
+    temps: usize,
+    labels: usize,
+    /// The label of the current basic block, and whether it
+    /// ends with a terminator already.
+    block: String,
+    terminated: bool,
+    targets: Targets<String>,
+    /// The metadata nodes, the first of which describe the
+    /// compile unit.
+    metadata: Vec<String>,
+    locations: HashMap<(usize, usize, usize), usize>,
+    /// The metadata node of the current function, if it has
+    /// debug information, and the current source location.
+    subprogram: Option<usize>,
+    loc: (usize, usize),
+}
+
+impl<'a> Generator<'a> {
+    /// Returns the `!dbg` attachment of the current location.
+    fn dbg(&mut self) -> String {
+        let scope = match self.subprogram {
+            Some(scope) => scope,
+            None => return String::new(),
+        };
+        let (line, col) = self.loc;
+        let node = match self.locations.get(&(line, col, scope)) {
+            Some(&node) => node,
+            None => {
+                self.metadata.push(format!("!DILocation(line: {}, column: {}, scope: !{})", line, col, scope));
+                let node = self.metadata.len() - 1;
+                self.locations.insert((line, col, scope), node);
+                node
+            }
+        };
+        return format!(", !dbg !{}", node);
+    }
+
+    /// Writes an instruction.  Code that follows a terminator
+    /// is unreachable, but still needs a block of its own.
+    fn inst(&mut self, s: &str) {
+        if self.terminated {
+            let label = self.new_label();
+            self.body.push_str(&format!("{}:\n", label));
+            self.block = label;
+            self.terminated = false;
+        }
+        let dbg = self.dbg();
+        self.body.push_str(&format!("  {}{}\n", s, dbg));
+    }
+
+    /// Writes an instruction that defines a temporary, and
+    /// returns the temporary.
+    fn value(&mut self, s: &str) -> String {
+        self.temps += 1;
+        let name = format!("%t{}", self.temps);
+        self.inst(&format!("{} = {}", name, s));
+        return name;
+    }
+
+    fn terminate(&mut self, s: &str) {
+        self.inst(s);
+        self.terminated = true;
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        return format!("L{}", self.labels);
+    }
+
+    /// Starts a basic block, which the current one falls
+    /// through to if it is not terminated.
+    fn start_block(&mut self, label: &str) {
+        if !self.terminated {
+            self.inst(&format!("br label %{}", label));
+        }
+        self.body.push_str(&format!("{}:\n", label));
+        self.block = label.to_string();
+        self.terminated = false;
+    }
+
+    /// Allocates memory for a value of type `ty` in the entry
+    /// block, named `name` or a new temporary.
+    fn alloca(&mut self, ty: TypeId, name: Option<String>) -> String {
+        let name = name.unwrap_or_else(|| {
+            self.temps += 1;
+            format!("%t{}", self.temps)
+        });
+        let dbg = self.dbg();
+        self.allocas.push_str(&format!("  {} = alloca {}{}\n", name, self.llty(ty), dbg));
+        return name;
+    }
+
+    /// Stores a value in a new temporary, and returns its
+    /// address.
+    fn spill(&mut self, ty: TypeId, value: &str) -> String {
+        let ptr = self.alloca(ty, None);
+        self.inst(&format!("store {} {}, ptr {}", self.llty(ty), value, ptr));
+        return ptr;
+    }
+
+    /// Returns a global that holds the bytes of a string, with a
+    /// terminating NUL character if it is a C string.
+    fn string_global(&mut self, bytes: &[u8], c_string: bool) -> String {
+        let key = (c_string, bytes.to_vec());
+        if let Some(name) = self.strings.get(&key) {
+            return name.clone();
+        }
+        let name = format!("@.{}.{}", if c_string { "loc" } else { "str" }, self.strings.len());
+        let mut bytes = bytes.to_vec();
+        if c_string {
+            bytes.push(0);
+        }
+        self.consts.push_str(&format!("{} = private unnamed_addr constant [{} x i8] {}\n",
+                                      name, bytes.len(), ir_string(&bytes)));
+        self.strings.insert(key, name.clone());
+        return name;
+    }
+
+    /// Returns the location of a runtime error, as a C string.
+    fn location(&mut self, loc: &Loc) -> String {
+        return self.string_global(loc.to_string().as_bytes(), true);
+    }
+
+
+    // TYPES
+
+    /// Returns the LLVM type that represents a GoLite type.
+    fn llty(&self, ty: TypeId) -> String {
+        let ty = self.types.default_type(ty);
+        match *self.types.under(ty) {
+            TypeKind::Int => "i64".to_string(),
+            TypeKind::Float64 => "double".to_string(),
+            TypeKind::Bool => "i1".to_string(),
+            TypeKind::Rune => "i32".to_string(),
+            TypeKind::String => "%gore.string".to_string(),
+            TypeKind::Slice(_) => "%gore.slice".to_string(),
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => format!("%t{}", self.types.underlying(ty)),
+            _ => "void".to_string(),
+        }
+    }
+
+    /// Returns the zero value of a type, as an LLVM constant.
+    fn zero(&self, ty: TypeId) -> String {
+        match self.llty(ty).as_str() {
+            "i64" | "i32" => return "0".to_string(),
+            "double" => return "0.0".to_string(),
+            "i1" => return "false".to_string(),
+            _ => return "zeroinitializer".to_string(),
+        }
+    }
+
+    fn is_rune(&self, ty: TypeId) -> bool {
+        match *self.types.under(self.types.default_type(ty)) {
+            TypeKind::Rune => return true,
+            _ => return false,
+        }
+    }
+
+    /// Names every array and struct type, and defines an equality
+    /// function for those that are comparable.
+    fn type_defs(&mut self) -> String {
+        let mut out = String::new();
+        let mut defined = vec![false; self.types.len()];
+        for ty in 0 .. self.types.len() {
+            self.type_def(ty, &mut defined, &mut out);
+        }
+        if !out.is_empty() {
+            out.insert(0, '\n');
+        }
+        return out;
+    }
+
+    /// Names the underlying type of `ty` if it is an array or a
+    /// struct that is not `defined` yet.  LLVM only allows forward
+    /// references to struct types, so its element or field types
+    /// are named first.
+    fn type_def(&mut self, ty: TypeId, defined: &mut Vec<bool>, out: &mut String) {
+        let ty = self.types.underlying(ty);
+        if defined[ty] {
+            return;
+        }
+        defined[ty] = true;
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                self.type_def(elem, defined, out);
+                out.push_str(&format!("%t{} = type [{} x {}]\n", ty, len, self.llty(elem)));
+            }
+            TypeKind::Struct(fields) => {
+                for &(_, field_ty) in &fields {
+                    self.type_def(field_ty, defined, out);
+                }
+                let fields: Vec<String> = fields.iter().map(|&(_, ty)| self.llty(ty)).collect();
+                out.push_str(&format!("%t{} = type {{ {} }}\n", ty, fields.join(", ")));
+            }
+            _ => return,
+        }
+        if self.types.is_comparable(ty) {
+            self.eq_function(ty);
+        }
+    }
+
+    /// Defines `@gore_eq_tN`, which compares two arrays or
+    /// structs of type `N` given their addresses.  Blank fields
+    /// are not compared.
+    fn eq_function(&mut self, ty: TypeId) {
+        self.temps = 0;
+        self.labels = 0;
+        self.block = "entry".to_string();
+        self.terminated = false;
+        let llty = self.llty(ty);
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                let (head, body, differ) = (self.new_label(), self.new_label(), self.new_label());
+                self.start_block(&head);
+                self.temps += 2;
+                let (i, next) = (format!("%t{}", self.temps - 1), format!("%t{}", self.temps));
+                self.inst(&format!("{} = phi i64 [ 0, %entry ], [ {}, %{} ]", i, next, body));
+                let done = self.value(&format!("icmp eq i64 {}, {}", i, len));
+                self.terminate(&format!("br i1 {}, label %equal, label %{}", done, body));
+                self.start_block(&body);
+                let a = self.value(&format!("getelementptr {}, ptr %a, i64 0, i64 {}", llty, i));
+                let b = self.value(&format!("getelementptr {}, ptr %b, i64 0, i64 {}", llty, i));
+                let eq = self.equal_at(elem, &a, &b);
+                self.inst(&format!("{} = add i64 {}, 1", next, i));
+                self.terminate(&format!("br i1 {}, label %{}, label %{}", eq, head, differ));
+                self.start_block(&differ);
+                self.terminate("ret i1 false");
+                self.start_block("equal");
+                self.terminate("ret i1 true");
+            }
+            TypeKind::Struct(fields) => {
+                let mut result = "true".to_string();
+                for (i, &(ref name, field_ty)) in fields.iter().enumerate() {
+                    if name == "_" {
+                        continue;
+                    }
+                    let a = self.value(&format!("getelementptr {}, ptr %a, i32 0, i32 {}", llty, i));
+                    let b = self.value(&format!("getelementptr {}, ptr %b, i32 0, i32 {}", llty, i));
+                    let eq = self.equal_at(field_ty, &a, &b);
+                    result = self.value(&format!("and i1 {}, {}", result, eq));
+                }
+                self.terminate(&format!("ret i1 {}", result));
+            }
+            _ => {}
+        }
+        let body = mem::take(&mut self.body);
+        self.funcs.push_str(&format!("\ndefine internal i1 @gore_eq_t{}(ptr %a, ptr %b) {{\nentry:\n{}}}\n",
+                                     ty, body));
+    }
+
+    /// Compares the values of type `ty` at two addresses.
+    fn equal_at(&mut self, ty: TypeId, a: &str, b: &str) -> String {
+        let llty = self.llty(ty);
+        if llty.starts_with("%t") {
+            return self.value(&format!("call i1 @gore_eq_t{}(ptr {}, ptr {})",
+                                       self.types.underlying(self.types.default_type(ty)), a, b));
+        }
+        let x = self.value(&format!("load {}, ptr {}", llty, a));
+        let y = self.value(&format!("load {}, ptr {}", llty, b));
+        return self.equal(ty, &x, &y);
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) if !f.name.is_blank() => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        let mut globals = String::new();
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        let name = format!("@{}.{}", id.name, id.sym.unwrap_or(0));
+                        let ty = self.var_type(id);
+                        globals.push_str(&format!("{} = internal global {} {}\n", name, self.llty(ty), self.zero(ty)));
+                        if let Some(sym) = id.sym {
+                            self.globals.insert(sym, name);
+                        }
+                    }
+                }
+            }
+        }
+        if !globals.is_empty() {
+            self.consts.push_str(&format!("\n{}", globals));
+        }
+
+        // Package variables are initialized in the order of
+        // their declarations.
+        self.begin_function("init", "main.init", &prog.package.loc);
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec, false);
+                }
+            }
+        }
+        self.terminate("ret void");
+        self.end_function("define internal void @main.init()");
+
+        for f in &funcs {
+            self.func_decl(f);
+        }
+
+        self.funcs.push_str("\ndefine i32 @main() {\n");
+        self.funcs.push_str("  call void @main.init()\n");
+        if funcs.iter().any(|f| f.name.name == "main") {
+            self.funcs.push_str("  call void @main.main()\n");
+        }
+        self.funcs.push_str("  ret i32 0\n}\n");
+    }
+
+    /// Starts the body of a function, with its debug information.
+    fn begin_function(&mut self, name: &str, linkage_name: &str, loc: &Loc) {
+        self.temps = 0;
+        self.labels = 0;
+        self.block = "entry".to_string();
+        self.terminated = false;
+        self.loc = (loc.line, loc.col);
+        self.metadata.push(format!("distinct !DISubprogram(name: {}, linkageName: {}, scope: !1, file: !1, \
+                                    line: {}, type: !2, scopeLine: {}, spFlags: DISPFlagDefinition, unit: !0)",
+                                   meta_string(name), meta_string(linkage_name), loc.line, loc.line));
+        self.subprogram = Some(self.metadata.len() - 1);
+    }
+
+    /// Writes the current function, given its header.
+    fn end_function(&mut self, header: &str) {
+        let allocas = mem::take(&mut self.allocas);
+        let body = mem::take(&mut self.body);
+        let subprogram = self.subprogram.take().unwrap_or(0);
+        self.funcs.push_str(&format!("\n{} !dbg !{} {{\nentry:\n{}{}}}\n", header, subprogram, allocas, body));
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.begin_function(&f.name.name, &format!("main.{}", f.name.name), &f.loc);
+        let mut params = Vec::new();
+        for (i, p) in f.params.iter().enumerate() {
+            let ty = self.var_type(&p.name);
+            let arg = format!("%arg{}", i);
+            params.push(format!("{} {}", self.llty(ty), arg));
+            if !p.name.is_blank() {
+                let var = self.local(&p.name);
+                self.inst(&format!("store {} {}, ptr {}", self.llty(ty), arg, var));
+            }
+        }
+        self.stmts(&f.body.stmts);
+        // The type checker makes sure that the end of a function
+        // with a result is not reached.
+        let result = lower::result_type(f, self.types, self.table);
+        if !self.terminated {
+            self.loc = (f.body.loc.line, f.body.loc.col);
+            if result == VOID {
+                self.terminate("ret void");
+            } else {
+                let ret = format!("ret {} {}", self.llty(result), self.zero(result));
+                self.terminate(&ret);
+            }
+        }
+        let header = format!("define internal {} @main.{}({})", self.llty(result), f.name.name, params.join(", "));
+        self.end_function(&header);
+    }
+
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    /// Returns the address of a variable.
+    fn var_addr(&self, id: &Ident) -> String {
+        match id.sym.and_then(|sym| self.globals.get(&sym)) {
+            Some(name) => return name.clone(),
+            None => return format!("%{}.{}", id.name, id.sym.unwrap_or(0)),
+        }
+    }
+
+    /// Allocates a local variable and returns its address.
+    fn local(&mut self, id: &Ident) -> String {
+        let name = self.var_addr(id);
+        let ty = self.var_type(id);
+        return self.alloca(ty, Some(name));
+    }
+
+    /// Declares (or, for package variables, initializes) the
+    /// variables of a `var` spec.  All the values are evaluated
+    /// before they are stored.
+    fn var_spec(&mut self, spec: &VarSpec, local: bool) {
+        self.loc = (spec.loc.line, spec.loc.col);
+        let values: Vec<String> = spec.values.iter().map(|e| self.expr(e)).collect();
+        for (i, id) in spec.names.iter().enumerate() {
+            if id.is_blank() {
+                continue;
+            }
+            let ty = self.var_type(id);
+            let value = match values.get(i) {
+                Some(value) => value.clone(),
+                None => self.zero(ty),
+            };
+            let var = if local { self.local(id) } else { self.var_addr(id) };
+            self.inst(&format!("store {} {}, ptr {}", self.llty(ty), value, var));
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.loc = (stmt.loc.line, stmt.loc.col);
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => {
+                if let ExprKind::Call(ref f, ref args) = e.kind {
+                    self.func_call(f, args, e.ty.unwrap_or(VOID));
+                }
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec, true);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs),
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, &stmt.loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), &stmt.loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), &stmt.loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                let values: Vec<String> = values.iter().map(|e| self.expr(e)).collect();
+                for (id, value) in names.iter().zip(values) {
+                    if id.is_blank() {
+                        continue;
+                    }
+                    let var = if self.is_new_var(id) { self.local(id) } else { self.var_addr(id) };
+                    let llty = self.llty(self.var_type(id));
+                    self.inst(&format!("store {} {}, ptr {}", llty, value, var));
+                }
+            }
+            StmtKind::Print(ref args) => self.print(args, false),
+            StmtKind::Println(ref args) => self.print(args, true),
+            StmtKind::Return(None) => self.terminate("ret void"),
+            StmtKind::Return(Some(ref e)) => {
+                let value = self.expr(e);
+                let ret = format!("ret {} {}", self.llty(e.ty.unwrap_or(INVALID)), value);
+                self.terminate(&ret);
+            }
+            StmtKind::Break => {
+                if let Some(label) = self.targets.brk() {
+                    self.terminate(&format!("br label %{}", label));
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(label) = self.targets.cont() {
+                    self.terminate(&format!("br label %{}", label));
+                }
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+        }
+    }
+
+    /// Returns whether a name of a `:=` statement declares a
+    /// variable, rather than assigning to an existing one.
+    fn is_new_var(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => self.table.symbol(sym).loc.as_ref() == Some(&id.loc),
+            None => false,
+        }
+    }
+
+    /// Assigns values to variables, elements and fields.  The
+    /// index of a single element is checked once its value is
+    /// known; with several targets, their operands are all
+    /// evaluated and checked before the values.
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr]) {
+        let mut targets = Vec::new();
+        for e in lhs {
+            let target = match e.kind {
+                ExprKind::Blank => None,
+                _ => {
+                    let place = self.place(e);
+                    if lhs.len() > 1 {
+                        Some(Place::Ptr(self.resolve(place)))
+                    } else {
+                        Some(place)
+                    }
+                }
+            };
+            targets.push(target);
+        }
+        let values: Vec<String> = rhs.iter().map(|e| self.expr(e)).collect();
+        for ((target, value), e) in targets.into_iter().zip(values).zip(rhs) {
+            if let Some(place) = target {
+                let ptr = self.resolve(place);
+                let llty = self.llty(e.ty.unwrap_or(INVALID));
+                self.inst(&format!("store {} {}, ptr {}", llty, value, ptr));
+            }
+        }
+    }
+
+    /// `x op= y`: the value of `x` is loaded from its address,
+    /// which is computed once, and the result stored back there.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let ty = lhs.ty.unwrap_or(INVALID);
+        let place = self.place(lhs);
+        let ptr = self.resolve(place);
+        let llty = self.llty(ty);
+        let x = self.value(&format!("load {}, ptr {}", llty, ptr));
+        let y = self.expr(rhs);
+        let result = self.arith(op, ty, rhs.ty.unwrap_or(INVALID), &x, &y, loc);
+        self.inst(&format!("store {} {}, ptr {}", llty, result, ptr));
+    }
+
+    /// Computes the values of all the arguments, then calls the
+    /// runtime to print each of them.
+    fn print(&mut self, args: &[Expr], newline: bool) {
+        let values: Vec<String> = args.iter().map(|arg| self.expr(arg)).collect();
+        for (i, (arg, value)) in args.iter().zip(values).enumerate() {
+            if newline && i > 0 {
+                self.value("call i32 @putchar(i32 32)");
+            }
+            let ty = self.types.default_type(arg.ty.unwrap_or(INVALID));
+            let call = match *self.types.under(ty) {
+                TypeKind::Float64 => format!("call void @gore_print_float(double {})", value),
+                TypeKind::Bool => format!("call void @gore_print_bool(i1 {})", value),
+                TypeKind::String => format!("call void @gore_print_string(%gore.string {})", value),
+                _ => {
+                    let value = self.to_i64(ty, &value);
+                    format!("call void @gore_print_int(i64 {})", value)
+                }
+            };
+            self.inst(&call);
+        }
+        if newline {
+            self.value("call i32 @putchar(i32 10)");
+        }
+    }
+
+    /// Generates the simple statement that introduces an `if`,
+    /// `switch` or `for` statement.
+    fn init(&mut self, init: &Option<Box<Stmt>>) {
+        if let Some(ref init) = *init {
+            self.stmt(init);
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        self.init(&if_stmt.init);
+        let cond = self.expr(&if_stmt.cond);
+        let (then, els, end) = (self.new_label(), self.new_label(), self.new_label());
+        self.terminate(&format!("br i1 {}, label %{}, label %{}", cond, then, els));
+        self.start_block(&then);
+        self.stmts(&if_stmt.then.stmts);
+        if !self.terminated {
+            self.terminate(&format!("br label %{}", end));
+        }
+        self.start_block(&els);
+        if let Some(ref els) = if_stmt.els {
+            self.stmt(els);
+        }
+        self.start_block(&end);
+    }
+
+    /// The tag and the case expressions are evaluated in order
+    /// until a case matches, which selects the clause to execute.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) {
+        self.init(&switch_stmt.init);
+        let tag = switch_stmt.tag.as_ref().map(|tag| (self.expr(tag), tag.ty.unwrap_or(INVALID)));
+        let end = self.new_label();
+        let bodies: Vec<String> = switch_stmt.clauses.iter().map(|_| self.new_label()).collect();
+        for (case, body) in switch_stmt.clauses.iter().zip(&bodies) {
+            let exprs = match case.exprs {
+                Some(ref exprs) => exprs,
+                None => continue,
+            };
+            for e in exprs {
+                let value = self.expr(e);
+                let cond = match tag {
+                    Some((ref tag, ty)) => self.equal(ty, tag, &value),
+                    None => value,
+                };
+                let next = self.new_label();
+                self.terminate(&format!("br i1 {}, label %{}, label %{}", cond, body, next));
+                self.start_block(&next);
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|c| c.exprs.is_none());
+        let otherwise = default.map_or(end.clone(), |i| bodies[i].clone());
+        self.terminate(&format!("br label %{}", otherwise));
+
+        self.targets.push_switch(end.clone());
+        for (case, body) in switch_stmt.clauses.iter().zip(&bodies) {
+            self.start_block(body);
+            self.stmts(&case.body);
+            if !self.terminated {
+                self.terminate(&format!("br label %{}", end));
+            }
+        }
+        self.targets.pop();
+        self.start_block(&end);
+    }
+
+    /// The condition is tested at the top of the loop, and
+    /// `continue` jumps to the post statement.
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        self.init(&for_stmt.init);
+        let (head, body, post, end) = (self.new_label(), self.new_label(), self.new_label(), self.new_label());
+        self.start_block(&head);
+        if let Some(ref cond) = for_stmt.cond {
+            let cond = self.expr(cond);
+            self.terminate(&format!("br i1 {}, label %{}, label %{}", cond, body, end));
+        }
+        self.start_block(&body);
+        self.targets.push_loop(end.clone(), post.clone());
+        self.stmts(&for_stmt.body.stmts);
+        self.targets.pop();
+        self.start_block(&post);
+        if let Some(ref stmt) = for_stmt.post {
+            self.stmt(stmt);
+        }
+        self.terminate(&format!("br label %{}", head));
+        self.start_block(&end);
+    }
+
+
+    // EXPRESSIONS
+
+    /// Returns an operand with the value of `e`, after generating
+    /// the instructions that compute it.
+    fn expr(&mut self, e: &Expr) -> String {
+        let saved = self.loc;
+        self.loc = (e.loc.line, e.loc.col);
+        let value = self.expr_value(e);
+        self.loc = saved;
+        return value;
+    }
+
+    fn expr_value(&mut self, e: &Expr) -> String {
+        let ty = e.ty.unwrap_or(INVALID);
+        if let Some(ref value) = e.value {
+            return self.constant(value, ty);
+        }
+        match e.kind {
+            ExprKind::Unary(op, ref x) => {
+                let x = self.expr(x);
+                return self.unary(op, ty, &x);
+            }
+            ExprKind::Binary(BinOp::And, ref x, ref y) => return self.logical(true, x, y),
+            ExprKind::Binary(BinOp::Or, ref x, ref y) => return self.logical(false, x, y),
+            ExprKind::Binary(op, ref x, ref y) => {
+                let (x_ty, y_ty) = (x.ty.unwrap_or(INVALID), y.ty.unwrap_or(INVALID));
+                let x = self.expr(x);
+                let y = self.expr(y);
+                if op.is_comparison() {
+                    return self.compare(op, x_ty, &x, &y);
+                }
+                return self.arith(op, x_ty, y_ty, &x, &y, &e.loc);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                match kind {
+                    SymbolKind::Type => return self.conversion(ty, &args[0]),
+                    SymbolKind::Builtin => return self.builtin(f, &args[0]),
+                    _ => return self.func_call(f, args, ty),
+                }
+            }
+            ExprKind::Selector(ref x, _) if !self.is_addressable(x) => {
+                // The field of a struct that is not stored.
+                let index = self.field_index(e);
+                let llty = self.llty(x.ty.unwrap_or(INVALID));
+                let x = self.expr(x);
+                return self.value(&format!("extractvalue {} {}, {}", llty, x, index));
+            }
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => {
+                let place = self.place(e);
+                let ptr = self.resolve(place);
+                return self.value(&format!("load {}, ptr {}", self.llty(ty), ptr));
+            }
+            ExprKind::Append(ref s, ref x) => {
+                let s = self.expr(s);
+                let elem = match *self.types.under(ty) {
+                    TypeKind::Slice(elem) => elem,
+                    _ => INVALID,
+                };
+                let x = self.expr(x);
+                let x = self.spill(elem, &x);
+                let size = format!("ptrtoint (ptr getelementptr ({}, ptr null, i32 1) to i64)", self.llty(elem));
+                return self.value(&format!("call %gore.slice @gore_append(%gore.slice {}, ptr {}, i64 {})",
+                                           s, x, size));
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => return self.zero(ty),
+        }
+    }
+
+    /// Returns where the value of a variable, element or field
+    /// is stored.  Arrays and structs that are not stored in
+    /// variables are copied to temporaries.
+    fn place(&mut self, e: &Expr) -> Place {
+        let saved = self.loc;
+        self.loc = (e.loc.line, e.loc.col);
+        let place = match e.kind {
+            ExprKind::Id(ref id) => Place::Ptr(self.var_addr(id)),
+            ExprKind::Index(ref a, ref i) => {
+                let a_ty = a.ty.unwrap_or(INVALID);
+                match *self.types.under(a_ty) {
+                    TypeKind::Array(len, _) => {
+                        let base = self.base(a);
+                        let index = self.expr(i);
+                        let index = self.to_i64(i.ty.unwrap_or(INVALID), &index);
+                        Place::Elem {
+                            gep: format!("{}, ptr {}, i64 0", self.llty(a_ty), base),
+                            index: index,
+                            len: len.to_string(),
+                            loc: e.loc.clone(),
+                        }
+                    }
+                    _ => {
+                        let s = self.expr(a);
+                        let index = self.expr(i);
+                        let index = self.to_i64(i.ty.unwrap_or(INVALID), &index);
+                        let data = self.value(&format!("extractvalue %gore.slice {}, 0", s));
+                        let len = self.value(&format!("extractvalue %gore.slice {}, 1", s));
+                        Place::Elem {
+                            gep: format!("{}, ptr {}", self.llty(e.ty.unwrap_or(INVALID)), data),
+                            index: index,
+                            len: len,
+                            loc: e.loc.clone(),
+                        }
+                    }
+                }
+            }
+            ExprKind::Selector(ref x, _) => {
+                let index = self.field_index(e);
+                let base = self.base(x);
+                let llty = self.llty(x.ty.unwrap_or(INVALID));
+                Place::Ptr(self.value(&format!("getelementptr {}, ptr {}, i32 0, i32 {}", llty, base, index)))
+            }
+            _ => {
+                let value = self.expr(e);
+                Place::Ptr(self.spill(e.ty.unwrap_or(INVALID), &value))
+            }
+        };
+        self.loc = saved;
+        return place;
+    }
+
+    /// Returns the address of an array or struct.
+    fn base(&mut self, e: &Expr) -> String {
+        let place = self.place(e);
+        return self.resolve(place);
+    }
+
+    /// Returns the address of a place, checking its index.
+    fn resolve(&mut self, place: Place) -> String {
+        match place {
+            Place::Ptr(ptr) => return ptr,
+            Place::Elem { gep, index, len, loc } => {
+                let saved = self.loc;
+                self.loc = (loc.line, loc.col);
+                let loc = self.location(&loc);
+                let index = self.value(&format!("call i64 @gore_index(i64 {}, i64 {}, ptr {})", index, len, loc));
+                let ptr = self.value(&format!("getelementptr {}, i64 {}", gep, index));
+                self.loc = saved;
+                return ptr;
+            }
+        }
+    }
+
+    /// Returns whether an expression denotes a variable or a part
+    /// of one, which can be accessed in place.  The elements of
+    /// slices are always stored in their backing array.
+    fn is_addressable(&self, e: &Expr) -> bool {
+        match e.kind {
+            ExprKind::Id(_) => return true,
+            ExprKind::Index(ref a, _) => {
+                if let TypeKind::Slice(_) = *self.types.under(a.ty.unwrap_or(INVALID)) {
+                    return true;
+                }
+                return self.is_addressable(a);
+            }
+            ExprKind::Selector(ref x, _) => return self.is_addressable(x),
+            _ => return false,
+        }
+    }
+
+    /// The index of the field that a selector expression selects.
+    fn field_index(&self, e: &Expr) -> usize {
+        if let ExprKind::Selector(ref x, ref field) = e.kind {
+            if let TypeKind::Struct(ref fields) = *self.types.under(x.ty.unwrap_or(INVALID)) {
+                return fields.iter().position(|f| f.0 == field.name).unwrap_or(0);
+            }
+        }
+        return 0;
+    }
+
+    /// Widens a rune to an `int`.
+    fn to_i64(&mut self, ty: TypeId, value: &str) -> String {
+        if self.is_rune(ty) {
+            return self.value(&format!("sext i32 {} to i64", value));
+        }
+        return value.to_string();
+    }
+
+    fn constant(&mut self, value: &Value, ty: TypeId) -> String {
+        let ty = self.types.default_type(ty);
+        match *value {
+            Value::Bool(b) => return b.to_string(),
+            Value::String(ref s) => {
+                let bytes = constant::string_bytes(s);
+                if bytes.is_empty() {
+                    return "zeroinitializer".to_string();
+                }
+                let global = self.string_global(&bytes, false);
+                return format!("{{ ptr {}, i64 {} }}", global, bytes.len());
+            }
+            Value::Int(_) | Value::Float(_) if self.types.is_float(ty) => {
+                let f = value.to_rat().map_or(0.0, |r| r.to_f64());
+                return format!("0x{:016X}", f.to_bits());
+            }
+            Value::Int(_) | Value::Float(_) => {
+                let n = value.to_int().and_then(|n| n.to_i64()).unwrap_or(0);
+                return n.to_string();
+            }
+        }
+    }
+
+    fn unary(&mut self, op: UnOp, ty: TypeId, x: &str) -> String {
+        let llty = self.llty(ty);
+        match op {
+            UnOp::Plus => return x.to_string(),
+            UnOp::Neg if llty == "double" => return self.value(&format!("fneg double {}", x)),
+            UnOp::Neg => return self.value(&format!("sub {} 0, {}", llty, x)),
+            UnOp::Not => return self.value(&format!("xor i1 {}, true", x)),
+            UnOp::BitNot => return self.value(&format!("xor {} {}, -1", llty, x)),
+        }
+    }
+
+    /// `&&` and `||` only evaluate their right operand if
+    /// needed.
+    fn logical(&mut self, and: bool, x: &Expr, y: &Expr) -> String {
+        let x = self.expr(x);
+        let from = self.block.clone();
+        let (rhs, end) = (self.new_label(), self.new_label());
+        if and {
+            self.terminate(&format!("br i1 {}, label %{}, label %{}", x, rhs, end));
+        } else {
+            self.terminate(&format!("br i1 {}, label %{}, label %{}", x, end, rhs));
+        }
+        self.start_block(&rhs);
+        let y = self.expr(y);
+        let rhs_end = self.block.clone();
+        self.start_block(&end);
+        return self.value(&format!("phi i1 [ {}, %{} ], [ {}, %{} ]", !and, from, y, rhs_end));
+    }
+
+    /// Returns the result of an arithmetic or bitwise operator on
+    /// operands of type `ty` (and `y_ty` for the count of a
+    /// shift).  Integer operations wrap around and division by
+    /// zero panics, as in Go.
+    fn arith(&mut self, op: BinOp, ty: TypeId, y_ty: TypeId, x: &str, y: &str, loc: &Loc) -> String {
+        let ty = self.types.default_type(ty);
+        let llty = self.llty(ty);
+        let bits = match *self.types.under(ty) {
+            TypeKind::Int => 64,
+            TypeKind::Rune => 32,
+            TypeKind::String => return self.value(&format!("call %gore.string @gore_concat(%gore.string {}, \
+                                                             %gore.string {})", x, y)),
+            _ => {
+                let inst = match op {
+                    BinOp::Add => "fadd",
+                    BinOp::Sub => "fsub",
+                    BinOp::Mul => "fmul",
+                    _ => "fdiv",
+                };
+                return self.value(&format!("{} double {}, {}", inst, x, y));
+            }
+        };
+        let inst = match op {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::BitAnd => "and",
+            BinOp::BitOr => "or",
+            BinOp::BitXor => "xor",
+            BinOp::BitClear => {
+                let not = self.value(&format!("xor {} {}, -1", llty, y));
+                return self.value(&format!("and {} {}, {}", llty, x, not));
+            }
+            BinOp::Div | BinOp::Rem => {
+                let loc = self.location(loc);
+                let f = if op == BinOp::Div { "div" } else { "rem" };
+                return self.value(&format!("call {} @gore_{}{}({} {}, {} {}, ptr {})",
+                                           llty, f, bits, llty, x, llty, y, loc));
+            }
+            _ => {
+                let loc = self.location(loc);
+                let n = self.to_i64(y_ty, y);
+                let f = if op == BinOp::Shl { "shl" } else { "shr" };
+                return self.value(&format!("call {} @gore_{}{}({} {}, i64 {}, ptr {})",
+                                           llty, f, bits, llty, x, n, loc));
+            }
+        };
+        return self.value(&format!("{} {} {}, {}", inst, llty, x, y));
+    }
+
+    fn compare(&mut self, op: BinOp, ty: TypeId, x: &str, y: &str) -> String {
+        match op {
+            BinOp::Eq => return self.equal(ty, x, y),
+            BinOp::Ne => {
+                let eq = self.equal(ty, x, y);
+                return self.value(&format!("xor i1 {}, true", eq));
+            }
+            _ => {}
+        }
+        let pred = match op {
+            BinOp::Lt => "lt",
+            BinOp::Le => "le",
+            BinOp::Gt => "gt",
+            _ => "ge",
+        };
+        let llty = self.llty(ty);
+        match llty.as_str() {
+            "double" => return self.value(&format!("fcmp o{} double {}, {}", pred, x, y)),
+            "%gore.string" => {
+                let c = self.value(&format!("call i32 @gore_string_cmp(%gore.string {}, %gore.string {})", x, y));
+                return self.value(&format!("icmp s{} i32 {}, 0", pred, c));
+            }
+            _ => return self.value(&format!("icmp s{} {} {}, {}", pred, llty, x, y)),
+        }
+    }
+
+    fn equal(&mut self, ty: TypeId, x: &str, y: &str) -> String {
+        let ty = self.types.default_type(ty);
+        let llty = self.llty(ty);
+        match llty.as_str() {
+            "double" => return self.value(&format!("fcmp oeq double {}, {}", x, y)),
+            "%gore.string" => {
+                return self.value(&format!("call i1 @gore_string_eq(%gore.string {}, %gore.string {})", x, y));
+            }
+            _ if llty.starts_with("%t") => {
+                let a = self.spill(ty, x);
+                let b = self.spill(ty, y);
+                return self.equal_at(ty, &a, &b);
+            }
+            _ => return self.value(&format!("icmp eq {} {}, {}", llty, x, y)),
+        }
+    }
+
+    /// Calls a function, after evaluating its arguments, and
+    /// returns its result.
+    fn func_call(&mut self, f: &Expr, args: &[Expr], ty: TypeId) -> String {
+        let name = match f.kind {
+            ExprKind::Id(ref id) => id.name.clone(),
+            _ => String::new(),
+        };
+        let args: Vec<String> = args.iter()
+            .map(|arg| {
+                let value = self.expr(arg);
+                format!("{} {}", self.llty(arg.ty.unwrap_or(INVALID)), value)
+            })
+            .collect();
+        let call = format!("call {} @main.{}({})", self.llty(ty), name, args.join(", "));
+        if ty == VOID {
+            self.inst(&call);
+            return String::new();
+        }
+        return self.value(&call);
+    }
+
+    fn conversion(&mut self, ty: TypeId, arg: &Expr) -> String {
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        let x = self.expr(arg);
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            let x = self.to_i64(arg_ty, &x);
+            return self.value(&format!("call %gore.string @gore_string_from_rune(i64 {})", x));
+        }
+        let (from, to) = (self.llty(arg_ty), self.llty(ty));
+        match (from.as_str(), to.as_str()) {
+            ("double", "double") => return x,
+            ("double", _) => {
+                // Out of range values saturate, as in the
+                // interpreters.
+                let n = self.value(&format!("call i64 @llvm.fptosi.sat.i64.f64(double {})", x));
+                if to == "i32" {
+                    return self.value(&format!("trunc i64 {} to i32", n));
+                }
+                return n;
+            }
+            (_, "double") => return self.value(&format!("sitofp {} {} to double", from, x)),
+            ("i32", "i64") => return self.value(&format!("sext i32 {} to i64", x)),
+            ("i64", "i32") => return self.value(&format!("trunc i64 {} to i32", x)),
+            _ => return x,
+        }
+    }
+
+    fn builtin(&mut self, f: &Expr, arg: &Expr) -> String {
+        let cap = match f.kind {
+            ExprKind::Id(ref id) => id.name == "cap",
+            _ => false,
+        };
+        let ty = arg.ty.unwrap_or(INVALID);
+        if let TypeKind::Array(len, _) = *self.types.under(ty) {
+            return len.to_string();
+        }
+        let x = self.expr(arg);
+        let llty = self.llty(ty);
+        return self.value(&format!("extractvalue {} {}, {}", llty, x, if cap { 2 } else { 1 }));
+    }
+}
+
+/// Returns an LLVM string constant.  Quotes, backslashes and
+/// anything but printable ASCII are escaped in hexadecimal.
+fn ir_string(bytes: &[u8]) -> String {
+    let mut out = String::from("c\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => out.push_str(&format!("\\{:02X}", b)),
+            0x20 ..= 0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:02X}", b)),
+        }
+    }
+    out.push('"');
+    return out;
+}
+
+/// Returns a metadata string.
+fn meta_string(s: &str) -> String {
+    return ir_string(s.as_bytes())[1 ..].to_string();
+}
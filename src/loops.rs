@@ -0,0 +1,128 @@
+use std::collections::BTreeSet;
+
+use ir::{self, Block, BlockId, Function, Inst, Op, Term};
+
+/// A natural loop: the blocks from which the header, which
+/// dominates them, can be reached again without leaving them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub header: BlockId,
+    /// The blocks of the loop, the header included, in increasing
+    /// order.
+    pub blocks: Vec<BlockId>,
+    /// The blocks of the loop that branch back to the header.
+    pub latches: Vec<BlockId>,
+    /// The only block outside the loop that branches to the
+    /// header, if it does nothing else but jump there.
+    pub preheader: Option<BlockId>,
+    /// The innermost loop that contains this one, and the number
+    /// of loops that contain it.
+    pub parent: Option<usize>,
+    pub depth: usize,
+}
+
+impl Loop {
+    pub fn contains(&self, b: BlockId) -> bool {
+        return self.blocks.binary_search(&b).is_ok();
+    }
+}
+
+/// Returns the natural loops of a function, outer loops before
+/// the loops they contain.  The edges to a block from the blocks
+/// it dominates are back edges, and the loops of the back edges
+/// to the same header are one loop.  Functions built from GoLite
+/// have no other cycles.
+pub fn find(f: &Function) -> Vec<Loop> {
+    let idom = ir::dominators(f);
+    let preds = f.predecessors();
+    let mut loops: Vec<Loop> = Vec::new();
+    for h in f.reverse_postorder() {
+        let latches: Vec<BlockId> = preds[h].iter().cloned()
+            .filter(|&p| idom[p].is_some() && ir::dominates(&idom, h, p)).collect();
+        if latches.is_empty() {
+            continue;
+        }
+        // The blocks that reach a latch without going through the
+        // header.
+        let mut blocks = BTreeSet::new();
+        blocks.insert(h);
+        let mut work = latches.clone();
+        while let Some(b) = work.pop() {
+            if blocks.insert(b) {
+                work.extend(preds[b].iter().cloned().filter(|&p| idom[p].is_some()));
+            }
+        }
+        let blocks: Vec<BlockId> = blocks.into_iter().collect();
+        let outside: Vec<BlockId> = preds[h].iter().cloned().filter(|p| blocks.binary_search(p).is_err()).collect();
+        let preheader = match outside[..] {
+            [p] if f.blocks[p].term == Term::Jump(h) => Some(p),
+            _ => None,
+        };
+        // The headers come in reverse postorder, so the loops that
+        // contain this one are already found, and the last of them
+        // is the innermost.
+        let parent = loops.iter().rposition(|l| l.contains(h));
+        let depth = parent.map_or(0, |p| loops[p].depth + 1);
+        loops.push(Loop {
+            header: h,
+            blocks: blocks,
+            latches: latches,
+            preheader: preheader,
+            parent: parent,
+            depth: depth,
+        });
+    }
+    return loops;
+}
+
+/// Gives every loop of a function a preheader, and returns the
+/// loops.  The preheader of a loop is where the code that runs
+/// once before it goes.
+pub fn insert_preheaders(f: &mut Function) -> Vec<Loop> {
+    let loops = find(f);
+    if loops.iter().all(|l| l.preheader.is_some()) {
+        return loops;
+    }
+    for l in &loops {
+        if l.preheader.is_none() {
+            insert_preheader(f, l);
+        }
+    }
+    return find(f);
+}
+
+/// Adds a block that the blocks outside a loop go through to its
+/// header, and which merges the values they give its phi nodes.
+fn insert_preheader(f: &mut Function, l: &Loop) {
+    let h = l.header;
+    let p = f.blocks.len();
+    let outside: Vec<BlockId> = f.predecessors()[h].iter().cloned().filter(|&b| !l.contains(b)).collect();
+    for &b in &outside {
+        f.blocks[b].term = match f.blocks[b].term {
+            Term::Jump(_) => Term::Jump(p),
+            Term::Branch(c, t, e) => Term::Branch(c, if t == h { p } else { t }, if e == h { p } else { e }),
+            ref term => term.clone(),
+        };
+    }
+    let mut insts = Vec::new();
+    for i in 0 .. f.blocks[h].insts.len() {
+        let (result, pos, incoming) = match f.blocks[h].insts[i] {
+            Inst { result: Some(v), op: Op::Phi(ref incoming), pos } => (v, pos, incoming.clone()),
+            _ => break,
+        };
+        let (entering, staying): (Vec<_>, Vec<_>) = incoming.into_iter().partition(|&(b, _)| !l.contains(b));
+        let v = match entering[..] {
+            [(_, v)] => v,
+            _ => {
+                let v = f.new_value(f.types[result].clone());
+                insts.push(Inst { result: Some(v), op: Op::Phi(entering), pos: pos });
+                v
+            }
+        };
+        let mut incoming = vec![(p, v)];
+        incoming.extend(staying);
+        f.blocks[h].insts[i].op = Op::Phi(incoming);
+    }
+    let pos = f.blocks[h].pos;
+    f.blocks.push(Block { insts: insts, term: Term::Jump(h), pos: pos });
+}
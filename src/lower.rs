@@ -0,0 +1,77 @@
+//! What the translations of the syntax tree share: the backends,
+//! the bytecode compiler, the IR builder and the interpreter.
+//!
+//! They all follow the same rules where the order of evaluation
+//! shows:
+//!
+//! - `print` and `println` evaluate all their arguments before
+//!   printing any of them, since evaluating one of them may panic;
+//! - `x op= y`, `x++` and `x--` evaluate the operands of `x` (the
+//!   indexed value and the index, say) only once.
+
+use ast::*;
+use constant::{BigInt, Value};
+use symtab::SymbolTable;
+use types::*;
+
+/// The constant 1 with the type of `e`, for `e++` and `e--`.
+pub fn one(e: &Expr) -> Expr {
+    let mut one = Expr::new(ExprKind::Int("1".to_string(), IntBase::Dec), e.loc.clone());
+    one.ty = e.ty;
+    one.value = Some(Value::Int(BigInt::from_u64(1)));
+    return one;
+}
+
+/// The result type of the function that `f` declares (`VOID` if
+/// it has none).
+pub fn result_type(f: &FuncDecl, types: &TypeTable, table: &SymbolTable) -> TypeId {
+    match f.name.sym.and_then(|sym| table.symbol(sym).ty) {
+        Some(ty) => match *types.kind(ty) {
+            TypeKind::Func(_, result) => return result,
+            _ => return VOID,
+        },
+        None => return VOID,
+    }
+}
+
+/// The statements that `break` and `continue` may leave: the
+/// loops and switch statements that enclose the one being
+/// translated, innermost last, with the labels (or blocks) that
+/// leave them and that continue the loops.
+pub struct Targets<L> {
+    targets: Vec<(L, Option<L>)>,
+}
+
+impl<L: Clone> Targets<L> {
+    /// Enters a switch statement, which `break` leaves for `brk`.
+    pub fn push_switch(&mut self, brk: L) {
+        self.targets.push((brk, None));
+    }
+
+    /// Enters a loop, which `break` leaves for `brk` and which
+    /// `continue` continues at `cont`.
+    pub fn push_loop(&mut self, brk: L, cont: L) {
+        self.targets.push((brk, Some(cont)));
+    }
+
+    pub fn pop(&mut self) {
+        self.targets.pop();
+    }
+
+    /// Where `break` goes: out of the innermost statement.
+    pub fn brk(&self) -> Option<L> {
+        return self.targets.last().map(|target| target.0.clone());
+    }
+
+    /// Where `continue` goes: to the next iteration of the
+    /// innermost loop.
+    pub fn cont(&self) -> Option<L> {
+        return self.targets.iter().rev().find_map(|target| target.1.clone());
+    }
+}
+
+impl<L> Default for Targets<L> {
+    fn default() -> Targets<L> {
+        return Targets { targets: Vec::new() };
+    }
+}
@@ -1,46 +1,537 @@
+// See lib.rs
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::env;
+use std::fs::{self, File};
 use std::io;
-use std::io::Read;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process;
+use std::thread;
 
 extern crate gore;
 
 use gore::token::Token;
 use gore::scanner::Scanner;
+use gore::parser;
+use gore::pretty;
+use gore::weeder;
+use gore::symtab::{self, SymbolTable};
+use gore::typecheck;
+use gore::reach;
+use gore::unused;
+use gore::cfg;
+use gore::codegen;
+use gore::llvm;
+use gore::regalloc;
+use gore::x86_64;
+use gore::wasm;
+use gore::jvm;
+use gore::interp;
+use gore::bcgen;
+use gore::bytecode;
+use gore::vm;
+use gore::bcfile;
+use gore::ir;
+use gore::irgen;
+use gore::ireval;
+use gore::opt;
+use gore::inline;
+use gore::types::TypeTable;
+use gore::ast::Program;
 use gore::error::Error;
 
+const USAGE: &str = "usage: gore <scan|parse|pretty|check|pptype|cfg|codegen|run|disasm|ir|build|exec> \
+                     [--dumpsymtab] [--warn-unused] [--vm|--ir] [-O] [--inline-report] [--dump-regalloc] [--target=c|llvm|x86_64|wasm|jvm] [--emit=bytecode] [-o output] [file]";
+
+/// Command-line options.
+struct Options {
+    command: String,
+    file: Option<String>,
+    /// Print the scopes and symbols of the program (`check`).
+    dump_symtab: bool,
+    /// Report the unused variables as warnings rather than
+    /// errors.
+    warn_unused: bool,
+    /// Execute the program with the bytecode machine rather
+    /// than the interpreter (`run`).
+    vm: bool,
+    /// Execute the SSA form of the program (`run`).
+    ir: bool,
+    /// Optimise the SSA form of the program (`ir` and `run --ir`).
+    optimize: bool,
+    /// Print which calls are inlined, and why the others are not,
+    /// when optimising (`ir` and `run --ir`).
+    inline_report: bool,
+    /// Print the live intervals of the variables and their
+    /// registers (`codegen --target=x86_64`).
+    dump_regalloc: bool,
+    /// The language to generate (`codegen`).
+    target: String,
+    /// The file to write (`codegen` and `build`), or the
+    /// directory of the classes (`codegen --target=jvm`).
+    output: Option<String>,
+}
+
+fn parse_args(args: &[String]) -> Option<Options> {
+    let mut opts = Options {
+        command: String::new(),
+        file: None,
+        dump_symtab: false,
+        warn_unused: false,
+        vm: false,
+        ir: false,
+        optimize: false,
+        inline_report: false,
+        dump_regalloc: false,
+        target: "c".to_string(),
+        output: None,
+    };
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dumpsymtab" => opts.dump_symtab = true,
+            "--warn-unused" => opts.warn_unused = true,
+            "--vm" => opts.vm = true,
+            "--ir" => opts.ir = true,
+            "-O" => opts.optimize = true,
+            "--inline-report" => opts.inline_report = true,
+            "--dump-regalloc" => opts.dump_regalloc = true,
+            // Bytecode is the only format that `build` emits.
+            "--emit=bytecode" => {}
+            "--target=c" | "--target=llvm" | "--target=x86_64" | "--target=wasm" | "--target=jvm" => {
+                opts.target = arg["--target=".len() ..].to_string();
+            }
+            "-o" => opts.output = Some(args.next()?.clone()),
+            _ if arg.starts_with("--") => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    if positional.is_empty() || positional.len() > 2 {
+        return None;
+    }
+    opts.command = positional.remove(0);
+    opts.file = positional.pop();
+    return Some(opts);
+}
+
 fn main() {
-    let mut stdin = io::stdin();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let opts = match parse_args(&args) {
+        Some(opts) => opts,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let (filename, bytes) = match read_input(opts.file.as_ref()) {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("gore: {}", err);
+            process::exit(1);
+        }
+    };
+    if opts.command == "exec" {
+        process::exit(exec(&filename, &bytes));
+    }
+    let scanner = Scanner::new(filename.clone(), bytes);
+
+    let status = match opts.command.as_str() {
+        "scan" => scan(scanner),
+        "parse" => parse(scanner),
+        "pretty" => pretty(scanner),
+        "check" => check(scanner, &opts),
+        "pptype" => pptype(scanner, &opts),
+        "cfg" => print_cfg(scanner, &filename),
+        "codegen" => generate(scanner, &opts),
+        "run" => run(scanner, &opts),
+        "disasm" => disasm(scanner, &opts),
+        "ir" => dump_ir(scanner, &opts),
+        "build" => build(scanner, &opts),
+        _ => {
+            eprintln!("{}", USAGE);
+            1
+        }
+    };
+    process::exit(status);
+}
+
+
+/// Reads the named file, or standard input if no file is given.
+fn read_input(filename: Option<&String>) -> io::Result<(String, Vec<u8>)> {
     let mut bytes = Vec::new();
-    let _ = stdin.read_to_end(&mut bytes);
-    let mut scanner = Scanner::new("<stdin>".to_string(), bytes);
-    //scan(&mut scanner);
+    match filename {
+        Some(filename) => {
+            let mut file = File::open(filename)?;
+            file.read_to_end(&mut bytes)?;
+            return Ok((filename.clone(), bytes));
+        }
+        None => {
+            io::stdin().read_to_end(&mut bytes)?;
+            return Ok(("<stdin>".to_string(), bytes));
+        }
+    }
+}
+
+
+fn scan(mut scanner: Scanner) -> i32 {
     match all_tokens(&mut scanner) {
         Ok(ref toks) => {
             for tok in toks {
                 println!("{:?}", tok);
             }
+            return 0;
         }
         Err(err) => {
             println!("{}", err);
+            return 1;
         }
     }
 }
 
 
-fn scan(scanner: &mut Scanner) {
-    loop {
-        match scanner.next() {
-            Ok(tok) => {
-                if tok.is_eof() { break; }
+/// Parses the input and checks the context-sensitive
+/// syntax rules.
+fn parse_and_weed(scanner: Scanner) -> Result<Program, Vec<Error>> {
+    let prog = parser::parse(scanner)?;
+    weeder::weed(&prog)?;
+    return Ok(prog);
+}
+
+
+fn parse(scanner: Scanner) -> i32 {
+    match parse_and_weed(scanner) {
+        Ok(_) => {
+            println!("OK");
+            return 0;
+        }
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    }
+}
+
+
+fn pretty(scanner: Scanner) -> i32 {
+    match parse_and_weed(scanner) {
+        Ok(prog) => {
+            print!("{}", pretty::pretty_program(&prog));
+            return 0;
+        }
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    }
+}
+
+
+/// Parses the input and performs all the static checks.
+fn check_program(scanner: Scanner, opts: &Options) -> Result<(Program, SymbolTable, TypeTable), Vec<Error>> {
+    let mut prog = parse_and_weed(scanner)?;
+    let mut table = symtab::resolve(&mut prog)?;
+    let types = typecheck::check(&mut prog, &mut table)?;
+    let mut diagnostics = reach::unreachable(&prog);
+    diagnostics.extend(unused::check(&prog, &table, opts.warn_unused));
+    let (warnings, errors): (Vec<Error>, Vec<Error>) = diagnostics.into_iter().partition(|d| d.warning);
+    warn(&warnings);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    return Ok((prog, table, types));
+}
+
+
+/// Checks the program and removes the statements that are never
+/// executed, and the values that are never read, before it is
+/// translated.
+fn compile_program(scanner: Scanner, opts: &Options) -> Result<(Program, SymbolTable, TypeTable), Vec<Error>> {
+    let (mut prog, table, types) = check_program(scanner, opts)?;
+    reach::eliminate(&mut prog);
+    unused::eliminate(&mut prog, &table, &types);
+    return Ok((prog, table, types));
+}
+
+
+fn check(scanner: Scanner, opts: &Options) -> i32 {
+    match check_program(scanner, opts) {
+        Ok((_, table, _)) => {
+            if opts.dump_symtab {
+                print!("{}", table.dump());
             }
-            Err(err) => {
-                println!("{}", err);
-                return;
+            println!("OK");
+            return 0;
+        }
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    }
+}
+
+
+/// Pretty-prints the program with the type of every expression.
+fn pptype(scanner: Scanner, opts: &Options) -> i32 {
+    match check_program(scanner, opts) {
+        Ok((prog, table, types)) => {
+            print!("{}", pretty::pretty_typed_program(&prog, &types, &table));
+            return 0;
+        }
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    }
+}
+
+
+/// Prints the control-flow graph of every function in the DOT
+/// language of Graphviz.
+fn print_cfg(scanner: Scanner, filename: &str) -> i32 {
+    match parse_and_weed(scanner) {
+        Ok(prog) => {
+            print!("{}", cfg::dot(filename, &cfg::build_program(&prog)));
+            return 0;
+        }
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    }
+}
+
+
+/// Translates the program to C, LLVM IR, x86-64 assembly,
+/// WebAssembly text or Jasmin assembly.
+fn generate(scanner: Scanner, opts: &Options) -> i32 {
+    let (prog, table, types) = match compile_program(scanner, opts) {
+        Ok(checked) => checked,
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    };
+    if opts.target == "jvm" {
+        return write_classes(&jvm::generate(&prog, &types, &table), opts);
+    }
+    if opts.target == "x86_64" && opts.dump_regalloc {
+        eprint!("{}", regalloc::dump(&regalloc::allocate_program(&prog, &types, &table)));
+    }
+    let code = match opts.target.as_str() {
+        "llvm" => llvm::generate(&prog, &types, &table),
+        "x86_64" => x86_64::generate(&prog, &types, &table),
+        "wasm" => wasm::generate(&prog, &types, &table),
+        _ => codegen::generate(&prog, &types, &table),
+    };
+    match opts.output {
+        Some(ref output) => {
+            if let Err(err) = File::create(output).and_then(|mut file| file.write_all(code.as_bytes())) {
+                eprintln!("gore: {}: {}", output, err);
+                return 1;
+            }
+        }
+        None => print!("{}", code),
+    }
+    return 0;
+}
+
+
+/// Writes the files of the classes of a program, and the
+/// runtime class, to the output directory; without one, prints
+/// the Jasmin files.
+fn write_classes(files: &[(String, String)], opts: &Options) -> i32 {
+    let dir = match opts.output {
+        Some(ref dir) => Path::new(dir),
+        None => {
+            for file in files.iter().filter(|f| f.0.ends_with(".j")) {
+                print!("{}", file.1);
+            }
+            return 0;
+        }
+    };
+    for (name, text) in files {
+        let path = dir.join(name);
+        if let Err(err) = fs::create_dir_all(dir).and_then(|_| fs::write(&path, text)) {
+            eprintln!("gore: {}: {}", path.display(), err);
+            return 1;
+        }
+    }
+    return 0;
+}
+
+
+/// The stack size of the interpreter's thread, which is enough
+/// for `interp::MAX_DEPTH` nested calls and small enough to be
+/// reserved under a limit on the size of the address space.
+const INTERP_STACK_SIZE: usize = 256 << 20;
+
+/// Executes the program; runtime errors exit with status 2.
+fn run(scanner: Scanner, opts: &Options) -> i32 {
+    let (prog, table, types) = match compile_program(scanner, opts) {
+        Ok(checked) => checked,
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    };
+    if opts.vm {
+        // The virtual machine keeps its frames on the heap.
+        let module = bcgen::compile(&prog, &types, &table);
+        return execute(|out| vm::run(&module, out));
+    }
+    let (use_ir, optimize, inline_report) = (opts.ir, opts.optimize, opts.inline_report);
+    return execute_on_thread(move |out| {
+        if use_ir {
+            let mut module = irgen::build(&prog, &types, &table);
+            if optimize {
+                let decisions = opt::optimize(&mut module);
+                if inline_report {
+                    eprint!("{}", inline::report(&module.filename, &decisions));
+                }
             }
+            return ireval::run(&module, out);
+        }
+        return interp::run(&prog, &types, &table, out);
+    });
+}
+
+
+/// Executes a compiled program read from a bytecode file.
+fn exec(filename: &str, bytes: &[u8]) -> i32 {
+    match bcfile::read(bytes) {
+        Ok(module) => return execute(|out| vm::run(&module, out)),
+        Err(err) => {
+            eprintln!("gore: {}: {}", filename, err);
+            return 1;
+        }
+    }
+}
+
+
+/// Runs `f` with a buffered standard output, and reports its
+/// runtime error.
+fn execute<F>(f: F) -> i32
+    where F: FnOnce(&mut dyn Write) -> Result<(), interp::RuntimeError>
+{
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let result = f(&mut out);
+    let _ = out.flush();
+    match result {
+        Ok(()) => return 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
         }
     }
 }
 
 
+/// Runs `execute(f)` on a thread with a large stack, for the
+/// interpreters that recurse on the nested calls of the program.
+fn execute_on_thread<F>(f: F) -> i32
+    where F: FnOnce(&mut dyn Write) -> Result<(), interp::RuntimeError> + Send + 'static
+{
+    let interp = thread::Builder::new()
+        .stack_size(INTERP_STACK_SIZE)
+        .spawn(move || execute(f));
+    match interp.map(|handle| handle.join()) {
+        Ok(Ok(status)) => return status,
+        Ok(Err(_)) => {
+            eprintln!("gore: the interpreter failed");
+            return 1;
+        }
+        Err(err) => {
+            eprintln!("gore: cannot start the interpreter: {}", err);
+            return 1;
+        }
+    }
+}
+
+
+/// Compiles the program to a bytecode file, named after the
+/// source file unless `-o` is given.
+fn build(scanner: Scanner, opts: &Options) -> i32 {
+    let (prog, table, types) = match compile_program(scanner, opts) {
+        Ok(checked) => checked,
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    };
+    let output = match (&opts.output, &opts.file) {
+        (Some(output), _) => output.clone(),
+        (None, Some(file)) => Path::new(file).with_extension("gorec").to_string_lossy().into_owned(),
+        (None, None) => "out.gorec".to_string(),
+    };
+    let bytes = bcfile::write(&bcgen::compile(&prog, &types, &table));
+    if let Err(err) = File::create(&output).and_then(|mut file| file.write_all(&bytes)) {
+        eprintln!("gore: {}: {}", output, err);
+        return 1;
+    }
+    return 0;
+}
+
+
+/// Prints the bytecode of the program.
+fn disasm(scanner: Scanner, opts: &Options) -> i32 {
+    match compile_program(scanner, opts) {
+        Ok((prog, table, types)) => {
+            print!("{}", bytecode::disassemble(&bcgen::compile(&prog, &types, &table)));
+            return 0;
+        }
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    }
+}
+
+
+/// Prints the SSA form of the program, and checks it.
+fn dump_ir(scanner: Scanner, opts: &Options) -> i32 {
+    let (prog, table, types) = match compile_program(scanner, opts) {
+        Ok(checked) => checked,
+        Err(errs) => {
+            report(&errs);
+            return 1;
+        }
+    };
+    let mut module = irgen::build(&prog, &types, &table);
+    if opts.optimize {
+        let decisions = opt::optimize(&mut module);
+        if opts.inline_report {
+            eprint!("{}", inline::report(&module.filename, &decisions));
+        }
+    }
+    print!("{}", ir::dump(&module));
+    if let Err(msg) = ir::verify(&module) {
+        eprintln!("gore: invalid IR: {}", msg);
+        return 1;
+    }
+    return 0;
+}
+
+
+fn report(errs: &[Error]) {
+    for err in errs {
+        println!("{}", err);
+    }
+}
+
+
+/// Prints warnings on the standard error, so that the output of
+/// the command is unchanged.
+fn warn(warnings: &[Error]) {
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+}
+
+
 fn all_tokens(scanner: &mut Scanner) -> Result<Vec<Token>, Error> {
     let mut toks = Vec::new();
     loop {
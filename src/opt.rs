@@ -0,0 +1,29 @@
+use dce;
+use fold;
+use inline::{self, Decision};
+use ir::Module;
+use licm;
+use strength;
+
+/// Optimises the functions of a module: they are simplified,
+/// the small ones are inlined into their callers, which are
+/// simplified again, and then their loops are optimised.  Returns
+/// what became of every call.
+pub fn optimize(module: &mut Module) -> Vec<Decision> {
+    simplify(module);
+    let decisions = inline::inline(module);
+    simplify(module);
+    for f in &mut module.functions {
+        licm::hoist(f);
+        strength::reduce(f);
+    }
+    simplify(module);
+    return decisions;
+}
+
+fn simplify(module: &mut Module) {
+    for f in &mut module.functions {
+        fold::fold(f);
+        dce::eliminate(f);
+    }
+}
@@ -0,0 +1,756 @@
+use std::mem;
+use std::result;
+
+use ast::*;
+use error::{Error, Result, err, err_msg};
+use error::ErrorType as ET;
+use loc::Loc;
+use scanner::Scanner;
+use token::Token;
+use token::TokenType as TT;
+
+/// Parses a complete source file and returns either the
+/// program or every syntax error that was found.
+pub fn parse(scanner: Scanner) -> result::Result<Program, Vec<Error>> {
+    let mut parser = Parser::new(scanner);
+    let prog = parser.parse_program();
+    if parser.errors.is_empty() {
+        return Ok(prog);
+    } else {
+        return Err(parser.errors);
+    }
+}
+
+/// A recursive-descent parser for GoLite.
+///
+/// The parser pulls tokens from the scanner one at a
+/// time and keeps a single token of lookahead (`tok`).
+///
+/// Syntax errors do not stop the parser: the error is
+/// recorded, the parser skips ahead to a synchronisation
+/// point (see `sync`), an error node takes the place of
+/// the faulty statement or declaration, and parsing resumes.
+/// Scanner errors, on the other hand, end the parse since
+/// the scanner cannot resume after one.
+#[derive(Debug)]
+pub struct Parser {
+    scanner: Scanner,
+    tok: Token,
+    errors: Vec<Error>,
+    scan_failed: bool,
+}
+
+impl Parser {
+    // UTILITY METHODS
+
+    /// Creates a parser and reads the first token.
+    pub fn new(scanner: Scanner) -> Parser {
+        let eof = Token::new(TT::Eof, Loc::new("", 0, 0), None);
+        let mut parser = Parser {
+            scanner: scanner,
+            tok: eof,
+            errors: Vec::new(),
+            scan_failed: false,
+        };
+        parser.bump();
+        return parser;
+    }
+
+    /// Returns the errors reported so far.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Records an error.  Like the Go parser, only the first
+    /// error on a given line is kept, since the following
+    /// ones are usually a consequence of the first.
+    fn report(&mut self, error: Error) {
+        if self.scan_failed {
+            return;
+        }
+        if let Some(last) = self.errors.last() {
+            if last.loc.line == error.loc.line && last.loc.filename == error.loc.filename {
+                return;
+            }
+        }
+        self.errors.push(error);
+    }
+
+    /// Returns `true` if the current token is of type `ty`.
+    fn peek(&self, ty: TT) -> bool {
+        self.tok.ty == ty
+    }
+
+    /// Returns the location of the current token.
+    fn loc(&self) -> Loc {
+        self.tok.loc.clone()
+    }
+
+    /// Moves to the next token and returns the current one.
+    /// A scanner error is reported and replaces the rest of
+    /// the input with `Eof`.
+    fn bump(&mut self) -> Token {
+        let next = if self.scan_failed {
+            Token::new(TT::Eof, self.loc(), None)
+        } else {
+            match self.scanner.next() {
+                Ok(tok) => tok,
+                Err(error) => {
+                    let loc = error.loc.clone();
+                    self.report(error);
+                    self.scan_failed = true;
+                    Token::new(TT::Eof, loc, None)
+                }
+            }
+        };
+        return mem::replace(&mut self.tok, next);
+    }
+
+    /// Returns an "expected X, found Y" syntax error
+    /// located at the current token.
+    fn unexpected<T>(&self, expected: &str) -> Result<T> {
+        let msg = format!("syntax error: expected {}, found {}", expected, self.tok.ty);
+        return err_msg(ET::UnexpectedToken, self.loc(), msg);
+    }
+
+    /// Consumes the current token if it is of type `ty`,
+    /// and returns an error otherwise.
+    fn expect(&mut self, ty: TT) -> Result<Token> {
+        if self.peek(ty) {
+            return Ok(self.bump());
+        } else {
+            return self.unexpected(&ty.to_string());
+        }
+    }
+
+    /// Consumes the current token if it is of type `ty`
+    /// and returns whether it did.
+    fn eat(&mut self, ty: TT) -> bool {
+        if self.peek(ty) {
+            self.bump();
+            return true;
+        }
+        return false;
+    }
+
+    /// Consumes a semi-colon, unless the current token
+    /// is `closing` (semi-colons may be omitted before a
+    /// closing `)` or `}`).
+    fn expect_semi(&mut self, closing: TT) -> Result<()> {
+        if !self.peek(closing) {
+            self.expect(TT::Semi)?;
+        }
+        return Ok(());
+    }
+
+    /// Skips tokens until a synchronisation point: a `;` or
+    /// a `}` that does not close a brace skipped along the way,
+    /// or a keyword that starts a top-level declaration.
+    /// Since `func` cannot occur inside a function body, it
+    /// stops the search even inside skipped braces.
+    fn sync(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.tok.ty {
+                TT::Eof | TT::Func => return,
+                TT::Semi | TT::Var | TT::Type if depth == 0 => return,
+                TT::RBrace if depth == 0 => return,
+                TT::RBrace => depth -= 1,
+                TT::LBrace => depth += 1,
+                _ => {}
+            }
+            self.bump();
+        }
+    }
+
+    fn ident(&mut self) -> Result<Ident> {
+        let loc = self.loc();
+        match self.tok.ty {
+            TT::Id => {
+                let tok = self.bump();
+                let name = tok.lexeme.unwrap_or_default();
+                return Ok(Ident::new(&name, loc));
+            }
+            TT::Blank => {
+                self.bump();
+                return Ok(Ident::new("_", loc));
+            }
+            _ => {
+                return self.unexpected("name");
+            }
+        }
+    }
+
+    fn ident_list(&mut self) -> Result<Vec<Ident>> {
+        let mut ids = vec![self.ident()?];
+        while self.eat(TT::Comma) {
+            ids.push(self.ident()?);
+        }
+        return Ok(ids);
+    }
+
+
+    // DECLARATIONS
+
+    /// Parses an entire source file.  Errors are available
+    /// through `errors()` once this method returns.
+    pub fn parse_program(&mut self) -> Program {
+        let package = match self.package_clause() {
+            Ok(package) => package,
+            Err(error) => {
+                let loc = error.loc.clone();
+                self.report(error);
+                self.sync();
+                self.eat(TT::Semi);
+                Ident::new("_", loc)
+            }
+        };
+
+        let mut decls = Vec::new();
+        while !self.peek(TT::Eof) {
+            let loc = self.loc();
+            let decl = self.top_level_decl()
+                .and_then(|decl| { self.expect_semi(TT::Eof)?; Ok(decl) });
+            match decl {
+                Ok(decl) => decls.push(decl),
+                Err(error) => {
+                    self.report(error);
+                    self.sync();
+                    // A stray `}` or `;` at the top level is skipped,
+                    // anything else starts the next declaration.
+                    if self.peek(TT::Semi) || self.peek(TT::RBrace) {
+                        self.bump();
+                    }
+                    decls.push(TopLevelDecl::Error(loc));
+                }
+            }
+        }
+        return Program { package: package, decls: decls };
+    }
+
+    fn package_clause(&mut self) -> Result<Ident> {
+        self.expect(TT::Package)?;
+        let package = self.ident()?;
+        self.expect_semi(TT::Eof)?;
+        return Ok(package);
+    }
+
+    fn top_level_decl(&mut self) -> Result<TopLevelDecl> {
+        match self.tok.ty {
+            TT::Var => Ok(TopLevelDecl::Var(self.var_decl()?)),
+            TT::Type => Ok(TopLevelDecl::Type(self.type_decl()?)),
+            TT::Func => Ok(TopLevelDecl::Func(self.func_decl()?)),
+            _ => self.unexpected("declaration"),
+        }
+    }
+
+    /// Parses `var spec` or `var ( spec; ... )`.
+    fn var_decl(&mut self) -> Result<Vec<VarSpec>> {
+        self.expect(TT::Var)?;
+        if self.eat(TT::LParen) {
+            let mut specs = Vec::new();
+            while !self.peek(TT::RParen) {
+                specs.push(self.var_spec()?);
+                self.expect_semi(TT::RParen)?;
+            }
+            self.expect(TT::RParen)?;
+            return Ok(specs);
+        } else {
+            return Ok(vec![self.var_spec()?]);
+        }
+    }
+
+    fn var_spec(&mut self) -> Result<VarSpec> {
+        let loc = self.loc();
+        let names = self.ident_list()?;
+        let ty = if self.peek(TT::Assign) { None } else { Some(self.type_expr()?) };
+        let values = if self.eat(TT::Assign) { self.expr_list()? } else { Vec::new() };
+        return Ok(VarSpec { names: names, ty: ty, values: values, loc: loc });
+    }
+
+    /// Parses `type spec` or `type ( spec; ... )`.
+    fn type_decl(&mut self) -> Result<Vec<TypeSpec>> {
+        self.expect(TT::Type)?;
+        if self.eat(TT::LParen) {
+            let mut specs = Vec::new();
+            while !self.peek(TT::RParen) {
+                specs.push(self.type_spec()?);
+                self.expect_semi(TT::RParen)?;
+            }
+            self.expect(TT::RParen)?;
+            return Ok(specs);
+        } else {
+            return Ok(vec![self.type_spec()?]);
+        }
+    }
+
+    fn type_spec(&mut self) -> Result<TypeSpec> {
+        let loc = self.loc();
+        let name = self.ident()?;
+        let ty = self.type_expr()?;
+        return Ok(TypeSpec { name: name, ty: ty, loc: loc });
+    }
+
+    fn func_decl(&mut self) -> Result<FuncDecl> {
+        let loc = self.loc();
+        self.expect(TT::Func)?;
+        let name = self.ident()?;
+        self.expect(TT::LParen)?;
+        let params = self.params()?;
+        self.expect(TT::RParen)?;
+        let result = if self.peek(TT::LBrace) { None } else { Some(self.type_expr()?) };
+        let body = self.block()?;
+        return Ok(FuncDecl {
+            name: name, params: params, result: result, body: body, loc: loc
+        });
+    }
+
+    /// Parses a parameter list such as `a, b int, c string`;
+    /// every name in a group shares the group's type.
+    fn params(&mut self) -> Result<Vec<Param>> {
+        let mut params = Vec::new();
+        while !self.peek(TT::RParen) {
+            let names = self.ident_list()?;
+            let ty = self.type_expr()?;
+            for name in names {
+                params.push(Param { name: name, ty: ty.clone() });
+            }
+            if !self.eat(TT::Comma) {
+                break;
+            }
+        }
+        return Ok(params);
+    }
+
+
+    // TYPES
+
+    fn type_expr(&mut self) -> Result<TypeExpr> {
+        let loc = self.loc();
+        let kind = match self.tok.ty {
+            TT::Id => {
+                let tok = self.bump();
+                TypeExprKind::Name(Ident::new(&tok.lexeme.unwrap_or_default(), loc.clone()))
+            }
+            TT::LParen => {
+                self.bump();
+                let ty = self.type_expr()?;
+                self.expect(TT::RParen)?;
+                return Ok(ty);
+            }
+            TT::LBracket => {
+                self.bump();
+                if self.eat(TT::RBracket) {
+                    TypeExprKind::Slice(Box::new(self.type_expr()?))
+                } else {
+                    let len = self.expr()?;
+                    self.expect(TT::RBracket)?;
+                    TypeExprKind::Array(Box::new(len), Box::new(self.type_expr()?))
+                }
+            }
+            TT::Struct => {
+                self.bump();
+                self.expect(TT::LBrace)?;
+                let mut fields = Vec::new();
+                while !self.peek(TT::RBrace) {
+                    let names = self.ident_list()?;
+                    let ty = self.type_expr()?;
+                    for name in names {
+                        fields.push(Field { name: name, ty: ty.clone() });
+                    }
+                    self.expect_semi(TT::RBrace)?;
+                }
+                self.expect(TT::RBrace)?;
+                TypeExprKind::Struct(fields)
+            }
+            _ => {
+                let msg = format!("syntax error: expected type, found {}", self.tok.ty);
+                return err_msg(ET::ExpectedType, loc, msg);
+            }
+        };
+        return Ok(TypeExpr { kind: kind, loc: loc });
+    }
+
+
+    // STATEMENTS
+
+    fn block(&mut self) -> Result<Block> {
+        let loc = self.loc();
+        self.expect(TT::LBrace)?;
+        let stmts = self.stmt_list()?;
+        let end = self.loc();
+        self.expect(TT::RBrace)?;
+        return Ok(Block { stmts: stmts, loc: loc, end: end });
+    }
+
+    /// Parses statements up to a closing brace or the next
+    /// clause of a switch statement.  A statement with a syntax
+    /// error is replaced by an error node.  Empty statements
+    /// have no meaning and are left out of the tree.
+    fn stmt_list(&mut self) -> Result<Vec<Stmt>> {
+        let mut stmts = Vec::new();
+        loop {
+            match self.tok.ty {
+                TT::RBrace | TT::Case | TT::Default | TT::Func | TT::Eof => break,
+                TT::Semi => {
+                    self.bump();
+                    continue;
+                }
+                _ => {}
+            }
+            let loc = self.loc();
+            let stmt = self.stmt()
+                .and_then(|stmt| { self.expect_semi(TT::RBrace)?; Ok(stmt) });
+            match stmt {
+                Ok(stmt) => stmts.push(stmt),
+                Err(error) => {
+                    self.report(error);
+                    self.sync();
+                    if self.peek(TT::Semi) {
+                        self.bump();
+                    }
+                    stmts.push(Stmt { kind: StmtKind::Error, loc: loc });
+                }
+            }
+        }
+        return Ok(stmts);
+    }
+
+    fn stmt(&mut self) -> Result<Stmt> {
+        let loc = self.loc();
+        let kind = match self.tok.ty {
+            TT::Var => StmtKind::Var(self.var_decl()?),
+            TT::Type => StmtKind::Type(self.type_decl()?),
+            TT::LBrace => StmtKind::Block(self.block()?),
+            TT::Print => {
+                self.bump();
+                StmtKind::Print(self.print_args()?)
+            }
+            TT::Println => {
+                self.bump();
+                StmtKind::Println(self.print_args()?)
+            }
+            TT::Return => {
+                self.bump();
+                if self.peek(TT::Semi) || self.peek(TT::RBrace) {
+                    StmtKind::Return(None)
+                } else {
+                    StmtKind::Return(Some(self.expr()?))
+                }
+            }
+            TT::Break => {
+                self.bump();
+                StmtKind::Break
+            }
+            TT::Continue => {
+                self.bump();
+                StmtKind::Continue
+            }
+            TT::If => return self.if_stmt(),
+            TT::Switch => return self.switch_stmt(),
+            TT::For => return self.for_stmt(),
+            _ => return self.simple_stmt(),
+        };
+        return Ok(Stmt { kind: kind, loc: loc });
+    }
+
+    fn print_args(&mut self) -> Result<Vec<Expr>> {
+        self.expect(TT::LParen)?;
+        let args = if self.peek(TT::RParen) { Vec::new() } else { self.expr_list()? };
+        self.expect(TT::RParen)?;
+        return Ok(args);
+    }
+
+    /// Parses expression statements, assignments,
+    /// op-assignments, increments/decrements and short
+    /// variable declarations.
+    fn simple_stmt(&mut self) -> Result<Stmt> {
+        let loc = self.loc();
+        let mut lhs = self.expr_list()?;
+        let kind = match self.tok.ty {
+            TT::Assign => {
+                self.bump();
+                StmtKind::Assign(lhs, self.expr_list()?)
+            }
+            TT::ColonEq => {
+                let mut names = Vec::new();
+                for e in lhs {
+                    match e.kind {
+                        ExprKind::Id(id) => names.push(id),
+                        ExprKind::Blank => names.push(Ident::new("_", e.loc)),
+                        _ => return err(ET::NonNameInShortVarDecl, e.loc),
+                    }
+                }
+                self.bump();
+                StmtKind::ShortVarDecl(names, self.expr_list()?)
+            }
+            _ if lhs.len() > 1 => {
+                return self.unexpected(":= or =");
+            }
+            TT::Incr => {
+                self.bump();
+                StmtKind::Incr(lhs.pop().unwrap())
+            }
+            TT::Decr => {
+                self.bump();
+                StmtKind::Decr(lhs.pop().unwrap())
+            }
+            ty => match BinOp::from_assign_token(ty) {
+                Some(op) => {
+                    self.bump();
+                    StmtKind::OpAssign(op, lhs.pop().unwrap(), self.expr()?)
+                }
+                None => StmtKind::Expr(lhs.pop().unwrap()),
+            },
+        };
+        return Ok(Stmt { kind: kind, loc: loc });
+    }
+
+    /// Parses the optional simple statement of an `if`
+    /// or `switch` header.  Returns the init statement (if
+    /// any) and the expression that followed it (if any).
+    fn header(&mut self) -> Result<(Option<Box<Stmt>>, Option<Expr>)> {
+        if self.peek(TT::LBrace) {
+            return Ok((None, None));
+        }
+        if self.eat(TT::Semi) {
+            let expr = if self.peek(TT::LBrace) { None } else { Some(self.expr()?) };
+            return Ok((None, expr));
+        }
+        let init = self.simple_stmt()?;
+        if self.eat(TT::Semi) {
+            let expr = if self.peek(TT::LBrace) { None } else { Some(self.expr()?) };
+            return Ok((Some(Box::new(init)), expr));
+        }
+        match init.kind {
+            StmtKind::Expr(e) => Ok((None, Some(e))),
+            _ => err(ET::MissingCondition, init.loc),
+        }
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt> {
+        let loc = self.loc();
+        self.expect(TT::If)?;
+        let (init, cond) = self.header()?;
+        let cond = match cond {
+            Some(cond) => cond,
+            None => return err(ET::MissingCondition, loc),
+        };
+        let then = self.block()?;
+        let els = if self.eat(TT::Else) {
+            if self.peek(TT::If) {
+                Some(Box::new(self.if_stmt()?))
+            } else {
+                let block_loc = self.loc();
+                let block = self.block()?;
+                Some(Box::new(Stmt { kind: StmtKind::Block(block), loc: block_loc }))
+            }
+        } else {
+            None
+        };
+        let if_stmt = IfStmt { init: init, cond: cond, then: then, els: els };
+        return Ok(Stmt { kind: StmtKind::If(if_stmt), loc: loc });
+    }
+
+    fn switch_stmt(&mut self) -> Result<Stmt> {
+        let loc = self.loc();
+        self.expect(TT::Switch)?;
+        let (init, tag) = self.header()?;
+        self.expect(TT::LBrace)?;
+        let mut clauses = Vec::new();
+        while !self.peek(TT::RBrace) {
+            clauses.push(self.case_clause()?);
+        }
+        self.expect(TT::RBrace)?;
+        let switch_stmt = SwitchStmt { init: init, tag: tag, clauses: clauses };
+        return Ok(Stmt { kind: StmtKind::Switch(switch_stmt), loc: loc });
+    }
+
+    fn case_clause(&mut self) -> Result<CaseClause> {
+        let loc = self.loc();
+        let exprs = match self.tok.ty {
+            TT::Case => {
+                self.bump();
+                Some(self.expr_list()?)
+            }
+            TT::Default => {
+                self.bump();
+                None
+            }
+            _ => {
+                return self.unexpected("case or default");
+            }
+        };
+        self.expect(TT::Colon)?;
+        let body = self.stmt_list()?;
+        return Ok(CaseClause { exprs: exprs, body: body, loc: loc });
+    }
+
+    fn for_stmt(&mut self) -> Result<Stmt> {
+        let loc = self.loc();
+        self.expect(TT::For)?;
+        let mut for_stmt = ForStmt { init: None, cond: None, post: None, body: Block {
+            stmts: Vec::new(), loc: loc.clone(), end: loc.clone()
+        }};
+
+        if !self.peek(TT::LBrace) {
+            let init = if self.peek(TT::Semi) { None } else { Some(self.simple_stmt()?) };
+            match init {
+                // for cond { ... }
+                Some(Stmt { kind: StmtKind::Expr(cond), .. }) if self.peek(TT::LBrace) => {
+                    for_stmt.cond = Some(cond);
+                }
+                // for init; cond; post { ... }
+                init => {
+                    for_stmt.init = init.map(Box::new);
+                    self.expect(TT::Semi)?;
+                    if !self.peek(TT::Semi) {
+                        for_stmt.cond = Some(self.expr()?);
+                    }
+                    self.expect(TT::Semi)?;
+                    if !self.peek(TT::LBrace) {
+                        for_stmt.post = Some(Box::new(self.simple_stmt()?));
+                    }
+                }
+            }
+        }
+
+        for_stmt.body = self.block()?;
+        return Ok(Stmt { kind: StmtKind::For(for_stmt), loc: loc });
+    }
+
+
+    // EXPRESSIONS
+
+    /// Parses a single expression.
+    pub fn parse_expr(&mut self) -> Result<Expr> {
+        self.expr()
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>> {
+        let mut exprs = vec![self.expr()?];
+        while self.eat(TT::Comma) {
+            exprs.push(self.expr()?);
+        }
+        return Ok(exprs);
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        self.binary_expr(1)
+    }
+
+    /// Precedence climbing: parses a sequence of binary
+    /// operations whose operators bind at least as tightly
+    /// as `min_prec`.  All binary operators are left-associative.
+    fn binary_expr(&mut self, min_prec: u8) -> Result<Expr> {
+        let mut lhs = self.unary_expr()?;
+        loop {
+            let op = match BinOp::from_token(self.tok.ty) {
+                Some(op) if op.precedence() >= min_prec => op,
+                _ => break,
+            };
+            let loc = self.loc();
+            self.bump();
+            let rhs = self.binary_expr(op.precedence() + 1)?;
+            lhs = Expr::new(ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), loc);
+        }
+        return Ok(lhs);
+    }
+
+    fn unary_expr(&mut self) -> Result<Expr> {
+        match UnOp::from_token(self.tok.ty) {
+            Some(op) => {
+                let loc = self.loc();
+                self.bump();
+                let operand = self.unary_expr()?;
+                return Ok(Expr::new(ExprKind::Unary(op, Box::new(operand)), loc));
+            }
+            None => {
+                return self.primary_expr();
+            }
+        }
+    }
+
+    /// Parses an operand followed by any number of calls,
+    /// index expressions and field selections.
+    /// The location of the resulting expression is that of
+    /// the operand.
+    fn primary_expr(&mut self) -> Result<Expr> {
+        let mut e = self.operand()?;
+        loop {
+            let loc = e.loc.clone();
+            let kind = match self.tok.ty {
+                TT::LParen => {
+                    self.bump();
+                    let args = if self.peek(TT::RParen) {
+                        Vec::new()
+                    } else {
+                        self.expr_list()?
+                    };
+                    self.expect(TT::RParen)?;
+                    ExprKind::Call(Box::new(e), args)
+                }
+                TT::LBracket => {
+                    self.bump();
+                    let index = self.expr()?;
+                    self.expect(TT::RBracket)?;
+                    ExprKind::Index(Box::new(e), Box::new(index))
+                }
+                TT::Dot => {
+                    self.bump();
+                    let field = self.ident()?;
+                    ExprKind::Selector(Box::new(e), field)
+                }
+                _ => break,
+            };
+            e = Expr::new(kind, loc);
+        }
+        return Ok(e);
+    }
+
+    fn operand(&mut self) -> Result<Expr> {
+        let loc = self.loc();
+        let kind = match self.tok.ty {
+            TT::Id => ExprKind::Id(Ident::new(&self.lexeme(), loc.clone())),
+            TT::Blank => {
+                self.bump();
+                ExprKind::Blank
+            }
+            TT::Int => ExprKind::Int(self.lexeme(), IntBase::Dec),
+            TT::IntOct => ExprKind::Int(self.lexeme(), IntBase::Oct),
+            TT::IntHex => ExprKind::Int(self.lexeme(), IntBase::Hex),
+            TT::Float => ExprKind::Float(self.lexeme()),
+            TT::String => ExprKind::String(self.lexeme()),
+            TT::Rune => {
+                let lexeme = self.lexeme();
+                ExprKind::Rune(lexeme.chars().next().unwrap_or('\0'))
+            }
+            TT::LParen => {
+                self.bump();
+                let e = self.expr()?;
+                self.expect(TT::RParen)?;
+                return Ok(e);
+            }
+            TT::Append => {
+                self.bump();
+                self.expect(TT::LParen)?;
+                let slice = self.expr()?;
+                self.expect(TT::Comma)?;
+                let elem = self.expr()?;
+                self.expect(TT::RParen)?;
+                ExprKind::Append(Box::new(slice), Box::new(elem))
+            }
+            _ => {
+                let msg = format!("syntax error: expected expression, found {}", self.tok.ty);
+                return err_msg(ET::ExpectedExpression, loc, msg);
+            }
+        };
+        return Ok(Expr::new(kind, loc));
+    }
+
+    /// Consumes the current token and returns its lexeme.
+    fn lexeme(&mut self) -> String {
+        let tok = self.bump();
+        return tok.lexeme.unwrap_or_default();
+    }
+}
@@ -0,0 +1,542 @@
+use ast::*;
+use symtab::SymbolTable;
+use token::TokenType as TT;
+use types::TypeTable;
+
+/// Precedence of unary expressions; binary operators
+/// use `BinOp::precedence` (1 to 5).
+const UNARY_PREC: u8 = 6;
+
+/// Precedence of operands and primary expressions.
+const PRIMARY_PREC: u8 = 7;
+
+/// Returns the canonical source text of a program.
+pub fn pretty_program(prog: &Program) -> String {
+    let mut p = Printer::new();
+    p.program(prog);
+    return p.buf;
+}
+
+/// Returns the canonical source text of a type-checked program,
+/// with the type of every expression in a comment after it, as in
+/// `x + 1 /* int */`, and the types of the variables declared by
+/// every `:=` statement in a comment after the statement.  The
+/// operands of operators, and the functions, arrays and structs
+/// that are called, indexed or selected, are not annotated: the
+/// comment follows the whole expression.
+pub fn pretty_typed_program(prog: &Program, types: &TypeTable, table: &SymbolTable) -> String {
+    let mut p = Printer::new();
+    p.types = Some((types, table));
+    p.program(prog);
+    return p.buf;
+}
+
+/// Returns the canonical source text of an expression.
+pub fn pretty_expr(e: &Expr) -> String {
+    let mut p = Printer::new();
+    p.expr(e);
+    return p.buf;
+}
+
+/// Returns the canonical source text of a statement.
+pub fn pretty_stmt(stmt: &Stmt) -> String {
+    let mut p = Printer::new();
+    p.stmt(stmt);
+    return p.buf;
+}
+
+/// Returns the canonical source text of a type expression.
+pub fn pretty_type(ty: &TypeExpr) -> String {
+    let mut p = Printer::new();
+    p.type_expr(ty);
+    return p.buf;
+}
+
+/// Pretty-printer producing gofmt-like output: tab indentation,
+/// one statement per line, a space on each side of binary
+/// operators, and parentheses only where the precedence
+/// of operators requires them.
+struct Printer<'a> {
+    buf: String,
+    indent: usize,
+    /// The types to print in comments, if any.
+    types: Option<(&'a TypeTable, &'a SymbolTable)>,
+}
+
+impl<'a> Printer<'a> {
+    fn new() -> Printer<'a> {
+        Printer { buf: String::new(), indent: 0, types: None }
+    }
+
+    fn write(&mut self, s: &str) {
+        self.buf.push_str(s);
+    }
+
+    /// Starts a new, indented line.
+    fn line(&mut self) {
+        self.buf.push('\n');
+        for _ in 0 .. self.indent {
+            self.buf.push('\t');
+        }
+    }
+
+    fn sep_list<T, F>(&mut self, items: &[T], mut f: F)
+        where F: FnMut(&mut Printer<'a>, &T)
+    {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            f(self, item);
+        }
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        self.write("package ");
+        self.write(&prog.package.name);
+        self.write("\n");
+        for decl in &prog.decls {
+            self.write("\n");
+            match *decl {
+                TopLevelDecl::Var(ref specs) => self.var_decl(specs),
+                TopLevelDecl::Type(ref specs) => self.type_decl(specs),
+                TopLevelDecl::Func(ref f) => self.func_decl(f),
+                TopLevelDecl::Error(_) => self.write("/* syntax error */"),
+            }
+            self.write("\n");
+        }
+    }
+
+    fn var_decl(&mut self, specs: &[VarSpec]) {
+        self.write("var ");
+        if specs.len() == 1 {
+            self.var_spec(&specs[0]);
+        } else {
+            self.group(specs, Printer::var_spec);
+        }
+    }
+
+    fn type_decl(&mut self, specs: &[TypeSpec]) {
+        self.write("type ");
+        if specs.len() == 1 {
+            self.type_spec(&specs[0]);
+        } else {
+            self.group(specs, Printer::type_spec);
+        }
+    }
+
+    /// Prints the specs of a grouped declaration, one per line.
+    fn group<T>(&mut self, specs: &[T], f: fn(&mut Printer<'a>, &T)) {
+        self.write("(");
+        self.indent += 1;
+        for spec in specs {
+            self.line();
+            f(self, spec);
+        }
+        self.indent -= 1;
+        self.line();
+        self.write(")");
+    }
+
+    fn var_spec(&mut self, spec: &VarSpec) {
+        self.sep_list(&spec.names, |p, id| p.write(&id.name));
+        if let Some(ref ty) = spec.ty {
+            self.write(" ");
+            self.type_expr(ty);
+        }
+        if !spec.values.is_empty() {
+            self.write(" = ");
+            self.expr_list(&spec.values);
+        }
+    }
+
+    fn type_spec(&mut self, spec: &TypeSpec) {
+        self.write(&spec.name.name);
+        self.write(" ");
+        self.type_expr(&spec.ty);
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.write("func ");
+        self.write(&f.name.name);
+        self.write("(");
+        let params: Vec<(&Ident, &TypeExpr)> =
+            f.params.iter().map(|p| (&p.name, &p.ty)).collect();
+        for (i, (names, ty)) in group_by_type(&params).into_iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            self.sep_list(&names, |p, id| p.write(&id.name));
+            self.write(" ");
+            self.type_expr(ty);
+        }
+        self.write(")");
+        if let Some(ref ty) = f.result {
+            self.write(" ");
+            self.type_expr(ty);
+        }
+        self.write(" ");
+        self.block(&f.body);
+    }
+
+
+    // TYPES
+
+    fn type_expr(&mut self, ty: &TypeExpr) {
+        match ty.kind {
+            TypeExprKind::Name(ref id) => self.write(&id.name),
+            TypeExprKind::Array(ref len, ref elem) => {
+                self.write("[");
+                self.expr(len);
+                self.write("]");
+                self.type_expr(elem);
+            }
+            TypeExprKind::Slice(ref elem) => {
+                self.write("[]");
+                self.type_expr(elem);
+            }
+            TypeExprKind::Struct(ref fields) => {
+                if fields.is_empty() {
+                    self.write("struct{}");
+                    return;
+                }
+                self.write("struct {");
+                self.indent += 1;
+                let fields: Vec<(&Ident, &TypeExpr)> =
+                    fields.iter().map(|f| (&f.name, &f.ty)).collect();
+                for (names, ty) in group_by_type(&fields) {
+                    self.line();
+                    self.sep_list(&names, |p, id| p.write(&id.name));
+                    self.write(" ");
+                    self.type_expr(ty);
+                }
+                self.indent -= 1;
+                self.line();
+                self.write("}");
+            }
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn block(&mut self, block: &Block) {
+        self.write("{");
+        self.stmts(&block.stmts);
+        self.line();
+        self.write("}");
+    }
+
+    /// Prints statements on their own lines, one level deeper
+    /// than the current indentation.
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        self.indent += 1;
+        for stmt in stmts {
+            self.line();
+            self.stmt(stmt);
+        }
+        self.indent -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.block(block),
+            StmtKind::Var(ref specs) => self.var_decl(specs),
+            StmtKind::Type(ref specs) => self.type_decl(specs),
+            StmtKind::Print(ref args) => {
+                self.write("print(");
+                self.expr_list(args);
+                self.write(")");
+            }
+            StmtKind::Println(ref args) => {
+                self.write("println(");
+                self.expr_list(args);
+                self.write(")");
+            }
+            StmtKind::Return(ref e) => {
+                self.write("return");
+                if let Some(ref e) = *e {
+                    self.write(" ");
+                    self.expr(e);
+                }
+            }
+            StmtKind::Break => self.write("break"),
+            StmtKind::Continue => self.write("continue"),
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+            StmtKind::Error => self.write("/* syntax error */"),
+            StmtKind::Expr(_)
+            | StmtKind::Assign(_, _)
+            | StmtKind::OpAssign(_, _, _)
+            | StmtKind::Incr(_)
+            | StmtKind::Decr(_)
+            | StmtKind::ShortVarDecl(_, _) => self.simple_stmt(stmt),
+        }
+    }
+
+    fn simple_stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Expr(ref e) => self.expr(e),
+            StmtKind::Assign(ref lhs, ref rhs) => {
+                self.expr_list(lhs);
+                self.write(" = ");
+                self.expr_list(rhs);
+            }
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => {
+                self.expr(lhs);
+                self.write(" ");
+                self.write(&op.assign_token().unwrap_or(TT::Assign).to_string());
+                self.write(" ");
+                self.expr(rhs);
+            }
+            StmtKind::Incr(ref e) => {
+                self.expr(e);
+                self.write("++");
+            }
+            StmtKind::Decr(ref e) => {
+                self.expr(e);
+                self.write("--");
+            }
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                self.sep_list(names, |p, id| p.write(&id.name));
+                self.write(" := ");
+                self.expr_list(values);
+                self.new_vars(names);
+            }
+            _ => self.stmt(stmt),
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        self.write("if ");
+        if let Some(ref init) = if_stmt.init {
+            self.simple_stmt(init);
+            self.write("; ");
+        }
+        self.expr(&if_stmt.cond);
+        self.write(" ");
+        self.block(&if_stmt.then);
+        if let Some(ref els) = if_stmt.els {
+            self.write(" else ");
+            self.stmt(els);
+        }
+    }
+
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) {
+        self.write("switch ");
+        if let Some(ref init) = switch_stmt.init {
+            self.simple_stmt(init);
+            self.write("; ");
+        }
+        if let Some(ref tag) = switch_stmt.tag {
+            self.expr(tag);
+            self.write(" ");
+        }
+        self.write("{");
+        for clause in &switch_stmt.clauses {
+            self.line();
+            match clause.exprs {
+                Some(ref exprs) => {
+                    self.write("case ");
+                    self.expr_list(exprs);
+                    self.write(":");
+                }
+                None => self.write("default:"),
+            }
+            self.stmts(&clause.body);
+        }
+        self.line();
+        self.write("}");
+    }
+
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        self.write("for ");
+        match (&for_stmt.init, &for_stmt.cond, &for_stmt.post) {
+            (&None, &None, &None) => {}
+            (&None, Some(cond), &None) => {
+                self.expr(cond);
+                self.write(" ");
+            }
+            (init, cond, post) => {
+                if let Some(ref init) = *init {
+                    self.simple_stmt(init);
+                }
+                self.write("; ");
+                if let Some(ref cond) = *cond {
+                    self.expr(cond);
+                }
+                self.write(";");
+                if let Some(ref post) = *post {
+                    self.write(" ");
+                    self.simple_stmt(post);
+                }
+                self.write(" ");
+            }
+        }
+        self.block(&for_stmt.body);
+    }
+
+
+    /// Prints the types of the variables that a `:=` statement
+    /// declares; the other names are assigned to.
+    fn new_vars(&mut self, names: &[Ident]) {
+        let (types, table) = match self.types {
+            Some(types) => types,
+            None => return,
+        };
+        let vars: Vec<String> = names.iter()
+            .filter_map(|id| id.sym.map(|sym| (id, table.symbol(sym))))
+            .filter(|&(id, sym)| sym.loc.as_ref() == Some(&id.loc))
+            .filter_map(|(id, sym)| sym.ty.map(|ty| format!("{} {}", id.name, types.name(ty))))
+            .collect();
+        if !vars.is_empty() {
+            self.write(&format!(" /* {} */", vars.join(", ")));
+        }
+    }
+
+
+    // EXPRESSIONS
+
+    fn expr_list(&mut self, exprs: &[Expr]) {
+        self.sep_list(exprs, Printer::expr);
+    }
+
+    /// Prints an expression, followed by its type if types
+    /// are printed.
+    fn expr(&mut self, e: &Expr) {
+        self.expr_kind(e);
+        if let (Some((types, _)), Some(ty)) = (self.types, e.ty) {
+            self.write(&format!(" /* {} */", types.name(ty)));
+        }
+    }
+
+    fn expr_kind(&mut self, e: &Expr) {
+        match e.kind {
+            ExprKind::Id(ref id) => self.write(&id.name),
+            ExprKind::Blank => self.write("_"),
+            ExprKind::Int(ref digits, IntBase::Hex) => {
+                self.write("0x");
+                self.write(digits);
+            }
+            ExprKind::Int(ref digits, _) => self.write(digits),
+            ExprKind::Float(ref digits) => self.write(digits),
+            ExprKind::Rune(c) => {
+                self.write("'");
+                self.write(&escape(c, '\''));
+                self.write("'");
+            }
+            ExprKind::String(ref s) => {
+                self.write("\"");
+                for c in s.chars() {
+                    self.write(&escape(c, '"'));
+                }
+                self.write("\"");
+            }
+            ExprKind::Unary(op, ref operand) => {
+                self.write(&op.token().to_string());
+                // `- -x` must not be printed as `--x`.
+                if let ExprKind::Unary(op2, _) = operand.kind {
+                    if op == op2 && (op == UnOp::Neg || op == UnOp::Plus) {
+                        self.write(" ");
+                    }
+                }
+                self.operand(operand, UNARY_PREC);
+            }
+            ExprKind::Binary(op, ref lhs, ref rhs) => {
+                // Binary operators are left-associative, so only
+                // the right operand needs parentheses when its
+                // operator has the same precedence.
+                self.operand(lhs, op.precedence());
+                self.write(" ");
+                self.write(&op.token().to_string());
+                self.write(" ");
+                self.operand(rhs, op.precedence() + 1);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                self.operand(f, PRIMARY_PREC);
+                self.write("(");
+                self.expr_list(args);
+                self.write(")");
+            }
+            ExprKind::Index(ref a, ref i) => {
+                self.operand(a, PRIMARY_PREC);
+                self.write("[");
+                self.expr(i);
+                self.write("]");
+            }
+            ExprKind::Selector(ref e, ref field) => {
+                self.operand(e, PRIMARY_PREC);
+                self.write(".");
+                self.write(&field.name);
+            }
+            ExprKind::Append(ref s, ref elem) => {
+                self.write("append(");
+                self.expr(s);
+                self.write(", ");
+                self.expr(elem);
+                self.write(")");
+            }
+        }
+    }
+
+    /// Prints `e`, parenthesized if it binds less
+    /// tightly than `min_prec`, without its type.
+    fn operand(&mut self, e: &Expr, min_prec: u8) {
+        if precedence(e) < min_prec {
+            self.write("(");
+            self.expr_kind(e);
+            self.write(")");
+        } else {
+            self.expr_kind(e);
+        }
+    }
+}
+
+fn precedence(e: &Expr) -> u8 {
+    match e.kind {
+        ExprKind::Binary(op, _, _) => op.precedence(),
+        ExprKind::Unary(_, _) => UNARY_PREC,
+        _ => PRIMARY_PREC,
+    }
+}
+
+/// Groups consecutive names that have the same type, so
+/// that `a int, b int` is printed as `a, b int`.  The types are
+/// compared by their text, and each group keeps the first of
+/// them, to be printed at the current indentation.
+fn group_by_type<'a, 'b>(items: &[(&'a Ident, &'b TypeExpr)]) -> Vec<(Vec<&'a Ident>, &'b TypeExpr)> {
+    let mut groups: Vec<(Vec<&Ident>, &TypeExpr, String)> = Vec::new();
+    for &(name, ty) in items {
+        let text = pretty_type(ty);
+        match groups.last_mut() {
+            Some(&mut (ref mut names, _, ref last)) if *last == text => {
+                names.push(name);
+                continue;
+            }
+            _ => {}
+        }
+        groups.push((vec![name], ty, text));
+    }
+    return groups.into_iter().map(|(names, ty, _)| (names, ty)).collect();
+}
+
+/// Returns the character as it must be written inside a
+/// literal delimited by `quote`.
+fn escape(c: char, quote: char) -> String {
+    match c {
+        '\x07' => "\\a".to_string(),
+        '\x08' => "\\b".to_string(),
+        '\x0c' => "\\f".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\x0b' => "\\v".to_string(),
+        '\\' => "\\\\".to_string(),
+        c if c == quote => format!("\\{}", c),
+        c => c.to_string(),
+    }
+}
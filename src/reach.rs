@@ -0,0 +1,217 @@
+use std::mem;
+use std::slice;
+
+use ast::*;
+use constant::Value;
+use error::Error;
+use error::ErrorType as ET;
+
+/// Finds the statements that can never be executed because the
+/// statement before them does not complete normally, as `go vet`
+/// does: the statements that follow a `return`, `break` or
+/// `continue`, an infinite loop that is never broken out of, or
+/// an `if`, a block or a switch with a default clause none of
+/// whose branches complete.  Only the first statement of each
+/// unreachable sequence is reported.
+///
+/// The bodies of `if` and `for` statements whose condition is a
+/// constant are deliberately not reported: `if false { ... }` is
+/// the usual way of disabling code.
+pub fn unreachable(prog: &Program) -> Vec<Error> {
+    let mut warnings = Vec::new();
+    for decl in &prog.decls {
+        if let TopLevelDecl::Func(ref f) = *decl {
+            warn_stmts(&f.body.stmts, &mut warnings);
+        }
+    }
+    return warnings;
+}
+
+/// Removes the statements that can never be executed from the
+/// bodies of the functions: the statements reported by
+/// `unreachable`, the branches of `if` statements that their
+/// constant condition excludes, and the loops whose condition is
+/// the constant `false`.
+pub fn eliminate(prog: &mut Program) {
+    for decl in &mut prog.decls {
+        if let TopLevelDecl::Func(ref mut f) = *decl {
+            eliminate_stmts(&mut f.body.stmts);
+        }
+    }
+}
+
+
+// WARNINGS
+
+fn warn_stmts(stmts: &[Stmt], warnings: &mut Vec<Error>) {
+    let mut reachable = true;
+    for stmt in stmts {
+        if !reachable {
+            warnings.push(Error::warning(ET::UnreachableCode, stmt.loc.clone()));
+        }
+        warn_stmt(stmt, warnings);
+        reachable = falls_through(stmt);
+    }
+}
+
+fn warn_stmt(stmt: &Stmt, warnings: &mut Vec<Error>) {
+    match stmt.kind {
+        StmtKind::Block(ref block) => warn_stmts(&block.stmts, warnings),
+        StmtKind::If(ref if_stmt) => {
+            warn_stmts(&if_stmt.then.stmts, warnings);
+            if let Some(ref els) = if_stmt.els {
+                warn_stmt(els, warnings);
+            }
+        }
+        StmtKind::Switch(ref switch_stmt) => {
+            for clause in &switch_stmt.clauses {
+                warn_stmts(&clause.body, warnings);
+            }
+        }
+        StmtKind::For(ref for_stmt) => warn_stmts(&for_stmt.body.stmts, warnings),
+        _ => {}
+    }
+}
+
+
+// ELIMINATION
+
+fn eliminate_stmts(stmts: &mut Vec<Stmt>) {
+    for stmt in stmts.iter_mut() {
+        eliminate_stmt(stmt);
+    }
+    if let Some(i) = stmts.iter().position(|stmt| !falls_through(stmt)) {
+        stmts.truncate(i + 1);
+    }
+}
+
+fn eliminate_stmt(stmt: &mut Stmt) {
+    let kind = mem::replace(&mut stmt.kind, StmtKind::Error);
+    stmt.kind = match kind {
+        // The condition is constant, so only the initialization
+        // statement and the branch taken remain, in a block that
+        // gives them the scope of the `if` statement.
+        StmtKind::If(if_stmt) => match constant(&if_stmt.cond) {
+            Some(cond) => {
+                let IfStmt { init, then, els, .. } = if_stmt;
+                let mut stmts: Vec<Stmt> = init.into_iter().map(|init| *init).collect();
+                if cond {
+                    let loc = then.loc.clone();
+                    stmts.push(Stmt { kind: StmtKind::Block(then), loc: loc });
+                } else if let Some(els) = els {
+                    stmts.push(*els);
+                }
+                eliminate_stmts(&mut stmts);
+                StmtKind::Block(Block { stmts: stmts, loc: stmt.loc.clone(), end: stmt.loc.clone() })
+            }
+            None => {
+                let mut if_stmt = if_stmt;
+                eliminate_stmts(&mut if_stmt.then.stmts);
+                if let Some(ref mut els) = if_stmt.els {
+                    eliminate_stmt(els);
+                }
+                StmtKind::If(if_stmt)
+            }
+        },
+        StmtKind::For(for_stmt) => match for_stmt.cond.as_ref().and_then(constant) {
+            Some(false) => {
+                let stmts = for_stmt.init.into_iter().map(|init| *init).collect();
+                StmtKind::Block(Block { stmts: stmts, loc: stmt.loc.clone(), end: for_stmt.body.end })
+            }
+            _ => {
+                let mut for_stmt = for_stmt;
+                eliminate_stmts(&mut for_stmt.body.stmts);
+                StmtKind::For(for_stmt)
+            }
+        },
+        StmtKind::Block(mut block) => {
+            eliminate_stmts(&mut block.stmts);
+            StmtKind::Block(block)
+        }
+        StmtKind::Switch(mut switch_stmt) => {
+            for clause in &mut switch_stmt.clauses {
+                eliminate_stmts(&mut clause.body);
+            }
+            StmtKind::Switch(switch_stmt)
+        }
+        kind => kind,
+    };
+}
+
+fn constant(cond: &Expr) -> Option<bool> {
+    match cond.value {
+        Some(Value::Bool(b)) => return Some(b),
+        _ => return None,
+    }
+}
+
+
+// REACHABILITY
+
+/// Whether the execution may continue after `stmt`.
+fn falls_through(stmt: &Stmt) -> bool {
+    match stmt.kind {
+        StmtKind::Return(_) | StmtKind::Break | StmtKind::Continue => return false,
+        StmtKind::Block(ref block) => return stmts_fall_through(&block.stmts),
+        StmtKind::If(ref if_stmt) => match if_stmt.els {
+            Some(ref els) => return stmts_fall_through(&if_stmt.then.stmts) || falls_through(els),
+            None => return true,
+        },
+        StmtKind::For(ref for_stmt) => {
+            return for_stmt.cond.is_some() || breaks(&for_stmt.body.stmts);
+        }
+        StmtKind::Switch(ref switch_stmt) => {
+            let clauses = &switch_stmt.clauses;
+            return !clauses.iter().any(|clause| clause.exprs.is_none())
+                || clauses.iter().any(|clause| stmts_fall_through(&clause.body) || breaks(&clause.body));
+        }
+        _ => return true,
+    }
+}
+
+fn stmts_fall_through(stmts: &[Stmt]) -> bool {
+    return stmts.last().is_none_or(falls_through);
+}
+
+/// Returns whether `stmt` is a terminating statement as the Go
+/// specification defines it: a `return`, a block that ends in a
+/// terminating statement, an `if` with an `else` whose branches
+/// both terminate, a `for` without a condition nor a `break` out
+/// of it, or a switch with a default clause, no `break` out of it
+/// and clauses that all terminate.  Unlike `falls_through`, it
+/// does not consider `break` and `continue` statements, as the
+/// weeder requires of the bodies of functions with a result.
+pub fn is_terminating(stmt: &Stmt) -> bool {
+    match stmt.kind {
+        StmtKind::Return(_) => return true,
+        StmtKind::Block(ref block) => return ends_in_terminating(&block.stmts),
+        StmtKind::If(ref if_stmt) => match if_stmt.els {
+            Some(ref els) => return ends_in_terminating(&if_stmt.then.stmts) && is_terminating(els),
+            None => return false,
+        },
+        StmtKind::For(ref for_stmt) => return for_stmt.cond.is_none() && !breaks(&for_stmt.body.stmts),
+        StmtKind::Switch(ref switch_stmt) => {
+            let clauses = &switch_stmt.clauses;
+            return clauses.iter().any(|clause| clause.exprs.is_none())
+                && clauses.iter().all(|clause| ends_in_terminating(&clause.body) && !breaks(&clause.body));
+        }
+        _ => return false,
+    }
+}
+
+pub fn ends_in_terminating(stmts: &[Stmt]) -> bool {
+    return stmts.last().is_some_and(is_terminating);
+}
+
+/// Whether `stmts` contain a `break` out of the statement they
+/// are the body of, rather than out of a nested loop or switch.
+fn breaks(stmts: &[Stmt]) -> bool {
+    return stmts.iter().any(|stmt| match stmt.kind {
+        StmtKind::Break => true,
+        StmtKind::Block(ref block) => breaks(&block.stmts),
+        StmtKind::If(ref if_stmt) => {
+            breaks(&if_stmt.then.stmts) || if_stmt.els.as_ref().is_some_and(|els| breaks(slice::from_ref(&**els)))
+        }
+        _ => false,
+    });
+}
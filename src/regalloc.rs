@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use ast::*;
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// The registers that hold variables: the integer registers that
+/// functions preserve, and those that calls clobber but that the
+/// x86-64 generator never uses as scratch registers.  No SSE
+/// register is preserved by calls.
+pub const INT_CALLEE_SAVED: [&str; 5] = ["%rbx", "%r12", "%r13", "%r14", "%r15"];
+pub const INT_CALLER_SAVED: [&str; 1] = ["%r10"];
+pub const SSE_CALLER_SAVED: [&str; 8] = ["%xmm8", "%xmm9", "%xmm10", "%xmm11", "%xmm12", "%xmm13", "%xmm14", "%xmm15"];
+
+/// The positions where a local variable of a function may be
+/// live, in the order in which the generator emits the code of
+/// the function, and the register that holds it, if any.
+#[derive(Debug, Clone)]
+pub struct Interval {
+    pub var: Ident,
+    /// Whether the variable is a `float64`, which lives in an
+    /// SSE register.
+    pub float: bool,
+    pub start: usize,
+    pub end: usize,
+    /// Whether a call happens while the variable is live, so
+    /// that only a register preserved by calls may hold it.
+    pub across_calls: bool,
+    pub reg: Option<&'static str>,
+}
+
+/// The intervals of the variables of a function, in the order of
+/// their declarations.
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    pub func: String,
+    pub intervals: Vec<Interval>,
+}
+
+impl Allocation {
+    /// Returns the registers that hold the variables.
+    pub fn registers(&self) -> HashMap<SymbolId, &'static str> {
+        return self.intervals.iter()
+            .filter_map(|i| match (i.var.sym, i.reg) {
+                (Some(sym), Some(reg)) => Some((sym, reg)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Returns the registers that the function must preserve for
+    /// its caller, in a fixed order.
+    pub fn callee_saved(&self) -> Vec<&'static str> {
+        return INT_CALLEE_SAVED.iter().cloned()
+            .filter(|&reg| self.intervals.iter().any(|i| i.reg == Some(reg)))
+            .collect();
+    }
+}
+
+/// Allocates registers to the local variables of every function
+/// of a program.
+pub fn allocate_program(prog: &Program, types: &TypeTable, table: &SymbolTable) -> Vec<Allocation> {
+    return prog.decls.iter()
+        .filter_map(|decl| match *decl {
+            TopLevelDecl::Func(ref f) if !f.name.is_blank() => Some(allocate(f, types, table)),
+            _ => None,
+        })
+        .collect();
+}
+
+/// Allocates registers to the scalar variables and parameters of
+/// a function, which are never addressed, with the linear scan of
+/// Poletto and Sarkar.
+///
+/// The code of a function is numbered in the order that the
+/// generator emits it, and the live interval of a variable runs
+/// from its declaration to its last use or assignment.  Branches
+/// only jump forward but for the loops, so the interval of a
+/// variable that is declared before a loop and used in it is
+/// extended to the end of the loop.  The intervals are then
+/// visited by increasing start, and each takes a free register of
+/// its class, if there is one; one that spans a call must take a
+/// register that calls preserve.  Otherwise, of the interval and
+/// the active ones whose register it could take, the one that
+/// ends last is spilled and keeps its slot in the frame.
+pub fn allocate(f: &FuncDecl, types: &TypeTable, table: &SymbolTable) -> Allocation {
+    let mut builder = Builder {
+        types: types,
+        table: table,
+        pos: 0,
+        index: HashMap::new(),
+        intervals: Vec::new(),
+        refs: Vec::new(),
+        calls: Vec::new(),
+        loops: Vec::new(),
+    };
+    for p in &f.params {
+        builder.declare(&p.name);
+    }
+    builder.stmts(&f.body.stmts);
+
+    let mut intervals = builder.intervals;
+    for &(start, end) in &builder.loops {
+        for (interval, refs) in intervals.iter_mut().zip(&builder.refs) {
+            if interval.start < start && refs.iter().any(|&p| p >= start && p <= end) {
+                interval.end = interval.end.max(end);
+            }
+        }
+    }
+    for interval in &mut intervals {
+        interval.across_calls = builder.calls.iter().any(|&c| interval.start < c && c < interval.end);
+    }
+    scan(&mut intervals);
+    return Allocation { func: format!("main.{}", f.name.name), intervals: intervals };
+}
+
+/// Returns the intervals and the registers of every function,
+/// for `--dump-regalloc`.
+pub fn dump(allocations: &[Allocation]) -> String {
+    let mut out = String::new();
+    for alloc in allocations {
+        out.push_str(&format!("{}:\n", alloc.func));
+        for i in &alloc.intervals {
+            let reg = i.reg.unwrap_or("spilled");
+            let calls = if i.across_calls { " across calls" } else { "" };
+            out.push_str(&format!("  {} {}:{} [{}, {}]{}: {}\n", i.var.name, i.var.loc.line, i.var.loc.col, i.start, i.end, calls, reg));
+        }
+    }
+    return out;
+}
+
+/// Returns the registers that an interval may take, in order of
+/// preference: those that calls clobber come first, since the
+/// others must be saved.
+fn candidates(interval: &Interval) -> Vec<&'static str> {
+    match (interval.float, interval.across_calls) {
+        (true, true) => return Vec::new(),
+        (true, false) => return SSE_CALLER_SAVED.to_vec(),
+        (false, true) => return INT_CALLEE_SAVED.to_vec(),
+        (false, false) => return INT_CALLER_SAVED.iter().chain(&INT_CALLEE_SAVED).cloned().collect(),
+    }
+}
+
+fn scan(intervals: &mut [Interval]) {
+    let mut order: Vec<usize> = (0 .. intervals.len()).collect();
+    order.sort_by_key(|&i| intervals[i].start);
+    // The intervals that hold a register.
+    let mut active: Vec<usize> = Vec::new();
+    for i in order {
+        let start = intervals[i].start;
+        active.retain(|&a| intervals[a].end >= start);
+        let candidates = candidates(&intervals[i]);
+        let free = candidates.iter().cloned()
+            .find(|&reg| active.iter().all(|&a| intervals[a].reg != Some(reg)));
+        if let Some(reg) = free {
+            intervals[i].reg = Some(reg);
+            active.push(i);
+            continue;
+        }
+        let victim = active.iter().cloned()
+            .filter(|&a| intervals[a].reg.is_some_and(|reg| candidates.contains(&reg)))
+            .max_by_key(|&a| intervals[a].end);
+        if let Some(a) = victim {
+            if intervals[a].end > intervals[i].end {
+                intervals[i].reg = intervals[a].reg.take();
+                active.retain(|&b| b != a);
+                active.push(i);
+            }
+        }
+    }
+}
+
+struct Builder<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    /// The last position.
+    pos: usize,
+    /// The interval of each variable, and the positions where it
+    /// is used or assigned.
+    index: HashMap<SymbolId, usize>,
+    intervals: Vec<Interval>,
+    refs: Vec<Vec<usize>>,
+    /// The positions of the calls, and the first and last
+    /// positions of the loops.
+    calls: Vec<usize>,
+    loops: Vec<(usize, usize)>,
+}
+
+impl<'a> Builder<'a> {
+    fn next(&mut self) -> usize {
+        self.pos += 1;
+        return self.pos;
+    }
+
+    fn call(&mut self) {
+        let pos = self.next();
+        self.calls.push(pos);
+    }
+
+    /// Returns the kind of a type, untyped constants having their
+    /// default type.
+    fn kind(&self, ty: Option<TypeId>) -> &TypeKind {
+        return self.types.under(self.types.default_type(ty.unwrap_or(INVALID)));
+    }
+
+    /// Starts the interval of a variable, if it is a scalar.
+    fn declare(&mut self, id: &Ident) {
+        let sym = match id.sym {
+            Some(sym) if !id.is_blank() => sym,
+            _ => return,
+        };
+        let float = match *self.kind(self.table.symbol(sym).ty) {
+            TypeKind::Float64 => true,
+            TypeKind::Int | TypeKind::Bool | TypeKind::Rune => false,
+            _ => return,
+        };
+        let pos = self.next();
+        self.index.insert(sym, self.intervals.len());
+        self.intervals.push(Interval {
+            var: id.clone(),
+            float: float,
+            start: pos,
+            end: pos,
+            across_calls: false,
+            reg: None,
+        });
+        self.refs.push(vec![pos]);
+    }
+
+    /// Extends the interval of a variable to a use or an
+    /// assignment.
+    fn reference(&mut self, id: &Ident) {
+        if let Some(&i) = id.sym.and_then(|sym| self.index.get(&sym)) {
+            let pos = self.next();
+            self.intervals[i].end = pos;
+            self.refs[i].push(pos);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => self.expr(e),
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.exprs(&spec.values);
+                    for id in &spec.names {
+                        self.declare(id);
+                    }
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error | StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::Assign(ref lhs, ref rhs) => {
+                // A single element is checked once its value is
+                // known, and several before the values are.
+                for e in lhs {
+                    self.target(e);
+                }
+                self.exprs(rhs);
+                for e in lhs {
+                    self.store(e);
+                }
+            }
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => {
+                self.target(lhs);
+                self.store(lhs);
+                self.expr(rhs);
+                if self.binary_calls(op, lhs.ty) {
+                    self.call();
+                }
+                self.store(lhs);
+            }
+            StmtKind::Incr(ref e) | StmtKind::Decr(ref e) => {
+                self.target(e);
+                self.store(e);
+                self.store(e);
+            }
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                self.exprs(values);
+                for id in names {
+                    let new = id.sym.is_some_and(|sym| self.table.symbol(sym).loc.as_ref() == Some(&id.loc));
+                    if new {
+                        self.declare(id);
+                    } else {
+                        self.reference(id);
+                    }
+                }
+            }
+            StmtKind::Print(ref args) | StmtKind::Println(ref args) => {
+                self.exprs(args);
+                self.call();
+            }
+            StmtKind::Return(ref e) => {
+                if let Some(ref e) = *e {
+                    self.expr(e);
+                }
+            }
+            StmtKind::If(ref if_stmt) => {
+                self.init(&if_stmt.init);
+                self.expr(&if_stmt.cond);
+                self.stmts(&if_stmt.then.stmts);
+                if let Some(ref els) = if_stmt.els {
+                    self.stmt(els);
+                }
+            }
+            // The cases are all tested before the bodies.
+            StmtKind::Switch(ref switch_stmt) => {
+                self.init(&switch_stmt.init);
+                let tag_ty = switch_stmt.tag.as_ref().map(|tag| tag.ty);
+                if let Some(ref tag) = switch_stmt.tag {
+                    self.expr(tag);
+                }
+                for case in &switch_stmt.clauses {
+                    for e in case.exprs.iter().flatten() {
+                        self.expr(e);
+                        if tag_ty.is_some_and(|ty| self.binary_calls(BinOp::Eq, ty)) {
+                            self.call();
+                        }
+                    }
+                }
+                for case in &switch_stmt.clauses {
+                    self.stmts(&case.body);
+                }
+            }
+            StmtKind::For(ref for_stmt) => {
+                self.init(&for_stmt.init);
+                let start = self.pos + 1;
+                if let Some(ref cond) = for_stmt.cond {
+                    self.expr(cond);
+                }
+                self.stmts(&for_stmt.body.stmts);
+                self.init(&for_stmt.post);
+                self.loops.push((start, self.pos));
+            }
+        }
+    }
+
+    fn init(&mut self, init: &Option<Box<Stmt>>) {
+        if let Some(ref init) = *init {
+            self.stmt(init);
+        }
+    }
+
+    /// Visits the operands of the address of a variable, element
+    /// or field that is assigned.
+    fn target(&mut self, e: &Expr) {
+        match e.kind {
+            ExprKind::Id(_) | ExprKind::Blank => {}
+            ExprKind::Selector(ref x, _) => self.target(x),
+            _ => self.expr(e),
+        }
+    }
+
+    /// Visits the store of a value in a variable, element or
+    /// field.
+    fn store(&mut self, e: &Expr) {
+        match e.kind {
+            ExprKind::Id(ref id) => self.reference(id),
+            ExprKind::Index(_, _) => self.call(),
+            _ => {}
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for e in exprs {
+            self.expr(e);
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        if e.value.is_some() {
+            return;
+        }
+        match e.kind {
+            ExprKind::Id(ref id) => self.reference(id),
+            ExprKind::Unary(_, ref x) | ExprKind::Selector(ref x, _) => self.expr(x),
+            ExprKind::Binary(op, ref x, ref y) => {
+                self.expr(x);
+                self.expr(y);
+                if self.binary_calls(op, x.ty) {
+                    self.call();
+                }
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                self.exprs(args);
+                match kind {
+                    SymbolKind::Type => {
+                        if self.conversion_calls(e.ty, args[0].ty) {
+                            self.call();
+                        }
+                    }
+                    SymbolKind::Builtin => {}
+                    _ => self.call(),
+                }
+            }
+            // Every index is checked by the runtime.
+            ExprKind::Index(ref a, ref i) | ExprKind::Append(ref a, ref i) => {
+                self.expr(a);
+                self.expr(i);
+                self.call();
+            }
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => {}
+        }
+    }
+
+    /// Whether a binary operator on operands of type `ty` calls
+    /// the runtime: those on strings, comparisons of arrays and
+    /// structs, and the integer operators that may panic.
+    fn binary_calls(&self, op: BinOp, ty: Option<TypeId>) -> bool {
+        match *self.kind(ty) {
+            TypeKind::String => return true,
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => return op.is_comparison(),
+            TypeKind::Int | TypeKind::Rune => {
+                return op == BinOp::Div || op == BinOp::Rem || op == BinOp::Shl || op == BinOp::Shr;
+            }
+            _ => return false,
+        }
+    }
+
+    /// Whether a conversion calls the runtime: those to strings,
+    /// and those of floats to integers.
+    fn conversion_calls(&self, ty: Option<TypeId>, arg_ty: Option<TypeId>) -> bool {
+        let (to, from) = (self.kind(ty), self.kind(arg_ty));
+        match (to, from) {
+            (&TypeKind::String, _) => return true,
+            (&TypeKind::Float64, _) | (_, &TypeKind::String) => return false,
+            (_, &TypeKind::Float64) => return true,
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,253 @@
+/* Runtime support for the Jasmin assembly generated by gore,
+ * which is compiled with the classes of the program:
+ *
+ *     gore codegen --target=jvm foo.go -o out
+ *     jasmin -d out out/*.j
+ *     javac -d out out/GoreRuntime.java
+ *     java -cp out foo
+ *
+ * Strings are byte arrays, null being the empty string.  An
+ * instance of this class is a slice: its backing array, which is
+ * a long[], double[], int[] (runes), boolean[] or Object[], and
+ * its length and capacity; slices are never modified, so that
+ * they can be shared, and null is the nil slice.  Runtime errors
+ * print a message in the format of Go's panics, with the location
+ * of the failing operation, and exit with status 2.
+ */
+import java.io.BufferedOutputStream;
+import java.io.FileDescriptor;
+import java.io.FileOutputStream;
+import java.io.PrintStream;
+import java.nio.charset.StandardCharsets;
+import java.util.Arrays;
+import java.util.Locale;
+
+public final class GoreRuntime {
+    public final Object data;
+    public final int len;
+    public final int cap;
+
+    private GoreRuntime(Object data, int len, int cap) {
+        this.data = data;
+        this.len = len;
+        this.cap = cap;
+    }
+
+    private static final PrintStream out =
+        new PrintStream(new BufferedOutputStream(new FileOutputStream(FileDescriptor.out), 1 << 16), false);
+
+    public static void panic(String msg, String loc) {
+        out.flush();
+        System.err.print("panic: runtime error: " + msg + "\n\tat " + loc + "\n");
+        System.err.flush();
+        System.exit(2);
+    }
+
+    /* Called at the end of the program. */
+    public static void exit() {
+        out.flush();
+    }
+
+
+    /* ARITHMETIC
+     * Integer operations wrap around in Java as in Go; only the
+     * division by zero and the shifts need to be checked. */
+
+    public static long div(long a, long b, String loc) {
+        if (b == 0)
+            panic("integer divide by zero", loc);
+        return a / b;
+    }
+
+    public static long rem(long a, long b, String loc) {
+        if (b == 0)
+            panic("integer divide by zero", loc);
+        return a % b;
+    }
+
+    public static int div(int a, int b, String loc) {
+        if (b == 0)
+            panic("integer divide by zero", loc);
+        return a / b;
+    }
+
+    public static int rem(int a, int b, String loc) {
+        if (b == 0)
+            panic("integer divide by zero", loc);
+        return a % b;
+    }
+
+    /* Shifts by the width of the operand or more are defined in Go. */
+
+    public static long shl(long x, long n, String loc) {
+        if (n < 0)
+            panic("negative shift amount", loc);
+        return n >= 64 ? 0 : x << n;
+    }
+
+    public static long shr(long x, long n, String loc) {
+        if (n < 0)
+            panic("negative shift amount", loc);
+        return x >> Math.min(n, 63);
+    }
+
+    public static int shl(int x, long n, String loc) {
+        if (n < 0)
+            panic("negative shift amount", loc);
+        return n >= 32 ? 0 : x << n;
+    }
+
+    public static int shr(int x, long n, String loc) {
+        if (n < 0)
+            panic("negative shift amount", loc);
+        return x >> Math.min(n, 31);
+    }
+
+
+    /* STRINGS */
+
+    /* Returns the bytes of a literal, whose characters are bytes. */
+    public static byte[] bytes(String s) {
+        return s.getBytes(StandardCharsets.ISO_8859_1);
+    }
+
+    public static long len(byte[] s) {
+        return s == null ? 0 : s.length;
+    }
+
+    public static boolean equal(byte[] a, byte[] b) {
+        return compare(a, b) == 0;
+    }
+
+    public static int compare(byte[] a, byte[] b) {
+        return Arrays.compareUnsigned(a == null ? new byte[0] : a, b == null ? new byte[0] : b);
+    }
+
+    public static byte[] concat(byte[] a, byte[] b) {
+        if (len(a) == 0)
+            return b;
+        if (len(b) == 0)
+            return a;
+        byte[] s = Arrays.copyOf(a, a.length + b.length);
+        System.arraycopy(b, 0, s, a.length, b.length);
+        return s;
+    }
+
+    /* Converts a code point to its UTF-8 encoding; invalid code
+     * points become U+FFFD, as in Go. */
+    public static byte[] fromRune(long r) {
+        if (r < 0 || r > 0x10ffff || (r >= 0xd800 && r <= 0xdfff))
+            r = 0xfffd;
+        return new String(Character.toChars((int) r)).getBytes(StandardCharsets.UTF_8);
+    }
+
+
+    /* SLICES */
+
+    /* Returns the index of an element after checking it. */
+    public static int index(long i, long len, String loc) {
+        if (i < 0 || i >= len)
+            panic("index out of range [" + i + "] with length " + len, loc);
+        return (int) i;
+    }
+
+    public static int index(GoreRuntime s, long i, String loc) {
+        return index(i, len(s), loc);
+    }
+
+    public static long len(GoreRuntime s) {
+        return s == null ? 0 : s.len;
+    }
+
+    public static long cap(GoreRuntime s) {
+        return s == null ? 0 : s.cap;
+    }
+
+    /* Returns the slice with room for one more element, copying
+     * the elements to a new array twice as large if `s` is full;
+     * `empty` is an empty array of the type of the elements. */
+    private static GoreRuntime grow(GoreRuntime s, Object empty) {
+        if (s == null)
+            s = new GoreRuntime(empty, 0, 0);
+        Object data = s.data;
+        int cap = s.cap;
+        if (s.len == cap) {
+            cap = cap == 0 ? 1 : 2 * cap;
+            data = java.lang.reflect.Array.newInstance(empty.getClass().getComponentType(), cap);
+            System.arraycopy(s.data, 0, data, 0, s.len);
+        }
+        return new GoreRuntime(data, s.len + 1, cap);
+    }
+
+    public static GoreRuntime append(GoreRuntime s, long x) {
+        s = grow(s, new long[0]);
+        ((long[]) s.data)[s.len - 1] = x;
+        return s;
+    }
+
+    public static GoreRuntime append(GoreRuntime s, double x) {
+        s = grow(s, new double[0]);
+        ((double[]) s.data)[s.len - 1] = x;
+        return s;
+    }
+
+    public static GoreRuntime append(GoreRuntime s, int x) {
+        s = grow(s, new int[0]);
+        ((int[]) s.data)[s.len - 1] = x;
+        return s;
+    }
+
+    public static GoreRuntime append(GoreRuntime s, boolean x) {
+        s = grow(s, new boolean[0]);
+        ((boolean[]) s.data)[s.len - 1] = x;
+        return s;
+    }
+
+    public static GoreRuntime append(GoreRuntime s, Object x) {
+        s = grow(s, new Object[0]);
+        ((Object[]) s.data)[s.len - 1] = x;
+        return s;
+    }
+
+
+    /* PRINTING
+     * `print` and `println` format their arguments as Go's built-in
+     * functions do; in particular, floats are printed with a sign, 7
+     * significant digits and a three-digit exponent. */
+
+    public static void print(long x) {
+        out.print(x);
+    }
+
+    public static void print(double x) {
+        if (Double.isNaN(x)) {
+            out.print("NaN");
+            return;
+        }
+        if (Double.isInfinite(x)) {
+            out.print(x > 0 ? "+Inf" : "-Inf");
+            return;
+        }
+        String s = String.format(Locale.ROOT, "%+.6e", x);
+        int e = s.indexOf('e');
+        int exp = Integer.parseInt(s.substring(e + 2));
+        out.print(s.substring(0, e + 2) + String.format(Locale.ROOT, "%03d", exp));
+    }
+
+    public static void print(boolean x) {
+        out.print(x);
+    }
+
+    public static void print(byte[] x) {
+        if (x != null)
+            out.write(x, 0, x.length);
+    }
+
+    public static void printSpace() {
+        out.write(' ');
+    }
+
+    public static void println() {
+        out.write('\n');
+    }
+}
@@ -0,0 +1,54 @@
+// Runs a WebAssembly module generated by gore under Node.js,
+// after it has been assembled, e.g. with wabt:
+//
+//     gore codegen --target=wasm foo.go -o foo.wat
+//     wat2wasm foo.wat -o foo.wasm
+//     node gore_host.js foo.wasm
+//
+// It provides the functions that the module imports from "gore",
+// which a browser would implement in the same way, with its own
+// output.
+'use strict';
+
+const fs = require('fs');
+
+// Formats a float64 as Go's print does: with a sign, 7
+// significant digits and a three-digit exponent.
+function formatFloat(x) {
+    if (Number.isNaN(x)) {
+        return 'NaN';
+    }
+    if (!Number.isFinite(x)) {
+        return x > 0 ? '+Inf' : '-Inf';
+    }
+    const negative = x < 0 || Object.is(x, -0);
+    const [digits, exp] = Math.abs(x).toExponential(6).split('e');
+    const e = Number(exp);
+    return (negative ? '-' : '+') + digits + 'e' + (e < 0 ? '-' : '+') + String(Math.abs(e)).padStart(3, '0');
+}
+
+function main() {
+    const bytes = fs.readFileSync(process.argv[2]);
+    let memory = null;
+    const text = (p, n) => Buffer.from(memory.buffer, p, n);
+    const imports = {
+        gore: {
+            print_int: (x) => fs.writeSync(1, x.toString()),
+            print_float: (x) => fs.writeSync(1, formatFloat(x)),
+            print_string: (p, n) => fs.writeSync(1, text(p, n)),
+            panic: (p, n, q, m) => {
+                fs.writeSync(2, Buffer.concat([
+                    Buffer.from('panic: runtime error: '), text(p, n),
+                    Buffer.from('\n\tat '), text(q, m), Buffer.from('\n'),
+                ]));
+                process.exit(2);
+            },
+        },
+    };
+    const module = new WebAssembly.Module(bytes);
+    const instance = new WebAssembly.Instance(module, imports);
+    memory = instance.exports.memory;
+    instance.exports.main();
+}
+
+main();
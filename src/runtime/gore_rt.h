@@ -0,0 +1,277 @@
+/* Runtime support for the C code generated by gore.
+ *
+ * Strings are immutable byte sequences; slices share a backing
+ * array that `gore_append` reallocates when it is full.  Memory
+ * is never freed.  Runtime errors print a message in the format
+ * of Go's panics, with the location of the failing operation,
+ * and exit with status 2.
+ */
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <math.h>
+
+typedef struct {
+    const char *data;
+    int64_t len;
+} gore_string;
+
+typedef struct {
+    void *data;
+    int64_t len;
+    int64_t cap;
+} gore_slice;
+
+/* The functions are static in the C code that gore generates;
+ * runtime.c defines GORE_API to give them external linkage. */
+#ifndef GORE_API
+#define GORE_API static inline
+#endif
+
+#define GORE_STR(s, n) ((gore_string){ (s), (n) })
+
+GORE_API void gore_panic(const char *msg, const char *loc)
+{
+    fflush(stdout);
+    fprintf(stderr, "panic: runtime error: %s\n\tat %s\n", msg, loc);
+    exit(2);
+}
+
+GORE_API void *gore_alloc(size_t size)
+{
+    void *p = calloc(size ? size : 1, 1);
+    if (p == NULL) {
+        fflush(stdout);
+        fprintf(stderr, "fatal error: out of memory\n");
+        exit(2);
+    }
+    return p;
+}
+
+
+/* ARITHMETIC
+ * Signed overflow wraps around as in Go, which C does not
+ * guarantee, so additions, subtractions and multiplications
+ * are done on unsigned integers. */
+
+#define GORE_ADD64(a, b) ((int64_t)((uint64_t)(a) + (uint64_t)(b)))
+#define GORE_SUB64(a, b) ((int64_t)((uint64_t)(a) - (uint64_t)(b)))
+#define GORE_MUL64(a, b) ((int64_t)((uint64_t)(a) * (uint64_t)(b)))
+#define GORE_NEG64(a) ((int64_t)(0 - (uint64_t)(a)))
+#define GORE_ADD32(a, b) ((int32_t)((uint32_t)(a) + (uint32_t)(b)))
+#define GORE_SUB32(a, b) ((int32_t)((uint32_t)(a) - (uint32_t)(b)))
+#define GORE_MUL32(a, b) ((int32_t)((uint32_t)(a) * (uint32_t)(b)))
+#define GORE_NEG32(a) ((int32_t)(0 - (uint32_t)(a)))
+
+GORE_API int64_t gore_div64(int64_t a, int64_t b, const char *loc)
+{
+    if (b == 0)
+        gore_panic("integer divide by zero", loc);
+    if (b == -1)
+        return GORE_NEG64(a);
+    return a / b;
+}
+
+GORE_API int64_t gore_rem64(int64_t a, int64_t b, const char *loc)
+{
+    if (b == 0)
+        gore_panic("integer divide by zero", loc);
+    if (b == -1)
+        return 0;
+    return a % b;
+}
+
+GORE_API int32_t gore_div32(int32_t a, int32_t b, const char *loc)
+{
+    if (b == 0)
+        gore_panic("integer divide by zero", loc);
+    if (b == -1)
+        return GORE_NEG32(a);
+    return a / b;
+}
+
+GORE_API int32_t gore_rem32(int32_t a, int32_t b, const char *loc)
+{
+    if (b == 0)
+        gore_panic("integer divide by zero", loc);
+    if (b == -1)
+        return 0;
+    return a % b;
+}
+
+/* Shifts by the width of the operand or more are defined in Go. */
+
+GORE_API int64_t gore_shl64(int64_t x, int64_t n, const char *loc)
+{
+    if (n < 0)
+        gore_panic("negative shift amount", loc);
+    return n >= 64 ? 0 : (int64_t)((uint64_t)x << n);
+}
+
+GORE_API int64_t gore_shr64(int64_t x, int64_t n, const char *loc)
+{
+    if (n < 0)
+        gore_panic("negative shift amount", loc);
+    if (n >= 64)
+        return x < 0 ? -1 : 0;
+    return x < 0 ? ~(~x >> n) : x >> n;
+}
+
+GORE_API int32_t gore_shl32(int32_t x, int64_t n, const char *loc)
+{
+    if (n < 0)
+        gore_panic("negative shift amount", loc);
+    return n >= 32 ? 0 : (int32_t)((uint32_t)x << n);
+}
+
+GORE_API int32_t gore_shr32(int32_t x, int64_t n, const char *loc)
+{
+    if (n < 0)
+        gore_panic("negative shift amount", loc);
+    if (n >= 32)
+        return x < 0 ? -1 : 0;
+    return x < 0 ? ~(~x >> n) : x >> n;
+}
+
+/* Converts a float to an int.  Out of range values saturate and
+ * NaN becomes 0, as in the interpreters. */
+GORE_API int64_t gore_float_to_int(double x)
+{
+    if (isnan(x))
+        return 0;
+    if (x >= 9223372036854775808.0)
+        return INT64_MAX;
+    if (x < -9223372036854775808.0)
+        return INT64_MIN;
+    return (int64_t)x;
+}
+
+
+/* STRINGS */
+
+GORE_API bool gore_string_eq(gore_string a, gore_string b)
+{
+    return a.len == b.len && (a.len == 0 || memcmp(a.data, b.data, (size_t)a.len) == 0);
+}
+
+GORE_API int gore_string_cmp(gore_string a, gore_string b)
+{
+    int64_t n = a.len < b.len ? a.len : b.len;
+    int c = n == 0 ? 0 : memcmp(a.data, b.data, (size_t)n);
+    if (c != 0)
+        return c;
+    return a.len < b.len ? -1 : a.len > b.len;
+}
+
+GORE_API gore_string gore_concat(gore_string a, gore_string b)
+{
+    char *data;
+    if (a.len == 0)
+        return b;
+    if (b.len == 0)
+        return a;
+    data = gore_alloc((size_t)(a.len + b.len));
+    memcpy(data, a.data, (size_t)a.len);
+    memcpy(data + a.len, b.data, (size_t)b.len);
+    return GORE_STR(data, a.len + b.len);
+}
+
+/* Converts a code point to its UTF-8 encoding; invalid code
+ * points become U+FFFD, as in Go. */
+GORE_API gore_string gore_string_from_rune(int64_t r)
+{
+    char *s = gore_alloc(4);
+    if (r < 0 || r > 0x10ffff || (r >= 0xd800 && r <= 0xdfff))
+        r = 0xfffd;
+    if (r < 0x80) {
+        s[0] = (char)r;
+        return GORE_STR(s, 1);
+    }
+    if (r < 0x800) {
+        s[0] = (char)(0xc0 | (r >> 6));
+        s[1] = (char)(0x80 | (r & 0x3f));
+        return GORE_STR(s, 2);
+    }
+    if (r < 0x10000) {
+        s[0] = (char)(0xe0 | (r >> 12));
+        s[1] = (char)(0x80 | ((r >> 6) & 0x3f));
+        s[2] = (char)(0x80 | (r & 0x3f));
+        return GORE_STR(s, 3);
+    }
+    s[0] = (char)(0xf0 | (r >> 18));
+    s[1] = (char)(0x80 | ((r >> 12) & 0x3f));
+    s[2] = (char)(0x80 | ((r >> 6) & 0x3f));
+    s[3] = (char)(0x80 | (r & 0x3f));
+    return GORE_STR(s, 4);
+}
+
+
+/* SLICES */
+
+GORE_API int64_t gore_index(int64_t i, int64_t len, const char *loc)
+{
+    if (i < 0 || i >= len) {
+        char msg[80];
+        sprintf(msg, "index out of range [%lld] with length %lld", (long long)i, (long long)len);
+        gore_panic(msg, loc);
+    }
+    return i;
+}
+
+/* Appends the element of `size` bytes at `elem` to `s`, copying
+ * the elements to a new array twice as large if `s` is full. */
+GORE_API gore_slice gore_append(gore_slice s, const void *elem, size_t size)
+{
+    if (s.len == s.cap) {
+        int64_t cap = s.cap == 0 ? 1 : 2 * s.cap;
+        void *data = gore_alloc((size_t)cap * size);
+        if (s.len > 0)
+            memcpy(data, s.data, (size_t)s.len * size);
+        s.data = data;
+        s.cap = cap;
+    }
+    memcpy((char *)s.data + (size_t)s.len * size, elem, size);
+    s.len++;
+    return s;
+}
+
+
+/* PRINTING
+ * `print` and `println` format their arguments as Go's built-in
+ * functions do; in particular, floats are printed with a sign, 7
+ * significant digits and a three-digit exponent. */
+
+GORE_API void gore_print_int(int64_t x)
+{
+    printf("%lld", (long long)x);
+}
+
+GORE_API void gore_print_float(double x)
+{
+    char buf[32];
+    char *e;
+    if (isnan(x)) {
+        fputs("NaN", stdout);
+        return;
+    }
+    if (isinf(x)) {
+        fputs(x > 0 ? "+Inf" : "-Inf", stdout);
+        return;
+    }
+    sprintf(buf, "%+.6e", x);
+    e = strchr(buf, 'e');
+    *e = '\0';
+    printf("%se%c%03d", buf, e[1], abs(atoi(e + 2)));
+}
+
+GORE_API void gore_print_bool(bool x)
+{
+    fputs(x ? "true" : "false", stdout);
+}
+
+GORE_API void gore_print_string(gore_string x)
+{
+    fwrite(x.data, 1, (size_t)x.len, stdout);
+}
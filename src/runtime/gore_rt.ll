@@ -0,0 +1,449 @@
+; Runtime support for the LLVM IR generated by gore.
+;
+; This is the LLVM counterpart of gore_rt.h, with the same
+; functions: strings and slices are the structs below, memory
+; is never freed, and runtime errors print a message in the
+; format of Go's panics and exit with status 2.  Only the C
+; library is needed, so that the program can be run by `lli` or
+; compiled by `llc` and linked with the C compiler.
+
+%gore.string = type { ptr, i64 }
+%gore.slice = type { ptr, i64, i64 }
+
+declare ptr @calloc(i64, i64)
+declare ptr @memcpy(ptr, ptr, i64)
+declare i32 @memcmp(ptr, ptr, i64)
+declare i32 @printf(ptr, ...)
+declare i32 @snprintf(ptr, i64, ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare i32 @putchar(i32)
+declare i32 @fflush(ptr)
+declare i32 @atoi(ptr)
+declare void @exit(i32) noreturn
+declare i64 @llvm.fptosi.sat.i64.f64(double)
+
+@.gore.panic = private unnamed_addr constant [33 x i8] c"panic: runtime error: %s\0A\09at %s\0A\00"
+@.gore.oom = private unnamed_addr constant [28 x i8] c"fatal error: out of memory\0A\00"
+@.gore.divide = private unnamed_addr constant [23 x i8] c"integer divide by zero\00"
+@.gore.shift = private unnamed_addr constant [22 x i8] c"negative shift amount\00"
+@.gore.index = private unnamed_addr constant [43 x i8] c"index out of range [%lld] with length %lld\00"
+@.gore.int = private unnamed_addr constant [5 x i8] c"%lld\00"
+@.gore.exp = private unnamed_addr constant [6 x i8] c"%+.6e\00"
+@.gore.float = private unnamed_addr constant [12 x i8] c"%.9se%c%03d\00"
+@.gore.nan = private unnamed_addr constant [4 x i8] c"NaN\00"
+@.gore.inf = private unnamed_addr constant [5 x i8] c"+Inf\00"
+@.gore.neginf = private unnamed_addr constant [5 x i8] c"-Inf\00"
+@.gore.true = private unnamed_addr constant [5 x i8] c"true\00"
+@.gore.false = private unnamed_addr constant [6 x i8] c"false\00"
+
+define internal void @gore_panic(ptr %msg, ptr %loc) noreturn {
+  %_1 = call i32 @fflush(ptr null)
+  %_2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.gore.panic, ptr %msg, ptr %loc)
+  call void @exit(i32 2)
+  unreachable
+}
+
+define internal ptr @gore_alloc(i64 %size) {
+entry:
+  %empty = icmp eq i64 %size, 0
+  %n = select i1 %empty, i64 1, i64 %size
+  %p = call ptr @calloc(i64 %n, i64 1)
+  %failed = icmp eq ptr %p, null
+  br i1 %failed, label %fail, label %done
+fail:
+  %_1 = call i32 @fflush(ptr null)
+  %_2 = call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.gore.oom)
+  call void @exit(i32 2)
+  unreachable
+done:
+  ret ptr %p
+}
+
+
+; ARITHMETIC
+; Additions, subtractions and multiplications are instructions,
+; which wrap around as in Go.
+
+define internal i64 @gore_div64(i64 %a, i64 %b, ptr %loc) {
+entry:
+  %zero = icmp eq i64 %b, 0
+  br i1 %zero, label %panic, label %nonzero
+panic:
+  call void @gore_panic(ptr @.gore.divide, ptr %loc)
+  unreachable
+nonzero:
+  %minus = icmp eq i64 %b, -1
+  br i1 %minus, label %negate, label %divide
+negate:
+  %neg = sub i64 0, %a
+  ret i64 %neg
+divide:
+  %q = sdiv i64 %a, %b
+  ret i64 %q
+}
+
+define internal i64 @gore_rem64(i64 %a, i64 %b, ptr %loc) {
+entry:
+  %zero = icmp eq i64 %b, 0
+  br i1 %zero, label %panic, label %nonzero
+panic:
+  call void @gore_panic(ptr @.gore.divide, ptr %loc)
+  unreachable
+nonzero:
+  %minus = icmp eq i64 %b, -1
+  br i1 %minus, label %one, label %divide
+one:
+  ret i64 0
+divide:
+  %r = srem i64 %a, %b
+  ret i64 %r
+}
+
+define internal i32 @gore_div32(i32 %a, i32 %b, ptr %loc) {
+entry:
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %panic, label %nonzero
+panic:
+  call void @gore_panic(ptr @.gore.divide, ptr %loc)
+  unreachable
+nonzero:
+  %minus = icmp eq i32 %b, -1
+  br i1 %minus, label %negate, label %divide
+negate:
+  %neg = sub i32 0, %a
+  ret i32 %neg
+divide:
+  %q = sdiv i32 %a, %b
+  ret i32 %q
+}
+
+define internal i32 @gore_rem32(i32 %a, i32 %b, ptr %loc) {
+entry:
+  %zero = icmp eq i32 %b, 0
+  br i1 %zero, label %panic, label %nonzero
+panic:
+  call void @gore_panic(ptr @.gore.divide, ptr %loc)
+  unreachable
+nonzero:
+  %minus = icmp eq i32 %b, -1
+  br i1 %minus, label %one, label %divide
+one:
+  ret i32 0
+divide:
+  %r = srem i32 %a, %b
+  ret i32 %r
+}
+
+; Shifts by the width of the operand or more are defined in Go.
+
+define internal i64 @gore_shl64(i64 %x, i64 %n, ptr %loc) {
+entry:
+  %negative = icmp slt i64 %n, 0
+  br i1 %negative, label %panic, label %shift
+panic:
+  call void @gore_panic(ptr @.gore.shift, ptr %loc)
+  unreachable
+shift:
+  %wide = icmp sge i64 %n, 64
+  %s = shl i64 %x, %n
+  %r = select i1 %wide, i64 0, i64 %s
+  ret i64 %r
+}
+
+define internal i64 @gore_shr64(i64 %x, i64 %n, ptr %loc) {
+entry:
+  %negative = icmp slt i64 %n, 0
+  br i1 %negative, label %panic, label %shift
+panic:
+  call void @gore_panic(ptr @.gore.shift, ptr %loc)
+  unreachable
+shift:
+  %wide = icmp sge i64 %n, 64
+  %m = select i1 %wide, i64 63, i64 %n
+  %r = ashr i64 %x, %m
+  ret i64 %r
+}
+
+define internal i32 @gore_shl32(i32 %x, i64 %n, ptr %loc) {
+entry:
+  %negative = icmp slt i64 %n, 0
+  br i1 %negative, label %panic, label %shift
+panic:
+  call void @gore_panic(ptr @.gore.shift, ptr %loc)
+  unreachable
+shift:
+  %wide = icmp sge i64 %n, 32
+  %m = trunc i64 %n to i32
+  %s = shl i32 %x, %m
+  %r = select i1 %wide, i32 0, i32 %s
+  ret i32 %r
+}
+
+define internal i32 @gore_shr32(i32 %x, i64 %n, ptr %loc) {
+entry:
+  %negative = icmp slt i64 %n, 0
+  br i1 %negative, label %panic, label %shift
+panic:
+  call void @gore_panic(ptr @.gore.shift, ptr %loc)
+  unreachable
+shift:
+  %wide = icmp sge i64 %n, 32
+  %m = trunc i64 %n to i32
+  %c = select i1 %wide, i32 31, i32 %m
+  %r = ashr i32 %x, %c
+  ret i32 %r
+}
+
+
+; STRINGS
+
+define internal i1 @gore_string_eq(%gore.string %a, %gore.string %b) {
+entry:
+  %alen = extractvalue %gore.string %a, 1
+  %blen = extractvalue %gore.string %b, 1
+  %same = icmp eq i64 %alen, %blen
+  br i1 %same, label %compare, label %done
+compare:
+  %adata = extractvalue %gore.string %a, 0
+  %bdata = extractvalue %gore.string %b, 0
+  %c = call i32 @memcmp(ptr %adata, ptr %bdata, i64 %alen)
+  %eq = icmp eq i32 %c, 0
+  br label %done
+done:
+  %r = phi i1 [ false, %entry ], [ %eq, %compare ]
+  ret i1 %r
+}
+
+define internal i32 @gore_string_cmp(%gore.string %a, %gore.string %b) {
+entry:
+  %alen = extractvalue %gore.string %a, 1
+  %blen = extractvalue %gore.string %b, 1
+  %adata = extractvalue %gore.string %a, 0
+  %bdata = extractvalue %gore.string %b, 0
+  %shorter = icmp slt i64 %alen, %blen
+  %n = select i1 %shorter, i64 %alen, i64 %blen
+  %c = call i32 @memcmp(ptr %adata, ptr %bdata, i64 %n)
+  %differ = icmp ne i32 %c, 0
+  br i1 %differ, label %done, label %lengths
+lengths:
+  %longer = icmp sgt i64 %alen, %blen
+  %gt = zext i1 %longer to i32
+  %lt = zext i1 %shorter to i32
+  %l = sub i32 %gt, %lt
+  br label %done
+done:
+  %r = phi i32 [ %c, %entry ], [ %l, %lengths ]
+  ret i32 %r
+}
+
+define internal %gore.string @gore_concat(%gore.string %a, %gore.string %b) {
+entry:
+  %alen = extractvalue %gore.string %a, 1
+  %blen = extractvalue %gore.string %b, 1
+  %aempty = icmp eq i64 %alen, 0
+  br i1 %aempty, label %first, label %nonempty
+first:
+  ret %gore.string %b
+nonempty:
+  %bempty = icmp eq i64 %blen, 0
+  br i1 %bempty, label %second, label %copy
+second:
+  ret %gore.string %a
+copy:
+  %len = add i64 %alen, %blen
+  %data = call ptr @gore_alloc(i64 %len)
+  %adata = extractvalue %gore.string %a, 0
+  %bdata = extractvalue %gore.string %b, 0
+  %_1 = call ptr @memcpy(ptr %data, ptr %adata, i64 %alen)
+  %end = getelementptr i8, ptr %data, i64 %alen
+  %_2 = call ptr @memcpy(ptr %end, ptr %bdata, i64 %blen)
+  %s = insertvalue %gore.string undef, ptr %data, 0
+  %r = insertvalue %gore.string %s, i64 %len, 1
+  ret %gore.string %r
+}
+
+; Converts a code point to its UTF-8 encoding; invalid code
+; points become U+FFFD, as in Go.
+define internal %gore.string @gore_string_from_rune(i64 %c) {
+entry:
+  %negative = icmp slt i64 %c, 0
+  %large = icmp sgt i64 %c, 1114111
+  %low = icmp sge i64 %c, 55296
+  %high = icmp sle i64 %c, 57343
+  %surrogate = and i1 %low, %high
+  %bad1 = or i1 %negative, %large
+  %bad = or i1 %bad1, %surrogate
+  %r = select i1 %bad, i64 65533, i64 %c
+  %s = call ptr @gore_alloc(i64 4)
+  %b1 = getelementptr i8, ptr %s, i64 1
+  %b2 = getelementptr i8, ptr %s, i64 2
+  %b3 = getelementptr i8, ptr %s, i64 3
+  %r6 = lshr i64 %r, 6
+  %r12 = lshr i64 %r, 12
+  %r18 = lshr i64 %r, 18
+  %c0 = and i64 %r, 63
+  %c6 = and i64 %r6, 63
+  %c12 = and i64 %r12, 63
+  %t0 = or i64 %c0, 128
+  %t6 = or i64 %c6, 128
+  %t12 = or i64 %c12, 128
+  %cont0 = trunc i64 %t0 to i8
+  %cont6 = trunc i64 %t6 to i8
+  %cont12 = trunc i64 %t12 to i8
+  %one = icmp slt i64 %r, 128
+  br i1 %one, label %len1, label %two
+len1:
+  %a = trunc i64 %r to i8
+  store i8 %a, ptr %s
+  br label %done
+two:
+  %is2 = icmp slt i64 %r, 2048
+  br i1 %is2, label %len2, label %three
+len2:
+  %h2 = or i64 %r6, 192
+  %a2 = trunc i64 %h2 to i8
+  store i8 %a2, ptr %s
+  store i8 %cont0, ptr %b1
+  br label %done
+three:
+  %is3 = icmp slt i64 %r, 65536
+  br i1 %is3, label %len3, label %len4
+len3:
+  %h3 = or i64 %r12, 224
+  %a3 = trunc i64 %h3 to i8
+  store i8 %a3, ptr %s
+  store i8 %cont6, ptr %b1
+  store i8 %cont0, ptr %b2
+  br label %done
+len4:
+  %h4 = or i64 %r18, 240
+  %a4 = trunc i64 %h4 to i8
+  store i8 %a4, ptr %s
+  store i8 %cont12, ptr %b1
+  store i8 %cont6, ptr %b2
+  store i8 %cont0, ptr %b3
+  br label %done
+done:
+  %len = phi i64 [ 1, %len1 ], [ 2, %len2 ], [ 3, %len3 ], [ 4, %len4 ]
+  %str = insertvalue %gore.string undef, ptr %s, 0
+  %result = insertvalue %gore.string %str, i64 %len, 1
+  ret %gore.string %result
+}
+
+
+; SLICES
+
+define internal i64 @gore_index(i64 %i, i64 %len, ptr %loc) {
+entry:
+  %msg = alloca [80 x i8]
+  %out = icmp uge i64 %i, %len
+  br i1 %out, label %panic, label %done
+panic:
+  %_1 = call i32 (ptr, i64, ptr, ...) @snprintf(ptr %msg, i64 80, ptr @.gore.index, i64 %i, i64 %len)
+  call void @gore_panic(ptr %msg, ptr %loc)
+  unreachable
+done:
+  ret i64 %i
+}
+
+; Appends the element of `size` bytes at `elem` to `s`, copying
+; the elements to a new array twice as large if `s` is full.
+define internal %gore.slice @gore_append(%gore.slice %s, ptr %elem, i64 %size) {
+entry:
+  %data = extractvalue %gore.slice %s, 0
+  %len = extractvalue %gore.slice %s, 1
+  %cap = extractvalue %gore.slice %s, 2
+  %full = icmp eq i64 %len, %cap
+  br i1 %full, label %grow, label %store
+grow:
+  %empty = icmp eq i64 %cap, 0
+  %double = mul i64 %cap, 2
+  %newcap = select i1 %empty, i64 1, i64 %double
+  %bytes = mul i64 %newcap, %size
+  %newdata = call ptr @gore_alloc(i64 %bytes)
+  %used = mul i64 %len, %size
+  %_1 = call ptr @memcpy(ptr %newdata, ptr %data, i64 %used)
+  br label %store
+store:
+  %d = phi ptr [ %data, %entry ], [ %newdata, %grow ]
+  %c = phi i64 [ %cap, %entry ], [ %newcap, %grow ]
+  %offset = mul i64 %len, %size
+  %slot = getelementptr i8, ptr %d, i64 %offset
+  %_2 = call ptr @memcpy(ptr %slot, ptr %elem, i64 %size)
+  %newlen = add i64 %len, 1
+  %s1 = insertvalue %gore.slice undef, ptr %d, 0
+  %s2 = insertvalue %gore.slice %s1, i64 %newlen, 1
+  %s3 = insertvalue %gore.slice %s2, i64 %c, 2
+  ret %gore.slice %s3
+}
+
+
+; PRINTING
+; `print` and `println` format their arguments as Go's built-in
+; functions do; in particular, floats are printed with a sign, 7
+; significant digits and a three-digit exponent.
+
+define internal void @gore_print_int(i64 %x) {
+  %_1 = call i32 (ptr, ...) @printf(ptr @.gore.int, i64 %x)
+  ret void
+}
+
+define internal void @gore_print_float(double %x) {
+entry:
+  %buf = alloca [32 x i8]
+  %nan = fcmp uno double %x, %x
+  br i1 %nan, label %printnan, label %number
+printnan:
+  %_1 = call i32 (ptr, ...) @printf(ptr @.gore.nan)
+  ret void
+number:
+  %inf = fcmp oeq double %x, 0x7FF0000000000000
+  br i1 %inf, label %printinf, label %finite
+printinf:
+  %_2 = call i32 (ptr, ...) @printf(ptr @.gore.inf)
+  ret void
+finite:
+  %neginf = fcmp oeq double %x, 0xFFF0000000000000
+  br i1 %neginf, label %printneginf, label %format
+printneginf:
+  %_3 = call i32 (ptr, ...) @printf(ptr @.gore.neginf)
+  ret void
+format:
+  ; "%+.6e" writes the sign, the digits and the point in 9
+  ; characters, then "e", the sign of the exponent and at
+  ; least two digits.
+  %_4 = call i32 (ptr, i64, ptr, ...) @snprintf(ptr %buf, i64 32, ptr @.gore.exp, double %x)
+  %signp = getelementptr i8, ptr %buf, i64 10
+  %sign = load i8, ptr %signp
+  %signc = sext i8 %sign to i32
+  %digits = getelementptr i8, ptr %buf, i64 11
+  %exp = call i32 @atoi(ptr %digits)
+  %_5 = call i32 (ptr, ...) @printf(ptr @.gore.float, ptr %buf, i32 %signc, i32 %exp)
+  ret void
+}
+
+define internal void @gore_print_bool(i1 %x) {
+  %s = select i1 %x, ptr @.gore.true, ptr @.gore.false
+  %_1 = call i32 (ptr, ...) @printf(ptr %s)
+  ret void
+}
+
+define internal void @gore_print_string(%gore.string %x) {
+entry:
+  %data = extractvalue %gore.string %x, 0
+  %len = extractvalue %gore.string %x, 1
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %entry ], [ %next, %body ]
+  %done = icmp eq i64 %i, %len
+  br i1 %done, label %exit, label %body
+body:
+  %p = getelementptr i8, ptr %data, i64 %i
+  %b = load i8, ptr %p
+  %c = zext i8 %b to i32
+  %_1 = call i32 @putchar(i32 %c)
+  %next = add i64 %i, 1
+  br label %loop
+exit:
+  ret void
+}
@@ -0,0 +1,11 @@
+/* Runtime support for the x86-64 assembly generated by gore,
+ * which is linked with the program:
+ *
+ *     gore codegen --target=x86_64 foo.go -o foo.s
+ *     cc foo.s runtime.c
+ *
+ * It exports the functions of gore_rt.h, which the generated
+ * code calls following the System V calling convention.
+ */
+#define GORE_API
+#include "gore_rt.h"
@@ -74,11 +74,11 @@ impl Scanner {
         self.pos += 1;
     }
 
-    /// Consumes `n` bytes of input and returns
-    /// a token of the given type starting positioned
-    /// at the current location.
-    fn tok_nolex(&mut self, ty: TT) -> Token {
-        let t = Token::new(ty, self.loc(), None);
+    /// Returns a token of the given type without a lexeme,
+    /// positioned at `loc`, where the token starts (the
+    /// operator has already been consumed by `looking_at`).
+    fn tok_nolex(&self, ty: TT, loc: &Loc) -> Token {
+        let t = Token::new(ty, loc.clone(), None);
         return t;
     }
 
@@ -116,6 +116,7 @@ impl Scanner {
             Err(err) => { return Err(err); }
         }
 
+        let start_loc = self.loc();
         let tok = {
             // Insert a semi-colon, if necessary, in
             // a file that isn't newline-terminated.
@@ -130,53 +131,53 @@ impl Scanner {
             // Operators and punctuation.
             // OPTIMIZE(vfoley): use nesting to avoid
             // looking at the same character multiple times.
-            else if self.looking_at(b"<<=") { self.tok_nolex(TT::LeftShiftEq) }
-            else if self.looking_at(b">>=") { self.tok_nolex(TT::RightShiftEq) }
-            else if self.looking_at(b":=")  { self.tok_nolex(TT::ColonEq) }
-            else if self.looking_at(b"+=")  { self.tok_nolex(TT::PlusEq) }
-            else if self.looking_at(b"-=")  { self.tok_nolex(TT::MinusEq) }
-            else if self.looking_at(b"*=")  { self.tok_nolex(TT::StarEq) }
-            else if self.looking_at(b"/=")  { self.tok_nolex(TT::SlashEq) }
-            else if self.looking_at(b"%=")  { self.tok_nolex(TT::PercentEq) }
-            else if self.looking_at(b"|=")  { self.tok_nolex(TT::BitorEq) }
-            else if self.looking_at(b"&=")  { self.tok_nolex(TT::BitandEq) }
-            else if self.looking_at(b"++")  { self.tok_nolex(TT::Incr) }
-            else if self.looking_at(b"--")  { self.tok_nolex(TT::Decr) }
-            else if self.looking_at(b"<<")  { self.tok_nolex(TT::LeftShift) }
-            else if self.looking_at(b">>")  { self.tok_nolex(TT::RightShift) }
-            else if self.looking_at(b"&^")  { self.tok_nolex(TT::BitClear) }
-            else if self.looking_at(b"&&")  { self.tok_nolex(TT::And) }
-            else if self.looking_at(b"||")  { self.tok_nolex(TT::Or) }
-            else if self.looking_at(b"==")  { self.tok_nolex(TT::Eq) }
-            else if self.looking_at(b"!=")  { self.tok_nolex(TT::Ne) }
-            else if self.looking_at(b"<=")  { self.tok_nolex(TT::Le) }
-            else if self.looking_at(b">=")  { self.tok_nolex(TT::Ge) }
-            else if self.looking_at(b"=")   { self.tok_nolex(TT::Assign) }
-            else if self.looking_at(b"+")   { self.tok_nolex(TT::Plus) }
-            else if self.looking_at(b"-")   { self.tok_nolex(TT::Minus) }
-            else if self.looking_at(b"*")   { self.tok_nolex(TT::Star) }
-            else if self.looking_at(b"/")   { self.tok_nolex(TT::Slash) }
-            else if self.looking_at(b"%")   { self.tok_nolex(TT::Percent) }
-            else if self.looking_at(b"^")   { self.tok_nolex(TT::Bitnot) }
-            else if self.looking_at(b"&")   { self.tok_nolex(TT::Bitand) }
-            else if self.looking_at(b"|")   { self.tok_nolex(TT::Bitor) }
-            else if self.looking_at(b"!")   { self.tok_nolex(TT::Not) }
-            else if self.looking_at(b"<")   { self.tok_nolex(TT::Lt) }
-            else if self.looking_at(b">")   { self.tok_nolex(TT::Gt) }
-            else if self.looking_at(b"(")   { self.tok_nolex(TT::LParen) }
-            else if self.looking_at(b")")   { self.tok_nolex(TT::RParen) }
-            else if self.looking_at(b"[")   { self.tok_nolex(TT::LBracket) }
-            else if self.looking_at(b"]")   { self.tok_nolex(TT::RBracket) }
-            else if self.looking_at(b"{")   { self.tok_nolex(TT::LBrace) }
-            else if self.looking_at(b"}")   { self.tok_nolex(TT::RBrace) }
-            else if self.looking_at(b",")   { self.tok_nolex(TT::Comma) }
-            else if self.looking_at(b";")   { self.tok_nolex(TT::Semi) }
-            else if self.looking_at(b":")   { self.tok_nolex(TT::Colon) }
+            else if self.looking_at(b"<<=") { self.tok_nolex(TT::LeftShiftEq, &start_loc) }
+            else if self.looking_at(b">>=") { self.tok_nolex(TT::RightShiftEq, &start_loc) }
+            else if self.looking_at(b":=")  { self.tok_nolex(TT::ColonEq, &start_loc) }
+            else if self.looking_at(b"+=")  { self.tok_nolex(TT::PlusEq, &start_loc) }
+            else if self.looking_at(b"-=")  { self.tok_nolex(TT::MinusEq, &start_loc) }
+            else if self.looking_at(b"*=")  { self.tok_nolex(TT::StarEq, &start_loc) }
+            else if self.looking_at(b"/=")  { self.tok_nolex(TT::SlashEq, &start_loc) }
+            else if self.looking_at(b"%=")  { self.tok_nolex(TT::PercentEq, &start_loc) }
+            else if self.looking_at(b"|=")  { self.tok_nolex(TT::BitorEq, &start_loc) }
+            else if self.looking_at(b"&=")  { self.tok_nolex(TT::BitandEq, &start_loc) }
+            else if self.looking_at(b"++")  { self.tok_nolex(TT::Incr, &start_loc) }
+            else if self.looking_at(b"--")  { self.tok_nolex(TT::Decr, &start_loc) }
+            else if self.looking_at(b"<<")  { self.tok_nolex(TT::LeftShift, &start_loc) }
+            else if self.looking_at(b">>")  { self.tok_nolex(TT::RightShift, &start_loc) }
+            else if self.looking_at(b"&^")  { self.tok_nolex(TT::BitClear, &start_loc) }
+            else if self.looking_at(b"&&")  { self.tok_nolex(TT::And, &start_loc) }
+            else if self.looking_at(b"||")  { self.tok_nolex(TT::Or, &start_loc) }
+            else if self.looking_at(b"==")  { self.tok_nolex(TT::Eq, &start_loc) }
+            else if self.looking_at(b"!=")  { self.tok_nolex(TT::Ne, &start_loc) }
+            else if self.looking_at(b"<=")  { self.tok_nolex(TT::Le, &start_loc) }
+            else if self.looking_at(b">=")  { self.tok_nolex(TT::Ge, &start_loc) }
+            else if self.looking_at(b"=")   { self.tok_nolex(TT::Assign, &start_loc) }
+            else if self.looking_at(b"+")   { self.tok_nolex(TT::Plus, &start_loc) }
+            else if self.looking_at(b"-")   { self.tok_nolex(TT::Minus, &start_loc) }
+            else if self.looking_at(b"*")   { self.tok_nolex(TT::Star, &start_loc) }
+            else if self.looking_at(b"/")   { self.tok_nolex(TT::Slash, &start_loc) }
+            else if self.looking_at(b"%")   { self.tok_nolex(TT::Percent, &start_loc) }
+            else if self.looking_at(b"^")   { self.tok_nolex(TT::Bitnot, &start_loc) }
+            else if self.looking_at(b"&")   { self.tok_nolex(TT::Bitand, &start_loc) }
+            else if self.looking_at(b"|")   { self.tok_nolex(TT::Bitor, &start_loc) }
+            else if self.looking_at(b"!")   { self.tok_nolex(TT::Not, &start_loc) }
+            else if self.looking_at(b"<")   { self.tok_nolex(TT::Lt, &start_loc) }
+            else if self.looking_at(b">")   { self.tok_nolex(TT::Gt, &start_loc) }
+            else if self.looking_at(b"(")   { self.tok_nolex(TT::LParen, &start_loc) }
+            else if self.looking_at(b")")   { self.tok_nolex(TT::RParen, &start_loc) }
+            else if self.looking_at(b"[")   { self.tok_nolex(TT::LBracket, &start_loc) }
+            else if self.looking_at(b"]")   { self.tok_nolex(TT::RBracket, &start_loc) }
+            else if self.looking_at(b"{")   { self.tok_nolex(TT::LBrace, &start_loc) }
+            else if self.looking_at(b"}")   { self.tok_nolex(TT::RBrace, &start_loc) }
+            else if self.looking_at(b",")   { self.tok_nolex(TT::Comma, &start_loc) }
+            else if self.looking_at(b";")   { self.tok_nolex(TT::Semi, &start_loc) }
+            else if self.looking_at(b":")   { self.tok_nolex(TT::Colon, &start_loc) }
             // Special case: a float literal can start with a period in Go
             else if self.peek() == b'.' && is_digit(self.peek_next()) {
                 self.number()?
             }
-            else if self.looking_at(b".") { self.tok_nolex(TT::Dot) }
+            else if self.looking_at(b".") { self.tok_nolex(TT::Dot, &start_loc) }
             else if is_alpha(self.peek()) { self.id_or_keyword() }
             else if is_digit(self.peek()) { self.number()? }
             else if self.peek() == b'"'   { self.interpreted_string()?}
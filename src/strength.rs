@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use ast::BinOp;
+use bytecode::Const;
+use ir::{self, BlockId, Function, Inst, Op, Value};
+use loops::{self, Loop};
+
+/// A basic induction variable: a phi node of the header of a loop
+/// that starts at `init` and that every iteration increments by a
+/// constant `step`, to give `next`.
+struct Induction {
+    init: Value,
+    next: Value,
+    step: i64,
+}
+
+/// Reduces the strength of the multiplications of the basic
+/// induction variables of loops by values defined outside the
+/// loops: `i * k` becomes a new induction variable that starts at
+/// `init * k` and is incremented by `step * k`, which the
+/// preheader computes.  Integers wrap around, so the new variable
+/// is always equal to the product.
+pub fn reduce(f: &mut Function) {
+    let loops = loops::insert_preheaders(f);
+    let mut replaced = HashMap::new();
+    for l in &loops {
+        reduce_loop(f, l, &mut replaced);
+    }
+    ir::replace_uses(f, &replaced);
+}
+
+fn reduce_loop(f: &mut Function, l: &Loop, replaced: &mut HashMap<Value, Value>) {
+    let preheader = match l.preheader {
+        Some(p) => p,
+        None => return,
+    };
+    let mut steps = HashMap::new();
+    let mut defs = HashMap::new();
+    let mut in_loop = vec![false; f.types.len()];
+    for (b, block) in f.blocks.iter().enumerate() {
+        for inst in &block.insts {
+            if let Some(v) = inst.result {
+                defs.insert(v, inst.op.clone());
+                in_loop[v] = l.contains(b);
+                if let Op::Const(Const::Int(n)) = inst.op {
+                    steps.insert(v, n);
+                }
+            }
+        }
+    }
+
+    let mut inductions = HashMap::new();
+    for inst in &f.blocks[l.header].insts {
+        let (phi, incoming) = match *inst {
+            Inst { result: Some(v), op: Op::Phi(ref incoming), .. } => (v, incoming),
+            _ => break,
+        };
+        let init = incoming.iter().find(|&&(b, _)| b == preheader).map(|&(_, v)| v);
+        let nexts: Vec<Value> = incoming.iter().filter(|&&(b, _)| b != preheader).map(|&(_, v)| v).collect();
+        let (init, next) = match (init, nexts.first()) {
+            (Some(init), Some(&next)) if nexts.iter().all(|&v| v == next) => (init, next),
+            _ => continue,
+        };
+        if !f.types[phi].is_integer() {
+            continue;
+        }
+        let step = match defs.get(&next) {
+            Some(&Op::Binary(BinOp::Add, x, y)) if x == phi => steps.get(&y).cloned(),
+            Some(&Op::Binary(BinOp::Add, x, y)) if y == phi => steps.get(&x).cloned(),
+            Some(&Op::Binary(BinOp::Sub, x, y)) if x == phi => steps.get(&y).map(|n| n.wrapping_neg()),
+            _ => None,
+        };
+        if let Some(step) = step {
+            inductions.insert(phi, Induction { init: init, next: next, step: step });
+        }
+    }
+
+    // The products of each induction variable and each factor
+    // become a single new induction variable.
+    let mut reduced: HashMap<(Value, Value), Value> = HashMap::new();
+    let mut products = Vec::new();
+    for &b in &l.blocks {
+        for inst in &f.blocks[b].insts {
+            if let (Some(v), &Op::Binary(BinOp::Mul, x, y)) = (inst.result, &inst.op) {
+                if inductions.contains_key(&x) && !in_loop[y] {
+                    products.push((v, x, y, inst.pos));
+                } else if inductions.contains_key(&y) && !in_loop[x] {
+                    products.push((v, y, x, inst.pos));
+                }
+            }
+        }
+    }
+    for (v, i, k, pos) in products {
+        if let Some(&t) = reduced.get(&(i, k)) {
+            replaced.insert(v, t);
+            continue;
+        }
+        let iv = &inductions[&i];
+        let incoming = match f.blocks[l.header].insts.iter().find(|inst| inst.result == Some(i)) {
+            Some(&Inst { op: Op::Phi(ref incoming), .. }) => incoming.clone(),
+            _ => continue,
+        };
+        let ty = f.types[i].clone();
+        let (step, start, increment) = (f.new_value(ty.clone()), f.new_value(ty.clone()), f.new_value(ty.clone()));
+        let (t, next) = (f.new_value(ty.clone()), f.new_value(ty));
+        f.blocks[preheader].insts.extend(vec![
+            Inst { result: Some(step), op: Op::Const(Const::Int(iv.step)), pos: pos },
+            Inst { result: Some(start), op: Op::Binary(BinOp::Mul, iv.init, k), pos: pos },
+            Inst { result: Some(increment), op: Op::Binary(BinOp::Mul, step, k), pos: pos },
+        ]);
+        let incoming = incoming.iter().map(|&(b, _)| (b, if b == preheader { start } else { next })).collect();
+        f.blocks[l.header].insts.insert(0, Inst { result: Some(t), op: Op::Phi(incoming), pos: pos });
+        // The increment follows that of the induction variable,
+        // which is available at the end of every latch.
+        if let Some((b, at)) = position(f, iv.next) {
+            let inst = Inst { result: Some(next), op: Op::Binary(BinOp::Add, t, increment), pos: pos };
+            f.blocks[b].insts.insert(at + 1, inst);
+        }
+        reduced.insert((i, k), t);
+        replaced.insert(v, t);
+    }
+}
+
+/// Returns the block and the index of the instruction that
+/// defines `v`.
+fn position(f: &Function, v: Value) -> Option<(BlockId, usize)> {
+    for (b, block) in f.blocks.iter().enumerate() {
+        if let Some(i) = block.insts.iter().position(|inst| inst.result == Some(v)) {
+            return Some((b, i));
+        }
+    }
+    return None;
+}
@@ -0,0 +1,511 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::result;
+
+use ast::*;
+use error::{Error, Result, err_msg};
+use error::ErrorType as ET;
+use loc::Loc;
+use types::{self, TypeId};
+
+pub type SymbolId = usize;
+pub type ScopeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Type,
+    Var,
+    Const,
+    Func,
+    Builtin,
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            SymbolKind::Type => "type",
+            SymbolKind::Var => "var",
+            SymbolKind::Const => "const",
+            SymbolKind::Func => "func",
+            SymbolKind::Builtin => "builtin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the symbol is declared; `None` for
+    /// predeclared symbols.
+    pub loc: Option<Loc>,
+    pub scope: ScopeId,
+    /// The type of a variable, constant or function, or the
+    /// type denoted by a type name; filled in by the type
+    /// checker except for predeclared symbols.
+    pub ty: Option<TypeId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeKind {
+    Universe,
+    Package,
+    /// The scope of a function's parameters and of the
+    /// outermost declarations of its body.
+    Function(String),
+    Block,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+    pub loc: Option<Loc>,
+    /// The symbols of the scope, in order of declaration.
+    pub symbols: Vec<SymbolId>,
+    names: HashMap<String, SymbolId>,
+}
+
+/// All the scopes and symbols of a program.  Scopes are
+/// numbered in the order in which they are opened, and
+/// symbols in the order in which they are declared.
+///
+/// While a program is being resolved, the table also keeps
+/// track of the current scope; `open_scope` and `close_scope`
+/// move in and out of nested scopes.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    pub scopes: Vec<Scope>,
+    pub symbols: Vec<Symbol>,
+    current: ScopeId,
+}
+
+/// The symbols of the universe block.  `len` and `cap` are
+/// predeclared functions in GoLite as they are in Go.
+const PREDECLARED: &[(&str, SymbolKind, Option<TypeId>)] = &[
+    ("int", SymbolKind::Type, Some(types::INT)),
+    ("float64", SymbolKind::Type, Some(types::FLOAT64)),
+    ("bool", SymbolKind::Type, Some(types::BOOL)),
+    ("rune", SymbolKind::Type, Some(types::RUNE)),
+    ("string", SymbolKind::Type, Some(types::STRING)),
+    ("true", SymbolKind::Const, Some(types::UNTYPED_BOOL)),
+    ("false", SymbolKind::Const, Some(types::UNTYPED_BOOL)),
+    ("len", SymbolKind::Builtin, None),
+    ("cap", SymbolKind::Builtin, None),
+];
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable::new()
+    }
+}
+
+impl SymbolTable {
+    /// Creates a symbol table containing only the universe
+    /// block, which is the current scope.
+    pub fn new() -> SymbolTable {
+        let mut table = SymbolTable { scopes: Vec::new(), symbols: Vec::new(), current: 0 };
+        table.open_scope(ScopeKind::Universe, None);
+        for &(name, kind, ty) in PREDECLARED {
+            if let Ok(id) = table.declare(name, kind, None) {
+                table.symbols[id].ty = ty;
+            }
+        }
+        return table;
+    }
+
+    pub fn current(&self) -> ScopeId {
+        self.current
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id]
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id]
+    }
+
+    /// Opens a new scope nested in the current one.
+    pub fn open_scope(&mut self, kind: ScopeKind, loc: Option<Loc>) -> ScopeId {
+        let parent = if self.scopes.is_empty() { None } else { Some(self.current) };
+        self.scopes.push(Scope {
+            kind: kind,
+            parent: parent,
+            loc: loc,
+            symbols: Vec::new(),
+            names: HashMap::new(),
+        });
+        self.current = self.scopes.len() - 1;
+        return self.current;
+    }
+
+    /// Returns to the parent of the current scope.
+    pub fn close_scope(&mut self) {
+        if let Some(parent) = self.scopes[self.current].parent {
+            self.current = parent;
+        }
+    }
+
+    /// Declares a symbol in the current scope; fails if the
+    /// scope already has a symbol with the same name.
+    pub fn declare(&mut self, name: &str, kind: SymbolKind, loc: Option<Loc>) -> Result<SymbolId> {
+        if let Some(prev) = self.lookup_current(name) {
+            let msg = match self.symbols[prev].loc {
+                Some(ref prev_loc) =>
+                    format!("{} redeclared in this block (previous declaration at {})",
+                            name, prev_loc),
+                None => format!("{} redeclared in this block", name),
+            };
+            let loc = loc.unwrap_or_else(|| Loc::new("<builtin>", 0, 0));
+            return err_msg(ET::RedeclaredIdentifier, loc, msg);
+        }
+        let id = self.symbols.len();
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            kind: kind,
+            loc: loc,
+            scope: self.current,
+            ty: None,
+        });
+        let scope = &mut self.scopes[self.current];
+        scope.symbols.push(id);
+        scope.names.insert(name.to_string(), id);
+        return Ok(id);
+    }
+
+    /// Looks up a name in the current scope only.
+    pub fn lookup_current(&self, name: &str) -> Option<SymbolId> {
+        self.scopes[self.current].names.get(name).cloned()
+    }
+
+    /// Looks up a name in the current scope and, failing
+    /// that, in the enclosing scopes.
+    pub fn lookup(&self, name: &str) -> Option<SymbolId> {
+        let mut scope = Some(self.current);
+        while let Some(id) = scope {
+            if let Some(sym) = self.scopes[id].names.get(name) {
+                return Some(*sym);
+            }
+            scope = self.scopes[id].parent;
+        }
+        return None;
+    }
+
+    /// Returns the number of scopes between `id` and the universe.
+    fn depth(&self, id: ScopeId) -> usize {
+        let mut depth = 0;
+        let mut scope = self.scopes[id].parent;
+        while let Some(parent) = scope {
+            depth += 1;
+            scope = self.scopes[parent].parent;
+        }
+        return depth;
+    }
+
+    /// Returns a listing of every scope, in order of opening
+    /// and indented by nesting depth, with its symbols.
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        for (id, scope) in self.scopes.iter().enumerate() {
+            let indent = "  ".repeat(self.depth(id));
+            let header = match scope.kind {
+                ScopeKind::Universe => "universe scope".to_string(),
+                ScopeKind::Package => "package scope".to_string(),
+                ScopeKind::Function(ref name) => format!("function {} scope", name),
+                ScopeKind::Block => "block scope".to_string(),
+            };
+            out.push_str(&indent);
+            out.push_str(&header);
+            if let Some(ref loc) = scope.loc {
+                out.push_str(&format!(", {}", loc));
+            }
+            out.push('\n');
+            for &sym in &scope.symbols {
+                let sym = &self.symbols[sym];
+                out.push_str(&format!("{}  {} [{}]", indent, sym.name, sym.kind));
+                if let Some(ref loc) = sym.loc {
+                    out.push_str(&format!(" {}", loc));
+                }
+                out.push('\n');
+            }
+        }
+        return out;
+    }
+}
+
+
+/// Resolves every identifier of a program, filling in the
+/// `sym` field of its `Ident`s, and returns the symbol table.
+///
+/// As in GoLite (but unlike Go), identifiers must be declared
+/// before they are used, including at the top level.  The scope
+/// of a variable starts after its declaration (so `var x = x`
+/// refers to an outer `x`), while the scope of a type or of a
+/// function starts at its name, which allows recursive
+/// functions and types.
+pub fn resolve(prog: &mut Program) -> result::Result<SymbolTable, Vec<Error>> {
+    let mut resolver = Resolver { table: SymbolTable::new(), errors: Vec::new() };
+    resolver.program(prog);
+    if resolver.errors.is_empty() {
+        return Ok(resolver.table);
+    } else {
+        return Err(resolver.errors);
+    }
+}
+
+struct Resolver {
+    table: SymbolTable,
+    errors: Vec<Error>,
+}
+
+impl Resolver {
+    /// Declares `id` in the current scope, unless it is blank.
+    fn declare(&mut self, id: &mut Ident, kind: SymbolKind) {
+        if id.is_blank() {
+            return;
+        }
+        match self.table.declare(&id.name, kind, Some(id.loc.clone())) {
+            Ok(sym) => id.sym = Some(sym),
+            Err(error) => self.errors.push(error),
+        }
+    }
+
+    /// Resolves a use of `id`.
+    fn use_ident(&mut self, id: &mut Ident) {
+        match self.table.lookup(&id.name) {
+            Some(sym) => id.sym = Some(sym),
+            None => {
+                let msg = format!("undefined: {}", id.name);
+                self.errors.push(Error::with_msg(ET::UndeclaredIdentifier, id.loc.clone(), msg));
+            }
+        }
+    }
+
+    fn program(&mut self, prog: &mut Program) {
+        self.table.open_scope(ScopeKind::Package, None);
+        for decl in &mut prog.decls {
+            match *decl {
+                TopLevelDecl::Var(ref mut specs) => self.var_specs(specs),
+                TopLevelDecl::Type(ref mut specs) => self.type_specs(specs),
+                TopLevelDecl::Func(ref mut f) => self.func_decl(f),
+                TopLevelDecl::Error(_) => {}
+            }
+        }
+        self.table.close_scope();
+    }
+
+    fn var_specs(&mut self, specs: &mut [VarSpec]) {
+        for spec in specs {
+            if let Some(ref mut ty) = spec.ty {
+                self.type_expr(ty);
+            }
+            self.exprs(&mut spec.values);
+            for name in &mut spec.names {
+                self.declare(name, SymbolKind::Var);
+            }
+        }
+    }
+
+    fn type_specs(&mut self, specs: &mut [TypeSpec]) {
+        for spec in specs {
+            self.declare(&mut spec.name, SymbolKind::Type);
+            self.type_expr(&mut spec.ty);
+        }
+    }
+
+    fn func_decl(&mut self, f: &mut FuncDecl) {
+        self.declare(&mut f.name, SymbolKind::Func);
+        for param in &mut f.params {
+            self.type_expr(&mut param.ty);
+        }
+        if let Some(ref mut ty) = f.result {
+            self.type_expr(ty);
+        }
+        self.table.open_scope(ScopeKind::Function(f.name.name.clone()), Some(f.loc.clone()));
+        for param in &mut f.params {
+            self.declare(&mut param.name, SymbolKind::Var);
+        }
+        // The parameters and the outermost declarations
+        // of the body share the function scope.
+        self.stmts(&mut f.body.stmts);
+        self.table.close_scope();
+    }
+
+    fn type_expr(&mut self, ty: &mut TypeExpr) {
+        match ty.kind {
+            TypeExprKind::Name(ref mut id) => {
+                self.use_ident(id);
+                if let Some(sym) = id.sym {
+                    if self.table.symbol(sym).kind != SymbolKind::Type {
+                        let msg = format!("{} is not a type", id.name);
+                        self.errors.push(Error::with_msg(ET::NotAType, id.loc.clone(), msg));
+                    }
+                }
+            }
+            TypeExprKind::Array(ref mut len, ref mut elem) => {
+                self.expr(len);
+                self.type_expr(elem);
+            }
+            TypeExprKind::Slice(ref mut elem) => self.type_expr(elem),
+            TypeExprKind::Struct(ref mut fields) => {
+                for field in fields {
+                    self.type_expr(&mut field.ty);
+                }
+            }
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn block(&mut self, block: &mut Block) {
+        self.table.open_scope(ScopeKind::Block, Some(block.loc.clone()));
+        self.stmts(&mut block.stmts);
+        self.table.close_scope();
+    }
+
+    fn stmts(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref mut block) => self.block(block),
+            StmtKind::Expr(ref mut e) => self.expr(e),
+            StmtKind::Var(ref mut specs) => self.var_specs(specs),
+            StmtKind::Type(ref mut specs) => self.type_specs(specs),
+            StmtKind::Assign(ref mut lhs, ref mut rhs) => {
+                self.exprs(lhs);
+                self.exprs(rhs);
+            }
+            StmtKind::OpAssign(_, ref mut lhs, ref mut rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            StmtKind::Incr(ref mut e) | StmtKind::Decr(ref mut e) => self.expr(e),
+            StmtKind::ShortVarDecl(ref mut names, ref mut values) => {
+                self.exprs(values);
+                self.short_var_decl(names, &stmt.loc);
+            }
+            StmtKind::Print(ref mut args) | StmtKind::Println(ref mut args) => self.exprs(args),
+            StmtKind::Return(ref mut e) => {
+                if let Some(ref mut e) = *e {
+                    self.expr(e);
+                }
+            }
+            // `if`, `switch` and `for` statements are in an implicit
+            // block that contains the variables of their init statement.
+            StmtKind::If(ref mut if_stmt) => {
+                self.table.open_scope(ScopeKind::Block, Some(stmt.loc.clone()));
+                if let Some(ref mut init) = if_stmt.init {
+                    self.stmt(init);
+                }
+                self.expr(&mut if_stmt.cond);
+                self.block(&mut if_stmt.then);
+                if let Some(ref mut els) = if_stmt.els {
+                    self.stmt(els);
+                }
+                self.table.close_scope();
+            }
+            StmtKind::Switch(ref mut switch_stmt) => {
+                self.table.open_scope(ScopeKind::Block, Some(stmt.loc.clone()));
+                if let Some(ref mut init) = switch_stmt.init {
+                    self.stmt(init);
+                }
+                if let Some(ref mut tag) = switch_stmt.tag {
+                    self.expr(tag);
+                }
+                for clause in &mut switch_stmt.clauses {
+                    if let Some(ref mut exprs) = clause.exprs {
+                        self.exprs(exprs);
+                    }
+                    self.table.open_scope(ScopeKind::Block, Some(clause.loc.clone()));
+                    self.stmts(&mut clause.body);
+                    self.table.close_scope();
+                }
+                self.table.close_scope();
+            }
+            StmtKind::For(ref mut for_stmt) => {
+                self.table.open_scope(ScopeKind::Block, Some(stmt.loc.clone()));
+                if let Some(ref mut init) = for_stmt.init {
+                    self.stmt(init);
+                }
+                if let Some(ref mut cond) = for_stmt.cond {
+                    self.expr(cond);
+                }
+                if let Some(ref mut post) = for_stmt.post {
+                    self.stmt(post);
+                }
+                self.block(&mut for_stmt.body);
+                self.table.close_scope();
+            }
+            StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+        }
+    }
+
+    /// Variables already declared in the current scope are
+    /// assigned to by `:=` rather than redeclared, but at
+    /// least one new variable must be declared.
+    fn short_var_decl(&mut self, names: &mut [Ident], loc: &Loc) {
+        let mut any_new = false;
+        let mut seen: Vec<String> = Vec::new();
+        for name in names {
+            if name.is_blank() {
+                continue;
+            }
+            if seen.contains(&name.name) {
+                let msg = format!("{} repeated on left side of :=", name.name);
+                self.errors.push(Error::with_msg(ET::RedeclaredIdentifier, name.loc.clone(), msg));
+                continue;
+            }
+            seen.push(name.name.clone());
+            match self.table.lookup_current(&name.name) {
+                Some(sym) => name.sym = Some(sym),
+                None => {
+                    any_new = true;
+                    self.declare(name, SymbolKind::Var);
+                }
+            }
+        }
+        if !any_new {
+            self.errors.push(Error::new(ET::NoNewVariables, loc.clone()));
+        }
+    }
+
+
+    // EXPRESSIONS
+
+    fn exprs(&mut self, exprs: &mut [Expr]) {
+        for e in exprs {
+            self.expr(e);
+        }
+    }
+
+    fn expr(&mut self, e: &mut Expr) {
+        match e.kind {
+            ExprKind::Id(ref mut id) => self.use_ident(id),
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => {}
+            ExprKind::Unary(_, ref mut operand) => self.expr(operand),
+            ExprKind::Binary(_, ref mut lhs, ref mut rhs)
+            | ExprKind::Index(ref mut lhs, ref mut rhs)
+            | ExprKind::Append(ref mut lhs, ref mut rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::Call(ref mut f, ref mut args) => {
+                self.expr(f);
+                self.exprs(args);
+            }
+            // Fields are resolved by the type checker.
+            ExprKind::Selector(ref mut e, _) => self.expr(e),
+        }
+    }
+}
@@ -0,0 +1,1104 @@
+use std::cmp::Ordering;
+use std::result;
+
+use ast::*;
+use constant::{self, BigInt, Rat, Value};
+use error::Error;
+use error::ErrorType as ET;
+use loc::Loc;
+use pretty::pretty_expr;
+use symtab::{SymbolKind, SymbolTable};
+use types::*;
+
+/// Checks the static semantics of a resolved program.
+///
+/// The type of every expression is stored in its `ty` field and
+/// the type of every symbol in the symbol table.  Untyped constants
+/// are given the type that their context imposes (e.g., `1` in
+/// `x + 1` gets the type of `x`), or their default type when the
+/// context imposes none, so that once a program has been checked
+/// only constant subexpressions may still have an untyped type.
+///
+/// Constant expressions are evaluated exactly, with arbitrary
+/// precision, and their `value` is checked to be representable
+/// in their type once it is known.
+pub fn check(prog: &mut Program, table: &mut SymbolTable) -> result::Result<TypeTable, Vec<Error>> {
+    let mut checker = Checker {
+        table: table,
+        types: TypeTable::new(),
+        errors: Vec::new(),
+        result: VOID,
+        defining: Vec::new(),
+    };
+    checker.program(prog);
+    if checker.errors.is_empty() {
+        return Ok(checker.types);
+    } else {
+        return Err(checker.errors);
+    }
+}
+
+struct Checker<'a> {
+    table: &'a mut SymbolTable,
+    types: TypeTable,
+    errors: Vec<Error>,
+    /// The result type of the function being checked.
+    result: TypeId,
+    /// The named types whose declaration is being checked.
+    defining: Vec<TypeId>,
+}
+
+/// The largest count of a constant shift; larger shifts would
+/// create huge constants for no good reason.
+const MAX_SHIFT: u64 = 10000;
+
+/// The outcome of typing an operator: the result type, or the
+/// error type and the reason why the operation is invalid.
+type OpResult = result::Result<TypeId, (ET, String)>;
+
+impl<'a> Checker<'a> {
+    fn error_msg(&mut self, ty: ET, loc: &Loc, msg: String) {
+        self.errors.push(Error::with_msg(ty, loc.clone(), msg));
+    }
+
+    fn name(&self, ty: TypeId) -> String {
+        self.types.name(ty)
+    }
+
+    /// Describes an expression and its type for error messages.
+    fn describe(&self, e: &Expr) -> String {
+        format!("{} (type {})", pretty_expr(e), self.name(e.ty.unwrap_or(INVALID)))
+    }
+
+    /// Records the type of a declared identifier.
+    fn declare(&mut self, id: &Ident, ty: TypeId) {
+        if let Some(sym) = id.sym {
+            self.table.symbols[sym].ty = Some(ty);
+        }
+    }
+
+    fn program(&mut self, prog: &mut Program) {
+        for decl in &mut prog.decls {
+            match *decl {
+                TopLevelDecl::Var(ref mut specs) => self.var_specs(specs),
+                TopLevelDecl::Type(ref mut specs) => self.type_specs(specs),
+                TopLevelDecl::Func(ref mut f) => self.func_decl(f),
+                TopLevelDecl::Error(_) => {}
+            }
+        }
+    }
+
+    fn var_specs(&mut self, specs: &mut [VarSpec]) {
+        for spec in specs {
+            let declared = spec.ty.as_mut().map(|ty| self.type_expr(ty, false));
+            let mut value_types = Vec::new();
+            for value in &mut spec.values {
+                self.value(value);
+                match declared {
+                    Some(ty) => {
+                        self.assign(value, ty, "variable declaration");
+                        value_types.push(ty);
+                    }
+                    None => value_types.push(self.default_value(value)),
+                }
+            }
+            for (i, name) in spec.names.iter().enumerate() {
+                let ty = declared.unwrap_or_else(|| value_types.get(i).cloned().unwrap_or(INVALID));
+                self.declare(name, ty);
+            }
+        }
+    }
+
+    /// A type declaration creates a new named type, which is
+    /// visible in its own definition; a recursive reference
+    /// must go through a slice, since the type would otherwise
+    /// have an infinite size.
+    fn type_specs(&mut self, specs: &mut [TypeSpec]) {
+        for spec in specs {
+            match spec.name.sym {
+                Some(sym) => {
+                    let named = self.types.named(&spec.name.name, sym);
+                    self.table.symbols[sym].ty = Some(named);
+                    self.defining.push(named);
+                    let underlying = self.type_expr(&mut spec.ty, true);
+                    self.defining.pop();
+                    self.types.set_underlying(named, underlying);
+                }
+                None => {
+                    self.type_expr(&mut spec.ty, true);
+                }
+            }
+        }
+    }
+
+    fn func_decl(&mut self, f: &mut FuncDecl) {
+        let mut params = Vec::new();
+        for param in &mut f.params {
+            let ty = self.type_expr(&mut param.ty, false);
+            self.declare(&param.name, ty);
+            params.push(ty);
+        }
+        let result = match f.result {
+            Some(ref mut ty) => self.type_expr(ty, false),
+            None => VOID,
+        };
+        if f.name.name == "main" && (!params.is_empty() || result != VOID) {
+            self.error_msg(ET::InvalidMain, &f.name.loc,
+                           "func main must have no arguments and no return values".to_string());
+        }
+        let ty = self.types.intern(TypeKind::Func(params, result));
+        self.declare(&f.name, ty);
+        self.result = result;
+        self.stmts(&mut f.body.stmts);
+        self.result = VOID;
+    }
+
+    /// Returns the type denoted by a type expression.  `direct`
+    /// is false below a slice, where a type may refer to itself.
+    fn type_expr(&mut self, te: &mut TypeExpr, direct: bool) -> TypeId {
+        match te.kind {
+            TypeExprKind::Name(ref id) => {
+                let ty = match id.sym {
+                    Some(sym) if self.table.symbol(sym).kind == SymbolKind::Type =>
+                        self.table.symbol(sym).ty.unwrap_or(INVALID),
+                    _ => INVALID,
+                };
+                if direct && self.defining.contains(&ty) {
+                    let msg = format!("invalid recursive type {}", id.name);
+                    self.error_msg(ET::InvalidRecursiveType, &id.loc, msg);
+                    return INVALID;
+                }
+                return ty;
+            }
+            TypeExprKind::Array(ref mut len, ref mut elem) => {
+                let len = self.array_length(len);
+                let elem = self.type_expr(elem, direct);
+                match len {
+                    Some(len) if elem != INVALID => self.types.intern(TypeKind::Array(len, elem)),
+                    _ => INVALID,
+                }
+            }
+            TypeExprKind::Slice(ref mut elem) => {
+                let elem = self.type_expr(elem, false);
+                if elem == INVALID {
+                    return INVALID;
+                }
+                return self.types.intern(TypeKind::Slice(elem));
+            }
+            TypeExprKind::Struct(ref mut fields) => {
+                let mut field_types: Vec<(String, TypeId)> = Vec::new();
+                for field in fields {
+                    let ty = self.type_expr(&mut field.ty, direct);
+                    let name = &field.name;
+                    if !name.is_blank() && field_types.iter().any(|f| f.0 == name.name) {
+                        let msg = format!("duplicate field {}", name.name);
+                        self.error_msg(ET::DuplicateField, &name.loc, msg);
+                    }
+                    field_types.push((name.name.clone(), ty));
+                }
+                if field_types.iter().any(|f| f.1 == INVALID) {
+                    return INVALID;
+                }
+                return self.types.intern(TypeKind::Struct(field_types));
+            }
+        }
+    }
+
+    /// Returns the length of an array type, which must be
+    /// a non-negative constant representable as an `int`.
+    fn array_length(&mut self, len: &mut Expr) -> Option<u64> {
+        let ty = self.value(len);
+        if ty == INVALID {
+            return None;
+        }
+        let mut n = None;
+        if self.is_integer_value(len) {
+            self.set_untyped(len, INT);
+            n = len.value.as_ref().and_then(|v| v.to_int()).and_then(|n| n.to_i64());
+        }
+        match n {
+            Some(n) if n >= 0 => return Some(n as u64),
+            _ => {
+                let msg = format!("array length {} must be a non-negative integer constant",
+                                  pretty_expr(len));
+                self.error_msg(ET::InvalidArrayLength, &len.loc, msg);
+                return None;
+            }
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref mut block) => self.stmts(&mut block.stmts),
+            StmtKind::Expr(ref mut e) => self.expr_stmt(e),
+            StmtKind::Var(ref mut specs) => self.var_specs(specs),
+            StmtKind::Type(ref mut specs) => self.type_specs(specs),
+            StmtKind::Assign(ref mut lhs, ref mut rhs) => {
+                for (target, value) in lhs.iter_mut().zip(rhs.iter_mut()) {
+                    if let ExprKind::Blank = target.kind {
+                        self.value(value);
+                        self.default_value(value);
+                        continue;
+                    }
+                    let ty = self.lvalue(target);
+                    self.value(value);
+                    self.assign(value, ty, "assignment");
+                }
+            }
+            StmtKind::OpAssign(op, ref mut lhs, ref mut rhs) => {
+                let lt = self.lvalue(lhs);
+                let rt = self.value(rhs);
+                if lt == INVALID || rt == INVALID {
+                    return;
+                }
+                if let Err((ty, reason)) = self.binary_op(op, lhs, rhs) {
+                    let token = op.assign_token().unwrap_or_else(|| op.token());
+                    let msg = format!("invalid operation: {} {} {} ({})",
+                                      pretty_expr(lhs), token, pretty_expr(rhs), reason);
+                    self.error_msg(ty, &stmt.loc, msg);
+                }
+            }
+            StmtKind::Incr(ref mut e) => self.inc_dec(e, "++"),
+            StmtKind::Decr(ref mut e) => self.inc_dec(e, "--"),
+            StmtKind::ShortVarDecl(ref names, ref mut values) => {
+                for (name, value) in names.iter().zip(values.iter_mut()) {
+                    self.value(value);
+                    let existing = match name.sym {
+                        Some(sym) => self.table.symbol(sym).ty,
+                        None => None,
+                    };
+                    match existing {
+                        Some(ty) => self.assign(value, ty, "assignment"),
+                        None => {
+                            let ty = self.default_value(value);
+                            self.declare(name, ty);
+                        }
+                    }
+                }
+            }
+            StmtKind::Print(ref mut args) | StmtKind::Println(ref mut args) => {
+                for arg in args {
+                    self.value(arg);
+                    let ty = self.default_value(arg);
+                    if ty != INVALID && !self.types.is_basic(ty) {
+                        let msg = format!("invalid argument {} for print", self.describe(arg));
+                        self.error_msg(ET::InvalidArgument, &arg.loc, msg);
+                    }
+                }
+            }
+            StmtKind::Return(ref mut e) => {
+                match *e {
+                    None if self.result != VOID => {
+                        self.error_msg(ET::ReturnMismatch, &stmt.loc,
+                                       "not enough arguments to return".to_string());
+                    }
+                    None => {}
+                    Some(ref mut e) => {
+                        self.value(e);
+                        if self.result == VOID {
+                            self.error_msg(ET::ReturnMismatch, &e.loc,
+                                           "too many arguments to return".to_string());
+                        } else {
+                            let result = self.result;
+                            self.assign(e, result, "return argument");
+                        }
+                    }
+                }
+            }
+            StmtKind::If(ref mut if_stmt) => {
+                if let Some(ref mut init) = if_stmt.init {
+                    self.stmt(init);
+                }
+                self.condition(&mut if_stmt.cond, "if statement");
+                self.stmts(&mut if_stmt.then.stmts);
+                if let Some(ref mut els) = if_stmt.els {
+                    self.stmt(els);
+                }
+            }
+            StmtKind::Switch(ref mut switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref mut for_stmt) => {
+                if let Some(ref mut init) = for_stmt.init {
+                    self.stmt(init);
+                }
+                if let Some(ref mut cond) = for_stmt.cond {
+                    self.condition(cond, "for statement");
+                }
+                if let Some(ref mut post) = for_stmt.post {
+                    self.stmt(post);
+                }
+                self.stmts(&mut for_stmt.body.stmts);
+            }
+            StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+        }
+    }
+
+    fn inc_dec(&mut self, e: &mut Expr, op: &str) {
+        let ty = self.lvalue(e);
+        if ty != INVALID && !self.types.is_numeric(ty) {
+            let msg = format!("invalid operation: {}{} (non-numeric type {})",
+                              pretty_expr(e), op, self.name(ty));
+            self.error_msg(ET::InvalidOperation, &e.loc, msg);
+        }
+    }
+
+    /// Only function calls may be used as statements.
+    fn expr_stmt(&mut self, e: &mut Expr) {
+        let ty = self.expr(e);
+        let is_call = match e.kind {
+            ExprKind::Call(ref f, _) => self.callee_kind(f).is_none(),
+            _ => false,
+        };
+        if !is_call && ty != INVALID {
+            let msg = format!("{} evaluated but not used", pretty_expr(e));
+            self.error_msg(ET::UnusedExpression, &e.loc, msg);
+        }
+    }
+
+    fn condition(&mut self, cond: &mut Expr, context: &str) {
+        let ty = self.value(cond);
+        if ty == INVALID {
+            return;
+        }
+        if !self.types.is_bool(ty) {
+            let msg = format!("non-boolean condition in {}", context);
+            self.error_msg(ET::NonBooleanCondition, &cond.loc, msg);
+        } else {
+            self.set_untyped(cond, BOOL);
+        }
+    }
+
+    /// A switch without a tag is equivalent to `switch true`.
+    fn switch_stmt(&mut self, switch_stmt: &mut SwitchStmt) {
+        if let Some(ref mut init) = switch_stmt.init {
+            self.stmt(init);
+        }
+        let tag_ty = match switch_stmt.tag {
+            Some(ref mut tag) => {
+                self.value(tag);
+                let ty = self.default_value(tag);
+                if ty != INVALID && !self.types.is_comparable(ty) {
+                    let msg = format!("cannot switch on {}", self.describe(tag));
+                    self.error_msg(ET::InvalidOperation, &tag.loc, msg);
+                    INVALID
+                } else {
+                    ty
+                }
+            }
+            None => BOOL,
+        };
+        for clause in &mut switch_stmt.clauses {
+            if let Some(ref mut exprs) = clause.exprs {
+                for e in exprs {
+                    let ty = self.value(e);
+                    if ty == INVALID || tag_ty == INVALID {
+                        continue;
+                    }
+                    let matches = match switch_stmt.tag {
+                        Some(_) => self.types.is_assignable(ty, tag_ty)
+                            || self.types.is_assignable(tag_ty, ty),
+                        None => self.types.is_bool(ty),
+                    };
+                    if !matches {
+                        let on = match switch_stmt.tag {
+                            Some(ref tag) => format!(" on {}", pretty_expr(tag)),
+                            None => String::new(),
+                        };
+                        let msg = format!("invalid case {} in switch{} (mismatched types {} and {})",
+                                          pretty_expr(e), on, self.name(ty), self.name(tag_ty));
+                        self.error_msg(ET::MismatchedTypes, &e.loc, msg);
+                    } else {
+                        self.set_untyped(e, tag_ty);
+                    }
+                }
+            }
+            self.stmts(&mut clause.body);
+        }
+    }
+
+
+    // ASSIGNABILITY
+
+    /// Checks an expression that is assigned to and returns
+    /// its type.
+    fn lvalue(&mut self, e: &mut Expr) -> TypeId {
+        let ty = self.value(e);
+        if ty != INVALID && !self.is_addressable(e) {
+            let msg = format!("cannot assign to {}", pretty_expr(e));
+            self.error_msg(ET::NonAddressableAssignment, &e.loc, msg);
+            return INVALID;
+        }
+        return ty;
+    }
+
+    /// Returns whether a typed expression denotes a variable.
+    /// Elements of arrays are variables only if the array is.
+    fn is_addressable(&self, e: &Expr) -> bool {
+        match e.kind {
+            ExprKind::Id(ref id) => match id.sym {
+                Some(sym) => self.table.symbol(sym).kind == SymbolKind::Var,
+                None => false,
+            },
+            ExprKind::Index(ref a, _) => match *self.types.under(a.ty.unwrap_or(INVALID)) {
+                TypeKind::Slice(_) => true,
+                _ => self.is_addressable(a),
+            },
+            ExprKind::Selector(ref x, _) => self.is_addressable(x),
+            _ => false,
+        }
+    }
+
+    /// Checks that a typed expression can be assigned to a
+    /// variable of type `ty`.
+    fn assign(&mut self, e: &mut Expr, ty: TypeId, context: &str) {
+        let value_ty = e.ty.unwrap_or(INVALID);
+        if value_ty == INVALID || ty == INVALID {
+            return;
+        }
+        if !self.types.is_assignable(value_ty, ty) {
+            let msg = format!("cannot use {} as type {} in {}", self.describe(e), self.name(ty), context);
+            self.error_msg(ET::IncompatibleAssign, &e.loc, msg);
+            return;
+        }
+        self.set_untyped(e, ty);
+    }
+
+    /// Gives a typed expression its default type if it is
+    /// untyped, and returns its type.
+    fn default_value(&mut self, e: &mut Expr) -> TypeId {
+        let ty = self.types.default_type(e.ty.unwrap_or(INVALID));
+        self.set_untyped(e, ty);
+        return ty;
+    }
+
+    /// Gives the type `ty` to an untyped expression and to
+    /// the untyped operands whose type it determines.  A constant
+    /// must be representable in its new type.
+    fn set_untyped(&mut self, e: &mut Expr, ty: TypeId) {
+        self.convert_untyped(e, ty, true);
+    }
+
+    /// Only the value of the outermost constant expression is
+    /// checked, since the values of its operands are not used
+    /// once it has been evaluated (e.g., in `1 << 70 >> 68`).
+    fn convert_untyped(&mut self, e: &mut Expr, ty: TypeId, check: bool) {
+        match e.ty {
+            Some(t) if self.types.is_untyped(t) => e.ty = Some(ty),
+            _ => return,
+        }
+        if let Some(value) = e.value.take() {
+            match self.represent(&value, ty) {
+                Ok(value) => e.value = Some(value),
+                Err((err, msg)) => {
+                    if check {
+                        self.error_msg(err, &e.loc, msg);
+                    } else {
+                        e.value = Some(value);
+                    }
+                }
+            }
+        }
+        match e.kind {
+            ExprKind::Unary(_, ref mut x) => self.convert_untyped(x, ty, false),
+            // The operands of a comparison are given their
+            // types when it is checked.
+            ExprKind::Binary(op, _, _) if op.is_comparison() => {}
+            ExprKind::Binary(BinOp::Shl, ref mut x, _)
+            | ExprKind::Binary(BinOp::Shr, ref mut x, _) => self.convert_untyped(x, ty, false),
+            ExprKind::Binary(_, ref mut x, ref mut y) => {
+                self.convert_untyped(x, ty, false);
+                self.convert_untyped(y, ty, false);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns a constant value as it is represented in type
+    /// `ty`: integer types hold integers in their range, and
+    /// `float64` values are rounded to the nearest float.
+    /// Ref.: https://golang.org/ref/spec#Representability
+    fn represent(&self, value: &Value, ty: TypeId) -> result::Result<Value, (ET, String)> {
+        let bits = match *self.types.under(ty) {
+            TypeKind::Int => Some(64),
+            TypeKind::Rune => Some(32),
+            TypeKind::UntypedInt | TypeKind::UntypedRune => None,
+            TypeKind::Float64 => {
+                let f = value.to_rat().map_or(0.0, |r| r.to_f64());
+                return match Rat::from_f64(f) {
+                    Some(r) => Ok(Value::Float(r)),
+                    None => Err((ET::ConstantOverflow,
+                                 format!("constant {} overflows {}", value, self.name(ty)))),
+                };
+            }
+            TypeKind::UntypedFloat => return Ok(Value::Float(value.to_rat().unwrap_or_else(|| {
+                Rat::from_int(BigInt::zero())
+            }))),
+            _ => return Ok(value.clone()),
+        };
+        let n = match value.to_int() {
+            Some(n) => n,
+            None => return Err((ET::ConstantTruncated,
+                                format!("constant {} truncated to integer", value))),
+        };
+        if let Some(bits) = bits {
+            if !n.fits_signed(bits) {
+                return Err((ET::ConstantOverflow,
+                            format!("constant {} overflows {}", n, self.name(ty))));
+            }
+        }
+        return Ok(Value::Int(n));
+    }
+
+    /// Returns whether an expression can be used as an integer:
+    /// it has an integer type, or it is an untyped constant with
+    /// an integral value (e.g., `2.0`).
+    fn is_integer_value(&self, e: &Expr) -> bool {
+        let ty = e.ty.unwrap_or(INVALID);
+        if self.types.is_integer(ty) {
+            return true;
+        }
+        return self.types.is_untyped(ty) && self.types.is_numeric(ty)
+            && e.value.as_ref().and_then(|v| v.to_int()).is_some();
+    }
+
+
+    // EXPRESSIONS
+
+    /// Checks an expression that must have a value, i.e.,
+    /// that is not a call to a function without a result.
+    fn value(&mut self, e: &mut Expr) -> TypeId {
+        let ty = self.expr(e);
+        if ty == VOID {
+            let msg = format!("{} (no value) used as value", pretty_expr(e));
+            self.error_msg(ET::NoValue, &e.loc, msg);
+            e.ty = Some(INVALID);
+            return INVALID;
+        }
+        return ty;
+    }
+
+    /// Checks an expression and stores its type and, if it
+    /// is constant, its value.
+    fn expr(&mut self, e: &mut Expr) -> TypeId {
+        let ty = match e.kind {
+            ExprKind::Id(ref id) => {
+                if self.is_constant(id) {
+                    e.value = Some(Value::Bool(id.name == "true"));
+                }
+                self.ident(id)
+            }
+            ExprKind::Blank => INVALID,
+            ExprKind::Int(ref digits, base) => {
+                let radix = match base {
+                    IntBase::Dec => 10,
+                    IntBase::Oct => 8,
+                    IntBase::Hex => 16,
+                };
+                e.value = BigInt::parse(digits, radix).map(Value::Int);
+                UNTYPED_INT
+            }
+            ExprKind::Float(ref lexeme) => {
+                e.value = Rat::parse(lexeme).map(Value::Float);
+                UNTYPED_FLOAT
+            }
+            ExprKind::Rune(c) => {
+                e.value = Some(Value::Int(BigInt::from_u64(c as u64)));
+                UNTYPED_RUNE
+            }
+            ExprKind::String(ref s) => {
+                e.value = Some(Value::String(s.clone()));
+                UNTYPED_STRING
+            }
+            ExprKind::Unary(_, _) => self.unary(e),
+            ExprKind::Binary(_, _, _) => self.binary(e),
+            ExprKind::Call(_, _) => self.call(e),
+            ExprKind::Index(_, _) => self.index(e),
+            ExprKind::Selector(_, _) => self.selector(e),
+            ExprKind::Append(_, _) => self.append(e),
+        };
+        e.ty = Some(ty);
+        // The result of an operation on typed constants
+        // must be representable in its type.
+        if let Some(value) = e.value.take() {
+            if ty == INVALID {
+                return ty;
+            }
+            match self.represent(&value, ty) {
+                Ok(value) => e.value = Some(value),
+                Err((err, msg)) => self.error_msg(err, &e.loc, msg),
+            }
+        }
+        return ty;
+    }
+
+    /// Returns whether an identifier denotes a constant;
+    /// `true` and `false` are the only constants in GoLite.
+    fn is_constant(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => self.table.symbol(sym).kind == SymbolKind::Const,
+            None => false,
+        }
+    }
+
+    fn ident(&mut self, id: &Ident) -> TypeId {
+        let sym = match id.sym {
+            Some(sym) => self.table.symbol(sym),
+            None => return INVALID,
+        };
+        match sym.kind {
+            SymbolKind::Var | SymbolKind::Const | SymbolKind::Func => sym.ty.unwrap_or(INVALID),
+            SymbolKind::Type => {
+                let msg = format!("type {} is not an expression", id.name);
+                self.error_msg(ET::NotAnExpression, &id.loc, msg);
+                INVALID
+            }
+            SymbolKind::Builtin => {
+                let msg = format!("{} (built-in function) must be called", id.name);
+                self.error_msg(ET::NotAnExpression, &id.loc, msg);
+                INVALID
+            }
+        }
+    }
+
+    fn unary(&mut self, e: &mut Expr) -> TypeId {
+        let (op, ty, value) = match e.kind {
+            ExprKind::Unary(op, ref mut x) => (op, self.value(x), x.value.clone()),
+            _ => return INVALID,
+        };
+        let ok = match op {
+            UnOp::Plus | UnOp::Neg => self.types.is_numeric(ty),
+            UnOp::Not => self.types.is_bool(ty),
+            UnOp::BitNot => self.types.is_integer(ty),
+        };
+        if ty == INVALID || ok {
+            e.value = value.and_then(|v| constant::unary_op(op, &v));
+            return ty;
+        }
+        let msg = format!("invalid operation: {} (operator {} not defined on {})",
+                          pretty_expr(e), op.token(), self.name(ty));
+        self.error_msg(ET::InvalidOperation, &e.loc, msg);
+        return INVALID;
+    }
+
+    fn binary(&mut self, e: &mut Expr) -> TypeId {
+        let (result, value) = match e.kind {
+            ExprKind::Binary(op, ref mut x, ref mut y) => {
+                let xt = self.value(x);
+                let yt = self.value(y);
+                if xt == INVALID || yt == INVALID {
+                    return INVALID;
+                }
+                let result = self.binary_op(op, x, y);
+                let value = match (&result, &x.value, &y.value) {
+                    (Ok(_), Some(a), Some(b)) => fold(op, a, b),
+                    _ => None,
+                };
+                (result, value)
+            }
+            _ => return INVALID,
+        };
+        match result {
+            Ok(ty) => {
+                e.value = value;
+                return ty;
+            }
+            Err((ty, reason)) => {
+                let msg = format!("invalid operation: {} ({})", pretty_expr(e), reason);
+                self.error_msg(ty, &e.loc, msg);
+                return INVALID;
+            }
+        }
+    }
+
+    /// Types `x op y`, whose operands are valid and typed.
+    /// Ref.: https://golang.org/ref/spec#Operators
+    fn binary_op(&mut self, op: BinOp, x: &mut Expr, y: &mut Expr) -> OpResult {
+        let xt = x.ty.unwrap_or(INVALID);
+        let yt = y.ty.unwrap_or(INVALID);
+        if op == BinOp::Shl || op == BinOp::Shr {
+            return self.shift(x, y);
+        }
+        let ty = match self.unify(x, y, op.is_comparison()) {
+            Some(ty) => ty,
+            None => return Err((ET::MismatchedTypes,
+                                format!("mismatched types {} and {}", self.name(xt), self.name(yt)))),
+        };
+        if (op == BinOp::Div || op == BinOp::Rem) && y.value.as_ref().is_some_and(|v| v.is_zero()) {
+            return Err((ET::DivisionByZero, "division by constant zero".to_string()));
+        }
+        let defined = match op {
+            BinOp::Eq | BinOp::Ne => self.types.is_comparable(ty),
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => self.types.is_ordered(ty),
+            BinOp::Or | BinOp::And => self.types.is_bool(ty),
+            BinOp::Add => self.types.is_numeric(ty) || self.types.is_string(ty),
+            BinOp::Sub | BinOp::Mul | BinOp::Div => self.types.is_numeric(ty),
+            BinOp::Rem | BinOp::BitOr | BinOp::BitXor | BinOp::BitAnd | BinOp::BitClear =>
+                self.types.is_integer(ty),
+            BinOp::Shl | BinOp::Shr => true,
+        };
+        if !defined {
+            return Err((ET::InvalidOperation,
+                        format!("operator {} not defined on {}", op.token(), self.name(ty))));
+        }
+        if op.is_comparison() {
+            // Untyped operands of a comparison take their default
+            // type, since the result does not determine it.
+            let default = self.types.default_type(ty);
+            self.set_untyped(x, default);
+            self.set_untyped(y, default);
+            return Ok(UNTYPED_BOOL);
+        }
+        return Ok(ty);
+    }
+
+    /// Returns the common type of the operands of a binary
+    /// operator, converting an untyped operand to the type of
+    /// the other one.  The operands of a comparison need only
+    /// be assignable to one another.
+    fn unify(&mut self, x: &mut Expr, y: &mut Expr, comparison: bool) -> Option<TypeId> {
+        let xt = x.ty.unwrap_or(INVALID);
+        let yt = y.ty.unwrap_or(INVALID);
+        let (x_untyped, y_untyped) = (self.types.is_untyped(xt), self.types.is_untyped(yt));
+        if x_untyped && y_untyped {
+            if self.types.is_numeric(xt) && self.types.is_numeric(yt) {
+                return Some(self.types.untyped_max(xt, yt));
+            }
+            return if xt == yt { Some(xt) } else { None };
+        }
+        if x_untyped {
+            if !self.types.is_assignable(xt, yt) {
+                return None;
+            }
+            self.set_untyped(x, yt);
+            return Some(yt);
+        }
+        if y_untyped {
+            if !self.types.is_assignable(yt, xt) {
+                return None;
+            }
+            self.set_untyped(y, xt);
+            return Some(xt);
+        }
+        if xt == yt {
+            return Some(xt);
+        }
+        if comparison && (self.types.is_assignable(xt, yt) || self.types.is_assignable(yt, xt)) {
+            return Some(xt);
+        }
+        return None;
+    }
+
+    /// Both operands of a shift are integers; the result has
+    /// the type of the shifted operand.  A shift is constant if
+    /// both operands are untyped, and its result is then an
+    /// integer constant; otherwise an untyped shifted operand
+    /// becomes an `int`.
+    fn shift(&mut self, x: &mut Expr, y: &mut Expr) -> OpResult {
+        let xt = x.ty.unwrap_or(INVALID);
+        let yt = y.ty.unwrap_or(INVALID);
+        if !self.is_integer_value(y) {
+            return Err((ET::InvalidOperation,
+                        format!("shift count type {}, must be integer", self.name(yt))));
+        }
+        if !self.is_integer_value(x) {
+            return Err((ET::InvalidOperation,
+                        format!("shifted operand type {}, must be integer", self.name(xt))));
+        }
+        if let Some(count) = y.value.as_ref().and_then(|v| v.to_int()) {
+            if count.is_negative() {
+                return Err((ET::InvalidOperation, format!("negative shift count {}", count)));
+            }
+            if x.value.is_some() && count.cmp(&BigInt::from_u64(MAX_SHIFT)) == Ordering::Greater {
+                return Err((ET::InvalidOperation, format!("shift count {} too large", count)));
+            }
+        }
+        let constant = self.types.is_untyped(xt) && self.types.is_untyped(yt);
+        self.set_untyped(y, INT);
+        if constant {
+            return Ok(if xt == UNTYPED_FLOAT { UNTYPED_INT } else { xt });
+        }
+        self.set_untyped(x, INT);
+        return Ok(x.ty.unwrap_or(INVALID));
+    }
+
+    /// Returns the kind of the symbol called by `f` if it is
+    /// a type or a builtin, and `None` for function calls.
+    fn callee_kind(&self, f: &Expr) -> Option<SymbolKind> {
+        let kind = match f.kind {
+            ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+            _ => return None,
+        };
+        match kind {
+            SymbolKind::Type | SymbolKind::Builtin => Some(kind),
+            _ => None,
+        }
+    }
+
+    fn call(&mut self, e: &mut Expr) -> TypeId {
+        let (f, args) = match e.kind {
+            ExprKind::Call(ref mut f, ref mut args) => (f, args),
+            _ => return INVALID,
+        };
+        for arg in args.iter_mut() {
+            self.value(arg);
+        }
+        let (name, ty) = match f.kind {
+            ExprKind::Id(ref id) => (id.name.clone(), id.sym.and_then(|sym| self.table.symbol(sym).ty)),
+            _ => (String::new(), None),
+        };
+        let (ty, value) = match self.callee_kind(f) {
+            Some(SymbolKind::Type) => self.conversion(ty.unwrap_or(INVALID), args, &e.loc),
+            Some(_) => self.builtin(&name, args, &e.loc),
+            None => return self.func_call(f, args, &e.loc),
+        };
+        e.value = value;
+        return ty;
+    }
+
+    fn func_call(&mut self, f: &mut Expr, args: &mut [Expr], loc: &Loc) -> TypeId {
+
+        let ft = self.value(f);
+        if ft == INVALID {
+            return INVALID;
+        }
+        let (params, result) = match *self.types.under(ft) {
+            TypeKind::Func(ref params, result) => (params.clone(), result),
+            _ => {
+                let msg = format!("cannot call non-function {}", self.describe(f));
+                self.error_msg(ET::NotCallable, loc, msg);
+                return INVALID;
+            }
+        };
+        let fname = pretty_expr(f);
+        if args.len() != params.len() {
+            let msg = format!("{} arguments in call to {}",
+                              if args.len() < params.len() { "not enough" } else { "too many" },
+                              fname);
+            self.error_msg(ET::WrongArgumentCount, loc, msg);
+            return result;
+        }
+        let context = format!("argument to {}", fname);
+        for (arg, &param) in args.iter_mut().zip(params.iter()) {
+            self.assign(arg, param, &context);
+        }
+        return result;
+    }
+
+    /// Checks the conversion of the (typed) arguments to `ty`, and
+    /// returns the converted value of a constant argument.
+    /// Ref.: https://golang.org/ref/spec#Conversions
+    fn conversion(&mut self, ty: TypeId, args: &mut [Expr], loc: &Loc) -> (TypeId, Option<Value>) {
+        if args.len() != 1 {
+            let msg = format!("{} argument{} in conversion to {}",
+                              if args.is_empty() { "missing" } else { "too many" },
+                              if args.is_empty() { "" } else { "s" },
+                              self.name(ty));
+            self.error_msg(ET::WrongArgumentCount, loc, msg);
+            return (ty, None);
+        }
+        let arg = &mut args[0];
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        if arg_ty == INVALID || ty == INVALID {
+            return (ty, None);
+        }
+        if !self.types.is_convertible(arg_ty, ty) {
+            let msg = format!("cannot convert {} to type {}", self.describe(arg), self.name(ty));
+            self.error_msg(ET::InvalidConversion, &arg.loc, msg);
+            return (ty, None);
+        }
+        if self.types.is_assignable(arg_ty, ty) {
+            self.set_untyped(arg, ty);
+            return (ty, arg.value.clone());
+        }
+        self.default_value(arg);
+        let value = match arg.value {
+            Some(ref value) => value.clone(),
+            None => return (ty, None),
+        };
+        if self.types.is_string(ty) {
+            if self.types.is_string(arg_ty) {
+                return (ty, Some(value));
+            }
+            // An integer converts to the UTF-8 encoding of the
+            // code point, or of U+FFFD if it is not valid.
+            let c = value.to_int()
+                .and_then(|n| n.to_i64())
+                .and_then(|n| if n >= 0 && n <= 0x10ffff { Some(n as u32) } else { None })
+                .and_then(::std::char::from_u32)
+                .unwrap_or('\u{fffd}');
+            return (ty, Some(Value::String(constant::encode_rune(c))));
+        }
+        match self.represent(&value, ty) {
+            Ok(value) => return (ty, Some(value)),
+            Err((err, msg)) => {
+                self.error_msg(err, &arg.loc, msg);
+                return (ty, None);
+            }
+        }
+    }
+
+    /// `len` applies to strings, arrays and slices, and `cap`
+    /// to arrays and slices; both return an `int`.  The length
+    /// of a constant string is a constant.
+    fn builtin(&mut self, name: &str, args: &mut [Expr], loc: &Loc) -> (TypeId, Option<Value>) {
+        if args.len() != 1 {
+            let msg = format!("{} arguments in call to {}",
+                              if args.is_empty() { "not enough" } else { "too many" },
+                              name);
+            self.error_msg(ET::WrongArgumentCount, loc, msg);
+            return (INT, None);
+        }
+        let arg = &mut args[0];
+        let ty = self.default_value(arg);
+        if ty == INVALID {
+            return (INT, None);
+        }
+        let ok = match *self.types.under(ty) {
+            TypeKind::Array(_, _) | TypeKind::Slice(_) => true,
+            TypeKind::String => name == "len",
+            _ => false,
+        };
+        if !ok {
+            let msg = format!("invalid argument {} for {}", self.describe(arg), name);
+            self.error_msg(ET::InvalidArgument, &arg.loc, msg);
+            return (INT, None);
+        }
+        match arg.value {
+            Some(Value::String(ref s)) => return (INT, Some(Value::Int(BigInt::from_u64(s.chars().count() as u64)))),
+            _ => return (INT, None),
+        }
+    }
+
+    fn index(&mut self, e: &mut Expr) -> TypeId {
+        let (elem, indexed_ty) = match e.kind {
+            ExprKind::Index(ref mut a, ref mut i) => {
+                let ty = self.value(a);
+                let index_ty = self.value(i);
+                let (elem, len) = match *self.types.under(ty) {
+                    TypeKind::Array(len, elem) => (Some(elem), Some(len)),
+                    TypeKind::Slice(elem) => (Some(elem), None),
+                    TypeKind::Invalid => (Some(INVALID), None),
+                    _ => (None, None),
+                };
+                if index_ty != INVALID {
+                    if self.is_integer_value(i) {
+                        self.set_untyped(i, INT);
+                        self.constant_index(i, len);
+                    } else {
+                        let msg = format!("invalid index {} (type {} is not an integer)",
+                                          pretty_expr(i), self.name(index_ty));
+                        self.error_msg(ET::InvalidIndex, &i.loc, msg);
+                    }
+                }
+                (elem, ty)
+            }
+            _ => return INVALID,
+        };
+        match elem {
+            Some(elem) => return elem,
+            None => {
+                let msg = format!("invalid operation: {} (type {} does not support indexing)",
+                                  pretty_expr(e), self.name(indexed_ty));
+                self.error_msg(ET::InvalidIndex, &e.loc, msg);
+                return INVALID;
+            }
+        }
+    }
+
+    /// Checks that a constant index is in range: it may not be
+    /// negative, nor exceed the length of an array.
+    fn constant_index(&mut self, i: &Expr, len: Option<u64>) {
+        let n = match i.value.as_ref().and_then(|v| v.to_int()) {
+            Some(n) => n,
+            None => return,
+        };
+        let reason = if n.is_negative() {
+            "index must be non-negative".to_string()
+        } else {
+            match len {
+                Some(len) if n.cmp(&BigInt::from_u64(len)) != Ordering::Less => {
+                    format!("out of bounds for {}-element array", len)
+                }
+                _ => return,
+            }
+        };
+        let msg = format!("invalid index {} ({})", pretty_expr(i), reason);
+        self.error_msg(ET::InvalidIndex, &i.loc, msg);
+    }
+
+    fn selector(&mut self, e: &mut Expr) -> TypeId {
+        let (ty, field) = match e.kind {
+            ExprKind::Selector(ref mut x, ref field) => (self.value(x), field.clone()),
+            _ => return INVALID,
+        };
+        if ty == INVALID {
+            return INVALID;
+        }
+        if let TypeKind::Struct(ref fields) = *self.types.under(ty) {
+            if !field.is_blank() {
+                if let Some(&(_, field_ty)) = fields.iter().find(|f| f.0 == field.name) {
+                    return field_ty;
+                }
+            }
+        }
+        let msg = format!("{} undefined (type {} has no field {})",
+                          pretty_expr(e), self.name(ty), field.name);
+        self.error_msg(ET::UnknownField, &field.loc, msg);
+        return INVALID;
+    }
+
+    fn append(&mut self, e: &mut Expr) -> TypeId {
+        match e.kind {
+            ExprKind::Append(ref mut s, ref mut x) => {
+                let ty = self.value(s);
+                self.value(x);
+                if ty == INVALID {
+                    return INVALID;
+                }
+                let elem = match *self.types.under(ty) {
+                    TypeKind::Slice(elem) => elem,
+                    _ => {
+                        let msg = format!("invalid argument {} for append: not a slice",
+                                          self.describe(s));
+                        self.error_msg(ET::InvalidArgument, &s.loc, msg);
+                        return INVALID;
+                    }
+                };
+                self.assign(x, elem, "append");
+                return ty;
+            }
+            _ => return INVALID,
+        }
+    }
+}
+
+
+/// Evaluates a binary operation on constant operands, which
+/// have already been checked; division by zero and overlong
+/// shifts have been reported.
+fn fold(op: BinOp, x: &Value, y: &Value) -> Option<Value> {
+    match op {
+        BinOp::Shl | BinOp::Shr => {
+            let x = x.to_int()?;
+            let count = y.to_int()?.to_i64()?;
+            if count < 0 || count as u64 > MAX_SHIFT {
+                return None;
+            }
+            let count = count as usize;
+            if op == BinOp::Shl {
+                return Some(Value::Int(x.shl(count)));
+            } else {
+                return Some(Value::Int(x.shr(count)));
+            }
+        }
+        _ => return constant::binary_op(op, x, y),
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use symtab::SymbolId;
+
+pub type TypeId = usize;
+
+// The predeclared types, in the order in which
+// `TypeTable::new` creates them.
+pub const INT: TypeId = 0;
+pub const FLOAT64: TypeId = 1;
+pub const BOOL: TypeId = 2;
+pub const RUNE: TypeId = 3;
+pub const STRING: TypeId = 4;
+pub const UNTYPED_INT: TypeId = 5;
+pub const UNTYPED_FLOAT: TypeId = 6;
+pub const UNTYPED_RUNE: TypeId = 7;
+pub const UNTYPED_BOOL: TypeId = 8;
+pub const UNTYPED_STRING: TypeId = 9;
+/// The "type" of a call to a function without a result.
+pub const VOID: TypeId = 10;
+/// The type of erroneous expressions; no error is reported
+/// for an operation involving an invalid operand, so a
+/// mistake is only reported once.
+pub const INVALID: TypeId = 11;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Int,
+    Float64,
+    Bool,
+    Rune,
+    String,
+    UntypedInt,
+    UntypedFloat,
+    UntypedRune,
+    UntypedBool,
+    UntypedString,
+    Void,
+    Invalid,
+    Array(u64, TypeId),
+    Slice(TypeId),
+    Struct(Vec<(String, TypeId)>),
+    /// Parameter types and result type (`VOID` if none).
+    Func(Vec<TypeId>, TypeId),
+    /// A type declared with `type T U`; its underlying
+    /// type is kept in the table.
+    Named(String, SymbolId),
+}
+
+/// The types of a program.
+///
+/// Types are interned: two type literals with the same structure
+/// get the same id, so that type identity is id equality.  Named
+/// types are never merged, since every `type` declaration creates
+/// a new type that is distinct from all others.
+#[derive(Debug, Clone)]
+pub struct TypeTable {
+    kinds: Vec<TypeKind>,
+    underlying: Vec<TypeId>,
+    index: HashMap<TypeKind, TypeId>,
+}
+
+impl Default for TypeTable {
+    fn default() -> TypeTable {
+        TypeTable::new()
+    }
+}
+
+impl TypeTable {
+    pub fn new() -> TypeTable {
+        let mut table = TypeTable {
+            kinds: Vec::new(),
+            underlying: Vec::new(),
+            index: HashMap::new(),
+        };
+        for kind in vec![
+            TypeKind::Int, TypeKind::Float64, TypeKind::Bool, TypeKind::Rune,
+            TypeKind::String, TypeKind::UntypedInt, TypeKind::UntypedFloat,
+            TypeKind::UntypedRune, TypeKind::UntypedBool, TypeKind::UntypedString,
+            TypeKind::Void, TypeKind::Invalid,
+        ] {
+            table.intern(kind);
+        }
+        return table;
+    }
+
+    /// Returns the id of a (non-named) type, creating it if needed.
+    pub fn intern(&mut self, kind: TypeKind) -> TypeId {
+        if let Some(&id) = self.index.get(&kind) {
+            return id;
+        }
+        let id = self.kinds.len();
+        self.kinds.push(kind.clone());
+        self.underlying.push(id);
+        self.index.insert(kind, id);
+        return id;
+    }
+
+    /// Creates a new named type.  Its underlying type is
+    /// `INVALID` until `set_underlying` is called, which
+    /// allows recursive types such as `type t []t`.
+    pub fn named(&mut self, name: &str, sym: SymbolId) -> TypeId {
+        let id = self.kinds.len();
+        self.kinds.push(TypeKind::Named(name.to_string(), sym));
+        self.underlying.push(INVALID);
+        return id;
+    }
+
+    pub fn set_underlying(&mut self, named: TypeId, underlying: TypeId) {
+        self.underlying[named] = self.underlying[underlying];
+    }
+
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn kind(&self, id: TypeId) -> &TypeKind {
+        &self.kinds[id]
+    }
+
+    /// Returns the underlying type: the type itself for all
+    /// but named types.
+    pub fn underlying(&self, id: TypeId) -> TypeId {
+        self.underlying[id]
+    }
+
+    /// Returns the kind of the underlying type.
+    pub fn under(&self, id: TypeId) -> &TypeKind {
+        &self.kinds[self.underlying[id]]
+    }
+
+    /// Returns the type as it is written in GoLite.
+    pub fn name(&self, id: TypeId) -> String {
+        match self.kinds[id] {
+            TypeKind::Int => "int".to_string(),
+            TypeKind::Float64 => "float64".to_string(),
+            TypeKind::Bool => "bool".to_string(),
+            TypeKind::Rune => "rune".to_string(),
+            TypeKind::String => "string".to_string(),
+            TypeKind::UntypedInt => "untyped int".to_string(),
+            TypeKind::UntypedFloat => "untyped float".to_string(),
+            TypeKind::UntypedRune => "untyped rune".to_string(),
+            TypeKind::UntypedBool => "untyped bool".to_string(),
+            TypeKind::UntypedString => "untyped string".to_string(),
+            TypeKind::Void => "no value".to_string(),
+            TypeKind::Invalid => "invalid type".to_string(),
+            TypeKind::Array(len, elem) => format!("[{}]{}", len, self.name(elem)),
+            TypeKind::Slice(elem) => format!("[]{}", self.name(elem)),
+            TypeKind::Struct(ref fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|&(ref name, ty)| format!("{} {}", name, self.name(ty)))
+                    .collect();
+                if fields.is_empty() {
+                    "struct{}".to_string()
+                } else {
+                    format!("struct {{ {} }}", fields.join("; "))
+                }
+            }
+            TypeKind::Func(ref params, result) => {
+                let params: Vec<String> = params.iter().map(|&p| self.name(p)).collect();
+                if result == VOID {
+                    format!("func({})", params.join(", "))
+                } else {
+                    format!("func({}) {}", params.join(", "), self.name(result))
+                }
+            }
+            TypeKind::Named(ref name, _) => name.clone(),
+        }
+    }
+
+    /// Returns whether the type has a name: the predeclared
+    /// types are defined types, just like declared ones.
+    pub fn is_defined(&self, id: TypeId) -> bool {
+        match self.kinds[id] {
+            TypeKind::Int
+            | TypeKind::Float64
+            | TypeKind::Bool
+            | TypeKind::Rune
+            | TypeKind::String
+            | TypeKind::Named(_, _) => true,
+            _ => false,
+        }
+    }
+
+
+    // PREDICATES
+    // All predicates look at the underlying type, so that
+    // e.g. a named type defined as `int` is an integer type.
+
+    pub fn is_untyped(&self, id: TypeId) -> bool {
+        match self.kinds[id] {
+            TypeKind::UntypedInt
+            | TypeKind::UntypedFloat
+            | TypeKind::UntypedRune
+            | TypeKind::UntypedBool
+            | TypeKind::UntypedString => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_integer(&self, id: TypeId) -> bool {
+        match *self.under(id) {
+            TypeKind::Int | TypeKind::Rune | TypeKind::UntypedInt | TypeKind::UntypedRune => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_float(&self, id: TypeId) -> bool {
+        match *self.under(id) {
+            TypeKind::Float64 | TypeKind::UntypedFloat => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_numeric(&self, id: TypeId) -> bool {
+        self.is_integer(id) || self.is_float(id)
+    }
+
+    pub fn is_string(&self, id: TypeId) -> bool {
+        match *self.under(id) {
+            TypeKind::String | TypeKind::UntypedString => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_bool(&self, id: TypeId) -> bool {
+        match *self.under(id) {
+            TypeKind::Bool | TypeKind::UntypedBool => true,
+            _ => false,
+        }
+    }
+
+    /// Returns whether the type is one of the base types
+    /// (possibly through a named type).
+    pub fn is_basic(&self, id: TypeId) -> bool {
+        self.is_numeric(id) || self.is_string(id) || self.is_bool(id)
+    }
+
+    /// Types whose values can be compared with `<`, `<=`, `>`, `>=`.
+    pub fn is_ordered(&self, id: TypeId) -> bool {
+        self.is_numeric(id) || self.is_string(id)
+    }
+
+    /// Types whose values can be compared with `==` and `!=`.
+    /// Ref.: https://golang.org/ref/spec#Comparison_operators
+    pub fn is_comparable(&self, id: TypeId) -> bool {
+        match *self.under(id) {
+            TypeKind::Array(_, elem) => self.is_comparable(elem),
+            TypeKind::Struct(ref fields) => fields.iter().all(|&(_, ty)| self.is_comparable(ty)),
+            TypeKind::Slice(_) | TypeKind::Func(_, _) | TypeKind::Void => false,
+            _ => true,
+        }
+    }
+
+    /// Returns the type that an untyped constant takes when
+    /// the context does not impose one; typed types are
+    /// returned unchanged.
+    pub fn default_type(&self, id: TypeId) -> TypeId {
+        match self.kinds[id] {
+            TypeKind::UntypedInt => INT,
+            TypeKind::UntypedFloat => FLOAT64,
+            TypeKind::UntypedRune => RUNE,
+            TypeKind::UntypedBool => BOOL,
+            TypeKind::UntypedString => STRING,
+            _ => id,
+        }
+    }
+
+    /// Returns whether a value of type `v` may be assigned to
+    /// a variable of type `t`.  Whether an untyped constant is
+    /// representable in `t` is checked separately.
+    /// Ref.: https://golang.org/ref/spec#Assignability
+    pub fn is_assignable(&self, v: TypeId, t: TypeId) -> bool {
+        if v == t || v == INVALID || t == INVALID {
+            return true;
+        }
+        if self.is_untyped(v) {
+            return match self.kinds[v] {
+                TypeKind::UntypedBool => self.is_bool(t),
+                TypeKind::UntypedString => self.is_string(t),
+                _ => self.is_numeric(t),
+            };
+        }
+        return self.underlying(v) == self.underlying(t) && (!self.is_defined(v) || !self.is_defined(t));
+    }
+
+    /// Returns whether a value of type `v` can be explicitly
+    /// converted to type `t`.
+    /// Ref.: https://golang.org/ref/spec#Conversions
+    pub fn is_convertible(&self, v: TypeId, t: TypeId) -> bool {
+        self.is_assignable(v, t)
+            || self.underlying(v) == self.underlying(t)
+            || (self.is_numeric(v) && self.is_numeric(t))
+            || (self.is_integer(v) && self.is_string(t))
+    }
+
+    /// Returns the untyped type of higher rank: when untyped
+    /// numeric constants are combined, the result is a float
+    /// if either operand is, otherwise a rune if either is.
+    pub fn untyped_max(&self, a: TypeId, b: TypeId) -> TypeId {
+        let rank = |id: TypeId| match id {
+            UNTYPED_INT => 1,
+            UNTYPED_RUNE => 2,
+            UNTYPED_FLOAT => 3,
+            _ => 0,
+        };
+        if rank(a) >= rank(b) { a } else { b }
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashSet;
+use std::mem;
+
+use ast::*;
+use constant::Value;
+use error::Error;
+use error::ErrorType as ET;
+use loc::Loc;
+use symtab::{ScopeKind, SymbolId, SymbolKind, SymbolTable};
+use types::TypeTable;
+
+/// The local variables that may be read before they are assigned.
+type Live = HashSet<SymbolId>;
+
+/// Finds the local variables that are declared and never used,
+/// which Go rejects, and reports them as errors, or as warnings
+/// if `warn_unused` is set.  As in Go, assigning to a variable
+/// with `=` or `:=` does not use it, but updating it with `++` or
+/// `+=` does, and so does assigning to one of its elements or
+/// fields.  The blank identifier declares nothing.
+///
+/// Also warns about the parameters that are never used, and about
+/// the ineffectual assignments: those whose value is assigned
+/// again, or goes out of scope, before it is ever read.
+pub fn check(prog: &Program, table: &SymbolTable, warn_unused: bool) -> Vec<Error> {
+    let mut errors = Vec::new();
+    for decl in &prog.decls {
+        if let TopLevelDecl::Func(ref f) = *decl {
+            let mut checker = Checker::new(table);
+            checker.collect_stmts(&f.body.stmts);
+            for name in &checker.declared {
+                if let Some(sym) = name.sym {
+                    if !checker.used.contains(&sym) {
+                        let msg = format!("declared and not used: {}", name.name);
+                        let mut error = Error::with_msg(ET::UnusedVariable, name.loc.clone(), msg);
+                        error.warning = warn_unused;
+                        errors.push(error);
+                    }
+                }
+            }
+            for param in &f.params {
+                if let Some(sym) = param.name.sym {
+                    if !checker.used.contains(&sym) {
+                        let msg = format!("unused parameter: {}", param.name.name);
+                        errors.push(warning(ET::UnusedParameter, &param.name.loc, msg));
+                    }
+                }
+            }
+            checker.stmts(&f.body.stmts, Live::new());
+            let mut ineffectual = checker.ineffectual;
+            ineffectual.sort_by_key(|ineffectual| (ineffectual.0.line, ineffectual.0.col));
+            ineffectual.dedup_by(|a, b| a.0 == b.0);
+            for (loc, name) in ineffectual {
+                let msg = format!("ineffectual assignment to {}", name);
+                errors.push(warning(ET::IneffectualAssignment, &loc, msg));
+            }
+        }
+    }
+    errors.sort_by_key(|error| (error.loc.line, error.loc.col));
+    return errors;
+}
+
+/// Removes the assignments of values that are never read, when
+/// computing them has no effect: the ineffectual assignments, the
+/// assignments to the blank identifier, and those to variables
+/// that are never used (which are only warnings with
+/// `--warn-unused`).  A declaration whose values are removed
+/// declares its variables with their zero values instead.
+///
+/// Calls, appends and the operations that may panic have effects:
+/// indexing, and the integer divisions and shifts whose divisor or
+/// count is not a constant that makes them safe.  The statements
+/// that initialize `if`, `switch` and `for` statements, and the
+/// post statements of loops, are kept as they are.
+pub fn eliminate(prog: &mut Program, table: &SymbolTable, types: &TypeTable) {
+    for decl in &mut prog.decls {
+        if let TopLevelDecl::Func(ref mut f) = *decl {
+            // Removing an assignment may make the values that it
+            // reads dead in turn.
+            loop {
+                let mut checker = Checker::new(table);
+                checker.collect_stmts(&f.body.stmts);
+                checker.stmts(&f.body.stmts, Live::new());
+                let mut eliminator = Eliminator { checker: checker, types: types, changed: false };
+                eliminator.stmts(&mut f.body.stmts);
+                if !eliminator.changed {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn warning(ty: ET, loc: &Loc, msg: String) -> Error {
+    let mut error = Error::with_msg(ty, loc.clone(), msg);
+    error.warning = true;
+    return error;
+}
+
+struct Checker<'a> {
+    table: &'a SymbolTable,
+    /// The local variables that are used, and those that the body
+    /// of the function declares, in order.
+    used: HashSet<SymbolId>,
+    declared: Vec<Ident>,
+    /// What is live where the enclosing loops and switch statements
+    /// are left by `break`, and where the enclosing loops continue.
+    targets: Vec<(Live, Option<Live>)>,
+    /// Whether ineffectual assignments are recorded; they are not
+    /// while the liveness of a loop is still being computed.
+    report: bool,
+    ineffectual: Vec<(Loc, String)>,
+    /// The locations of the names to which a value is assigned
+    /// that is never read.
+    dead: Vec<Loc>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(table: &'a SymbolTable) -> Checker<'a> {
+        return Checker {
+            table: table,
+            used: HashSet::new(),
+            declared: Vec::new(),
+            targets: Vec::new(),
+            report: true,
+            ineffectual: Vec::new(),
+            dead: Vec::new(),
+        };
+    }
+
+    /// Returns the local variable that `id` denotes, if any.
+    fn local(&self, id: &Ident) -> Option<SymbolId> {
+        let sym = id.sym?;
+        let symbol = self.table.symbol(sym);
+        match self.table.scope(symbol.scope).kind {
+            ScopeKind::Function(_) | ScopeKind::Block if symbol.kind == SymbolKind::Var => return Some(sym),
+            _ => return None,
+        }
+    }
+
+    /// Whether `id` declares its variable rather than assigning to
+    /// a variable of the same scope, as `:=` may.
+    fn declares(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => return self.table.symbol(sym).loc.as_ref() == Some(&id.loc),
+            None => return false,
+        }
+    }
+
+
+    // USES
+
+    fn collect_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.collect_stmt(stmt);
+        }
+    }
+
+    fn collect_stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.collect_stmts(&block.stmts),
+            StmtKind::Expr(ref e) => self.collect_expr(e),
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.collect_exprs(&spec.values);
+                    self.declared.extend(spec.names.iter().filter(|name| name.sym.is_some()).cloned());
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => {
+                for e in lhs {
+                    if let ExprKind::Id(_) = e.kind {
+                        continue;
+                    }
+                    self.collect_expr(e);
+                }
+                self.collect_exprs(rhs);
+            }
+            StmtKind::OpAssign(_, ref lhs, ref rhs) => {
+                self.collect_expr(lhs);
+                self.collect_expr(rhs);
+            }
+            StmtKind::Incr(ref e) | StmtKind::Decr(ref e) => self.collect_expr(e),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                self.collect_exprs(values);
+                let declared: Vec<Ident> = names.iter().filter(|name| self.declares(name)).cloned().collect();
+                self.declared.extend(declared);
+            }
+            StmtKind::Print(ref args) | StmtKind::Println(ref args) => self.collect_exprs(args),
+            StmtKind::Return(ref e) => {
+                if let Some(ref e) = *e {
+                    self.collect_expr(e);
+                }
+            }
+            StmtKind::If(ref if_stmt) => {
+                if let Some(ref init) = if_stmt.init {
+                    self.collect_stmt(init);
+                }
+                self.collect_expr(&if_stmt.cond);
+                self.collect_stmts(&if_stmt.then.stmts);
+                if let Some(ref els) = if_stmt.els {
+                    self.collect_stmt(els);
+                }
+            }
+            StmtKind::Switch(ref switch_stmt) => {
+                if let Some(ref init) = switch_stmt.init {
+                    self.collect_stmt(init);
+                }
+                if let Some(ref tag) = switch_stmt.tag {
+                    self.collect_expr(tag);
+                }
+                for clause in &switch_stmt.clauses {
+                    if let Some(ref exprs) = clause.exprs {
+                        self.collect_exprs(exprs);
+                    }
+                    self.collect_stmts(&clause.body);
+                }
+            }
+            StmtKind::For(ref for_stmt) => {
+                if let Some(ref init) = for_stmt.init {
+                    self.collect_stmt(init);
+                }
+                if let Some(ref cond) = for_stmt.cond {
+                    self.collect_expr(cond);
+                }
+                if let Some(ref post) = for_stmt.post {
+                    self.collect_stmt(post);
+                }
+                self.collect_stmts(&for_stmt.body.stmts);
+            }
+        }
+    }
+
+    fn collect_exprs(&mut self, exprs: &[Expr]) {
+        for e in exprs {
+            self.collect_expr(e);
+        }
+    }
+
+    fn collect_expr(&mut self, e: &Expr) {
+        let mut uses = Live::new();
+        self.uses(e, &mut uses);
+        self.used.extend(uses);
+    }
+
+    /// Adds the local variables that `e` reads to `live`.
+    fn uses(&self, e: &Expr, live: &mut Live) {
+        match e.kind {
+            ExprKind::Id(ref id) => {
+                if let Some(sym) = self.local(id) {
+                    live.insert(sym);
+                }
+            }
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => {}
+            ExprKind::Unary(_, ref operand) => self.uses(operand, live),
+            ExprKind::Binary(_, ref lhs, ref rhs)
+            | ExprKind::Index(ref lhs, ref rhs)
+            | ExprKind::Append(ref lhs, ref rhs) => {
+                self.uses(lhs, live);
+                self.uses(rhs, live);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                self.uses(f, live);
+                for arg in args {
+                    self.uses(arg, live);
+                }
+            }
+            ExprKind::Selector(ref e, _) => self.uses(e, live),
+        }
+    }
+
+
+    // LIVENESS
+
+    /// Returns what is live before `stmts`, given what is live
+    /// after them.
+    fn stmts(&mut self, stmts: &[Stmt], mut live: Live) -> Live {
+        for stmt in stmts.iter().rev() {
+            live = self.stmt(stmt, live);
+        }
+        return live;
+    }
+
+    /// Records the assignment of a value to `id`, which is
+    /// ineffectual if the variable is not live after it, unless
+    /// it is never used at all and already reported as such.
+    fn assign(&mut self, id: &Ident, live: &mut Live) {
+        if let Some(sym) = self.local(id) {
+            if self.report && !live.contains(&sym) {
+                self.dead.push(id.loc.clone());
+                if self.used.contains(&sym) {
+                    self.ineffectual.push((id.loc.clone(), id.name.clone()));
+                }
+            }
+            live.remove(&sym);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, mut live: Live) -> Live {
+        match stmt.kind {
+            StmtKind::Block(ref block) => return self.stmts(&block.stmts, live),
+            StmtKind::Expr(ref e) | StmtKind::Incr(ref e) | StmtKind::Decr(ref e) => self.uses(e, &mut live),
+            StmtKind::Var(ref specs) => {
+                for spec in specs.iter().rev() {
+                    for name in &spec.names {
+                        match self.local(name) {
+                            Some(sym) if spec.values.is_empty() => {
+                                live.remove(&sym);
+                            }
+                            _ => self.assign(name, &mut live),
+                        }
+                    }
+                    for e in &spec.values {
+                        self.uses(e, &mut live);
+                    }
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => {
+                for e in lhs {
+                    if let ExprKind::Id(ref id) = e.kind {
+                        self.assign(id, &mut live);
+                    }
+                }
+                for e in lhs {
+                    if let ExprKind::Id(_) = e.kind {
+                        continue;
+                    }
+                    self.uses(e, &mut live);
+                }
+                for e in rhs {
+                    self.uses(e, &mut live);
+                }
+            }
+            StmtKind::OpAssign(_, ref lhs, ref rhs) => {
+                self.uses(lhs, &mut live);
+                self.uses(rhs, &mut live);
+            }
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                for name in names {
+                    self.assign(name, &mut live);
+                }
+                for e in values {
+                    self.uses(e, &mut live);
+                }
+            }
+            StmtKind::Print(ref args) | StmtKind::Println(ref args) => {
+                for e in args {
+                    self.uses(e, &mut live);
+                }
+            }
+            StmtKind::Return(ref e) => {
+                live = Live::new();
+                if let Some(ref e) = *e {
+                    self.uses(e, &mut live);
+                }
+            }
+            StmtKind::Break => {
+                if let Some(target) = self.targets.last() {
+                    live = target.0.clone();
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(next) = self.targets.iter().rev().find_map(|target| target.1.as_ref()) {
+                    live = next.clone();
+                }
+            }
+            StmtKind::If(ref if_stmt) => {
+                let els = match if_stmt.els {
+                    Some(ref els) => self.stmt(els, live.clone()),
+                    None => live.clone(),
+                };
+                live = self.stmts(&if_stmt.then.stmts, live);
+                live.extend(els);
+                self.uses(&if_stmt.cond, &mut live);
+                if let Some(ref init) = if_stmt.init {
+                    live = self.stmt(init, live);
+                }
+            }
+            StmtKind::Switch(ref switch_stmt) => {
+                // The cases are tested in order until one matches,
+                // and the default clause runs if none does.
+                self.targets.push((live.clone(), None));
+                let bodies: Vec<Live> = switch_stmt.clauses.iter()
+                    .map(|clause| self.stmts(&clause.body, live.clone())).collect();
+                self.targets.pop();
+                let default = switch_stmt.clauses.iter().position(|clause| clause.exprs.is_none());
+                let mut tests = match default {
+                    Some(i) => bodies[i].clone(),
+                    None => live,
+                };
+                for (clause, body) in switch_stmt.clauses.iter().zip(bodies).rev() {
+                    if let Some(ref exprs) = clause.exprs {
+                        tests.extend(body);
+                        for e in exprs {
+                            self.uses(e, &mut tests);
+                        }
+                    }
+                }
+                live = tests;
+                if let Some(ref tag) = switch_stmt.tag {
+                    self.uses(tag, &mut live);
+                }
+                if let Some(ref init) = switch_stmt.init {
+                    live = self.stmt(init, live);
+                }
+            }
+            StmtKind::For(ref for_stmt) => {
+                live = self.for_stmt(for_stmt, live);
+                if let Some(ref init) = for_stmt.init {
+                    live = self.stmt(init, live);
+                }
+            }
+        }
+        return live;
+    }
+
+    /// Returns what is live before the condition of a loop: the
+    /// liveness of the body is computed again until it does not
+    /// change, without recording anything, and once more to
+    /// record the ineffectual assignments.
+    fn for_stmt(&mut self, for_stmt: &ForStmt, exit: Live) -> Live {
+        let report = self.report;
+        self.report = false;
+        let mut header = Live::new();
+        loop {
+            let next = self.loop_iteration(for_stmt, &exit, &header);
+            if next == header {
+                break;
+            }
+            header = next;
+        }
+        self.report = report;
+        return self.loop_iteration(for_stmt, &exit, &header);
+    }
+
+    /// Returns what is live before the condition of a loop, given
+    /// what is live after the loop and before its condition.
+    fn loop_iteration(&mut self, for_stmt: &ForStmt, exit: &Live, header: &Live) -> Live {
+        let post = match for_stmt.post {
+            Some(ref post) => self.stmt(post, header.clone()),
+            None => header.clone(),
+        };
+        self.targets.push((exit.clone(), Some(post.clone())));
+        let mut live = self.stmts(&for_stmt.body.stmts, post);
+        self.targets.pop();
+        if let Some(ref cond) = for_stmt.cond {
+            live.extend(exit.iter().cloned());
+            self.uses(cond, &mut live);
+        }
+        return live;
+    }
+}
+
+
+// ELIMINATION
+
+struct Eliminator<'a> {
+    /// The checker that found the dead assignments.
+    checker: Checker<'a>,
+    types: &'a TypeTable,
+    /// Whether an assignment was removed.
+    changed: bool,
+}
+
+impl<'a> Eliminator<'a> {
+    fn stmts(&mut self, stmts: &mut Vec<Stmt>) {
+        stmts.retain_mut(|stmt| self.stmt(stmt));
+    }
+
+    /// Removes the dead assignments of `stmt` and of the statements
+    /// that it contains; returns whether anything is left of it.
+    fn stmt(&mut self, stmt: &mut Stmt) -> bool {
+        match stmt.kind {
+            StmtKind::Block(ref mut block) => self.stmts(&mut block.stmts),
+            StmtKind::Assign(ref mut lhs, ref mut rhs) => {
+                let rhs_len = rhs.len();
+                let (kept_lhs, kept_rhs): (Vec<Expr>, Vec<Expr>) = mem::take(lhs).into_iter()
+                    .zip(mem::take(rhs))
+                    .filter(|(x, value)| !self.is_dead(x) || !self.is_pure(value))
+                    .unzip();
+                self.changed |= kept_lhs.len() < rhs_len;
+                if kept_lhs.is_empty() {
+                    return false;
+                }
+                *lhs = kept_lhs;
+                *rhs = kept_rhs;
+            }
+            StmtKind::Var(ref mut specs) => {
+                for spec in specs.iter_mut() {
+                    if self.is_dead_decl(&spec.names, &spec.values) {
+                        spec.values.clear();
+                        self.changed = true;
+                    }
+                }
+                specs.retain(|spec| !spec.values.is_empty() || !spec.names.iter().all(|name| name.is_blank()));
+                return !specs.is_empty();
+            }
+            StmtKind::ShortVarDecl(ref names, ref values)
+                if self.is_dead_decl(names, values)
+                    && names.iter().all(|name| name.is_blank() || self.checker.declares(name)) => {
+                let spec = VarSpec { names: names.clone(), ty: None, values: Vec::new(), loc: stmt.loc.clone() };
+                stmt.kind = StmtKind::Var(vec![spec]);
+                self.changed = true;
+            }
+            StmtKind::If(ref mut if_stmt) => {
+                self.stmts(&mut if_stmt.then.stmts);
+                if let Some(ref mut els) = if_stmt.els {
+                    self.stmt(els);
+                }
+            }
+            StmtKind::Switch(ref mut switch_stmt) => {
+                for clause in &mut switch_stmt.clauses {
+                    self.stmts(&mut clause.body);
+                }
+            }
+            StmtKind::For(ref mut for_stmt) => self.stmts(&mut for_stmt.body.stmts),
+            _ => {}
+        }
+        return true;
+    }
+
+    /// Whether the value assigned to `e` is never read.
+    fn is_dead(&self, e: &Expr) -> bool {
+        match e.kind {
+            ExprKind::Blank => return true,
+            ExprKind::Id(ref id) => return self.checker.dead.contains(&id.loc),
+            _ => return false,
+        }
+    }
+
+    /// Whether the values of a declaration can be left out: none of
+    /// them is ever read, and computing them has no effect.
+    fn is_dead_decl(&self, names: &[Ident], values: &[Expr]) -> bool {
+        return !values.is_empty()
+            && names.iter().all(|name| name.is_blank() || self.checker.dead.contains(&name.loc))
+            && values.iter().all(|value| self.is_pure(value));
+    }
+
+    /// Whether evaluating `e` has no effect and cannot panic.
+    fn is_pure(&self, e: &Expr) -> bool {
+        if e.value.is_some() {
+            return true;
+        }
+        match e.kind {
+            ExprKind::Blank
+            | ExprKind::Id(_)
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => return true,
+            ExprKind::Unary(_, ref x) | ExprKind::Selector(ref x, _) => return self.is_pure(x),
+            ExprKind::Binary(op, ref x, ref y) => {
+                let integer = e.ty.is_some_and(|ty| self.types.is_integer(ty));
+                let safe = match (op, &y.value) {
+                    (BinOp::Div, &Some(Value::Int(ref n))) | (BinOp::Rem, &Some(Value::Int(ref n))) => !n.is_zero(),
+                    (BinOp::Shl, &Some(Value::Int(ref n))) | (BinOp::Shr, &Some(Value::Int(ref n))) => !n.is_negative(),
+                    (BinOp::Div, _) | (BinOp::Rem, _) | (BinOp::Shl, _) | (BinOp::Shr, _) => !integer,
+                    _ => true,
+                };
+                return safe && self.is_pure(x) && self.is_pure(y);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.checker.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                match kind {
+                    SymbolKind::Type | SymbolKind::Builtin => return args.iter().all(|arg| self.is_pure(arg)),
+                    _ => return false,
+                }
+            }
+            ExprKind::Index(_, _) | ExprKind::Append(_, _) => return false,
+        }
+    }
+}
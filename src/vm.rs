@@ -0,0 +1,515 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::result;
+
+use bytecode::{Const, Function, Layout, Module, Op};
+use constant;
+use interp::{self, RuntimeError};
+use loc::Loc;
+
+/// The maximum depth of nested calls.  Frames are kept on the
+/// heap, so the limit is much higher than the interpreter's.
+pub const MAX_FRAMES: usize = 1 << 20;
+
+type Result<T> = result::Result<T, RuntimeError>;
+
+/// A value of the machine.  Strings are immutable and shared;
+/// arrays and structs are mutable heap objects, and slices
+/// share the backing array that they refer to.
+#[derive(Debug, Clone)]
+enum Val {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<Vec<u8>>),
+    /// The backing array (whose length is the capacity) and
+    /// the length of a slice.
+    Slice(Rc<RefCell<Vec<Val>>>, usize),
+    /// The elements of an array or the fields of a struct.
+    Agg(Rc<RefCell<Vec<Val>>>),
+}
+
+/// The call that a frame returns to.
+struct Frame {
+    func: usize,
+    pc: usize,
+    base: usize,
+}
+
+/// Executes a compiled program, writing what it prints to `out`.
+pub fn run(module: &Module, out: &mut dyn Write) -> Result<()> {
+    let constants = module.constants.iter()
+        .map(|c| match *c {
+            Const::Int(n) => Val::Int(n),
+            Const::Float(f) => Val::Float(f),
+            Const::Bool(b) => Val::Bool(b),
+            Const::Str(ref s) => Val::Str(Rc::new(s.clone())),
+        })
+        .collect();
+    let mut vm = Vm {
+        module: module,
+        constants: constants,
+        globals: vec![Val::Int(0); module.globals as usize],
+        stack: Vec::with_capacity(1024),
+        frames: Vec::new(),
+        out: out,
+    };
+    return vm.execute();
+}
+
+struct Vm<'a> {
+    module: &'a Module,
+    constants: Vec<Val>,
+    globals: Vec<Val>,
+    /// The local variables and operands of the active calls.
+    stack: Vec<Val>,
+    frames: Vec<Frame>,
+    out: &'a mut dyn Write,
+}
+
+impl<'a> Vm<'a> {
+    fn pop(&mut self) -> Val {
+        return self.stack.pop().unwrap_or(Val::Int(0));
+    }
+
+    fn pop_int(&mut self) -> i64 {
+        match self.stack.pop() {
+            Some(Val::Int(n)) => return n,
+            _ => return 0,
+        }
+    }
+
+    fn pop_float(&mut self) -> f64 {
+        match self.stack.pop() {
+            Some(Val::Float(f)) => return f,
+            _ => return 0.0,
+        }
+    }
+
+    fn pop_bool(&mut self) -> bool {
+        match self.stack.pop() {
+            Some(Val::Bool(b)) => return b,
+            _ => return false,
+        }
+    }
+
+    /// Returns the runtime error `msg` at the instruction
+    /// before `pc`.
+    fn error<T>(&self, func: &Function, pc: usize, msg: String) -> Result<T> {
+        let pos = func.positions.get(pc.wrapping_sub(1)).cloned();
+        let (line, col) = pos.map_or((0, 0), |pos| (pos.line as usize, pos.col as usize));
+        return Err(RuntimeError { msg: msg, loc: Loc::new(&self.module.filename, line, col) });
+    }
+
+    fn execute(&mut self) -> Result<()> {
+        let module = self.module;
+        let mut index = module.entry as usize;
+        let mut func = &module.functions[index];
+        let mut pc = 0;
+        let mut base = 0;
+        self.stack.resize(func.locals as usize, Val::Int(0));
+
+        macro_rules! int_op {
+            ($x:ident, $y:ident, $e:expr) => {{
+                let $y = self.pop_int();
+                let $x = self.pop_int();
+                self.stack.push(Val::Int($e));
+            }};
+        }
+        macro_rules! float_op {
+            ($x:ident, $y:ident, $e:expr) => {{
+                let $y = self.pop_float();
+                let $x = self.pop_float();
+                self.stack.push(Val::Float($e));
+            }};
+        }
+        macro_rules! compare {
+            ($op:tt) => {{
+                let y = self.pop();
+                let x = self.pop();
+                let result = match (x, y) {
+                    (Val::Int(x), Val::Int(y)) => x $op y,
+                    (Val::Float(x), Val::Float(y)) => x $op y,
+                    (Val::Str(x), Val::Str(y)) => x $op y,
+                    _ => false,
+                };
+                self.stack.push(Val::Bool(result));
+            }};
+        }
+
+        loop {
+            let op = match func.code.get(pc) {
+                Some(&op) => op,
+                None => return self.error(func, pc, "end of function reached".to_string()),
+            };
+            pc += 1;
+            match op {
+                Op::Const(k) => {
+                    let value = self.constants[k as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Zero(layout) => self.stack.push(zero(&module.layouts[layout as usize])),
+                Op::Pop => {
+                    self.stack.pop();
+                }
+                Op::Dup => {
+                    let x = self.pop();
+                    self.stack.push(x.clone());
+                    self.stack.push(x);
+                }
+                Op::Dup2 => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    self.stack.extend_from_slice(&[x.clone(), y.clone(), x, y]);
+                }
+                Op::LoadLocal(n) => {
+                    let value = self.stack[base + n as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::StoreLocal(n) => {
+                    let value = self.pop();
+                    assign(&mut self.stack[base + n as usize], value);
+                }
+                Op::LoadGlobal(n) => {
+                    let value = self.globals[n as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::StoreGlobal(n) => {
+                    let value = self.pop();
+                    assign(&mut self.globals[n as usize], value);
+                }
+                Op::Copy => {
+                    let value = self.pop();
+                    self.stack.push(copy(&value));
+                }
+
+                Op::Index => {
+                    let i = self.pop_int();
+                    let (elems, len) = match self.pop() {
+                        Val::Slice(elems, len) => (elems, len),
+                        Val::Agg(elems) => {
+                            let len = elems.borrow().len();
+                            (elems, len)
+                        }
+                        _ => return self.error(func, pc, invalid_operand(op)),
+                    };
+                    if i < 0 || i as u64 >= len as u64 {
+                        return self.error(func, pc, index_error(i, len));
+                    }
+                    let value = elems.borrow()[i as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetIndex => {
+                    let value = self.pop();
+                    let i = self.pop_int();
+                    let (elems, len) = match self.pop() {
+                        Val::Slice(elems, len) => (elems, len),
+                        Val::Agg(elems) => {
+                            let len = elems.borrow().len();
+                            (elems, len)
+                        }
+                        _ => return self.error(func, pc, invalid_operand(op)),
+                    };
+                    if i < 0 || i as u64 >= len as u64 {
+                        return self.error(func, pc, index_error(i, len));
+                    }
+                    assign(&mut elems.borrow_mut()[i as usize], value);
+                }
+                Op::Field(f) => {
+                    let value = match self.pop() {
+                        Val::Agg(ref fields) => fields.borrow().get(f as usize).cloned(),
+                        _ => None,
+                    };
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => return self.error(func, pc, invalid_operand(op)),
+                    }
+                }
+                Op::SetField(f) => {
+                    let value = self.pop();
+                    let fields = match self.pop() {
+                        Val::Agg(ref fields) if (f as usize) < fields.borrow().len() => fields.clone(),
+                        _ => return self.error(func, pc, invalid_operand(op)),
+                    };
+                    assign(&mut fields.borrow_mut()[f as usize], value);
+                }
+                Op::Len | Op::Cap => {
+                    let n = match self.pop() {
+                        Val::Str(s) => s.len(),
+                        Val::Agg(elems) => elems.borrow().len(),
+                        Val::Slice(elems, _) if op == Op::Cap => elems.borrow().len(),
+                        Val::Slice(_, len) => len,
+                        _ => 0,
+                    };
+                    self.stack.push(Val::Int(n as i64));
+                }
+                Op::Append => {
+                    let value = self.pop();
+                    match self.pop() {
+                        Val::Slice(elems, len) => self.stack.push(append(elems, len, value)),
+                        _ => return self.error(func, pc, invalid_operand(op)),
+                    }
+                }
+
+                Op::AddInt => int_op!(x, y, x.wrapping_add(y)),
+                Op::SubInt => int_op!(x, y, x.wrapping_sub(y)),
+                Op::MulInt => int_op!(x, y, x.wrapping_mul(y)),
+                Op::DivInt | Op::RemInt => {
+                    let y = self.pop_int();
+                    let x = self.pop_int();
+                    if y == 0 {
+                        return self.error(func, pc, "integer divide by zero".to_string());
+                    }
+                    let n = if op == Op::DivInt { x.wrapping_div(y) } else { x.wrapping_rem(y) };
+                    self.stack.push(Val::Int(n));
+                }
+                Op::NegInt => {
+                    let x = self.pop_int();
+                    self.stack.push(Val::Int(x.wrapping_neg()));
+                }
+                Op::Shl | Op::Shr => {
+                    let y = self.pop_int();
+                    let x = self.pop_int();
+                    if y < 0 {
+                        return self.error(func, pc, "negative shift amount".to_string());
+                    }
+                    let n = match op {
+                        Op::Shl if y >= 64 => 0,
+                        Op::Shl => ((x as u64) << y) as i64,
+                        _ => x >> y.min(63),
+                    };
+                    self.stack.push(Val::Int(n));
+                }
+                Op::BitAnd => int_op!(x, y, x & y),
+                Op::BitOr => int_op!(x, y, x | y),
+                Op::BitXor => int_op!(x, y, x ^ y),
+                Op::BitClear => int_op!(x, y, x & !y),
+                Op::BitNot => {
+                    let x = self.pop_int();
+                    self.stack.push(Val::Int(!x));
+                }
+                Op::Wrap32 => {
+                    let x = self.pop_int();
+                    self.stack.push(Val::Int(x as i32 as i64));
+                }
+                Op::AddFloat => float_op!(x, y, x + y),
+                Op::SubFloat => float_op!(x, y, x - y),
+                Op::MulFloat => float_op!(x, y, x * y),
+                Op::DivFloat => float_op!(x, y, x / y),
+                Op::NegFloat => {
+                    let x = self.pop_float();
+                    self.stack.push(Val::Float(-x));
+                }
+                Op::Concat => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    let mut s = Vec::new();
+                    for x in &[x, y] {
+                        match *x {
+                            Val::Str(ref x) => s.extend_from_slice(x),
+                            _ => return self.error(func, pc, invalid_operand(op)),
+                        }
+                    }
+                    self.stack.push(Val::Str(Rc::new(s)));
+                }
+                Op::Not => {
+                    let x = self.pop_bool();
+                    self.stack.push(Val::Bool(!x));
+                }
+
+                Op::Eq | Op::Ne => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    let eq = equal(None, &x, &y);
+                    self.stack.push(Val::Bool(if op == Op::Eq { eq } else { !eq }));
+                }
+                Op::Lt => compare!(<),
+                Op::Le => compare!(<=),
+                Op::Gt => compare!(>),
+                Op::Ge => compare!(>=),
+                Op::EqAgg(layout) => {
+                    let y = self.pop();
+                    let x = self.pop();
+                    let eq = equal(Some(&module.layouts[layout as usize]), &x, &y);
+                    self.stack.push(Val::Bool(eq));
+                }
+
+                Op::IntToFloat => {
+                    let x = self.pop_int();
+                    self.stack.push(Val::Float(x as f64));
+                }
+                Op::FloatToInt => {
+                    let x = self.pop_float();
+                    self.stack.push(Val::Int(x as i64));
+                }
+                Op::RuneToString => {
+                    let x = self.pop_int();
+                    // Invalid code points become U+FFFD.
+                    let c = if x >= 0 && x <= u32::MAX as i64 { char::from_u32(x as u32) } else { None };
+                    let s = constant::encode_rune(c.unwrap_or('\u{fffd}'));
+                    self.stack.push(Val::Str(Rc::new(constant::string_bytes(&s))));
+                }
+
+                Op::Jump(target) => pc = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !self.pop_bool() {
+                        pc = target as usize;
+                    }
+                }
+                Op::JumpIfTrue(target) => {
+                    if self.pop_bool() {
+                        pc = target as usize;
+                    }
+                }
+                Op::Call(f) => {
+                    if self.frames.len() >= MAX_FRAMES {
+                        return self.error(func, pc, "stack overflow".to_string());
+                    }
+                    self.frames.push(Frame { func: index, pc: pc, base: base });
+                    index = f as usize;
+                    func = &module.functions[index];
+                    pc = 0;
+                    base = self.stack.len() - func.params as usize;
+                    self.stack.resize(base + func.locals as usize, Val::Int(0));
+                }
+                Op::Return | Op::ReturnValue => {
+                    let result = if op == Op::ReturnValue { Some(self.pop()) } else { None };
+                    self.stack.truncate(base);
+                    self.stack.extend(result);
+                    match self.frames.pop() {
+                        Some(frame) => {
+                            index = frame.func;
+                            func = &module.functions[index];
+                            pc = frame.pc;
+                            base = frame.base;
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                Op::Print(n) | Op::Println(n) => {
+                    let newline = op != Op::Print(n);
+                    let values = self.stack.split_off(self.stack.len().saturating_sub(n as usize));
+                    if let Err(err) = self.out.write_all(&print(&values, newline)) {
+                        return self.error(func, pc, err.to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The error for an instruction applied to values of the wrong
+/// kind, which a compiled program never does but a corrupted
+/// bytecode file may.
+fn invalid_operand(op: Op) -> String {
+    return format!("invalid bytecode: wrong operand for {}", op.name());
+}
+
+fn index_error(i: i64, len: usize) -> String {
+    return format!("index out of range [{}] with length {}", i, len);
+}
+
+fn zero(layout: &Layout) -> Val {
+    match *layout {
+        Layout::Int => return Val::Int(0),
+        Layout::Float => return Val::Float(0.0),
+        Layout::Bool => return Val::Bool(false),
+        Layout::Str => return Val::Str(Rc::new(Vec::new())),
+        Layout::Slice => return Val::Slice(Rc::new(RefCell::new(Vec::new())), 0),
+        Layout::Array(len, ref elem) => {
+            return Val::Agg(Rc::new(RefCell::new((0 .. len).map(|_| zero(elem)).collect())));
+        }
+        Layout::Struct(ref fields) => {
+            return Val::Agg(Rc::new(RefCell::new(fields.iter().map(|f| zero(&f.1)).collect())));
+        }
+    }
+}
+
+/// Copies an array or struct, including the arrays and structs
+/// that it contains; other values are shared.
+fn copy(value: &Val) -> Val {
+    match *value {
+        Val::Agg(ref elems) => {
+            return Val::Agg(Rc::new(RefCell::new(elems.borrow().iter().map(copy).collect())));
+        }
+        ref value => return value.clone(),
+    }
+}
+
+/// Stores a value in a variable, element or field.  Arrays and
+/// structs are overwritten in place, so that the objects that
+/// the operands of an assignment refer to remain those of the
+/// variables.
+fn assign(slot: &mut Val, value: Val) {
+    if let (Val::Agg(old), Val::Agg(new)) = (&*slot, &value) {
+        if !Rc::ptr_eq(old, new) {
+            let elems = new.borrow().clone();
+            *old.borrow_mut() = elems;
+        }
+        return;
+    }
+    *slot = value;
+}
+
+/// Appends to a slice, copying its elements to a new backing
+/// array twice as large if it is full.  The remaining elements
+/// of the new array are never read before they are appended.
+fn append(elems: Rc<RefCell<Vec<Val>>>, len: usize, value: Val) -> Val {
+    let cap = elems.borrow().len();
+    if len < cap {
+        elems.borrow_mut()[len] = value;
+        return Val::Slice(elems, len + 1);
+    }
+    let new_cap = if cap == 0 { 1 } else { 2 * cap };
+    let mut data = Vec::with_capacity(new_cap);
+    data.extend(elems.borrow()[.. len].iter().map(copy));
+    data.push(value);
+    data.resize(new_cap, Val::Int(0));
+    return Val::Slice(Rc::new(RefCell::new(data)), len + 1);
+}
+
+/// Compares two values; arrays and structs need their layout
+/// to skip blank fields.
+fn equal(layout: Option<&Layout>, x: &Val, y: &Val) -> bool {
+    match (x, y) {
+        (Val::Int(x), Val::Int(y)) => return x == y,
+        (Val::Float(x), Val::Float(y)) => return x == y,
+        (Val::Bool(x), Val::Bool(y)) => return x == y,
+        (Val::Str(x), Val::Str(y)) => return x == y,
+        (Val::Agg(x), Val::Agg(y)) => {
+            let (x, y) = (x.borrow(), y.borrow());
+            match layout {
+                Some(Layout::Array(_, elem)) => {
+                    return x.iter().zip(y.iter()).all(|(x, y)| equal(Some(elem), x, y));
+                }
+                Some(Layout::Struct(fields)) => {
+                    return fields.iter().zip(x.iter().zip(y.iter()))
+                        .all(|(field, (x, y))| !field.0 || equal(Some(&field.1), x, y));
+                }
+                _ => return false,
+            }
+        }
+        _ => return false,
+    }
+}
+
+fn print(values: &[Val], newline: bool) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        if newline && i > 0 {
+            bytes.push(b' ');
+        }
+        match *value {
+            Val::Int(n) => bytes.extend_from_slice(n.to_string().as_bytes()),
+            Val::Float(f) => bytes.extend_from_slice(interp::format_float(f).as_bytes()),
+            Val::Bool(b) => bytes.extend_from_slice(b.to_string().as_bytes()),
+            Val::Str(ref s) => bytes.extend_from_slice(s),
+            _ => {}
+        }
+    }
+    if newline {
+        bytes.push(b'\n');
+    }
+    return bytes;
+}
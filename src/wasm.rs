@@ -0,0 +1,1378 @@
+use std::collections::HashMap;
+use std::mem;
+
+use ast::*;
+use constant::{self, Value};
+use loc::Loc;
+use lower::{self, one, Targets};
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// The runtime support, which is copied at the beginning of
+/// every generated module.
+const RUNTIME: &str = include_str!("runtime/gore_rt.wat");
+
+/// The size of the stack, at the bottom of the memory.
+const STACK_SIZE: u32 = 1 << 20;
+
+const PAGE_SIZE: u32 = 1 << 16;
+
+/// The strings that the runtime uses, as the names of the
+/// globals that hold their addresses.
+const RUNTIME_STRINGS: [(&str, &str); 8] = [
+    ("true", "true"),
+    ("false", "false"),
+    ("space", " "),
+    ("newline", "\n"),
+    ("divide", "integer divide by zero"),
+    ("shift", "negative shift amount"),
+    ("index", "index out of range ["),
+    ("length", "] with length "),
+];
+
+/// Stands for the epilogue of a function in its body, until
+/// the size of its frame is known.
+const EPILOGUE: &str = "\u{1}";
+
+/// Translates a type-checked program to a WebAssembly module in
+/// the text format, which imports its output functions from the
+/// host (see `runtime/gore_rt.wat` and `runtime/gore_host.js`).
+///
+/// The code uses the operand stack of WebAssembly: every
+/// expression pushes its value.  Ints are `i64`s and floats
+/// `f64`s; booleans, runes, strings and slices are `i32`s, the
+/// last two being addresses in the linear memory.  Arrays and
+/// structs are stored in the memory, every scalar taking 8
+/// bytes, and their values are the addresses of copies that
+/// belong to the code that uses them: loading an array or a
+/// struct copies it to a temporary in the frame of the function.
+/// Scalar variables are locals and globals of the module; the
+/// other variables live in the frame of their function, on the
+/// stack of the linear memory, and package variables after the
+/// string literals.
+///
+/// Arrays and structs are passed as the addresses of their
+/// copies, and returned by copying them to the address given as
+/// a hidden first parameter.
+pub fn generate(prog: &Program, types: &TypeTable, table: &SymbolTable) -> String {
+    let filename = prog.package.loc.filename.clone();
+    let mut gen = Generator {
+        types: types,
+        table: table,
+        vars: HashMap::new(),
+        strings: HashMap::new(),
+        data: String::new(),
+        data_end: STACK_SIZE,
+        globals: String::new(),
+        funcs: String::new(),
+        body: String::new(),
+        locals: String::new(),
+        depth: 0,
+        temps: 0,
+        labels: 0,
+        frame: 0,
+        targets: Targets::default(),
+    };
+    for &(name, s) in RUNTIME_STRINGS.iter() {
+        let addr = gen.string_addr(s.as_bytes());
+        gen.globals.push_str(&format!("  (global $gore.str.{} i32 (i32.const {}))\n", name, addr));
+    }
+    gen.type_defs();
+    gen.program(prog);
+
+    let heap = align(gen.data_end, 8);
+    let mut out = format!(";; Generated by gore from {}; see gore_host.js.\n(module\n", filename);
+    out.push_str(RUNTIME);
+    out.push_str(&format!("\n  (memory (export \"memory\") {})\n", heap / PAGE_SIZE + 1));
+    out.push_str(&format!("  (global $gore_sp (mut i32) (i32.const {}))\n", STACK_SIZE));
+    out.push_str(&format!("  (global $gore_heap (mut i32) (i32.const {}))\n", heap));
+    out.push_str(&gen.globals);
+    out.push_str(&format!("\n{}", gen.data));
+    out.push_str(&gen.funcs);
+    out.push_str(")\n");
+    return out;
+}
+
+/// Where a value is stored.
+#[derive(Clone)]
+enum Place {
+    /// A local or global of the module.
+    Local(String),
+    Global(String),
+    /// At an offset from the address in a local.
+    Saved(String, u32),
+    /// At a fixed address.
+    Static(u32),
+    /// At an offset from the address on top of the stack.
+    Pushed(u32),
+    /// An element whose index is only checked when it is
+    /// accessed: the stack holds the address of the array, or
+    /// the slice, below the index.
+    Elem { size: u32, len: Option<u64>, loc: Loc },
+}
+
+struct Generator<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    /// Where the package variables and the variables of the
+    /// current function are stored.
+    vars: HashMap<SymbolId, Place>,
+    /// The addresses of string literals and locations.
+    strings: HashMap<Vec<u8>, u32>,
+    /// The data segments, and the end of the static data.
+    data: String,
+    data_end: u32,
+    globals: String,
+    funcs: String,
+    /// The instructions and the locals of the current function,
+    /// and the nesting depth of its blocks.
+    body: String,
+    locals: String,
+    depth: usize,
+    /// Counters for the names of temporaries and labels.
+    temps: usize,
+    labels: usize,
+    /// The size of the frame of the current function.
+    frame: u32,
+    targets: Targets<String>,
+}
+
+impl<'a> Generator<'a> {
+    fn emit(&mut self, s: &str) {
+        self.body.push_str(&format!("{:width$}{}\n", "", s, width = 4 + 2 * self.depth));
+    }
+
+    /// Starts a block, loop or `if`.
+    fn open(&mut self, s: &str) {
+        self.emit(s);
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.emit("end");
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        return format!("$L{}", self.labels);
+    }
+
+    /// Declares a new local of type `valty`, and returns its name.
+    fn temp(&mut self, valty: &str) -> String {
+        self.temps += 1;
+        let name = format!("$t{}", self.temps);
+        self.locals.push_str(&format!("    (local {} {})\n", name, valty));
+        return name;
+    }
+
+    /// Pops a value of type `ty` into a new local.
+    fn stash(&mut self, ty: TypeId) -> String {
+        let valty = self.valty(ty);
+        let name = self.temp(valty);
+        self.emit(&format!("local.set {}", name));
+        return name;
+    }
+
+    /// Reserves `size` bytes in the frame, and returns their
+    /// offset from the frame pointer.
+    fn slot(&mut self, size: u32) -> u32 {
+        let offset = self.frame;
+        self.frame += align(size, 8);
+        return offset;
+    }
+
+    fn push_slot(&mut self, offset: u32) {
+        self.emit("local.get $fp");
+        self.add(offset);
+    }
+
+    /// Adds an offset to the address on top of the stack.
+    fn add(&mut self, offset: u32) {
+        if offset > 0 {
+            self.emit(&format!("i32.const {}", offset));
+            self.emit("i32.add");
+        }
+    }
+
+    /// Copies `size` bytes; the stack holds the destination
+    /// below the source.
+    fn copy(&mut self, size: u32) {
+        self.emit(&format!("i32.const {}", size));
+        self.emit("memory.copy");
+    }
+
+    /// Returns the address of a string in the static data: its
+    /// length followed by its bytes.
+    fn string_addr(&mut self, bytes: &[u8]) -> u32 {
+        if let Some(&addr) = self.strings.get(bytes) {
+            return addr;
+        }
+        let addr = align(self.data_end, 4);
+        let mut data = (bytes.len() as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(bytes);
+        self.data.push_str(&format!("  (data (i32.const {}) {})\n", addr, wat_string(&data)));
+        self.data_end = addr + data.len() as u32;
+        self.strings.insert(bytes.to_vec(), addr);
+        return addr;
+    }
+
+    /// Pushes the location of a runtime error, as a string.
+    fn location(&mut self, loc: &Loc) {
+        let addr = self.string_addr(loc.to_string().as_bytes());
+        self.emit(&format!("i32.const {}", addr));
+    }
+
+
+    // TYPES
+
+    /// Returns the kind of a type, untyped constants having
+    /// their default type.
+    fn kind(&self, ty: TypeId) -> &TypeKind {
+        return self.types.under(self.types.default_type(ty));
+    }
+
+    fn is_rune(&self, ty: TypeId) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Rune => return true,
+            _ => return false,
+        }
+    }
+
+    /// Returns whether values of a type are stored in memory
+    /// and represented by their addresses.
+    fn is_aggregate(&self, ty: TypeId) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => return true,
+            _ => return false,
+        }
+    }
+
+    /// Returns the WebAssembly type of the values of a type.
+    fn valty(&self, ty: TypeId) -> &'static str {
+        match *self.kind(ty) {
+            TypeKind::Int => return "i64",
+            TypeKind::Float64 => return "f64",
+            _ => return "i32",
+        }
+    }
+
+    /// Returns the size of a value in memory, which is a
+    /// multiple of 8.
+    fn size(&self, ty: TypeId) -> u32 {
+        match *self.kind(ty) {
+            TypeKind::Array(len, elem) => return len as u32 * self.size(elem),
+            TypeKind::Struct(ref fields) => return fields.iter().map(|&(_, ty)| self.size(ty)).sum(),
+            _ => return 8,
+        }
+    }
+
+    /// Returns the offset of the field that a selector
+    /// expression selects.
+    fn field_offset(&self, e: &Expr) -> u32 {
+        if let ExprKind::Selector(ref x, ref field) = e.kind {
+            if let TypeKind::Struct(ref fields) = *self.kind(x.ty.unwrap_or(INVALID)) {
+                return fields.iter()
+                    .take_while(|f| f.0 != field.name)
+                    .map(|&(_, ty)| self.size(ty))
+                    .sum();
+            }
+        }
+        return 0;
+    }
+
+    /// Defines an equality function for every comparable array
+    /// and struct type.
+    fn type_defs(&mut self) {
+        let mut defined = vec![false; self.types.len()];
+        for ty in 0 .. self.types.len() {
+            let ty = self.types.underlying(ty);
+            if defined[ty] {
+                continue;
+            }
+            defined[ty] = true;
+            match *self.types.kind(ty) {
+                TypeKind::Array(_, _) | TypeKind::Struct(_) if self.types.is_comparable(ty) => self.eq_function(ty),
+                _ => {}
+            }
+        }
+    }
+
+    /// Defines `$gore.eq.tN`, which compares two arrays or
+    /// structs of type `N` given their addresses, and returns 0
+    /// or 1.  Blank fields are not compared.
+    fn eq_function(&mut self, ty: TypeId) {
+        self.begin_function();
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                let size = self.size(elem);
+                let (i, x, y) = (self.temp("i32"), self.temp("i32"), self.temp("i32"));
+                let (done, next) = (self.new_label(), self.new_label());
+                self.open(&format!("block {}", done));
+                self.open(&format!("loop {}", next));
+                self.emit(&format!("local.get {}", i));
+                self.emit(&format!("i32.const {}", len));
+                self.emit("i32.ge_u");
+                self.emit(&format!("br_if {}", done));
+                for &(base, addr) in [("$a", &x), ("$b", &y)].iter() {
+                    self.emit(&format!("local.get {}", base));
+                    self.emit(&format!("local.get {}", i));
+                    self.emit(&format!("i32.const {}", size));
+                    self.emit("i32.mul");
+                    self.emit("i32.add");
+                    self.emit(&format!("local.set {}", addr));
+                }
+                self.differ(elem, &x, &y, 0);
+                self.emit(&format!("local.get {}", i));
+                self.emit("i32.const 1");
+                self.emit("i32.add");
+                self.emit(&format!("local.set {}", i));
+                self.emit(&format!("br {}", next));
+                self.close();
+                self.close();
+            }
+            TypeKind::Struct(fields) => {
+                let mut offset = 0;
+                for &(ref name, field_ty) in &fields {
+                    if name != "_" {
+                        self.differ(field_ty, "$a", "$b", offset);
+                    }
+                    offset += self.size(field_ty);
+                }
+            }
+            _ => {}
+        }
+        self.emit("i32.const 1");
+        self.end_function(&format!("$gore.eq.t{} (param $a i32) (param $b i32) (result i32)", ty));
+    }
+
+    /// Returns 0 if the values of type `ty` at an offset from the
+    /// addresses in two locals differ.
+    fn differ(&mut self, ty: TypeId, a: &str, b: &str, offset: u32) {
+        for addr in [a, b].iter() {
+            self.emit(&format!("local.get {}", addr));
+            if self.is_aggregate(ty) {
+                self.add(offset);
+            } else {
+                self.load_at(ty, offset);
+            }
+        }
+        self.equal(ty);
+        self.emit("i32.eqz");
+        self.open("if");
+        self.emit("i32.const 0");
+        self.emit("return");
+        self.close();
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) if !f.name.is_blank() => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        let ty = self.var_type(id);
+                        let place = if self.is_aggregate(ty) {
+                            let addr = align(self.data_end, 8);
+                            self.data_end = addr + self.size(ty);
+                            Place::Static(addr)
+                        } else {
+                            let name = format!("${}.{}", id.name, id.sym.unwrap_or(0));
+                            let valty = self.valty(ty);
+                            self.globals.push_str(&format!("  (global {} (mut {}) ({}.const 0))\n",
+                                                           name, valty, valty));
+                            Place::Global(name)
+                        };
+                        if let Some(sym) = id.sym {
+                            self.vars.insert(sym, place);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Package variables are initialized in the order of
+        // their declarations.
+        self.begin_function();
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec, false);
+                }
+            }
+        }
+        self.emit(EPILOGUE);
+        self.end_function("$gore.init");
+
+        for f in &funcs {
+            self.func_decl(f);
+        }
+
+        self.funcs.push_str("\n  (func $gore.main (export \"main\")\n    call $gore.init\n");
+        if funcs.iter().any(|f| f.name.name == "main") {
+            self.funcs.push_str("    call $main.main\n");
+        }
+        self.funcs.push_str("  )\n");
+    }
+
+    fn begin_function(&mut self) {
+        self.depth = 0;
+        self.temps = 0;
+        self.labels = 0;
+        self.frame = 0;
+    }
+
+    /// Writes the current function, given its name and
+    /// signature, with a prologue that allocates its frame.
+    fn end_function(&mut self, header: &str) {
+        let body = mem::take(&mut self.body);
+        let locals = mem::take(&mut self.locals);
+        self.funcs.push_str(&format!("\n  (func {}\n", header));
+        if self.frame > 0 {
+            self.funcs.push_str("    (local $fp i32)\n    (local $sp i32)\n");
+        }
+        self.funcs.push_str(&locals);
+        if self.frame > 0 {
+            self.funcs.push_str(&format!("    global.get $gore_sp\n    local.tee $sp\n    i32.const {}\n    \
+                                          i32.sub\n    local.tee $fp\n    global.set $gore_sp\n", self.frame));
+        }
+        for line in body.lines() {
+            if line.trim_start() != EPILOGUE {
+                self.funcs.push_str(&format!("{}\n", line));
+            } else if self.frame > 0 {
+                let indent = &line[.. line.len() - EPILOGUE.len()];
+                self.funcs.push_str(&format!("{}local.get $sp\n{}global.set $gore_sp\n", indent, indent));
+            }
+        }
+        self.funcs.push_str("  )\n");
+    }
+
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.begin_function();
+        let result = lower::result_type(f, self.types, self.table);
+        let mut header = format!("$main.{}", f.name.name);
+        if result != VOID && self.is_aggregate(result) {
+            header.push_str(" (param $result i32)");
+        }
+        for (i, p) in f.params.iter().enumerate() {
+            let ty = self.var_type(&p.name);
+            let name = match p.name.sym {
+                Some(sym) if !p.name.is_blank() => {
+                    let name = format!("${}.{}", p.name.name, sym);
+                    if self.is_aggregate(ty) {
+                        self.vars.insert(sym, Place::Saved(name.clone(), 0));
+                    } else {
+                        self.vars.insert(sym, Place::Local(name.clone()));
+                    }
+                    name
+                }
+                _ => format!("$arg{}", i),
+            };
+            header.push_str(&format!(" (param {} {})", name, self.valty(ty)));
+        }
+        if result != VOID && !self.is_aggregate(result) {
+            header.push_str(&format!(" (result {})", self.valty(result)));
+        }
+        self.stmts(&f.body.stmts);
+        // The type checker makes sure that the end of a function
+        // with a result is not reached.
+        if result == VOID {
+            self.emit(EPILOGUE);
+        } else {
+            self.emit("unreachable");
+        }
+        self.end_function(&header);
+    }
+
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    fn var(&self, id: &Ident) -> Place {
+        match id.sym.and_then(|sym| self.vars.get(&sym)) {
+            Some(place) => return place.clone(),
+            None => return Place::Static(0),
+        }
+    }
+
+    /// Declares a local variable: a local of the function if it
+    /// is a scalar, a slot of its frame otherwise.
+    fn local(&mut self, id: &Ident) -> Place {
+        let ty = self.var_type(id);
+        let place = if self.is_aggregate(ty) {
+            let size = self.size(ty);
+            Place::Saved("$fp".to_string(), self.slot(size))
+        } else {
+            let name = format!("${}.{}", id.name, id.sym.unwrap_or(0));
+            self.locals.push_str(&format!("    (local {} {})\n", name, self.valty(ty)));
+            Place::Local(name)
+        };
+        if let Some(sym) = id.sym {
+            self.vars.insert(sym, place.clone());
+        }
+        return place;
+    }
+
+    /// Declares (or, for package variables, initializes) the
+    /// variables of a `var` spec.
+    fn var_spec(&mut self, spec: &VarSpec, local: bool) {
+        if spec.values.is_empty() {
+            if local {
+                for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                    let var = self.local(id);
+                    let ty = self.var_type(id);
+                    self.zero(var, ty);
+                }
+            }
+            return;
+        }
+        let targets: Vec<Option<Place>> = spec.names.iter()
+            .map(|id| {
+                if id.is_blank() {
+                    return None;
+                }
+                return Some(if local { self.local(id) } else { self.var(id) });
+            })
+            .collect();
+        self.store_values(targets, &spec.values);
+    }
+
+    /// Stores the zero value of a type in a variable, which may
+    /// be declared in a loop.
+    fn zero(&mut self, place: Place, ty: TypeId) {
+        if self.is_aggregate(ty) {
+            let size = self.size(ty);
+            self.push_address(place);
+            self.emit("i32.const 0");
+            self.emit(&format!("i32.const {}", size));
+            self.emit("memory.fill");
+            return;
+        }
+        let place = self.prepare(place);
+        self.emit(&format!("{}.const 0", self.valty(ty)));
+        self.store(place, ty);
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => {
+                if let ExprKind::Call(ref f, ref args) = e.kind {
+                    let ty = e.ty.unwrap_or(VOID);
+                    self.func_call(f, args, ty);
+                    if ty != VOID {
+                        self.emit("drop");
+                    }
+                }
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec, true);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs),
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, &stmt.loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), &stmt.loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), &stmt.loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                let targets: Vec<Option<Place>> = names.iter()
+                    .map(|id| {
+                        if id.is_blank() {
+                            return None;
+                        }
+                        return Some(if self.is_new_var(id) { self.local(id) } else { self.var(id) });
+                    })
+                    .collect();
+                self.store_values(targets, values);
+            }
+            StmtKind::Print(ref args) => self.print(args, false),
+            StmtKind::Println(ref args) => self.print(args, true),
+            StmtKind::Return(ref e) => self.return_stmt(e),
+            StmtKind::Break => {
+                if let Some(label) = self.targets.brk() {
+                    self.emit(&format!("br {}", label));
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(label) = self.targets.cont() {
+                    self.emit(&format!("br {}", label));
+                }
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+        }
+    }
+
+    /// Returns whether a name of a `:=` statement declares a
+    /// variable, rather than assigning to an existing one.
+    fn is_new_var(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => self.table.symbol(sym).loc.as_ref() == Some(&id.loc),
+            None => false,
+        }
+    }
+
+    /// Returns the result, or copies it where the caller asked
+    /// for it.
+    fn return_stmt(&mut self, e: &Option<Expr>) {
+        if let Some(ref e) = *e {
+            let ty = e.ty.unwrap_or(INVALID);
+            if self.is_aggregate(ty) {
+                self.emit("local.get $result");
+                self.expr(e);
+                let size = self.size(ty);
+                self.copy(size);
+            } else {
+                self.expr(e);
+            }
+        }
+        self.emit(EPILOGUE);
+        self.emit("return");
+    }
+
+    /// Assigns values to variables, elements and fields.  The
+    /// index of a single element is checked once its value is
+    /// known; with several targets, their operands are all
+    /// evaluated and checked before the values.
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr]) {
+        if lhs.len() == 1 {
+            if let ExprKind::Blank = lhs[0].kind {
+                self.expr(&rhs[0]);
+                self.emit("drop");
+                return;
+            }
+            let place = self.place(&lhs[0]);
+            let place = self.prepare(place);
+            self.expr(&rhs[0]);
+            self.store(place, rhs[0].ty.unwrap_or(INVALID));
+            return;
+        }
+        let mut targets = Vec::new();
+        for e in lhs {
+            let target = match e.kind {
+                ExprKind::Blank => None,
+                _ => {
+                    let place = self.place(e);
+                    match self.resolve(place) {
+                        Place::Pushed(offset) => {
+                            let addr = self.temp("i32");
+                            self.emit(&format!("local.set {}", addr));
+                            Some(Place::Saved(addr, offset))
+                        }
+                        place => Some(place),
+                    }
+                }
+            };
+            targets.push(target);
+        }
+        self.store_values(targets, rhs);
+    }
+
+    /// Evaluates all the values before storing them, from left
+    /// to right, in places that do not use the stack.
+    fn store_values(&mut self, targets: Vec<Option<Place>>, values: &[Expr]) {
+        if values.len() == 1 {
+            let ty = values[0].ty.unwrap_or(INVALID);
+            match targets.into_iter().next() {
+                Some(Some(place)) => {
+                    let place = self.prepare(place);
+                    self.expr(&values[0]);
+                    self.store(place, ty);
+                }
+                _ => {
+                    self.expr(&values[0]);
+                    self.emit("drop");
+                }
+            }
+            return;
+        }
+        let temps: Vec<String> = values.iter()
+            .map(|e| {
+                self.expr(e);
+                return self.stash(e.ty.unwrap_or(INVALID));
+            })
+            .collect();
+        for ((target, e), temp) in targets.into_iter().zip(values).zip(temps) {
+            if let Some(place) = target {
+                let place = self.prepare(place);
+                self.emit(&format!("local.get {}", temp));
+                self.store(place, e.ty.unwrap_or(INVALID));
+            }
+        }
+    }
+
+    /// `x op= y`: an address that `x` pushes is kept in a local,
+    /// from which its value is loaded and then stored.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let ty = lhs.ty.unwrap_or(INVALID);
+        let place = self.place(lhs);
+        let place = match self.resolve(place) {
+            Place::Pushed(offset) => {
+                let addr = self.temp("i32");
+                self.emit(&format!("local.set {}", addr));
+                Place::Saved(addr, offset)
+            }
+            place => place,
+        };
+        let target = self.prepare(place.clone());
+        self.load(place, ty);
+        self.expr(rhs);
+        self.arith(op, ty, rhs.ty.unwrap_or(INVALID), loc);
+        self.store(target, ty);
+    }
+
+    /// Stashes the values of all the arguments in locals, then
+    /// prints each of them.
+    fn print(&mut self, args: &[Expr], newline: bool) {
+        let temps: Vec<String> = args.iter()
+            .map(|arg| {
+                self.expr(arg);
+                return self.stash(arg.ty.unwrap_or(INVALID));
+            })
+            .collect();
+        for (i, (arg, temp)) in args.iter().zip(temps).enumerate() {
+            if newline && i > 0 {
+                self.emit("global.get $gore.str.space");
+                self.emit("call $gore_print_string");
+            }
+            self.emit(&format!("local.get {}", temp));
+            let ty = arg.ty.unwrap_or(INVALID);
+            match *self.kind(ty) {
+                TypeKind::Float64 => self.emit("call $host.print_float"),
+                TypeKind::Bool => self.emit("call $gore_print_bool"),
+                TypeKind::String => self.emit("call $gore_print_string"),
+                _ => {
+                    self.to_i64(ty);
+                    self.emit("call $host.print_int");
+                }
+            }
+        }
+        if newline {
+            self.emit("global.get $gore.str.newline");
+            self.emit("call $gore_print_string");
+        }
+    }
+
+    /// Generates the simple statement that introduces an `if`,
+    /// `switch` or `for` statement.
+    fn init(&mut self, init: &Option<Box<Stmt>>) {
+        if let Some(ref init) = *init {
+            self.stmt(init);
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        self.init(&if_stmt.init);
+        self.expr(&if_stmt.cond);
+        self.open("if");
+        self.stmts(&if_stmt.then.stmts);
+        if let Some(ref els) = if_stmt.els {
+            self.depth -= 1;
+            self.emit("else");
+            self.depth += 1;
+            self.stmt(els);
+        }
+        self.close();
+    }
+
+    /// The tag and the case expressions are evaluated in order
+    /// until a case matches, which selects the clause to execute.
+    /// Every clause follows the end of a block, which the case
+    /// expressions branch out of; the blocks of the first clauses
+    /// are the innermost.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) {
+        self.init(&switch_stmt.init);
+        let tag = switch_stmt.tag.as_ref().map(|tag| {
+            self.expr(tag);
+            let ty = tag.ty.unwrap_or(INVALID);
+            return (self.stash(ty), ty);
+        });
+        let end = self.new_label();
+        let bodies: Vec<String> = switch_stmt.clauses.iter().map(|_| self.new_label()).collect();
+        self.open(&format!("block {}", end));
+        for body in bodies.iter().rev() {
+            self.open(&format!("block {}", body));
+        }
+        for (case, body) in switch_stmt.clauses.iter().zip(&bodies) {
+            let exprs = match case.exprs {
+                Some(ref exprs) => exprs,
+                None => continue,
+            };
+            for e in exprs {
+                match tag {
+                    Some((ref tag, ty)) => {
+                        self.emit(&format!("local.get {}", tag));
+                        self.expr(e);
+                        self.equal(ty);
+                    }
+                    None => self.expr(e),
+                }
+                self.emit(&format!("br_if {}", body));
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|c| c.exprs.is_none());
+        let otherwise = default.map_or(end.clone(), |i| bodies[i].clone());
+        self.emit(&format!("br {}", otherwise));
+
+        self.targets.push_switch(end.clone());
+        for (i, case) in switch_stmt.clauses.iter().enumerate() {
+            self.close();
+            self.stmts(&case.body);
+            if i + 1 < bodies.len() {
+                self.emit(&format!("br {}", end));
+            }
+        }
+        self.targets.pop();
+        self.close();
+    }
+
+    /// The condition is tested at the top of the loop, and
+    /// `continue` branches out of the block of its body, to the
+    /// post statement.
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        self.init(&for_stmt.init);
+        let (end, head, post) = (self.new_label(), self.new_label(), self.new_label());
+        self.open(&format!("block {}", end));
+        self.open(&format!("loop {}", head));
+        if let Some(ref cond) = for_stmt.cond {
+            self.expr(cond);
+            self.emit("i32.eqz");
+            self.emit(&format!("br_if {}", end));
+        }
+        self.open(&format!("block {}", post));
+        self.targets.push_loop(end.clone(), post.clone());
+        self.stmts(&for_stmt.body.stmts);
+        self.targets.pop();
+        self.close();
+        if let Some(ref stmt) = for_stmt.post {
+            self.stmt(stmt);
+        }
+        self.emit(&format!("br {}", head));
+        self.close();
+        self.close();
+    }
+
+
+    // EXPRESSIONS
+
+    /// Pushes the value of `e`, if it has one.
+    fn expr(&mut self, e: &Expr) {
+        let ty = e.ty.unwrap_or(INVALID);
+        if let Some(ref value) = e.value {
+            self.constant(value, ty);
+            return;
+        }
+        match e.kind {
+            ExprKind::Unary(op, ref x) => self.unary(op, ty, x),
+            ExprKind::Binary(BinOp::And, ref x, ref y) => self.logical(true, x, y),
+            ExprKind::Binary(BinOp::Or, ref x, ref y) => self.logical(false, x, y),
+            ExprKind::Binary(op, ref x, ref y) => {
+                let (x_ty, y_ty) = (x.ty.unwrap_or(INVALID), y.ty.unwrap_or(INVALID));
+                self.expr(x);
+                self.expr(y);
+                if op.is_comparison() {
+                    self.compare(op, x_ty);
+                } else {
+                    self.arith(op, x_ty, y_ty, &e.loc);
+                }
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                match kind {
+                    SymbolKind::Type => self.conversion(ty, &args[0]),
+                    SymbolKind::Builtin => self.builtin(f, &args[0]),
+                    _ => self.func_call(f, args, ty),
+                }
+            }
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => {
+                let place = self.place(e);
+                self.load(place, ty);
+            }
+            ExprKind::Append(ref s, ref x) => {
+                self.expr(s);
+                let elem = x.ty.unwrap_or(INVALID);
+                let size = self.size(elem);
+                if self.is_aggregate(elem) {
+                    self.expr(x);
+                } else {
+                    // The element is copied from a temporary.
+                    let slot = self.slot(size);
+                    self.push_slot(slot);
+                    self.expr(x);
+                    self.store(Place::Pushed(0), elem);
+                    self.push_slot(slot);
+                }
+                self.emit(&format!("i32.const {}", size));
+                self.emit("call $gore_append");
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => self.emit(&format!("{}.const 0", self.valty(ty))),
+        }
+    }
+
+    /// Returns where the value of a variable, element or field
+    /// is stored, after pushing the operands of its address.
+    /// The value of an expression that is not stored is an
+    /// address already.
+    fn place(&mut self, e: &Expr) -> Place {
+        match e.kind {
+            ExprKind::Id(ref id) => return self.var(id),
+            ExprKind::Index(ref a, ref i) => {
+                let size = self.size(e.ty.unwrap_or(INVALID));
+                let len = match *self.kind(a.ty.unwrap_or(INVALID)) {
+                    TypeKind::Array(len, _) => {
+                        let base = self.place(a);
+                        self.push_address(base);
+                        Some(len)
+                    }
+                    _ => {
+                        self.expr(a);
+                        None
+                    }
+                };
+                self.expr(i);
+                self.to_i64(i.ty.unwrap_or(INVALID));
+                return Place::Elem { size: size, len: len, loc: e.loc.clone() };
+            }
+            ExprKind::Selector(ref x, _) => {
+                let offset = self.field_offset(e);
+                let base = self.place(x);
+                match self.resolve(base) {
+                    Place::Saved(addr, base) => return Place::Saved(addr, base + offset),
+                    Place::Static(base) => return Place::Static(base + offset),
+                    Place::Pushed(base) => return Place::Pushed(base + offset),
+                    place => return place,
+                }
+            }
+            _ => {
+                self.expr(e);
+                return Place::Pushed(0);
+            }
+        }
+    }
+
+    /// Checks the index of an element and pushes its address.
+    fn resolve(&mut self, place: Place) -> Place {
+        match place {
+            Place::Elem { size, len, loc } => {
+                if let Some(len) = len {
+                    self.emit(&format!("i64.const {}", len));
+                }
+                self.emit(&format!("i32.const {}", size));
+                self.location(&loc);
+                self.emit(if len.is_some() { "call $gore_elem" } else { "call $gore_slice_elem" });
+                return Place::Pushed(0);
+            }
+            place => return place,
+        }
+    }
+
+    /// Pushes the address of a place in memory.
+    fn push_address(&mut self, place: Place) {
+        match self.resolve(place) {
+            Place::Saved(addr, offset) => {
+                self.emit(&format!("local.get {}", addr));
+                self.add(offset);
+            }
+            Place::Static(addr) => self.emit(&format!("i32.const {}", addr)),
+            Place::Pushed(offset) => self.add(offset),
+            _ => {}
+        }
+    }
+
+    /// Pushes the base address of a place in memory that does
+    /// not use the stack, before its value is pushed.
+    fn prepare(&mut self, place: Place) -> Place {
+        match place {
+            Place::Saved(addr, offset) => {
+                self.emit(&format!("local.get {}", addr));
+                return Place::Pushed(offset);
+            }
+            Place::Static(addr) => {
+                self.emit(&format!("i32.const {}", addr));
+                return Place::Pushed(0);
+            }
+            place => return place,
+        }
+    }
+
+    /// Loads a scalar from an offset from the address on top of
+    /// the stack.
+    fn load_at(&mut self, ty: TypeId, offset: u32) {
+        let valty = self.valty(ty);
+        if offset > 0 {
+            self.emit(&format!("{}.load offset={}", valty, offset));
+        } else {
+            self.emit(&format!("{}.load", valty));
+        }
+    }
+
+    /// Pushes the value stored in a place; an array or a struct
+    /// is copied to a temporary.
+    fn load(&mut self, place: Place, ty: TypeId) {
+        if self.is_aggregate(ty) {
+            let size = self.size(ty);
+            let slot = self.slot(size);
+            match self.resolve(place) {
+                Place::Pushed(offset) => {
+                    self.add(offset);
+                    let src = self.temp("i32");
+                    self.emit(&format!("local.set {}", src));
+                    self.push_slot(slot);
+                    self.emit(&format!("local.get {}", src));
+                }
+                place => {
+                    self.push_slot(slot);
+                    self.push_address(place);
+                }
+            }
+            self.copy(size);
+            self.push_slot(slot);
+            return;
+        }
+        match self.resolve(place) {
+            Place::Local(name) => self.emit(&format!("local.get {}", name)),
+            Place::Global(name) => self.emit(&format!("global.get {}", name)),
+            Place::Saved(addr, offset) => {
+                self.emit(&format!("local.get {}", addr));
+                self.load_at(ty, offset);
+            }
+            Place::Static(addr) => {
+                self.emit(&format!("i32.const {}", addr));
+                self.load_at(ty, 0);
+            }
+            Place::Pushed(offset) => self.load_at(ty, offset),
+            Place::Elem { .. } => {}
+        }
+    }
+
+    /// Pops a value into a place, whose base address, if any, is
+    /// below it on the stack.  The index of an element is
+    /// checked after its value is computed.
+    fn store(&mut self, place: Place, ty: TypeId) {
+        match place {
+            Place::Local(name) => self.emit(&format!("local.set {}", name)),
+            Place::Global(name) => self.emit(&format!("global.set {}", name)),
+            Place::Pushed(offset) if self.is_aggregate(ty) => {
+                let src = self.temp("i32");
+                self.emit(&format!("local.set {}", src));
+                self.add(offset);
+                self.emit(&format!("local.get {}", src));
+                let size = self.size(ty);
+                self.copy(size);
+            }
+            Place::Pushed(offset) => {
+                let valty = self.valty(ty);
+                if offset > 0 {
+                    self.emit(&format!("{}.store offset={}", valty, offset));
+                } else {
+                    self.emit(&format!("{}.store", valty));
+                }
+            }
+            place => {
+                let value = self.stash(ty);
+                let place = self.resolve(place);
+                match self.prepare(place) {
+                    Place::Pushed(offset) if self.is_aggregate(ty) => {
+                        self.add(offset);
+                        self.emit(&format!("local.get {}", value));
+                        let size = self.size(ty);
+                        self.copy(size);
+                    }
+                    place => {
+                        self.emit(&format!("local.get {}", value));
+                        self.store(place, ty);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Widens a rune on top of the stack to an `int`.
+    fn to_i64(&mut self, ty: TypeId) {
+        if self.is_rune(ty) {
+            self.emit("i64.extend_i32_s");
+        }
+    }
+
+    fn constant(&mut self, value: &Value, ty: TypeId) {
+        let valty = self.valty(ty);
+        match *value {
+            Value::Bool(b) => self.emit(&format!("i32.const {}", b as i32)),
+            Value::String(ref s) => {
+                let bytes = constant::string_bytes(s);
+                let addr = if bytes.is_empty() { 0 } else { self.string_addr(&bytes) };
+                self.emit(&format!("i32.const {}", addr));
+            }
+            Value::Int(_) | Value::Float(_) if valty == "f64" => {
+                let f = value.to_rat().map_or(0.0, |r| r.to_f64());
+                self.emit(&format!("f64.const {:?}", f));
+            }
+            Value::Int(_) | Value::Float(_) => {
+                let n = value.to_int().and_then(|n| n.to_i64()).unwrap_or(0);
+                self.emit(&format!("{}.const {}", valty, n));
+            }
+        }
+    }
+
+    fn unary(&mut self, op: UnOp, ty: TypeId, x: &Expr) {
+        let valty = self.valty(ty);
+        match op {
+            UnOp::Plus => self.expr(x),
+            UnOp::Neg if valty == "f64" => {
+                self.expr(x);
+                self.emit("f64.neg");
+            }
+            UnOp::Neg => {
+                self.emit(&format!("{}.const 0", valty));
+                self.expr(x);
+                self.emit(&format!("{}.sub", valty));
+            }
+            UnOp::Not => {
+                self.expr(x);
+                self.emit("i32.eqz");
+            }
+            UnOp::BitNot => {
+                self.expr(x);
+                self.emit(&format!("{}.const -1", valty));
+                self.emit(&format!("{}.xor", valty));
+            }
+        }
+    }
+
+    /// `&&` and `||` only evaluate their right operand if
+    /// needed.
+    fn logical(&mut self, and: bool, x: &Expr, y: &Expr) {
+        self.expr(x);
+        self.open("if (result i32)");
+        if and {
+            self.expr(y);
+        } else {
+            self.emit("i32.const 1");
+        }
+        self.depth -= 1;
+        self.emit("else");
+        self.depth += 1;
+        if and {
+            self.emit("i32.const 0");
+        } else {
+            self.expr(y);
+        }
+        self.close();
+    }
+
+    /// Applies an arithmetic or bitwise operator to the two
+    /// values on top of the stack, of type `ty` (and `y_ty` for
+    /// the count of a shift).  Integer operations wrap around
+    /// and division by zero panics, as in Go.
+    fn arith(&mut self, op: BinOp, ty: TypeId, y_ty: TypeId, loc: &Loc) {
+        let valty = self.valty(ty);
+        let bits = match *self.kind(ty) {
+            TypeKind::Int => 64,
+            TypeKind::Rune => 32,
+            TypeKind::String => {
+                self.emit("call $gore_concat");
+                return;
+            }
+            _ => {
+                let inst = match op {
+                    BinOp::Add => "f64.add",
+                    BinOp::Sub => "f64.sub",
+                    BinOp::Mul => "f64.mul",
+                    _ => "f64.div",
+                };
+                self.emit(inst);
+                return;
+            }
+        };
+        let inst = match op {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::BitAnd => "and",
+            BinOp::BitOr => "or",
+            BinOp::BitXor => "xor",
+            BinOp::BitClear => {
+                self.emit(&format!("{}.const -1", valty));
+                self.emit(&format!("{}.xor", valty));
+                "and"
+            }
+            BinOp::Div | BinOp::Rem => {
+                self.location(loc);
+                let f = if op == BinOp::Div { "div" } else { "rem" };
+                self.emit(&format!("call $gore_{}{}", f, bits));
+                return;
+            }
+            _ => {
+                self.to_i64(y_ty);
+                self.location(loc);
+                let f = if op == BinOp::Shl { "shl" } else { "shr" };
+                self.emit(&format!("call $gore_{}{}", f, bits));
+                return;
+            }
+        };
+        self.emit(&format!("{}.{}", valty, inst));
+    }
+
+    /// Compares the two values on top of the stack.
+    fn compare(&mut self, op: BinOp, ty: TypeId) {
+        match op {
+            BinOp::Eq => return self.equal(ty),
+            BinOp::Ne => {
+                self.equal(ty);
+                self.emit("i32.eqz");
+                return;
+            }
+            _ => {}
+        }
+        let cond = match op {
+            BinOp::Lt => "lt",
+            BinOp::Le => "le",
+            BinOp::Gt => "gt",
+            _ => "ge",
+        };
+        match *self.kind(ty) {
+            TypeKind::Float64 => self.emit(&format!("f64.{}", cond)),
+            TypeKind::String => {
+                self.emit("call $gore_string_cmp");
+                self.emit("i32.const 0");
+                self.emit(&format!("i32.{}_s", cond));
+            }
+            _ => {
+                let valty = self.valty(ty);
+                self.emit(&format!("{}.{}_s", valty, cond));
+            }
+        }
+    }
+
+    fn equal(&mut self, ty: TypeId) {
+        let ty = self.types.default_type(ty);
+        match *self.kind(ty) {
+            TypeKind::Float64 => self.emit("f64.eq"),
+            TypeKind::String => self.emit("call $gore_string_eq"),
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => {
+                self.emit(&format!("call $gore.eq.t{}", self.types.underlying(ty)));
+            }
+            _ => {
+                let valty = self.valty(ty);
+                self.emit(&format!("{}.eq", valty));
+            }
+        }
+    }
+
+    /// Calls a function, after evaluating its arguments, and
+    /// pushes its result.
+    fn func_call(&mut self, f: &Expr, args: &[Expr], ty: TypeId) {
+        let name = match f.kind {
+            ExprKind::Id(ref id) => id.name.clone(),
+            _ => String::new(),
+        };
+        let result = if ty != VOID && self.is_aggregate(ty) {
+            let size = self.size(ty);
+            let slot = self.slot(size);
+            self.push_slot(slot);
+            Some(slot)
+        } else {
+            None
+        };
+        for arg in args {
+            self.expr(arg);
+        }
+        self.emit(&format!("call $main.{}", name));
+        if let Some(slot) = result {
+            self.push_slot(slot);
+        }
+    }
+
+    fn conversion(&mut self, ty: TypeId, arg: &Expr) {
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        self.expr(arg);
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            self.to_i64(arg_ty);
+            self.emit("call $gore_string_from_rune");
+            return;
+        }
+        match (self.valty(arg_ty), self.valty(ty)) {
+            ("f64", "f64") => {}
+            ("f64", to) => {
+                // Out of range values saturate, as in the
+                // interpreters.
+                self.emit("i64.trunc_sat_f64_s");
+                if to == "i32" {
+                    self.emit("i32.wrap_i64");
+                }
+            }
+            (from, "f64") => self.emit(&format!("f64.convert_{}_s", from)),
+            ("i32", "i64") => self.emit("i64.extend_i32_s"),
+            ("i64", "i32") => self.emit("i32.wrap_i64"),
+            _ => {}
+        }
+    }
+
+    fn builtin(&mut self, f: &Expr, arg: &Expr) {
+        let cap = match f.kind {
+            ExprKind::Id(ref id) => id.name == "cap",
+            _ => false,
+        };
+        let ty = arg.ty.unwrap_or(INVALID);
+        match *self.kind(ty) {
+            TypeKind::Array(len, _) => self.emit(&format!("i64.const {}", len)),
+            TypeKind::String => {
+                self.expr(arg);
+                self.emit("call $gore_string_len");
+                self.emit("i64.extend_i32_u");
+            }
+            _ => {
+                self.expr(arg);
+                self.emit(if cap { "call $gore_slice_cap" } else { "call $gore_slice_len" });
+            }
+        }
+    }
+}
+
+fn align(n: u32, to: u32) -> u32 {
+    return n.div_ceil(to) * to;
+}
+
+/// Returns a WebAssembly string.  Quotes, backslashes and
+/// anything but printable ASCII are escaped in hexadecimal.
+fn wat_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => out.push_str(&format!("\\{:02x}", b)),
+            0x20 ..= 0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:02x}", b)),
+        }
+    }
+    out.push('"');
+    return out;
+}
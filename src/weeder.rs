@@ -0,0 +1,278 @@
+use std::result;
+
+use ast::*;
+use error::Error;
+use error::ErrorType as ET;
+use loc::Loc;
+use pretty::pretty_expr;
+use reach;
+
+/// Checks the context-sensitive syntax rules that the
+/// parser does not enforce:
+///
+/// - `break` must be inside a loop or a switch;
+/// - `continue` must be inside a loop;
+/// - a switch has at most one `default` clause;
+/// - the blank identifier is never used as a value;
+/// - only addressable expressions are assigned to;
+/// - both sides of an assignment have the same number
+///   of expressions;
+/// - the body of a function with a result ends in a
+///   terminating statement.
+pub fn weed(prog: &Program) -> result::Result<(), Vec<Error>> {
+    let mut weeder = Weeder { errors: Vec::new(), loops: 0, switches: 0 };
+    weeder.program(prog);
+    if weeder.errors.is_empty() {
+        return Ok(());
+    } else {
+        return Err(weeder.errors);
+    }
+}
+
+struct Weeder {
+    errors: Vec<Error>,
+    /// Number of enclosing loops.
+    loops: usize,
+    /// Number of enclosing switch statements.
+    switches: usize,
+}
+
+impl Weeder {
+    fn error(&mut self, ty: ET, loc: &Loc) {
+        self.errors.push(Error::new(ty, loc.clone()));
+    }
+
+    fn error_msg(&mut self, ty: ET, loc: &Loc, msg: String) {
+        self.errors.push(Error::with_msg(ty, loc.clone(), msg));
+    }
+
+    fn program(&mut self, prog: &Program) {
+        for decl in &prog.decls {
+            match *decl {
+                TopLevelDecl::Var(ref specs) => self.var_specs(specs),
+                TopLevelDecl::Type(ref specs) => self.type_specs(specs),
+                TopLevelDecl::Func(ref f) => {
+                    for param in &f.params {
+                        self.type_expr(&param.ty);
+                    }
+                    if let Some(ref ty) = f.result {
+                        self.type_expr(ty);
+                    }
+                    self.block(&f.body);
+                    if f.result.is_some() && !reach::ends_in_terminating(&f.body.stmts) {
+                        self.error(ET::MissingReturn, &f.body.end);
+                    }
+                }
+                TopLevelDecl::Error(_) => {}
+            }
+        }
+    }
+
+    fn var_specs(&mut self, specs: &[VarSpec]) {
+        for spec in specs {
+            if let Some(ref ty) = spec.ty {
+                self.type_expr(ty);
+            }
+            if !spec.values.is_empty() {
+                self.counts(spec.names.len(), spec.values.len(), &spec.loc);
+            }
+            self.exprs(&spec.values);
+        }
+    }
+
+    fn type_specs(&mut self, specs: &[TypeSpec]) {
+        for spec in specs {
+            self.type_expr(&spec.ty);
+        }
+    }
+
+    /// Array lengths are the only expressions inside types.
+    fn type_expr(&mut self, ty: &TypeExpr) {
+        match ty.kind {
+            TypeExprKind::Name(_) => {}
+            TypeExprKind::Array(ref len, ref elem) => {
+                self.expr(len);
+                self.type_expr(elem);
+            }
+            TypeExprKind::Slice(ref elem) => self.type_expr(elem),
+            TypeExprKind::Struct(ref fields) => {
+                for field in fields {
+                    self.type_expr(&field.ty);
+                }
+            }
+        }
+    }
+
+    /// Checks that `lhs` variables are given `rhs` values.
+    fn counts(&mut self, lhs: usize, rhs: usize, loc: &Loc) {
+        if lhs != rhs {
+            let msg = format!("assignment mismatch: {} variable{} but {} value{}",
+                              lhs, plural(lhs), rhs, plural(rhs));
+            self.error_msg(ET::AssignmentMismatch, loc, msg);
+        }
+    }
+
+
+    // STATEMENTS
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.block(block),
+            StmtKind::Expr(ref e) => self.expr(e),
+            StmtKind::Var(ref specs) => self.var_specs(specs),
+            StmtKind::Type(ref specs) => self.type_specs(specs),
+            StmtKind::Assign(ref lhs, ref rhs) => {
+                self.counts(lhs.len(), rhs.len(), &stmt.loc);
+                for e in lhs {
+                    // `_ = e` discards the value of `e`.
+                    if let ExprKind::Blank = e.kind {
+                        continue;
+                    }
+                    self.lvalue(e);
+                }
+                self.exprs(rhs);
+            }
+            StmtKind::OpAssign(_, ref lhs, ref rhs) => {
+                self.lvalue(lhs);
+                self.expr(rhs);
+            }
+            StmtKind::Incr(ref e) | StmtKind::Decr(ref e) => self.lvalue(e),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                self.counts(names.len(), values.len(), &stmt.loc);
+                self.exprs(values);
+            }
+            StmtKind::Print(ref args) | StmtKind::Println(ref args) => self.exprs(args),
+            StmtKind::Return(ref e) => {
+                if let Some(ref e) = *e {
+                    self.expr(e);
+                }
+            }
+            StmtKind::If(ref if_stmt) => {
+                if let Some(ref init) = if_stmt.init {
+                    self.stmt(init);
+                }
+                self.expr(&if_stmt.cond);
+                self.block(&if_stmt.then);
+                if let Some(ref els) = if_stmt.els {
+                    self.stmt(els);
+                }
+            }
+            StmtKind::Switch(ref switch_stmt) => {
+                if let Some(ref init) = switch_stmt.init {
+                    self.stmt(init);
+                }
+                if let Some(ref tag) = switch_stmt.tag {
+                    self.expr(tag);
+                }
+                let mut seen_default = false;
+                self.switches += 1;
+                for clause in &switch_stmt.clauses {
+                    match clause.exprs {
+                        Some(ref exprs) => self.exprs(exprs),
+                        None if seen_default => self.error(ET::MultipleDefaults, &clause.loc),
+                        None => seen_default = true,
+                    }
+                    for stmt in &clause.body {
+                        self.stmt(stmt);
+                    }
+                }
+                self.switches -= 1;
+            }
+            StmtKind::For(ref for_stmt) => {
+                if let Some(ref init) = for_stmt.init {
+                    self.stmt(init);
+                }
+                if let Some(ref cond) = for_stmt.cond {
+                    self.expr(cond);
+                }
+                if let Some(ref post) = for_stmt.post {
+                    self.stmt(post);
+                }
+                self.loops += 1;
+                self.block(&for_stmt.body);
+                self.loops -= 1;
+            }
+            StmtKind::Break => {
+                if self.loops == 0 && self.switches == 0 {
+                    self.error(ET::BreakOutsideLoop, &stmt.loc);
+                }
+            }
+            StmtKind::Continue => {
+                if self.loops == 0 {
+                    if self.switches > 0 {
+                        self.error(ET::ContinueInSwitch, &stmt.loc);
+                    } else {
+                        self.error(ET::ContinueOutsideLoop, &stmt.loc);
+                    }
+                }
+            }
+            StmtKind::Error => {}
+        }
+    }
+
+    /// Checks an expression that is assigned to.  Updating the
+    /// blank identifier is reported as a use of its value.
+    fn lvalue(&mut self, e: &Expr) {
+        let is_blank = matches!(e.kind, ExprKind::Blank);
+        if !is_blank && !is_addressable(e) {
+            let msg = format!("cannot assign to {}", pretty_expr(e));
+            self.error_msg(ET::NonAddressableAssignment, &e.loc, msg);
+        }
+        self.expr(e);
+    }
+
+
+    // EXPRESSIONS
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for e in exprs {
+            self.expr(e);
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e.kind {
+            ExprKind::Blank => self.error(ET::BlankAsValue, &e.loc),
+            ExprKind::Id(_)
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => {}
+            ExprKind::Unary(_, ref operand) => self.expr(operand),
+            ExprKind::Binary(_, ref lhs, ref rhs)
+            | ExprKind::Index(ref lhs, ref rhs)
+            | ExprKind::Append(ref lhs, ref rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::Call(ref f, ref args) => {
+                self.expr(f);
+                self.exprs(args);
+            }
+            ExprKind::Selector(ref e, _) => self.expr(e),
+        }
+    }
+}
+
+/// Returns whether `e` may syntactically denote a variable:
+/// an identifier, an indexed element, or a field of an
+/// addressable struct.  Indexing the result of a call is
+/// allowed here since it is addressable when the result
+/// is a slice; the type checker rejects arrays.
+fn is_addressable(e: &Expr) -> bool {
+    match e.kind {
+        ExprKind::Id(_) | ExprKind::Index(_, _) => true,
+        ExprKind::Selector(ref e, _) => is_addressable(e),
+        _ => false,
+    }
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
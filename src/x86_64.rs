@@ -0,0 +1,1801 @@
+use std::collections::HashMap;
+use std::mem;
+
+use ast::*;
+use constant::{self, Value};
+use loc::Loc;
+use lower::{self, one, Targets};
+use regalloc;
+use symtab::{SymbolId, SymbolKind, SymbolTable};
+use types::*;
+
+/// The registers that pass integer and floating-point arguments,
+/// in order.
+const INT_ARGS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+const SSE_ARGS: [&str; 8] = ["%xmm0", "%xmm1", "%xmm2", "%xmm3", "%xmm4", "%xmm5", "%xmm6", "%xmm7"];
+
+/// The registers that return integer and floating-point results.
+const INT_RESULTS: [&str; 2] = ["%rax", "%rdx"];
+const SSE_RESULTS: [&str; 2] = ["%xmm0", "%xmm1"];
+
+/// The registers that hold the values of the stack that are not
+/// pushed yet.  The generator only uses them otherwise to pass
+/// arguments and to copy values.
+const TEMPS: [&str; 3] = ["%r8", "%r9", "%r11"];
+
+/// The largest value copied with moves rather than `rep movsq`.
+const MAX_UNROLLED: i64 = 64;
+
+/// Translates a type-checked program to GNU assembly for x86-64
+/// Linux, to be linked with `runtime/runtime.c`:
+///
+/// ```text
+/// gore codegen --target=x86_64 foo.go -o foo.s
+/// cc foo.s runtime.c
+/// ```
+///
+/// The code is that of a stack machine on the hardware stack:
+/// every expression pushes its value, and operators pop their
+/// operands into registers.  The values on top of the stack stay
+/// in registers, though, until an instruction needs the stack or
+/// changes them, so that most of the temporaries of expressions
+/// never reach memory.  Every scalar takes 8 bytes, runes
+/// being sign-extended and booleans being 0 or 1; strings and
+/// slices are the structs of the runtime, and arrays and structs
+/// are laid out as C would lay out their fields.  Variables live
+/// in the frame of their function, at negative offsets from
+/// `%rbp`, and package variables in `.bss`, but for the scalar
+/// variables that `regalloc` puts in registers.
+///
+/// Functions follow the System V calling convention, their
+/// parameters and results being classified as C structs of
+/// 8-byte fields.  The generator keeps track of the depth of the
+/// stack, so that it is aligned on 16 bytes at every call.
+pub fn generate(prog: &Program, types: &TypeTable, table: &SymbolTable) -> String {
+    let filename = prog.package.loc.filename.clone();
+    let mut gen = Generator {
+        types: types,
+        table: table,
+        globals: HashMap::new(),
+        locals: HashMap::new(),
+        regs: HashMap::new(),
+        saved: Vec::new(),
+        strings: HashMap::new(),
+        rodata: String::new(),
+        bss: String::new(),
+        text: String::new(),
+        body: String::new(),
+        labels: 0,
+        depth: 0,
+        cache: Vec::new(),
+        frame: 0,
+        result: None,
+        targets: Targets::default(),
+    };
+    gen.type_defs();
+    gen.program(prog);
+
+    let mut out = format!("# Generated by gore from {}; link with runtime.c.\n", filename);
+    out.push_str(&format!("\t.file {}\n\t.text\n", asm_string(filename.as_bytes())));
+    out.push_str(&gen.text);
+    if !gen.rodata.is_empty() {
+        out.push_str(&format!("\n\t.section .rodata\n{}", gen.rodata));
+    }
+    if !gen.bss.is_empty() {
+        out.push_str(&format!("\n\t.bss\n\t.align 8\n{}", gen.bss));
+    }
+    out.push_str("\n\t.section .note.GNU-stack,\"\",@progbits\n");
+    return out;
+}
+
+/// A memory operand: an offset from the address in a register,
+/// or from a symbol, relative to the instruction pointer.
+#[derive(Clone)]
+struct Mem {
+    base: String,
+    offset: i64,
+}
+
+impl Mem {
+    fn new(base: &str, offset: i64) -> Mem {
+        return Mem { base: base.to_string(), offset: offset };
+    }
+
+    /// Returns the operand `offset` bytes further.
+    fn at(&self, offset: i64) -> String {
+        let offset = self.offset + offset;
+        if self.base.starts_with('%') {
+            if offset == 0 {
+                return format!("({})", self.base);
+            }
+            return format!("{}({})", offset, self.base);
+        }
+        if offset == 0 {
+            return format!("{}(%rip)", self.base);
+        }
+        return format!("{}{:+}(%rip)", self.base, offset);
+    }
+
+    fn plus(&self, offset: i64) -> Mem {
+        return Mem::new(&self.base, self.offset + offset);
+    }
+}
+
+/// Where a value is stored.
+#[derive(Clone)]
+enum Place {
+    Fixed(Mem),
+    /// A variable in a register.
+    Reg(&'static str),
+    /// At an offset from the address on top of the stack.
+    Pushed(i64),
+    /// At an offset from the address stored in a slot.
+    Saved(Mem, i64),
+    /// An element whose index is only checked when it is
+    /// accessed: the stack holds the address of the array, or
+    /// the slice, below the index.
+    Elem { size: i64, len: Option<u64>, loc: Loc },
+}
+
+struct Generator<'a> {
+    types: &'a TypeTable,
+    table: &'a SymbolTable,
+    /// The addresses of the package variables and of the
+    /// variables of the current function.
+    globals: HashMap<SymbolId, Mem>,
+    locals: HashMap<SymbolId, Mem>,
+    /// The registers of the variables of the current function,
+    /// and the slots where it saves the registers that it must
+    /// preserve.
+    regs: HashMap<SymbolId, &'static str>,
+    saved: Vec<(&'static str, Mem)>,
+    /// The labels of string literals and locations.
+    strings: HashMap<(bool, Vec<u8>), String>,
+    rodata: String,
+    bss: String,
+    text: String,
+    /// The instructions of the current function.
+    body: String,
+    labels: usize,
+    /// The number of bytes pushed since the end of the prologue,
+    /// and the size of the frame.
+    depth: i64,
+    /// The values on top of the stack that are not pushed yet,
+    /// from the lowest one: immediates and registers that have
+    /// kept the value that was pushed, and registers of `TEMPS`.
+    /// They count in `depth`.
+    cache: Vec<String>,
+    frame: i64,
+    /// The slot that holds the address where the current
+    /// function stores its result, if it is returned in memory.
+    result: Option<Mem>,
+    targets: Targets<String>,
+}
+
+impl<'a> Generator<'a> {
+    /// Emits an instruction, after pushing the values of the
+    /// cache if it uses the stack or jumps, and after moving
+    /// those that it may change out of their registers.
+    fn emit(&mut self, s: &str) {
+        if !self.cache.is_empty() {
+            let mnemonic = s.split_whitespace().next().unwrap_or("");
+            if s.contains("%rsp") || mnemonic.starts_with('j') || ["call", "ret", "leave"].contains(&mnemonic) {
+                self.flush();
+            } else {
+                self.clobber(&registers(s), None);
+            }
+        }
+        self.inst(s);
+    }
+
+    fn inst(&mut self, s: &str) {
+        self.body.push('\t');
+        self.body.push_str(s);
+        self.body.push('\n');
+    }
+
+    /// Pushes the values of the cache.
+    fn flush(&mut self) {
+        for value in mem::take(&mut self.cache) {
+            self.inst(&format!("pushq {}", value));
+        }
+    }
+
+    /// Moves the values of the cache that are in `regs` to other
+    /// registers of `TEMPS`, but for `keep`, or pushes the cache
+    /// if there is none.
+    fn clobber(&mut self, regs: &[String], keep: Option<&str>) {
+        for i in 0 .. self.cache.len() {
+            if !regs.contains(&self.cache[i]) {
+                continue;
+            }
+            let free = TEMPS.iter()
+                .find(|&&temp| !regs.iter().any(|reg| reg == temp)
+                      && keep != Some(temp)
+                      && !self.cache.iter().any(|value| value == temp));
+            match free {
+                Some(&temp) if !TEMPS.contains(&self.cache[i].as_str()) => {
+                    let value = mem::replace(&mut self.cache[i], temp.to_string());
+                    self.inst(&format!("movq {}, {}", value, temp));
+                }
+                _ => {
+                    self.flush();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        return format!(".L{}", self.labels);
+    }
+
+    fn label(&mut self, label: &str) {
+        self.flush();
+        self.body.push_str(&format!("{}:\n", label));
+    }
+
+    /// Pushes an immediate or the value of a register or of a
+    /// memory operand, which is loaded in a register of `TEMPS`
+    /// if one is free.
+    fn push(&mut self, operand: &str) {
+        self.depth += 8;
+        if operand.starts_with('$') || operand.starts_with('%') {
+            self.cache.push(operand.to_string());
+            return;
+        }
+        if operand == "(%rsp)" {
+            if let Some(top) = self.cache.last().cloned() {
+                if !TEMPS.contains(&top.as_str()) {
+                    self.cache.push(top);
+                    return;
+                }
+            }
+        }
+        let free = TEMPS.iter().find(|&&temp| !self.cache.iter().any(|value| value == temp));
+        match free {
+            Some(&temp) if !operand.contains("%rsp") => {
+                self.inst(&format!("movq {}, {}", operand, temp));
+                self.cache.push(temp.to_string());
+            }
+            _ => {
+                self.flush();
+                self.inst(&format!("pushq {}", operand));
+            }
+        }
+    }
+
+    /// Pops the value on top of the stack into a register or a
+    /// memory operand.
+    fn pop(&mut self, operand: &str) {
+        self.depth -= 8;
+        let top = match self.cache.pop() {
+            Some(top) if !operand.contains("%rsp") => top,
+            top => {
+                self.cache.extend(top);
+                self.flush();
+                self.inst(&format!("popq {}", operand));
+                return;
+            }
+        };
+        if operand.starts_with('%') {
+            self.clobber(&registers(operand), Some(&top));
+        }
+        if top != operand {
+            self.inst(&format!("movq {}, {}", top, operand));
+        }
+    }
+
+    /// Reserves `size` bytes on the stack.
+    fn alloc(&mut self, size: i64) {
+        if size > 0 {
+            self.emit(&format!("subq ${}, %rsp", size));
+            self.depth += size;
+        }
+    }
+
+    /// Releases `size` bytes of the stack.
+    fn free(&mut self, size: i64) {
+        if size > 0 {
+            self.emit(&format!("addq ${}, %rsp", size));
+            self.depth -= size;
+        }
+    }
+
+    /// Pushes a 64-bit constant.
+    fn push_imm(&mut self, n: i64) {
+        if n >= i32::MIN as i64 && n <= i32::MAX as i64 {
+            self.push(&format!("${}", n));
+        } else {
+            self.emit(&format!("movabsq ${}, %rax", n));
+            self.push("%rax");
+        }
+    }
+
+    /// Calls a function of the runtime or the C library, with
+    /// the stack aligned on 16 bytes.
+    fn call(&mut self, name: &str) {
+        if self.depth % 16 != 0 {
+            self.emit("subq $8, %rsp");
+            self.emit(&format!("call {}@PLT", name));
+            self.emit("addq $8, %rsp");
+        } else {
+            self.emit(&format!("call {}@PLT", name));
+        }
+    }
+
+    /// Allocates a slot of `size` bytes in the frame.
+    fn slot(&mut self, size: i64) -> Mem {
+        self.frame += (size + 7) / 8 * 8;
+        return Mem::new("%rbp", -self.frame);
+    }
+
+    /// Copies `size` bytes, using `%r11` (or `%rsi`, `%rdi` and
+    /// `%rcx` for large values) as scratch registers.
+    fn copy(&mut self, dst: &Mem, src: &Mem, size: i64) {
+        if size <= MAX_UNROLLED {
+            for i in (0 .. size).step_by(8) {
+                self.emit(&format!("movq {}, %r11", src.at(i)));
+                self.emit(&format!("movq %r11, {}", dst.at(i)));
+            }
+            return;
+        }
+        self.emit(&format!("leaq {}, %rsi", src.at(0)));
+        self.emit(&format!("leaq {}, %rdi", dst.at(0)));
+        self.emit(&format!("movq ${}, %rcx", size / 8));
+        self.emit("rep movsq");
+    }
+
+    /// Sets `size` bytes to zero.
+    fn zero(&mut self, dst: &Mem, size: i64) {
+        if size <= MAX_UNROLLED {
+            for i in (0 .. size).step_by(8) {
+                self.emit(&format!("movq $0, {}", dst.at(i)));
+            }
+            return;
+        }
+        self.emit(&format!("leaq {}, %rdi", dst.at(0)));
+        self.emit(&format!("movq ${}, %rcx", size / 8));
+        self.emit("xorl %eax, %eax");
+        self.emit("rep stosq");
+    }
+
+    /// Returns the label of the bytes of a string, with a
+    /// terminating NUL character if it is a C string.
+    fn string_label(&mut self, bytes: &[u8], c_string: bool) -> String {
+        let key = (c_string, bytes.to_vec());
+        if let Some(label) = self.strings.get(&key) {
+            return label.clone();
+        }
+        let label = format!(".LS{}", self.strings.len());
+        let directive = if c_string { ".asciz" } else { ".ascii" };
+        self.rodata.push_str(&format!("{}:\n\t{} {}\n", label, directive, asm_string(bytes)));
+        self.strings.insert(key, label.clone());
+        return label;
+    }
+
+    /// Loads the location of a runtime error, as a C string, in
+    /// a register.
+    fn location(&mut self, loc: &Loc, reg: &str) {
+        let label = self.string_label(loc.to_string().as_bytes(), true);
+        self.emit(&format!("leaq {}(%rip), {}", label, reg));
+    }
+
+
+    // TYPES
+
+    /// Returns the kind of a type, untyped constants having
+    /// their default type.
+    fn kind(&self, ty: TypeId) -> &TypeKind {
+        return self.types.under(self.types.default_type(ty));
+    }
+
+    fn is_rune(&self, ty: TypeId) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Rune => return true,
+            _ => return false,
+        }
+    }
+
+    /// Returns the size of a value in bytes, which is a multiple
+    /// of 8.
+    fn size(&self, ty: TypeId) -> i64 {
+        match *self.kind(ty) {
+            TypeKind::Int | TypeKind::Float64 | TypeKind::Bool | TypeKind::Rune => return 8,
+            TypeKind::String => return 16,
+            TypeKind::Slice(_) => return 24,
+            TypeKind::Array(len, elem) => return len as i64 * self.size(elem),
+            TypeKind::Struct(ref fields) => return fields.iter().map(|&(_, ty)| self.size(ty)).sum(),
+            _ => return 0,
+        }
+    }
+
+    /// Returns the offset of the field that a selector
+    /// expression selects.
+    fn field_offset(&self, e: &Expr) -> i64 {
+        if let ExprKind::Selector(ref x, ref field) = e.kind {
+            if let TypeKind::Struct(ref fields) = *self.kind(x.ty.unwrap_or(INVALID)) {
+                return fields.iter()
+                    .take_while(|f| f.0 != field.name)
+                    .map(|&(_, ty)| self.size(ty))
+                    .sum();
+            }
+        }
+        return 0;
+    }
+
+    /// Adds the classes of the 8-byte words of a value to
+    /// `classes`, `true` standing for SSE and `false` for
+    /// INTEGER; stops after three words.
+    fn words(&self, ty: TypeId, classes: &mut Vec<bool>) {
+        match *self.kind(ty) {
+            TypeKind::Float64 => classes.push(true),
+            TypeKind::Array(len, elem) => {
+                for _ in 0 .. len {
+                    if classes.len() > 2 {
+                        return;
+                    }
+                    self.words(elem, classes);
+                }
+            }
+            TypeKind::Struct(ref fields) => {
+                for &(_, ty) in fields {
+                    self.words(ty, classes);
+                }
+            }
+            _ => {
+                for _ in 0 .. self.size(ty) / 8 {
+                    classes.push(false);
+                }
+            }
+        }
+    }
+
+    /// Returns the classes of the words of a value that is
+    /// passed in registers, or `None` if it is passed in memory,
+    /// as it is when it is larger than 16 bytes.
+    fn classify(&self, ty: TypeId) -> Option<Vec<bool>> {
+        let mut classes = Vec::new();
+        self.words(ty, &mut classes);
+        if classes.len() > 2 {
+            return None;
+        }
+        return Some(classes);
+    }
+
+    /// Returns the registers that pass each argument, or `None`
+    /// for those passed on the stack, after the address of the
+    /// result if it is returned in memory.
+    fn arg_registers(&self, tys: &[TypeId], hidden: bool) -> Vec<Option<Vec<&'static str>>> {
+        let mut ints = if hidden { 1 } else { 0 };
+        let mut sses = 0;
+        let mut regs = Vec::new();
+        for &ty in tys {
+            let classes = match self.classify(ty) {
+                Some(classes) => classes,
+                None => {
+                    regs.push(None);
+                    continue;
+                }
+            };
+            let needed = classes.iter().filter(|&&sse| !sse).count();
+            if ints + needed > INT_ARGS.len() || sses + classes.len() - needed > SSE_ARGS.len() {
+                regs.push(None);
+                continue;
+            }
+            let mut arg = Vec::new();
+            for sse in classes {
+                if sse {
+                    arg.push(SSE_ARGS[sses]);
+                    sses += 1;
+                } else {
+                    arg.push(INT_ARGS[ints]);
+                    ints += 1;
+                }
+            }
+            regs.push(Some(arg));
+        }
+        return regs;
+    }
+
+    /// Returns the registers that return a value, in the order
+    /// of its words.
+    fn result_registers(&self, classes: &[bool]) -> Vec<&'static str> {
+        let (mut ints, mut sses) = (0, 0);
+        let mut regs = Vec::new();
+        for &sse in classes {
+            if sse {
+                regs.push(SSE_RESULTS[sses]);
+                sses += 1;
+            } else {
+                regs.push(INT_RESULTS[ints]);
+                ints += 1;
+            }
+        }
+        return regs;
+    }
+
+    /// Defines an equality function for every comparable array
+    /// and struct type.
+    fn type_defs(&mut self) {
+        let mut defined = vec![false; self.types.len()];
+        for ty in 0 .. self.types.len() {
+            let ty = self.types.underlying(ty);
+            if defined[ty] {
+                continue;
+            }
+            defined[ty] = true;
+            match *self.types.kind(ty) {
+                TypeKind::Array(_, _) | TypeKind::Struct(_) if self.types.is_comparable(ty) => self.eq_function(ty),
+                _ => {}
+            }
+        }
+    }
+
+    /// Defines `gore.eq.tN`, which compares two arrays or structs
+    /// of type `N` given their addresses in `%rdi` and `%rsi`,
+    /// and returns 0 or 1.  Blank fields are not compared.
+    fn eq_function(&mut self, ty: TypeId) {
+        let (differ, done) = (self.new_label(), self.new_label());
+        self.depth = 0;
+        // Saving three registers aligns the stack.
+        self.emit("pushq %rbx");
+        self.emit("pushq %r12");
+        self.emit("pushq %r13");
+        self.emit("movq %rdi, %rbx");
+        self.emit("movq %rsi, %r12");
+        match self.types.kind(ty).clone() {
+            TypeKind::Array(len, elem) => {
+                let (head, equal) = (self.new_label(), self.new_label());
+                self.emit(&format!("movq ${}, %r13", len));
+                self.label(&head);
+                self.emit("testq %r13, %r13");
+                self.emit(&format!("je {}", equal));
+                self.compare_words(elem, 0, &differ);
+                let size = self.size(elem);
+                self.emit(&format!("addq ${}, %rbx", size));
+                self.emit(&format!("addq ${}, %r12", size));
+                self.emit("decq %r13");
+                self.emit(&format!("jmp {}", head));
+                self.label(&equal);
+            }
+            TypeKind::Struct(fields) => {
+                let mut offset = 0;
+                for &(ref name, field_ty) in &fields {
+                    if name != "_" {
+                        self.compare_words(field_ty, offset, &differ);
+                    }
+                    offset += self.size(field_ty);
+                }
+            }
+            _ => {}
+        }
+        self.emit("movl $1, %eax");
+        self.emit(&format!("jmp {}", done));
+        self.label(&differ);
+        self.emit("xorl %eax, %eax");
+        self.label(&done);
+        self.emit("popq %r13");
+        self.emit("popq %r12");
+        self.emit("popq %rbx");
+        self.emit("ret");
+        let name = format!("gore.eq.t{}", ty);
+        let body = mem::take(&mut self.body);
+        self.text.push_str(&format!("\n\t.type {}, @function\n{}:\n{}\t.size {}, .-{}\n", name, name, body, name, name));
+    }
+
+    /// Compares the values of type `ty` at `offset` from `%rbx`
+    /// and `%r12`, and jumps to `differ` if they differ.
+    fn compare_words(&mut self, ty: TypeId, offset: i64, differ: &str) {
+        let (a, b) = (Mem::new("%rbx", offset), Mem::new("%r12", offset));
+        match *self.kind(ty) {
+            TypeKind::Float64 => {
+                self.emit(&format!("movq {}, %xmm0", a.at(0)));
+                self.emit(&format!("ucomisd {}, %xmm0", b.at(0)));
+                self.emit(&format!("jne {}", differ));
+                self.emit(&format!("jp {}", differ));
+            }
+            TypeKind::String => {
+                self.emit(&format!("movq {}, %rdi", a.at(0)));
+                self.emit(&format!("movq {}, %rsi", a.at(8)));
+                self.emit(&format!("movq {}, %rdx", b.at(0)));
+                self.emit(&format!("movq {}, %rcx", b.at(8)));
+                self.call("gore_string_eq");
+                self.emit("testb %al, %al");
+                self.emit(&format!("je {}", differ));
+            }
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => {
+                self.emit(&format!("leaq {}, %rdi", a.at(0)));
+                self.emit(&format!("leaq {}, %rsi", b.at(0)));
+                self.emit(&format!("call gore.eq.t{}", self.types.underlying(self.types.default_type(ty))));
+                self.emit("testq %rax, %rax");
+                self.emit(&format!("je {}", differ));
+            }
+            _ => {
+                self.emit(&format!("movq {}, %rax", a.at(0)));
+                self.emit(&format!("cmpq {}, %rax", b.at(0)));
+                self.emit(&format!("jne {}", differ));
+            }
+        }
+    }
+
+
+    // DECLARATIONS
+
+    fn program(&mut self, prog: &Program) {
+        let funcs: Vec<&FuncDecl> = prog.decls.iter()
+            .filter_map(|decl| match *decl {
+                TopLevelDecl::Func(ref f) if !f.name.is_blank() => Some(f),
+                _ => None,
+            })
+            .collect();
+
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                        let name = format!("{}.{}", id.name, id.sym.unwrap_or(0));
+                        let size = self.size(self.var_type(id));
+                        self.bss.push_str(&format!("{}:\n\t.zero {}\n", name, size));
+                        if let Some(sym) = id.sym {
+                            self.globals.insert(sym, Mem::new(&name, 0));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Package variables are initialized in the order of
+        // their declarations.
+        self.begin_function();
+        for decl in &prog.decls {
+            if let TopLevelDecl::Var(ref specs) = *decl {
+                for spec in specs {
+                    self.var_spec(spec, false);
+                }
+            }
+        }
+        self.end_function("gore.init");
+
+        for f in &funcs {
+            self.func_decl(f);
+        }
+
+        self.text.push_str("\n\t.globl main\n\t.type main, @function\nmain:\n");
+        self.text.push_str("\tpushq %rbp\n\tmovq %rsp, %rbp\n\tcall gore.init\n");
+        if funcs.iter().any(|f| f.name.name == "main") {
+            self.text.push_str("\tcall main.main\n");
+        }
+        self.text.push_str("\txorl %eax, %eax\n\tpopq %rbp\n\tret\n\t.size main, .-main\n");
+    }
+
+    fn begin_function(&mut self) {
+        self.locals.clear();
+        self.regs.clear();
+        self.saved.clear();
+        self.depth = 0;
+        self.cache.clear();
+        self.frame = 0;
+        self.result = None;
+    }
+
+    /// Restores the registers that the current function saved,
+    /// before it returns.
+    fn restore_registers(&mut self) {
+        for (reg, slot) in self.saved.clone() {
+            self.emit(&format!("movq {}, {}", slot.at(0), reg));
+        }
+    }
+
+    /// Writes the current function, with its prologue and an
+    /// epilogue for the end of its body.
+    fn end_function(&mut self, name: &str) {
+        self.restore_registers();
+        let body = mem::take(&mut self.body);
+        let frame = (self.frame + 15) / 16 * 16;
+        self.text.push_str(&format!("\n\t.type {}, @function\n{}:\n\tpushq %rbp\n\tmovq %rsp, %rbp\n", name, name));
+        if frame > 0 {
+            self.text.push_str(&format!("\tsubq ${}, %rsp\n", frame));
+        }
+        self.text.push_str(&body);
+        self.text.push_str(&format!("\tleave\n\tret\n\t.size {}, .-{}\n", name, name));
+    }
+
+    /// Stores the parameters that are passed in registers in the
+    /// frame, or in the registers allocated to them; the others
+    /// are used where the caller left them, above the return
+    /// address, unless they are allocated a register too.
+    fn func_decl(&mut self, f: &FuncDecl) {
+        self.begin_function();
+        let alloc = regalloc::allocate(f, self.types, self.table);
+        self.regs = alloc.registers();
+        for reg in alloc.callee_saved() {
+            let slot = self.slot(8);
+            self.emit(&format!("movq {}, {}", reg, slot.at(0)));
+            self.saved.push((reg, slot));
+        }
+        let result = lower::result_type(f, self.types, self.table);
+        if result != VOID && self.classify(result).is_none() {
+            let slot = self.slot(8);
+            self.emit(&format!("movq %rdi, {}", slot.at(0)));
+            self.result = Some(slot);
+        }
+        let tys: Vec<TypeId> = f.params.iter().map(|p| self.var_type(&p.name)).collect();
+        let regs = self.arg_registers(&tys, self.result.is_some());
+        let mut offset = 16;
+        for ((p, &ty), regs) in f.params.iter().zip(&tys).zip(regs) {
+            match regs {
+                Some(regs) => {
+                    if p.name.is_blank() {
+                        continue;
+                    }
+                    match self.declare(&p.name) {
+                        Place::Reg(var) => self.emit(&format!("movq {}, {}", regs[0], var)),
+                        Place::Fixed(var) => {
+                            for (i, reg) in regs.iter().enumerate() {
+                                self.emit(&format!("movq {}, {}", reg, var.at(8 * i as i64)));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                None => {
+                    let arg = Mem::new("%rbp", offset);
+                    match p.name.sym.and_then(|sym| self.regs.get(&sym)) {
+                        Some(reg) => self.emit(&format!("movq {}, {}", arg.at(0), reg)),
+                        None => {
+                            if let Some(sym) = p.name.sym {
+                                self.locals.insert(sym, arg);
+                            }
+                        }
+                    }
+                    offset += self.size(ty);
+                }
+            }
+        }
+        self.stmts(&f.body.stmts);
+        self.end_function(&format!("main.{}", f.name.name));
+    }
+
+    fn var_type(&self, id: &Ident) -> TypeId {
+        return id.sym.and_then(|sym| self.table.symbol(sym).ty).unwrap_or(INVALID);
+    }
+
+    /// Returns the address of a variable.
+    fn var_addr(&self, id: &Ident) -> Mem {
+        let sym = id.sym.unwrap_or(0);
+        match self.locals.get(&sym).or_else(|| self.globals.get(&sym)) {
+            Some(mem) => return mem.clone(),
+            None => return Mem::new("%rbp", 0),
+        }
+    }
+
+    /// Returns where a variable is stored.
+    fn var_place(&self, id: &Ident) -> Place {
+        match id.sym.and_then(|sym| self.regs.get(&sym)) {
+            Some(reg) => return Place::Reg(reg),
+            None => return Place::Fixed(self.var_addr(id)),
+        }
+    }
+
+    /// Allocates a local variable, unless it has a register, and
+    /// returns where it is stored.
+    fn declare(&mut self, id: &Ident) -> Place {
+        match id.sym.and_then(|sym| self.regs.get(&sym)) {
+            Some(reg) => return Place::Reg(reg),
+            None => return Place::Fixed(self.local(id)),
+        }
+    }
+
+    /// Allocates a local variable and returns its address.
+    fn local(&mut self, id: &Ident) -> Mem {
+        let size = self.size(self.var_type(id));
+        let slot = self.slot(size);
+        if let Some(sym) = id.sym {
+            self.locals.insert(sym, slot.clone());
+        }
+        return slot;
+    }
+
+    /// Declares (or, for package variables, initializes) the
+    /// variables of a `var` spec.
+    fn var_spec(&mut self, spec: &VarSpec, local: bool) {
+        if spec.values.is_empty() {
+            if local {
+                for id in spec.names.iter().filter(|id| !id.is_blank()) {
+                    match self.declare(id) {
+                        Place::Reg(reg) if reg.starts_with("%xmm") => self.emit(&format!("xorpd {}, {}", reg, reg)),
+                        Place::Reg(reg) => self.emit(&format!("movq $0, {}", reg)),
+                        Place::Fixed(var) => {
+                            let size = self.size(self.var_type(id));
+                            self.zero(&var, size);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            return;
+        }
+        let targets: Vec<Option<Place>> = spec.names.iter()
+            .map(|id| {
+                if id.is_blank() {
+                    return None;
+                }
+                if local {
+                    return Some(self.declare(id));
+                }
+                return Some(Place::Fixed(self.var_addr(id)));
+            })
+            .collect();
+        self.store_values(targets, &spec.values);
+    }
+
+
+    // STATEMENTS
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::Block(ref block) => self.stmts(&block.stmts),
+            StmtKind::Expr(ref e) => {
+                if let ExprKind::Call(ref f, ref args) = e.kind {
+                    let ty = e.ty.unwrap_or(VOID);
+                    self.func_call(f, args, ty);
+                    let size = self.size(ty);
+                    self.free(size);
+                }
+            }
+            StmtKind::Var(ref specs) => {
+                for spec in specs {
+                    self.var_spec(spec, true);
+                }
+            }
+            StmtKind::Type(_) | StmtKind::Error => {}
+            StmtKind::Assign(ref lhs, ref rhs) => self.assign(lhs, rhs),
+            StmtKind::OpAssign(op, ref lhs, ref rhs) => self.op_assign(op, lhs, rhs, &stmt.loc),
+            StmtKind::Incr(ref e) => self.op_assign(BinOp::Add, e, &one(e), &stmt.loc),
+            StmtKind::Decr(ref e) => self.op_assign(BinOp::Sub, e, &one(e), &stmt.loc),
+            StmtKind::ShortVarDecl(ref names, ref values) => {
+                let targets: Vec<Option<Place>> = names.iter()
+                    .map(|id| {
+                        if id.is_blank() {
+                            return None;
+                        }
+                        if self.is_new_var(id) {
+                            return Some(self.declare(id));
+                        }
+                        return Some(self.var_place(id));
+                    })
+                    .collect();
+                self.store_values(targets, values);
+            }
+            StmtKind::Print(ref args) => self.print(args, false),
+            StmtKind::Println(ref args) => self.print(args, true),
+            StmtKind::Return(ref e) => self.return_stmt(e),
+            StmtKind::Break => {
+                if let Some(label) = self.targets.brk() {
+                    self.emit(&format!("jmp {}", label));
+                }
+            }
+            StmtKind::Continue => {
+                if let Some(label) = self.targets.cont() {
+                    self.emit(&format!("jmp {}", label));
+                }
+            }
+            StmtKind::If(ref if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::Switch(ref switch_stmt) => self.switch_stmt(switch_stmt),
+            StmtKind::For(ref for_stmt) => self.for_stmt(for_stmt),
+        }
+    }
+
+    /// Returns whether a name of a `:=` statement declares a
+    /// variable, rather than assigning to an existing one.
+    fn is_new_var(&self, id: &Ident) -> bool {
+        match id.sym {
+            Some(sym) => self.table.symbol(sym).loc.as_ref() == Some(&id.loc),
+            None => false,
+        }
+    }
+
+    /// Puts the result in its registers, or where the caller
+    /// asked for it.
+    fn return_stmt(&mut self, e: &Option<Expr>) {
+        if let Some(ref e) = *e {
+            let ty = e.ty.unwrap_or(INVALID);
+            let size = self.size(ty);
+            self.expr(e);
+            let classes = self.classify(ty).unwrap_or_default();
+            match self.result.clone() {
+                Some(slot) => {
+                    self.emit(&format!("movq {}, %rax", slot.at(0)));
+                    self.copy(&Mem::new("%rax", 0), &Mem::new("%rsp", 0), size);
+                }
+                None if size == 8 => {
+                    let reg = self.result_registers(&classes)[0];
+                    self.pop("%rax");
+                    if reg != "%rax" {
+                        self.emit(&format!("movq %rax, {}", reg));
+                    }
+                    self.depth += size;
+                }
+                None => {
+                    for (i, reg) in self.result_registers(&classes).iter().enumerate() {
+                        self.emit(&format!("movq {}(%rsp), {}", 8 * i, reg));
+                    }
+                }
+            }
+            // The epilogue pops the value.
+            self.depth -= size;
+        }
+        self.restore_registers();
+        self.emit("leave");
+        self.emit("ret");
+    }
+
+    /// Assigns values to variables, elements and fields.  The
+    /// index of a single element is checked once its value is
+    /// known; with several targets, their operands are all
+    /// evaluated and checked before the values.
+    fn assign(&mut self, lhs: &[Expr], rhs: &[Expr]) {
+        if lhs.len() == 1 {
+            let ty = rhs[0].ty.unwrap_or(INVALID);
+            if let ExprKind::Blank = lhs[0].kind {
+                self.expr(&rhs[0]);
+                let size = self.size(ty);
+                self.free(size);
+                return;
+            }
+            let place = self.place(&lhs[0]);
+            self.expr(&rhs[0]);
+            self.store(place, ty);
+            return;
+        }
+        let mut targets = Vec::new();
+        for e in lhs {
+            let target = match e.kind {
+                ExprKind::Blank => None,
+                _ => {
+                    let place = self.place(e);
+                    match self.resolve(place) {
+                        Place::Pushed(offset) => {
+                            let slot = self.slot(8);
+                            self.pop(&slot.at(0));
+                            Some(Place::Saved(slot, offset))
+                        }
+                        place => Some(place),
+                    }
+                }
+            };
+            targets.push(target);
+        }
+        self.store_values(targets, rhs);
+    }
+
+    /// Evaluates all the values before storing them, from left
+    /// to right, in places that are fixed or saved.
+    fn store_values(&mut self, targets: Vec<Option<Place>>, values: &[Expr]) {
+        if values.len() == 1 {
+            let ty = values[0].ty.unwrap_or(INVALID);
+            self.expr(&values[0]);
+            match targets.into_iter().next() {
+                Some(Some(place)) => self.store(place, ty),
+                _ => {
+                    let size = self.size(ty);
+                    self.free(size);
+                }
+            }
+            return;
+        }
+        let start = self.depth;
+        let mut ends = Vec::new();
+        for e in values {
+            self.expr(e);
+            ends.push(self.depth);
+        }
+        for ((target, e), end) in targets.into_iter().zip(values).zip(ends) {
+            let size = self.size(e.ty.unwrap_or(INVALID));
+            let src = Mem::new("%rsp", self.depth - end);
+            match target {
+                Some(Place::Fixed(mem)) => self.copy(&mem, &src, size),
+                Some(Place::Reg(reg)) => self.emit(&format!("movq {}, {}", src.at(0), reg)),
+                Some(Place::Saved(slot, offset)) => {
+                    self.emit(&format!("movq {}, %rax", slot.at(0)));
+                    self.copy(&Mem::new("%rax", offset), &src, size);
+                }
+                _ => {}
+            }
+        }
+        let size = self.depth - start;
+        self.free(size);
+    }
+
+    /// `x op= y`: an address that `x` pushes is copied, so that
+    /// loading the value of `x` leaves it for the store.
+    fn op_assign(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, loc: &Loc) {
+        let ty = lhs.ty.unwrap_or(INVALID);
+        let place = self.place(lhs);
+        let place = self.resolve(place);
+        if let Place::Pushed(_) = place {
+            // Loading the value pops the address.
+            self.push("(%rsp)");
+        }
+        self.load(place.clone(), ty);
+        self.expr(rhs);
+        self.arith(op, ty, loc);
+        self.store(place, ty);
+    }
+
+    /// Pushes the values of all the arguments, passes each of
+    /// them to the runtime, and then frees them.
+    fn print(&mut self, args: &[Expr], newline: bool) {
+        let start = self.depth;
+        let mut ends = Vec::new();
+        for arg in args {
+            self.expr(arg);
+            ends.push(self.depth);
+        }
+        for (i, (arg, end)) in args.iter().zip(ends).enumerate() {
+            if newline && i > 0 {
+                self.emit("movl $32, %edi");
+                self.call("putchar");
+            }
+            let value = Mem::new("%rsp", self.depth - end);
+            match *self.kind(arg.ty.unwrap_or(INVALID)) {
+                TypeKind::Float64 => {
+                    self.emit(&format!("movq {}, %xmm0", value.at(0)));
+                    self.call("gore_print_float");
+                }
+                TypeKind::Bool => {
+                    self.emit(&format!("movq {}, %rdi", value.at(0)));
+                    self.call("gore_print_bool");
+                }
+                TypeKind::String => {
+                    self.emit(&format!("movq {}, %rdi", value.at(0)));
+                    self.emit(&format!("movq {}, %rsi", value.at(8)));
+                    self.call("gore_print_string");
+                }
+                _ => {
+                    self.emit(&format!("movq {}, %rdi", value.at(0)));
+                    self.call("gore_print_int");
+                }
+            }
+        }
+        if newline {
+            self.emit("movl $10, %edi");
+            self.call("putchar");
+        }
+        let size = self.depth - start;
+        self.free(size);
+    }
+
+    /// Generates the simple statement that introduces an `if`,
+    /// `switch` or `for` statement.
+    fn init(&mut self, init: &Option<Box<Stmt>>) {
+        if let Some(ref init) = *init {
+            self.stmt(init);
+        }
+    }
+
+    /// Pops a boolean and jumps to `label` if it is `jump_if`.
+    fn branch(&mut self, jump_if: bool, label: &str) {
+        self.pop("%rax");
+        self.emit("testq %rax, %rax");
+        self.emit(&format!("{} {}", if jump_if { "jne" } else { "je" }, label));
+    }
+
+    fn if_stmt(&mut self, if_stmt: &IfStmt) {
+        self.init(&if_stmt.init);
+        self.expr(&if_stmt.cond);
+        let (els, end) = (self.new_label(), self.new_label());
+        self.branch(false, &els);
+        self.stmts(&if_stmt.then.stmts);
+        self.emit(&format!("jmp {}", end));
+        self.label(&els);
+        if let Some(ref els) = if_stmt.els {
+            self.stmt(els);
+        }
+        self.label(&end);
+    }
+
+    /// The tag and the case expressions are evaluated in order
+    /// until a case matches, which selects the clause to execute.
+    /// The tag is kept in a slot.
+    fn switch_stmt(&mut self, switch_stmt: &SwitchStmt) {
+        self.init(&switch_stmt.init);
+        let tag = match switch_stmt.tag {
+            Some(ref tag) => {
+                let ty = tag.ty.unwrap_or(INVALID);
+                self.expr(tag);
+                let slot = self.slot(self.size(ty));
+                self.store(Place::Fixed(slot.clone()), ty);
+                Some((slot, ty))
+            }
+            None => None,
+        };
+        let end = self.new_label();
+        let bodies: Vec<String> = switch_stmt.clauses.iter().map(|_| self.new_label()).collect();
+        for (case, body) in switch_stmt.clauses.iter().zip(&bodies) {
+            let exprs = match case.exprs {
+                Some(ref exprs) => exprs,
+                None => continue,
+            };
+            for e in exprs {
+                match tag {
+                    Some((ref slot, ty)) => {
+                        self.load(Place::Fixed(slot.clone()), ty);
+                        self.expr(e);
+                        self.compare(BinOp::Eq, ty);
+                    }
+                    None => self.expr(e),
+                }
+                self.branch(true, body);
+            }
+        }
+        let default = switch_stmt.clauses.iter().position(|c| c.exprs.is_none());
+        let otherwise = default.map_or(end.clone(), |i| bodies[i].clone());
+        self.emit(&format!("jmp {}", otherwise));
+
+        self.targets.push_switch(end.clone());
+        for (case, body) in switch_stmt.clauses.iter().zip(&bodies) {
+            self.label(body);
+            self.stmts(&case.body);
+            self.emit(&format!("jmp {}", end));
+        }
+        self.targets.pop();
+        self.label(&end);
+    }
+
+    /// The condition is tested at the top of the loop, and
+    /// `continue` jumps to the post statement.
+    fn for_stmt(&mut self, for_stmt: &ForStmt) {
+        self.init(&for_stmt.init);
+        let (head, post, end) = (self.new_label(), self.new_label(), self.new_label());
+        self.label(&head);
+        if let Some(ref cond) = for_stmt.cond {
+            self.expr(cond);
+            self.branch(false, &end);
+        }
+        self.targets.push_loop(end.clone(), post.clone());
+        self.stmts(&for_stmt.body.stmts);
+        self.targets.pop();
+        self.label(&post);
+        if let Some(ref stmt) = for_stmt.post {
+            self.stmt(stmt);
+        }
+        self.emit(&format!("jmp {}", head));
+        self.label(&end);
+    }
+
+
+    // EXPRESSIONS
+
+    /// Pushes the value of `e`.
+    fn expr(&mut self, e: &Expr) {
+        let ty = e.ty.unwrap_or(INVALID);
+        if let Some(ref value) = e.value {
+            self.constant(value, ty);
+            return;
+        }
+        match e.kind {
+            ExprKind::Unary(op, ref x) => {
+                self.expr(x);
+                self.unary(op, ty);
+            }
+            ExprKind::Binary(BinOp::And, ref x, ref y) => self.logical(true, x, y),
+            ExprKind::Binary(BinOp::Or, ref x, ref y) => self.logical(false, x, y),
+            ExprKind::Binary(op, ref x, ref y) => {
+                let operand_ty = x.ty.unwrap_or(INVALID);
+                self.expr(x);
+                self.expr(y);
+                if op.is_comparison() {
+                    self.compare(op, operand_ty);
+                } else {
+                    self.arith(op, operand_ty, &e.loc);
+                }
+            }
+            ExprKind::Call(ref f, ref args) => {
+                let kind = match f.kind {
+                    ExprKind::Id(Ident { sym: Some(sym), .. }) => self.table.symbol(sym).kind,
+                    _ => SymbolKind::Func,
+                };
+                match kind {
+                    SymbolKind::Type => self.conversion(ty, &args[0]),
+                    SymbolKind::Builtin => self.builtin(f, &args[0]),
+                    _ => self.func_call(f, args, ty),
+                }
+            }
+            ExprKind::Id(_) | ExprKind::Index(_, _) | ExprKind::Selector(_, _) => {
+                let place = self.place(e);
+                self.load(place, ty);
+            }
+            ExprKind::Append(ref s, ref x) => {
+                let size = match *self.kind(ty) {
+                    TypeKind::Slice(elem) => self.size(elem),
+                    _ => 0,
+                };
+                self.expr(s);
+                self.expr(x);
+                // `gore_append` takes the slice on the stack, and
+                // returns the new one where the slice was.
+                let pad = (16 - (self.depth + 24) % 16) % 16;
+                self.alloc(pad + 24);
+                let slice = Mem::new("%rsp", pad + 24 + size);
+                self.copy(&Mem::new("%rsp", 0), &slice, 24);
+                self.emit(&format!("leaq {}, %rdi", slice.at(0)));
+                self.emit(&format!("leaq {}(%rsp), %rsi", pad + 24));
+                self.emit(&format!("movq ${}, %rdx", size));
+                self.call("gore_append");
+                self.free(pad + 24 + size);
+            }
+            // Literals are constants, and blank identifiers
+            // are never evaluated.
+            ExprKind::Blank
+            | ExprKind::Int(_, _)
+            | ExprKind::Float(_)
+            | ExprKind::Rune(_)
+            | ExprKind::String(_) => {
+                let size = self.size(ty);
+                self.alloc(size);
+                self.zero(&Mem::new("%rsp", 0), size);
+            }
+        }
+    }
+
+    /// Returns where the value of a variable, element or field
+    /// is stored, after pushing the operands of its address.
+    /// Arrays and structs that are not stored in variables are
+    /// copied to slots.
+    fn place(&mut self, e: &Expr) -> Place {
+        let ty = e.ty.unwrap_or(INVALID);
+        match e.kind {
+            ExprKind::Id(ref id) => return self.var_place(id),
+            ExprKind::Index(ref a, ref i) => {
+                match *self.kind(a.ty.unwrap_or(INVALID)) {
+                    TypeKind::Array(len, _) => {
+                        let base = self.place(a);
+                        self.push_address(base);
+                        self.expr(i);
+                        return Place::Elem { size: self.size(ty), len: Some(len), loc: e.loc.clone() };
+                    }
+                    _ => {
+                        self.expr(a);
+                        self.expr(i);
+                        return Place::Elem { size: self.size(ty), len: None, loc: e.loc.clone() };
+                    }
+                }
+            }
+            ExprKind::Selector(ref x, _) => {
+                let offset = self.field_offset(e);
+                let place = self.place(x);
+                match self.resolve(place) {
+                    Place::Fixed(mem) => return Place::Fixed(mem.plus(offset)),
+                    Place::Pushed(base) => return Place::Pushed(base + offset),
+                    Place::Saved(slot, base) => return Place::Saved(slot, base + offset),
+                    place => return place,
+                }
+            }
+            _ => {
+                self.expr(e);
+                let size = self.size(ty);
+                let slot = self.slot(size);
+                self.copy(&slot, &Mem::new("%rsp", 0), size);
+                self.free(size);
+                return Place::Fixed(slot);
+            }
+        }
+    }
+
+    /// Checks the index of an element and pushes its address;
+    /// other places are returned as they are.
+    fn resolve(&mut self, place: Place) -> Place {
+        match place {
+            Place::Elem { size, len, loc } => {
+                self.pop("%rdi");
+                match len {
+                    Some(len) => self.emit(&format!("movq ${}, %rsi", len)),
+                    None => self.emit("movq 8(%rsp), %rsi"),
+                }
+                self.location(&loc, "%rdx");
+                self.call("gore_index");
+                self.pop("%rcx");
+                if len.is_none() {
+                    // The length and the capacity of the slice.
+                    self.free(16);
+                }
+                if size != 1 {
+                    self.emit(&format!("imulq ${}, %rax, %rax", size));
+                }
+                self.emit("addq %rcx, %rax");
+                self.push("%rax");
+                return Place::Pushed(0);
+            }
+            place => return place,
+        }
+    }
+
+    /// Pushes the address of a place.
+    fn push_address(&mut self, place: Place) {
+        match self.resolve(place) {
+            Place::Fixed(mem) => {
+                self.emit(&format!("leaq {}, %rax", mem.at(0)));
+                self.push("%rax");
+            }
+            Place::Pushed(offset) => {
+                if offset != 0 {
+                    self.emit(&format!("addq ${}, (%rsp)", offset));
+                }
+            }
+            Place::Saved(slot, offset) => {
+                self.emit(&format!("movq {}, %rax", slot.at(0)));
+                self.emit(&format!("leaq {}(%rax), %rax", offset));
+                self.push("%rax");
+            }
+            Place::Reg(_) | Place::Elem { .. } => {}
+        }
+    }
+
+    /// Pushes the value stored in a place.
+    fn load(&mut self, place: Place, ty: TypeId) {
+        let size = self.size(ty);
+        match self.resolve(place) {
+            Place::Fixed(ref mem) if size == 8 => self.push(&mem.at(0)),
+            Place::Fixed(mem) => {
+                self.alloc(size);
+                self.copy(&Mem::new("%rsp", 0), &mem, size);
+            }
+            Place::Reg(reg) if reg.starts_with("%xmm") => {
+                self.emit(&format!("movq {}, %rax", reg));
+                self.push("%rax");
+            }
+            Place::Reg(reg) => self.push(reg),
+            Place::Pushed(offset) => {
+                self.pop("%rax");
+                self.alloc(size);
+                self.copy(&Mem::new("%rsp", 0), &Mem::new("%rax", offset), size);
+            }
+            Place::Saved(slot, offset) => {
+                self.emit(&format!("movq {}, %rax", slot.at(0)));
+                self.alloc(size);
+                self.copy(&Mem::new("%rsp", 0), &Mem::new("%rax", offset), size);
+            }
+            Place::Elem { .. } => {}
+        }
+    }
+
+    /// Pops a value into a place, whose operands are below the
+    /// value on the stack.
+    fn store(&mut self, place: Place, ty: TypeId) {
+        let size = self.size(ty);
+        let top = Mem::new("%rsp", 0);
+        match place {
+            Place::Fixed(ref mem) if size == 8 => self.pop(&mem.at(0)),
+            Place::Fixed(mem) => {
+                self.copy(&mem, &top, size);
+                self.free(size);
+            }
+            Place::Reg(reg) if reg.starts_with("%xmm") => {
+                self.pop("%rax");
+                self.emit(&format!("movq %rax, {}", reg));
+            }
+            Place::Reg(reg) => self.pop(reg),
+            Place::Pushed(offset) => {
+                self.emit(&format!("movq {}(%rsp), %rax", size));
+                self.copy(&Mem::new("%rax", offset), &top, size);
+                self.free(size + 8);
+            }
+            Place::Saved(slot, offset) => {
+                self.emit(&format!("movq {}, %rax", slot.at(0)));
+                self.copy(&Mem::new("%rax", offset), &top, size);
+                self.free(size);
+            }
+            elem => {
+                // The value is set aside while the index is
+                // checked.
+                let slot = self.slot(size);
+                self.copy(&slot, &top, size);
+                self.free(size);
+                if let Place::Pushed(offset) = self.resolve(elem) {
+                    self.pop("%rax");
+                    self.copy(&Mem::new("%rax", offset), &slot, size);
+                }
+            }
+        }
+    }
+
+    fn constant(&mut self, value: &Value, ty: TypeId) {
+        match *value {
+            Value::Bool(b) => self.push(if b { "$1" } else { "$0" }),
+            Value::String(ref s) => {
+                let bytes = constant::string_bytes(s);
+                self.push_imm(bytes.len() as i64);
+                if bytes.is_empty() {
+                    self.push("$0");
+                } else {
+                    let label = self.string_label(&bytes, false);
+                    self.emit(&format!("leaq {}(%rip), %rax", label));
+                    self.push("%rax");
+                }
+            }
+            Value::Int(_) | Value::Float(_) if self.types.is_float(self.types.default_type(ty)) => {
+                let f = value.to_rat().map_or(0.0, |r| r.to_f64());
+                self.push_imm(f.to_bits() as i64);
+            }
+            Value::Int(_) | Value::Float(_) => {
+                let n = value.to_int().and_then(|n| n.to_i64()).unwrap_or(0);
+                self.push_imm(n);
+            }
+        }
+    }
+
+    fn unary(&mut self, op: UnOp, ty: TypeId) {
+        let float = self.types.is_float(self.types.default_type(ty));
+        if op == UnOp::Plus {
+            return;
+        }
+        self.pop("%rax");
+        match op {
+            // Flips the sign bit.
+            UnOp::Neg if float => self.emit("btcq $63, %rax"),
+            UnOp::Neg if self.is_rune(ty) => {
+                self.emit("negl %eax");
+                self.emit("movslq %eax, %rax");
+            }
+            UnOp::Neg => self.emit("negq %rax"),
+            UnOp::Not => self.emit("xorq $1, %rax"),
+            _ => self.emit("notq %rax"),
+        }
+        self.push("%rax");
+    }
+
+    /// `&&` and `||` only evaluate their right operand if
+    /// needed.
+    fn logical(&mut self, and: bool, x: &Expr, y: &Expr) {
+        let end = self.new_label();
+        self.expr(x);
+        self.branch(!and, &end);
+        self.expr(y);
+        self.pop("%rax");
+        self.label(&end);
+        self.push("%rax");
+    }
+
+    /// Replaces the operands of an arithmetic or bitwise operator
+    /// of type `ty` by its result.  Integer operations wrap
+    /// around and division by zero panics, as in Go.
+    fn arith(&mut self, op: BinOp, ty: TypeId, loc: &Loc) {
+        let (bits, rune) = match *self.kind(ty) {
+            TypeKind::Int => (64, false),
+            TypeKind::Rune => (32, true),
+            TypeKind::String => {
+                self.emit("movq 16(%rsp), %rdi");
+                self.emit("movq 24(%rsp), %rsi");
+                self.emit("movq (%rsp), %rdx");
+                self.emit("movq 8(%rsp), %rcx");
+                self.free(32);
+                self.call("gore_concat");
+                self.alloc(16);
+                self.emit("movq %rax, (%rsp)");
+                self.emit("movq %rdx, 8(%rsp)");
+                return;
+            }
+            _ => {
+                let inst = match op {
+                    BinOp::Add => "addsd",
+                    BinOp::Sub => "subsd",
+                    BinOp::Mul => "mulsd",
+                    _ => "divsd",
+                };
+                self.pop("%rcx");
+                self.pop("%rax");
+                self.emit("movq %rax, %xmm0");
+                self.emit("movq %rcx, %xmm1");
+                self.emit(&format!("{} %xmm1, %xmm0", inst));
+                self.emit("movq %xmm0, %rax");
+                self.push("%rax");
+                return;
+            }
+        };
+        self.pop("%rcx");
+        self.pop("%rax");
+        match op {
+            BinOp::Add => self.emit("addq %rcx, %rax"),
+            BinOp::Sub => self.emit("subq %rcx, %rax"),
+            BinOp::Mul => self.emit("imulq %rcx, %rax"),
+            BinOp::BitAnd => self.emit("andq %rcx, %rax"),
+            BinOp::BitOr => self.emit("orq %rcx, %rax"),
+            BinOp::BitXor => self.emit("xorq %rcx, %rax"),
+            BinOp::BitClear => {
+                self.emit("notq %rcx");
+                self.emit("andq %rcx, %rax");
+            }
+            _ => {
+                let f = match op {
+                    BinOp::Div => "div",
+                    BinOp::Rem => "rem",
+                    BinOp::Shl => "shl",
+                    _ => "shr",
+                };
+                self.emit("movq %rax, %rdi");
+                self.emit("movq %rcx, %rsi");
+                self.location(loc, "%rdx");
+                self.call(&format!("gore_{}{}", f, bits));
+            }
+        }
+        if rune {
+            self.emit("movslq %eax, %rax");
+        }
+        self.push("%rax");
+    }
+
+    /// Replaces the operands of a comparison of type `ty` by its
+    /// result.
+    fn compare(&mut self, op: BinOp, ty: TypeId) {
+        match *self.kind(ty) {
+            TypeKind::String => {
+                self.emit("movq 16(%rsp), %rdi");
+                self.emit("movq 24(%rsp), %rsi");
+                self.emit("movq (%rsp), %rdx");
+                self.emit("movq 8(%rsp), %rcx");
+                self.free(32);
+                match op {
+                    BinOp::Eq | BinOp::Ne => {
+                        self.call("gore_string_eq");
+                        self.emit("movzbl %al, %eax");
+                        if op == BinOp::Ne {
+                            self.emit("xorl $1, %eax");
+                        }
+                    }
+                    _ => {
+                        self.call("gore_string_cmp");
+                        self.emit("cmpl $0, %eax");
+                        self.emit(&format!("set{} %al", condition(op)));
+                        self.emit("movzbl %al, %eax");
+                    }
+                }
+            }
+            TypeKind::Float64 => {
+                self.pop("%rcx");
+                self.pop("%rax");
+                self.emit("movq %rax, %xmm0");
+                self.emit("movq %rcx, %xmm1");
+                // Comparisons with NaN are false, except `!=`.
+                match op {
+                    BinOp::Eq => {
+                        self.emit("ucomisd %xmm1, %xmm0");
+                        self.emit("sete %al");
+                        self.emit("setnp %cl");
+                        self.emit("andb %cl, %al");
+                    }
+                    BinOp::Ne => {
+                        self.emit("ucomisd %xmm1, %xmm0");
+                        self.emit("setne %al");
+                        self.emit("setp %cl");
+                        self.emit("orb %cl, %al");
+                    }
+                    BinOp::Gt => {
+                        self.emit("ucomisd %xmm1, %xmm0");
+                        self.emit("seta %al");
+                    }
+                    BinOp::Ge => {
+                        self.emit("ucomisd %xmm1, %xmm0");
+                        self.emit("setae %al");
+                    }
+                    BinOp::Lt => {
+                        self.emit("ucomisd %xmm0, %xmm1");
+                        self.emit("seta %al");
+                    }
+                    _ => {
+                        self.emit("ucomisd %xmm0, %xmm1");
+                        self.emit("setae %al");
+                    }
+                }
+                self.emit("movzbl %al, %eax");
+            }
+            TypeKind::Array(_, _) | TypeKind::Struct(_) => {
+                let size = self.size(ty);
+                self.emit(&format!("leaq {}(%rsp), %rdi", size));
+                self.emit("movq %rsp, %rsi");
+                let name = format!("gore.eq.t{}", self.types.underlying(self.types.default_type(ty)));
+                if self.depth % 16 != 0 {
+                    self.emit("subq $8, %rsp");
+                    self.emit(&format!("call {}", name));
+                    self.emit("addq $8, %rsp");
+                } else {
+                    self.emit(&format!("call {}", name));
+                }
+                self.free(2 * size);
+                if op == BinOp::Ne {
+                    self.emit("xorl $1, %eax");
+                }
+            }
+            _ => {
+                self.pop("%rcx");
+                self.pop("%rax");
+                self.emit("cmpq %rcx, %rax");
+                self.emit(&format!("set{} %al", condition(op)));
+                self.emit("movzbl %al, %eax");
+            }
+        }
+        self.push("%rax");
+    }
+
+    /// Calls a function, after evaluating its arguments, and
+    /// pushes its result.  The arguments are pushed in order,
+    /// then those passed on the stack are copied below them and
+    /// the others loaded in registers.  A result returned in
+    /// memory is stored in a space reserved before the
+    /// arguments.
+    fn func_call(&mut self, f: &Expr, args: &[Expr], ty: TypeId) {
+        let name = match f.kind {
+            ExprKind::Id(ref id) => id.name.clone(),
+            _ => String::new(),
+        };
+        let size = self.size(ty);
+        let classes = if ty == VOID { Some(Vec::new()) } else { self.classify(ty) };
+        let start = self.depth;
+        if classes.is_none() {
+            self.alloc(size);
+        }
+        let tys: Vec<TypeId> = args.iter().map(|arg| arg.ty.unwrap_or(INVALID)).collect();
+        let mut ends = Vec::new();
+        for arg in args {
+            self.expr(arg);
+            ends.push(self.depth);
+        }
+        let regs = self.arg_registers(&tys, classes.is_none());
+        let outgoing: i64 = tys.iter().zip(&regs)
+            .filter(|&(_, regs)| regs.is_none())
+            .map(|(&ty, _)| self.size(ty))
+            .sum();
+        let pad = (16 - (self.depth + outgoing) % 16) % 16;
+        self.alloc(pad + outgoing);
+        let mut offset = 0;
+        for ((&ty, regs), &end) in tys.iter().zip(&regs).zip(&ends) {
+            if regs.is_none() {
+                let size = self.size(ty);
+                let src = Mem::new("%rsp", self.depth - end);
+                self.copy(&Mem::new("%rsp", offset), &src, size);
+                offset += size;
+            }
+        }
+        for (regs, &end) in regs.iter().zip(&ends) {
+            if let Some(ref regs) = *regs {
+                for (i, reg) in regs.iter().enumerate() {
+                    self.emit(&format!("movq {}(%rsp), {}", self.depth - end + 8 * i as i64, reg));
+                }
+            }
+        }
+        match classes {
+            Some(classes) => {
+                self.emit(&format!("call main.{}", name));
+                let args = self.depth - start;
+                self.free(args);
+                self.alloc(size);
+                for (i, reg) in self.result_registers(&classes).iter().enumerate() {
+                    self.emit(&format!("movq {}, {}(%rsp)", reg, 8 * i));
+                }
+            }
+            None => {
+                self.emit(&format!("leaq {}(%rsp), %rdi", self.depth - start - size));
+                self.emit(&format!("call main.{}", name));
+                let args = self.depth - start - size;
+                self.free(args);
+            }
+        }
+    }
+
+    fn conversion(&mut self, ty: TypeId, arg: &Expr) {
+        let arg_ty = arg.ty.unwrap_or(INVALID);
+        self.expr(arg);
+        if self.types.is_string(ty) && !self.types.is_string(arg_ty) {
+            self.pop("%rdi");
+            self.call("gore_string_from_rune");
+            self.alloc(16);
+            self.emit("movq %rax, (%rsp)");
+            self.emit("movq %rdx, 8(%rsp)");
+            return;
+        }
+        if !self.types.is_numeric(ty) {
+            return;
+        }
+        let from_float = self.types.is_float(self.types.default_type(arg_ty));
+        let to_float = self.types.is_float(ty);
+        if from_float && !to_float {
+            // Out of range values saturate, as in the
+            // interpreters.
+            self.pop("%rax");
+            self.emit("movq %rax, %xmm0");
+            self.call("gore_float_to_int");
+            self.push("%rax");
+        } else if !from_float && to_float {
+            self.pop("%rax");
+            self.emit("cvtsi2sdq %rax, %xmm0");
+            self.emit("movq %xmm0, %rax");
+            self.push("%rax");
+        }
+        if self.is_rune(ty) && !self.is_rune(arg_ty) {
+            self.pop("%rax");
+            self.emit("movslq %eax, %rax");
+            self.push("%rax");
+        }
+    }
+
+    fn builtin(&mut self, f: &Expr, arg: &Expr) {
+        let cap = match f.kind {
+            ExprKind::Id(ref id) => id.name == "cap",
+            _ => false,
+        };
+        let ty = arg.ty.unwrap_or(INVALID);
+        let size = self.size(ty);
+        self.expr(arg);
+        if let TypeKind::Array(len, _) = *self.kind(ty) {
+            self.free(size);
+            self.push_imm(len as i64);
+            return;
+        }
+        self.emit(&format!("movq {}(%rsp), %rax", if cap { 16 } else { 8 }));
+        self.free(size);
+        self.push("%rax");
+    }
+}
+
+/// The condition code of a comparison of integers.
+fn condition(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Eq => return "e",
+        BinOp::Ne => return "ne",
+        BinOp::Lt => return "l",
+        BinOp::Le => return "le",
+        BinOp::Gt => return "g",
+        _ => return "ge",
+    }
+}
+
+/// Returns the 64-bit general-purpose registers that an
+/// instruction names, in part or whole, or uses implicitly.
+fn registers(inst: &str) -> Vec<String> {
+    let mut regs = Vec::new();
+    if inst.starts_with("rep") {
+        regs.extend(["%rax", "%rcx", "%rsi", "%rdi"].iter().map(|reg| reg.to_string()));
+    }
+    for operand in inst.split('%').skip(1) {
+        let name: String = operand.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+        let full = match name.as_str() {
+            "al" | "ax" | "eax" => "rax".to_string(),
+            "bl" | "bx" | "ebx" => "rbx".to_string(),
+            "cl" | "cx" | "ecx" => "rcx".to_string(),
+            "dl" | "dx" | "edx" => "rdx".to_string(),
+            "sil" | "si" | "esi" => "rsi".to_string(),
+            "dil" | "di" | "edi" => "rdi".to_string(),
+            _ if name.starts_with('r') => name.trim_end_matches(['b', 'w', 'd']).to_string(),
+            _ => continue,
+        };
+        regs.push(format!("%{}", full));
+    }
+    return regs;
+}
+
+/// Returns a string for the `.ascii` directive.  Quotes,
+/// backslashes and anything but printable ASCII are escaped in
+/// octal.
+fn asm_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20 ..= 0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    out.push('"');
+    return out;
+}
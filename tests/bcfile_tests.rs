@@ -0,0 +1,179 @@
+extern crate gore;
+
+mod common;
+
+use gore::bcfile;
+use gore::bcgen;
+use gore::bytecode::{Const, Function, Module, Op, Pos};
+use gore::vm;
+
+fn compile(src: &str) -> Module {
+    let (prog, table, types) = common::check("t.go", src);
+    bcgen::compile(&prog, &types, &table)
+}
+
+fn output(module: &Module) -> String {
+    let mut out = Vec::new();
+    let result = vm::run(module, &mut out);
+    let mut out = String::from_utf8_lossy(&out).into_owned();
+    if let Err(err) = result {
+        out.push_str(&err.to_string());
+    }
+    out
+}
+
+const PROGRAM: &str = "package main
+type point struct {
+    x, y float64
+    _ int
+}
+func fib(n int) int {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+func main() {
+    var p, q point
+    p.x = 1.5
+    var g [2][3]rune
+    g[1][2] = 'x'
+    var s []int
+    for i := 0; i < 10 && i != 7; i++ {
+        switch {
+        case i % 3 == 0:
+            continue
+        }
+        s = append(s, i)
+    }
+    println(p == q, g[1][2], len(s), fib(15), \"done\" + \"!\", -0.5, true)
+    println(s[len(s)])
+}
+";
+
+#[test]
+fn test_round_trip() {
+    let module = compile(PROGRAM);
+    let bytes = bcfile::write(&module);
+    assert_eq!(b"GORE", &bytes[.. 4]);
+    let read = bcfile::read(&bytes).unwrap();
+    assert_eq!(module, read);
+    assert_eq!("false 120 4 610 done! -5.000000e-001 true\n\
+                panic: runtime error: index out of range [4] with length 4\n\tat t.go:26:13",
+               output(&read));
+    // Floats keep all their bits.
+    let mut module = compile("package main\nfunc main() {}\n");
+    module.constants = vec![Const::Float(-0.0), Const::Float(f64::NAN), Const::Str(vec![0, 255])];
+    let read = bcfile::read(&bcfile::write(&module)).unwrap();
+    assert_eq!(format!("{:?}", module.constants), format!("{:?}", read.constants));
+}
+
+fn assert_error(expected: &str, bytes: &[u8]) {
+    match bcfile::read(bytes) {
+        Ok(_) => panic!("{:?} was accepted", bytes),
+        Err(err) => assert_eq!(expected, err.to_string()),
+    }
+}
+
+#[test]
+fn test_header() {
+    let bytes = bcfile::write(&compile(PROGRAM));
+    assert_error("invalid bytecode file: not a GoLite bytecode file", b"");
+    assert_error("invalid bytecode file: not a GoLite bytecode file", b"package main\n");
+    let mut newer = bytes.clone();
+    newer[4] = 2;
+    assert_error("invalid bytecode file: unsupported format version 2 (expected 1)", &newer);
+    assert_error("invalid bytecode file: truncated file (10 bytes)", &bytes[.. 10]);
+    assert_error(&format!("invalid bytecode file: truncated file (100 bytes, expected {})", bytes.len()),
+                 &bytes[.. 100]);
+    let mut longer = bytes.clone();
+    longer.push(0);
+    assert_error("invalid bytecode file: 1 bytes of trailing data", &longer);
+}
+
+#[test]
+fn test_corruption() {
+    let bytes = bcfile::write(&compile(PROGRAM));
+    for len in 0 .. bytes.len() {
+        assert!(bcfile::read(&bytes[.. len]).is_err());
+    }
+    for i in 0 .. bytes.len() {
+        for &bit in &[0x01, 0x80] {
+            let mut corrupted = bytes.clone();
+            corrupted[i] ^= bit;
+            assert!(bcfile::read(&corrupted).is_err(), "byte {} bit {:x}", i, bit);
+        }
+    }
+    let mut corrupted = bytes.clone();
+    corrupted[20] ^= 1;
+    assert_error("invalid bytecode file: checksum mismatch, the file is corrupted", &corrupted);
+}
+
+fn function(name: &str, params: u32, result: bool, code: Vec<Op>) -> Function {
+    Function {
+        name: name.to_string(),
+        params,
+        locals: params,
+        result,
+        positions: vec![Pos { line: 1, col: 1 }; code.len()],
+        code,
+    }
+}
+
+/// Encodes a module whose entry calls `f` with `params`
+/// arguments, and returns the error of reading it.
+fn rejection(params: u32, result: bool, code: Vec<Op>) -> String {
+    let mut init = vec![Op::Const(0); params as usize];
+    init.push(Op::Call(1));
+    if result {
+        init.push(Op::Pop);
+    }
+    init.push(Op::Return);
+    let module = Module {
+        filename: "t.go".to_string(),
+        constants: vec![Const::Int(1)],
+        layouts: Vec::new(),
+        globals: 1,
+        functions: vec![function("<init>", 0, false, init), function("f", params, result, code)],
+        entry: 0,
+    };
+    match bcfile::read(&bcfile::write(&module)) {
+        Ok(_) => "accepted".to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+#[test]
+fn test_verify() {
+    assert_eq!("accepted", rejection(2, true, vec![
+        Op::LoadLocal(0), Op::JumpIfFalse(4), Op::LoadLocal(1), Op::ReturnValue,
+        Op::Const(0), Op::StoreGlobal(0), Op::Const(0), Op::ReturnValue,
+    ]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0001: add_int needs 2 operands, the stack has 1",
+               rejection(0, false, vec![Op::Const(0), Op::AddInt, Op::Return]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0000: constant 3 out of range",
+               rejection(0, false, vec![Op::Const(3), Op::Pop, Op::Return]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0000: local 1 out of range",
+               rejection(1, false, vec![Op::LoadLocal(1), Op::Pop, Op::Return]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0000: global 1 out of range",
+               rejection(0, false, vec![Op::LoadGlobal(1), Op::Pop, Op::Return]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0000: jump target 9 out of range",
+               rejection(0, false, vec![Op::Jump(9)]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0001: function 2 out of range",
+               rejection(1, false, vec![Op::LoadLocal(0), Op::Call(2), Op::Return]));
+    assert_eq!("invalid bytecode file: function 1 (f): execution runs past the end of the code",
+               rejection(0, false, vec![Op::Const(0), Op::Pop]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0000: stack height 0 and 1",
+               rejection(0, false, vec![Op::Const(0), Op::Jump(0)]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0001: return with a value",
+               rejection(0, false, vec![Op::Const(0), Op::ReturnValue]));
+    assert_eq!("invalid bytecode file: function 1 (f): 0000: return without a value",
+               rejection(0, true, vec![Op::Return]));
+    // The entry function cannot have parameters.
+    let mut module = compile("package main\nfunc main() {}\n");
+    module.entry = 1;
+    module.functions[1].params = 1;
+    module.functions[1].locals = 1;
+    assert_eq!(Err("invalid bytecode file: the entry function takes parameters or returns a value".to_string()),
+               bcfile::read(&bcfile::write(&module)).map_err(|err| err.to_string()));
+}
@@ -0,0 +1,123 @@
+extern crate gore;
+
+use gore::cfg::{self, Cfg};
+use gore::parser;
+use gore::scanner::Scanner;
+
+fn build(src: &str) -> Vec<Cfg> {
+    let scanner = Scanner::new("t.go".to_string(), src.as_bytes().to_vec());
+    match parser::parse(scanner) {
+        Ok(prog) => cfg::build_program(&prog),
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    }
+}
+
+/// Builds the graph of a `main` function with body `body`, and
+/// describes each block by its statements and its edges.
+fn blocks(body: &str) -> Vec<String> {
+    let cfgs = build(&format!("package main\nfunc main() {{\n{}\n}}\n", body));
+    let name = |b| match b {
+        cfg::ENTRY => "entry".to_string(),
+        cfg::EXIT => "exit".to_string(),
+        _ => format!("b{}", b - cfg::EXIT),
+    };
+    cfgs[0].blocks.iter().enumerate().map(|(b, block)| {
+        let stmts: Vec<&str> = block.stmts.iter().map(|s| s.0.as_str()).collect();
+        let succs: Vec<String> = block.succs.iter().map(|edge| match edge.label {
+            Some(label) => format!("{}:{}", name(edge.to), label),
+            None => name(edge.to),
+        }).collect();
+        format!("{} [{}] -> {}", name(b), stmts.join("; "), succs.join(" "))
+    }).collect()
+}
+
+#[test]
+fn test_straight_line() {
+    assert_eq!(vec!["entry [] -> exit", "exit [] -> "], blocks(""));
+    assert_eq!(vec!["entry [] -> b1", "exit [] -> ", "b1 [x := 1; var y int; var z = 2; println(x)] -> exit"],
+               blocks("x := 1\nvar (\ny int\nz = 2\n)\nprintln(x)"));
+}
+
+#[test]
+fn test_if() {
+    assert_eq!(vec![
+        "entry [] -> b1",
+        "exit [] -> ",
+        "b1 [x := 1; if x > 0] -> b2:true b3:false",
+        "b2 [x++] -> b5",
+        "b3 [if x < 0] -> b4:true b5:false",
+        "b4 [x--] -> b5",
+        "b5 [println(x)] -> exit",
+    ], blocks("x := 1\nif x > 0 {\nx++\n} else if x < 0 {\nx--\n}\nprintln(x)"));
+}
+
+#[test]
+fn test_for() {
+    assert_eq!(vec![
+        "entry [] -> b1",
+        "exit [] -> ",
+        "b1 [i := 0] -> b2",
+        "b2 [for i < 10] -> b4:true b7:false",
+        "b3 [i++] -> b2",
+        "b4 [if i == 5] -> b5:true b6:false",
+        "b5 [break] -> b7",
+        "b6 [continue] -> b3",
+        "b7 [println(i)] -> exit",
+    ], blocks("for i := 0; i < 10; i++ {\nif i == 5 {\nbreak\n}\ncontinue\n}\nprintln(i)"));
+    assert_eq!(vec!["entry [] -> b1", "exit [] -> ", "b1 [] -> b1"], blocks("for {\n}"));
+    assert_eq!(vec![
+        "entry [] -> b1",
+        "exit [] -> ",
+        "b1 [println(1)] -> b1",
+        "b2 [println(2)] -> exit",
+    ], blocks("for {\nprintln(1)\n}\nprintln(2)"));
+}
+
+#[test]
+fn test_switch() {
+    assert_eq!(vec![
+        "entry [] -> b1",
+        "exit [] -> ",
+        "b1 [x := 2; switch x] -> b2",
+        "b2 [case 1, 2] -> b3:true b4:false",
+        "b3 [println(1)] -> b5",
+        "b4 [break] -> b5",
+        "b5 [return] -> exit",
+        "b6 [println(3)] -> exit",
+    ], blocks("x := 2\nswitch x {\ncase 1, 2:\nprintln(1)\ndefault:\nbreak\n}\nreturn\nprintln(3)"));
+    // Without a default clause, the switch ends when no case matches.
+    assert_eq!(vec![
+        "entry [] -> b1",
+        "exit [] -> ",
+        "b1 [switch] -> b2",
+        "b2 [case true] -> b3:true exit:false",
+        "b3 [println(1)] -> exit",
+    ], blocks("switch {\ncase true:\nprintln(1)\n}"));
+}
+
+#[test]
+fn test_dot() {
+    let cfgs = build("package main
+func main() {
+    x := 1
+    if x > 0 {
+        println(\"yes\")
+    }
+}
+");
+    assert_eq!(r#"digraph "t.go" {
+    node [shape=box, fontname="monospace"];
+    subgraph "cluster_main" {
+        label="main";
+        "main.entry" [label="entry", shape=oval];
+        "main.exit" [label="exit", shape=oval];
+        "main.b1" [label="b1 (lines 3-4)\lx := 1\lif x > 0\l"];
+        "main.b2" [label="b2 (line 5)\lprintln(\"yes\")\l"];
+        "main.entry" -> "main.b1";
+        "main.b1" -> "main.b2" [label="true"];
+        "main.b1" -> "main.exit" [label="false"];
+        "main.b2" -> "main.exit";
+    }
+}
+"#, cfg::dot("t.go", &cfgs));
+}
@@ -0,0 +1,45 @@
+extern crate gore;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+use gore::codegen;
+
+/// Compiles `src`, read from `filename`, to C, compiles the C
+/// program with the system compiler and runs it; returns its
+/// standard output, standard error and exit status, or `None` if
+/// there is no C compiler.
+fn run(filename: &str, src: &str) -> Option<(String, String, i32)> {
+    let (prog, table, types) = common::check(filename, src);
+    let c = codegen::generate(&prog, &types, &table);
+
+    let base = env::temp_dir().join(format!("gore_codegen_{}_{}", filename.trim_end_matches(".go"), process::id()));
+    let c_file = base.with_extension("c");
+    fs::write(&c_file, c).unwrap();
+    let status = Command::new("cc")
+        .args(["-std=c99", "-Wall", "-Werror", "-o"])
+        .arg(&base)
+        .arg(&c_file)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "{}: the C program does not compile", filename),
+        Err(_) => {
+            eprintln!("{}: no C compiler, skipped", filename);
+            return None;
+        }
+    }
+    let output = Command::new(&base).output().unwrap();
+    let _ = fs::remove_file(&c_file);
+    let _ = fs::remove_file(&base);
+    Some((String::from_utf8_lossy(&output.stdout).into_owned(),
+          String::from_utf8_lossy(&output.stderr).into_owned(),
+          output.status.code().unwrap_or(-1)))
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| run(&sample.filename, &sample.src));
+}
@@ -0,0 +1,29 @@
+use gore::ast::Program;
+use gore::parser;
+use gore::scanner::Scanner;
+use gore::symtab::{self, SymbolTable};
+use gore::typecheck;
+use gore::types::TypeTable;
+
+// Not every test crate runs the sample programs.
+#[allow(dead_code)]
+pub mod samples;
+
+/// Parses, resolves and type-checks `src`, read from `filename`;
+/// panics with the first error if it is not a valid program.
+pub fn check(filename: &str, src: &str) -> (Program, SymbolTable, TypeTable) {
+    let scanner = Scanner::new(filename.to_string(), src.as_bytes().to_vec());
+    let mut prog = match parser::parse(scanner) {
+        Ok(prog) => prog,
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    };
+    let mut table = match symtab::resolve(&mut prog) {
+        Ok(table) => table,
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    };
+    let types = match typecheck::check(&mut prog, &mut table) {
+        Ok(types) => types,
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    };
+    (prog, table, types)
+}
@@ -0,0 +1,68 @@
+//! The programs of `tests/programs`, which every backend and
+//! interpreter runs.
+
+use std::fs;
+use std::path::Path;
+
+use gore::interp::RuntimeError;
+
+/// A program of `tests/programs`, with what it writes to the
+/// standard output and error: the `.out` file next to it and the
+/// `.err` file, if it panics.
+pub struct Sample {
+    pub filename: String,
+    pub src: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Sample {
+    /// The exit status of the program: 2 if it panics.
+    pub fn status(&self) -> i32 {
+        if self.stderr.is_empty() { 0 } else { 2 }
+    }
+}
+
+/// Returns the programs of `tests/programs`, sorted by name.
+pub fn samples() -> Vec<Sample> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut sources: Vec<_> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "go"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+    sources.iter()
+        .map(|source| Sample {
+            filename: source.file_name().unwrap().to_string_lossy().into_owned(),
+            src: fs::read_to_string(source).unwrap(),
+            stdout: fs::read_to_string(source.with_extension("out")).unwrap(),
+            stderr: fs::read_to_string(source.with_extension("err")).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Runs every sample with `run`, which returns its standard
+/// output, standard error and exit status, or `None` if the
+/// tools it needs are missing, and checks them.
+pub fn check_samples<F>(mut run: F)
+    where F: FnMut(&Sample) -> Option<(String, String, i32)>
+{
+    for sample in samples() {
+        if let Some((stdout, stderr, status)) = run(&sample) {
+            assert_eq!((sample.stdout.as_str(), sample.stderr.as_str(), sample.status()),
+                       (stdout.as_str(), stderr.as_str(), status),
+                       "{}", sample.filename);
+        }
+    }
+}
+
+/// Returns what an interpreter wrote to `out` and the error it
+/// stopped with, as `gore run` prints them, with its exit status.
+pub fn outcome(out: Vec<u8>, result: Result<(), RuntimeError>) -> (String, String, i32) {
+    let out = String::from_utf8_lossy(&out).into_owned();
+    match result {
+        Ok(()) => (out, String::new(), 0),
+        Err(err) => (out, format!("{}\n", err), 2),
+    }
+}
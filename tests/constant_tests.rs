@@ -0,0 +1,76 @@
+extern crate gore;
+
+use gore::ast::{BinOp, UnOp};
+use gore::constant::{self, BigInt, Rat, Value};
+
+fn int(s: &str) -> BigInt {
+    match s.strip_prefix('-') {
+        Some(digits) => BigInt::parse(digits, 10).unwrap().neg(),
+        None => BigInt::parse(s, 10).unwrap(),
+    }
+}
+
+#[test]
+fn test_bigint_arithmetic() {
+    let big = int("123456789012345678901234567890");
+    assert_eq!("123456789012345678901234567890", big.to_string());
+    assert_eq!("15241578753238836750495351562536198787501905199875019052100",
+               big.mul(&big).to_string());
+    assert_eq!("-123456789012345678901234567889", big.neg().add(&BigInt::from_u64(1)).to_string());
+    // Division truncates towards zero, like Go's.
+    let (q, r) = int("-7").div_rem(&int("2"));
+    assert_eq!(("-3".to_string(), "-1".to_string()), (q.to_string(), r.to_string()));
+    assert_eq!("1180591620717411303424", BigInt::from_u64(1).shl(70).to_string());
+    assert_eq!("-4", int("-7").shr(1).to_string());
+    assert_eq!("255", BigInt::parse("ff", 16).unwrap().to_string());
+    assert_eq!("8", BigInt::parse("10", 8).unwrap().to_string());
+}
+
+#[test]
+fn test_bigint_bitwise() {
+    assert_eq!("-6", int("5").not().to_string());
+    assert_eq!("2", int("-6").and(&int("7")).to_string());
+    assert_eq!("-1", int("-6").or(&int("5")).to_string());
+    assert_eq!("-3", int("-6").xor(&int("7")).to_string());
+    assert_eq!("4", int("6").and_not(&int("3")).to_string());
+}
+
+#[test]
+fn test_bigint_range() {
+    assert_eq!(Some(i64::MAX), int("9223372036854775807").to_i64());
+    assert_eq!(Some(i64::MIN), int("-9223372036854775808").to_i64());
+    assert_eq!(None, int("9223372036854775808").to_i64());
+    assert!(int("2147483647").fits_signed(32));
+    assert!(int("-2147483648").fits_signed(32));
+    assert!(!int("2147483648").fits_signed(32));
+    assert!(!int("-2147483649").fits_signed(32));
+}
+
+#[test]
+fn test_rationals() {
+    let r = Rat::parse("0.1").unwrap().add(&Rat::parse("0.2").unwrap());
+    assert_eq!(Rat::parse("0.3").unwrap(), r);
+    assert_eq!(0.3, r.to_f64());
+    assert_eq!("3.5", Rat::parse("7.").unwrap().quo(&Rat::from_int(int("2"))).to_string());
+    assert_eq!("-2", Rat::parse("2.5").unwrap().neg().trunc().to_string());
+    assert!(Rat::parse(".5").unwrap().mul(&Rat::from_int(int("4"))).is_int());
+    assert_eq!(None, Rat::from_f64(f64::INFINITY));
+    assert_eq!(Some(Rat::parse("0.25").unwrap()), Rat::from_f64(0.25));
+    assert_eq!("1e+21", constant::format_float(1e21));
+}
+
+#[test]
+fn test_operators() {
+    let i = |n: i64| Value::Int(BigInt::from_i64(n));
+    let f = |s: &str| Value::Float(Rat::parse(s).unwrap());
+    assert_eq!(Some(i(-3)), constant::binary_op(BinOp::Div, &i(-7), &i(2)));
+    assert_eq!(Some(f("3.5")), constant::binary_op(BinOp::Div, &i(7), &f("2.0")));
+    assert_eq!(Some(Value::Bool(true)), constant::binary_op(BinOp::Lt, &i(1), &f("1.5")));
+    assert_eq!(None, constant::binary_op(BinOp::Div, &i(1), &i(0)));
+    assert_eq!(None, constant::binary_op(BinOp::Rem, &f("1.0"), &f("2.0")));
+    assert_eq!(Some(Value::String("ab".to_string())),
+               constant::binary_op(BinOp::Add, &Value::String("a".to_string()),
+                                   &Value::String("b".to_string())));
+    assert_eq!(Some(i(-6)), constant::unary_op(UnOp::BitNot, &i(5)));
+    assert_eq!(Some(Value::Bool(false)), constant::unary_op(UnOp::Not, &Value::Bool(true)));
+}
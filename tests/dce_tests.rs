@@ -0,0 +1,84 @@
+extern crate gore;
+
+mod common;
+
+use gore::dce;
+use gore::ir::{self, Module};
+use gore::irgen;
+
+/// Returns the IR of function `f` of `src` once the dead code is
+/// eliminated.
+fn eliminate(src: &str) -> String {
+    let (prog, table, types) = common::check("t.go", src);
+    let mut module: Module = irgen::build(&prog, &types, &table);
+    for f in &mut module.functions {
+        dce::eliminate(f);
+    }
+    if let Err(err) = ir::verify(&module) {
+        panic!("{}: {}\n{}", src, err, ir::dump(&module));
+    }
+    let f = module.functions.iter().find(|f| f.name == "f").unwrap();
+    ir::dump_function(&module, f)
+}
+
+#[test]
+fn test_unused_values() {
+    let f = eliminate("package main
+var g int
+func f(n int, d int) int {
+    a := n * 2
+    b := -a + g
+    c := n / 2
+    e := n / d
+    h := n << d
+    var arr [3]int
+    arr[1] = b
+    var s []int
+    s = append(s, n)
+    return n
+}
+");
+    for op in ["mul", "neg", "load", "setindex"] {
+        assert!(!f.contains(op), "{}", f);
+    }
+    // Dividing by a variable and shifting by a variable may panic.
+    assert!(f.contains("div %0, %1") && f.contains("shl %0, %1"), "{}", f);
+    assert!(f.contains("append"), "{}", f);
+    assert!(f.contains("check"), "{}", f);
+    assert_eq!(1, f.matches("div").count(), "{}", f);
+}
+
+#[test]
+fn test_loops() {
+    let f = eliminate("package main
+func f(n int) int {
+    k := 0
+    for i := 0; i < n; i++ {
+        k += i
+    }
+    return n
+}
+");
+    // The phi node of `k` and the addition only use each other.
+    assert_eq!(1, f.matches("phi").count(), "{}", f);
+    assert_eq!(1, f.matches("add").count(), "{}", f);
+}
+
+#[test]
+fn test_unreachable_blocks() {
+    let f = eliminate("package main
+func f(n int) int {
+    for {
+        if n > 0 {
+            return n
+        }
+        n++
+        continue
+        println(\"dead\")
+    }
+    println(\"dead\")
+    return 0
+}
+");
+    assert!(!f.contains("println") && !f.contains("unreachable"), "{}", f);
+}
@@ -0,0 +1,233 @@
+extern crate gore;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use gore::ast::{BinOp, UnOp};
+use gore::bytecode::Const;
+use gore::fold;
+use gore::ir::{self, Module, Ty};
+use gore::ireval;
+use gore::irgen;
+use gore::opt;
+
+fn build(src: &str, optimize: bool) -> Module {
+    let (prog, table, types) = common::check("t.go", src);
+    let mut module = irgen::build(&prog, &types, &table);
+    if optimize {
+        opt::optimize(&mut module);
+    }
+    if let Err(err) = ir::verify(&module) {
+        panic!("{}: {}\n{}", src, err, ir::dump(&module));
+    }
+    module
+}
+
+/// Returns the optimised IR of function `name` of `src`.
+fn optimized(src: &str, name: &str) -> String {
+    let module = build(src, true);
+    let f = module.functions.iter().find(|f| f.name == name).unwrap();
+    ir::dump_function(&module, f)
+}
+
+/// Runs `src`, optimised or not; returns what it prints followed
+/// by its runtime error, if any.
+fn run(src: &str, optimize: bool) -> String {
+    let module = build(src, optimize);
+    let evaluator = thread::Builder::new().stack_size(2 << 30).spawn(move || {
+        let mut out = Vec::new();
+        let result = ireval::run(&module, &mut out);
+        let mut text = String::from_utf8_lossy(&out).into_owned();
+        if let Err(err) = result {
+            text.push_str(&err.to_string());
+        }
+        text
+    });
+    evaluator.unwrap().join().unwrap()
+}
+
+fn assert_same_output(src: &str) {
+    assert_eq!(run(src, false), run(src, true), "{}", src);
+}
+
+#[test]
+fn test_binary() {
+    let int = |n| Const::Int(n);
+    assert_eq!(Some(int(i64::MIN)), fold::binary(BinOp::Add, &Ty::Int, &int(i64::MAX), &int(1)));
+    assert_eq!(Some(int(i32::MIN as i64)), fold::binary(BinOp::Add, &Ty::Rune, &int(i32::MAX as i64), &int(1)));
+    assert_eq!(Some(int(i64::MIN)), fold::binary(BinOp::Div, &Ty::Int, &int(i64::MIN), &int(-1)));
+    assert_eq!(Some(int(0)), fold::binary(BinOp::Rem, &Ty::Int, &int(i64::MIN), &int(-1)));
+    assert_eq!(Some(int(-3)), fold::binary(BinOp::Div, &Ty::Int, &int(-7), &int(2)));
+    assert_eq!(Some(int(-1)), fold::binary(BinOp::Rem, &Ty::Int, &int(-7), &int(3)));
+    assert_eq!(None, fold::binary(BinOp::Div, &Ty::Int, &int(1), &int(0)));
+    assert_eq!(None, fold::binary(BinOp::Rem, &Ty::Int, &int(1), &int(0)));
+    assert_eq!(Some(int(0)), fold::binary(BinOp::Shl, &Ty::Int, &int(1), &int(64)));
+    assert_eq!(Some(int(i64::MIN)), fold::binary(BinOp::Shl, &Ty::Int, &int(1), &int(63)));
+    assert_eq!(Some(int(0)), fold::binary(BinOp::Shl, &Ty::Rune, &int(1), &int(32)));
+    assert_eq!(Some(int(-1)), fold::binary(BinOp::Shr, &Ty::Int, &int(-8), &int(100)));
+    assert_eq!(None, fold::binary(BinOp::Shl, &Ty::Int, &int(1), &int(-1)));
+    assert_eq!(None, fold::binary(BinOp::Shr, &Ty::Int, &int(1), &int(-1)));
+    assert_eq!(Some(int(4)), fold::binary(BinOp::BitClear, &Ty::Int, &int(5), &int(1)));
+
+    let float = |x| Const::Float(x);
+    assert_eq!(Some(float(0.30000000000000004)), fold::binary(BinOp::Add, &Ty::Float, &float(0.1), &float(0.2)));
+    assert_eq!(Some(float(f64::INFINITY)), fold::binary(BinOp::Div, &Ty::Float, &float(1.0), &float(0.0)));
+    assert_eq!(Some(Const::Bool(false)), fold::binary(BinOp::Eq, &Ty::Float, &float(f64::NAN), &float(f64::NAN)));
+
+    let s = |s: &str| Const::Str(s.as_bytes().to_vec());
+    assert_eq!(Some(s("ab")), fold::binary(BinOp::Add, &Ty::Str, &s("a"), &s("b")));
+    assert_eq!(Some(Const::Bool(true)), fold::binary(BinOp::Lt, &Ty::Str, &s("ab"), &s("b")));
+}
+
+#[test]
+fn test_unary_and_convert() {
+    assert_eq!(Some(Const::Int(i64::MIN)), fold::unary(UnOp::Neg, &Ty::Int, &Const::Int(i64::MIN)));
+    assert_eq!(Some(Const::Int(-6)), fold::unary(UnOp::BitNot, &Ty::Int, &Const::Int(5)));
+    assert_eq!(Some(Const::Bool(false)), fold::unary(UnOp::Not, &Ty::Bool, &Const::Bool(true)));
+    assert_eq!(Some(Const::Int(-2)), fold::convert(&Ty::Float, &Ty::Int, &Const::Float(-2.9)));
+    assert_eq!(Some(Const::Int(i64::MAX)), fold::convert(&Ty::Float, &Ty::Int, &Const::Float(1e300)));
+    assert_eq!(Some(Const::Int(-1)), fold::convert(&Ty::Int, &Ty::Rune, &Const::Int(u32::MAX as i64)));
+    assert_eq!(Some(Const::Float(3.0)), fold::convert(&Ty::Int, &Ty::Float, &Const::Int(3)));
+    assert_eq!(Some(Const::Str(b"A".to_vec())), fold::convert(&Ty::Rune, &Ty::Str, &Const::Int(65)));
+    assert_eq!(Some(Const::Str("\u{fffd}".as_bytes().to_vec())),
+               fold::convert(&Ty::Int, &Ty::Str, &Const::Int(-1)));
+}
+
+#[test]
+fn test_propagation() {
+    let f = optimized("package main
+func f() int {
+    x := 3 * 4
+    y := x + 1
+    z := y << 2
+    return z - x
+}
+func main() {
+    println(f())
+}
+", "f");
+    assert!(f.contains("ret %") && f.contains("const 40"), "{}", f);
+    assert!(!f.contains("add") && !f.contains("sub") && !f.contains("shl"), "{}", f);
+}
+
+#[test]
+fn test_branches() {
+    let f = optimized("package main
+func f(n int) int {
+    debug := false
+    if debug {
+        println(\"debug\")
+    }
+    k := 5
+    for i := 0; i < n; i++ {
+        if k != 5 {
+            k = i
+        }
+    }
+    return k
+}
+func main() {
+    println(f(3))
+}
+", "f");
+    assert!(!f.contains("debug"), "{}", f);
+    assert!(!f.contains("ne "), "{}", f);
+    // The loop remains, but the only value `k` takes is 5.
+    assert!(f.contains("lt "), "{}", f);
+    assert!(f.contains("const 5"), "{}", f);
+    assert_eq!(1, f.matches("phi").count(), "{}", f);
+}
+
+#[test]
+fn test_checks() {
+    let f = optimized("package main
+func f() int {
+    var a [4]int
+    a[3] = 1
+    i := 4
+    return a[i]
+}
+func main() {
+    println(f())
+}
+", "f");
+    // The check of 3 is gone, and the one of 4 panics at run time.
+    assert_eq!(1, f.matches("check").count(), "{}", f);
+}
+
+#[test]
+fn test_panics() {
+    let src = "package main
+func main() {
+    x := 0
+    println(\"before\")
+    println(1 / x)
+}
+";
+    assert!(optimized(src, "main").contains("div"));
+    assert_eq!("before\npanic: runtime error: integer divide by zero\n\tat t.go:5:15", run(src, true).trim_end());
+
+    let src = "package main
+func main() {
+    s := -1
+    println(1 << s)
+}
+";
+    assert!(optimized(src, "main").contains("shl"));
+    assert_same_output(src);
+}
+
+#[test]
+fn test_same_output() {
+    assert_same_output("package main
+var g int
+func next() int {
+    g++
+    return g
+}
+func main() {
+    a, b := 7, -7
+    println(a / 2, b / 2, b % 3, a % 3, 1 << 10, -8 >> 1, ^5, 5 &^ 3, b >> 10)
+    var r rune = 2147483647
+    r++
+    var i int = 9223372036854775807
+    i++
+    println(r, i, -i, i / -1)
+    f, zero := 1.0, 0.0
+    inf := f / zero
+    g := -2.9
+    println(f / 3, inf, inf - inf, -zero, int(g), string(rune(65)), string(-1))
+    s := \"go\"
+    t := s + \"pher\"
+    println(t, len(t), t < s, t == \"gopher\")
+    n := 0
+    for j := 0; j < 10; j++ {
+        if j % 2 == 0 {
+            continue
+        }
+        n += j
+        if n > 100 {
+            break
+        }
+    }
+    switch k := 3; k {
+    case 1, 2:
+        println(\"small\")
+    case 3:
+        println(\"three\", n + next())
+    default:
+        println(\"large\")
+    }
+}
+");
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/ir");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "go") {
+            assert_same_output(&fs::read_to_string(&path).unwrap());
+        }
+    }
+}
@@ -0,0 +1,18 @@
+package main
+
+type point struct {
+	x, y float64
+}
+
+var origin point
+
+func main() {
+	var grid [2][3]int
+	var path []point
+	grid[1][2] = 5
+	p := origin
+	p.y += 1.5
+	path = append(path, p)
+	path[0].x = 2
+	println(grid[1][2], len(path), path[0].x, p == origin)
+}
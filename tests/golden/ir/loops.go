@@ -0,0 +1,24 @@
+package main
+
+func collatz(n int) int {
+	steps := 0
+	for n != 1 {
+		if n%2 == 0 {
+			n /= 2
+		} else {
+			n = 3*n + 1
+		}
+		steps++
+	}
+	return steps
+}
+
+func main() {
+	best, arg := 0, 0
+	for i := 1; i < 30; i++ {
+		if s := collatz(i); s > best {
+			best, arg = s, i
+		}
+	}
+	println(arg, best)
+}
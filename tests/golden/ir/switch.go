@@ -0,0 +1,27 @@
+package main
+
+func classify(c rune) string {
+	switch {
+	case c >= '0' && c <= '9':
+		return "digit"
+	case c == ' ' || c == '\t':
+		return "space"
+	}
+	return "other"
+}
+
+func main() {
+	var s []rune
+	s = append(s, 'a')
+	s = append(s, '1')
+	for i := 0; i < len(s); i++ {
+		switch x := classify(s[i]); x {
+		case "digit":
+			println(i, x)
+			break
+		default:
+			continue
+		}
+		print("after\n")
+	}
+}
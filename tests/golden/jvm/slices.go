@@ -0,0 +1,18 @@
+package main
+
+func sum(s []int) int {
+	total := 0
+	for i := 0; i < len(s); i++ {
+		total += s[i]
+	}
+	return total
+}
+
+func main() {
+	var s []int
+	for i := 1; i <= 4; i++ {
+		s = append(s, i*i)
+	}
+	print(sum(s), cap(s), "\n")
+	s[4] = 1
+}
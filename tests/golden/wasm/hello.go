@@ -0,0 +1,9 @@
+package main
+
+var greeting = "hello"
+
+func main() {
+	x := 6
+	x *= 7
+	println(greeting, x, 1.5, x > 40 && true, 'g')
+}
@@ -0,0 +1,21 @@
+package main
+
+type point struct {
+	x, y int
+}
+
+func shift(p point, d int) point {
+	p.x += d
+	return p
+}
+
+func main() {
+	var ps [2]point
+	ps[1] = shift(ps[0], 3)
+	switch q := ps[1]; {
+	case q == ps[0]:
+		println("same")
+	default:
+		println(q.x, q.y)
+	}
+}
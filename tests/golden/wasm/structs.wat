@@ -0,0 +1,561 @@
+;; Generated by gore from structs.go; see gore_host.js.
+(module
+  ;; Runtime support for the WebAssembly generated by gore.
+  ;;
+  ;; The module imports its output from the host, as functions
+  ;; of the "gore" module:
+  ;;
+  ;;   print_int(x: i64)        prints an int in decimal
+  ;;   print_float(x: f64)      prints a float64 as Go's print does
+  ;;   print_string(p, n: i32)  prints the n bytes at address p
+  ;;   panic(p, n, q, m: i32)   prints "panic: runtime error: ",
+  ;;                            the message at p, "\n\tat " and the
+  ;;                            location at q to the standard error,
+  ;;                            and stops the program with status 2
+  ;;
+  ;; and exports its memory as "memory" and its entry point as
+  ;; "main".
+  ;;
+  ;; The bottom of the linear memory is the stack, which grows
+  ;; down from $gore_sp and holds the arrays and structs of the
+  ;; functions.  The string literals and package variables come
+  ;; next, then the heap, which grows until the end of the memory
+  ;; and is never freed.
+  ;;
+  ;; A string is the address of its length, an i32 followed by its
+  ;; bytes; 0 is the empty string.  A slice is the address of a
+  ;; header that holds the address of its backing array, its length
+  ;; and its capacity (i32s), and that is never modified, so that
+  ;; slices can be copied as addresses; 0 is the nil slice.
+
+  (import "gore" "print_int" (func $host.print_int (param i64)))
+  (import "gore" "print_float" (func $host.print_float (param f64)))
+  (import "gore" "print_string" (func $host.print_string (param i32 i32)))
+  (import "gore" "panic" (func $host.panic (param i32 i32 i32 i32)))
+
+  (func $gore_panic (param $msg i32) (param $loc i32)
+    (call $host.panic
+      (i32.add (local.get $msg) (i32.const 4)) (i32.load (local.get $msg))
+      (i32.add (local.get $loc) (i32.const 4)) (i32.load (local.get $loc)))
+    unreachable)
+
+  ;; Returns the address of `size` zeroed bytes, aligned on 8 bytes.
+  (func $gore_alloc (param $size i32) (result i32)
+    (local $p i32) (local $end i32) (local $avail i32)
+    (local.set $p (global.get $gore_heap))
+    (local.set $end
+      (i32.add (local.get $p) (i32.and (i32.add (local.get $size) (i32.const 7)) (i32.const -8))))
+    (local.set $avail (i32.shl (memory.size) (i32.const 16)))
+    (if (i32.gt_u (local.get $end) (local.get $avail))
+      (then
+        (if (i32.eq
+              (memory.grow
+                (i32.shr_u (i32.add (i32.sub (local.get $end) (local.get $avail)) (i32.const 65535))
+                           (i32.const 16)))
+              (i32.const -1))
+          (then unreachable))))
+    (global.set $gore_heap (local.get $end))
+    (local.get $p))
+
+
+  ;; ARITHMETIC
+  ;; Integer operations wrap around as in Go; the runtime handles
+  ;; the cases that trap in WebAssembly or differ from Go.
+
+  (func $gore_div64 (param $a i64) (param $b i64) (param $loc i32) (result i64)
+    (if (i64.eqz (local.get $b))
+      (then (call $gore_panic (global.get $gore.str.divide) (local.get $loc))))
+    (if (i64.eq (local.get $b) (i64.const -1))
+      (then (return (i64.sub (i64.const 0) (local.get $a)))))
+    (i64.div_s (local.get $a) (local.get $b)))
+
+  (func $gore_rem64 (param $a i64) (param $b i64) (param $loc i32) (result i64)
+    (if (i64.eqz (local.get $b))
+      (then (call $gore_panic (global.get $gore.str.divide) (local.get $loc))))
+    (i64.rem_s (local.get $a) (local.get $b)))
+
+  (func $gore_div32 (param $a i32) (param $b i32) (param $loc i32) (result i32)
+    (if (i32.eqz (local.get $b))
+      (then (call $gore_panic (global.get $gore.str.divide) (local.get $loc))))
+    (if (i32.eq (local.get $b) (i32.const -1))
+      (then (return (i32.sub (i32.const 0) (local.get $a)))))
+    (i32.div_s (local.get $a) (local.get $b)))
+
+  (func $gore_rem32 (param $a i32) (param $b i32) (param $loc i32) (result i32)
+    (if (i32.eqz (local.get $b))
+      (then (call $gore_panic (global.get $gore.str.divide) (local.get $loc))))
+    (i32.rem_s (local.get $a) (local.get $b)))
+
+  ;; Shifts by the width of the operand or more are defined in Go.
+
+  (func $gore_shl64 (param $x i64) (param $n i64) (param $loc i32) (result i64)
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (call $gore_panic (global.get $gore.str.shift) (local.get $loc))))
+    (if (i64.ge_s (local.get $n) (i64.const 64))
+      (then (return (i64.const 0))))
+    (i64.shl (local.get $x) (local.get $n)))
+
+  (func $gore_shr64 (param $x i64) (param $n i64) (param $loc i32) (result i64)
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (call $gore_panic (global.get $gore.str.shift) (local.get $loc))))
+    (if (i64.ge_s (local.get $n) (i64.const 64))
+      (then (local.set $n (i64.const 63))))
+    (i64.shr_s (local.get $x) (local.get $n)))
+
+  (func $gore_shl32 (param $x i32) (param $n i64) (param $loc i32) (result i32)
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (call $gore_panic (global.get $gore.str.shift) (local.get $loc))))
+    (if (i64.ge_s (local.get $n) (i64.const 32))
+      (then (return (i32.const 0))))
+    (i32.shl (local.get $x) (i32.wrap_i64 (local.get $n))))
+
+  (func $gore_shr32 (param $x i32) (param $n i64) (param $loc i32) (result i32)
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then (call $gore_panic (global.get $gore.str.shift) (local.get $loc))))
+    (if (i64.ge_s (local.get $n) (i64.const 32))
+      (then (local.set $n (i64.const 31))))
+    (i32.shr_s (local.get $x) (i32.wrap_i64 (local.get $n))))
+
+
+  ;; STRINGS
+
+  (func $gore_string_len (param $s i32) (result i32)
+    (if (result i32) (local.get $s)
+      (then (i32.load (local.get $s)))
+      (else (i32.const 0))))
+
+  (func $gore_string_cmp (param $a i32) (param $b i32) (result i32)
+    (local $alen i32) (local $blen i32) (local $n i32) (local $i i32) (local $x i32) (local $y i32)
+    (local.set $alen (call $gore_string_len (local.get $a)))
+    (local.set $blen (call $gore_string_len (local.get $b)))
+    (local.set $n (select (local.get $alen) (local.get $blen)
+                          (i32.lt_u (local.get $alen) (local.get $blen))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+        (local.set $x (i32.load8_u offset=4 (i32.add (local.get $a) (local.get $i))))
+        (local.set $y (i32.load8_u offset=4 (i32.add (local.get $b) (local.get $i))))
+        (if (i32.ne (local.get $x) (local.get $y))
+          (then (return (select (i32.const -1) (i32.const 1)
+                                (i32.lt_u (local.get $x) (local.get $y))))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i32.sub (i32.gt_u (local.get $alen) (local.get $blen))
+             (i32.lt_u (local.get $alen) (local.get $blen))))
+
+  (func $gore_string_eq (param $a i32) (param $b i32) (result i32)
+    (if (i32.ne (call $gore_string_len (local.get $a)) (call $gore_string_len (local.get $b)))
+      (then (return (i32.const 0))))
+    (i32.eqz (call $gore_string_cmp (local.get $a) (local.get $b))))
+
+  (func $gore_concat (param $a i32) (param $b i32) (result i32)
+    (local $alen i32) (local $blen i32) (local $s i32)
+    (local.set $alen (call $gore_string_len (local.get $a)))
+    (local.set $blen (call $gore_string_len (local.get $b)))
+    (if (i32.eqz (local.get $alen))
+      (then (return (local.get $b))))
+    (if (i32.eqz (local.get $blen))
+      (then (return (local.get $a))))
+    (local.set $s (call $gore_alloc (i32.add (i32.const 4) (i32.add (local.get $alen) (local.get $blen)))))
+    (i32.store (local.get $s) (i32.add (local.get $alen) (local.get $blen)))
+    (memory.copy (i32.add (local.get $s) (i32.const 4))
+                 (i32.add (local.get $a) (i32.const 4))
+                 (local.get $alen))
+    (memory.copy (i32.add (i32.add (local.get $s) (i32.const 4)) (local.get $alen))
+                 (i32.add (local.get $b) (i32.const 4))
+                 (local.get $blen))
+    (local.get $s))
+
+  ;; Converts a code point to its UTF-8 encoding; invalid code
+  ;; points become U+FFFD, as in Go.
+  (func $gore_string_from_rune (param $r i64) (result i32)
+    (local $c i32) (local $s i32)
+    (if (i32.or (i64.gt_u (local.get $r) (i64.const 0x10ffff))
+                (i32.and (i64.ge_s (local.get $r) (i64.const 0xd800))
+                         (i64.le_s (local.get $r) (i64.const 0xdfff))))
+      (then (local.set $r (i64.const 0xfffd))))
+    (local.set $c (i32.wrap_i64 (local.get $r)))
+    (local.set $s (call $gore_alloc (i32.const 8)))
+    (if (i32.lt_u (local.get $c) (i32.const 0x80))
+      (then
+        (i32.store8 offset=4 (local.get $s) (local.get $c))
+        (i32.store (local.get $s) (i32.const 1))
+        (return (local.get $s))))
+    (if (i32.lt_u (local.get $c) (i32.const 0x800))
+      (then
+        (i32.store8 offset=4 (local.get $s) (i32.or (i32.const 0xc0) (i32.shr_u (local.get $c) (i32.const 6))))
+        (i32.store8 offset=5 (local.get $s) (i32.or (i32.const 0x80) (i32.and (local.get $c) (i32.const 0x3f))))
+        (i32.store (local.get $s) (i32.const 2))
+        (return (local.get $s))))
+    (if (i32.lt_u (local.get $c) (i32.const 0x10000))
+      (then
+        (i32.store8 offset=4 (local.get $s) (i32.or (i32.const 0xe0) (i32.shr_u (local.get $c) (i32.const 12))))
+        (i32.store8 offset=5 (local.get $s)
+          (i32.or (i32.const 0x80) (i32.and (i32.shr_u (local.get $c) (i32.const 6)) (i32.const 0x3f))))
+        (i32.store8 offset=6 (local.get $s) (i32.or (i32.const 0x80) (i32.and (local.get $c) (i32.const 0x3f))))
+        (i32.store (local.get $s) (i32.const 3))
+        (return (local.get $s))))
+    (i32.store8 offset=4 (local.get $s) (i32.or (i32.const 0xf0) (i32.shr_u (local.get $c) (i32.const 18))))
+    (i32.store8 offset=5 (local.get $s)
+      (i32.or (i32.const 0x80) (i32.and (i32.shr_u (local.get $c) (i32.const 12)) (i32.const 0x3f))))
+    (i32.store8 offset=6 (local.get $s)
+      (i32.or (i32.const 0x80) (i32.and (i32.shr_u (local.get $c) (i32.const 6)) (i32.const 0x3f))))
+    (i32.store8 offset=7 (local.get $s) (i32.or (i32.const 0x80) (i32.and (local.get $c) (i32.const 0x3f))))
+    (i32.store (local.get $s) (i32.const 4))
+    (local.get $s))
+
+  ;; Returns the decimal representation of an int.
+  (func $gore_itoa (param $n i64) (result i32)
+    (local $m i64) (local $s i32) (local $i i32)
+    (local.set $s (call $gore_alloc (i32.const 28)))
+    (local.set $i (i32.const 28))
+    (local.set $m (select (i64.sub (i64.const 0) (local.get $n)) (local.get $n)
+                          (i64.lt_s (local.get $n) (i64.const 0))))
+    (loop $next
+      (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+      (i32.store8 (i32.add (local.get $s) (local.get $i))
+        (i32.add (i32.const 48) (i32.wrap_i64 (i64.rem_u (local.get $m) (i64.const 10)))))
+      (local.set $m (i64.div_u (local.get $m) (i64.const 10)))
+      (br_if $next (i64.ne (local.get $m) (i64.const 0))))
+    (if (i64.lt_s (local.get $n) (i64.const 0))
+      (then
+        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+        (i32.store8 (i32.add (local.get $s) (local.get $i)) (i32.const 45))))
+    (local.set $s (i32.sub (i32.add (local.get $s) (local.get $i)) (i32.const 4)))
+    (i32.store (local.get $s) (i32.sub (i32.const 28) (local.get $i)))
+    (local.get $s))
+
+
+  ;; SLICES
+
+  (func $gore_slice_len (param $s i32) (result i64)
+    (if (result i64) (local.get $s)
+      (then (i64.extend_i32_u (i32.load offset=4 (local.get $s))))
+      (else (i64.const 0))))
+
+  (func $gore_slice_cap (param $s i32) (result i64)
+    (if (result i64) (local.get $s)
+      (then (i64.extend_i32_u (i32.load offset=8 (local.get $s))))
+      (else (i64.const 0))))
+
+  ;; Returns the address of the element `i` of an array of `len`
+  ;; elements of `size` bytes at `base`, after checking the index.
+  (func $gore_elem (param $base i32) (param $i i64) (param $len i64) (param $size i32) (param $loc i32)
+                   (result i32)
+    (if (i64.ge_u (local.get $i) (local.get $len))
+      (then
+        (call $gore_panic
+          (call $gore_concat
+            (call $gore_concat
+              (call $gore_concat (global.get $gore.str.index) (call $gore_itoa (local.get $i)))
+              (global.get $gore.str.length))
+            (call $gore_itoa (local.get $len)))
+          (local.get $loc))))
+    (i32.add (local.get $base) (i32.mul (i32.wrap_i64 (local.get $i)) (local.get $size))))
+
+  (func $gore_slice_elem (param $s i32) (param $i i64) (param $size i32) (param $loc i32) (result i32)
+    (call $gore_elem
+      (if (result i32) (local.get $s)
+        (then (i32.load (local.get $s)))
+        (else (i32.const 0)))
+      (local.get $i) (call $gore_slice_len (local.get $s)) (local.get $size) (local.get $loc)))
+
+  ;; Appends the element of `size` bytes at `elem` to `s`, copying
+  ;; the elements to a new array twice as large if `s` is full.
+  (func $gore_append (param $s i32) (param $elem i32) (param $size i32) (result i32)
+    (local $data i32) (local $len i32) (local $cap i32) (local $new i32)
+    (if (local.get $s)
+      (then
+        (local.set $data (i32.load (local.get $s)))
+        (local.set $len (i32.load offset=4 (local.get $s)))
+        (local.set $cap (i32.load offset=8 (local.get $s)))))
+    (if (i32.eq (local.get $len) (local.get $cap))
+      (then
+        (local.set $cap (select (i32.shl (local.get $cap) (i32.const 1)) (i32.const 1) (local.get $cap)))
+        (local.set $new (call $gore_alloc (i32.mul (local.get $cap) (local.get $size))))
+        (memory.copy (local.get $new) (local.get $data) (i32.mul (local.get $len) (local.get $size)))
+        (local.set $data (local.get $new))))
+    (memory.copy (i32.add (local.get $data) (i32.mul (local.get $len) (local.get $size)))
+                 (local.get $elem) (local.get $size))
+    (local.set $s (call $gore_alloc (i32.const 12)))
+    (i32.store (local.get $s) (local.get $data))
+    (i32.store offset=4 (local.get $s) (i32.add (local.get $len) (i32.const 1)))
+    (i32.store offset=8 (local.get $s) (local.get $cap))
+    (local.get $s))
+
+
+  ;; PRINTING
+
+  (func $gore_print_string (param $s i32)
+    (if (local.get $s)
+      (then (call $host.print_string (i32.add (local.get $s) (i32.const 4)) (i32.load (local.get $s))))))
+
+  (func $gore_print_bool (param $x i32)
+    (call $gore_print_string (select (global.get $gore.str.true) (global.get $gore.str.false) (local.get $x))))
+
+  (memory (export "memory") 17)
+  (global $gore_sp (mut i32) (i32.const 1048576))
+  (global $gore_heap (mut i32) (i32.const 1048800))
+  (global $gore.str.true i32 (i32.const 1048576))
+  (global $gore.str.false i32 (i32.const 1048584))
+  (global $gore.str.space i32 (i32.const 1048596))
+  (global $gore.str.newline i32 (i32.const 1048604))
+  (global $gore.str.divide i32 (i32.const 1048612))
+  (global $gore.str.shift i32 (i32.const 1048640))
+  (global $gore.str.index i32 (i32.const 1048668))
+  (global $gore.str.length i32 (i32.const 1048692))
+
+  (data (i32.const 1048576) "\04\00\00\00true")
+  (data (i32.const 1048584) "\05\00\00\00false")
+  (data (i32.const 1048596) "\01\00\00\00 ")
+  (data (i32.const 1048604) "\01\00\00\00\0a")
+  (data (i32.const 1048612) "\16\00\00\00integer divide by zero")
+  (data (i32.const 1048640) "\15\00\00\00negative shift amount")
+  (data (i32.const 1048668) "\14\00\00\00index out of range [")
+  (data (i32.const 1048692) "\0e\00\00\00] with length ")
+  (data (i32.const 1048712) "\10\00\00\00structs.go:14:16")
+  (data (i32.const 1048732) "\0f\00\00\00structs.go:14:2")
+  (data (i32.const 1048752) "\10\00\00\00structs.go:15:14")
+  (data (i32.const 1048772) "\10\00\00\00structs.go:16:12")
+  (data (i32.const 1048792) "\04\00\00\00same")
+
+  (func $gore.eq.t13 (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    i64.load
+    local.get $b
+    i64.load
+    i64.eq
+    i32.eqz
+    if
+      i32.const 0
+      return
+    end
+    local.get $a
+    i64.load offset=8
+    local.get $b
+    i64.load offset=8
+    i64.eq
+    i32.eqz
+    if
+      i32.const 0
+      return
+    end
+    i32.const 1
+  )
+
+  (func $gore.eq.t16 (param $a i32) (param $b i32) (result i32)
+    (local $t1 i32)
+    (local $t2 i32)
+    (local $t3 i32)
+    block $L1
+      loop $L2
+        local.get $t1
+        i32.const 2
+        i32.ge_u
+        br_if $L1
+        local.get $a
+        local.get $t1
+        i32.const 16
+        i32.mul
+        i32.add
+        local.set $t2
+        local.get $b
+        local.get $t1
+        i32.const 16
+        i32.mul
+        i32.add
+        local.set $t3
+        local.get $t2
+        local.get $t3
+        call $gore.eq.t13
+        i32.eqz
+        if
+          i32.const 0
+          return
+        end
+        local.get $t1
+        i32.const 1
+        i32.add
+        local.set $t1
+        br $L2
+      end
+    end
+    i32.const 1
+  )
+
+  (func $gore.init
+  )
+
+  (func $main.shift (param $result i32) (param $p.11 i32) (param $d.12 i64)
+    (local $fp i32)
+    (local $sp i32)
+    global.get $gore_sp
+    local.tee $sp
+    i32.const 16
+    i32.sub
+    local.tee $fp
+    global.set $gore_sp
+    local.get $p.11
+    local.get $p.11
+    i64.load
+    local.get $d.12
+    i64.add
+    i64.store
+    local.get $result
+    local.get $fp
+    local.get $p.11
+    i32.const 16
+    memory.copy
+    local.get $fp
+    i32.const 16
+    memory.copy
+    local.get $sp
+    global.set $gore_sp
+    return
+    unreachable
+  )
+
+  (func $main.main
+    (local $fp i32)
+    (local $sp i32)
+    (local $t1 i32)
+    (local $t2 i32)
+    (local $t3 i32)
+    (local $t4 i32)
+    (local $t5 i32)
+    (local $t6 i32)
+    (local $t7 i64)
+    (local $t8 i64)
+    global.get $gore_sp
+    local.tee $sp
+    i32.const 128
+    i32.sub
+    local.tee $fp
+    global.set $gore_sp
+    local.get $fp
+    i32.const 0
+    i32.const 32
+    memory.fill
+    local.get $fp
+    i64.const 1
+    local.get $fp
+    i32.const 32
+    i32.add
+    local.get $fp
+    i64.const 0
+    i64.const 2
+    i32.const 16
+    i32.const 1048712
+    call $gore_elem
+    local.set $t1
+    local.get $fp
+    i32.const 48
+    i32.add
+    local.get $t1
+    i32.const 16
+    memory.copy
+    local.get $fp
+    i32.const 48
+    i32.add
+    i64.const 3
+    call $main.shift
+    local.get $fp
+    i32.const 32
+    i32.add
+    local.set $t2
+    i64.const 2
+    i32.const 16
+    i32.const 1048732
+    call $gore_elem
+    local.get $t2
+    i32.const 16
+    memory.copy
+    local.get $fp
+    local.get $fp
+    i64.const 1
+    i64.const 2
+    i32.const 16
+    i32.const 1048752
+    call $gore_elem
+    local.set $t3
+    local.get $fp
+    i32.const 80
+    i32.add
+    local.get $t3
+    i32.const 16
+    memory.copy
+    local.get $fp
+    i32.const 80
+    i32.add
+    local.set $t4
+    i32.const 64
+    i32.add
+    local.get $t4
+    i32.const 16
+    memory.copy
+    block $L1
+      block $L3
+        block $L2
+          local.get $fp
+          i32.const 96
+          i32.add
+          local.get $fp
+          i32.const 64
+          i32.add
+          i32.const 16
+          memory.copy
+          local.get $fp
+          i32.const 96
+          i32.add
+          local.get $fp
+          i64.const 0
+          i64.const 2
+          i32.const 16
+          i32.const 1048772
+          call $gore_elem
+          local.set $t5
+          local.get $fp
+          i32.const 112
+          i32.add
+          local.get $t5
+          i32.const 16
+          memory.copy
+          local.get $fp
+          i32.const 112
+          i32.add
+          call $gore.eq.t13
+          br_if $L2
+          br $L3
+        end
+        i32.const 1048792
+        local.set $t6
+        local.get $t6
+        call $gore_print_string
+        global.get $gore.str.newline
+        call $gore_print_string
+        br $L1
+      end
+      local.get $fp
+      i64.load offset=64
+      local.set $t7
+      local.get $fp
+      i64.load offset=72
+      local.set $t8
+      local.get $t7
+      call $host.print_int
+      global.get $gore.str.space
+      call $gore_print_string
+      local.get $t8
+      call $host.print_int
+      global.get $gore.str.newline
+      call $gore_print_string
+    end
+    local.get $sp
+    global.set $gore_sp
+  )
+
+  (func $gore.main (export "main")
+    call $gore.init
+    call $main.main
+  )
+)
@@ -0,0 +1,211 @@
+extern crate gore;
+
+mod common;
+
+use std::thread;
+
+use gore::inline::{self, Decision, Reason};
+use gore::ir::{self, Module};
+use gore::ireval;
+use gore::irgen;
+use gore::opt;
+
+fn build(src: &str) -> Module {
+    let (prog, table, types) = common::check("t.go", src);
+    irgen::build(&prog, &types, &table)
+}
+
+/// Optimises `src`, and returns its module and what became of
+/// its calls.
+fn optimize(src: &str) -> (Module, Vec<Decision>) {
+    let mut module = build(src);
+    let decisions = opt::optimize(&mut module);
+    if let Err(err) = ir::verify(&module) {
+        panic!("{}: {}\n{}", src, err, ir::dump(&module));
+    }
+    (module, decisions)
+}
+
+/// Describes each call site of `src` by its caller, its callee and
+/// whether it is inlined.
+fn decisions(src: &str) -> Vec<String> {
+    optimize(src).1.iter().map(|d| {
+        let reason = match d.reason {
+            Reason::Inlined(_) => "inlined",
+            Reason::TooLarge(_) => "too large",
+            Reason::Recursive => "recursive",
+            Reason::CallerTooLarge => "caller too large",
+        };
+        format!("{} -> {}: {}", d.caller, d.callee, reason)
+    }).collect()
+}
+
+/// Runs a module; returns what it prints followed by its runtime
+/// error, if any.
+fn run(module: Module) -> String {
+    let evaluator = thread::Builder::new().stack_size(2 << 30).spawn(move || {
+        let mut out = Vec::new();
+        let result = ireval::run(&module, &mut out);
+        let mut text = String::from_utf8_lossy(&out).into_owned();
+        if let Err(err) = result {
+            text.push_str(&err.to_string());
+        }
+        text
+    });
+    evaluator.unwrap().join().unwrap()
+}
+
+fn assert_same_output(src: &str) {
+    assert_eq!(run(build(src)), run(optimize(src).0), "{}", src);
+}
+
+#[test]
+fn test_decisions() {
+    let mut big = String::from("func big(n int) int {\n");
+    for _ in 0 .. inline::MAX_CALLEE_SIZE {
+        big.push_str("    println(n)\n");
+    }
+    big.push_str("    return n\n}\n");
+    let src = format!("package main
+func sq(n int) int {{
+    return n * n
+}}
+func fact(n int) int {{
+    if n <= 1 {{
+        return 1
+    }}
+    return n * fact(n - 1)
+}}
+{}func main() {{
+    println(sq(2), fact(3), big(5))
+}}
+", big);
+    assert_eq!(vec![
+        "<init> -> main: inlined",
+        "fact -> fact: recursive",
+        "main -> sq: inlined",
+        "main -> fact: recursive",
+        "main -> big: too large",
+    ], decisions(&src));
+}
+
+#[test]
+fn test_nested_calls() {
+    let (module, decisions) = optimize("package main
+func one() int {
+    return 1
+}
+func two() int {
+    return one() + one()
+}
+func main() {
+    println(two())
+}
+");
+    assert!(decisions.iter().all(|d| d.reason != Reason::Recursive));
+    // Once `two` has its calls inlined, it is inlined in turn, and
+    // everything folds to a constant.
+    let main = module.functions.iter().find(|f| f.name == "main").unwrap();
+    let text = ir::dump_function(&module, main);
+    assert!(!text.contains("call") && text.contains("const 2"), "{}", text);
+}
+
+#[test]
+fn test_report() {
+    let (_, decisions) = optimize("package main
+func f(n int) int {
+    return f(n)
+}
+func g() {
+}
+func main() {
+    println(f(1))
+    g()
+}
+");
+    assert_eq!("t.go:1:9: inlined call to main in <init> (size 6)
+t.go:3:12: call to f in f not inlined: f is recursive
+t.go:8:13: call to f in main not inlined: f is recursive
+t.go:9:5: inlined call to g in main (size 1)
+", inline::report("t.go", &decisions));
+}
+
+#[test]
+fn test_same_output() {
+    // Arrays and structs are copied when they are passed.
+    assert_same_output("package main
+type point struct {
+    x, y int
+}
+func clear(a [3]int, p point) int {
+    a[0] = 0
+    p.x = 0
+    return a[0] + p.x
+}
+func main() {
+    var a [3]int
+    a[0] = 5
+    var p point
+    p.x = 7
+    println(clear(a, p), a[0], p.x)
+}
+");
+    // Functions with several returns, called in loops and in
+    // conditions.
+    assert_same_output("package main
+func abs(n int) int {
+    if n < 0 {
+        return -n
+    }
+    return n
+}
+func sign(n int) int {
+    switch {
+    case n < 0:
+        return -1
+    case n > 0:
+        return 1
+    }
+    return 0
+}
+func sum(n int) int {
+    s := 0
+    for i := 0; i < n; i++ {
+        s += abs(i - n / 2)
+    }
+    return s
+}
+func main() {
+    for i := -2; i < 3; i++ {
+        if abs(i) > 1 && sign(i) < 0 {
+            println(\"far\", i)
+        }
+        println(sum(i), sign(i))
+    }
+}
+");
+    // Runtime errors still happen, where they happen in the callee.
+    assert_same_output("package main
+func div(a int, b int) int {
+    return a / b
+}
+func at(s []int, i int) int {
+    return s[i]
+}
+func main() {
+    var s []int
+    s = append(s, 1)
+    println(div(6, 3), at(s, 0))
+    println(div(1, at(s, 0) - 1))
+}
+");
+    assert_same_output("package main
+func at(s []int, i int) int {
+    return s[i]
+}
+func main() {
+    var s []int
+    println(at(s, 2))
+}
+");
+}
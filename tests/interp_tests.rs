@@ -0,0 +1,19 @@
+extern crate gore;
+
+mod common;
+
+use gore::interp;
+
+/// Runs `src`, read from `filename`; returns what it prints, its
+/// runtime error and its exit status, as `gore run` would.
+fn run(filename: &str, src: &str) -> (String, String, i32) {
+    let (prog, table, types) = common::check(filename, src);
+    let mut out = Vec::new();
+    let result = interp::run(&prog, &types, &table, &mut out);
+    common::samples::outcome(out, result)
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| Some(run(&sample.filename, &sample.src)));
+}
@@ -0,0 +1,206 @@
+extern crate gore;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use gore::ast::BinOp;
+use gore::bytecode::{Const, Pos};
+use gore::ir::{self, Block, Function, Inst, Module, Op, Term, Ty};
+use gore::ireval;
+use gore::irgen;
+
+fn build(filename: &str, src: &str) -> Module {
+    let (prog, table, types) = common::check(filename, src);
+    let module = irgen::build(&prog, &types, &table);
+    if let Err(err) = ir::verify(&module) {
+        panic!("{}: {}\n{}", src, err, ir::dump(&module));
+    }
+    module
+}
+
+/// Builds and runs `src`, read from `filename`, on a thread with
+/// a stack large enough for the deepest recursion; returns what
+/// it prints, its runtime error and its exit status, as `gore run
+/// --ir` would.
+fn run(filename: &str, src: &str) -> (String, String, i32) {
+    let module = build(filename, src);
+    let evaluator = thread::Builder::new().stack_size(2 << 30).spawn(move || {
+        let mut out = Vec::new();
+        let result = ireval::run(&module, &mut out);
+        common::samples::outcome(out, result)
+    });
+    evaluator.unwrap().join().unwrap()
+}
+
+/// Compares the IR of every program of `tests/golden/ir` with
+/// the `.ir` file next to it; running the tests with
+/// `GORE_BLESS` rewrites the `.ir` files instead.
+#[test]
+fn test_golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/ir");
+    let mut sources: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "go"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+    for source in sources {
+        let name = source.file_name().unwrap().to_string_lossy().into_owned();
+        let text = ir::dump(&build(&name, &fs::read_to_string(&source).unwrap()));
+        let golden = source.with_extension("ir");
+        if env::var_os("GORE_BLESS").is_some() {
+            fs::write(&golden, text).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        assert!(expected == text, "{} differs from {}; run with GORE_BLESS=1 to update it",
+                name, golden.display());
+    }
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| Some(run(&sample.filename, &sample.src)));
+}
+
+#[test]
+fn test_stack_overflow() {
+    let (_, err, _) = run("t.go", "package main
+func f(n int) int {
+    return f(n + 1)
+}
+func main() {
+    f(0)
+}
+");
+    assert_eq!("panic: runtime error: stack overflow\n\tat t.go:3:12\n", err);
+}
+
+fn function(blocks: Vec<Block>, types: Vec<Ty>) -> Module {
+    let mut main = Function::new("main", Vec::new(), None);
+    main.blocks = blocks;
+    main.types = types;
+    Module { filename: "t.go".to_string(), globals: Vec::new(), functions: vec![main], entry: 0 }
+}
+
+fn inst(result: Option<usize>, op: Op) -> Inst {
+    Inst { result, op, pos: Pos { line: 1, col: 1 } }
+}
+
+fn block(insts: Vec<Inst>, term: Term) -> Block {
+    Block { insts, term, pos: Pos { line: 1, col: 1 } }
+}
+
+#[test]
+fn test_verify() {
+    let one = || inst(Some(0), Op::Const(Const::Int(1)));
+    let ok = function(vec![block(vec![one(), inst(None, Op::Println(vec![0]))], Term::Return(None))],
+                      vec![Ty::Int]);
+    assert_eq!(Ok(()), ir::verify(&ok));
+
+    let undefined = function(vec![block(vec![inst(None, Op::Println(vec![0]))], Term::Return(None))],
+                             vec![Ty::Int]);
+    assert!(ir::verify(&undefined).unwrap_err().contains("%0 is not defined"));
+
+    // A value defined in one branch is used after the join.
+    let not_dominated = function(vec![
+        block(vec![inst(Some(0), Op::Const(Const::Bool(true)))], Term::Branch(0, 1, 2)),
+        block(vec![inst(Some(1), Op::Const(Const::Int(1)))], Term::Jump(2)),
+        block(vec![inst(None, Op::Println(vec![1]))], Term::Return(None)),
+    ], vec![Ty::Bool, Ty::Int]);
+    assert!(ir::verify(&not_dominated).unwrap_err().contains("does not dominate"));
+
+    let phi = function(vec![
+        block(vec![inst(Some(0), Op::Const(Const::Bool(true)))], Term::Branch(0, 1, 2)),
+        block(vec![inst(Some(1), Op::Const(Const::Int(1)))], Term::Jump(2)),
+        block(vec![inst(Some(2), Op::Phi(vec![(1, 1)]))], Term::Return(None)),
+    ], vec![Ty::Bool, Ty::Int, Ty::Int]);
+    assert!(ir::verify(&phi).unwrap_err().contains("do not match the predecessors"));
+
+    let types = function(vec![block(vec![
+        inst(Some(0), Op::Const(Const::Int(1))),
+        inst(Some(1), Op::Const(Const::Float(1.0))),
+        inst(Some(2), Op::Binary(BinOp::Add, 0, 1)),
+    ], Term::Return(None))], vec![Ty::Int, Ty::Float, Ty::Int]);
+    assert!(ir::verify(&types).unwrap_err().contains("operands of the wrong type"));
+
+    let twice = function(vec![block(vec![one(), one()], Term::Return(None))], vec![Ty::Int]);
+    assert!(ir::verify(&twice).unwrap_err().contains("defined twice"));
+}
+
+#[test]
+fn test_dump() {
+    let module = build("t.go", "package main
+var n = 2
+func count(k int) int {
+    s := 0
+    for i := 0; i < k; i++ {
+        if i % 2 == 0 {
+            s += n
+        }
+    }
+    return s
+}
+func main() {
+    println(count(21))
+}
+");
+    assert_eq!("\
+; t.go
+; entry <init>
+
+global @n int
+
+func <init>() {
+b0:
+    %0: int = const 2
+    store @n, %0
+    call main()
+    ret
+}
+
+func count(int) int {
+b0:
+    %0: int = param 0
+    %1: int = const 0
+    %2: int = const 0
+    jump b1
+b1:
+    %3: int = phi [b0: %2], [b5: %14]
+    %4: int = phi [b0: %1], [b5: %12]
+    %5: bool = lt %3, %0
+    branch %5, b2, b6
+b2:
+    %6: int = const 2
+    %7: int = rem %3, %6
+    %8: int = const 0
+    %9: bool = eq %7, %8
+    branch %9, b3, b4
+b3:
+    %10: int = load @n
+    %11: int = add %4, %10
+    jump b4
+b4:
+    %12: int = phi [b2: %4], [b3: %11]
+    jump b5
+b5:
+    %13: int = const 1
+    %14: int = add %3, %13
+    jump b1
+b6:
+    ret %4
+}
+
+func main() {
+b0:
+    %0: int = const 21
+    %1: int = call count(%0)
+    println %1
+    ret
+}
+", ir::dump(&module));
+}
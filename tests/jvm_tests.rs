@@ -0,0 +1,100 @@
+extern crate gore;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use gore::jvm;
+
+fn generate(filename: &str, src: &str) -> Vec<(String, String)> {
+    let (prog, table, types) = common::check(filename, src);
+    jvm::generate(&prog, &types, &table)
+}
+
+/// Returns the Jasmin files of a program, one after the other.
+fn jasmin(files: &[(String, String)]) -> String {
+    files.iter()
+        .filter(|f| f.0.ends_with(".j"))
+        .map(|f| f.1.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Compiles `src`, read from `filename`, to Jasmin assembly,
+/// assembles it with `jasmin`, compiles the runtime class with
+/// `javac` and runs the program with `java`; returns its standard
+/// output, standard error and exit status, or `None` if a tool is
+/// missing.
+fn run(filename: &str, src: &str) -> Option<(String, String, i32)> {
+    let name = filename.trim_end_matches(".go");
+    let dir = env::temp_dir().join(format!("gore_jvm_{}_{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut classes = Vec::new();
+    for (file, text) in generate(filename, src) {
+        fs::write(dir.join(&file), text).unwrap();
+        if file.ends_with(".j") {
+            classes.push(dir.join(&file));
+        }
+    }
+    let mut jasmin = Command::new("jasmin");
+    jasmin.arg("-d").arg(&dir).args(&classes);
+    let mut javac = Command::new("javac");
+    javac.arg("-d").arg(&dir).arg(dir.join("GoreRuntime.java"));
+    for (tool, command) in [("jasmin", &mut jasmin), ("javac", &mut javac)].iter_mut() {
+        match command.output() {
+            Ok(output) => assert!(output.status.success(), "{}: {} failed: {}",
+                                  name, tool, String::from_utf8_lossy(&output.stderr)),
+            Err(_) => {
+                let _ = fs::remove_dir_all(&dir);
+                eprintln!("{}: no {}, skipped", name, tool);
+                return None;
+            }
+        }
+    }
+    let output = Command::new("java").arg("-cp").arg(&dir).arg(name).output();
+    let _ = fs::remove_dir_all(&dir);
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => {
+            eprintln!("{}: no java, skipped", name);
+            return None;
+        }
+    };
+    Some((String::from_utf8_lossy(&output.stdout).into_owned(),
+          String::from_utf8_lossy(&output.stderr).into_owned(),
+          output.status.code().unwrap_or(-1)))
+}
+
+/// Compares the classes generated from every program of
+/// `tests/golden/jvm` with the `.j` file next to it; setting
+/// `GORE_BLESS` rewrites the `.j` files instead.
+#[test]
+fn test_golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/jvm");
+    let mut sources: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "go"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+    for source in sources {
+        let name = source.file_name().unwrap().to_string_lossy().into_owned();
+        let text = jasmin(&generate(&name, &fs::read_to_string(&source).unwrap()));
+        let golden = source.with_extension("j");
+        if env::var_os("GORE_BLESS").is_some() {
+            fs::write(&golden, text).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        assert!(expected == text, "{} differs from {}; run with GORE_BLESS=1 to update it",
+                name, golden.display());
+    }
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| run(&sample.filename, &sample.src));
+}
@@ -0,0 +1,121 @@
+extern crate gore;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+use gore::llvm;
+
+fn generate(filename: &str, src: &str) -> String {
+    let (prog, table, types) = common::check(filename, src);
+    llvm::generate(&prog, &types, &table)
+}
+
+/// Returns the arguments that make `lli` accept opaque
+/// pointers, or `None` if it is not installed.
+fn lli_args() -> Option<Vec<&'static str>> {
+    let output = Command::new("lli").arg("--version").output().ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).into_owned();
+    let major = version.split("version ").nth(1)
+        .and_then(|v| v.split('.').next())
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(0);
+    // Opaque pointers are the default from LLVM 15 on.
+    Some(if major < 15 { vec!["-opaque-pointers"] } else { vec![] })
+}
+
+/// Compiles `src`, read from `filename`, to LLVM IR and runs it
+/// with `lli`; returns its standard output, standard error and
+/// exit status, or `None` if there is no `lli`.
+fn run(filename: &str, src: &str) -> Option<(String, String, i32)> {
+    let ir = generate(filename, src);
+    let args = match lli_args() {
+        Some(args) => args,
+        None => {
+            eprintln!("{}: no lli, skipped", filename);
+            return None;
+        }
+    };
+    let name = filename.trim_end_matches(".go");
+    let file = env::temp_dir().join(format!("gore_llvm_{}_{}.ll", name, process::id()));
+    fs::write(&file, ir).unwrap();
+    let output = Command::new("lli").args(args).arg(&file).output().unwrap();
+    let _ = fs::remove_file(&file);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(!stderr.starts_with("lli:"), "{}: {}", filename, stderr);
+    Some((String::from_utf8_lossy(&output.stdout).into_owned(), stderr, output.status.code().unwrap_or(-1)))
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| run(&sample.filename, &sample.src));
+}
+
+/// Returns the position of the definition of the LLVM type that
+/// starts with `def` in `ir`.
+fn type_def_position(ir: &str, def: &str) -> usize {
+    let line = ir.lines().position(|line| line.contains(" = type ") && line.contains(def));
+    line.unwrap_or_else(|| panic!("{}: {}", def, ir))
+}
+
+/// Element and field types are defined before the types that
+/// contain them, even when they are declared after them.
+#[test]
+fn test_nested_types() {
+    let grid = "package main
+type grid [3][3]int
+func main() {
+    var g grid
+    g[1][2] = 7
+    h := g
+    h[0][0] = 1
+    println(g[1][2], h[0][0], g == h, g[1] == h[1])
+}
+";
+    let ir = generate("t.go", grid);
+    assert!(type_def_position(&ir, "type [3 x i64]") < type_def_position(&ir, "type [3 x %t"), "{}", ir);
+
+    let outer = "package main
+type inner struct {
+    a int
+    b float64
+}
+type outer struct {
+    arr [2]inner
+    n int
+}
+func main() {
+    var o, p outer
+    o.arr[1].a = 3
+    p.arr[1].b = 2.5
+    println(o.arr[1].a, p.arr[1].b, o == p, o.arr[0] == p.arr[0])
+}
+";
+    let ir = generate("t.go", outer);
+    assert!(type_def_position(&ir, "type { i64, double }") < type_def_position(&ir, "type [2 x %t"), "{}", ir);
+    assert!(type_def_position(&ir, "type [2 x %t") < type_def_position(&ir, "type { %t"), "{}", ir);
+}
+
+#[test]
+fn test_debug_info() {
+    let ir = generate("t.go", "package main
+func div(x, y int) int {
+    return x / y
+}
+func main() {
+    println(div(1, 0))
+}
+");
+    assert!(ir.contains("!0 = distinct !DICompileUnit(language: DW_LANG_Go, file: !1"));
+    assert!(ir.contains("!1 = !DIFile(filename: \"t.go\""));
+    let line = ir.lines().find(|line| line.contains("!DISubprogram(name: \"div\"")).unwrap();
+    assert!(line.contains("linkageName: \"main.div\"") && line.contains("line: 2,"), "{}", line);
+    let node = line.split(" = ").next().unwrap();
+    assert!(ir.contains(&format!("define internal i64 @main.div(i64 %arg0, i64 %arg1) {} {{", node.replace('!', "!dbg !"))));
+    // The division carries the location of the operator.
+    let call = ir.lines().find(|line| line.contains("call i64 @gore_div64")).unwrap();
+    let dbg = call.split("!dbg ").nth(1).unwrap();
+    assert!(ir.contains(&format!("{} = !DILocation(line: 3, column: 14, scope: {})", dbg, node)), "{}", call);
+}
@@ -0,0 +1,222 @@
+extern crate gore;
+
+mod common;
+
+use std::thread;
+
+use gore::ast::BinOp;
+use gore::dce;
+use gore::ir::{self, Block, Function, Module, Op};
+use gore::ireval;
+use gore::irgen;
+use gore::licm;
+use gore::loops;
+use gore::opt;
+use gore::strength;
+
+fn build(src: &str) -> Module {
+    let (prog, table, types) = common::check("t.go", src);
+    irgen::build(&prog, &types, &table)
+}
+
+fn function<'a>(module: &'a Module, name: &str) -> &'a Function {
+    module.functions.iter().find(|f| f.name == name).unwrap()
+}
+
+/// Applies `pass` to function `f` of `src`, checks the result
+/// and returns it.
+fn apply(src: &str, pass: fn(&mut Function)) -> String {
+    let mut module = build(src);
+    for f in &mut module.functions {
+        pass(f);
+    }
+    if let Err(err) = ir::verify(&module) {
+        panic!("{}: {}\n{}", src, err, ir::dump(&module));
+    }
+    ir::dump_function(&module, function(&module, "f"))
+}
+
+/// Returns the lines of the block of `text` that the line that
+/// contains `inst` is in.
+fn block_of<'a>(text: &'a str, inst: &str) -> Vec<&'a str> {
+    let lines: Vec<&str> = text.lines().collect();
+    let at = lines.iter().position(|line| line.contains(inst)).unwrap_or_else(|| panic!("{}", text));
+    let start = lines[.. at].iter().rposition(|line| line.ends_with(':')).unwrap();
+    let end = at + lines[at ..].iter().position(|line| line.ends_with(':') || *line == "}").unwrap();
+    lines[start .. end].to_vec()
+}
+
+fn run(module: Module) -> String {
+    let evaluator = thread::Builder::new().stack_size(2 << 30).spawn(move || {
+        let mut out = Vec::new();
+        let result = ireval::run(&module, &mut out);
+        let mut text = String::from_utf8_lossy(&out).into_owned();
+        if let Err(err) = result {
+            text.push_str(&err.to_string());
+        }
+        text
+    });
+    evaluator.unwrap().join().unwrap()
+}
+
+fn assert_same_output(src: &str) {
+    let mut module = build(src);
+    opt::optimize(&mut module);
+    if let Err(err) = ir::verify(&module) {
+        panic!("{}: {}\n{}", src, err, ir::dump(&module));
+    }
+    assert_eq!(run(build(src)), run(module), "{}", src);
+}
+
+#[test]
+fn test_find() {
+    let module = build("package main
+func f(n int) int {
+    s := 0
+    for i := 0; i < n; i++ {
+        for j := 0; j < i; j++ {
+            s += j
+        }
+    }
+    for {
+        if s > 100 {
+            break
+        }
+        if s < 0 {
+            s = -s
+            continue
+        }
+        s *= 2
+    }
+    return s
+}
+");
+    let f = function(&module, "f");
+    let found = loops::find(f);
+    assert_eq!(3, found.len());
+    let depths: Vec<(usize, Option<usize>)> = found.iter().map(|l| (l.depth, l.parent)).collect();
+    assert_eq!(vec![(0, None), (1, Some(0)), (0, None)], depths);
+    // Even the loop with a `continue` statement has one block that
+    // continues it.
+    assert!(found.iter().all(|l| l.latches.len() == 1));
+    assert!(found[1].blocks.iter().all(|&b| found[0].contains(b)));
+    assert!(!found[2].blocks.iter().any(|&b| found[0].contains(b)));
+    let idom = ir::dominators(f);
+    for l in &found {
+        assert!(l.blocks.iter().all(|&b| ir::dominates(&idom, l.header, b)));
+    }
+
+    // Every loop gets a block that only jumps to its header.
+    let mut f = f.clone();
+    for l in loops::insert_preheaders(&mut f) {
+        let p = l.preheader.unwrap();
+        assert!(!l.contains(p));
+        assert_eq!(ir::Term::Jump(l.header), f.blocks[p].term);
+        assert_eq!(1, f.predecessors()[l.header].iter().filter(|&&b| !l.contains(b)).count());
+    }
+}
+
+#[test]
+fn test_hoist() {
+    let f = apply("package main
+func f(a []int, k int) int {
+    s := 0
+    for i := 0; i < len(a); i++ {
+        s += a[i] + k * 2 + len(a)
+    }
+    return s
+}
+", licm::hoist);
+    // The length and the product are computed once, before the
+    // loop; the check and the read of the element stay.
+    let body = block_of(&f, "loadelem");
+    assert!(body.iter().all(|line| !line.contains("len") && !line.contains("mul")), "{}", f);
+    assert!(body.iter().any(|line| line.contains("check")), "{}", f);
+}
+
+#[test]
+fn test_panics_not_hoisted() {
+    // The division would panic when the loop does not run.
+    let f = apply("package main
+func f(n int, d int) int {
+    s := 0
+    for i := 0; i < n; i++ {
+        s += 100 / d
+    }
+    return s
+}
+", licm::hoist);
+    assert!(block_of(&f, "div").iter().any(|line| line.contains("add")), "{}", f);
+    // Unless it is computed first, in the loop condition.
+    let f = apply("package main
+func f(n int, d int) int {
+    s := 0
+    for i := 0; i < n / d; i++ {
+        s += i
+    }
+    return s
+}
+", licm::hoist);
+    assert!(!block_of(&f, "div").iter().any(|line| line.contains("phi")), "{}", f);
+    assert_same_output("package main
+func f(n int, d int) int {
+    s := 0
+    for i := 0; i < n; i++ {
+        s += 100 / d
+    }
+    return s
+}
+func main() {
+    println(f(0, 0), f(3, 10))
+    println(f(3, 0))
+}
+");
+}
+
+#[test]
+fn test_strength_reduction() {
+    let module = build("package main
+func f(a []int, n int) int {
+    s := 0
+    for i := 0; i < n; i++ {
+        s += a[i * n] + n * i
+    }
+    return s
+}
+");
+    let mut f = function(&module, "f").clone();
+    strength::reduce(&mut f);
+    dce::eliminate(&mut f);
+    let found = loops::find(&f);
+    let (in_loop, outside): (Vec<_>, Vec<_>) = f.blocks.iter().enumerate().partition(|&(b, _)| found[0].contains(b));
+    let muls = |blocks: &[(usize, &Block)]| {
+        blocks.iter().flat_map(|&(_, block)| &block.insts)
+            .filter(|inst| matches!(inst.op, Op::Binary(BinOp::Mul, _, _))).count()
+    };
+    // Both products are one new induction variable, which starts
+    // at `0 * n` and is incremented by `1 * n`.
+    assert_eq!(0, muls(&in_loop), "{:?}", f);
+    assert_eq!(2, muls(&outside), "{:?}", f);
+    let phis = f.blocks[found[0].header].insts.iter().filter(|inst| matches!(inst.op, Op::Phi(_))).count();
+    assert_eq!(3, phis, "{:?}", f);
+
+    assert_same_output("package main
+func main() {
+    var a [10]int
+    for i := 0; i < 10; i++ {
+        a[i] = i * i
+    }
+    s := 0
+    for i := 9; i >= 0; i -= 2 {
+        for j := 0; j < 3; j++ {
+            s += a[i] * j + i * 7 + j * i
+        }
+    }
+    var r rune = 0
+    for c := rune(2147483640); c > 0; c += 3 {
+        r += c * 1000
+    }
+    println(s, r)
+}
+");
+}
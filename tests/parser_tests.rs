@@ -0,0 +1,335 @@
+extern crate gore;
+
+use gore::ast::*;
+use gore::error::Error;
+use gore::error::ErrorType as ET;
+use gore::parser::{self, Parser};
+use gore::scanner::Scanner;
+
+fn parse(src: &[u8]) -> Result<Program, Vec<Error>> {
+    let scanner = Scanner::new("-".to_string(), src.to_vec());
+    parser::parse(scanner)
+}
+
+fn parse_expr(src: &[u8]) -> Expr {
+    let scanner = Scanner::new("-".to_string(), src.to_vec());
+    let mut parser = Parser::new(scanner);
+    parser.parse_expr().unwrap()
+}
+
+/// Parses `src` and returns the program, including error
+/// nodes, along with the line and message of every error.
+fn parse_with_errors(src: &[u8]) -> (Program, Vec<(usize, String)>) {
+    let scanner = Scanner::new("-".to_string(), src.to_vec());
+    let mut parser = Parser::new(scanner);
+    let prog = parser.parse_program();
+    let errs = parser.errors().iter()
+        .map(|e| (e.loc.line, e.msg.clone().unwrap_or(e.ty.to_string())))
+        .collect();
+    (prog, errs)
+}
+
+/// Renders an expression fully parenthesized, so that
+/// tests can check associativity and precedence.
+fn sexp(e: &Expr) -> String {
+    match e.kind {
+        ExprKind::Id(ref id) => id.name.clone(),
+        ExprKind::Blank => "_".to_string(),
+        ExprKind::Int(ref digits, _) => digits.clone(),
+        ExprKind::Float(ref digits) => digits.clone(),
+        ExprKind::Rune(c) => format!("'{}'", c),
+        ExprKind::String(ref s) => format!("{:?}", s),
+        ExprKind::Unary(op, ref e) => format!("({} {})", op.token(), sexp(e)),
+        ExprKind::Binary(op, ref l, ref r) =>
+            format!("({} {} {})", op.token(), sexp(l), sexp(r)),
+        ExprKind::Call(ref f, ref args) => {
+            let args: Vec<String> = args.iter().map(sexp).collect();
+            format!("(call {} {})", sexp(f), args.join(" "))
+        }
+        ExprKind::Index(ref a, ref i) => format!("(index {} {})", sexp(a), sexp(i)),
+        ExprKind::Selector(ref e, ref f) => format!("(. {} {})", sexp(e), f.name),
+        ExprKind::Append(ref s, ref e) => format!("(append {} {})", sexp(s), sexp(e)),
+    }
+}
+
+fn assert_expr(expected: &str, src: &[u8]) {
+    assert_eq!(expected, sexp(&parse_expr(src)));
+}
+
+fn assert_parses(src: &[u8]) {
+    if let Err(errs) = parse(src) {
+        panic!("{}: {}", String::from_utf8_lossy(src), errs[0]);
+    }
+}
+
+fn assert_err(expected_err: ET, src: &[u8]) {
+    match parse(src) {
+        Ok(_) => { panic!("{}: parsed successfully", String::from_utf8_lossy(src)); }
+        Err(errs) => { assert_eq!(expected_err, errs[0].ty); }
+    }
+}
+
+fn body(src: &[u8]) -> Vec<Stmt> {
+    let mut prog = parse(src).unwrap();
+    match prog.decls.pop() {
+        Some(TopLevelDecl::Func(f)) => f.body.stmts,
+        _ => panic!("expected a function"),
+    }
+}
+
+#[test]
+fn test_precedence() {
+    assert_expr("(+ 1 (* 2 3))", b"1 + 2 * 3");
+    assert_expr("(- (- 1 2) 3)", b"1 - 2 - 3");
+    assert_expr("(|| a (&& b c))", b"a || b && c");
+    assert_expr("(== (+ a b) (<< c 2))", b"a + b == c << 2");
+    assert_expr("(&& (< a b) (!= c d))", b"a < b && c != d");
+    assert_expr("(| (&^ a b) (^ c))", b"a &^ b | ^c");
+    assert_expr("(* (+ 1 2) 3)", b"(1 + 2) * 3");
+    assert_expr("(- (! x))", b"-!x");
+}
+
+#[test]
+fn test_primary_exprs() {
+    assert_expr("(call f )", b"f()");
+    assert_expr("(call f 1 2)", b"f(1, 2)");
+    assert_expr("(. (index (. a b) 3) c)", b"a.b[3].c");
+    assert_expr("(append xs (+ 1 2))", b"append(xs, 1 + 2)");
+    assert_expr("(- (index a i))", b"-a[i]");
+    assert_expr("(call int 'a')", b"int('a')");
+}
+
+#[test]
+fn test_literals() {
+    match parse_expr(b"0x1F").kind {
+        ExprKind::Int(ref digits, IntBase::Hex) => assert_eq!("1F", digits),
+        _ => panic!("expected a hex literal"),
+    }
+    match parse_expr(b"017").kind {
+        ExprKind::Int(ref digits, IntBase::Oct) => assert_eq!("017", digits),
+        _ => panic!("expected an octal literal"),
+    }
+    match parse_expr(b"'\\n'").kind {
+        ExprKind::Rune(c) => assert_eq!('\n', c),
+        _ => panic!("expected a rune literal"),
+    }
+}
+
+#[test]
+fn test_top_level_decls() {
+    assert_parses(b"package main");
+    assert_parses(b"package main\nvar x int\n");
+    assert_parses(b"package main\nvar x, y int = 1, 2\n");
+    assert_parses(b"package main\nvar x = 1\n");
+    assert_parses(b"package main\nvar (\n\tx int\n\ty = 2\n)\n");
+    assert_parses(b"package main\nvar ()\n");
+    assert_parses(b"package main\ntype celsius float64\n");
+    assert_parses(b"package main\ntype (\n\tnum int\n\tnums []num\n)\n");
+    assert_parses(b"package main\ntype point struct {\n\tx, y float64\n\tname string\n}\n");
+    assert_parses(b"package main\ntype grid [3][3]int\n");
+    assert_parses(b"package main\nfunc main() {}\n");
+    assert_parses(b"package main\nfunc f(a, b int, c string) int { return a }\n");
+    assert_parses(b"package main\nfunc f(a []struct{ x int }) {}\n");
+
+    let prog = parse(b"package main\nfunc f(a, b int, c string) {}\n").unwrap();
+    assert_eq!("main", prog.package.name);
+    match prog.decls[0] {
+        TopLevelDecl::Func(ref f) => {
+            let names: Vec<&str> = f.params.iter().map(|p| p.name.name.as_str()).collect();
+            assert_eq!(vec!["a", "b", "c"], names);
+            assert!(f.result.is_none());
+        }
+        _ => panic!("expected a function"),
+    }
+}
+
+#[test]
+fn test_simple_stmts() {
+    let stmts = body(b"package main
+func main() {
+    x := 1
+    a, _ = b, c
+    x += 2
+    x <<= 1
+    x++
+    y.f[0]--
+    f(x)
+    ;
+}
+");
+    match stmts[0].kind {
+        StmtKind::ShortVarDecl(ref names, ref values) => {
+            assert_eq!(1, names.len());
+            assert_eq!(1, values.len());
+        }
+        _ => panic!("expected :="),
+    }
+    match stmts[1].kind {
+        StmtKind::Assign(ref lhs, ref rhs) => {
+            assert_eq!(2, lhs.len());
+            assert_eq!(2, rhs.len());
+        }
+        _ => panic!("expected ="),
+    }
+    match stmts[2].kind {
+        StmtKind::OpAssign(BinOp::Add, _, _) => {}
+        _ => panic!("expected +="),
+    }
+    match stmts[3].kind {
+        StmtKind::OpAssign(BinOp::Shl, _, _) => {}
+        _ => panic!("expected <<="),
+    }
+    match stmts[4].kind { StmtKind::Incr(_) => {} _ => panic!("expected ++") }
+    match stmts[5].kind { StmtKind::Decr(_) => {} _ => panic!("expected --") }
+    match stmts[6].kind { StmtKind::Expr(_) => {} _ => panic!("expected a call") }
+    // Empty statements are not part of the tree.
+    assert_eq!(7, stmts.len());
+}
+
+#[test]
+fn test_control_flow() {
+    assert_parses(b"package main
+func main() {
+    if x { } else if y { } else { }
+    if x := f(); x > 0 { return }
+    for { break }
+    for x < 10 { continue }
+    for i := 0; i < 10; i++ { }
+    for ;; { }
+    for i := 0; ; { }
+    switch { }
+    switch x { case 1, 2: print(x); default: println() }
+    switch x := f(); x {
+    case 1:
+        x++
+        fallthroughIsNotAKeyword()
+    }
+    switch x := f(); { default: }
+    { var x int; type t int }
+}
+");
+
+    let stmts = body(b"package main\nfunc main() { for i := 0; i < 3; i++ { } }");
+    match stmts[0].kind {
+        StmtKind::For(ref f) => {
+            assert!(f.init.is_some() && f.cond.is_some() && f.post.is_some());
+        }
+        _ => panic!("expected a for loop"),
+    }
+
+    let stmts = body(b"package main\nfunc main() { for x { } }");
+    match stmts[0].kind {
+        StmtKind::For(ref f) => {
+            assert!(f.init.is_none() && f.cond.is_some() && f.post.is_none());
+        }
+        _ => panic!("expected a for loop"),
+    }
+}
+
+#[test]
+fn test_syntax_errors() {
+    assert_err(ET::UnexpectedToken, b"func main() {}");
+    assert_err(ET::UnexpectedToken, b"package main\nfunc main() {\n");
+    assert_err(ET::ExpectedExpression, b"package main\nfunc main() { x + }");
+    assert_err(ET::ExpectedExpression, b"package main\nvar x = )\n");
+    assert_err(ET::ExpectedType, b"package main\nvar x *int\n");
+    assert_err(ET::NonNameInShortVarDecl, b"package main\nfunc f() { a[0] := 1 }");
+    assert_err(ET::MissingCondition, b"package main\nfunc f() { if x := 1 { } }");
+    assert_err(ET::UnexpectedToken, b"package main\nfunc f() { a, b++ }");
+    assert_err(ET::UnexpectedToken, b"package main\nx := 1\n");
+    assert_err(ET::UnrecognizedCharacter, b"package main\nvar x = #\n");
+}
+
+#[test]
+fn test_error_location() {
+    match parse(b"package main\n\nfunc f() {\n    x := (1 + 2\n}\n") {
+        Ok(_) => panic!("parsed successfully"),
+        Err(errs) => {
+            assert_eq!(1, errs.len());
+            assert_eq!(4, errs[0].loc.line);
+            assert_eq!(16, errs[0].loc.col);
+        }
+    }
+}
+
+#[test]
+fn test_error_messages() {
+    let (_, errs) = parse_with_errors(b"package main\nfunc main() {\n    f(1, 2\n}\n");
+    assert_eq!(vec![(3, "syntax error: expected ), found ;".to_string())], errs);
+
+    let (_, errs) = parse_with_errors(b"package main\nvar x = )\n");
+    assert_eq!(vec![(2, "syntax error: expected expression, found )".to_string())], errs);
+
+    let (_, errs) = parse_with_errors(b"package main\nx := 1\n");
+    assert_eq!(vec![(2, "syntax error: expected declaration, found <id>".to_string())], errs);
+}
+
+#[test]
+fn test_recovery_reports_all_errors() {
+    let (prog, errs) = parse_with_errors(b"package main
+
+func f() {
+    x := (1 + 2
+    y := 3
+    if y > { println(y) }
+    for i := 0; i < 10 i++ {
+    }
+    z = = 4
+}
+
+var v int = *
+
+func g() int {
+    return 1
+}
+");
+    let lines: Vec<usize> = errs.iter().map(|e| e.0).collect();
+    assert_eq!(vec![4, 6, 7, 9, 12], lines);
+
+    // The valid statements and declarations survive, and
+    // error nodes stand in for the rest.
+    assert_eq!(3, prog.decls.len());
+    match prog.decls[0] {
+        TopLevelDecl::Func(ref f) => {
+            let kinds: Vec<bool> = f.body.stmts.iter()
+                .map(|s| matches!(s.kind, StmtKind::Error))
+                .collect();
+            assert_eq!(vec![true, false, true, true, true], kinds);
+        }
+        _ => panic!("expected a function"),
+    }
+    match prog.decls[1] { TopLevelDecl::Error(_) => {} _ => panic!("expected an error node") }
+    match prog.decls[2] { TopLevelDecl::Func(ref f) => assert_eq!("g", f.name.name), _ => panic!() }
+}
+
+#[test]
+fn test_recovery_at_top_level() {
+    // A missing closing brace is reported when the next
+    // function starts, and that function is still parsed.
+    let (prog, errs) = parse_with_errors(b"package main
+func f() {
+    x := 1
+
+func g() {
+    y := )
+}
+type t struct { x int }
+");
+    let lines: Vec<usize> = errs.iter().map(|e| e.0).collect();
+    assert_eq!(vec![5, 6], lines);
+    assert_eq!("syntax error: expected }, found func", errs[0].1);
+    match prog.decls[1] { TopLevelDecl::Func(ref f) => assert_eq!("g", f.name.name), _ => panic!() }
+    match prog.decls[2] { TopLevelDecl::Type(_) => {} _ => panic!("expected a type declaration") }
+
+    let (_, errs) = parse_with_errors(b"pakage main\nvar x int = ]\n}\nvar y = [\n");
+    let lines: Vec<usize> = errs.iter().map(|e| e.0).collect();
+    assert_eq!(vec![1, 2, 3, 4], lines);
+}
+
+#[test]
+fn test_scanner_errors_stop_parsing() {
+    let (_, errs) = parse_with_errors(b"package main\nvar x = #\nvar y = )\n");
+    assert_eq!(1, errs.len());
+    assert_eq!(2, errs[0].0);
+    assert_eq!("unrecognized character", errs[0].1);
+}
@@ -0,0 +1,254 @@
+extern crate gore;
+
+mod common;
+
+use gore::ast::Program;
+use gore::parser;
+use gore::pretty::{pretty_program, pretty_expr, pretty_typed_program};
+use gore::scanner::Scanner;
+
+fn parse(src: &[u8]) -> Program {
+    let scanner = Scanner::new("-".to_string(), src.to_vec());
+    match parser::parse(scanner) {
+        Ok(prog) => prog,
+        Err(errs) => panic!("{}: {}", String::from_utf8_lossy(src), errs[0]),
+    }
+}
+
+/// Returns the debug representation of a tree with all
+/// locations removed, so that trees can be compared.
+fn tree(prog: &Program) -> String {
+    let debug = format!("{:?}", prog);
+    let mut out = String::new();
+    let mut rest = debug.as_str();
+    while let Some(start) = rest.find("Loc {") {
+        out.push_str(&rest[.. start]);
+        let end = start + rest[start ..].find('}').unwrap();
+        rest = &rest[end + 1 ..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Checks that pretty-printing `src` is idempotent and that
+/// re-parsing the output yields the same tree.
+fn assert_roundtrip(src: &[u8]) {
+    let prog = parse(src);
+    let printed = pretty_program(&prog);
+    let reparsed = parse(printed.as_bytes());
+    assert_eq!(tree(&prog), tree(&reparsed), "{}", printed);
+    assert_eq!(printed, pretty_program(&reparsed));
+}
+
+fn assert_pretty(expected: &str, src: &[u8]) {
+    assert_eq!(expected, pretty_program(&parse(src)));
+}
+
+fn assert_pretty_expr(expected: &str, src: &str) {
+    let prog = parse(format!("package main\nvar x = {}\n", src).as_bytes());
+    match prog.decls[0] {
+        gore::ast::TopLevelDecl::Var(ref specs) => {
+            assert_eq!(expected, pretty_expr(&specs[0].values[0]));
+        }
+        _ => panic!("expected a var declaration"),
+    }
+}
+
+#[test]
+fn test_parentheses() {
+    assert_pretty_expr("1 + 2 * 3", "1 + (2 * 3)");
+    assert_pretty_expr("(1 + 2) * 3", "((1 + 2)) * 3");
+    assert_pretty_expr("a - b - c", "(a - b) - c");
+    assert_pretty_expr("a - (b - c)", "a - (b - c)");
+    assert_pretty_expr("a || b && c", "a || (b && c)");
+    assert_pretty_expr("(a || b) && c", "(a || b) && c");
+    assert_pretty_expr("-(a + b)", "-(a+b)");
+    assert_pretty_expr("- -a", "-(-a)");
+    assert_pretty_expr("-^!a", "-(^(!a))");
+    assert_pretty_expr("(a + b)[0].f(x)", "(a+b)[0].f(x)");
+    assert_pretty_expr("-a[0]", "-(a[0])");
+    assert_pretty_expr("a &^ b << 2", "a&^b<<2");
+}
+
+#[test]
+fn test_literals() {
+    assert_pretty_expr("0x1f", "0x1f");
+    assert_pretty_expr("0755", "0755");
+    assert_pretty_expr(".5", ".5");
+    assert_pretty_expr("'\\n'", "'\\n'");
+    assert_pretty_expr("'\\''", "'\\''");
+    assert_pretty_expr("\"tab\\t\\\"quoted\\\" \\\\\"", "\"tab\\t\\\"quoted\\\" \\\\\"");
+    assert_pretty_expr("\"raw\\\\n\"", "`raw\\n`");
+}
+
+#[test]
+fn test_declarations() {
+    assert_pretty("package main
+
+var x int
+
+var (
+\ta, b = 1, 2
+\tc []int
+)
+
+type point struct {
+\tx, y float64
+\tname string
+}
+
+type empty struct{}
+
+func f(a, b int, s string) [2]int {
+\treturn g(a, b)
+}
+", b"package main
+var x int
+var (a, b = 1, 2; c []int)
+type point struct { x float64; y float64; name string }
+type empty struct {}
+func f(a int, b int, s string) [2]int { return g(a, b) }
+");
+}
+
+/// Struct types nested in struct fields and parameters are
+/// indented like the fields around them.
+#[test]
+fn test_nested_structs() {
+    let src = b"package main
+type A struct { x, y int; b [2]struct{ z float64; w struct { v int } } }
+func f(p struct { q int }) {}
+";
+    assert_pretty("package main
+
+type A struct {
+\tx, y int
+\tb [2]struct {
+\t\tz float64
+\t\tw struct {
+\t\t\tv int
+\t\t}
+\t}
+}
+
+func f(p struct {
+\tq int
+}) {
+}
+", src);
+    assert_roundtrip(src);
+}
+
+#[test]
+fn test_statements() {
+    assert_pretty("package main
+
+func main() {
+\tx := 1
+\tif x := f(); x > 0 {
+\t\tx++
+\t} else if x < 0 {
+\t\tx--
+\t} else {
+\t}
+\tfor {
+\t\tbreak
+\t}
+\tfor x < 10 {
+\t\tcontinue
+\t}
+\tfor i := 0; i < 10; i += 2 {
+\t}
+\tfor ; ; x <<= 1 {
+\t}
+\tswitch x {
+\tcase 1, 2:
+\t\tprintln(x, \"a\")
+\tdefault:
+\t\tprint()
+\t}
+\t{
+\t\tvar y int
+\t\t_, y = y, 2
+\t}
+}
+", b"package main
+func main() {
+    x := 1;;
+    if x := f(); x > 0 { x++ } else if x < 0 { x-- } else {}
+    for { break }
+    for ; x < 10 ; { continue }
+    for i := 0; i < 10; i += 2 {}
+    for ;; x <<= 1 {}
+    switch x { case 1, 2: println(x, \"a\"); default: print() }
+    { var y int; _, y = y, 2 }
+}
+");
+}
+
+#[test]
+fn test_roundtrip() {
+    assert_roundtrip(b"package main
+
+type (
+    celsius float64
+    matrix [4][4]struct { re, im float64 }
+)
+
+var grid matrix
+
+func fib(n int) int {
+    if n < 2 { return n }
+    return fib(n - 1) + fib(n - 2)
+}
+
+func main() {
+    var xs []int
+    for i := 0; i < 10; i++ {
+        xs = append(xs, fib(i) * -(-i) % 3 &^ 1)
+    }
+    switch y := len(xs); {
+    case y > 5 && !(y == 6 || y != 7):
+        println(xs[y - 1], grid[0][1].re, 'x', \"done\\n\")
+    }
+    var c celsius = celsius(float64(3) / 2.)
+    c += 1.5
+}
+");
+}
+
+/// Expressions are annotated with their types, and `:=`
+/// statements with the types of the variables they declare.
+/// Operands keep only the parentheses that precedence requires,
+/// and calls are annotated with the type of their result.
+#[test]
+fn test_typed() {
+    let (prog, table, types) = common::check("-", "package main
+var a [2]float64
+func f(n int) int {
+    return -(n - 1)
+}
+func main() {
+    x, y := 1, a[0] * 2
+    x, z := -x + 1, \"s\"
+    println(x < 2, len(z), f(x * (x + 1)))
+}
+");
+    let printed = pretty_typed_program(&prog, &types, &table);
+    assert_eq!("package main
+
+var a [2 /* int */]float64
+
+func f(n int) int {
+\treturn -(n - 1) /* int */
+}
+
+func main() {
+\tx, y := 1 /* int */, a[0 /* int */] * 2 /* float64 */ /* x int, y float64 */
+\tx, z := -x + 1 /* int */, \"s\" /* string */ /* z string */
+\tprintln(x < 2 /* bool */, len(z /* string */) /* int */, f(x * (x + 1) /* int */) /* int */)
+}
+", printed);
+    // The comments leave the program unchanged.
+    assert_eq!(pretty_program(&prog), pretty_program(&parse(printed.as_bytes())));
+}
@@ -0,0 +1,37 @@
+package main
+
+type grid [3][3]int
+
+type inner struct {
+    a int
+    b float64
+}
+
+type outer struct {
+    arr [2]inner
+    n int
+}
+
+func swap(g grid) grid {
+    g[0], g[1] = g[1], g[0]
+    return g
+}
+
+func main() {
+    var g grid
+    g[1][2] = 7
+    println(swap(g)[0][2] + 5, g[1][2], swap(g) == g, swap(swap(g)) == g)
+    var o, p outer
+    o.arr[1].a = 3
+    p.arr[1].b = 2.5
+    println(o.arr[1].a, p.arr[1].b, o == p, o.arr[0] == p.arr[0])
+    // Appending an array copies it.
+    var rows [][2]int
+    var row [2]int
+    rows = append(rows, row)
+    row[0] = 5
+    rows[0][1] = 2
+    rows = append(rows, row)
+    rows[1][0] = 99
+    println(rows[1][0], rows[0][1] + rows[0][0])
+}
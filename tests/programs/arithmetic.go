@@ -0,0 +1,23 @@
+package main
+
+func main() {
+    a, b := 7, -7
+    println(a / 2, b / 2, b % 3, a % 3, 1 << 10, -8 >> 1, ^5, 5 &^ 3, b >> 10)
+    var r rune = 2147483647
+    r++
+    var i int = 9223372036854775807
+    i++
+    j := i
+    j--
+    println(r, i, j)
+    f := 1.0
+    var zero float64
+    var inf = f / zero
+    println(f / 3, -15000000000.0, zero, inf, inf - inf)
+    g := 2.9
+    println(int(g), int(-g), 'x', rune(65), string(rune(65)), string(-1), 1 < 2, !true)
+    var c rune = -1
+    c <<= 7
+    println(c, 'x' % 'q', int(inf), "ab" < "b", "a" + "b" == "ba")
+    println(!(g < 1) && g > 2 || false)
+}
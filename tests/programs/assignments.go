@@ -0,0 +1,26 @@
+package main
+
+type pair struct {
+    a, b int
+}
+
+var g pair
+
+func set() int {
+    g.b = 2
+    return 5
+}
+
+// The operands on the left are evaluated before the values, but
+// package variables are only read once they are known.
+func main() {
+    g.a = set()
+    println(g.a, g.b)
+    x, y := 1, 2
+    x, y = x, x + y
+    println(x, y)
+    var a [2]int
+    k := 0
+    k, a[k] = 1, 7
+    println(a[0], a[1], k)
+}
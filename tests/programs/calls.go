@@ -0,0 +1,74 @@
+package main
+
+type vec struct {
+    x, y float64
+}
+
+type big struct {
+    a [3]int
+    s string
+}
+
+type mixed struct {
+    n int
+    f float64
+}
+
+func add(a, b vec) vec {
+    var r vec
+    r.x = a.x + b.x
+    r.y = a.y + b.y
+    return r
+}
+
+func many(a, b, c, d, e, f, g, h int, x, y float64, s string, t []int) int {
+    return a + b * 2 + c * 3 + d * 4 + e * 5 + f * 6 + g * 7 + h * 8 + int(x) + int(y) + len(s) + t[0]
+}
+
+func floats(a, b, c, d, e, f, g, h, i, j float64) float64 {
+    return a + b + c + d + e + f + g + h + i * j
+}
+
+func build(n int, s string) big {
+    var b big
+    b.a[2] = n
+    b.s = s
+    return b
+}
+
+func twice(b big, m mixed) big {
+    b.a[0] = b.a[2] * 2 + m.n
+    b.s = b.s + b.s
+    return b
+}
+
+func mix(m mixed) mixed {
+    m.n++
+    m.f = m.f * 2
+    return m
+}
+
+func greet(s string) string {
+    return "hello, " + s
+}
+
+func grid() [4][4]int {
+    var g [4][4]int
+    g[3][3] = 9
+    return g
+}
+
+func main() {
+    var a, b vec
+    a.x, b.y = 1.5, 2.5
+    c := add(a, b)
+    println(c.x, c.y)
+    var t []int
+    t = append(t, 100)
+    println(many(1, 2, 3, 4, 5, 6, 7, 8, 9.5, 10.5, "abc", t))
+    println(floats(1, 2, 3, 4, 5, 6, 7, 8, 9, 10))
+    var m mixed
+    m.n, m.f = 3, 0.25
+    x := twice(build(5, "ab"), mix(m))
+    println(x.a[0], x.a[2], x.s, mix(m).n, greet("gore"), grid()[3][3], grid() == grid())
+}
@@ -0,0 +1,83 @@
+package main
+
+func trace(s string, n int) int {
+    print(s)
+    return n
+}
+
+func yes(s string) bool {
+    print(s)
+    return true
+}
+
+func fib(n int) int {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+
+func sum(n int) int {
+    s := 0
+    for i := 0; ; i++ {
+        switch {
+        case i > n:
+            return s
+        case i % 2 == 0:
+            continue
+        }
+        s += i
+    }
+}
+
+func main() {
+    x := 2
+    switch z := x * 10; {
+    case z > 15:
+        println("big")
+        break
+        println("unreachable")
+    case z > 5:
+        println("medium")
+    }
+    switch x {
+    case 1, trace("c", 2), trace("d", 3):
+        println("two")
+    default:
+        println("other")
+    }
+    k := 0
+    n := 0
+    for {
+        k++
+        if k > 3 {
+            break
+        }
+        for j := 0; j < 10; j++ {
+            if j == 1 {
+                continue
+            }
+            if j > 1 {
+                break
+            }
+            n++
+        }
+    }
+    {
+        k := k + 1
+        n := k
+        println(k - 1, n)
+    }
+    i := 0
+    for i < 10 && yes(".") {
+        i += 3
+        switch {
+        case i == 6:
+            continue
+        default:
+            println(i)
+        case i > 100 && yes("no"):
+        }
+    }
+    println(fib(25), sum(6), n)
+}
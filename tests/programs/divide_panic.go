@@ -0,0 +1,9 @@
+package main
+
+func div(x, y int) int {
+    return x / y
+}
+
+func main() {
+    println(div(1, 0))
+}
@@ -0,0 +1,30 @@
+package main
+
+var calls int
+
+func bump() int {
+    calls++
+    return calls
+}
+
+func trace(s string, n int) int {
+    print(s)
+    return n
+}
+
+func yes(s string) bool {
+    print(s)
+    return true
+}
+
+var first = bump()
+var _ = bump()
+
+func main() {
+    println(first, calls)
+    x := trace("a", 1) + trace("b", 2) * trace("c", 3) - 1
+    println("", x)
+    if false && yes("no") || yes("y") {
+        println("!")
+    }
+}
@@ -0,0 +1,8 @@
+package main
+
+func main() {
+    var s []int
+    s = append(s, 1)
+    println("before")
+    println(s[0], s[1])
+}
@@ -0,0 +1,47 @@
+package main
+
+// More variables live at once than there are registers, some of
+// them across calls, and floats, which calls clobber.
+func pressure(n int) int {
+    a, b, c, d, e, f, g, h := 1, 2, 3, 4, 5, 6, 7, 8
+    for k := 0; k < n; k++ {
+        a, b, c, d = b, c, d, a
+        e, f, g, h = f + k, g, h, e
+    }
+    return a + b + c + d + e + f + g + h
+}
+
+func floats(x, y float64, n int) float64 {
+    s, t := 0.0, 1.0
+    for i := 0; i < n; i++ {
+        s += x
+        t *= y
+    }
+    println(s, t, s + t * 1.5)
+    return s * t
+}
+
+func count(s []int, stop int) int {
+    n := 0
+    for i := 0; i < len(s); i++ {
+        if s[i] == stop {
+            break
+        }
+        if s[i] % 2 == 0 {
+            continue
+        }
+        n++
+    }
+    return n
+}
+
+func main() {
+    println(pressure(4))
+    p := floats(1.25, 2.0, 2)
+    var s []int
+    for i := 3; i > 0; i-- {
+        s = append(s, i)
+    }
+    println(s[0], s[1], s[2], p + 0.5)
+    println(count(append(s, 5), 9) + 1)
+}
@@ -0,0 +1,12 @@
+package main
+
+func show(s string) int {
+    println(s)
+    return 1
+}
+
+func main() {
+    var a [2][2]int
+    i, j := 5, 0
+    a[j][i] = show("value")
+}
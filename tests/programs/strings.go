@@ -0,0 +1,10 @@
+package main
+
+func main() {
+    s := "a"
+    s += "b"
+    println(s, len(s), s < "b", s == "a")
+    var r rune = 233
+    t := "h" + string(r) + "llo"
+    println(t, len(t))
+}
@@ -0,0 +1,59 @@
+package main
+
+type point struct {
+    x, y float64
+    _ int
+}
+
+type pair [2]string
+
+var origin point
+
+func swap(p pair) pair {
+    p[0], p[1] = p[1], p[0]
+    return p
+}
+
+type stack struct {
+    items []int
+}
+
+func main() {
+    var p point
+    p.x = 1.5
+    q := p
+    q.y = -2
+    println(p.x, p.y, q.y, p == q, origin == q == false)
+    var g, h [3][3]int
+    g[1][2] = 7
+    h = g
+    h[0][0] = 1
+    println(g[1][2], h[0][0], g[0][0], g == h, g[1] == h[1])
+    var x pair
+    x[0] = "a"
+    y := swap(x)
+    println(x[0], y[0] + y[1], x == y, swap(y) == x)
+    // Slices share their elements.
+    var s []int
+    for i := 0; i < 5; i++ {
+        s = append(s, i * i)
+    }
+    t := s
+    t[0] = 99
+    println(s[0], len(s), cap(s))
+    // The element is assigned in the new value of the array.
+    var a, b [2]int
+    b[1] = 9
+    a, a[0] = b, 3
+    a[1] = len(swap(x)[1]) + 13
+    println(a[0], a[1], b[1])
+    // Appending to a full slice copies its elements.
+    var st stack
+    st.items = append(st.items, 1)
+    st.items = append(st.items, 2)
+    v := append(st.items, 3)
+    v[0] = 7
+    w := append(v, 4)
+    w[1] = 8
+    println(st.items[0], v[0], v[1], len(w))
+}
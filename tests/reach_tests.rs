@@ -0,0 +1,67 @@
+extern crate gore;
+
+mod common;
+
+use gore::ast::Program;
+use gore::error::ErrorType as ET;
+use gore::pretty::pretty_program;
+use gore::reach;
+
+/// Checks a program whose `main` function has body `body`.
+fn check(body: &str) -> Program {
+    let src = format!("package main\nfunc f(x int) int {{\nreturn x\n}}\nfunc main() {{\n{}\n}}\n", body);
+    let (prog, _, _) = common::check("-", &src);
+    prog
+}
+
+/// Returns the lines of `body` that are reported as unreachable.
+fn unreachable_lines(body: &str) -> Vec<usize> {
+    let warnings = reach::unreachable(&check(body));
+    assert!(warnings.iter().all(|w| w.ty == ET::UnreachableCode && w.warning));
+    warnings.iter().map(|w| w.loc.line - 5).collect()
+}
+
+/// Returns the body of `main` once the unreachable statements
+/// are removed.
+fn eliminate(body: &str) -> String {
+    let mut prog = check(body);
+    reach::eliminate(&mut prog);
+    let text = pretty_program(&prog);
+    let start = text.find("func main() {\n").unwrap() + "func main() {\n".len();
+    text[start ..].trim_end().trim_end_matches('}').lines()
+        .map(|line| line.trim()).collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn test_warnings() {
+    assert_eq!(Vec::<usize>::new(), unreachable_lines("println(1)\nreturn"));
+    assert_eq!(vec![2], unreachable_lines("return\nprintln(1)\nprintln(2)"));
+    assert_eq!(vec![2], unreachable_lines("for { break\nprintln(1) }"));
+    assert_eq!(vec![2], unreachable_lines("for { continue\nprintln(1) }"));
+    assert_eq!(vec![2], unreachable_lines("for {}\nprintln(1)"));
+    assert_eq!(Vec::<usize>::new(), unreachable_lines("for { if f(1) > 0 { break } }\nprintln(1)"));
+    assert_eq!(vec![2], unreachable_lines("for { for { break } }\nprintln(1)"));
+    assert_eq!(vec![2], unreachable_lines("if f(1) > 0 { return } else { return }\nprintln(1)"));
+    assert_eq!(Vec::<usize>::new(), unreachable_lines("if f(1) > 0 { return }\nprintln(1)"));
+    assert_eq!(vec![2], unreachable_lines("{ return }\nprintln(1)"));
+    assert_eq!(vec![3], unreachable_lines("switch { case true: return\ndefault: return }\nprintln(1)"));
+    assert_eq!(Vec::<usize>::new(), unreachable_lines("switch { case true: return }\nprintln(1)"));
+    assert_eq!(Vec::<usize>::new(), unreachable_lines("switch { default: break }\nprintln(1)"));
+    // The statements after each unreachable one are reachable from it.
+    assert_eq!(vec![2, 3], unreachable_lines("return\nfor {}\nprintln(1)\nprintln(2)"));
+    // Constant conditions are deliberately ignored.
+    assert_eq!(Vec::<usize>::new(), unreachable_lines("if false { println(1) }\nfor false { println(2) }"));
+}
+
+#[test]
+fn test_eliminate() {
+    assert_eq!("println(1) return", eliminate("println(1)\nreturn\nprintln(2)"));
+    assert_eq!("for { break }", eliminate("for { break\nprintln(1) }"));
+    assert_eq!("{ }", eliminate("if false { println(1) }"));
+    assert_eq!("{ { println(1) } }", eliminate("if true { println(1) } else { println(2) }"));
+    assert_eq!("{ y := f(1) { println(y) } }", eliminate("if y := f(1); false { println(1) } else { println(y) }"));
+    assert_eq!("{ y := f(1) } println(2)", eliminate("for y := f(1); false; y++ { println(1) }\nprintln(2)"));
+    assert_eq!("{ { return } }", eliminate("if true { return }\nprintln(1)"));
+    assert_eq!("switch { case f(1) > 0: return default: println(1) }",
+               eliminate("switch { case f(1) > 0: return\nprintln(2)\ndefault: println(1) }"));
+}
@@ -0,0 +1,115 @@
+extern crate gore;
+
+mod common;
+
+use gore::ast::{FuncDecl, TopLevelDecl};
+use gore::regalloc::{self, Allocation, Interval};
+use gore::x86_64;
+
+/// Allocates the registers of function `f` of `src`.
+fn allocate(src: &str) -> Allocation {
+    let (prog, table, types) = common::check("t.go", src);
+    let f: &FuncDecl = prog.decls.iter()
+        .filter_map(|decl| match *decl {
+            TopLevelDecl::Func(ref f) if f.name.name == "f" => Some(f),
+            _ => None,
+        })
+        .next()
+        .unwrap();
+    regalloc::allocate(f, &types, &table)
+}
+
+fn interval<'a>(alloc: &'a Allocation, name: &str) -> &'a Interval {
+    alloc.intervals.iter().find(|i| i.var.name == name).unwrap_or_else(|| panic!("{:?}", alloc))
+}
+
+#[test]
+fn test_intervals() {
+    let alloc = allocate("package main
+func f(n int) int {
+    s := 0
+    for i := 0; i < n; i++ {
+        t := i * 2
+        s += t
+    }
+    var a [3]int
+    u := s
+    return u + a[0]
+}
+");
+    // Arrays are never in registers.
+    let names: Vec<&str> = alloc.intervals.iter().map(|i| i.var.name.as_str()).collect();
+    assert_eq!(vec!["n", "s", "i", "t", "u"], names);
+    let (n, s, i, t, u) = (interval(&alloc, "n"), interval(&alloc, "s"), interval(&alloc, "i"),
+                           interval(&alloc, "t"), interval(&alloc, "u"));
+    // The variables declared before the loop and used in it live
+    // until its end; those declared in it do not.
+    assert!(n.end >= i.end && s.end > i.end, "{:?}", alloc);
+    assert!(t.start > i.start && t.end < i.end, "{:?}", alloc);
+    assert!(u.start > i.end && u.start > s.end, "{:?}", alloc);
+    assert!(alloc.intervals.iter().all(|i| !i.float && !i.across_calls));
+}
+
+#[test]
+fn test_calling_convention() {
+    let alloc = allocate("package main
+func g(x float64) float64 {
+    return x
+}
+func f(k int, x float64) float64 {
+    y := x * 2.0
+    z := x + 1.0
+    println(k)
+    return y + g(z) + float64(k)
+}
+");
+    let (k, x, y, z) = (interval(&alloc, "k"), interval(&alloc, "x"), interval(&alloc, "y"), interval(&alloc, "z"));
+    assert!(x.float && !x.across_calls && x.reg.is_some_and(|reg| reg.starts_with("%xmm")), "{:?}", alloc);
+    // No SSE register survives a call.
+    assert!(y.float && y.across_calls && y.reg.is_none(), "{:?}", alloc);
+    assert!(z.across_calls && z.reg.is_none(), "{:?}", alloc);
+    assert!(k.across_calls && k.reg.is_some_and(|reg| regalloc::INT_CALLEE_SAVED.contains(&reg)), "{:?}", alloc);
+    assert_eq!(vec!["%rbx"], alloc.callee_saved());
+}
+
+#[test]
+fn test_spills() {
+    // Eight integers live at once, across calls, for five
+    // registers: those that end last are spilled.
+    let alloc = allocate("package main
+func f() int {
+    a, b, c, d, e, g, h, i := 1, 2, 3, 4, 5, 6, 7, 8
+    println(a + b + c + d + e + g + h + i)
+    println(i, h, g)
+    println(e)
+    println(d, c, b, a)
+    return 0
+}
+");
+    let spilled: Vec<&str> = alloc.intervals.iter().filter(|i| i.reg.is_none()).map(|i| i.var.name.as_str()).collect();
+    assert_eq!(vec!["a", "b", "c"], spilled);
+    let mut regs: Vec<&str> = alloc.intervals.iter().filter_map(|i| i.reg).collect();
+    regs.sort();
+    regs.dedup();
+    assert_eq!(5, regs.len());
+    assert_eq!(regalloc::INT_CALLEE_SAVED.to_vec(), alloc.callee_saved());
+}
+
+#[test]
+fn test_dump() {
+    let (prog, table, types) = common::check("t.go", "package main
+func f(n int) int {
+    m := n + 1
+    println(m)
+    return m
+}
+");
+    assert_eq!("main.f:
+  n 2:8 [1, 2]: %r10
+  m 3:5 [3, 6] across calls: %rbx
+", regalloc::dump(&regalloc::allocate_program(&prog, &types, &table)));
+    // The function saves the register that it uses for its
+    // caller, and restores it.
+    let asm = x86_64::generate(&prog, &types, &table);
+    assert!(asm.contains("movq %rbx, -8(%rbp)") && asm.contains("movq -8(%rbp), %rbx"), "{}", asm);
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::map_clone)]
+
 extern crate gore;
 
 use gore::token::Token;
@@ -504,3 +506,27 @@ fn test_rune() {
     assert_err(ET::InvalidEscape, b"'\\p'");
     assert_err(ET::TrailingRune, b"'xx'");
 }
+
+#[test]
+fn test_operator_locations() {
+    // Operators and punctuation are located at their first
+    // character, not after it.
+    let mut scanner = Scanner::new("-".to_string(), b"x <<= (y+1)".to_vec());
+    let mut cols = Vec::new();
+    loop {
+        let tok = scanner.next().unwrap();
+        if tok.ty == TT::Eof || tok.ty == TT::Semi {
+            break;
+        }
+        cols.push((tok.ty, tok.loc.line, tok.loc.col));
+    }
+    assert_eq!(vec![
+        (TT::Id, 1, 1),
+        (TT::LeftShiftEq, 1, 3),
+        (TT::LParen, 1, 7),
+        (TT::Id, 1, 8),
+        (TT::Plus, 1, 9),
+        (TT::Int, 1, 10),
+        (TT::RParen, 1, 11),
+    ], cols);
+}
@@ -0,0 +1,213 @@
+extern crate gore;
+
+use gore::ast::*;
+use gore::error::Error;
+use gore::error::ErrorType as ET;
+use gore::parser;
+use gore::scanner::Scanner;
+use gore::symtab::{self, SymbolKind, SymbolTable};
+
+fn resolve(src: &str) -> (Program, Result<SymbolTable, Vec<Error>>) {
+    let scanner = Scanner::new("t.go".to_string(), src.as_bytes().to_vec());
+    let mut prog = match parser::parse(scanner) {
+        Ok(prog) => prog,
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    };
+    let table = symtab::resolve(&mut prog);
+    (prog, table)
+}
+
+fn assert_ok(src: &str) {
+    if let Err(errs) = resolve(src).1 {
+        panic!("{}: {}", src, errs[0]);
+    }
+}
+
+/// Checks the errors of a program, as (type, line) pairs.
+fn assert_errs(expected: &[(ET, usize)], src: &str) {
+    match resolve(src).1 {
+        Ok(_) => panic!("{}: resolved successfully", src),
+        Err(errs) => {
+            let actual: Vec<(ET, usize)> = errs.iter().map(|e| (e.ty, e.loc.line)).collect();
+            assert_eq!(expected, &actual[..], "{}", src);
+        }
+    }
+}
+
+#[test]
+fn test_universe() {
+    assert_ok("package main
+var b bool = true || false
+var s string
+var r rune
+var f float64
+var n int = len(s) + cap(s)
+");
+    // Predeclared names can be shadowed.
+    assert_ok("package main\nvar int = 3\nvar true = false\n");
+    assert_ok("package main\nfunc f() { var string int; string = 1 }\n");
+}
+
+#[test]
+fn test_redeclarations() {
+    assert_errs(&[(ET::RedeclaredIdentifier, 3)], "package main\nvar x int\nvar x int\n");
+    assert_errs(&[(ET::RedeclaredIdentifier, 3)], "package main\nfunc f() {}\ntype f int\n");
+    assert_errs(&[(ET::RedeclaredIdentifier, 2)], "package main\nvar x, x int\n");
+    assert_errs(&[(ET::RedeclaredIdentifier, 2)],
+                "package main\nfunc f(a int, a string) {}\n");
+    // Parameters share the scope of the function body.
+    assert_errs(&[(ET::RedeclaredIdentifier, 2)],
+                "package main\nfunc f(a int) { var a int }\n");
+    assert_errs(&[(ET::RedeclaredIdentifier, 2)],
+                "package main\nfunc f() { a := 1; var a int }\n");
+
+    // Inner scopes may shadow outer ones.
+    assert_ok("package main
+var x int
+func f(x int) {
+    var y int
+    { var x, y string }
+    if x := 1; x > 0 { var x int }
+    for x := 0; x < 3; x++ { x := 1 }
+    switch x := 2; x { case 1: x := 3; default: var x int }
+}
+");
+    // The blank identifier is never declared.
+    assert_ok("package main\nvar _, _ int\nfunc _() {}\nfunc _(_ int, _ string) {}\n");
+}
+
+#[test]
+fn test_undeclared() {
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)], "package main\nvar x = y\n");
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)], "package main\nvar x t\n");
+    assert_errs(&[(ET::UndeclaredIdentifier, 2), (ET::UndeclaredIdentifier, 2)],
+                "package main\nfunc f() { x = y }\n");
+    // Identifiers must be declared before use.
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)], "package main\nfunc f() { g() }\nfunc g() {}\n");
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)], "package main\nfunc f() { x++; var x int }\n");
+    // A variable is not in scope in its own initializer.
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)], "package main\nvar x = x\n");
+    // Block-scoped variables are not visible outside the block.
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)],
+                "package main\nfunc f() { if x := 1; x > 0 { }; x++ }\n");
+    assert_errs(&[(ET::UndeclaredIdentifier, 2)],
+                "package main\nfunc f() { switch { case true: y := 1 }; y++ }\n");
+    // Fields are not resolved as identifiers.
+    assert_ok("package main\ntype p struct { x int }\nfunc f(a p) { a.x = a.x + 1 }\n");
+
+    // Functions and types can be recursive.
+    assert_ok("package main\nfunc f(n int) int { return f(n - 1) }\n");
+    assert_ok("package main\ntype tree struct { children []tree }\n");
+}
+
+#[test]
+fn test_not_a_type() {
+    assert_errs(&[(ET::NotAType, 3)], "package main\nvar x int\nvar y x\n");
+    assert_errs(&[(ET::NotAType, 2)], "package main\nvar y []true\n");
+    assert_errs(&[(ET::NotAType, 3)], "package main\nfunc f() {}\nfunc g(x f) {}\n");
+    // Types may appear in expressions, as conversions.
+    assert_ok("package main\nvar x = float64(3)\n");
+}
+
+#[test]
+fn test_short_var_decl() {
+    assert_ok("package main\nfunc f() { a := 1; a, b := 2, 3; b, c := a, b }\n");
+    assert_ok("package main\nvar a int\nfunc f() { a := 1; a++ }\n");
+    assert_errs(&[(ET::NoNewVariables, 2)], "package main\nfunc f() { a := 1; a := 2 }\n");
+    assert_errs(&[(ET::NoNewVariables, 2)], "package main\nfunc f() { _ := 2 }\n");
+    assert_errs(&[(ET::RedeclaredIdentifier, 2)], "package main\nfunc f() { a, a := 1, 2 }\n");
+
+    // Redeclared variables refer to the existing symbol.
+    let (prog, table) = resolve("package main\nfunc f() { a := 1; a, b := 2, 3 }\n");
+    let table = table.unwrap();
+    let stmts = match prog.decls[0] {
+        TopLevelDecl::Func(ref f) => &f.body.stmts,
+        _ => panic!("expected a function"),
+    };
+    let first = match stmts[0].kind {
+        StmtKind::ShortVarDecl(ref names, _) => names[0].sym,
+        _ => panic!("expected :="),
+    };
+    match stmts[1].kind {
+        StmtKind::ShortVarDecl(ref names, _) => {
+            assert_eq!(first, names[0].sym);
+            assert!(names[1].sym.is_some() && names[1].sym != first);
+            assert_eq!("b", table.symbol(names[1].sym.unwrap()).name);
+        }
+        _ => panic!("expected :="),
+    }
+}
+
+#[test]
+fn test_uses_are_linked_to_declarations() {
+    let (prog, table) = resolve("package main
+var x int
+func f(x string) string {
+    return x
+}
+var y = x
+");
+    let table = table.unwrap();
+    let (param, ret) = match prog.decls[1] {
+        TopLevelDecl::Func(ref f) => {
+            let ret = match f.body.stmts[0].kind {
+                StmtKind::Return(Some(Expr { kind: ExprKind::Id(ref id), .. })) => id.sym,
+                _ => panic!("expected return x"),
+            };
+            (f.params[0].name.sym, ret)
+        }
+        _ => panic!("expected a function"),
+    };
+    assert_eq!(param, ret);
+    let global = match prog.decls[0] {
+        TopLevelDecl::Var(ref specs) => specs[0].names[0].sym,
+        _ => panic!("expected a var declaration"),
+    };
+    match prog.decls[2] {
+        TopLevelDecl::Var(ref specs) => match specs[0].values[0].kind {
+            ExprKind::Id(ref id) => assert_eq!(global, id.sym),
+            _ => panic!("expected x"),
+        },
+        _ => panic!("expected a var declaration"),
+    }
+    assert_eq!(SymbolKind::Var, table.symbol(global.unwrap()).kind);
+    assert!(global != param);
+}
+
+#[test]
+fn test_error_messages() {
+    let (_, table) = resolve("package main\nvar x int\nfunc f() { var x, x int; y++ }\nvar x int\n");
+    let msgs: Vec<String> = table.unwrap_err().iter().map(|e| e.to_string()).collect();
+    assert_eq!(vec![
+        "t.go:3:19: x redeclared in this block (previous declaration at t.go:3:16)",
+        "t.go:3:26: undefined: y",
+        "t.go:4:5: x redeclared in this block (previous declaration at t.go:2:5)",
+    ], msgs);
+}
+
+#[test]
+fn test_dump() {
+    let (_, table) = resolve("package main
+type t int
+func f(a t) {
+    b := a
+    if c := b; c > 0 {
+        var d int
+    }
+}
+");
+    let dump = table.unwrap().dump();
+    let expected = "  package scope
+    t [type] t.go:2:6
+    f [func] t.go:3:6
+    function f scope, t.go:3:1
+      a [var] t.go:3:8
+      b [var] t.go:4:5
+      block scope, t.go:5:5
+        c [var] t.go:5:8
+        block scope, t.go:5:22
+          d [var] t.go:6:13
+";
+    assert!(dump.starts_with("universe scope\n  int [type]\n"), "{}", dump);
+    assert!(dump.ends_with(expected), "{}", dump);
+}
@@ -0,0 +1,368 @@
+extern crate gore;
+
+use gore::ast::*;
+use gore::error::Error;
+use gore::error::ErrorType as ET;
+use gore::parser;
+use gore::scanner::Scanner;
+use gore::symtab::{self, SymbolTable};
+use gore::typecheck;
+use gore::types::{self, TypeTable};
+
+fn check(src: &str) -> (Program, SymbolTable, Result<TypeTable, Vec<Error>>) {
+    let scanner = Scanner::new("t.go".to_string(), src.as_bytes().to_vec());
+    let mut prog = match parser::parse(scanner) {
+        Ok(prog) => prog,
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    };
+    let mut table = match symtab::resolve(&mut prog) {
+        Ok(table) => table,
+        Err(errs) => panic!("{}: {}", src, errs[0]),
+    };
+    let types = typecheck::check(&mut prog, &mut table);
+    (prog, table, types)
+}
+
+/// Checks a program made of `decls` and a `main`
+/// function whose body is `body`.
+fn check_body(decls: &str, body: &str) -> Vec<(ET, usize)> {
+    let src = format!("package main\n{}\nfunc main() {{\n{}\n}}\n", decls, body);
+    match check(&src).2 {
+        Ok(_) => Vec::new(),
+        Err(errs) => errs.iter().map(|e| (e.ty, e.loc.line)).collect(),
+    }
+}
+
+const DECLS: &str = "type celsius float64
+type point struct { x, y float64 }
+type ints []int
+var i int
+var f float64
+var r rune
+var b bool
+var s string
+var c celsius
+var p point
+var a [3]int
+var sl []int
+func g() {}
+func h(x int, y string) int { return x }";
+
+fn assert_ok(body: &str) {
+    assert_eq!(Vec::<(ET, usize)>::new(), check_body(DECLS, body), "{}", body);
+}
+
+fn assert_err(expected: ET, body: &str) {
+    let errs: Vec<ET> = check_body(DECLS, body).iter().map(|e| e.0).collect();
+    assert_eq!(vec![expected], errs, "{}", body);
+}
+
+fn assert_decl_err(expected: &[(ET, usize)], src: &str) {
+    match check(src).2 {
+        Ok(_) => panic!("{}: type checked successfully", src),
+        Err(errs) => {
+            let actual: Vec<(ET, usize)> = errs.iter().map(|e| (e.ty, e.loc.line)).collect();
+            assert_eq!(expected, &actual[..], "{}", src);
+        }
+    }
+}
+
+#[test]
+fn test_declarations() {
+    assert_ok("var x, y int = 1, 2; var z = x + y; w := z; w++");
+    assert_ok("var q = p; q.x = 1.5; var t [3]int = a; t[1] = i");
+    assert_ok("type t struct { next []t; val int }; var x t; x.next = append(x.next, x)");
+    assert_ok("type temp celsius; var x temp = 3; var y celsius = celsius(x)");
+    assert_err(ET::IncompatibleAssign, "var x int = \"s\"");
+    assert_err(ET::IncompatibleAssign, "var x [4]int = a");
+    assert_decl_err(&[(ET::InvalidRecursiveType, 2)], "package main\ntype t struct { x t }\n");
+    assert_decl_err(&[(ET::InvalidRecursiveType, 2)], "package main\ntype t [2]t\n");
+    assert_decl_err(&[(ET::DuplicateField, 2)], "package main\ntype t struct { a int; a string }\n");
+    assert_decl_err(&[(ET::InvalidArrayLength, 3)], "package main\nvar n int\nvar a [n]int\n");
+    assert_decl_err(&[(ET::InvalidMain, 2)], "package main\nfunc main(x int) {}\n");
+}
+
+#[test]
+fn test_defined_types() {
+    // A defined type is distinct from its underlying type...
+    assert_err(ET::IncompatibleAssign, "c = f");
+    assert_err(ET::IncompatibleAssign, "f = c");
+    assert_err(ET::MismatchedTypes, "_ = c + f");
+    assert_err(ET::IncompatibleAssign, "type myint int; var x myint; i = x");
+    // ...but it can be converted, and untyped constants
+    // and unnamed types are assignable to it.
+    assert_ok("c = celsius(f); f = float64(c) + 1; c = 2 * c");
+    assert_ok("var x ints = sl; sl = x; x = append(x, 1)");
+    assert_ok("type pair struct { a, b int }; var x pair; var y struct { a, b int } = x; x = y");
+}
+
+#[test]
+fn test_arithmetic() {
+    assert_ok("i = i + 1 - i * i / i % i");
+    assert_ok("f = f + 1 - f * 2.5 / f");
+    assert_ok("r = r + 'a' - 1; s = s + \"x\"");
+    assert_ok("i = i & 3 | 4 ^ i &^ 1; i = ^i; i = -i + +i");
+    assert_ok("i = i << 2 >> r; r = r << i; i = 1 << i");
+    assert_ok("i += 1; i -= 2; i *= 3; i /= 4; i %= 5; i &= 6; i |= 7; i <<= 1; i >>= 1");
+    assert_ok("s += \"x\"; f /= 2; c *= 1.5");
+
+    assert_err(ET::InvalidOperation, "_ = f % f");
+    assert_err(ET::InvalidOperation, "_ = f & f");
+    assert_err(ET::InvalidOperation, "_ = s - s");
+    assert_err(ET::InvalidOperation, "_ = b + b");
+    assert_err(ET::InvalidOperation, "_ = -s");
+    assert_err(ET::InvalidOperation, "_ = ^f");
+    assert_err(ET::InvalidOperation, "_ = !i");
+    assert_err(ET::InvalidOperation, "_ = i << f");
+    assert_err(ET::InvalidOperation, "_ = f << 2");
+    assert_err(ET::InvalidOperation, "_ = p + p");
+    assert_err(ET::InvalidOperation, "s -= \"x\"");
+    assert_err(ET::InvalidOperation, "b++");
+    assert_err(ET::MismatchedTypes, "_ = i + f");
+    assert_err(ET::MismatchedTypes, "_ = i + r");
+    assert_err(ET::MismatchedTypes, "_ = s + 1");
+    assert_err(ET::MismatchedTypes, "i += f");
+}
+
+#[test]
+fn test_comparisons_and_logic() {
+    assert_ok("b = i < 2 && f >= 1.5 || s != \"x\" && r == 'a'");
+    assert_ok("b = p == p && a != a && c > 3 && !b");
+    assert_ok("type mybool bool; var x mybool = i < 2; x = x && true; b = 1 < 2");
+
+    assert_err(ET::InvalidOperation, "_ = sl == sl");
+    assert_err(ET::InvalidOperation, "_ = p < p");
+    assert_err(ET::InvalidOperation, "_ = b < b");
+    assert_err(ET::InvalidOperation, "_ = i && i");
+    assert_err(ET::MismatchedTypes, "_ = i && b");
+    assert_err(ET::InvalidOperation, "type t struct { s []int }; var x t; _ = x == x");
+    assert_err(ET::MismatchedTypes, "_ = i == f");
+    assert_err(ET::MismatchedTypes, "_ = s == 1");
+    assert_err(ET::MismatchedTypes, "type mybool bool; var x mybool; _ = x && b");
+}
+
+#[test]
+fn test_calls() {
+    assert_ok("g(); i = h(1, \"a\"); h(i, s)");
+    assert_ok("i = len(s) + len(a) + len(sl) + cap(a) + cap(sl) + len(\"abc\")");
+    assert_ok("i = int(f); f = float64(i); r = rune(i); s = string(r); s = string(65)");
+    assert_ok("sl = append(sl, 1); sl = append(sl, i)");
+
+    assert_err(ET::NoValue, "i = g()");
+    assert_err(ET::NoValue, "println(g())");
+    assert_err(ET::WrongArgumentCount, "h(1)");
+    assert_err(ET::WrongArgumentCount, "h(1, s, s)");
+    assert_err(ET::WrongArgumentCount, "i = len(s, s)");
+    assert_err(ET::WrongArgumentCount, "i = int()");
+    assert_err(ET::IncompatibleAssign, "h(s, s)");
+    assert_err(ET::NotCallable, "i()");
+    assert_err(ET::InvalidArgument, "i = len(i)");
+    assert_err(ET::InvalidArgument, "i = cap(s)");
+    assert_err(ET::InvalidArgument, "_ = append(a, 1)");
+    assert_err(ET::IncompatibleAssign, "sl = append(sl, s)");
+    assert_err(ET::InvalidConversion, "s = string(f)");
+    assert_err(ET::InvalidConversion, "b = bool(i)");
+    assert_err(ET::InvalidConversion, "i = int(\"1\")");
+    assert_err(ET::InvalidConversion, "_ = point(a)");
+    assert_err(ET::NotAnExpression, "_ = len");
+    assert_err(ET::NotAnExpression, "_ = int");
+}
+
+#[test]
+fn test_index_and_select() {
+    assert_ok("a[0] = sl[i]; sl[r] = sl['a']; p.x = p.y");
+    assert_err(ET::InvalidIndex, "_ = s[0]");
+    assert_err(ET::InvalidIndex, "_ = i[0]");
+    assert_err(ET::InvalidIndex, "_ = a[f]");
+    assert_err(ET::InvalidIndex, "_ = a[\"0\"]");
+    assert_err(ET::UnknownField, "_ = p.z");
+    assert_err(ET::UnknownField, "_ = i.x");
+}
+
+#[test]
+fn test_assignments() {
+    assert_ok("i, f = 1, 2; _ = g; _, s = 1.5, \"x\"");
+    assert_ok("x := 1; x, y := 2, 3.5; y = 1");
+    assert_err(ET::IncompatibleAssign, "i = s");
+    assert_err(ET::IncompatibleAssign, "x := 1; x, y := \"s\", 3");
+    assert_err(ET::NonAddressableAssignment, "g = g");
+    assert_err(ET::NonAddressableAssignment, "true = false");
+    // Elements of a slice are variables, those of an array
+    // are only if the array is.
+    assert_decl_err(&[(ET::NonAddressableAssignment, 4)], "package main
+func f() [2]int { var a [2]int; return a }
+func g() []int { var s []int; return s }
+func main() { g()[0] = 1; f()[0] = 1 }
+");
+}
+
+#[test]
+fn test_statements() {
+    assert_ok("if b { } else if i > 0 { }; for i < 3 { }; for { }");
+    assert_ok("switch i { case 1, 2: case 'a': default: }; switch { case b, i > 2: }");
+    assert_ok("switch x := f; x { case 1.5, 2: }");
+    assert_ok("print(i, f, r, b, s, c, 1, 'a', \"s\", true); println()");
+    assert_err(ET::NonBooleanCondition, "if i { }");
+    assert_err(ET::NonBooleanCondition, "for s { }");
+    assert_err(ET::MismatchedTypes, "switch i { case \"a\": }");
+    assert_err(ET::MismatchedTypes, "switch { case 1: }");
+    assert_err(ET::InvalidOperation, "switch sl { }");
+    assert_err(ET::InvalidArgument, "println(p)");
+    assert_err(ET::InvalidArgument, "print(a)");
+    assert_err(ET::UnusedExpression, "i + 1");
+    assert_err(ET::UnusedExpression, "len(s)");
+    assert_err(ET::UnusedExpression, "float64(i)");
+    assert_err(ET::ReturnMismatch, "return 1");
+    assert_decl_err(&[(ET::ReturnMismatch, 2)], "package main\nfunc f() int { return }\n");
+    assert_decl_err(&[(ET::IncompatibleAssign, 2)], "package main\nfunc f() int { return \"\" }\n");
+}
+
+/// Untyped constants take the type of their context.
+#[test]
+fn test_untyped_constants() {
+    let (prog, _, types) = check("package main
+var f float64
+func main() {
+    var x = f + 1
+    y := 1 + 2.5
+    z := 'a' + 1
+    var w float64 = 3 * 2
+    println(f == 1, 1 < 2)
+}
+");
+    types.unwrap();
+    let body = match prog.decls[1] {
+        TopLevelDecl::Func(ref f) => &f.body.stmts,
+        _ => panic!("expected main"),
+    };
+    let expr = |i: usize| -> &Expr {
+        match body[i].kind {
+            StmtKind::Var(ref specs) => &specs[0].values[0],
+            StmtKind::ShortVarDecl(_, ref values) => &values[0],
+            _ => panic!("expected a declaration"),
+        }
+    };
+    let operands = |e: &Expr| match e.kind {
+        ExprKind::Binary(_, ref x, ref y) => (x.ty, y.ty),
+        _ => panic!("expected a binary expression"),
+    };
+    assert_eq!(Some(types::FLOAT64), expr(0).ty);
+    assert_eq!((Some(types::FLOAT64), Some(types::FLOAT64)), operands(expr(0)));
+    assert_eq!(Some(types::FLOAT64), expr(1).ty);
+    assert_eq!(Some(types::RUNE), expr(2).ty);
+    assert_eq!((Some(types::RUNE), Some(types::RUNE)), operands(expr(2)));
+    assert_eq!((Some(types::FLOAT64), Some(types::FLOAT64)), operands(expr(3)));
+    match body[4].kind {
+        StmtKind::Println(ref args) => {
+            assert_eq!(Some(types::BOOL), args[0].ty);
+            assert_eq!((Some(types::FLOAT64), Some(types::FLOAT64)), operands(&args[0]));
+            assert_eq!((Some(types::INT), Some(types::INT)), operands(&args[1]));
+        }
+        _ => panic!("expected println"),
+    }
+}
+
+/// Constant expressions are evaluated exactly, and must be
+/// representable in the type they end up with.
+#[test]
+fn test_constant_representability() {
+    assert_ok("var x int = 1.5 * 2; r = 'a' + 1; i = 1 << 62; f = 1 << 100");
+    assert_ok("i = 9223372036854775807; r = 2147483647; i = int(2.0)");
+    assert_ok("i = (1 << 100) >> 98; i = 10 / 4 * 4; f = 10 / 4.0; _ = 1.0 << 3");
+    assert_ok("var x [2.0]int; x[1] = a[2]; i = len(\"abc\") << 2");
+
+    assert_err(ET::ConstantTruncated, "var x int = 1.5");
+    assert_err(ET::ConstantTruncated, "i = 7 / 2.0");
+    assert_err(ET::ConstantTruncated, "i = int(2.5)");
+    assert_err(ET::ConstantOverflow, "i = 9223372036854775808");
+    assert_err(ET::ConstantOverflow, "r = 3000000000");
+    assert_err(ET::ConstantOverflow, "i = 1 << 63");
+    assert_err(ET::ConstantOverflow, "i = -(1 << 63) - 1");
+    assert_err(ET::ConstantOverflow, "f = 1 << 1100");
+    assert_err(ET::ConstantOverflow, "i = int(1 << 70) - 1");
+    assert_err(ET::DivisionByZero, "_ = 1 / 0");
+    assert_err(ET::DivisionByZero, "i = i % (1 - 1)");
+    assert_err(ET::DivisionByZero, "f = f / 0.0");
+    assert_err(ET::InvalidOperation, "i = 1 << -1");
+    assert_err(ET::InvalidOperation, "i = 1 << 100000");
+    assert_err(ET::InvalidOperation, "_ = 1.5 << 2");
+    assert_err(ET::InvalidIndex, "_ = a[3]");
+    assert_err(ET::InvalidIndex, "_ = sl[-1]");
+    assert_err(ET::InvalidArrayLength, "var x [-1]int");
+}
+
+#[test]
+fn test_constant_values() {
+    let (prog, _, types) = check("package main
+func main() {
+    a := 1 << 70 >> 68
+    b := 7 / 2
+    c := 7 / 2.0
+    d := -7 % 3
+    e := string('x') + \"yz\"
+    f := 1 < 2 && !false
+    g := ^5 &^ 2
+    h := 0.1 + 0.2
+    i := len(\"hello\")
+}
+");
+    types.unwrap();
+    let body = match prog.decls[0] {
+        TopLevelDecl::Func(ref f) => &f.body.stmts,
+        _ => panic!("expected main"),
+    };
+    let values: Vec<String> = body.iter().map(|stmt| match stmt.kind {
+        StmtKind::ShortVarDecl(_, ref values) => values[0].value.as_ref().unwrap().to_string(),
+        _ => panic!("expected a declaration"),
+    }).collect();
+    assert_eq!(vec!["4", "3", "3.5", "-1", "\"xyz\"", "true", "-8", "0.3", "5"],
+               values);
+}
+
+#[test]
+fn test_symbol_types() {
+    let (prog, table, types) = check("package main
+type t struct { a int; b []t }
+func f(x t, n int) float64 { return 1 }
+");
+    let types = types.unwrap();
+    let sym = |i: usize| match prog.decls[i] {
+        TopLevelDecl::Type(ref specs) => specs[0].name.sym.unwrap(),
+        TopLevelDecl::Func(ref f) => f.name.sym.unwrap(),
+        _ => panic!("expected a declaration"),
+    };
+    let t = table.symbol(sym(0)).ty.unwrap();
+    assert_eq!("t", types.name(t));
+    assert_eq!("struct { a int; b []t }", types.name(types.underlying(t)));
+    assert_eq!("func(t, int) float64", types.name(table.symbol(sym(1)).ty.unwrap()));
+}
+
+#[test]
+fn test_error_messages() {
+    let (_, _, types) = check("package main
+var x int = \"s\"
+func f(n int) {
+    var y float64
+    y = x % y
+    f(y)
+    n.z = 1
+    if n { }
+    var r rune = 3000000000
+    x = 9223372036854775808
+    y = 1 / 0
+    x = 5.5
+}
+");
+    let msgs: Vec<String> = types.unwrap_err().iter().map(|e| e.to_string()).collect();
+    assert_eq!(vec![
+        "t.go:2:13: cannot use \"s\" (type untyped string) as type int in variable declaration",
+        "t.go:5:11: invalid operation: x % y (mismatched types int and float64)",
+        "t.go:6:7: cannot use y (type float64) as type int in argument to f",
+        "t.go:7:7: n.z undefined (type int has no field z)",
+        "t.go:8:8: non-boolean condition in if statement",
+        "t.go:9:18: constant 3000000000 overflows rune",
+        "t.go:10:9: constant 9223372036854775808 overflows int",
+        "t.go:11:11: invalid operation: 1 / 0 (division by constant zero)",
+        "t.go:12:9: constant 5.5 truncated to integer",
+    ], msgs);
+}
@@ -0,0 +1,122 @@
+extern crate gore;
+
+mod common;
+
+use gore::error::ErrorType as ET;
+use gore::pretty::pretty_program;
+use gore::unused;
+
+/// Checks a program whose function `f` has parameter `p` and body
+/// `body`, and describes each diagnostic by its kind, its line in
+/// `body` and the name it is about.
+fn diagnostics(body: &str, warn_unused: bool) -> Vec<String> {
+    let src = format!("package main\nfunc f(p int) int {{\n{}\n}}\n", body);
+    let (prog, table, _) = common::check("-", &src);
+    unused::check(&prog, &table, warn_unused).iter().map(|d| {
+        let kind = match d.ty {
+            ET::UnusedVariable if d.warning => "unused?",
+            ET::UnusedVariable => "unused",
+            ET::UnusedParameter => "param",
+            ET::IneffectualAssignment => "ineffectual",
+            _ => panic!("{}", d),
+        };
+        let name = d.to_string().rsplit(' ').next().unwrap().to_string();
+        format!("{} {} {}", kind, d.loc.line - 2, name)
+    }).collect()
+}
+
+fn check(body: &str) -> Vec<String> {
+    diagnostics(body, false)
+}
+
+/// Returns the body of `f` once the values that are never read
+/// are removed.
+fn eliminate(body: &str) -> String {
+    let src = format!("package main\nfunc g() int {{\nreturn 1\n}}\nfunc f(p int) int {{\n{}\n}}\n", body);
+    let (mut prog, table, types) = common::check("-", &src);
+    unused::eliminate(&mut prog, &table, &types);
+    let text = pretty_program(&prog);
+    let start = text.find("func f(p int) int {\n").unwrap() + "func f(p int) int {\n".len();
+    text[start ..].trim_end().trim_end_matches('}').lines()
+        .map(|line| line.trim()).collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn test_unused_variables() {
+    assert_eq!(vec!["unused 1 x", "unused 2 y"], check("x := 1\nvar y int\nreturn p"));
+    assert_eq!(vec!["unused? 1 x"], diagnostics("x := 1\nreturn p", true));
+    // Assigning to a variable does not use it, updating it does.
+    assert_eq!(vec!["unused 1 x"], check("x := 1\nx = 2\nreturn p"));
+    assert_eq!(Vec::<String>::new(), check("x := 1\nx++\nreturn p"));
+    assert_eq!(Vec::<String>::new(), check("x := 1\nx += 2\nreturn p"));
+    assert_eq!(Vec::<String>::new(), check("var a [2]int\na[0] = 1\nreturn p"));
+    // The blank identifier is never unused, and uses what it is
+    // assigned.
+    assert_eq!(Vec::<String>::new(), check("x, _ := 1, 2\n_ = x\nvar _ int\nreturn p"));
+    // Redeclaring a variable with `:=` does not declare it again.
+    assert_eq!(vec!["unused 1 x"], check("x, y := 1, 2\nx, z := 3, y\nreturn z + p"));
+    assert_eq!(vec!["unused 2 x"], check("if x := p; x > 0 {\nx := 1\n}\nreturn p"));
+}
+
+#[test]
+fn test_unused_parameters() {
+    assert_eq!(vec!["param 0 p"], check("return 0"));
+    assert_eq!(vec!["param 0 p"], check("p = 1\nreturn 0"));
+}
+
+#[test]
+fn test_ineffectual_assignments() {
+    assert_eq!(vec!["ineffectual 1 x"], check("x := 1\nx = 2\nreturn x + p"));
+    assert_eq!(vec!["ineffectual 1 x"], check("var x = p\nx = 2\nreturn x"));
+    assert_eq!(vec!["ineffectual 3 x"], check("x := p\nprintln(x)\nx = 2\nreturn 0"));
+    // The zero value of a variable is not assigned.
+    assert_eq!(Vec::<String>::new(), check("var x int\nx = p\nreturn x"));
+    // The value may be used on one of the paths.
+    assert_eq!(Vec::<String>::new(), check("x := 1\nif p > 0 {\nx = 2\n}\nreturn x"));
+    assert_eq!(vec!["ineffectual 1 x"], check("x := 1\nif p > 0 {\nx = 2\n} else {\nx = 3\n}\nreturn x"));
+    assert_eq!(Vec::<String>::new(), check("x := 1\nswitch p {\ncase 1:\nx = 2\n}\nreturn x"));
+    assert_eq!(vec!["ineffectual 1 x"], check("x := 1\nswitch p {\ncase 1:\nx = 2\ndefault:\nx = 3\n}\nreturn x"));
+    // The value is used by the next iterations of the loop.
+    assert_eq!(Vec::<String>::new(), check("k := 0\nfor i := 0; i < p; i++ {\nk = k + i\n}\nreturn k"));
+    assert_eq!(Vec::<String>::new(), check("k := 0\nfor {\nif k > p {\nbreak\n}\nk++\n}\nreturn 0"));
+    assert_eq!(Vec::<String>::new(), check("k := 0\nfor i := 0; i < p; i++ {\nif i == 1 {\ncontinue\n}\nk = i\n}\nreturn k"));
+    assert_eq!(vec!["ineffectual 1 x", "ineffectual 3 x"], check("x := 0\nfor i := 0; i < p; i++ {\nx = i\nx = 2 * i\nprintln(x)\n}\nreturn 0"));
+    assert_eq!(vec!["ineffectual 4 x"], check("x := 0\nfor {\nprintln(x)\nx = 1\nbreak\n}\nreturn p"));
+}
+
+#[test]
+fn test_eliminate() {
+    // The dead values are removed, and dead declarations declare
+    // their variables with their zero values.
+    assert_eq!("var x x = 3 return x", eliminate("x := p * 2\nx = 3\nreturn x"));
+    assert_eq!("var x, y x, y = 1, 2 return x + y", eliminate("var x, y = p, p / 2\nx, y = 1, 2\nreturn x + y"));
+    assert_eq!("var x y := 1 return y", eliminate("x := p\ny := 1\n_, x = x + 1, x\nreturn y"));
+    assert_eq!("x := 1 return x", eliminate("x := 1\nvar _ = x\n_ = float64(x) / 3.0\nreturn x"));
+    // Removing an assignment can make the values that it reads
+    // dead.
+    assert_eq!("var x var y y = 2 return y", eliminate("x := p + 1\ny := x\ny = 2\nreturn y"));
+    // Only what is dead is removed from an assignment.
+    assert_eq!("x, y := p, p x = x y = 3 return x + y", eliminate("x, y := p, p\nx, y = x, 3\ny = 3\nreturn x + y"));
+    assert_eq!("var x, y y = 3 return y", eliminate("x, y := p, p\nx, y = y + x, 3\nreturn y"));
+    // The same in nested statements and loops.
+    assert_eq!("for i := 0; i < p; i++ { var x x = i p += x } return p",
+               eliminate("for i := 0; i < p; i++ {\nx := i * i\nx = i\np += x\n}\nreturn p"));
+    assert_eq!("x := 0 for i := 0; i < p; i++ { x = x + i } return x",
+               eliminate("x := 0\nfor i := 0; i < p; i++ {\nx = x + i\n}\nreturn x"));
+}
+
+#[test]
+fn test_eliminate_effects() {
+    // Calls, appends and the operations that may panic are kept,
+    // even when their value is never read.
+    assert_eq!("x := g() x = 1 return x", eliminate("x := g()\nx = 1\nreturn x"));
+    assert_eq!("x := 1 / p x = 1 return x", eliminate("x := 1 / p\nx = 1\nreturn x"));
+    assert_eq!("x := 1 << p x = 1 return x", eliminate("x := 1 << p\nx = 1\nreturn x"));
+    assert_eq!("var a [2]int _ = a[p] var s []int _ = append(s, 1) return p",
+               eliminate("var a [2]int\n_ = a[p]\nvar s []int\n_ = append(s, 1)\nreturn p"));
+    // Divisions and shifts by constants that make them safe are
+    // not.
+    assert_eq!("var x x = 1 return x", eliminate("x := p / 2 + p % 3 + p << 1\nx = 1\nreturn x"));
+    // The initial statements of loops are kept.
+    assert_eq!("for i := p; ; { i = 2 return i }", eliminate("for i := p; ; {\ni = 2\nreturn i\n}"));
+}
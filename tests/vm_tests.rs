@@ -0,0 +1,83 @@
+extern crate gore;
+
+mod common;
+
+use gore::bcfile;
+use gore::bcgen;
+use gore::bytecode::{self, Module};
+use gore::vm;
+
+fn compile(filename: &str, src: &str) -> Module {
+    let (prog, table, types) = common::check(filename, src);
+    let module = bcgen::compile(&prog, &types, &table);
+    if let Err(err) = bcfile::verify(&module) {
+        panic!("{}: {}", src, err);
+    }
+    module
+}
+
+/// Compiles and runs `src`, read from `filename`; returns what it
+/// prints, its runtime error and its exit status, as `gore run
+/// --vm` would.
+fn run(filename: &str, src: &str) -> (String, String, i32) {
+    let mut out = Vec::new();
+    let result = vm::run(&compile(filename, src), &mut out);
+    common::samples::outcome(out, result)
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| Some(run(&sample.filename, &sample.src)));
+}
+
+#[test]
+fn test_stack_overflow() {
+    let (_, err, _) = run("t.go", "package main
+func f(n int) int {
+    return f(n + 1)
+}
+func main() {
+    f(0)
+}
+");
+    assert_eq!("panic: runtime error: stack overflow\n\tat t.go:3:12\n", err);
+}
+
+#[test]
+fn test_disassemble() {
+    let module = compile("t.go", "package main
+var n = 2
+func double(x int) int {
+    return x * n
+}
+func main() {
+    println(double(21))
+}
+");
+    assert_eq!("\
+; t.go
+; 1 globals, entry <init>
+
+constants:
+     0  2
+     1  21
+
+func 0 <init> (params 0, locals 0):
+  0000     2  const 0              ; 2
+  0001     |  store_global 0
+  0002     1  call 2               ; main
+  0003     |  return
+
+func 1 double (params 1, locals 1):
+  0000     4  load_local 0
+  0001     |  load_global 0
+  0002     |  mul_int
+  0003     |  return_value
+
+func 2 main (params 0, locals 0):
+  0000     7  const 1              ; 21
+  0001     |  call 1               ; double
+  0002     |  println 1
+  0003     6  return
+", bytecode::disassemble(&module));
+}
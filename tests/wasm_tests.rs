@@ -0,0 +1,83 @@
+extern crate gore;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use gore::wasm;
+
+fn generate(filename: &str, src: &str) -> String {
+    let (prog, table, types) = common::check(filename, src);
+    wasm::generate(&prog, &types, &table)
+}
+
+fn runtime_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/runtime")
+}
+
+/// Compiles `src`, read from `filename`, to WebAssembly text,
+/// assembles it with `wat2wasm` and runs it under Node.js with
+/// `gore_host.js`; returns its standard output, standard error
+/// and exit status, or `None` if either tool is missing.
+fn run(filename: &str, src: &str) -> Option<(String, String, i32)> {
+    let wat = generate(filename, src);
+    let name = filename.trim_end_matches(".go");
+    let base = env::temp_dir().join(format!("gore_wasm_{}_{}", name, process::id()));
+    let (wat_file, wasm_file) = (base.with_extension("wat"), base.with_extension("wasm"));
+    fs::write(&wat_file, wat).unwrap();
+    let status = Command::new("wat2wasm").arg(&wat_file).arg("-o").arg(&wasm_file).status();
+    let _ = fs::remove_file(&wat_file);
+    match status {
+        Ok(status) => assert!(status.success(), "{}: the module does not assemble", name),
+        Err(_) => {
+            eprintln!("{}: no wat2wasm, skipped", name);
+            return None;
+        }
+    }
+    let output = Command::new("node").arg(runtime_dir().join("gore_host.js")).arg(&wasm_file).output();
+    let _ = fs::remove_file(&wasm_file);
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => {
+            eprintln!("{}: no node, skipped", name);
+            return None;
+        }
+    };
+    Some((String::from_utf8_lossy(&output.stdout).into_owned(),
+          String::from_utf8_lossy(&output.stderr).into_owned(),
+          output.status.code().unwrap_or(-1)))
+}
+
+/// Compares the module generated from every program of
+/// `tests/golden/wasm` with the `.wat` file next to it; setting
+/// `GORE_BLESS` rewrites the `.wat` files instead.
+#[test]
+fn test_golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/wasm");
+    let mut sources: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "go"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+    for source in sources {
+        let name = source.file_name().unwrap().to_string_lossy().into_owned();
+        let wat = generate(&name, &fs::read_to_string(&source).unwrap());
+        let golden = source.with_extension("wat");
+        if env::var_os("GORE_BLESS").is_some() {
+            fs::write(&golden, wat).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        assert!(expected == wat, "{} differs from {}; run with GORE_BLESS=1 to update it",
+                name, golden.display());
+    }
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| run(&sample.filename, &sample.src));
+}
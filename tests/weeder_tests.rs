@@ -0,0 +1,195 @@
+extern crate gore;
+
+use gore::error::ErrorType as ET;
+use gore::parser;
+use gore::scanner::Scanner;
+use gore::weeder::weed;
+
+/// Weeds the body of a `main` function and returns
+/// the types and lines of the errors.
+fn weed_body(body: &str) -> Vec<(ET, usize)> {
+    let src = format!("package main\nfunc main() {{\n{}\n}}\n", body);
+    let scanner = Scanner::new("-".to_string(), src.into_bytes());
+    let prog = match parser::parse(scanner) {
+        Ok(prog) => prog,
+        Err(errs) => panic!("{}: {}", body, errs[0]),
+    };
+    match weed(&prog) {
+        Ok(()) => Vec::new(),
+        Err(errs) => errs.iter().map(|e| (e.ty, e.loc.line - 2)).collect(),
+    }
+}
+
+fn assert_ok(body: &str) {
+    assert_eq!(Vec::<(ET, usize)>::new(), weed_body(body), "{}", body);
+}
+
+fn assert_err(expected_err: ET, body: &str) {
+    let errs = weed_body(body);
+    assert_eq!(1, errs.len(), "{}", body);
+    assert_eq!(expected_err, errs[0].0, "{}", body);
+}
+
+#[test]
+fn test_break_continue() {
+    assert_ok("for { break }");
+    assert_ok("for { continue }");
+    assert_ok("for { if x { break } else { continue } }");
+    assert_ok("switch { default: break }");
+    assert_ok("for { switch { case x: continue } }");
+    assert_ok("for { { { break } } }");
+
+    assert_err(ET::BreakOutsideLoop, "break");
+    assert_err(ET::BreakOutsideLoop, "if x { break }");
+    assert_err(ET::ContinueOutsideLoop, "continue");
+    assert_err(ET::ContinueOutsideLoop, "{ continue }");
+    assert_err(ET::ContinueInSwitch, "switch { case true: continue }");
+    assert_err(ET::ContinueInSwitch, "switch { default: for {}; continue }");
+    assert_err(ET::BreakOutsideLoop, "for {}; break");
+}
+
+#[test]
+fn test_multiple_defaults() {
+    assert_ok("switch x { case 1: default: case 2: }");
+    assert_err(ET::MultipleDefaults, "switch x { default: case 1: default: }");
+    assert_eq!(vec![(ET::MultipleDefaults, 3), (ET::MultipleDefaults, 4)],
+               weed_body("switch {\ndefault:\ndefault:\ndefault:\n}"));
+}
+
+#[test]
+fn test_blank() {
+    assert_ok("_ = f()");
+    assert_ok("_, x = 1, 2");
+    assert_ok("_, x := 1, 2");
+    assert_ok("var _ int = 3");
+
+    assert_err(ET::BlankAsValue, "x = _");
+    assert_err(ET::BlankAsValue, "x := _ + 1");
+    assert_err(ET::BlankAsValue, "println(_)");
+    assert_err(ET::BlankAsValue, "f(_)");
+    assert_err(ET::BlankAsValue, "a[_] = 1");
+    assert_err(ET::BlankAsValue, "_ += 1");
+    assert_err(ET::BlankAsValue, "_++");
+    assert_err(ET::BlankAsValue, "if _ { }");
+    assert_err(ET::BlankAsValue, "for _ { }");
+    assert_err(ET::BlankAsValue, "switch _ { }");
+    assert_err(ET::BlankAsValue, "return _");
+    assert_err(ET::BlankAsValue, "var x [_]int");
+}
+
+#[test]
+fn test_addressable() {
+    assert_ok("x = 1");
+    assert_ok("a[0] = 1");
+    assert_ok("a.b[0].c = 1");
+    assert_ok("f()[0] = 1");
+    assert_ok("x += 1; a[0]++; p.x--");
+
+    assert_err(ET::NonAddressableAssignment, "1 = x");
+    assert_err(ET::NonAddressableAssignment, "f() = x");
+    assert_err(ET::NonAddressableAssignment, "f().x = 1");
+    assert_err(ET::NonAddressableAssignment, "x + y = 1");
+    assert_err(ET::NonAddressableAssignment, "-x += 1");
+    assert_err(ET::NonAddressableAssignment, "\"s\"++");
+    assert_err(ET::NonAddressableAssignment, "append(s, 1)--");
+}
+
+#[test]
+fn test_assignment_counts() {
+    assert_ok("a, b = b, a");
+    assert_ok("a, b := 1, 2");
+    assert_ok("var a, b int");
+    assert_ok("var a, b = 1, 2");
+
+    assert_err(ET::AssignmentMismatch, "a, b = x");
+    assert_err(ET::AssignmentMismatch, "a = x, y");
+    assert_err(ET::AssignmentMismatch, "a, b := 1");
+    assert_err(ET::AssignmentMismatch, "var a, b = 1");
+    assert_err(ET::AssignmentMismatch, "var a int = 1, 2");
+}
+
+/// Weeds a function with a result whose body is `body`, and
+/// returns the errors.
+fn weed_func(body: &str) -> Vec<ET> {
+    let src = format!("package main\nfunc f() int {{\n{}\n}}\n", body);
+    let prog = parser::parse(Scanner::new("-".to_string(), src.into_bytes())).unwrap();
+    match weed(&prog) {
+        Ok(()) => Vec::new(),
+        Err(errs) => errs.iter().map(|e| e.ty).collect(),
+    }
+}
+
+#[test]
+fn test_terminating() {
+    let terminating = [
+        "return 1",
+        "println(); return 2",
+        "{ { return 1 } }",
+        "if x { return 1 } else { return 2 }",
+        "if x { return 1 } else if y { return 2 } else { return 3 }",
+        "for {}",
+        "for { if x { return 1 } }",
+        "for { for { break } }",
+        "for { switch { case x: break } }",
+        "switch { default: return 1 }",
+        "switch x { case 1: return 1; default: for {} }",
+    ];
+    for body in &terminating {
+        assert_eq!(Vec::<ET>::new(), weed_func(body), "{}", body);
+    }
+    let missing = [
+        "",
+        "println()",
+        "return 1; println()",
+        "if x { return 1 }",
+        "if x { return 1 } else { }",
+        "if x { return 1 } else if y { return 2 }",
+        "for x { return 1 }",
+        "for ;; { break }",
+        "for { if x { break } }",
+        "for { { break } }",
+        "switch { case x: return 1 }",
+        "switch { case x: return 1; default: }",
+        "switch { default: break; return 1 }",
+        "switch { default: if x { break }; return 1 }",
+    ];
+    for body in &missing {
+        assert_eq!(vec![ET::MissingReturn], weed_func(body), "{}", body);
+    }
+    // `main` has no result, so it needs no terminating statement.
+    assert_ok("println()");
+}
+
+#[test]
+fn test_error_messages() {
+    let src = b"package main
+var a, b = 1
+func main() {
+    f().x = 1
+}
+".to_vec();
+    let prog = parser::parse(Scanner::new("t.go".to_string(), src)).unwrap();
+    let msgs: Vec<String> = weed(&prog).unwrap_err().iter().map(|e| e.to_string()).collect();
+    assert_eq!(vec!["t.go:2:5: assignment mismatch: 2 variables but 1 value",
+                    "t.go:4:5: cannot assign to f().x"], msgs);
+
+    let src = b"package main
+func f(x int) int {
+    if x > 0 {
+        return 1
+    }
+}
+".to_vec();
+    let prog = parser::parse(Scanner::new("t.go".to_string(), src)).unwrap();
+    let msgs: Vec<String> = weed(&prog).unwrap_err().iter().map(|e| e.to_string()).collect();
+    assert_eq!(vec!["t.go:6:1: missing return at end of function"], msgs);
+}
+
+#[test]
+fn test_all_errors_reported() {
+    assert_eq!(vec![(ET::BreakOutsideLoop, 1),
+                    (ET::BlankAsValue, 2),
+                    (ET::AssignmentMismatch, 3),
+                    (ET::NonAddressableAssignment, 3)],
+               weed_body("break\nx = _\n1, b = 2"));
+}
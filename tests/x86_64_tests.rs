@@ -0,0 +1,77 @@
+extern crate gore;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+use gore::x86_64;
+
+/// Compiles `src`, read from `filename`, to assembly, assembles
+/// and links it with the runtime and runs it; returns its
+/// standard output, standard error and exit status, or `None` if
+/// the host is not x86-64 Linux or there is no C compiler.
+fn run(filename: &str, src: &str) -> Option<(String, String, i32)> {
+    let (prog, table, types) = common::check(filename, src);
+    let asm = x86_64::generate(&prog, &types, &table);
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        eprintln!("{}: not an x86-64 Linux host, skipped", filename);
+        return None;
+    }
+
+    let name = filename.trim_end_matches(".go");
+    let base = env::temp_dir().join(format!("gore_x86_64_{}_{}", name, process::id()));
+    let asm_file = base.with_extension("s");
+    fs::write(&asm_file, asm).unwrap();
+    let runtime = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/runtime/runtime.c");
+    let status = Command::new("cc")
+        .args(["-std=c99", "-Wall", "-Werror", "-o"])
+        .arg(&base)
+        .arg(&asm_file)
+        .arg(&runtime)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "{}: the program does not assemble", filename),
+        Err(_) => {
+            eprintln!("{}: no C compiler, skipped", filename);
+            return None;
+        }
+    }
+    let output = Command::new(&base).output().unwrap();
+    let _ = fs::remove_file(&asm_file);
+    let _ = fs::remove_file(&base);
+    Some((String::from_utf8_lossy(&output.stdout).into_owned(),
+          String::from_utf8_lossy(&output.stderr).into_owned(),
+          output.status.code().unwrap_or(-1)))
+}
+
+#[test]
+fn test_programs() {
+    common::samples::check_samples(|sample| run(&sample.filename, &sample.src));
+}
+
+#[test]
+fn test_temporaries_in_registers() {
+    let src = "package main\n\
+               func f(n int) int {\n\
+               \ts := 0\n\
+               \tfor i := 0; i < n; i++ {\n\
+               \t\ts += i * 2 + 1\n\
+               \t}\n\
+               \treturn s\n\
+               }\n\
+               func main() {\n\
+               \tprintln(f(10))\n\
+               }\n";
+    let (prog, table, types) = common::check("temporaries.go", src);
+    let asm = x86_64::generate(&prog, &types, &table);
+    let body = asm.split("main.f:\n").nth(1).unwrap();
+    let body = body.split(".size main.f").next().unwrap();
+    for line in body.lines() {
+        let line = line.trim();
+        assert!(line == "pushq %rbp" || !(line.starts_with("pushq") || line.starts_with("popq")),
+                "{} in\n{}", line, body);
+    }
+}